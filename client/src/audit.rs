@@ -0,0 +1,124 @@
+// 冲突自动解决审计日志
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// 默认显示的审计记录数量
+pub const DEFAULT_AUDIT_LIMIT: usize = 20;
+
+/// 一次自动解决冲突的记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// 文件路径
+    pub path: PathBuf,
+    /// 采用的解决策略（如 auto_merge、keep_local）
+    pub strategy: String,
+    /// 解决时间
+    pub timestamp: DateTime<Utc>,
+    /// 解决前本地内容的哈希
+    pub local_hash: String,
+    /// 解决前远程内容的哈希
+    pub remote_hash: String,
+    /// 解决后内容的哈希
+    pub merged_hash: String,
+}
+
+/// 只追加的审计日志（JSONL，每行一条记录）
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    /// 创建指向日志文件的审计日志（文件在首次写入时创建）
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// 日志文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 追加一条记录
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建审计日志目录: {:?}", parent))?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("无法打开审计日志: {:?}", self.path))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("无法写入审计日志: {:?}", self.path))?;
+
+        Ok(())
+    }
+
+    /// 读取最近的 limit 条记录（按时间先后排列，无法解析的行会被跳过）
+    pub fn recent(&self, limit: usize) -> Result<Vec<AuditEntry>> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("无法读取审计日志: {:?}", self.path)),
+        };
+
+        let mut entries: Vec<AuditEntry> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        entries.drain(..skip);
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str) -> AuditEntry {
+        AuditEntry {
+            path: PathBuf::from(path),
+            strategy: "auto_merge".to_string(),
+            timestamp: Utc::now(),
+            local_hash: "a".to_string(),
+            remote_hash: "b".to_string(),
+            merged_hash: "c".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recent_returns_latest_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(temp_dir.path().join("audit").join("audit.jsonl"));
+        assert!(log.recent(10).unwrap().is_empty());
+
+        for i in 0..5 {
+            log.append(&entry(&format!("{}.json", i))).unwrap();
+        }
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(log.path())
+            .unwrap()
+            .write_all(b"not json\n")
+            .unwrap();
+
+        let recent = log.recent(2).unwrap();
+        assert_eq!(
+            recent.iter().map(|e| e.path.clone()).collect::<Vec<_>>(),
+            vec![PathBuf::from("3.json"), PathBuf::from("4.json")]
+        );
+    }
+}
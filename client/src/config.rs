@@ -1,11 +1,30 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tracing::{debug, info};
 
+use crate::content_hash::HashAlgorithm;
+use crate::paths::SyncRoots;
+
+/// 表示使用顶层服务器和认证配置的配置档名
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// 命令行 --profile 选择的配置档（优先于配置文件中的 active_profile）
+static SELECTED_PROFILE: OnceLock<String> = OnceLock::new();
+
+/// 选择本次运行使用的配置档（只在启动时调用一次）
+pub fn select_profile(name: String) {
+    let _ = SELECTED_PROFILE.set(name);
+}
+
 /// 客户端配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
+    /// 当前使用的配置档（未设置时使用顶层服务器和认证配置）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
     /// 服务器配置
     pub server: ServerConfig,
     /// 认证配置
@@ -18,6 +37,35 @@ pub struct ClientConfig {
     pub performance: PerformanceConfig,
     /// 日志配置
     pub logging: LoggingConfig,
+    /// 命名的服务器配置档（如本地测试服务器和生产服务器）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// 应用配置档前的顶层配置（保存时写回，不序列化）
+    #[serde(skip)]
+    base: Option<ProfileBase>,
+}
+
+/// 服务器配置档
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    /// 服务器配置
+    pub server: ServerConfig,
+
+    /// Token 存储路径（未设置时为 auth.token_dir 下的 profiles/<配置档名>）
+    #[serde(default)]
+    pub token_dir: Option<PathBuf>,
+
+    /// Token 加密密钥（未设置时沿用 auth.encryption_key）
+    #[serde(default)]
+    pub encryption_key: Option<String>,
+}
+
+/// 应用配置档前的顶层服务器和认证配置
+#[derive(Debug, Clone)]
+struct ProfileBase {
+    name: String,
+    server: ServerConfig,
+    auth: AuthConfig,
 }
 
 /// 服务器配置
@@ -35,16 +83,62 @@ pub struct ServerConfig {
     #[serde(default = "default_connection_timeout")]
     pub connection_timeout: u64,
 
-    /// 请求超时（秒）
+    /// 请求超时（秒，未单独配置超时的操作使用此值）
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
 
+    /// 普通请求（元数据查询、变更上报等）超时（秒，未设置时使用 request_timeout）
+    #[serde(default)]
+    pub rpc_timeout: Option<u64>,
+
+    /// 文件上传超时（秒，未设置时使用 request_timeout）
+    #[serde(default)]
+    pub upload_timeout: Option<u64>,
+
+    /// 文件下载超时（秒，未设置时使用 request_timeout）
+    #[serde(default)]
+    pub download_timeout: Option<u64>,
+
+    /// HTTP/2 保活 PING 间隔（秒，0 表示不发送；需小于 NAT/负载均衡器的空闲回收时间）
+    #[serde(default = "default_keepalive_interval")]
+    pub keepalive_interval: u64,
+
+    /// 等待保活 PING 响应的超时（秒，超时后断开连接并重连）
+    #[serde(default = "default_keepalive_timeout")]
+    pub keepalive_timeout: u64,
+
     /// 启用 TLS
     #[serde(default = "default_tls_enabled")]
     pub tls_enabled: bool,
 
     /// TLS 证书路径（可选）
     pub tls_cert_path: Option<String>,
+
+    /// mTLS 客户端证书路径（可选）
+    #[serde(default)]
+    pub tls_client_cert_path: Option<String>,
+
+    /// mTLS 客户端私钥路径（可选）
+    #[serde(default)]
+    pub tls_client_key_path: Option<String>,
+}
+
+impl ServerConfig {
+    /// 验证 TLS 配置
+    pub fn validate_tls(&self) -> Result<()> {
+        if self.tls_enabled && self.address.starts_with("http://") {
+            anyhow::bail!(
+                "已启用 TLS，但服务器地址使用明文 http://: {}（请改为 https://）",
+                self.address
+            );
+        }
+
+        if self.tls_client_cert_path.is_some() != self.tls_client_key_path.is_some() {
+            anyhow::bail!("客户端证书和私钥必须同时配置");
+        }
+
+        Ok(())
+    }
 }
 
 /// 认证配置
@@ -57,6 +151,10 @@ pub struct AuthConfig {
     /// Token 加密密钥（可选，未设置则不加密）
     pub encryption_key: Option<String>,
 
+    /// Token 存储后端：file（Token 文件）或 keyring（系统密钥环）
+    #[serde(default)]
+    pub token_store: TokenStoreKind,
+
     /// 自动刷新 Token
     #[serde(default = "default_auto_refresh")]
     pub auto_refresh: bool,
@@ -66,6 +164,17 @@ pub struct AuthConfig {
     pub refresh_before: u64,
 }
 
+/// Token 存储后端
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStoreKind {
+    /// 保存到 token 目录下的文件（可用 encryption_key 加密）
+    #[default]
+    File,
+    /// 保存到系统密钥环（macOS 钥匙串、Windows 凭据管理器、Linux Secret Service）
+    Keyring,
+}
+
 /// 同步配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncConfig {
@@ -85,6 +194,10 @@ pub struct SyncConfig {
     #[serde(default = "default_exclude_dirs")]
     pub exclude_dirs: Vec<String>,
 
+    /// 任意层级按名称排除的目录（如 node_modules）
+    #[serde(default)]
+    pub exclude_dir_names: Vec<String>,
+
     /// 排除文件模式
     #[serde(default = "default_exclude_patterns")]
     pub exclude_patterns: Vec<String>,
@@ -96,6 +209,119 @@ pub struct SyncConfig {
     /// 同步规则（本地配置，优先级低于服务器规则）
     #[serde(default)]
     pub rules: Vec<crate::rules::SyncRule>,
+
+    /// 同步状态快照文件
+    #[serde(default = "default_state_file")]
+    pub state_file: PathBuf,
+
+    /// 离线队列文件（服务器不可达期间的本地修改，网络恢复后重放）
+    #[serde(default = "default_offline_queue_file")]
+    pub offline_queue_file: PathBuf,
+
+    /// 守护进程启动时等待目录稳定的静默期（毫秒，0 表示不等待）
+    #[serde(default = "default_settle_quiet_period")]
+    pub settle_quiet_period: u64,
+
+    /// 等待目录稳定的最长时间（毫秒）
+    #[serde(default = "default_settle_max_wait")]
+    pub settle_max_wait: u64,
+
+    /// 上传前确认文件大小和修改时间不再变化的时间窗口（毫秒，0 表示不检查）
+    #[serde(default = "default_write_settle_window")]
+    pub write_settle_window: u64,
+
+    /// 等待文件写入完成的最长时间（毫秒，超时后跳过本次事件）
+    #[serde(default = "default_write_settle_max_wait")]
+    pub write_settle_max_wait: u64,
+
+    /// 同步文件的最大大小（字节，未设置表示不限制）
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+
+    /// 同步文件的最小大小（字节，未设置表示不限制）
+    #[serde(default)]
+    pub min_file_size: Option<u64>,
+
+    /// 一次全量同步允许上传的总字节数（预计超出时不上传任何文件，未设置表示不限制）
+    #[serde(default)]
+    pub max_total_upload: Option<u64>,
+
+    /// 是否跟随符号链接（跟随时会跳过循环链接；不跟随时符号链接不会被同步）
+    #[serde(default)]
+    pub follow_symlinks: bool,
+
+    /// 守护进程控制端口（pause/resume 命令使用，为空则不启动）
+    #[serde(default = "default_control_address")]
+    pub control_address: String,
+
+    /// 守护进程心跳间隔（秒，0 表示不发送；需小于服务器 30 秒的在线超时）
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+
+    /// 守护进程重新获取服务器同步规则的间隔（秒，0 表示只在订阅变更通知时获取）
+    #[serde(default = "default_rules_refresh_interval")]
+    pub rules_refresh_interval: u64,
+
+    /// 电池供电时自动暂停同步（需启用 power-management 特性，平台不支持时不生效）
+    #[serde(default)]
+    pub pause_on_battery: bool,
+
+    /// 按流量计费的网络上自动暂停同步（需启用 power-management 特性，平台不支持时不生效）
+    #[serde(default)]
+    pub pause_on_metered: bool,
+
+    /// 电源与网络状态检查间隔（秒）
+    #[serde(default = "default_power_check_interval")]
+    pub power_check_interval: u64,
+
+    /// 路径是否大小写不敏感（未设置时按平台判断：macOS/Windows 不敏感）
+    #[serde(default)]
+    pub case_insensitive: Option<bool>,
+
+    /// 仅大小写不同的路径冲突处理方式：flag（标记为冲突）或 merge（合并到已有路径）
+    #[serde(default = "default_case_collision")]
+    pub case_collision: String,
+
+    /// 是否同步 Unix 权限位（如 hook 脚本的可执行位，Windows 上不生效）
+    #[serde(default = "default_preserve_mode")]
+    pub preserve_mode: bool,
+
+    /// 安全删除：本地内容与上次同步的版本不一致时不按远程删除移除本地文件，改为记为冲突
+    #[serde(default = "default_safe_delete")]
+    pub safe_delete: bool,
+
+    /// 同步方向：push（只上传）、pull（只下载）或 both（双向）
+    #[serde(default)]
+    pub direction: SyncDirection,
+
+    /// 除 Claude 目录外额外监控和同步的目录（服务器上以 `@目录名/` 区分）
+    #[serde(default)]
+    pub additional_watch_dirs: Vec<PathBuf>,
+}
+
+/// 同步方向
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncDirection {
+    /// 只上传本地修改，不应用远程变更
+    Push,
+    /// 只应用远程变更，忽略本地修改（跟随设备）
+    Pull,
+    /// 双向同步
+    #[default]
+    Both,
+}
+
+impl SyncDirection {
+    /// 是否上传本地修改
+    pub fn allows_push(self) -> bool {
+        matches!(self, Self::Push | Self::Both)
+    }
+
+    /// 是否应用远程变更
+    pub fn allows_pull(self) -> bool {
+        matches!(self, Self::Pull | Self::Both)
+    }
 }
 
 /// 冲突解决配置
@@ -105,6 +331,10 @@ pub struct ConflictConfig {
     #[serde(default = "default_conflict_strategy")]
     pub default_strategy: String,
 
+    /// 按文件类型覆盖的解决策略（键为 detect_file_type 返回的类型，如 json、text、log）
+    #[serde(default)]
+    pub per_type_strategy: HashMap<String, String>,
+
     /// 自动合并文本文件
     #[serde(default = "default_auto_merge_text")]
     pub auto_merge_text: bool,
@@ -120,6 +350,52 @@ pub struct ConflictConfig {
     /// 保留冲突副本
     #[serde(default = "default_keep_conflict_copy")]
     pub keep_conflict_copy: bool,
+
+    /// JSON/YAML 对象数组按标识字段合并（关闭时直接使用远程数组）
+    #[serde(default)]
+    pub array_merge_by_key: bool,
+
+    /// 数组元素的标识字段（按顺序尝试）
+    #[serde(default = "default_array_merge_keys")]
+    pub array_merge_keys: Vec<String>,
+
+    /// 自动解决冲突的审计日志（JSONL）
+    #[serde(default = "default_audit_log")]
+    pub audit_log: PathBuf,
+
+    /// 冲突标记风格：git2、diff3（包含基线段）或 custom（使用 conflict_marker_labels）
+    #[serde(default = "default_conflict_marker_style")]
+    pub conflict_marker_style: String,
+
+    /// custom 风格的冲突标记标签
+    #[serde(default)]
+    pub conflict_marker_labels: ConflictMarkerLabels,
+}
+
+/// 自定义冲突标记标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConflictMarkerLabels {
+    /// 本地段标签
+    #[serde(default = "default_local_marker_label")]
+    pub local: String,
+
+    /// 基线段标签（不设置时不输出基线段）
+    #[serde(default)]
+    pub base: Option<String>,
+
+    /// 远程段标签
+    #[serde(default = "default_remote_marker_label")]
+    pub remote: String,
+}
+
+impl Default for ConflictMarkerLabels {
+    fn default() -> Self {
+        Self {
+            local: default_local_marker_label(),
+            base: None,
+            remote: default_remote_marker_label(),
+        }
+    }
 }
 
 /// 性能配置
@@ -129,6 +405,10 @@ pub struct PerformanceConfig {
     #[serde(default = "default_debounce_delay")]
     pub debounce_delay: u64,
 
+    /// 文件监控同时防抖的文件数上限（超出的文件事件合并排队）
+    #[serde(default = "default_max_pending_files")]
+    pub max_pending_files: usize,
+
     /// 大文件阈值（字节，默认 10MB）
     #[serde(default = "default_large_file_threshold")]
     pub large_file_threshold: u64,
@@ -152,6 +432,30 @@ pub struct PerformanceConfig {
     /// 重试延迟（秒）
     #[serde(default = "default_retry_delay")]
     pub retry_delay: u64,
+
+    /// 传输分块大小（字节，64KB–64MB）
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: usize,
+
+    /// 性能指标落盘文件
+    #[serde(default = "default_metrics_file")]
+    pub metrics_file: PathBuf,
+
+    /// 守护进程 /metrics 端点监听地址（为空则不启动）
+    #[serde(default = "default_metrics_address")]
+    pub metrics_address: String,
+
+    /// 修改较小时只上传与上次同步内容的差异（超过 16MB 的文件总是流式完整上传）
+    #[serde(default = "default_delta_upload")]
+    pub delta_upload: bool,
+
+    /// 增量上传基准内容的保存目录
+    #[serde(default = "default_delta_base_dir")]
+    pub delta_base_dir: PathBuf,
+
+    /// 内容哈希算法（sha256/blake3，切换后已记录的哈希仍按各自的算法校验）
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
 }
 
 /// 日志配置
@@ -187,6 +491,14 @@ fn default_request_timeout() -> u64 {
     300
 }
 
+fn default_keepalive_interval() -> u64 {
+    30
+}
+
+fn default_keepalive_timeout() -> u64 {
+    10
+}
+
 fn default_tls_enabled() -> bool {
     false
 }
@@ -245,8 +557,71 @@ fn default_include_types() -> Vec<String> {
     ]
 }
 
+fn default_state_file() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法找到用户主目录")
+        .join(".claude-sync")
+        .join("sync_state.json")
+}
+
+fn default_offline_queue_file() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法找到用户主目录")
+        .join(".claude-sync")
+        .join("offline_queue.ndjson")
+}
+
+fn default_settle_quiet_period() -> u64 {
+    2000 // 2 秒
+}
+
+fn default_settle_max_wait() -> u64 {
+    30000 // 30 秒
+}
+
+fn default_write_settle_window() -> u64 {
+    300 // 300 毫秒
+}
+
+fn default_write_settle_max_wait() -> u64 {
+    10000 // 10 秒
+}
+
+fn default_control_address() -> String {
+    "127.0.0.1:9466".to_string()
+}
+
+fn default_heartbeat_interval() -> u64 {
+    10
+}
+
+fn default_rules_refresh_interval() -> u64 {
+    300
+}
+
+fn default_power_check_interval() -> u64 {
+    60
+}
+
+fn default_case_collision() -> String {
+    "flag".to_string()
+}
+
+fn default_preserve_mode() -> bool {
+    true
+}
+
+fn default_safe_delete() -> bool {
+    true
+}
+
 fn default_conflict_strategy() -> String {
-    "manual".to_string() // manual, keep_local, keep_remote, keep_newer
+    "manual".to_string() // manual, keep_local, keep_remote, keep_newer, keep_both, auto_merge
+}
+
+/// 检查冲突解决策略是否有效
+fn is_valid_conflict_strategy(strategy: &str) -> bool {
+    crate::conflict::ResolutionStrategy::parse(strategy).is_some()
 }
 
 fn default_auto_merge_text() -> bool {
@@ -264,6 +639,29 @@ fn default_conflict_dir() -> PathBuf {
         .join("conflicts")
 }
 
+fn default_audit_log() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法找到用户主目录")
+        .join(".claude-sync")
+        .join("audit.jsonl")
+}
+
+fn default_conflict_marker_style() -> String {
+    "git2".to_string()
+}
+
+fn default_local_marker_label() -> String {
+    "LOCAL".to_string()
+}
+
+fn default_remote_marker_label() -> String {
+    "REMOTE".to_string()
+}
+
+fn default_array_merge_keys() -> Vec<String> {
+    vec!["id".to_string(), "name".to_string()]
+}
+
 fn default_keep_conflict_copy() -> bool {
     true
 }
@@ -272,6 +670,10 @@ fn default_debounce_delay() -> u64 {
     500 // 500 毫秒
 }
 
+fn default_max_pending_files() -> usize {
+    crate::watcher::DEFAULT_MAX_PENDING_FILES
+}
+
 fn default_large_file_threshold() -> u64 {
     10 * 1024 * 1024 // 10MB
 }
@@ -296,6 +698,32 @@ fn default_retry_delay() -> u64 {
     5
 }
 
+fn default_chunk_size() -> usize {
+    crate::transfer::DEFAULT_CHUNK_SIZE
+}
+
+fn default_metrics_file() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法找到用户主目录")
+        .join(".claude-sync")
+        .join("metrics.json")
+}
+
+fn default_metrics_address() -> String {
+    "127.0.0.1:9465".to_string()
+}
+
+fn default_delta_upload() -> bool {
+    true
+}
+
+fn default_delta_base_dir() -> PathBuf {
+    dirs::home_dir()
+        .expect("无法找到用户主目录")
+        .join(".claude-sync")
+        .join("bases")
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -308,20 +736,103 @@ fn default_enabled() -> bool {
     true
 }
 
+/// 合并同步规则：ID 相同的规则以导入的为准并保留原有位置，其余导入规则追加在后面
+fn merge_rules_by_id(
+    existing: &[crate::rules::SyncRule],
+    imported: Vec<crate::rules::SyncRule>,
+) -> Vec<crate::rules::SyncRule> {
+    let mut merged = existing.to_vec();
+    for rule in imported {
+        match merged.iter_mut().find(|existing| existing.id == rule.id) {
+            Some(existing) => *existing = rule,
+            None => merged.push(rule),
+        }
+    }
+    merged
+}
+
 impl ClientConfig {
     /// 加载配置文件
     pub fn load() -> Result<Self> {
         let config_path = Self::config_path()?;
 
-        if !config_path.exists() {
+        let mut config = if config_path.exists() {
+            Self::load_from(&config_path)?
+        } else {
             info!("配置文件不存在，将创建默认配置: {:?}", config_path);
             let default_config = Self::default();
             default_config.save(&config_path)?;
-            return Ok(default_config);
+            default_config
+        };
+
+        // 命令行选择的配置档优先于配置文件中的 active_profile
+        let profile = SELECTED_PROFILE
+            .get()
+            .cloned()
+            .or_else(|| config.active_profile.clone());
+        if let Some(profile) = profile {
+            config.apply_profile(&profile)?;
+        }
+
+        Ok(config)
+    }
+
+    /// 使用指定配置档的服务器和认证配置（default 表示顶层配置）
+    ///
+    /// 每个配置档的 Token 默认保存在单独的目录中，切换配置档不会混用登录状态。
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let base = self.base.take().unwrap_or_else(|| ProfileBase {
+            name: DEFAULT_PROFILE.to_string(),
+            server: self.server.clone(),
+            auth: self.auth.clone(),
+        });
+        self.server = base.server.clone();
+        self.auth = base.auth.clone();
+        if name == DEFAULT_PROFILE && !self.profiles.contains_key(name) {
+            return Ok(());
+        }
+
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::bail!("配置档不存在: {}（可用: {}）", name, names.join(", "));
+        };
+        self.server = profile.server;
+        self.auth.token_dir = profile
+            .token_dir
+            .unwrap_or_else(|| base.auth.token_dir.join("profiles").join(name));
+        if profile.encryption_key.is_some() {
+            self.auth.encryption_key = profile.encryption_key;
         }
+        self.base = Some(ProfileBase {
+            name: name.to_string(),
+            ..base
+        });
+
+        debug!("使用配置档: {}", name);
+        Ok(())
+    }
+
+    /// 当前使用的配置档名
+    pub fn profile_name(&self) -> &str {
+        self.base
+            .as_ref()
+            .map_or(DEFAULT_PROFILE, |base| base.name.as_str())
+    }
 
+    /// 还原为顶层服务器和认证配置（保存配置文件时使用）
+    fn without_profile(&self) -> Self {
+        let mut config = self.clone();
+        if let Some(base) = config.base.take() {
+            config.server = base.server;
+            config.auth = base.auth;
+        }
+        config
+    }
+
+    /// 从指定路径加载配置文件
+    pub fn load_from(config_path: &Path) -> Result<Self> {
         info!("加载配置文件: {:?}", config_path);
-        let content = std::fs::read_to_string(&config_path)
+        let content = std::fs::read_to_string(config_path)
             .with_context(|| format!("无法读取配置文件: {:?}", config_path))?;
 
         let config: ClientConfig = toml::from_str(&content)
@@ -340,15 +851,66 @@ impl ClientConfig {
                 .with_context(|| format!("无法创建配置目录: {:?}", parent))?;
         }
 
-        let content = toml::to_string_pretty(self).context("无法序列化配置")?;
+        // 应用的配置档只影响本次运行，文件中保留顶层配置
+        let content = toml::to_string_pretty(&self.without_profile()).context("无法序列化配置")?;
 
-        std::fs::write(path, content).with_context(|| format!("无法写入配置文件: {:?}", path))?;
+        crate::transfer::write_atomic_sync(path, content)
+            .with_context(|| format!("无法写入配置文件: {:?}", path))?;
 
         info!("配置已保存: {:?}", path);
 
         Ok(())
     }
 
+    /// 去掉密钥等敏感信息后的配置（用于导出到其他机器）
+    pub fn redacted(&self) -> Self {
+        let mut config = self.without_profile();
+        config.auth.encryption_key = None;
+        for profile in config.profiles.values_mut() {
+            profile.encryption_key = None;
+        }
+        config
+    }
+
+    /// 导出完整配置（含同步规则），include_secrets 为 false 时去掉密钥
+    pub fn export_to(&self, path: &Path, include_secrets: bool) -> Result<()> {
+        if include_secrets {
+            self.save(path)
+        } else {
+            self.redacted().save(path)
+        }
+    }
+
+    /// 读取导出的配置并与当前配置合并，校验通过后返回（不写入配置文件）
+    ///
+    /// 导出时去掉的密钥沿用当前配置；merge_rules 为 true 时导入的规则合并到现有规则中，
+    /// 否则替换现有规则。
+    pub fn import_from(path: &Path, current: &ClientConfig, merge_rules: bool) -> Result<Self> {
+        let mut config = Self::load_from(path)?;
+
+        let current = current.without_profile();
+        if config.auth.encryption_key.is_none() {
+            config.auth.encryption_key = current.auth.encryption_key.clone();
+        }
+        for (name, profile) in &mut config.profiles {
+            if profile.encryption_key.is_none() {
+                profile.encryption_key = current
+                    .profiles
+                    .get(name)
+                    .and_then(|current| current.encryption_key.clone());
+            }
+        }
+        if merge_rules {
+            config.sync.rules = merge_rules_by_id(&current.sync.rules, config.sync.rules);
+        }
+
+        config
+            .validate()
+            .with_context(|| format!("导入的配置无效: {:?}", path))?;
+
+        Ok(config)
+    }
+
     /// 获取配置文件路径
     pub fn config_path() -> Result<PathBuf> {
         let config_dir = dirs::home_dir()
@@ -365,19 +927,146 @@ impl ClientConfig {
             anyhow::bail!("服务器地址不能为空");
         }
 
+        // 验证 TLS 配置
+        self.server.validate_tls()?;
+
+        // 验证请求超时
+        for (name, timeout) in [
+            ("request_timeout", Some(self.server.request_timeout)),
+            ("rpc_timeout", self.server.rpc_timeout),
+            ("upload_timeout", self.server.upload_timeout),
+            ("download_timeout", self.server.download_timeout),
+            ("keepalive_timeout", Some(self.server.keepalive_timeout)),
+        ] {
+            if timeout == Some(0) {
+                anyhow::bail!("{} 必须大于 0", name);
+            }
+        }
+
+        // 验证配置档
+        for (name, profile) in &self.profiles {
+            if profile.server.address.is_empty() {
+                anyhow::bail!("配置档 {} 的服务器地址不能为空", name);
+            }
+            profile
+                .server
+                .validate_tls()
+                .with_context(|| format!("配置档 {} 的 TLS 配置无效", name))?;
+        }
+        if let Some(name) = &self.active_profile {
+            if name != DEFAULT_PROFILE && !self.profiles.contains_key(name) {
+                anyhow::bail!("当前配置档不存在: {}", name);
+            }
+        }
+
         // 验证 Claude 目录
         if !self.sync.claude_dir.exists() {
             anyhow::bail!("Claude 配置目录不存在: {:?}", self.sync.claude_dir);
         }
 
+        // 验证附加监控目录（目录名作为服务器上的路径前缀，不能重复）
+        let mut root_ids = HashSet::new();
+        for dir in &self.sync.additional_watch_dirs {
+            if !dir.is_dir() {
+                anyhow::bail!("附加监控目录不存在: {:?}", dir);
+            }
+            if dir.file_name().is_none() || !root_ids.insert(SyncRoots::root_id(dir)) {
+                anyhow::bail!("附加监控目录的目录名为空或重复: {:?}", dir);
+            }
+        }
+
         // 验证冲突解决策略
-        match self.conflict.default_strategy.as_str() {
-            "manual" | "keep_local" | "keep_remote" | "keep_newer" => {}
-            _ => {
-                anyhow::bail!("无效的冲突解决策略: {}", self.conflict.default_strategy);
+        if !is_valid_conflict_strategy(&self.conflict.default_strategy) {
+            anyhow::bail!("无效的冲突解决策略: {}", self.conflict.default_strategy);
+        }
+
+        for (file_type, strategy) in &self.conflict.per_type_strategy {
+            if !is_valid_conflict_strategy(strategy) {
+                anyhow::bail!("文件类型 {} 的冲突解决策略无效: {}", file_type, strategy);
+            }
+        }
+
+        // 验证冲突标记风格（标签出现在标记行中，不能为空或跨行）
+        if !matches!(
+            self.conflict.conflict_marker_style.as_str(),
+            "git2" | "diff3" | "custom"
+        ) {
+            anyhow::bail!(
+                "无效的冲突标记风格: {}",
+                self.conflict.conflict_marker_style
+            );
+        }
+        let labels = &self.conflict.conflict_marker_labels;
+        for label in [
+            Some(&labels.local),
+            labels.base.as_ref(),
+            Some(&labels.remote),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if label.trim().is_empty() || label.contains('\n') {
+                anyhow::bail!("无效的冲突标记标签: {:?}", label);
             }
         }
 
+        // 验证文件大小范围
+        if let (Some(min), Some(max)) = (self.sync.min_file_size, self.sync.max_file_size) {
+            if min > max {
+                anyhow::bail!("min_file_size ({}) 不能大于 max_file_size ({})", min, max);
+            }
+        }
+
+        // 验证大小写冲突处理方式
+        if !matches!(self.sync.case_collision.as_str(), "flag" | "merge") {
+            anyhow::bail!("无效的大小写冲突处理方式: {}", self.sync.case_collision);
+        }
+
+        // 验证控制端口地址
+        if !self.sync.control_address.is_empty()
+            && self
+                .sync
+                .control_address
+                .parse::<std::net::SocketAddr>()
+                .is_err()
+        {
+            anyhow::bail!("无效的控制端口地址: {}", self.sync.control_address);
+        }
+
+        // 验证电源与网络状态检查间隔
+        if (self.sync.pause_on_battery || self.sync.pause_on_metered)
+            && self.sync.power_check_interval == 0
+        {
+            anyhow::bail!("power_check_interval 必须大于 0");
+        }
+
+        // 验证防抖文件数上限
+        if self.performance.max_pending_files == 0 {
+            anyhow::bail!("max_pending_files 必须大于 0");
+        }
+
+        // 验证传输分块大小
+        let chunk_range = crate::transfer::MIN_CHUNK_SIZE..=crate::transfer::MAX_CHUNK_SIZE;
+        if !chunk_range.contains(&self.performance.chunk_size) {
+            anyhow::bail!(
+                "无效的分块大小 chunk_size: {} (应在 {} 到 {} 字节之间)",
+                self.performance.chunk_size,
+                chunk_range.start(),
+                chunk_range.end()
+            );
+        }
+
+        // 验证指标端点地址
+        if !self.performance.metrics_address.is_empty()
+            && self
+                .performance
+                .metrics_address
+                .parse::<std::net::SocketAddr>()
+                .is_err()
+        {
+            anyhow::bail!("无效的指标端点地址: {}", self.performance.metrics_address);
+        }
+
         // 验证日志级别
         match self.logging.level.as_str() {
             "trace" | "debug" | "info" | "warn" | "error" => {}
@@ -414,27 +1103,59 @@ impl ClientConfig {
 
     /// 获取排除目录的完整路径
     pub fn get_exclude_paths(&self) -> Vec<PathBuf> {
-        self.sync
-            .exclude_dirs
+        self.sync_roots()
             .iter()
-            .map(|dir| self.sync.claude_dir.join(dir))
+            .flat_map(|root| self.sync.exclude_dirs.iter().map(|dir| root.path.join(dir)))
             .collect()
     }
 
+    /// 所有同步根目录（Claude 目录和附加监控目录）
+    pub fn sync_roots(&self) -> SyncRoots {
+        SyncRoots::new(
+            self.sync.claude_dir.clone(),
+            &self.sync.additional_watch_dirs,
+        )
+    }
+
+    /// 路径匹配是否忽略大小写
+    pub fn case_insensitive_paths(&self) -> bool {
+        self.sync
+            .case_insensitive
+            .unwrap_or_else(crate::paths::platform_case_insensitive)
+    }
+
+    /// glob 匹配选项（跟随大小写设置）
+    fn glob_match_options(&self) -> glob::MatchOptions {
+        glob::MatchOptions {
+            case_sensitive: !self.case_insensitive_paths(),
+            ..Default::default()
+        }
+    }
+
     /// 检查路径是否应该被排除
     pub fn should_exclude(&self, path: &Path) -> bool {
-        // 检查排除目录
-        for exclude_dir in &self.sync.exclude_dirs {
-            if path.starts_with(self.sync.claude_dir.join(exclude_dir)) {
+        let case_insensitive = self.case_insensitive_paths();
+        let options = self.glob_match_options();
+
+        // 检查排除目录（每个同步根目录分别应用）
+        for exclude_path in self.get_exclude_paths() {
+            if crate::paths::starts_with(path, &exclude_path, case_insensitive) {
                 debug!("路径在排除目录中: {:?}", path);
                 return true;
             }
         }
 
+        // 检查任意层级的排除目录名（只比较同步根目录以下的部分）
+        let relative = self.sync_roots().relative_path(path).to_path_buf();
+        if crate::paths::in_dir_named(&relative, &self.sync.exclude_dir_names, case_insensitive) {
+            debug!("路径在按名称排除的目录中: {:?}", path);
+            return true;
+        }
+
         // 检查排除模式
         for pattern in &self.sync.exclude_patterns {
             if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
-                if glob_pattern.matches_path(path) {
+                if glob_pattern.matches_path_with(path, options) {
                     debug!("路径匹配排除模式: {:?} (pattern: {})", path, pattern);
                     return true;
                 }
@@ -448,6 +1169,7 @@ impl ClientConfig {
     pub fn apply_rules(&self, path: &Path, file_type: &str) -> bool {
         let mut should_sync = true;
         let mut highest_priority = i32::MIN;
+        let options = self.glob_match_options();
 
         for rule in &self.sync.rules {
             if !rule.enabled {
@@ -463,7 +1185,7 @@ impl ClientConfig {
 
             // 检查模式匹配
             if let Ok(glob_pattern) = glob::Pattern::new(&rule.pattern) {
-                if glob_pattern.matches_path(path) {
+                if glob_pattern.matches_path_with(path, options) {
                     // 优先级更高的规则会覆盖之前的规则
                     if rule.priority > highest_priority {
                         should_sync = matches!(rule.rule_type, crate::rules::RuleType::Include);
@@ -480,17 +1202,26 @@ impl ClientConfig {
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
+            active_profile: None,
             server: ServerConfig {
                 address: default_server_address(),
                 health_check_address: default_health_check_address(),
                 connection_timeout: default_connection_timeout(),
                 request_timeout: default_request_timeout(),
+                rpc_timeout: None,
+                upload_timeout: None,
+                download_timeout: None,
+                keepalive_interval: default_keepalive_interval(),
+                keepalive_timeout: default_keepalive_timeout(),
                 tls_enabled: default_tls_enabled(),
                 tls_cert_path: None,
+                tls_client_cert_path: None,
+                tls_client_key_path: None,
             },
             auth: AuthConfig {
                 token_dir: default_token_dir(),
                 encryption_key: None,
+                token_store: TokenStoreKind::default(),
                 auto_refresh: default_auto_refresh(),
                 refresh_before: default_refresh_before(),
             },
@@ -499,31 +1230,69 @@ impl Default for ClientConfig {
                 sync_interval: default_sync_interval(),
                 batch_window: default_batch_window(),
                 exclude_dirs: default_exclude_dirs(),
+                exclude_dir_names: vec![],
                 exclude_patterns: default_exclude_patterns(),
                 include_types: default_include_types(),
                 rules: vec![],
+                state_file: default_state_file(),
+                offline_queue_file: default_offline_queue_file(),
+                settle_quiet_period: default_settle_quiet_period(),
+                settle_max_wait: default_settle_max_wait(),
+                write_settle_window: default_write_settle_window(),
+                write_settle_max_wait: default_write_settle_max_wait(),
+                max_file_size: None,
+                min_file_size: None,
+                max_total_upload: None,
+                follow_symlinks: false,
+                control_address: default_control_address(),
+                heartbeat_interval: default_heartbeat_interval(),
+                rules_refresh_interval: default_rules_refresh_interval(),
+                pause_on_battery: false,
+                pause_on_metered: false,
+                power_check_interval: default_power_check_interval(),
+                case_insensitive: None,
+                case_collision: default_case_collision(),
+                preserve_mode: default_preserve_mode(),
+                safe_delete: default_safe_delete(),
+                direction: SyncDirection::default(),
+                additional_watch_dirs: Vec::new(),
             },
             conflict: ConflictConfig {
                 default_strategy: default_conflict_strategy(),
+                per_type_strategy: HashMap::new(),
                 auto_merge_text: default_auto_merge_text(),
                 auto_merge_structured: default_auto_merge_structured(),
                 conflict_dir: default_conflict_dir(),
                 keep_conflict_copy: default_keep_conflict_copy(),
+                array_merge_by_key: false,
+                array_merge_keys: default_array_merge_keys(),
+                audit_log: default_audit_log(),
+                conflict_marker_style: default_conflict_marker_style(),
+                conflict_marker_labels: ConflictMarkerLabels::default(),
             },
             performance: PerformanceConfig {
                 debounce_delay: default_debounce_delay(),
+                max_pending_files: default_max_pending_files(),
                 large_file_threshold: default_large_file_threshold(),
                 max_concurrent_uploads: default_max_concurrent_uploads(),
                 max_concurrent_downloads: default_max_concurrent_downloads(),
                 upload_retries: default_upload_retries(),
                 download_retries: default_download_retries(),
                 retry_delay: default_retry_delay(),
+                chunk_size: default_chunk_size(),
+                metrics_file: default_metrics_file(),
+                metrics_address: default_metrics_address(),
+                delta_upload: default_delta_upload(),
+                delta_base_dir: default_delta_base_dir(),
+                hash_algorithm: HashAlgorithm::default(),
             },
             logging: LoggingConfig {
                 level: default_log_level(),
                 log_file: None,
                 format: default_log_format(),
             },
+            profiles: BTreeMap::new(),
+            base: None,
         }
     }
 }
@@ -539,6 +1308,98 @@ mod tests {
         assert_eq!(config.performance.debounce_delay, 500);
     }
 
+    #[test]
+    fn test_tls_requires_https_address() {
+        let mut server = ClientConfig::default().server;
+        server.address = "http://localhost:50051".to_string();
+        server.tls_enabled = true;
+
+        let err = server.validate_tls().unwrap_err();
+        assert!(err.to_string().contains("https://"));
+
+        server.address = "https://localhost:50051".to_string();
+        assert!(server.validate_tls().is_ok());
+
+        // 客户端证书缺少私钥
+        server.tls_client_cert_path = Some("client.pem".to_string());
+        assert!(server.validate_tls().is_err());
+    }
+
+    #[test]
+    fn test_validate_per_type_strategy() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config
+            .conflict
+            .per_type_strategy
+            .insert("log".to_string(), "keep_newer".to_string());
+        config
+            .conflict
+            .per_type_strategy
+            .insert("toml".to_string(), "keep_both".to_string());
+        config
+            .conflict
+            .per_type_strategy
+            .insert("md".to_string(), "auto_merge".to_string());
+        assert!(config.validate().is_ok());
+
+        config
+            .conflict
+            .per_type_strategy
+            .insert("json".to_string(), "merge_everything".to_string());
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("json"));
+    }
+
+    #[test]
+    fn test_validate_conflict_marker_style() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.conflict.conflict_marker_style = "custom".to_string();
+        config.conflict.conflict_marker_labels.base = Some("ANCESTOR".to_string());
+        assert!(config.validate().is_ok());
+
+        config.conflict.conflict_marker_labels.local = "two\nlines".to_string();
+        assert!(config.validate().is_err());
+
+        config.conflict.conflict_marker_labels = ConflictMarkerLabels::default();
+        config.conflict.conflict_marker_style = "zdiff3".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("zdiff3"));
+    }
+
+    #[test]
+    fn test_validate_file_size_range() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.sync.min_file_size = Some(10);
+        config.sync.max_file_size = Some(10);
+        assert!(config.validate().is_ok());
+
+        config.sync.max_file_size = Some(5);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("max_file_size"));
+    }
+
+    #[test]
+    fn test_validate_chunk_size() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.performance.chunk_size = 64 * 1024;
+        assert!(config.validate().is_ok());
+
+        config.performance.chunk_size = 1024;
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("chunk_size"));
+
+        config.performance.chunk_size = 128 * 1024 * 1024;
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_should_exclude() {
         let config = ClientConfig::default();
@@ -553,6 +1414,261 @@ mod tests {
         assert!(!config.should_exclude(&agents_path));
     }
 
+    #[test]
+    fn test_should_exclude_nested_dir_names() {
+        let mut config = ClientConfig::default();
+        config.sync.exclude_dir_names = vec!["node_modules".to_string()];
+        let claude_dir = config.sync.claude_dir.clone();
+
+        // 任意层级的同名目录都被排除
+        let nested = claude_dir
+            .join("plugins")
+            .join("a")
+            .join("b")
+            .join("node_modules")
+            .join("pkg")
+            .join("index.js");
+        assert!(config.should_exclude(&nested));
+        assert!(config.should_exclude(&claude_dir.join("node_modules").join("x.json")));
+
+        // 同名文件、名称相近的目录不受影响
+        assert!(!config.should_exclude(&claude_dir.join("agents").join("node_modules")));
+        assert!(!config.should_exclude(&claude_dir.join("node_modules_docs").join("a.md")));
+
+        // 同步根目录本身的上级路径不参与匹配
+        config.sync.claude_dir = PathBuf::from("/work/node_modules/.claude");
+        assert!(!config.should_exclude(Path::new("/work/node_modules/.claude/agents/a.md")));
+    }
+
+    #[test]
+    fn test_should_exclude_case_insensitive() {
+        let mut config = ClientConfig::default();
+        config.sync.exclude_patterns = vec!["*.log".to_string()];
+        let claude_dir = config.sync.claude_dir.clone();
+        let cache_path = claude_dir.join("Cache").join("test.txt");
+        let log_path = claude_dir.join("debug.LOG");
+
+        config.sync.case_insensitive = Some(false);
+        assert!(!config.should_exclude(&cache_path));
+        assert!(!config.should_exclude(&log_path));
+
+        config.sync.case_insensitive = Some(true);
+        assert!(config.should_exclude(&cache_path));
+        assert!(config.should_exclude(&log_path));
+    }
+
+    #[test]
+    fn test_additional_watch_dirs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let team_dir = temp_dir.path().join("team");
+        std::fs::create_dir_all(&team_dir).unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&config.sync.claude_dir).unwrap();
+        config.sync.additional_watch_dirs = vec![team_dir.clone()];
+        assert!(config.validate().is_ok());
+
+        // 排除目录在附加目录中同样生效
+        config.sync.exclude_dirs = vec!["cache".to_string()];
+        assert!(config.should_exclude(&team_dir.join("cache").join("a.json")));
+        assert!(!config.should_exclude(&team_dir.join("agents").join("a.md")));
+
+        // 目录名重复时无法区分服务器路径
+        let other = temp_dir.path().join("other").join("team");
+        std::fs::create_dir_all(&other).unwrap();
+        config.sync.additional_watch_dirs.push(other);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_case_collision() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.sync.case_collision = "merge".to_string();
+        assert!(config.validate().is_ok());
+
+        config.sync.case_collision = "rename".to_string();
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("rename"));
+    }
+
+    fn rule(id: &str, pattern: &str) -> crate::rules::SyncRule {
+        crate::rules::SyncRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            rule_type: crate::rules::RuleType::Include,
+            pattern: pattern.to_string(),
+            pattern_type: crate::rules::PatternType::Glob,
+            file_type: None,
+            priority: 0,
+            enabled: true,
+            description: None,
+            rewrite: None,
+        }
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let export_path = temp_dir.path().join("export.toml");
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.server.address = "https://sync.example.com:50051".to_string();
+        config.sync.rules = vec![rule("agents", "agents/**/*")];
+        config.auth.encryption_key = Some("secret".to_string());
+
+        config.export_to(&export_path, true).unwrap();
+        let imported =
+            ClientConfig::import_from(&export_path, &ClientConfig::default(), false).unwrap();
+        assert_eq!(
+            toml::to_string(&imported).unwrap(),
+            toml::to_string(&config).unwrap()
+        );
+
+        // 导入前校验，无效配置不会被接受
+        let mut invalid = config.clone();
+        invalid.logging.level = "verbose".to_string();
+        invalid.export_to(&export_path, true).unwrap();
+        assert!(ClientConfig::import_from(&export_path, &config, false).is_err());
+    }
+
+    #[test]
+    fn test_export_redacts_secrets() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let export_path = temp_dir.path().join("export.toml");
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.auth.encryption_key = Some("top-secret-key".to_string());
+
+        config.export_to(&export_path, false).unwrap();
+        let exported = std::fs::read_to_string(&export_path).unwrap();
+        assert!(!exported.contains("top-secret-key"));
+
+        // 导入到没有密钥的机器上仍然没有密钥，已有密钥的机器沿用本机密钥
+        let fresh = ClientConfig::import_from(&export_path, &ClientConfig::default(), false);
+        assert_eq!(fresh.unwrap().auth.encryption_key, None);
+        let mut local = ClientConfig::default();
+        local.auth.encryption_key = Some("local-key".to_string());
+        let imported = ClientConfig::import_from(&export_path, &local, false).unwrap();
+        assert_eq!(imported.auth.encryption_key.as_deref(), Some("local-key"));
+    }
+
+    #[test]
+    fn test_import_merges_rules_by_id() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let export_path = temp_dir.path().join("export.toml");
+        let mut exported = ClientConfig::default();
+        exported.sync.claude_dir = temp_dir.path().to_path_buf();
+        exported.sync.rules = vec![rule("shared", "skills/**/*"), rule("new", "*.json")];
+        exported.export_to(&export_path, false).unwrap();
+
+        let mut local = ClientConfig::default();
+        local.sync.rules = vec![rule("local", "agents/**/*"), rule("shared", "old/**/*")];
+
+        let merged = ClientConfig::import_from(&export_path, &local, true).unwrap();
+        let rules: Vec<(&str, &str)> = merged
+            .sync
+            .rules
+            .iter()
+            .map(|rule| (rule.id.as_str(), rule.pattern.as_str()))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("local", "agents/**/*"),
+                ("shared", "skills/**/*"),
+                ("new", "*.json")
+            ]
+        );
+
+        let replaced = ClientConfig::import_from(&export_path, &local, false).unwrap();
+        assert_eq!(replaced.sync.rules.len(), 2);
+    }
+
+    fn profile(address: &str) -> ProfileConfig {
+        ProfileConfig {
+            server: ServerConfig {
+                address: address.to_string(),
+                ..ClientConfig::default().server
+            },
+            token_dir: None,
+            encryption_key: None,
+        }
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = ClientConfig::default();
+        config.server.address = "https://sync.example.com:50051".to_string();
+        config
+            .profiles
+            .insert("local".to_string(), profile("http://localhost:50051"));
+
+        config.apply_profile("local").unwrap();
+        assert_eq!(config.profile_name(), "local");
+        assert_eq!(config.server.address, "http://localhost:50051");
+        assert!(config.apply_profile("missing").is_err());
+
+        // 保存时写回顶层配置，配置档只影响本次运行
+        config.apply_profile("local").unwrap();
+        config.save(&config_path).unwrap();
+        let saved = ClientConfig::load_from(&config_path).unwrap();
+        assert_eq!(saved.server.address, "https://sync.example.com:50051");
+        assert!(saved.profiles.contains_key("local"));
+
+        config.apply_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(config.profile_name(), DEFAULT_PROFILE);
+        assert_eq!(config.server.address, "https://sync.example.com:50051");
+    }
+
+    #[test]
+    fn test_profile_tokens_are_isolated() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = ClientConfig::default();
+        config.auth.token_dir = temp_dir.path().join("tokens");
+        config
+            .profiles
+            .insert("local".to_string(), profile("http://localhost:50051"));
+        config.profiles.insert(
+            "prod".to_string(),
+            profile("https://sync.example.com:50051"),
+        );
+
+        let token_manager = |name: &str| {
+            let mut config = config.clone();
+            config.apply_profile(name).unwrap();
+            crate::token::TokenManager::new(
+                config.auth.token_dir,
+                config.auth.encryption_key,
+                String::new(),
+            )
+        };
+        let (local, prod, default) = (
+            token_manager("local"),
+            token_manager("prod"),
+            token_manager(DEFAULT_PROFILE),
+        );
+
+        local
+            .save_tokens(crate::token::TokenStorage {
+                access_token: "access".to_string(),
+                refresh_token: "refresh".to_string(),
+                device_id: "device".to_string(),
+                user_id: "user".to_string(),
+                access_expires_at: 0,
+                refresh_expires_at: 0,
+            })
+            .unwrap();
+
+        assert!(local.has_tokens());
+        assert!(!prod.has_tokens());
+        assert!(!default.has_tokens());
+        assert!(temp_dir.path().join("tokens/profiles/local").is_dir());
+    }
+
     #[test]
     fn test_apply_rules() {
         let mut config = ClientConfig::default();
@@ -568,6 +1684,7 @@ mod tests {
             priority: 0,
             enabled: true,
             description: None,
+            rewrite: None,
         });
 
         // 添加排除规则
@@ -581,6 +1698,7 @@ mod tests {
             priority: 10,
             enabled: true,
             description: None,
+            rewrite: None,
         });
 
         let test_path = PathBuf::from("test-temp.md");
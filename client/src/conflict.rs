@@ -1,7 +1,12 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde_json::Value as JsonValue;
+use std::collections::HashMap;
 use std::path::Path;
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::audit::{AuditEntry, AuditLog};
+use crate::transfer::TransferManager;
 
 /// 冲突类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,6 +32,39 @@ pub enum ResolutionStrategy {
     AutoMerge,
     /// 手动解决
     Manual,
+    /// 保留两个版本：本地不变，远程版本另存为 `<文件名> (remote).<扩展名>`
+    KeepBoth,
+}
+
+impl ResolutionStrategy {
+    /// 全部策略（配置校验和解析共用）
+    pub const ALL: [Self; 6] = [
+        ResolutionStrategy::KeepLocal,
+        ResolutionStrategy::KeepRemote,
+        ResolutionStrategy::KeepNewer,
+        ResolutionStrategy::AutoMerge,
+        ResolutionStrategy::Manual,
+        ResolutionStrategy::KeepBoth,
+    ];
+
+    /// 从配置字符串解析策略
+    pub fn parse(strategy: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|candidate| candidate.as_str() == strategy)
+    }
+
+    /// 配置字符串形式
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResolutionStrategy::KeepLocal => "keep_local",
+            ResolutionStrategy::KeepRemote => "keep_remote",
+            ResolutionStrategy::KeepNewer => "keep_newer",
+            ResolutionStrategy::AutoMerge => "auto_merge",
+            ResolutionStrategy::Manual => "manual",
+            ResolutionStrategy::KeepBoth => "keep_both",
+        }
+    }
 }
 
 /// 合并结果
@@ -38,10 +76,159 @@ pub enum MergeResult {
     NoConflict,
     /// 有冲突，无法自动合并
     Conflict(String),
+    /// 保留两个版本（本地不变，远程版本另存）
+    KeepBoth,
     /// 错误
     Error(String),
 }
 
+/// JSON 数组合并方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrayMergeMode {
+    /// 直接使用远程数组
+    TakeRemote,
+    /// 对象数组按标识字段合并（依次尝试各字段名）
+    ByKey(Vec<String>),
+}
+
+/// 冲突标记风格
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictMarkerStyle {
+    /// Git 默认风格：本地和远程两段
+    Git2,
+    /// diff3 风格：有基线时在本地和远程之间输出基线段
+    Diff3,
+    /// 自定义标签（base 为 None 时不输出基线段）
+    Custom {
+        local: String,
+        base: Option<String>,
+        remote: String,
+    },
+}
+
+impl ConflictMarkerStyle {
+    /// 本地、基线、远程段的标签
+    fn labels(&self) -> (&str, Option<&str>, &str) {
+        match self {
+            ConflictMarkerStyle::Git2 => ("LOCAL", None, "REMOTE"),
+            ConflictMarkerStyle::Diff3 => ("LOCAL", Some("BASE"), "REMOTE"),
+            ConflictMarkerStyle::Custom {
+                local,
+                base,
+                remote,
+            } => (local, base.as_deref(), remote),
+        }
+    }
+}
+
+/// 合并驱动：负责某类文件的三方合并
+pub trait MergeDriver: Send + Sync {
+    /// 是否能处理该文件类型（`detect_file_type` 的返回值）
+    fn can_handle(&self, file_type: &str) -> bool;
+
+    /// 是否为结构化合并（受 auto_merge_structured 控制，否则受 auto_merge_text 控制）
+    fn is_structured(&self) -> bool {
+        true
+    }
+
+    /// 合并本地和远程内容，`base` 为共同基线版本
+    fn merge(
+        &self,
+        resolver: &ConflictResolver,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+    ) -> Result<MergeResult>;
+}
+
+/// 文本合并驱动
+pub struct TextMergeDriver;
+
+impl MergeDriver for TextMergeDriver {
+    fn can_handle(&self, file_type: &str) -> bool {
+        matches!(file_type, "text" | "md" | "rst" | "txt")
+    }
+
+    fn is_structured(&self) -> bool {
+        false
+    }
+
+    fn merge(
+        &self,
+        resolver: &ConflictResolver,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+    ) -> Result<MergeResult> {
+        resolver.merge_text(local, remote, base)
+    }
+}
+
+/// JSON 合并驱动
+pub struct JsonMergeDriver;
+
+impl MergeDriver for JsonMergeDriver {
+    fn can_handle(&self, file_type: &str) -> bool {
+        file_type == "json"
+    }
+
+    fn merge(
+        &self,
+        resolver: &ConflictResolver,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+    ) -> Result<MergeResult> {
+        resolver.merge_json(local, remote, base)
+    }
+}
+
+/// YAML 合并驱动
+pub struct YamlMergeDriver;
+
+impl MergeDriver for YamlMergeDriver {
+    fn can_handle(&self, file_type: &str) -> bool {
+        matches!(file_type, "yaml" | "yml")
+    }
+
+    fn merge(
+        &self,
+        resolver: &ConflictResolver,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+    ) -> Result<MergeResult> {
+        resolver.merge_yaml(local, remote, base)
+    }
+}
+
+/// TOML 合并驱动
+pub struct TomlMergeDriver;
+
+impl MergeDriver for TomlMergeDriver {
+    fn can_handle(&self, file_type: &str) -> bool {
+        file_type == "toml"
+    }
+
+    fn merge(
+        &self,
+        resolver: &ConflictResolver,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+    ) -> Result<MergeResult> {
+        resolver.merge_toml(local, remote, base)
+    }
+}
+
+/// 内置合并驱动，位于自定义驱动之后
+static BUILTIN_DRIVERS: [&dyn MergeDriver; 4] = [
+    &TextMergeDriver,
+    &JsonMergeDriver,
+    &YamlMergeDriver,
+    &TomlMergeDriver,
+];
+
 /// 冲突解决器
 pub struct ConflictResolver {
     /// 默认解决策略
@@ -52,6 +239,21 @@ pub struct ConflictResolver {
 
     /// 是否自动合并结构化文件
     auto_merge_structured: bool,
+
+    /// JSON 数组合并方式
+    array_merge: ArrayMergeMode,
+
+    /// 按文件类型覆盖的解决策略
+    type_strategies: HashMap<String, ResolutionStrategy>,
+
+    /// 自定义合并驱动（按注册顺序优先于内置驱动）
+    drivers: Vec<Box<dyn MergeDriver>>,
+
+    /// 自动解决冲突的审计日志
+    audit_log: Option<AuditLog>,
+
+    /// 冲突标记风格
+    marker_style: ConflictMarkerStyle,
 }
 
 impl ConflictResolver {
@@ -65,9 +267,65 @@ impl ConflictResolver {
             default_strategy,
             auto_merge_text,
             auto_merge_structured,
+            array_merge: ArrayMergeMode::TakeRemote,
+            type_strategies: HashMap::new(),
+            drivers: Vec::new(),
+            audit_log: None,
+            marker_style: ConflictMarkerStyle::Git2,
         }
     }
 
+    /// 设置冲突标记风格
+    pub fn with_marker_style(mut self, marker_style: ConflictMarkerStyle) -> Self {
+        self.marker_style = marker_style;
+        self
+    }
+
+    /// 记录每次自动解决冲突到审计日志
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// 注册自定义合并驱动
+    pub fn with_driver(mut self, driver: Box<dyn MergeDriver>) -> Self {
+        self.drivers.push(driver);
+        self
+    }
+
+    /// 查找第一个能处理该文件类型的合并驱动
+    pub fn driver_for(&self, file_type: &str) -> Option<&dyn MergeDriver> {
+        self.drivers
+            .iter()
+            .map(|driver| driver.as_ref())
+            .chain(BUILTIN_DRIVERS.iter().copied())
+            .find(|driver| driver.can_handle(file_type))
+    }
+
+    /// 设置按文件类型覆盖的解决策略
+    pub fn with_type_strategies(
+        mut self,
+        type_strategies: HashMap<String, ResolutionStrategy>,
+    ) -> Self {
+        self.type_strategies = type_strategies;
+        self
+    }
+
+    /// 获取文件适用的解决策略（优先使用文件类型对应的策略）
+    pub fn strategy_for(&self, path: &Path) -> ResolutionStrategy {
+        let file_type = crate::rules::detect_file_type(path);
+        self.type_strategies
+            .get(&file_type)
+            .copied()
+            .unwrap_or(self.default_strategy)
+    }
+
+    /// 设置 JSON 数组合并方式
+    pub fn with_array_merge(mut self, array_merge: ArrayMergeMode) -> Self {
+        self.array_merge = array_merge;
+        self
+    }
+
     /// 解决冲突
     pub fn resolve(
         &self,
@@ -76,19 +334,85 @@ impl ConflictResolver {
         remote_content: &str,
         base_content: Option<&str>,
         conflict_type: ConflictType,
+    ) -> Result<MergeResult> {
+        self.resolve_with_remote_time(
+            local_path,
+            local_content,
+            remote_content,
+            base_content,
+            conflict_type,
+            None,
+        )
+    }
+
+    /// 解决冲突，remote_modified 为远程版本的修改时间（keep_newer 策略据此比较）
+    pub fn resolve_with_remote_time(
+        &self,
+        local_path: &Path,
+        local_content: &str,
+        remote_content: &str,
+        base_content: Option<&str>,
+        conflict_type: ConflictType,
+        remote_modified: Option<DateTime<Utc>>,
     ) -> Result<MergeResult> {
         info!("解决冲突: {:?}, 类型: {:?}", local_path, conflict_type);
 
-        match conflict_type {
-            ConflictType::ModifyModify => {
-                self.resolve_modify_modify(local_path, local_content, remote_content, base_content)
-            }
+        let result = match conflict_type {
+            ConflictType::ModifyModify => self.resolve_modify_modify(
+                local_path,
+                local_content,
+                remote_content,
+                base_content,
+                remote_modified,
+            ),
             ConflictType::ModifyDelete => {
                 self.resolve_modify_delete(local_path, local_content, remote_content, conflict_type)
             }
             ConflictType::BinaryConflict => Ok(MergeResult::Conflict(
                 "二进制文件冲突无法自动解决".to_string(),
             )),
+        }?;
+
+        if let (MergeResult::Merged(merged), Some(_)) = (&result, &self.audit_log) {
+            let entry = AuditEntry {
+                path: local_path.to_path_buf(),
+                strategy: self
+                    .applied_strategy(local_path, conflict_type)
+                    .as_str()
+                    .to_string(),
+                timestamp: chrono::Utc::now(),
+                local_hash: TransferManager::calculate_hash(local_content.as_bytes())?,
+                remote_hash: TransferManager::calculate_hash(remote_content.as_bytes())?,
+                merged_hash: TransferManager::calculate_hash(merged.as_bytes())?,
+            };
+            self.record_audit(&entry);
+        }
+
+        Ok(result)
+    }
+
+    /// 写入一条审计记录（未配置审计日志时忽略，写入失败不影响冲突解决）
+    pub fn record_audit(&self, entry: &AuditEntry) {
+        if let Some(audit_log) = &self.audit_log {
+            if let Err(e) = audit_log.append(entry) {
+                warn!("写入冲突审计日志失败: {:#}", e);
+            }
+        }
+    }
+
+    /// 冲突实际采用的解决策略（与 resolve_modify_modify / resolve_modify_delete 的选择一致）
+    fn applied_strategy(&self, path: &Path, conflict_type: ConflictType) -> ResolutionStrategy {
+        if conflict_type != ConflictType::ModifyModify {
+            return self.strategy_for(path);
+        }
+
+        let file_type = crate::rules::detect_file_type(path);
+        if let Some(strategy) = self.type_strategies.get(&file_type) {
+            *strategy
+        } else if self.driver_for(&file_type).is_some() {
+            ResolutionStrategy::AutoMerge
+        } else {
+            self.default_strategy
         }
     }
 
@@ -99,59 +423,98 @@ impl ConflictResolver {
         local_content: &str,
         remote_content: &str,
         base_content: Option<&str>,
+        remote_modified: Option<DateTime<Utc>>,
     ) -> Result<MergeResult> {
         // 检查文件类型
         let file_type = crate::rules::detect_file_type(path);
 
-        match file_type.as_str() {
-            "text" | "md" | "rst" | "txt" => {
-                if self.auto_merge_text {
-                    self.merge_text(local_content, remote_content, base_content)
-                } else {
-                    Ok(self.create_conflict_marker(local_content, remote_content))
-                }
-            }
-            "json" => {
-                if self.auto_merge_structured {
-                    self.merge_json(local_content, remote_content, base_content)
-                } else {
-                    Ok(self.create_conflict_marker(local_content, remote_content))
-                }
-            }
-            "yaml" | "yml" => {
-                if self.auto_merge_structured {
-                    self.merge_yaml(local_content, remote_content, base_content)
-                } else {
-                    Ok(self.create_conflict_marker(local_content, remote_content))
+        // 文件类型配置了专用策略时优先使用
+        if let Some(strategy) = self.type_strategies.get(&file_type) {
+            return match strategy {
+                ResolutionStrategy::KeepLocal => Ok(MergeResult::Merged(local_content.to_string())),
+                ResolutionStrategy::KeepRemote => {
+                    Ok(MergeResult::Merged(remote_content.to_string()))
                 }
-            }
-            _ => {
-                // 其他文件类型，使用默认策略
-                match self.default_strategy {
-                    ResolutionStrategy::KeepLocal => {
-                        Ok(MergeResult::Merged(local_content.to_string()))
-                    }
-                    ResolutionStrategy::KeepRemote => {
-                        Ok(MergeResult::Merged(remote_content.to_string()))
-                    }
-                    ResolutionStrategy::Manual => {
-                        Ok(self.create_conflict_marker(local_content, remote_content))
-                    }
-                    _ => Ok(self.create_conflict_marker(local_content, remote_content)),
+                ResolutionStrategy::AutoMerge => match self.driver_for(&file_type) {
+                    Some(driver) => driver.merge(self, local_content, remote_content, base_content),
+                    None => Ok(self.create_conflict_marker(
+                        local_content,
+                        remote_content,
+                        base_content,
+                    )),
+                },
+                ResolutionStrategy::KeepBoth => Ok(MergeResult::KeepBoth),
+                ResolutionStrategy::KeepNewer => Ok(self.keep_newer(
+                    path,
+                    local_content,
+                    remote_content,
+                    base_content,
+                    remote_modified,
+                )),
+                ResolutionStrategy::Manual => {
+                    Ok(self.create_conflict_marker(local_content, remote_content, base_content))
                 }
+            };
+        }
+
+        if let Some(driver) = self.driver_for(&file_type) {
+            let enabled = if driver.is_structured() {
+                self.auto_merge_structured
+            } else {
+                self.auto_merge_text
+            };
+
+            return if enabled {
+                driver.merge(self, local_content, remote_content, base_content)
+            } else {
+                Ok(self.create_conflict_marker(local_content, remote_content, base_content))
+            };
+        }
+
+        // 没有合并驱动的文件类型，使用默认策略
+        match self.default_strategy {
+            ResolutionStrategy::KeepLocal => Ok(MergeResult::Merged(local_content.to_string())),
+            ResolutionStrategy::KeepRemote => Ok(MergeResult::Merged(remote_content.to_string())),
+            ResolutionStrategy::KeepBoth => Ok(MergeResult::KeepBoth),
+            ResolutionStrategy::KeepNewer => Ok(self.keep_newer(
+                path,
+                local_content,
+                remote_content,
+                base_content,
+                remote_modified,
+            )),
+            ResolutionStrategy::Manual => {
+                Ok(self.create_conflict_marker(local_content, remote_content, base_content))
             }
+            _ => Ok(self.create_conflict_marker(local_content, remote_content, base_content)),
+        }
+    }
+
+    /// 保留修改时间较新的一端，任一时间未知时生成冲突标记
+    fn keep_newer(
+        &self,
+        path: &Path,
+        local_content: &str,
+        remote_content: &str,
+        base_content: Option<&str>,
+        remote_modified: Option<DateTime<Utc>>,
+    ) -> MergeResult {
+        match local_is_newer(path, remote_modified) {
+            Some(true) => MergeResult::Merged(local_content.to_string()),
+            Some(false) => MergeResult::Merged(remote_content.to_string()),
+            None => self.create_conflict_marker(local_content, remote_content, base_content),
         }
     }
 
     /// 解决 ModifyDelete 冲突
     fn resolve_modify_delete(
         &self,
-        _path: &Path,
+        path: &Path,
         local_content: &str,
         remote_content: &str,
         _conflict_type: ConflictType,
     ) -> Result<MergeResult> {
-        match self.default_strategy {
+        match self.strategy_for(path) {
             ResolutionStrategy::KeepLocal => Ok(MergeResult::Merged(local_content.to_string())),
             ResolutionStrategy::KeepRemote => {
                 if remote_content.is_empty() {
@@ -161,7 +524,12 @@ impl ConflictResolver {
                     Ok(MergeResult::Merged(remote_content.to_string()))
                 }
             }
-            _ => Ok(self.create_conflict_marker(local_content, remote_content)),
+            // 一端已删除时只有一个版本，保留仍存在的那个
+            ResolutionStrategy::KeepBoth if local_content.is_empty() => {
+                Ok(MergeResult::Merged(remote_content.to_string()))
+            }
+            ResolutionStrategy::KeepBoth => Ok(MergeResult::Merged(local_content.to_string())),
+            _ => Ok(self.create_conflict_marker(local_content, remote_content, None)),
         }
     }
 
@@ -175,7 +543,7 @@ impl ConflictResolver {
 
             if local_differs && remote_differs && local_and_remote_different {
                 // 双方都修改了且修改不同，创建冲突标记
-                Ok(self.create_conflict_marker(local, remote))
+                Ok(self.create_conflict_marker(local, remote, Some(base)))
             } else if local_differs {
                 // 只有本地修改
                 Ok(MergeResult::Merged(local.to_string()))
@@ -188,7 +556,7 @@ impl ConflictResolver {
             }
         } else {
             // 没有基线版本，创建冲突标记
-            Ok(self.create_conflict_marker(local, remote))
+            Ok(self.create_conflict_marker(local, remote, None))
         }
     }
 
@@ -198,27 +566,40 @@ impl ConflictResolver {
         let local_value: JsonValue = serde_json::from_str(local).context("无法解析本地 JSON")?;
         let remote_value: JsonValue = serde_json::from_str(remote).context("无法解析远程 JSON")?;
 
+        let mut conflicts = Vec::new();
         let merged = if let Some(base_str) = base {
             let base_value: JsonValue =
                 serde_json::from_str(base_str).context("无法解析基线 JSON")?;
-            self.merge_json_values(&base_value, &local_value, &remote_value)?
+            self.merge_json_values(
+                &base_value,
+                &local_value,
+                &remote_value,
+                "$",
+                &mut conflicts,
+            )?
         } else {
             // 没有基线，尝试递归合并
             self.merge_json_values_without_base(&local_value, &remote_value)?
         };
 
+        if let Some(conflict) = self.structured_conflict(local, remote, base, &conflicts) {
+            return Ok(conflict);
+        }
+
         // 格式化输出
         let merged_str = serde_json::to_string_pretty(&merged).context("无法序列化合并的 JSON")?;
 
         Ok(MergeResult::Merged(merged_str))
     }
 
-    /// 递归合并 JSON 值（有基线）
+    /// 递归合并 JSON 值（有基线），两端对同一字段做了不同修改时记录到 conflicts
     fn merge_json_values(
         &self,
         base: &JsonValue,
         local: &JsonValue,
         remote: &JsonValue,
+        path: &str,
+        conflicts: &mut Vec<String>,
     ) -> Result<JsonValue> {
         match (base, local, remote) {
             // 都是对象，递归合并
@@ -240,6 +621,7 @@ impl ConflictResolver {
                     let base_value = base_map.get(key);
                     let local_value = local_map.get(key);
                     let remote_value = remote_map.get(key);
+                    let key_path = format!("{}.{}", path, key);
 
                     match (base_value, local_value, remote_value) {
                         // 三者都存在且相同
@@ -248,11 +630,13 @@ impl ConflictResolver {
                         }
                         // 三者都存在且不同，递归合并
                         (Some(b), Some(l), Some(r)) => {
-                            let merged_value = self.merge_json_values(b, l, r)?;
+                            let merged_value =
+                                self.merge_json_values(b, l, r, &key_path, conflicts)?;
                             merged.insert(key.clone(), merged_value);
                         }
                         // 基线没有，但本地和远程都有（需要选择策略，这里使用本地）
                         (None, Some(l), Some(_r)) => {
+                            conflicts.push(key_path);
                             merged.insert(key.clone(), l.clone());
                         }
                         // 只有本地有
@@ -270,15 +654,117 @@ impl ConflictResolver {
 
                 Ok(JsonValue::Object(merged))
             }
-            // 都是数组，使用远程版本（需要领域知识）
-            (JsonValue::Array(_), JsonValue::Array(_), JsonValue::Array(remote)) => {
-                Ok(JsonValue::Array(remote.clone()))
+            // 都是数组：对象数组按标识字段合并，否则使用远程版本
+            (
+                JsonValue::Array(base_items),
+                JsonValue::Array(local_items),
+                JsonValue::Array(remote_items),
+            ) => match self.array_merge_key(&[base_items, local_items, remote_items]) {
+                Some(key) => self.merge_arrays_by_key(
+                    base_items,
+                    local_items,
+                    remote_items,
+                    key,
+                    path,
+                    conflicts,
+                ),
+                None => Ok(JsonValue::Array(remote_items.clone())),
+            },
+            // 其他类型：只有一端修改时采用修改的一端，两端修改不一致时使用本地版本
+            _ => {
+                if local == base {
+                    Ok(remote.clone())
+                } else {
+                    if remote != base && remote != local {
+                        conflicts.push(path.to_string());
+                    }
+                    Ok(local.clone())
+                }
             }
-            // 其他类型，使用本地版本
-            _ => Ok(local.clone()),
         }
     }
 
+    /// 按标识字段合并对象数组（保留本地顺序，远程新增的元素追加到末尾）
+    fn merge_arrays_by_key(
+        &self,
+        base: &[JsonValue],
+        local: &[JsonValue],
+        remote: &[JsonValue],
+        key: &str,
+        path: &str,
+        conflicts: &mut Vec<String>,
+    ) -> Result<JsonValue> {
+        let base_map = index_by_key(base, key);
+        let local_map = index_by_key(local, key);
+        let remote_map = index_by_key(remote, key);
+
+        let mut merged = Vec::new();
+        for id in merge_order(local, remote, key) {
+            let item_path = format!("{}[{}={}]", path, key, id);
+
+            match (base_map.get(&id), local_map.get(&id), remote_map.get(&id)) {
+                // 两端都有，递归合并
+                (Some(b), Some(l), Some(r)) => {
+                    merged.push(self.merge_json_values(b, l, r, &item_path, conflicts)?);
+                }
+                // 两端都新增了同一元素
+                (None, Some(l), Some(r)) => {
+                    let empty = JsonValue::Object(serde_json::Map::new());
+                    merged.push(self.merge_json_values(&empty, l, r, &item_path, conflicts)?);
+                }
+                // 一端删除：另一端未修改则删除，否则保留修改
+                (Some(b), Some(item), None) | (Some(b), None, Some(item)) if item != b => {
+                    merged.push((*item).clone());
+                }
+                // 只有一端新增
+                (None, Some(item), None) | (None, None, Some(item)) => {
+                    merged.push((*item).clone());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(JsonValue::Array(merged))
+    }
+
+    /// 查找可用于合并的标识字段（所有元素都是带唯一标识的对象）
+    fn array_merge_key<'a>(&'a self, arrays: &[&Vec<JsonValue>]) -> Option<&'a str> {
+        let keys = match &self.array_merge {
+            ArrayMergeMode::TakeRemote => return None,
+            ArrayMergeMode::ByKey(keys) => keys,
+        };
+
+        if arrays.iter().all(|items| items.is_empty()) {
+            return None;
+        }
+
+        keys.iter()
+            .find(|key| {
+                arrays.iter().all(|items| {
+                    let ids: Vec<_> = items.iter().map(|item| element_key(item, key)).collect();
+                    let unique: std::collections::HashSet<_> = ids.iter().collect();
+                    ids.iter().all(Option::is_some) && unique.len() == ids.len()
+                })
+            })
+            .map(String::as_str)
+    }
+
+    /// 按键合并模式下，同一字段两端修改不一致时视为冲突
+    fn structured_conflict(
+        &self,
+        local: &str,
+        remote: &str,
+        base: Option<&str>,
+        conflicts: &[String],
+    ) -> Option<MergeResult> {
+        if conflicts.is_empty() || self.array_merge == ArrayMergeMode::TakeRemote {
+            return None;
+        }
+
+        info!("结构化合并存在冲突字段: {:?}", conflicts);
+        Some(self.create_conflict_marker(local, remote, base))
+    }
+
     /// 递归合并 JSON 值（无基线）
     fn merge_json_values_without_base(
         &self,
@@ -304,6 +790,28 @@ impl ConflictResolver {
 
                 Ok(JsonValue::Object(merged))
             }
+            // 对象数组按标识字段取并集
+            (JsonValue::Array(local_items), JsonValue::Array(remote_items)) => {
+                let Some(key) = self.array_merge_key(&[local_items, remote_items]) else {
+                    return Ok(local.clone());
+                };
+
+                let local_map = index_by_key(local_items, key);
+                let remote_map = index_by_key(remote_items, key);
+
+                let mut merged = Vec::new();
+                for id in merge_order(local_items, remote_items, key) {
+                    match (local_map.get(&id), remote_map.get(&id)) {
+                        (Some(l), Some(r)) => {
+                            merged.push(self.merge_json_values_without_base(l, r)?);
+                        }
+                        (Some(item), None) | (None, Some(item)) => merged.push((*item).clone()),
+                        (None, None) => {}
+                    }
+                }
+
+                Ok(JsonValue::Array(merged))
+            }
             // 其他类型，使用本地版本
             _ => Ok(local.clone()),
         }
@@ -315,26 +823,76 @@ impl ConflictResolver {
         let local_value: JsonValue = serde_yaml::from_str(local).context("无法解析本地 YAML")?;
         let remote_value: JsonValue = serde_yaml::from_str(remote).context("无法解析远程 YAML")?;
 
+        let mut conflicts = Vec::new();
         let merged = if let Some(base_str) = base {
             let base_value: JsonValue =
                 serde_yaml::from_str(base_str).context("无法解析基线 YAML")?;
-            self.merge_json_values(&base_value, &local_value, &remote_value)?
+            self.merge_json_values(
+                &base_value,
+                &local_value,
+                &remote_value,
+                "$",
+                &mut conflicts,
+            )?
         } else {
             self.merge_json_values_without_base(&local_value, &remote_value)?
         };
 
+        if let Some(conflict) = self.structured_conflict(local, remote, base, &conflicts) {
+            return Ok(conflict);
+        }
+
         // 格式化输出为 YAML
         let merged_str = serde_yaml::to_string(&merged).context("无法序列化合并的 YAML")?;
 
         Ok(MergeResult::Merged(merged_str))
     }
 
-    /// 创建冲突标记（Git 风格）
-    fn create_conflict_marker(&self, local: &str, remote: &str) -> MergeResult {
-        let conflict = format!(
-            "<<<<<<< LOCAL\n{}\n=======\n{}\n>>>>>>> REMOTE",
-            local, remote
-        );
+    /// 合并 TOML 文件（转换为 JSON 后合并）
+    fn merge_toml(&self, local: &str, remote: &str, base: Option<&str>) -> Result<MergeResult> {
+        let parse = |content: &str, side: &str| -> Result<JsonValue> {
+            let value: toml::Value =
+                toml::from_str(content).with_context(|| format!("无法解析{} TOML", side))?;
+            serde_json::to_value(value).with_context(|| format!("无法转换{} TOML", side))
+        };
+
+        let local_value = parse(local, "本地")?;
+        let remote_value = parse(remote, "远程")?;
+
+        let mut conflicts = Vec::new();
+        let merged = if let Some(base_str) = base {
+            let base_value = parse(base_str, "基线")?;
+            self.merge_json_values(
+                &base_value,
+                &local_value,
+                &remote_value,
+                "$",
+                &mut conflicts,
+            )?
+        } else {
+            self.merge_json_values_without_base(&local_value, &remote_value)?
+        };
+
+        if let Some(conflict) = self.structured_conflict(local, remote, base, &conflicts) {
+            return Ok(conflict);
+        }
+
+        // 转回 TOML 值（保留日期时间类型）后输出
+        let merged: toml::Value = serde_json::from_value(merged).context("无法转换合并的 TOML")?;
+        let merged_str = toml::to_string_pretty(&merged).context("无法序列化合并的 TOML")?;
+
+        Ok(MergeResult::Merged(merged_str))
+    }
+
+    /// 按配置的标记风格创建冲突标记（有基线且风格包含基线段时输出 `|||||||` 段）
+    fn create_conflict_marker(&self, local: &str, remote: &str, base: Option<&str>) -> MergeResult {
+        let (local_label, base_label, remote_label) = self.marker_style.labels();
+
+        let mut conflict = format!("<<<<<<< {}\n{}\n", local_label, local);
+        if let (Some(base_label), Some(base)) = (base_label, base) {
+            conflict.push_str(&format!("||||||| {}\n{}\n", base_label, base));
+        }
+        conflict.push_str(&format!("=======\n{}\n>>>>>>> {}", remote, remote_label));
 
         MergeResult::Conflict(conflict)
     }
@@ -344,11 +902,67 @@ impl ConflictResolver {
         match self.default_strategy {
             ResolutionStrategy::KeepLocal => MergeResult::Merged(local_content.to_string()),
             ResolutionStrategy::KeepRemote => MergeResult::Merged(remote_content.to_string()),
-            _ => self.create_conflict_marker(local_content, remote_content),
+            ResolutionStrategy::KeepBoth => MergeResult::KeepBoth,
+            _ => self.create_conflict_marker(local_content, remote_content, None),
+        }
+    }
+}
+
+/// 比较本地文件与远程版本的修改时间，本地不早于远程时返回 true
+///
+/// 远程时间未知或本地文件的修改时间无法读取时返回 None。
+pub fn local_is_newer(local_path: &Path, remote_modified: Option<DateTime<Utc>>) -> Option<bool> {
+    let remote_modified = remote_modified?;
+    let local_modified = std::fs::metadata(local_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    Some(DateTime::<Utc>::from(local_modified) >= remote_modified)
+}
+
+/// 检查内容是否仍包含未处理的冲突标记
+pub fn contains_conflict_markers(content: &str) -> bool {
+    content.lines().any(|line| {
+        line.starts_with("<<<<<<< ")
+            || line.starts_with("||||||| ")
+            || line.starts_with(">>>>>>> ")
+            || line == "======="
+    })
+}
+
+/// 获取数组元素的标识值
+fn element_key(item: &JsonValue, key: &str) -> Option<String> {
+    match item.get(key)? {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// 按标识值索引数组元素
+fn index_by_key<'a>(items: &'a [JsonValue], key: &str) -> HashMap<String, &'a JsonValue> {
+    items
+        .iter()
+        .filter_map(|item| element_key(item, key).map(|id| (id, item)))
+        .collect()
+}
+
+/// 合并后的元素顺序：先本地顺序，再追加远程独有的元素
+fn merge_order(local: &[JsonValue], remote: &[JsonValue], key: &str) -> Vec<String> {
+    let mut order: Vec<String> = local
+        .iter()
+        .filter_map(|item| element_key(item, key))
+        .collect();
+    for id in remote.iter().filter_map(|item| element_key(item, key)) {
+        if !order.contains(&id) {
+            order.push(id);
         }
     }
+    order
 }
 
+/// 内容嗅探时读取的最大字节数
+const SNIFF_LEN: usize = 8192;
+
 /// 文件类型检测器
 pub struct FileTypeDetector;
 
@@ -359,12 +973,28 @@ impl FileTypeDetector {
     }
 
     /// 检查是否是文本文件
+    ///
+    /// 已知二进制扩展名直接判定；其余情况读取文件开头的内容判断，
+    /// 文件无法读取时回退到按扩展名判断。
     pub fn is_text_file(path: &Path) -> bool {
-        crate::rules::is_text_file(path)
+        if Self::has_binary_extension(path) {
+            return false;
+        }
+        match Self::sniff_binary(path) {
+            Some(binary) => !binary,
+            None => crate::rules::is_text_file(path),
+        }
     }
 
     /// 检查是否是二进制文件
+    ///
+    /// 文本扩展名的文件也会检查内容，避免包含二进制数据的 `.md` 被当作文本合并。
     pub fn is_binary_file(path: &Path) -> bool {
+        Self::has_binary_extension(path) || Self::sniff_binary(path).unwrap_or(false)
+    }
+
+    /// 扩展名是否属于已知的二进制格式
+    fn has_binary_extension(path: &Path) -> bool {
         if let Some(ext) = path.extension() {
             let ext_str = ext.to_string_lossy().to_lowercase();
             matches!(
@@ -376,6 +1006,28 @@ impl FileTypeDetector {
         }
     }
 
+    /// 读取文件开头的内容判断是否为二进制（无法读取时返回 None）
+    fn sniff_binary(path: &Path) -> Option<bool> {
+        use std::io::Read;
+
+        let file = std::fs::File::open(path).ok()?;
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        file.take(SNIFF_LEN as u64).read_to_end(&mut head).ok()?;
+        Some(Self::is_binary_content(&head))
+    }
+
+    /// 包含 NUL 字节或非法 UTF-8 的内容视为二进制
+    fn is_binary_content(head: &[u8]) -> bool {
+        if head.contains(&0) {
+            return true;
+        }
+        match std::str::from_utf8(head) {
+            Ok(_) => false,
+            // 截断在多字节字符中间不算非法
+            Err(e) => e.error_len().is_some(),
+        }
+    }
+
     /// 检查是否是配置文件
     pub fn is_config_file(path: &Path) -> bool {
         crate::rules::is_config_file(path)
@@ -389,7 +1041,7 @@ mod tests {
     #[test]
     fn test_create_conflict_marker() {
         let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true);
-        let result = resolver.create_conflict_marker("local content", "remote content");
+        let result = resolver.create_conflict_marker("local content", "remote content", None);
 
         match result {
             MergeResult::Conflict(markers) => {
@@ -439,6 +1091,390 @@ mod tests {
         }
     }
 
+    fn key_merge_resolver() -> ConflictResolver {
+        ConflictResolver::new(ResolutionStrategy::Manual, true, true).with_array_merge(
+            ArrayMergeMode::ByKey(vec!["id".to_string(), "name".to_string()]),
+        )
+    }
+
+    fn merged_json(result: Result<MergeResult>) -> JsonValue {
+        match result {
+            Ok(MergeResult::Merged(merged)) => serde_json::from_str(&merged).unwrap(),
+            other => panic!("Expected Merged result, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_merge_json_array_by_key_additions() {
+        let resolver = key_merge_resolver();
+
+        let base = r#"{"servers": [{"name": "a", "cmd": "a"}]}"#;
+        let local = r#"{"servers": [{"name": "a", "cmd": "a"}, {"name": "local", "cmd": "l"}]}"#;
+        let remote = r#"{"servers": [{"name": "a", "cmd": "a"}, {"name": "remote", "cmd": "r"}]}"#;
+
+        let merged = merged_json(resolver.merge_json(local, remote, Some(base)));
+        let names: Vec<_> = merged["servers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "local", "remote"]);
+
+        // 默认模式仍然直接使用远程数组
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true);
+        let merged = merged_json(resolver.merge_json(local, remote, Some(base)));
+        assert_eq!(merged["servers"].as_array().unwrap().len(), 2);
+        assert_eq!(merged["servers"][1]["name"], "remote");
+    }
+
+    #[test]
+    fn test_merge_json_array_by_key_removals() {
+        let resolver = key_merge_resolver();
+
+        let base = r#"[{"id": 1, "v": "a"}, {"id": 2, "v": "b"}, {"id": 3, "v": "c"}]"#;
+        // 本地删除 1，远程删除 2 并修改 3
+        let local = r#"[{"id": 2, "v": "b"}, {"id": 3, "v": "c"}]"#;
+        let remote = r#"[{"id": 1, "v": "a"}, {"id": 3, "v": "c2"}]"#;
+
+        let merged = merged_json(resolver.merge_json(local, remote, Some(base)));
+        assert_eq!(merged, serde_json::json!([{"id": 3, "v": "c2"}]));
+    }
+
+    #[test]
+    fn test_merge_json_array_by_key_edits() {
+        let resolver = key_merge_resolver();
+
+        let base = r#"[{"name": "a", "cmd": "x", "args": ["1"]}]"#;
+        let local = r#"[{"name": "a", "cmd": "local", "args": ["1"]}]"#;
+        let remote = r#"[{"name": "a", "cmd": "x", "args": ["2"]}]"#;
+
+        // 不同字段的修改都保留
+        let merged = merged_json(resolver.merge_json(local, remote, Some(base)));
+        assert_eq!(
+            merged,
+            serde_json::json!([{"name": "a", "cmd": "local", "args": ["2"]}])
+        );
+
+        // 同一字段修改不一致时产生冲突
+        let remote = r#"[{"name": "a", "cmd": "remote", "args": ["1"]}]"#;
+        let result = resolver.merge_json(local, remote, Some(base)).unwrap();
+        assert!(matches!(result, MergeResult::Conflict(_)));
+    }
+
+    #[test]
+    fn test_merge_json_array_by_key_without_base() {
+        let resolver = key_merge_resolver();
+
+        let local = r#"[{"name": "a", "cmd": "x"}, {"name": "b"}]"#;
+        let remote = r#"[{"name": "a", "env": "y"}, {"name": "c"}]"#;
+
+        let merged = merged_json(resolver.merge_json(local, remote, None));
+        assert_eq!(
+            merged,
+            serde_json::json!([
+                {"name": "a", "cmd": "x", "env": "y"},
+                {"name": "b"},
+                {"name": "c"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_strategy_names_round_trip() {
+        for strategy in ResolutionStrategy::ALL {
+            assert_eq!(ResolutionStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(ResolutionStrategy::parse("merge_everything"), None);
+    }
+
+    #[test]
+    fn test_keep_newer_uses_modification_time() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("run.log");
+        std::fs::write(&path, "local").unwrap();
+        let resolver = ConflictResolver::new(ResolutionStrategy::KeepNewer, true, true);
+        let resolve = |remote_modified| {
+            resolver
+                .resolve_with_remote_time(
+                    &path,
+                    "local",
+                    "remote",
+                    None,
+                    ConflictType::ModifyModify,
+                    remote_modified,
+                )
+                .unwrap()
+        };
+
+        let older = DateTime::from_timestamp(1, 0);
+        assert!(matches!(resolve(older), MergeResult::Merged(ref c) if c == "local"));
+
+        let newer = Some(Utc::now() + chrono::Duration::days(1));
+        assert!(matches!(resolve(newer), MergeResult::Merged(ref c) if c == "remote"));
+
+        // 缺少远程修改时间时无法比较
+        assert!(matches!(resolve(None), MergeResult::Conflict(_)));
+    }
+
+    #[test]
+    fn test_per_type_strategy() {
+        let resolver =
+            ConflictResolver::new(ResolutionStrategy::KeepLocal, true, true).with_type_strategies(
+                HashMap::from([("json".to_string(), ResolutionStrategy::KeepRemote)]),
+            );
+
+        assert_eq!(
+            resolver.strategy_for(Path::new("settings.json")),
+            ResolutionStrategy::KeepRemote
+        );
+        assert_eq!(
+            resolver.strategy_for(Path::new("run.log")),
+            ResolutionStrategy::KeepLocal
+        );
+
+        // JSON 使用类型专用策略
+        let result = resolver
+            .resolve(
+                Path::new("settings.json"),
+                r#"{"a": 1}"#,
+                r#"{"a": 2}"#,
+                None,
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+        assert!(matches!(result, MergeResult::Merged(ref c) if c == r#"{"a": 2}"#));
+
+        // 未配置的类型回退到全局默认策略
+        let result = resolver
+            .resolve(
+                Path::new("run.log"),
+                "local",
+                "remote",
+                None,
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+        assert!(matches!(result, MergeResult::Merged(ref c) if c == "local"));
+    }
+
+    #[test]
+    fn test_merge_toml() {
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true);
+
+        let base = "[server]\naddress = \"a\"\nport = 1\n\n[sync]\ninterval = 60\n";
+        let local = "[server]\naddress = \"b\"\nport = 1\n\n[sync]\ninterval = 60\n";
+        let remote = "[server]\naddress = \"a\"\nport = 1\n\n[sync]\ninterval = 30\nupdated = 2024-01-01T00:00:00Z\n";
+
+        let result = resolver
+            .resolve(
+                Path::new("config.toml"),
+                local,
+                remote,
+                Some(base),
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+
+        let MergeResult::Merged(merged) = result else {
+            panic!("Expected Merged result, got {:?}", result);
+        };
+        let merged: toml::Value = toml::from_str(&merged).unwrap();
+        assert_eq!(merged["server"]["address"].as_str(), Some("b"));
+        assert_eq!(merged["sync"]["interval"].as_integer(), Some(30));
+        assert!(merged["sync"]["updated"].is_datetime());
+
+        // 按标识字段合并时，两端修改同一字段为不同值会产生冲突
+        let remote = "[server]\naddress = \"c\"\nport = 1\n\n[sync]\ninterval = 60\n";
+        let result = key_merge_resolver()
+            .resolve(
+                Path::new("config.toml"),
+                local,
+                remote,
+                Some(base),
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+        assert!(matches!(result, MergeResult::Conflict(_)));
+    }
+
+    /// 测试用驱动：把两端内容拼接，并标记来源
+    struct ConcatDriver(&'static str);
+
+    impl MergeDriver for ConcatDriver {
+        fn can_handle(&self, file_type: &str) -> bool {
+            file_type == "json" || file_type == "csv"
+        }
+
+        fn merge(
+            &self,
+            _resolver: &ConflictResolver,
+            local: &str,
+            remote: &str,
+            _base: Option<&str>,
+        ) -> Result<MergeResult> {
+            Ok(MergeResult::Merged(format!(
+                "{}:{}{}",
+                self.0, local, remote
+            )))
+        }
+    }
+
+    #[test]
+    fn test_merge_driver_selection_order() {
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true);
+        assert!(resolver.driver_for("toml").is_some());
+        assert!(resolver.driver_for("csv").is_none());
+
+        // 自定义驱动按注册顺序优先于内置驱动
+        let resolver = resolver
+            .with_driver(Box::new(ConcatDriver("first")))
+            .with_driver(Box::new(ConcatDriver("second")));
+
+        let merge = |path: &str| {
+            resolver
+                .resolve(Path::new(path), "l", "r", None, ConflictType::ModifyModify)
+                .unwrap()
+        };
+        assert!(matches!(merge("data.json"), MergeResult::Merged(ref c) if c == "first:lr"));
+        assert!(matches!(merge("data.csv"), MergeResult::Merged(ref c) if c == "first:lr"));
+
+        // 没有驱动的类型回退到默认策略
+        assert!(matches!(merge("data.bin"), MergeResult::Conflict(_)));
+    }
+
+    #[test]
+    fn test_auto_merged_json_is_audited() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let audit_log = AuditLog::new(temp_dir.path().join("audit.jsonl"));
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true)
+            .with_audit_log(audit_log.clone());
+
+        let local = r#"{"theme": "dark", "fontSize": 12}"#;
+        let remote = r#"{"theme": "light", "fontSize": 14}"#;
+        let base = r#"{"theme": "light", "fontSize": 12}"#;
+        let result = resolver
+            .resolve(
+                Path::new("settings.json"),
+                local,
+                remote,
+                Some(base),
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+        let MergeResult::Merged(merged) = result else {
+            panic!("Expected Merged result");
+        };
+
+        // 无法自动合并的冲突不记录
+        let result = resolver
+            .resolve(
+                Path::new("image.png"),
+                "a",
+                "b",
+                None,
+                ConflictType::BinaryConflict,
+            )
+            .unwrap();
+        assert!(matches!(result, MergeResult::Conflict(_)));
+
+        let entries = audit_log.recent(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = &entries[0];
+        assert_eq!(entry.path, Path::new("settings.json"));
+        assert_eq!(entry.strategy, "auto_merge");
+        assert_eq!(
+            entry.local_hash,
+            TransferManager::calculate_hash(local.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            entry.remote_hash,
+            TransferManager::calculate_hash(remote.as_bytes()).unwrap()
+        );
+        assert_eq!(
+            entry.merged_hash,
+            TransferManager::calculate_hash(merged.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_conflict_marker_styles() {
+        let markers = |style: ConflictMarkerStyle, base: Option<&str>| {
+            let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true)
+                .with_marker_style(style);
+            match resolver.create_conflict_marker("local", "remote", base) {
+                MergeResult::Conflict(markers) => markers,
+                _ => panic!("Expected Conflict result"),
+            }
+        };
+
+        // git2 风格忽略基线
+        assert_eq!(
+            markers(ConflictMarkerStyle::Git2, Some("base")),
+            "<<<<<<< LOCAL\nlocal\n=======\nremote\n>>>>>>> REMOTE"
+        );
+
+        // diff3 风格在有基线时输出基线段
+        let diff3 = markers(ConflictMarkerStyle::Diff3, Some("base"));
+        assert_eq!(
+            diff3,
+            "<<<<<<< LOCAL\nlocal\n||||||| BASE\nbase\n=======\nremote\n>>>>>>> REMOTE"
+        );
+        assert!(contains_conflict_markers(&diff3));
+        assert_eq!(
+            markers(ConflictMarkerStyle::Diff3, None),
+            "<<<<<<< LOCAL\nlocal\n=======\nremote\n>>>>>>> REMOTE"
+        );
+
+        // 自定义标签
+        let custom = |base: Option<&str>| ConflictMarkerStyle::Custom {
+            local: "mine".to_string(),
+            base: base.map(str::to_string),
+            remote: "theirs".to_string(),
+        };
+        assert_eq!(
+            markers(custom(Some("ancestor")), Some("base")),
+            "<<<<<<< mine\nlocal\n||||||| ancestor\nbase\n=======\nremote\n>>>>>>> theirs"
+        );
+        assert_eq!(
+            markers(custom(None), Some("base")),
+            "<<<<<<< mine\nlocal\n=======\nremote\n>>>>>>> theirs"
+        );
+    }
+
+    #[test]
+    fn test_diff3_text_conflict_includes_base() {
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true)
+            .with_marker_style(ConflictMarkerStyle::Diff3);
+        let result = resolver
+            .resolve(
+                Path::new("CLAUDE.md"),
+                "local edit",
+                "remote edit",
+                Some("original"),
+                ConflictType::ModifyModify,
+            )
+            .unwrap();
+
+        let MergeResult::Conflict(markers) = result else {
+            panic!("Expected Conflict result");
+        };
+        assert_eq!(
+            markers,
+            "<<<<<<< LOCAL\nlocal edit\n||||||| BASE\noriginal\n=======\nremote edit\n>>>>>>> REMOTE"
+        );
+    }
+
+    #[test]
+    fn test_contains_conflict_markers() {
+        let resolver = ConflictResolver::new(ResolutionStrategy::Manual, true, true);
+        let MergeResult::Conflict(markers) = resolver.create_conflict_marker("a", "b", None) else {
+            panic!("Expected Conflict result");
+        };
+        assert!(contains_conflict_markers(&markers));
+        assert!(!contains_conflict_markers("# 标题\n\n=== 分隔 ===\n"));
+    }
+
     #[test]
     fn test_file_type_detection() {
         assert!(FileTypeDetector::is_text_file(Path::new("test.md")));
@@ -449,4 +1485,41 @@ mod tests {
         assert!(FileTypeDetector::is_binary_file(Path::new("test.png")));
         assert!(!FileTypeDetector::is_binary_file(Path::new("test.md")));
     }
+
+    #[test]
+    fn test_extensionless_text_file_detected_by_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("Makefile");
+        std::fs::write(&path, "all:\n\techo \"构建\"\n").unwrap();
+
+        assert!(FileTypeDetector::is_text_file(&path));
+        assert!(!FileTypeDetector::is_binary_file(&path));
+    }
+
+    #[test]
+    fn test_extensionless_binary_file_detected_by_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("blob");
+        std::fs::write(&path, [0x7f, b'E', b'L', b'F', 0x02, 0x01, 0xff, 0xfe]).unwrap();
+
+        assert!(!FileTypeDetector::is_text_file(&path));
+        assert!(FileTypeDetector::is_binary_file(&path));
+    }
+
+    #[test]
+    fn test_text_extension_with_nul_bytes_is_binary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        std::fs::write(&path, b"hello\0world").unwrap();
+
+        assert!(!FileTypeDetector::is_text_file(&path));
+        assert!(FileTypeDetector::is_binary_file(&path));
+    }
+
+    #[test]
+    fn test_truncated_utf8_sequence_is_text() {
+        let content = "中文".as_bytes();
+        assert!(!FileTypeDetector::is_binary_content(&content[..4]));
+        assert!(FileTypeDetector::is_binary_content(&[b'a', 0xff, b'b']));
+    }
 }
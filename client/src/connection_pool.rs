@@ -1,9 +1,13 @@
+use crate::config::ServerConfig;
 use crate::error::ClientError;
+use crate::grpc_client::{load_tls_config, Keepalive};
+use crate::monitoring::MonitoringManager;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, RwLock, Semaphore};
-use tonic::transport::Channel;
+use tonic::transport::{Channel, ClientTlsConfig};
 use tracing::{debug, info};
 
 /// 连接池配置
@@ -32,6 +36,12 @@ pub struct PoolConfig {
 
     /// 健康检查间隔（秒）
     pub health_check_interval_secs: u64,
+
+    /// TLS 配置（为空时使用明文连接）
+    pub tls_config: Option<ClientTlsConfig>,
+
+    /// HTTP/2 保活设置（为空时不发送 PING）
+    pub keepalive: Option<Keepalive>,
 }
 
 impl Default for PoolConfig {
@@ -45,10 +55,27 @@ impl Default for PoolConfig {
             acquire_timeout_secs: 5,
             enable_health_check: true,
             health_check_interval_secs: 60,
+            tls_config: None,
+            keepalive: None,
         }
     }
 }
 
+impl PoolConfig {
+    /// 按服务器配置创建连接池配置（TLS、保活和连接超时与 GrpcClient 一致）
+    pub fn from_server_config(server: &ServerConfig) -> Result<Self, ClientError> {
+        let tls_config =
+            load_tls_config(server).map_err(|e| ClientError::config(format!("{:#}", e)))?;
+
+        Ok(Self {
+            connection_timeout_secs: server.connection_timeout,
+            tls_config,
+            keepalive: Keepalive::from_config(server),
+            ..Self::default()
+        })
+    }
+}
+
 /// 连接包装器
 struct ConnectionWrapper {
     /// gRPC 通道
@@ -127,6 +154,15 @@ pub struct ConnectionPool {
 
     /// 池是否已关闭
     is_shutdown: Arc<RwLock<bool>>,
+
+    /// 正在等待连接的请求数
+    waiting: Arc<AtomicUsize>,
+
+    /// 下一个连接 ID
+    next_conn_id: Arc<AtomicU64>,
+
+    /// 健康检查时记录池指标的监控管理器
+    monitoring: Option<MonitoringManager>,
 }
 
 impl ConnectionPool {
@@ -141,9 +177,23 @@ impl ConnectionPool {
             active_connections: Arc::new(RwLock::new(HashMap::new())),
             semaphore,
             is_shutdown: Arc::new(RwLock::new(false)),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            next_conn_id: Arc::new(AtomicU64::new(1)),
+            monitoring: None,
         }
     }
 
+    /// 在健康检查循环中把池状态记录到监控管理器
+    pub fn with_monitoring(mut self, monitoring: MonitoringManager) -> Self {
+        self.monitoring = Some(monitoring);
+        self
+    }
+
+    /// 分配新的连接 ID
+    fn new_conn_id(&self) -> String {
+        format!("conn_{}", self.next_conn_id.fetch_add(1, Ordering::Relaxed))
+    }
+
     /// 获取连接
     pub async fn acquire(&self) -> Result<PooledConnection, ClientError> {
         // 检查池是否已关闭
@@ -152,13 +202,16 @@ impl ConnectionPool {
         }
 
         // 等待信号量（限制并发连接数）
-        let _permit = tokio::time::timeout(
+        self.waiting.fetch_add(1, Ordering::Relaxed);
+        let permit = tokio::time::timeout(
             Duration::from_secs(self.config.acquire_timeout_secs),
             self.semaphore.acquire(),
         )
-        .await
-        .map_err(|_| ClientError::timeout("获取连接", self.config.acquire_timeout_secs))?
-        .map_err(|_| ClientError::internal("信号量关闭", None))?;
+        .await;
+        self.waiting.fetch_sub(1, Ordering::Relaxed);
+        let _permit = permit
+            .map_err(|_| ClientError::timeout("获取连接", self.config.acquire_timeout_secs))?
+            .map_err(|_| ClientError::internal("信号量关闭", None))?;
 
         // 尝试从空闲连接中获取
         {
@@ -189,7 +242,7 @@ impl ConnectionPool {
             if let Some(mut conn) = idle.pop() {
                 conn.mark_in_use();
 
-                let conn_id = format!("conn_{}", conn.use_count);
+                let conn_id = self.new_conn_id();
                 self.active_connections
                     .write()
                     .await
@@ -217,7 +270,7 @@ impl ConnectionPool {
         let mut wrapper = ConnectionWrapper::new(channel);
         wrapper.mark_in_use();
 
-        let conn_id = format!("conn_{}", wrapper.use_count);
+        let conn_id = self.new_conn_id();
         self.active_connections
             .write()
             .await
@@ -263,8 +316,19 @@ impl ConnectionPool {
 
     /// 创建新连接
     async fn create_connection(&self) -> Result<Channel, ClientError> {
-        Channel::from_shared(self.server_address.clone())
-            .map_err(|e| ClientError::network("无效的服务器地址", Some(Box::new(e))))?
+        let mut endpoint = Channel::from_shared(self.server_address.clone())
+            .map_err(|e| ClientError::network("无效的服务器地址", Some(Box::new(e))))?;
+
+        if let Some(tls_config) = &self.config.tls_config {
+            endpoint = endpoint
+                .tls_config(tls_config.clone())
+                .map_err(|e| ClientError::config(format!("TLS 配置无效: {}", e)))?;
+        }
+        if let Some(keepalive) = &self.config.keepalive {
+            endpoint = keepalive.apply(endpoint);
+        }
+
+        endpoint
             .timeout(Duration::from_secs(self.config.connection_timeout_secs))
             .connect()
             .await
@@ -284,6 +348,11 @@ impl ConnectionPool {
         info!("连接池已关闭");
     }
 
+    /// 连接池是否已关闭
+    pub async fn is_shutdown(&self) -> bool {
+        *self.is_shutdown.read().await
+    }
+
     /// 获取池统计信息
     pub async fn stats(&self) -> PoolStats {
         let idle_count = self.idle_connections.lock().await.len();
@@ -294,7 +363,26 @@ impl ConnectionPool {
             idle_connections: idle_count,
             active_connections: active_count,
             max_connections: self.config.max_connections,
-            waiting_for_connection: self.semaphore.available_permits(),
+            waiting_for_connection: self.waiting.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 记录池状态指标（pool_active、pool_idle、pool_waiting，带服务器地址标签）
+    pub async fn record_stats(&self) {
+        let Some(monitoring) = &self.monitoring else {
+            return;
+        };
+
+        let stats = self.stats().await;
+        let tags = vec![("server".to_string(), self.server_address.clone())];
+        for (name, value) in [
+            ("pool_active", stats.active_connections),
+            ("pool_idle", stats.idle_connections),
+            ("pool_waiting", stats.waiting_for_connection),
+        ] {
+            monitoring
+                .record_gauge(name, value as f64, tags.clone())
+                .await;
         }
     }
 
@@ -327,6 +415,9 @@ impl ConnectionPool {
                 if before_count != after_count {
                     debug!("清理了 {} 个过期连接", before_count - after_count);
                 }
+                drop(idle);
+
+                self.record_stats().await;
             }
         })
     }
@@ -341,6 +432,9 @@ impl Clone for ConnectionPool {
             active_connections: Arc::clone(&self.active_connections),
             semaphore: Arc::clone(&self.semaphore),
             is_shutdown: Arc::clone(&self.is_shutdown),
+            waiting: Arc::clone(&self.waiting),
+            next_conn_id: Arc::clone(&self.next_conn_id),
+            monitoring: self.monitoring.clone(),
         }
     }
 }
@@ -413,6 +507,9 @@ pub struct PoolStats {
 /// 连接池管理器（单例模式）
 pub struct ConnectionPoolManager {
     pools: Arc<RwLock<HashMap<String, Arc<ConnectionPool>>>>,
+
+    /// 新建的池共享的监控管理器
+    monitoring: Option<MonitoringManager>,
 }
 
 impl ConnectionPoolManager {
@@ -420,9 +517,16 @@ impl ConnectionPoolManager {
     pub fn new() -> Self {
         Self {
             pools: Arc::new(RwLock::new(HashMap::new())),
+            monitoring: None,
         }
     }
 
+    /// 新建的池在健康检查时记录指标到 monitoring
+    pub fn with_monitoring(mut self, monitoring: MonitoringManager) -> Self {
+        self.monitoring = Some(monitoring);
+        self
+    }
+
     /// 获取或创建连接池
     pub async fn get_or_create_pool(
         &self,
@@ -446,7 +550,11 @@ impl ConnectionPoolManager {
         }
 
         // 创建新池
-        let pool = Arc::new(ConnectionPool::new(server_address.clone(), config));
+        let mut pool = ConnectionPool::new(server_address.clone(), config);
+        if let Some(monitoring) = &self.monitoring {
+            pool = pool.with_monitoring(monitoring.clone());
+        }
+        let pool = Arc::new(pool);
 
         // 启动健康检查
         pool.clone().spawn_health_check();
@@ -558,6 +666,54 @@ mod tests {
         assert!(wrapper.is_expired(max_idle_time, max_lifetime));
     }
 
+    #[tokio::test]
+    async fn test_health_check_records_pool_gauges() {
+        let monitoring = MonitoringManager::new(100, 1000);
+        let config = PoolConfig {
+            health_check_interval_secs: 3600,
+            ..PoolConfig::default()
+        };
+        let pool = Arc::new(
+            ConnectionPool::new("http://localhost:50051".to_string(), config)
+                .with_monitoring(monitoring.clone()),
+        );
+
+        // 预置空闲连接（延迟连接，不需要服务器），再取出其中两个
+        for _ in 0..3 {
+            pool.idle_connections
+                .lock()
+                .await
+                .push(ConnectionWrapper::new(
+                    Channel::from_static("http://localhost:50051").connect_lazy(),
+                ));
+        }
+        let first = pool.acquire().await.unwrap();
+        let second = pool.acquire().await.unwrap();
+
+        // 健康检查循环的第一次检查立即执行
+        let handle = pool.clone().spawn_health_check();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.abort();
+
+        let gauge = |name: &'static str| {
+            let monitoring = monitoring.clone();
+            async move {
+                let metrics = monitoring.get_metrics_by_name(name).await;
+                let latest = metrics.last().expect("gauge not recorded").clone();
+                assert_eq!(
+                    latest.tags,
+                    vec![("server".to_string(), "http://localhost:50051".to_string())]
+                );
+                latest.value
+            }
+        };
+        assert_eq!(gauge("pool_active").await, 2.0);
+        assert_eq!(gauge("pool_idle").await, 1.0);
+        assert_eq!(gauge("pool_waiting").await, 0.0);
+
+        drop((first, second));
+    }
+
     #[tokio::test]
     async fn test_pool_manager() {
         let manager = ConnectionPoolManager::new();
@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::Path;
+
+/// BLAKE3 哈希的前缀（SHA-256 哈希不带前缀，与旧版本记录的哈希兼容）
+pub const BLAKE3_PREFIX: &str = "blake3:";
+
+/// 流式计算哈希时的读取缓冲区大小
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 内容哈希算法
+///
+/// 哈希字符串自带算法标记：`blake3:<hex>` 为 BLAKE3，没有前缀的为 SHA-256。
+/// 客户端和服务器都按哈希自身的标记校验内容，切换算法后旧哈希仍然有效。
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// SHA-256
+    #[default]
+    Sha256,
+    /// BLAKE3（大文件明显更快）
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// 哈希字符串使用的算法
+    pub fn of(hash: &str) -> Self {
+        if hash.starts_with(BLAKE3_PREFIX) {
+            Self::Blake3
+        } else {
+            Self::Sha256
+        }
+    }
+
+    /// 创建增量哈希计算器
+    pub fn hasher(self) -> ContentHasher {
+        match self {
+            Self::Sha256 => ContentHasher::Sha256(Sha256::new()),
+            Self::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// 计算内容哈希（带算法标记）
+    pub fn hash(self, content: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(content);
+        hasher.finalize()
+    }
+
+    /// 逐块读取文件并计算哈希
+    pub fn hash_file(self, path: &Path) -> Result<String> {
+        let mut file =
+            std::fs::File::open(path).with_context(|| format!("无法读取文件: {:?}", path))?;
+        let mut hasher = self.hasher();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .with_context(|| format!("无法读取文件: {:?}", path))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(hasher.finalize())
+    }
+}
+
+/// 增量哈希计算器（分块上传时逐块累计）
+pub enum ContentHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl ContentHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// 结束计算，返回带算法标记的哈希
+    pub fn finalize(self) -> String {
+        match self {
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Blake3(hasher) => format!("{}{}", BLAKE3_PREFIX, hasher.finalize().to_hex()),
+        }
+    }
+}
+
+/// 按期望哈希自身的算法计算内容哈希（用于校验和比较）
+pub fn hash_like(expected_hash: &str, content: &[u8]) -> String {
+    HashAlgorithm::of(expected_hash).hash(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithms_round_trip() {
+        let content = b"# CLAUDE.md\n\nAlways run the tests.\n";
+
+        let sha256 = HashAlgorithm::Sha256.hash(content);
+        assert_eq!(sha256.len(), 64);
+        assert_eq!(HashAlgorithm::of(&sha256), HashAlgorithm::Sha256);
+
+        let blake3 = HashAlgorithm::Blake3.hash(content);
+        assert!(blake3.starts_with(BLAKE3_PREFIX));
+        assert_eq!(blake3.len(), BLAKE3_PREFIX.len() + 64);
+        assert_eq!(HashAlgorithm::of(&blake3), HashAlgorithm::Blake3);
+
+        // 按哈希自身的算法重新计算得到相同结果
+        assert_eq!(hash_like(&sha256, content), sha256);
+        assert_eq!(hash_like(&blake3, content), blake3);
+        assert_ne!(hash_like(&blake3, b"other"), blake3);
+
+        // 分块累计与一次计算结果一致
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let mut hasher = algorithm.hasher();
+            for chunk in content.chunks(7) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), algorithm.hash(content));
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("CLAUDE.md");
+        std::fs::write(&path, content).unwrap();
+        assert_eq!(HashAlgorithm::Blake3.hash_file(&path).unwrap(), blake3);
+        assert_eq!(HashAlgorithm::Sha256.hash_file(&path).unwrap(), sha256);
+    }
+}
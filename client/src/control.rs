@@ -0,0 +1,300 @@
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::transfer::TransferProgress;
+
+/// 守护进程控制命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// 暂停处理文件事件
+    Pause,
+    /// 恢复处理并补发暂停期间的事件
+    Resume,
+    /// 查询当前状态
+    Status,
+    /// 订阅传输进度（回复状态后保持连接，每行推送一个 JSON 事件）
+    Progress,
+}
+
+impl ControlCommand {
+    /// 从文本解析命令
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "pause" => Some(Self::Pause),
+            "resume" => Some(Self::Resume),
+            "status" => Some(Self::Status),
+            "progress" => Some(Self::Progress),
+            _ => None,
+        }
+    }
+
+    /// 转换为文本
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::Status => "status",
+            Self::Progress => "progress",
+        }
+    }
+}
+
+/// 同步暂停开关（在同步引擎和控制端口之间共享）
+#[derive(Debug, Clone, Default)]
+pub struct SyncControl {
+    paused: Arc<AtomicBool>,
+    resumed: Arc<Notify>,
+}
+
+impl SyncControl {
+    /// 创建新的暂停开关（初始为运行状态）
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 暂停同步
+    pub fn pause(&self) {
+        if !self.paused.swap(true, Ordering::SeqCst) {
+            info!("同步已暂停");
+        }
+    }
+
+    /// 恢复同步
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::SeqCst) {
+            info!("同步已恢复");
+            self.resumed.notify_waiters();
+        }
+    }
+
+    /// 是否已暂停
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// 等待恢复通知
+    pub async fn resumed(&self) {
+        let notified = self.resumed.notified();
+        if !self.is_paused() {
+            return;
+        }
+        notified.await;
+    }
+
+    /// 执行控制命令，返回状态描述
+    pub fn apply(&self, command: ControlCommand) -> &'static str {
+        match command {
+            ControlCommand::Pause => self.pause(),
+            ControlCommand::Resume => self.resume(),
+            ControlCommand::Status | ControlCommand::Progress => {}
+        }
+
+        if self.is_paused() {
+            "paused"
+        } else {
+            "running"
+        }
+    }
+}
+
+/// 在本地控制端口上接受控制命令（每个连接一行命令，回复一行结果）
+///
+/// `progress` 为同步引擎的传输进度订阅，每个 progress 连接各自重新订阅。
+pub async fn spawn_control_server(
+    addr: &str,
+    control: SyncControl,
+    progress: broadcast::Receiver<TransferProgress>,
+) -> Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("无法绑定控制端口: {}", addr))?;
+    let local_addr = listener.local_addr()?;
+
+    info!("✓ 控制端口已启动: {}", local_addr);
+
+    let handle = tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!("接受控制连接失败: {}", e);
+                    continue;
+                }
+            };
+
+            debug!("控制连接: {}", peer);
+            let control = control.clone();
+            let progress = progress.resubscribe();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(stream, &control, progress).await {
+                    warn!("处理控制命令失败: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok((local_addr, handle))
+}
+
+/// 处理单个控制连接
+async fn handle_connection(
+    stream: TcpStream,
+    control: &SyncControl,
+    progress: broadcast::Receiver<TransferProgress>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader).read_line(&mut line).await?;
+
+    let command = ControlCommand::parse(&line);
+    let reply = match command {
+        Some(command) => format!("ok {}\n", control.apply(command)),
+        None => format!("error 未知命令: {}\n", line.trim()),
+    };
+
+    writer.write_all(reply.as_bytes()).await?;
+    if command == Some(ControlCommand::Progress) {
+        stream_progress(writer, progress).await?;
+    }
+    Ok(())
+}
+
+/// 持续推送传输进度，连接断开或同步引擎关闭时返回
+async fn stream_progress(
+    mut writer: OwnedWriteHalf,
+    mut progress: broadcast::Receiver<TransferProgress>,
+) -> Result<()> {
+    loop {
+        match progress.recv().await {
+            Ok(event) => {
+                let mut line = serde_json::to_string(&event)?;
+                line.push('\n');
+                writer.write_all(line.as_bytes()).await?;
+            }
+            // 订阅者读取过慢时跳过丢失的事件
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                debug!("进度订阅落后，跳过 {} 个事件", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+/// 向运行中的守护进程发送控制命令，返回其状态（paused/running）
+pub async fn send_command(addr: &str, command: ControlCommand) -> Result<String> {
+    let mut stream = TcpStream::connect(addr)
+        .await
+        .with_context(|| format!("无法连接守护进程控制端口 {}（守护进程是否在运行？）", addr))?;
+
+    stream
+        .write_all(format!("{}\n", command.as_str()).as_bytes())
+        .await?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await?;
+
+    match reply.trim().strip_prefix("ok ") {
+        Some(status) => Ok(status.to_string()),
+        None => anyhow::bail!("守护进程返回错误: {}", reply.trim()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            ControlCommand::parse("pause\n"),
+            Some(ControlCommand::Pause)
+        );
+        assert_eq!(
+            ControlCommand::parse(" RESUME "),
+            Some(ControlCommand::Resume)
+        );
+        assert_eq!(
+            ControlCommand::parse("status"),
+            Some(ControlCommand::Status)
+        );
+        assert_eq!(
+            ControlCommand::parse("progress"),
+            Some(ControlCommand::Progress)
+        );
+        assert_eq!(ControlCommand::parse("stop"), None);
+    }
+
+    #[tokio::test]
+    async fn test_control_server_toggles_pause() {
+        let control = SyncControl::new();
+        let (_progress_tx, progress_rx) = broadcast::channel(16);
+        let (addr, handle) = spawn_control_server("127.0.0.1:0", control.clone(), progress_rx)
+            .await
+            .unwrap();
+        let addr = addr.to_string();
+
+        assert_eq!(
+            send_command(&addr, ControlCommand::Pause).await.unwrap(),
+            "paused"
+        );
+        assert!(control.is_paused());
+
+        assert_eq!(
+            send_command(&addr, ControlCommand::Status).await.unwrap(),
+            "paused"
+        );
+
+        assert_eq!(
+            send_command(&addr, ControlCommand::Resume).await.unwrap(),
+            "running"
+        );
+        assert!(!control.is_paused());
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_progress_subscription_streams_events() {
+        let (progress_tx, progress_rx) = broadcast::channel(16);
+        let (addr, handle) = spawn_control_server("127.0.0.1:0", SyncControl::new(), progress_rx)
+            .await
+            .unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"progress\n").await.unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "ok running");
+
+        // 回复状态后已完成订阅，之后的进度逐行推送
+        for transferred_bytes in [512, 1024] {
+            progress_tx
+                .send(TransferProgress {
+                    file_path: "CLAUDE.md".into(),
+                    total_bytes: 1024,
+                    transferred_bytes,
+                    started_at: chrono::Utc::now(),
+                    completed_at: None,
+                    is_completed: transferred_bytes == 1024,
+                    is_failed: false,
+                    error_message: None,
+                })
+                .unwrap();
+        }
+        for expected in [512, 1024] {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let event: TransferProgress = serde_json::from_str(&line).unwrap();
+            assert_eq!(event.file_path, std::path::PathBuf::from("CLAUDE.md"));
+            assert_eq!(event.transferred_bytes, expected);
+        }
+
+        handle.abort();
+    }
+}
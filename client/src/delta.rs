@@ -0,0 +1,191 @@
+// 增量上传：基于上次同步的内容计算差异
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::content_hash::hash_like;
+use crate::transfer::write_atomic;
+
+/// 每个复制操作的编码开销（偏移量 + 长度）
+const COPY_OP_SIZE: usize = 16;
+
+/// 每个插入操作的固定编码开销
+const INSERT_OP_OVERHEAD: usize = 4;
+
+/// 增量操作：按顺序拼接得到新内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// 复制基准内容中的片段
+    Copy { offset: u64, len: u64 },
+    /// 新增数据
+    Insert(Vec<u8>),
+}
+
+/// 按行计算从 base 到 target 的增量
+pub fn compute_delta(base: &[u8], target: &[u8]) -> Vec<DeltaOp> {
+    let old_lines: Vec<&[u8]> = base.split_inclusive(|&b| b == b'\n').collect();
+    let new_lines: Vec<&[u8]> = target.split_inclusive(|&b| b == b'\n').collect();
+
+    // 每行在基准内容中的起始偏移量（多一项表示末尾）
+    let mut old_offsets = Vec::with_capacity(old_lines.len() + 1);
+    let mut offset = 0u64;
+    old_offsets.push(offset);
+    for line in &old_lines {
+        offset += line.len() as u64;
+        old_offsets.push(offset);
+    }
+
+    let mut ops: Vec<DeltaOp> = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        match tag {
+            DiffTag::Equal => {
+                let start = old_offsets[old_range.start];
+                let len = old_offsets[old_range.end] - start;
+                match ops.last_mut() {
+                    Some(DeltaOp::Copy {
+                        offset,
+                        len: last_len,
+                    }) if *offset + *last_len == start => *last_len += len,
+                    _ => ops.push(DeltaOp::Copy { offset: start, len }),
+                }
+            }
+            DiffTag::Insert | DiffTag::Replace => {
+                let data = new_lines[new_range].concat();
+                match ops.last_mut() {
+                    Some(DeltaOp::Insert(last)) => last.extend_from_slice(&data),
+                    _ => ops.push(DeltaOp::Insert(data)),
+                }
+            }
+            DiffTag::Delete => {}
+        }
+    }
+
+    ops
+}
+
+/// 按增量从基准内容重建新内容
+pub fn apply_delta(base: &[u8], ops: &[DeltaOp]) -> Result<Vec<u8>> {
+    let mut content = Vec::with_capacity(base.len());
+
+    for op in ops {
+        match op {
+            DeltaOp::Copy { offset, len } => {
+                let range = usize::try_from(*offset)
+                    .ok()
+                    .zip(usize::try_from(*len).ok())
+                    .and_then(|(offset, len)| Some(offset..offset.checked_add(len)?))
+                    .filter(|range| range.end <= base.len())
+                    .with_context(|| {
+                        format!(
+                            "增量复制超出基准内容范围: 偏移 {}, 长度 {}, 基准大小 {}",
+                            offset,
+                            len,
+                            base.len()
+                        )
+                    })?;
+                content.extend_from_slice(&base[range]);
+            }
+            DeltaOp::Insert(data) => content.extend_from_slice(data),
+        }
+    }
+
+    Ok(content)
+}
+
+/// 增量编码后的大致字节数（用于和完整上传比较）
+pub fn encoded_len(ops: &[DeltaOp]) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            DeltaOp::Copy { .. } => COPY_OP_SIZE,
+            DeltaOp::Insert(data) => INSERT_OP_OVERHEAD + data.len(),
+        })
+        .sum()
+}
+
+/// 增量上传的基准内容存储
+///
+/// 每个本地文件保存一份上次上传或下载的内容，文件名为本地路径的哈希。
+#[derive(Debug, Clone)]
+pub struct BaseStore {
+    dir: PathBuf,
+}
+
+impl BaseStore {
+    /// 创建指向存储目录的基准内容存储（目录在首次写入时创建）
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 文件对应的基准内容路径
+    fn base_path(&self, file_path: &Path) -> PathBuf {
+        let key = Sha256::digest(file_path.to_string_lossy().as_bytes());
+        self.dir.join(format!("{:x}", key))
+    }
+
+    /// 读取文件的基准内容，内容哈希与 expected_hash 不一致时返回 None
+    pub async fn load(&self, file_path: &Path, expected_hash: &str) -> Option<Vec<u8>> {
+        let content = tokio::fs::read(self.base_path(file_path)).await.ok()?;
+        if hash_like(expected_hash, &content) != expected_hash {
+            debug!("基准内容已过期: {:?}", file_path);
+            return None;
+        }
+        Some(content)
+    }
+
+    /// 保存文件的基准内容（覆盖旧的基准）
+    pub async fn save(&self, file_path: &Path, content: &[u8]) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("无法创建基准内容目录: {:?}", self.dir))?;
+        write_atomic(&self.base_path(file_path), content).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_edit_produces_small_delta() {
+        let base: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        let target = base.replace("line 100\n", "line one hundred\n");
+
+        let ops = compute_delta(base.as_bytes(), target.as_bytes());
+        assert_eq!(
+            apply_delta(base.as_bytes(), &ops).unwrap(),
+            target.as_bytes()
+        );
+        assert_eq!(ops.len(), 3);
+        assert!(encoded_len(&ops) < 64);
+
+        // 没有末尾换行、完全不同的内容
+        let ops = compute_delta(b"a\nb", b"c");
+        assert_eq!(apply_delta(b"a\nb", &ops).unwrap(), b"c");
+        assert_eq!(compute_delta(b"", b""), Vec::new());
+    }
+
+    #[test]
+    fn test_apply_delta_rejects_out_of_range_copy() {
+        let ops = [DeltaOp::Copy { offset: 3, len: 10 }];
+        assert!(apply_delta(b"short", &ops).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_base_store_checks_hash() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = BaseStore::new(temp_dir.path().join("bases"));
+        let file = Path::new("/home/user/.claude/settings.json");
+        let hash = format!("{:x}", Sha256::digest(b"v1"));
+
+        assert!(store.load(file, &hash).await.is_none());
+        store.save(file, b"v1").await.unwrap();
+        assert_eq!(store.load(file, &hash).await.unwrap(), b"v1");
+
+        store.save(file, b"v2").await.unwrap();
+        assert!(store.load(file, &hash).await.is_none());
+    }
+}
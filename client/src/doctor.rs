@@ -0,0 +1,593 @@
+use crate::config::ClientConfig;
+use crate::rules::{RuleEngine, SyncRule};
+use crate::token::TokenManager;
+use chrono::Utc;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+use tracing::debug;
+
+/// 单项诊断结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    /// 检查项名称
+    pub name: String,
+
+    /// 是否通过
+    pub passed: bool,
+
+    /// 未通过时是否为严重问题
+    pub critical: bool,
+
+    /// 检查结果说明
+    pub message: String,
+
+    /// 修复建议
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: true,
+            critical: false,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn fail(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            passed: false,
+            critical: true,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            critical: false,
+            ..Self::fail(name, message, hint)
+        }
+    }
+
+    /// 是否为未通过的严重问题
+    pub fn is_critical_failure(&self) -> bool {
+        !self.passed && self.critical
+    }
+}
+
+/// 检查配置文件能否解析
+pub fn check_config_file(path: &Path) -> (CheckResult, Option<ClientConfig>) {
+    const NAME: &str = "配置文件";
+
+    if !path.exists() {
+        return (
+            CheckResult::fail(
+                NAME,
+                format!("配置文件不存在: {:?}", path),
+                "运行 'claude-sync config-init' 生成默认配置",
+            ),
+            None,
+        );
+    }
+
+    match ClientConfig::load_from(path) {
+        Ok(config) => (
+            CheckResult::pass(NAME, format!("已加载 {:?}", path)),
+            Some(config),
+        ),
+        Err(e) => (
+            CheckResult::fail(
+                NAME,
+                format!("{:#}", e),
+                "检查配置文件的 TOML 语法，或删除后重新运行 'claude-sync config-init'",
+            ),
+            None,
+        ),
+    }
+}
+
+/// 检查配置的服务器地址和 TLS 设置
+pub fn check_server_config(config: &ClientConfig) -> CheckResult {
+    const NAME: &str = "服务器配置";
+
+    if config.server.address.is_empty() {
+        return CheckResult::fail(
+            NAME,
+            "服务器地址为空",
+            "在配置文件的 [server] 中设置 address",
+        );
+    }
+
+    match config.server.validate_tls() {
+        Ok(()) => CheckResult::pass(NAME, config.server.address.clone()),
+        Err(e) => CheckResult::fail(
+            NAME,
+            e.to_string(),
+            "启用 TLS 时使用 https:// 地址，并同时配置客户端证书和私钥",
+        ),
+    }
+}
+
+/// 检查 Claude 目录存在且可读
+pub fn check_claude_dir(dir: &Path) -> CheckResult {
+    const NAME: &str = "Claude 目录";
+
+    if !dir.exists() {
+        return CheckResult::fail(
+            NAME,
+            format!("目录不存在: {:?}", dir),
+            "确认已安装 Claude CLI，或在配置文件中修改 sync.claude_dir",
+        );
+    }
+
+    if !dir.is_dir() {
+        return CheckResult::fail(
+            NAME,
+            format!("不是目录: {:?}", dir),
+            "将 sync.claude_dir 指向 Claude 配置目录",
+        );
+    }
+
+    match std::fs::read_dir(dir) {
+        Ok(_) => CheckResult::pass(NAME, format!("{:?}", dir)),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("无法读取目录 {:?}: {}", dir, e),
+            "检查目录权限",
+        ),
+    }
+}
+
+/// 检查 Token 文件存在且未过期
+pub fn check_tokens(token_manager: &TokenManager) -> CheckResult {
+    const NAME: &str = "登录状态";
+
+    if !token_manager.has_tokens() {
+        return CheckResult::fail(NAME, "未找到 Token", "运行 'claude-sync login' 登录");
+    }
+
+    let tokens = match token_manager.load_tokens() {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            return CheckResult::fail(
+                NAME,
+                format!("无法读取 Token: {:#}", e),
+                "检查 auth.encryption_key 是否正确，或重新运行 'claude-sync login'",
+            )
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    if now >= tokens.refresh_expires_at {
+        CheckResult::fail(
+            NAME,
+            "Refresh Token 已过期",
+            "运行 'claude-sync login' 重新登录",
+        )
+    } else if now >= tokens.access_expires_at {
+        CheckResult::warn(
+            NAME,
+            "Access Token 已过期",
+            "下次同步时会自动刷新，或重新运行 'claude-sync login'",
+        )
+    } else {
+        CheckResult::pass(NAME, format!("用户 {}", tokens.user_id))
+    }
+}
+
+/// 解析服务器健康检查响应中的各组件状态
+pub fn check_health_components(body: &JsonValue) -> Vec<CheckResult> {
+    [
+        ("database", "服务器数据库"),
+        ("redis", "服务器 Redis"),
+        ("storage", "服务器存储"),
+    ]
+    .iter()
+    .map(|(key, name)| match body.get(key) {
+        Some(component) => {
+            let message = component
+                .get("message")
+                .and_then(JsonValue::as_str)
+                .unwrap_or_default()
+                .to_string();
+            if component.get("healthy").and_then(JsonValue::as_bool) == Some(true) {
+                CheckResult::pass(name, message)
+            } else {
+                CheckResult::fail(name, message, "联系服务器管理员检查服务状态")
+            }
+        }
+        None => CheckResult::warn(
+            name,
+            "健康检查响应中没有该组件",
+            "确认服务器版本与客户端匹配",
+        ),
+    })
+    .collect()
+}
+
+/// 通过健康检查端点检查服务器可达性及组件状态
+pub async fn check_server_health(health_check_address: &str) -> Vec<CheckResult> {
+    const NAME: &str = "服务器连接";
+
+    let health_url = format!("{}/health", health_check_address.trim_end_matches('/'));
+    debug!("诊断健康检查端点: {}", health_url);
+
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return vec![CheckResult::fail(NAME, e.to_string(), "检查系统网络配置")],
+    };
+
+    let response = match client.get(&health_url).send().await {
+        Ok(response) => response,
+        Err(e) => {
+            return vec![CheckResult::fail(
+                NAME,
+                format!("无法连接 {}: {}", health_url, e),
+                "确认 server.health_check_address 正确且服务器正在运行",
+            )]
+        }
+    };
+
+    // 不健康时服务器返回 503，但响应体中仍包含各组件状态
+    let status = response.status();
+    match response.json::<JsonValue>().await {
+        Ok(body) => {
+            let mut results = vec![CheckResult::pass(NAME, health_url)];
+            results.extend(check_health_components(&body));
+            results
+        }
+        Err(e) => vec![CheckResult::fail(
+            NAME,
+            format!("无法解析健康检查响应 ({}): {}", status, e),
+            "确认 server.health_check_address 指向 Claude Sync 服务器",
+        )],
+    }
+}
+
+/// 运行全部诊断检查
+pub async fn run_diagnostics(config_path: &Path) -> Vec<CheckResult> {
+    let (config_result, config) = check_config_file(config_path);
+    let mut results = vec![config_result];
+
+    // 配置无法加载时，其余检查使用默认配置
+    let config = config.unwrap_or_default();
+
+    results.push(check_server_config(&config));
+    results.push(check_claude_dir(&config.sync.claude_dir));
+    results.push(
+        match TokenManager::from_config(&config.auth, String::new()) {
+            Ok(token_manager) => check_tokens(&token_manager),
+            Err(e) => CheckResult::fail(
+                "登录状态",
+                format!("无法打开 Token 存储: {:#}", e),
+                "检查系统密钥环是否可用，或将 auth.token_store 改为 \"file\"",
+            ),
+        },
+    );
+    results.extend(check_server_health(&config.server.health_check_address).await);
+
+    results
+}
+
+/// 检查配置项取值（ClientConfig::validate）
+pub fn check_config_values(config: &ClientConfig) -> CheckResult {
+    const NAME: &str = "配置项";
+
+    match config.validate() {
+        Ok(()) => CheckResult::pass(NAME, "所有配置项有效"),
+        Err(e) => CheckResult::fail(NAME, format!("{:#}", e), "按提示修改配置文件中对应的配置项"),
+    }
+}
+
+/// 检查同步规则：规则本身的有效性、重复 ID、同优先级下相互矛盾的包含/排除
+pub fn check_rules(rules: &[SyncRule]) -> Vec<CheckResult> {
+    const NAME: &str = "同步规则";
+    let mut results = Vec::new();
+
+    for rule in rules {
+        if let Err(e) = RuleEngine::validate_rule(rule) {
+            results.push(CheckResult::fail(
+                NAME,
+                format!("规则 {} ({}) 无效: {:#}", rule.id, rule.name, e),
+                "修正规则的模式，或使用 'claude-sync rules remove' 删除该规则",
+            ));
+        }
+    }
+
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    for rule in rules {
+        *ids.entry(rule.id.as_str()).or_default() += 1;
+    }
+    let mut duplicates: Vec<_> = ids.into_iter().filter(|(_, count)| *count > 1).collect();
+    duplicates.sort();
+    for (id, count) in duplicates {
+        results.push(CheckResult::fail(
+            NAME,
+            format!("规则 ID {} 重复出现 {} 次", id, count),
+            "为每条规则设置唯一的 id",
+        ));
+    }
+
+    for (i, rule) in rules.iter().enumerate() {
+        let contradiction = rules[i + 1..].iter().find(|other| {
+            rule.enabled
+                && other.enabled
+                && other.pattern == rule.pattern
+                && other.pattern_type == rule.pattern_type
+                && other.file_type == rule.file_type
+                && other.priority == rule.priority
+                && other.rule_type != rule.rule_type
+        });
+        if let Some(other) = contradiction {
+            results.push(CheckResult::fail(
+                NAME,
+                format!(
+                    "规则 {} 与 {} 对模式 {} 的包含/排除相互矛盾（优先级均为 {}）",
+                    rule.id, other.id, rule.pattern, rule.priority
+                ),
+                "删除其中一条规则，或调整优先级明确哪条生效",
+            ));
+        }
+    }
+
+    if results.is_empty() {
+        results.push(CheckResult::pass(
+            NAME,
+            format!("{} 条规则均有效", rules.len()),
+        ));
+    }
+
+    results
+}
+
+/// 校验配置文件和同步规则（不访问网络）
+pub fn run_config_validation(config_path: &Path) -> Vec<CheckResult> {
+    let (config_result, config) = check_config_file(config_path);
+    let mut results = vec![config_result];
+
+    if let Some(config) = config {
+        results.push(check_config_values(&config));
+        results.extend(check_rules(&config.sync.rules));
+    }
+
+    results
+}
+
+/// 打印诊断报告
+pub fn print_report(results: &[CheckResult]) {
+    for result in results {
+        let mark = if result.passed {
+            "✓"
+        } else if result.critical {
+            "✗"
+        } else {
+            "⚠️ "
+        };
+        println!("{} {}: {}", mark, result.name, result.message);
+
+        if let Some(hint) = &result.hint {
+            println!("    💡 {}", hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{PatternType, RuleType};
+    use crate::token::TokenStorage;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_config_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+
+        let (result, config) = check_config_file(&path);
+        assert!(result.is_critical_failure());
+        assert!(config.is_none());
+
+        std::fs::write(&path, "[server\naddress = ").unwrap();
+        let (result, _) = check_config_file(&path);
+        assert!(result.is_critical_failure());
+
+        ClientConfig::default().save(&path).unwrap();
+        let (result, config) = check_config_file(&path);
+        assert!(result.passed);
+        assert!(config.is_some());
+    }
+
+    fn rule(id: &str, rule_type: RuleType, pattern: &str, pattern_type: PatternType) -> SyncRule {
+        SyncRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            rule_type,
+            pattern: pattern.to_string(),
+            pattern_type,
+            file_type: None,
+            priority: 10,
+            enabled: true,
+            description: None,
+            rewrite: None,
+        }
+    }
+
+    #[test]
+    fn test_config_validation_valid() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.toml");
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = temp_dir.path().to_path_buf();
+        config.sync.rules = vec![
+            rule("a", RuleType::Exclude, "**/*.log", PatternType::Glob),
+            rule(
+                "b",
+                RuleType::Include,
+                r"^agents/.*\.md$",
+                PatternType::Regex,
+            ),
+        ];
+        config.save(&path).unwrap();
+
+        let results = run_config_validation(&path);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.passed), "{:?}", results);
+    }
+
+    #[test]
+    fn test_check_rules_invalid_regex() {
+        let results = check_rules(&[
+            rule("ok", RuleType::Exclude, "*.tmp", PatternType::Glob),
+            rule(
+                "bad",
+                RuleType::Include,
+                "agents/(unclosed",
+                PatternType::Regex,
+            ),
+        ]);
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_critical_failure());
+        assert!(results[0].message.contains("bad"));
+    }
+
+    #[test]
+    fn test_check_rules_duplicate_id_and_contradiction() {
+        let results = check_rules(&[
+            rule("dup", RuleType::Exclude, "*.tmp", PatternType::Glob),
+            rule("dup", RuleType::Exclude, "*.bak", PatternType::Glob),
+        ]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_critical_failure());
+        assert!(results[0].message.contains("dup"));
+
+        let results = check_rules(&[
+            rule("keep", RuleType::Include, "cache/**", PatternType::Glob),
+            rule("drop", RuleType::Exclude, "cache/**", PatternType::Glob),
+        ]);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].message.contains("keep"));
+        assert!(results[0].message.contains("drop"));
+
+        // 优先级不同时规则顺序明确，不算矛盾
+        let mut higher = rule("drop", RuleType::Exclude, "cache/**", PatternType::Glob);
+        higher.priority = 20;
+        let results = check_rules(&[
+            rule("keep", RuleType::Include, "cache/**", PatternType::Glob),
+            higher,
+        ]);
+        assert!(results[0].passed);
+    }
+
+    #[test]
+    fn test_check_claude_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        assert!(check_claude_dir(temp_dir.path()).passed);
+
+        let missing = temp_dir.path().join("missing");
+        assert!(check_claude_dir(&missing).is_critical_failure());
+
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(check_claude_dir(&file).is_critical_failure());
+    }
+
+    #[test]
+    fn test_check_tokens() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = TokenManager::new(temp_dir.path().to_path_buf(), None, String::new());
+        assert!(check_tokens(&manager).is_critical_failure());
+
+        let now = Utc::now().timestamp();
+        let save = |access_expires_at: i64, refresh_expires_at: i64| {
+            manager
+                .save_tokens(TokenStorage {
+                    access_token: "access".to_string(),
+                    refresh_token: "refresh".to_string(),
+                    device_id: "device".to_string(),
+                    user_id: "user".to_string(),
+                    access_expires_at,
+                    refresh_expires_at,
+                })
+                .unwrap();
+        };
+
+        save(now + 3600, now + 7200);
+        assert!(check_tokens(&manager).passed);
+
+        // Access Token 过期只是警告
+        save(now - 10, now + 7200);
+        let result = check_tokens(&manager);
+        assert!(!result.passed);
+        assert!(!result.critical);
+
+        save(now - 20, now - 10);
+        assert!(check_tokens(&manager).is_critical_failure());
+    }
+
+    #[test]
+    fn test_check_health_components() {
+        let body = serde_json::json!({
+            "status": "unhealthy",
+            "database": {"healthy": true, "message": "OK"},
+            "redis": {"healthy": false, "message": "connection refused"},
+            "storage": {"healthy": true, "message": "OK"}
+        });
+
+        let results = check_health_components(&body);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].passed);
+        assert!(results[1].is_critical_failure());
+        assert_eq!(results[1].message, "connection refused");
+        assert!(results[2].passed);
+    }
+
+    #[tokio::test]
+    async fn test_check_server_health_mock() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+
+            let body = r#"{"status":"healthy","database":{"healthy":true,"message":"OK"},"redis":{"healthy":true,"message":"OK"},"storage":{"healthy":true,"message":"OK"}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let results = check_server_health(&format!("http://{}", addr)).await;
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|r| r.passed));
+    }
+
+    #[tokio::test]
+    async fn test_check_server_health_unreachable() {
+        // 绑定后立即释放端口，保证没有服务在监听
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let results = check_server_health(&format!("http://127.0.0.1:{}", port)).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_critical_failure());
+    }
+}
@@ -1,5 +1,25 @@
 use thiserror::Error;
 
+/// 默认可重试的 gRPC 状态码
+///
+/// ResourceExhausted 为服务器限流（退避后重试），Aborted 为并发写入冲突（重新提交即可）。
+pub const DEFAULT_RETRYABLE_CODES: [tonic::Code; 4] = [
+    tonic::Code::Unavailable,
+    tonic::Code::DeadlineExceeded,
+    tonic::Code::ResourceExhausted,
+    tonic::Code::Aborted,
+];
+
+/// 服务器在 ResourceExhausted 响应中返回配额信息的 metadata 键（字节数）
+pub const QUOTA_USED_METADATA: &str = "x-quota-used";
+pub const QUOTA_LIMIT_METADATA: &str = "x-quota-limit";
+
+/// 服务器返回结构化错误原因（如 NOT_FOUND、CONFLICT）的 metadata 键
+pub const ERROR_REASON_METADATA: &str = "x-error-reason";
+
+/// 服务器在冲突错误中返回文件路径的 metadata 键
+pub const ERROR_PATH_METADATA: &str = "x-error-path";
+
 /// 客户端统一错误类型
 #[derive(Error, Debug)]
 pub enum ClientError {
@@ -23,9 +43,13 @@ pub enum ClientError {
         source: Option<Box<dyn std::error::Error + Send + Sync>>,
     },
 
-    /// gRPC 错误
+    /// gRPC 错误（reason 为服务器返回的结构化错误原因）
     #[error("gRPC 错误: {code} - {message}")]
-    Grpc { code: tonic::Code, message: String },
+    Grpc {
+        code: tonic::Code,
+        message: String,
+        reason: Option<String>,
+    },
 
     /// 文件 I/O 错误
     #[error("文件错误: {path} - {message}")]
@@ -56,6 +80,18 @@ pub enum ClientError {
     #[error("验证错误: {message}")]
     Validation { message: String },
 
+    /// 存储配额不足（服务器返回，或超过客户端的上传总量上限）
+    #[error("存储配额不足: {message}")]
+    QuotaExceeded {
+        message: String,
+        used: Option<u64>,
+        limit: Option<u64>,
+    },
+
+    /// 上传内容被服务器的内容扫描拒绝（如包含密钥）
+    #[error("内容被拒绝: {message}")]
+    ContentRejected { message: String },
+
     /// 超时错误
     #[error("超时错误: {operation} 在 {timeout_secs} 秒后超时")]
     Timeout {
@@ -97,9 +133,14 @@ impl Clone for ClientError {
                 message: message.clone(),
                 source: None,
             },
-            Self::Grpc { code, message } => Self::Grpc {
+            Self::Grpc {
+                code,
+                message,
+                reason,
+            } => Self::Grpc {
                 code: *code,
                 message: message.clone(),
+                reason: reason.clone(),
             },
             Self::File {
                 path,
@@ -125,6 +166,18 @@ impl Clone for ClientError {
             Self::Validation { message } => Self::Validation {
                 message: message.clone(),
             },
+            Self::QuotaExceeded {
+                message,
+                used,
+                limit,
+            } => Self::QuotaExceeded {
+                message: message.clone(),
+                used: *used,
+                limit: *limit,
+            },
+            Self::ContentRejected { message } => Self::ContentRejected {
+                message: message.clone(),
+            },
             Self::Timeout {
                 operation,
                 timeout_secs,
@@ -187,6 +240,7 @@ impl ClientError {
         Self::Grpc {
             code,
             message: message.into(),
+            reason: None,
         }
     }
 
@@ -237,6 +291,26 @@ impl ClientError {
         }
     }
 
+    /// 创建配额不足错误（used、limit 为已用字节数和配额上限，未知时为 None）
+    pub fn quota_exceeded(
+        message: impl Into<String>,
+        used: Option<u64>,
+        limit: Option<u64>,
+    ) -> Self {
+        Self::QuotaExceeded {
+            message: message.into(),
+            used,
+            limit,
+        }
+    }
+
+    /// 创建内容被拒绝错误
+    pub fn content_rejected(message: impl Into<String>) -> Self {
+        Self::ContentRejected {
+            message: message.into(),
+        }
+    }
+
     /// 创建超时错误
     pub fn timeout(operation: impl Into<String>, timeout_secs: u64) -> Self {
         Self::Timeout {
@@ -269,17 +343,18 @@ impl ClientError {
         }
     }
 
-    /// 检查错误是否可重试
+    /// 检查错误是否可重试（gRPC 错误按默认的可重试状态码判断）
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            Self::Network { .. }
-                | Self::Grpc {
-                    code: tonic::Code::Unavailable | tonic::Code::DeadlineExceeded,
-                    ..
-                }
-                | Self::Timeout { .. }
-        )
+        self.is_retryable_with(|code| DEFAULT_RETRYABLE_CODES.contains(&code))
+    }
+
+    /// 检查错误是否可重试，gRPC 错误只在 is_retryable_code 返回 true 时重试
+    pub fn is_retryable_with(&self, is_retryable_code: impl Fn(tonic::Code) -> bool) -> bool {
+        match self {
+            Self::Network { .. } | Self::Timeout { .. } => true,
+            Self::Grpc { code, .. } => is_retryable_code(*code),
+            _ => false,
+        }
     }
 
     /// 获取用户友好的错误消息
@@ -289,7 +364,7 @@ impl ClientError {
             Self::Auth { message } => format!("认证失败：{}", message),
             Self::Token { message } => format!("Token 问题：{}", message),
             Self::Network { message, .. } => format!("网络连接失败：{}", message),
-            Self::Grpc { code, message } => {
+            Self::Grpc { code, message, .. } => {
                 format!("服务器错误 ({}): {}", code, message)
             }
             Self::File { path, message, .. } => {
@@ -299,6 +374,24 @@ impl ClientError {
             Self::Conflict { path, message } => format!("冲突 ({}): {}", path, message),
             Self::Parse { message, .. } => format!("解析失败：{}", message),
             Self::Validation { message } => format!("验证失败：{}", message),
+            Self::QuotaExceeded {
+                message,
+                used,
+                limit,
+            } => match (used, limit) {
+                (Some(used), Some(limit)) => format!(
+                    "存储配额不足：{}（已用 {} / 上限 {} 字节）",
+                    message, used, limit
+                ),
+                (None, Some(limit)) => format!("存储配额不足：{}（上限 {} 字节）", message, limit),
+                _ => format!("存储配额不足：{}", message),
+            },
+            Self::ContentRejected { message } => {
+                format!(
+                    "上传被服务器拒绝（内容中可能包含密钥等敏感信息）：{}",
+                    message
+                )
+            }
             Self::Timeout {
                 operation,
                 timeout_secs,
@@ -329,6 +422,8 @@ impl ClientError {
             Self::Conflict { .. } => "CONFLICT_ERROR",
             Self::Parse { .. } => "PARSE_ERROR",
             Self::Validation { .. } => "VALIDATION_ERROR",
+            Self::QuotaExceeded { .. } => "QUOTA_EXCEEDED",
+            Self::ContentRejected { .. } => "CONTENT_REJECTED",
             Self::Timeout { .. } => "TIMEOUT_ERROR",
             Self::RetryExhausted { .. } => "RETRY_EXHAUSTED",
             Self::Internal { .. } => "INTERNAL_ERROR",
@@ -376,9 +471,47 @@ impl From<tonic::transport::Error> for ClientError {
 
 impl From<tonic::Status> for ClientError {
     fn from(status: tonic::Status) -> Self {
+        let metadata = |key: &str| {
+            status
+                .metadata()
+                .get(key)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let reason = metadata(ERROR_REASON_METADATA);
+
+        // 服务器标记的版本冲突
+        if reason.as_deref() == Some("CONFLICT") {
+            return Self::conflict(
+                metadata(ERROR_PATH_METADATA).unwrap_or_default(),
+                status.message(),
+            );
+        }
+
+        // 服务器的内容扫描拒绝了上传（不重试）
+        if reason.as_deref() == Some("CONTENT_REJECTED") {
+            return Self::content_rejected(status.message());
+        }
+
+        // 带配额信息的 ResourceExhausted 为存储配额用尽（不重试），其余按限流处理
+        if status.code() == tonic::Code::ResourceExhausted {
+            let quota = |key: &str| {
+                status
+                    .metadata()
+                    .get(key)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+            };
+            let (used, limit) = (quota(QUOTA_USED_METADATA), quota(QUOTA_LIMIT_METADATA));
+            if used.is_some() || limit.is_some() || reason.as_deref() == Some("QUOTA_EXCEEDED") {
+                return Self::quota_exceeded(status.message(), used, limit);
+            }
+        }
+
         Self::Grpc {
             code: status.code(),
             message: status.message().to_string(),
+            reason,
         }
     }
 }
@@ -406,6 +539,108 @@ mod tests {
         assert!(!config_err.is_retryable());
     }
 
+    #[test]
+    fn test_retryable_grpc_codes() {
+        let grpc = |code| ClientError::from(tonic::Status::new(code, "错误"));
+
+        assert!(grpc(tonic::Code::Unavailable).is_retryable());
+        assert!(grpc(tonic::Code::ResourceExhausted).is_retryable());
+        assert!(grpc(tonic::Code::Aborted).is_retryable());
+        assert!(!grpc(tonic::Code::InvalidArgument).is_retryable());
+
+        let only_aborted = |code| code == tonic::Code::Aborted;
+        assert!(grpc(tonic::Code::Aborted).is_retryable_with(only_aborted));
+        assert!(!grpc(tonic::Code::Unavailable).is_retryable_with(only_aborted));
+        assert!(ClientError::network("连接失败", None).is_retryable_with(|_| false));
+    }
+
+    #[test]
+    fn test_quota_exceeded_status() {
+        let mut status = tonic::Status::resource_exhausted("存储空间已满");
+        status
+            .metadata_mut()
+            .insert(QUOTA_USED_METADATA, "1073741824".parse().unwrap());
+        status
+            .metadata_mut()
+            .insert(QUOTA_LIMIT_METADATA, "1073741824".parse().unwrap());
+
+        let err = ClientError::from(status);
+        assert!(matches!(
+            err,
+            ClientError::QuotaExceeded {
+                used: Some(1073741824),
+                limit: Some(1073741824),
+                ..
+            }
+        ));
+        assert_eq!(err.error_code(), "QUOTA_EXCEEDED");
+        assert!(err.user_message().contains("上限 1073741824 字节"));
+        assert!(!err.is_retryable());
+
+        // 不带配额信息的 ResourceExhausted 仍按限流重试
+        let throttled = ClientError::from(tonic::Status::resource_exhausted("请求过多"));
+        assert!(matches!(throttled, ClientError::Grpc { .. }));
+        assert!(throttled.is_retryable());
+    }
+
+    #[test]
+    fn test_structured_server_errors() {
+        let with_reason = |code, message: &str, reason: &'static str| {
+            let mut status = tonic::Status::new(code, message);
+            status
+                .metadata_mut()
+                .insert(ERROR_REASON_METADATA, reason.parse().unwrap());
+            status
+        };
+
+        // 服务器端的 NotFound 保留原因且不重试
+        let err = ClientError::from(with_reason(
+            tonic::Code::NotFound,
+            "Device not found",
+            "NOT_FOUND",
+        ));
+        assert!(matches!(
+            &err,
+            ClientError::Grpc {
+                code: tonic::Code::NotFound,
+                reason: Some(reason),
+                ..
+            } if reason == "NOT_FOUND"
+        ));
+        assert!(!err.is_retryable());
+
+        // 冲突映射为冲突错误
+        let mut status = with_reason(
+            tonic::Code::FailedPrecondition,
+            "Conflict on agents/a.md: stale base version",
+            "CONFLICT",
+        );
+        status
+            .metadata_mut()
+            .insert(ERROR_PATH_METADATA, "agents/a.md".parse().unwrap());
+        let err = ClientError::from(status);
+        assert!(matches!(&err, ClientError::Conflict { path, .. } if path == "agents/a.md"));
+        assert!(!err.is_retryable());
+
+        // 没有配额数值的配额错误
+        let err = ClientError::from(with_reason(
+            tonic::Code::ResourceExhausted,
+            "Storage quota exceeded",
+            "QUOTA_EXCEEDED",
+        ));
+        assert_eq!(err.error_code(), "QUOTA_EXCEEDED");
+
+        // 内容扫描拒绝
+        let err = ClientError::from(with_reason(
+            tonic::Code::PermissionDenied,
+            "Upload rejected by content scan: secrets:aws_access_key at line 3",
+            "CONTENT_REJECTED",
+        ));
+        assert_eq!(err.error_code(), "CONTENT_REJECTED");
+        assert!(err.user_message().contains("aws_access_key at line 3"));
+        assert!(!err.is_retryable());
+    }
+
     #[test]
     fn test_grpc_error() {
         let status = tonic::Status::cancelled("已取消");
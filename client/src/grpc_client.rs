@@ -1,5 +1,11 @@
+use crate::config::ServerConfig;
+use crate::error::ClientError;
+use crate::paths::validate_remote_path;
+use crate::transfer::UploadChunks;
 use anyhow::{Context, Result};
-use tonic::transport::Channel;
+use std::future::Future;
+use std::time::Duration;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
 use tracing::{debug, info};
 use uuid::Uuid;
 
@@ -14,18 +20,96 @@ pub struct GrpcClient {
 
     /// Access Token
     access_token: Option<String>,
+
+    /// 各类请求的超时
+    timeouts: RpcTimeouts,
+}
+
+/// 请求类型（不同类型使用不同的超时）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcKind {
+    /// 普通请求：元数据查询、变更上报等
+    Unary,
+    /// 文件上传（流式）
+    Upload,
+    /// 文件下载（流式）
+    Download,
+}
+
+/// 各类请求的超时（未单独配置时使用 request_timeout）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RpcTimeouts {
+    pub rpc: Duration,
+    pub upload: Duration,
+    pub download: Duration,
+}
+
+impl RpcTimeouts {
+    /// 从服务器配置读取超时
+    pub fn from_config(server: &ServerConfig) -> Self {
+        let timeout =
+            |value: Option<u64>| Duration::from_secs(value.unwrap_or(server.request_timeout));
+        Self {
+            rpc: timeout(server.rpc_timeout),
+            upload: timeout(server.upload_timeout),
+            download: timeout(server.download_timeout),
+        }
+    }
+
+    /// 指定类型请求的超时
+    pub fn for_kind(&self, kind: RpcKind) -> Duration {
+        match kind {
+            RpcKind::Unary => self.rpc,
+            RpcKind::Upload => self.upload,
+            RpcKind::Download => self.download,
+        }
+    }
+}
+
+/// HTTP/2 保活设置
+///
+/// 连接空闲时也定期发送 PING，避免 NAT/负载均衡器回收长时间没有流量的连接；
+/// PING 超时未响应时断开连接，下次请求重新建立。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Keepalive {
+    /// 从服务器配置读取保活设置（keepalive_interval 为 0 时返回 None）
+    pub fn from_config(server: &ServerConfig) -> Option<Self> {
+        (server.keepalive_interval > 0).then(|| Self {
+            interval: Duration::from_secs(server.keepalive_interval),
+            timeout: Duration::from_secs(server.keepalive_timeout),
+        })
+    }
+
+    /// 为连接端点启用保活
+    pub fn apply(&self, endpoint: Endpoint) -> Endpoint {
+        endpoint
+            .http2_keep_alive_interval(self.interval)
+            .keep_alive_timeout(self.timeout)
+            .keep_alive_while_idle(true)
+    }
 }
 
 impl GrpcClient {
     /// 创建新的 gRPC 客户端
-    pub async fn new(server_address: String) -> Result<Self> {
+    pub async fn new(server: &ServerConfig) -> Result<Self> {
+        let server_address = server.address.clone();
         info!("连接到 gRPC 服务器: {}", server_address);
 
-        let channel = Channel::from_shared(server_address.clone())
-            .context("无效的服务器地址")?
-            .connect()
-            .await
-            .context("无法连接到服务器")?;
+        let mut endpoint =
+            Channel::from_shared(server_address.clone()).context("无效的服务器地址")?;
+        if let Some(tls_config) = load_tls_config(server)? {
+            endpoint = endpoint.tls_config(tls_config).context("TLS 配置无效")?;
+        }
+        if let Some(keepalive) = Keepalive::from_config(server) {
+            endpoint = keepalive.apply(endpoint);
+        }
+
+        let channel = endpoint.connect().await.context("无法连接到服务器")?;
 
         info!("✓ gRPC 连接已建立");
 
@@ -33,6 +117,7 @@ impl GrpcClient {
             channel,
             server_address,
             access_token: None,
+            timeouts: RpcTimeouts::from_config(server),
         })
     }
 
@@ -41,6 +126,20 @@ impl GrpcClient {
         self.access_token = Some(token);
     }
 
+    /// 按请求类型的超时执行一次调用，超时返回 `ClientError::Timeout`
+    async fn call<T>(
+        &self,
+        kind: RpcKind,
+        operation: &str,
+        request: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let timeout = self.timeouts.for_kind(kind);
+        match tokio::time::timeout(timeout, request).await {
+            Ok(result) => result,
+            Err(_) => Err(ClientError::timeout(operation, timeout.as_secs()).into()),
+        }
+    }
+
     /// 用户注册
     #[allow(dead_code)]
     pub async fn register(
@@ -51,13 +150,16 @@ impl GrpcClient {
     ) -> Result<RegisterResponse> {
         debug!("用户注册: {}", email);
 
-        // TODO: 实现 AuthService.Register RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "用户注册", async {
+            // TODO: 实现 AuthService.Register RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(RegisterResponse {
-            user_id: Uuid::new_v4(),
-            message: "注册成功".to_string(),
+            Ok(RegisterResponse {
+                user_id: Uuid::new_v4(),
+                message: "注册成功".to_string(),
+            })
         })
+        .await
     }
 
     /// 用户登录
@@ -71,16 +173,19 @@ impl GrpcClient {
     ) -> Result<LoginResponse> {
         debug!("用户登录: {}", email);
 
-        // TODO: 实现 AuthService.Login RPC 调用
-        // 需要等待 protobuf 代码生成
-
-        Ok(LoginResponse {
-            user_id: Uuid::new_v4(),
-            device_id: Uuid::new_v4(),
-            access_token: "dummy_access_token".to_string(),
-            refresh_token: "dummy_refresh_token".to_string(),
-            message: "登录成功".to_string(),
+        self.call(RpcKind::Unary, "用户登录", async {
+            // TODO: 实现 AuthService.Login RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(LoginResponse {
+                user_id: Uuid::new_v4(),
+                device_id: Uuid::new_v4(),
+                access_token: "dummy_access_token".to_string(),
+                refresh_token: "dummy_refresh_token".to_string(),
+                message: "登录成功".to_string(),
+            })
         })
+        .await
     }
 
     /// 刷新 Token
@@ -88,13 +193,17 @@ impl GrpcClient {
     pub async fn refresh_token(&self, _refresh_token: String) -> Result<TokenRefreshResponse> {
         debug!("刷新 Token");
 
-        // TODO: 实现 AuthService.RefreshToken RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "刷新 Token", async {
+            // TODO: 实现 AuthService.RefreshToken RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(TokenRefreshResponse {
-            access_token: "new_access_token".to_string(),
-            message: "Token 刷新成功".to_string(),
+            Ok(TokenRefreshResponse {
+                access_token: "new_access_token".to_string(),
+                expires_at: chrono::Utc::now().timestamp() + 3600,
+                message: "Token 刷新成功".to_string(),
+            })
         })
+        .await
     }
 
     /// 登出
@@ -102,10 +211,13 @@ impl GrpcClient {
     pub async fn logout(&self) -> Result<()> {
         debug!("用户登出");
 
-        // TODO: 实现 AuthService.Logout RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "用户登出", async {
+            // TODO: 实现 AuthService.Logout RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(())
+            Ok(())
+        })
+        .await
     }
 
     /// 注册设备
@@ -118,13 +230,16 @@ impl GrpcClient {
     ) -> Result<DeviceResponse> {
         debug!("注册设备: {}", name);
 
-        // TODO: 实现 DeviceService.RegisterDevice RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "注册设备", async {
+            // TODO: 实现 DeviceService.RegisterDevice RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(DeviceResponse {
-            device_id: Uuid::new_v4(),
-            message: "设备注册成功".to_string(),
+            Ok(DeviceResponse {
+                device_id: Uuid::new_v4(),
+                message: "设备注册成功".to_string(),
+            })
         })
+        .await
     }
 
     /// 列出设备
@@ -132,26 +247,48 @@ impl GrpcClient {
     pub async fn list_devices(&self) -> Result<Vec<DeviceInfo>> {
         debug!("列出设备");
 
-        // TODO: 实现 DeviceService.ListDevices RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "列出设备", async {
+            // TODO: 实现 DeviceService.ListDevices RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(vec![])
+            Ok(vec![])
+        })
+        .await
+    }
+
+    /// 吊销设备（远程登出），返回服务器消息
+    pub async fn remove_device(&self, device_id: Uuid) -> Result<String> {
+        debug!("吊销设备: {}", device_id);
+
+        self.call(RpcKind::Unary, "吊销设备", async {
+            // TODO: 实现 DeviceService.RemoveDevice RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok("设备已吊销".to_string())
+        })
+        .await
     }
 
     /// 上报文件变更
     #[allow(dead_code)]
     pub async fn report_changes(&self, changes: Vec<FileChange>) -> Result<ReportChangesResponse> {
         debug!("上报 {} 个文件变更", changes.len());
-
-        // TODO: 实现 FileSyncService.ReportChanges RPC 调用
-        // 需要等待 protobuf 代码生成
-
-        Ok(ReportChangesResponse {
-            success: true,
-            message: "变更上报成功".to_string(),
-            conflicts_detected: vec![],
-            pending_uploads: vec![],
+        for change in &changes {
+            validate_remote_path(&change.file_path)?;
+        }
+
+        self.call(RpcKind::Unary, "上报文件变更", async {
+            // TODO: 实现 FileSyncService.ReportChanges RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(ReportChangesResponse {
+                success: true,
+                message: "变更上报成功".to_string(),
+                conflicts_detected: vec![],
+                pending_uploads: vec![],
+            })
         })
+        .await
     }
 
     /// 获取远程变更
@@ -163,10 +300,13 @@ impl GrpcClient {
     ) -> Result<Vec<FileChange>> {
         debug!("获取远程变更，版本: {}", since_version);
 
-        // TODO: 实现 FileSyncService.FetchChanges RPC 调用
-        // 需要等待 protobuf 代码生成
+        self.call(RpcKind::Unary, "获取远程变更", async {
+            // TODO: 实现 FileSyncService.FetchChanges RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(vec![])
+            Ok(vec![])
+        })
+        .await
     }
 
     /// 上传文件（流式）
@@ -176,19 +316,42 @@ impl GrpcClient {
         file_path: String,
         _file_hash: String,
         file_size: u64,
-        _content: Vec<u8>,
+        _file_mode: Option<u32>,
+        _content_type: String,
+        mut chunks: UploadChunks,
     ) -> Result<UploadFileResponse> {
         debug!("上传文件: {:?}, 大小: {} 字节", file_path, file_size);
+        validate_remote_path(&file_path)?;
+
+        self.call(RpcKind::Upload, "上传文件", async {
+            // TODO: 实现 FileSyncService.UploadFile RPC 调用（流式）
+            // 需要等待 protobuf 代码生成；元数据之后按顺序写入分块流中的分块
+            while let Some(chunk) = chunks.recv().await {
+                let chunk = chunk?;
+                debug!("上传分块: 偏移 {}, {} 字节", chunk.offset, chunk.data.len());
+            }
+
+            Ok(UploadFileResponse {
+                success: true,
+                message: "文件上传成功".to_string(),
+                version_id: Uuid::new_v4().to_string(),
+                version_number: 1,
+            })
+        })
+        .await
+    }
 
-        // TODO: 实现 FileSyncService.UploadFile RPC 调用（流式）
-        // 需要等待 protobuf 代码生成
+    /// 查询服务器是否已保存指定哈希的内容
+    pub async fn has_content(&self, file_hash: String) -> Result<bool> {
+        debug!("查询服务器内容: {}", file_hash);
+
+        self.call(RpcKind::Unary, "查询服务器内容", async {
+            // TODO: 实现 FileSyncService.HasContent RPC 调用
+            // 需要等待 protobuf 代码生成
 
-        Ok(UploadFileResponse {
-            success: true,
-            message: "文件上传成功".to_string(),
-            version_id: Uuid::new_v4().to_string(),
-            version_number: 1,
+            Ok(false)
         })
+        .await
     }
 
     /// 下载文件（流式）
@@ -199,17 +362,124 @@ impl GrpcClient {
         _version_number: Option<i64>,
     ) -> Result<DownloadFileData> {
         debug!("下载文件: {:?}", file_path);
+        validate_remote_path(&file_path)?;
+
+        self.call(RpcKind::Download, "下载文件", async {
+            // TODO: 实现 FileSyncService.DownloadFile RPC 调用（流式）
+            // 需要等待 protobuf 代码生成
+
+            Ok(DownloadFileData {
+                file_path,
+                file_hash: "dummy_hash".to_string(),
+                file_size: 0,
+                content: vec![],
+                version: 1,
+                file_mode: None,
+                content_type: None,
+            })
+        })
+        .await
+    }
 
-        // TODO: 实现 FileSyncService.DownloadFile RPC 调用（流式）
-        // 需要等待 protobuf 代码生成
+    /// 获取文件历史版本（按版本号倒序）
+    #[allow(dead_code)]
+    pub async fn get_file_history(
+        &self,
+        file_path: String,
+        limit: i32,
+    ) -> Result<Vec<FileVersionInfo>> {
+        debug!("获取文件历史: {:?}, 数量: {}", file_path, limit);
+        validate_remote_path(&file_path)?;
+
+        self.call(RpcKind::Unary, "获取文件历史", async {
+            // TODO: 实现 FileSyncService.GetFileHistory RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(vec![])
+        })
+        .await
+    }
+
+    /// 将服务器上的文件恢复到指定版本
+    #[allow(dead_code)]
+    pub async fn restore_file_version(
+        &self,
+        file_path: String,
+        version_number: i32,
+    ) -> Result<RestoreFileResponse> {
+        debug!("恢复文件: {:?}, 版本: {}", file_path, version_number);
+        validate_remote_path(&file_path)?;
+
+        self.call(RpcKind::Unary, "恢复文件", async {
+            // TODO: 实现 FileSyncService.RestoreFileVersion RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(RestoreFileResponse {
+                success: true,
+                message: "文件恢复成功".to_string(),
+                version_number: version_number as i64,
+            })
+        })
+        .await
+    }
+
+    /// 按保留策略清理服务器上的旧版本（dry_run 时只返回将被清理的版本）
+    pub async fn prune_versions(
+        &self,
+        keep_versions: u32,
+        retention_days: u32,
+        dry_run: bool,
+    ) -> Result<PruneVersionsResponse> {
+        debug!(
+            "清理旧版本: 保留 {} 个, {} 天, dry_run: {}",
+            keep_versions, retention_days, dry_run
+        );
+
+        self.call(RpcKind::Unary, "清理旧版本", async {
+            // TODO: 实现 FileSyncService.PruneVersions RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(PruneVersionsResponse {
+                pruned_versions: vec![],
+                deleted_objects: 0,
+                freed_bytes: 0,
+            })
+        })
+        .await
+    }
 
-        Ok(DownloadFileData {
-            file_path,
-            file_hash: "dummy_hash".to_string(),
-            file_size: 0,
-            content: vec![],
-            version: 1,
+    /// 按路径前缀搜索文件，include_content 时同时搜索已索引的文本内容
+    pub async fn search_files(
+        &self,
+        query: String,
+        include_content: bool,
+        limit: u32,
+    ) -> Result<Vec<SearchMatch>> {
+        debug!(
+            "搜索文件: {}, 包含内容: {}, limit: {}",
+            query, include_content, limit
+        );
+
+        self.call(RpcKind::Unary, "搜索文件", async {
+            // TODO: 实现 FileSyncService.SearchFiles RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(vec![])
         })
+        .await
+    }
+
+    /// 获取服务器上的同步规则（全局规则和指定设备的规则）
+    pub async fn list_rules(&self, device_id: Uuid) -> Result<Vec<ServerRule>> {
+        debug!("获取服务器同步规则，设备: {}", device_id);
+
+        self.call(RpcKind::Unary, "获取同步规则", async {
+            // TODO: 实现 SyncRuleService.ListRules RPC 调用
+            // 需要等待 protobuf 代码生成
+
+            Ok(vec![])
+        })
+        .await
     }
 
     /// 订阅文件变更通知
@@ -227,13 +497,18 @@ impl GrpcClient {
         Ok(rx)
     }
 
-    /// 心跳保活
-    #[allow(dead_code)]
-    pub async fn heartbeat(&self) -> Result<()> {
+    /// 心跳保活（双向流：从 requests 发送心跳，返回服务器的心跳响应）
+    pub async fn heartbeat(
+        &self,
+        _requests: tokio::sync::mpsc::Receiver<HeartbeatRequest>,
+    ) -> Result<tokio::sync::mpsc::Receiver<HeartbeatResponse>> {
+        debug!("打开心跳流");
+
         // TODO: 实现 NotificationService.Heartbeat RPC 调用
         // 需要等待 protobuf 代码生成
 
-        Ok(())
+        let (_tx, rx) = tokio::sync::mpsc::channel(100);
+        Ok(rx)
     }
 }
 
@@ -257,6 +532,7 @@ pub struct LoginResponse {
 #[derive(Debug, Clone)]
 pub struct TokenRefreshResponse {
     pub access_token: String,
+    pub expires_at: i64,
     pub message: String,
 }
 
@@ -281,6 +557,7 @@ pub struct FileChange {
     pub file_size: u64,
     pub modified_at: i64,
     pub version: i64,
+    pub is_deleted: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -306,6 +583,23 @@ pub struct DownloadFileData {
     pub file_size: u64,
     pub content: Vec<u8>,
     pub version: i64,
+    pub file_mode: Option<u32>,
+    /// 上传时记录的 MIME 类型（服务器未返回时为 None）
+    pub content_type: Option<String>,
+}
+
+/// 服务器上的同步规则
+#[derive(Debug, Clone)]
+pub struct ServerRule {
+    pub rule_id: String,
+    pub rule_name: String,
+    /// include 或 exclude
+    pub rule_type: String,
+    pub pattern: String,
+    pub file_type: Option<String>,
+    pub priority: i32,
+    /// 是否为本设备专属的规则（否则为全局规则）
+    pub device_specific: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -316,12 +610,361 @@ pub struct ChangeNotification {
     pub timestamp: i64,
 }
 
+#[derive(Debug, Clone)]
+pub struct HeartbeatRequest {
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeartbeatResponse {
+    pub timestamp: i64,
+    pub pending_changes: Vec<FileChange>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileVersionInfo {
+    pub version_id: String,
+    pub version_number: i64,
+    pub file_path: String,
+    pub file_hash: String,
+    pub file_size: u64,
+    pub device_id: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub file_mode: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    pub file_path: String,
+    pub version_number: i64,
+    pub file_size: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// 内容匹配时包含查询词的行（路径匹配时为 None）
+    pub snippet: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PruneVersionsResponse {
+    pub pruned_versions: Vec<FileVersionInfo>,
+    pub deleted_objects: u32,
+    pub freed_bytes: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RestoreFileResponse {
+    pub success: bool,
+    pub message: String,
+    pub version_number: i64,
+}
+
+/// 根据服务器配置加载 TLS 配置（未启用 TLS 时返回 None）
+pub fn load_tls_config(server: &ServerConfig) -> Result<Option<ClientTlsConfig>> {
+    server.validate_tls()?;
+
+    if !server.tls_enabled {
+        return Ok(None);
+    }
+
+    let mut tls_config = ClientTlsConfig::new();
+
+    if let Some(ca_path) = &server.tls_cert_path {
+        let ca_pem =
+            std::fs::read(ca_path).with_context(|| format!("无法读取 CA 证书: {}", ca_path))?;
+        tls_config = tls_config.ca_certificate(Certificate::from_pem(ca_pem));
+    }
+
+    if let (Some(cert_path), Some(key_path)) =
+        (&server.tls_client_cert_path, &server.tls_client_key_path)
+    {
+        let cert_pem = std::fs::read(cert_path)
+            .with_context(|| format!("无法读取客户端证书: {}", cert_path))?;
+        let key_pem =
+            std::fs::read(key_path).with_context(|| format!("无法读取客户端私钥: {}", key_path))?;
+        tls_config = tls_config.identity(Identity::from_pem(cert_pem, key_pem));
+    }
+
+    Ok(Some(tls_config))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::config::ClientConfig;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context as TaskContext, Poll};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tonic::codegen::http;
+    use tonic::transport::{Server, ServerTlsConfig};
+    use tower::{Service, ServiceExt};
+
+    /// 只用于完成握手的空服务
+    #[derive(Clone)]
+    struct MockService;
+
+    impl tonic::server::NamedService for MockService {
+        const NAME: &'static str = "claude_sync.Mock";
+    }
+
+    impl tower::Service<http::Request<tonic::transport::Body>> for MockService {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: http::Request<tonic::transport::Body>) -> Self::Future {
+            std::future::ready(Ok(http::Response::new(tonic::body::empty_body())))
+        }
+    }
+
+    /// 生成测试 CA 和由其签发的 localhost 服务器证书
+    fn generate_certs() -> (String, String, String) {
+        let mut ca_params = rcgen::CertificateParams::new(vec![]);
+        ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        ca_params.distinguished_name = rcgen::DistinguishedName::new();
+        ca_params
+            .distinguished_name
+            .push(rcgen::DnType::CommonName, "Claude Sync Test CA");
+        let ca = rcgen::Certificate::from_params(ca_params).unwrap();
+
+        let server = rcgen::Certificate::from_params(rcgen::CertificateParams::new(vec![
+            "localhost".to_string(),
+        ]))
+        .unwrap();
+
+        (
+            ca.serialize_pem().unwrap(),
+            server.serialize_pem_with_signer(&ca).unwrap(),
+            server.serialize_private_key_pem(),
+        )
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_grpc_client_connection() {
         // 测试 gRPC 连接
         // 需要实际服务器运行
     }
+
+    #[tokio::test]
+    async fn test_tls_handshake_with_custom_ca() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let (ca_pem, server_pem, server_key) = generate_certs();
+        let ca_path = temp_dir.path().join("ca.pem");
+        std::fs::write(&ca_path, &ca_pem).unwrap();
+
+        // 启动 TLS 模拟服务器
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = ([127, 0, 0, 1], port).into();
+        let server = Server::builder()
+            .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(server_pem, server_key)))
+            .unwrap()
+            .add_service(MockService)
+            .serve(addr);
+        let handle = tokio::spawn(server);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        let mut config = ClientConfig::default().server;
+        config.address = format!("https://localhost:{}", port);
+        config.tls_enabled = true;
+
+        // 未配置 CA 时证书不受信任
+        assert!(GrpcClient::new(&config).await.is_err());
+
+        // 使用配置的 CA 握手成功
+        config.tls_cert_path = Some(ca_path.to_string_lossy().to_string());
+        let client = GrpcClient::new(&config).await;
+        assert!(client.is_ok(), "TLS 握手失败: {:?}", client.err());
+
+        // 连接池使用同样的 TLS 配置
+        let pool = crate::connection_pool::ConnectionPool::new(
+            config.address.clone(),
+            crate::connection_pool::PoolConfig::from_server_config(&config).unwrap(),
+        );
+        let conn = pool.acquire().await;
+        assert!(conn.is_ok(), "连接池 TLS 握手失败: {:?}", conn.err());
+
+        handle.abort();
+    }
+
+    /// 模拟 NAT：转发 TCP 流量，双向都没有流量超过 `idle_timeout` 时断开连接
+    ///
+    /// 返回监听端口和已接受的连接数。
+    async fn spawn_idle_dropping_proxy(
+        upstream: u16,
+        idle_timeout: Duration,
+    ) -> (u16, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((inbound, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let outbound = tokio::net::TcpStream::connect(("127.0.0.1", upstream))
+                    .await
+                    .unwrap();
+                tokio::spawn(forward_until_idle(inbound, outbound, idle_timeout));
+            }
+        });
+        (port, accepted)
+    }
+
+    async fn forward_until_idle(
+        inbound: tokio::net::TcpStream,
+        outbound: tokio::net::TcpStream,
+        idle_timeout: Duration,
+    ) {
+        let (mut in_read, mut in_write) = inbound.into_split();
+        let (mut out_read, mut out_write) = outbound.into_split();
+        let (mut in_buf, mut out_buf) = (vec![0u8; 8192], vec![0u8; 8192]);
+        loop {
+            let chunk = tokio::time::timeout(idle_timeout, async {
+                tokio::select! {
+                    n = in_read.read(&mut in_buf) => (n, true),
+                    n = out_read.read(&mut out_buf) => (n, false),
+                }
+            })
+            .await;
+            // 空闲超时或任一方关闭时丢弃连接
+            let ok = match chunk {
+                Ok((Ok(n), true)) if n > 0 => out_write.write_all(&in_buf[..n]).await.is_ok(),
+                Ok((Ok(n), false)) if n > 0 => in_write.write_all(&out_buf[..n]).await.is_ok(),
+                _ => false,
+            };
+            if !ok {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_keeps_idle_connection_through_nat() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let handle = tokio::spawn(
+            Server::builder()
+                .add_service(MockService)
+                .serve(([127, 0, 0, 1], port).into()),
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let (proxy_port, accepted) =
+            spawn_idle_dropping_proxy(port, Duration::from_millis(600)).await;
+
+        let address = format!("http://127.0.0.1:{}", proxy_port);
+        // 配置以秒为单位，测试中直接缩短 PING 间隔
+        let keepalive = Keepalive {
+            interval: Duration::from_millis(200),
+            timeout: Duration::from_secs(5),
+        };
+        let endpoint = keepalive.apply(Channel::from_shared(address.clone()).unwrap());
+        let mut channel = endpoint.connect().await.unwrap();
+
+        let request = || {
+            http::Request::builder()
+                .uri(format!("{}/claude_sync.Mock/Ping", address))
+                .body(tonic::body::empty_body())
+                .unwrap()
+        };
+        channel.ready().await.unwrap();
+        assert!(channel.call(request()).await.is_ok());
+
+        // 空闲时间超过 NAT 回收时间，保活 PING 使连接保持可用
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        channel.ready().await.unwrap();
+        assert!(channel.call(request()).await.is_ok());
+        assert_eq!(accepted.load(Ordering::SeqCst), 1, "空闲连接被断开后重连");
+
+        handle.abort();
+    }
+
+    #[test]
+    fn test_keepalive_from_config() {
+        let mut config = ClientConfig::default().server;
+        assert_eq!(
+            Keepalive::from_config(&config),
+            Some(Keepalive {
+                interval: Duration::from_secs(30),
+                timeout: Duration::from_secs(10),
+            })
+        );
+
+        config.keepalive_interval = 0;
+        assert_eq!(Keepalive::from_config(&config), None);
+    }
+
+    #[tokio::test]
+    async fn test_short_rpc_timeout_does_not_limit_uploads() {
+        let client = GrpcClient {
+            channel: Channel::from_static("http://127.0.0.1:50051").connect_lazy(),
+            server_address: "http://127.0.0.1:50051".to_string(),
+            access_token: None,
+            timeouts: RpcTimeouts {
+                rpc: Duration::from_millis(50),
+                upload: Duration::from_secs(5),
+                download: Duration::from_millis(50),
+            },
+        };
+        // 模拟耗时 200 毫秒的服务器响应
+        let slow_response = || async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(())
+        };
+
+        let err = client
+            .call(RpcKind::Unary, "查询服务器内容", slow_response())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::Timeout { .. })
+        ));
+        assert!(client
+            .call(RpcKind::Upload, "上传文件", slow_response())
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_timeouts_fall_back_to_request_timeout() {
+        let mut config = ClientConfig::default().server;
+        config.request_timeout = 300;
+        config.rpc_timeout = Some(10);
+        config.upload_timeout = Some(3600);
+
+        let timeouts = RpcTimeouts::from_config(&config);
+        assert_eq!(timeouts.for_kind(RpcKind::Unary), Duration::from_secs(10));
+        assert_eq!(
+            timeouts.for_kind(RpcKind::Upload),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            timeouts.for_kind(RpcKind::Download),
+            Duration::from_secs(300)
+        );
+    }
+
+    #[test]
+    fn test_plaintext_address_with_tls_rejected() {
+        let mut config = ClientConfig::default().server;
+        config.address = "http://localhost:50051".to_string();
+        config.tls_enabled = true;
+
+        assert!(load_tls_config(&config).is_err());
+
+        config.tls_enabled = false;
+        assert!(load_tls_config(&config).unwrap().is_none());
+    }
 }
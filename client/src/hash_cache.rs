@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+use crate::content_hash::HashAlgorithm;
+use crate::transfer::write_atomic;
+
+/// 哈希缓存文件名（与同步状态快照位于同一目录）
+pub const HASH_CACHE_FILE_NAME: &str = "hash_cache.json";
+
+/// 缓存的文件哈希（大小或修改时间变化即失效）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedHash {
+    size: u64,
+    modified: DateTime<Utc>,
+    hash: String,
+}
+
+/// 本地文件哈希缓存
+///
+/// 按 (路径, 大小, 修改时间) 记忆内容哈希，未变化的文件无需再次读取；切换哈希算法后旧条目失效。
+/// 与同步状态不同，缓存只是计算结果的备忘，丢失或损坏时重新计算即可。
+pub struct HashCache {
+    /// 持久化路径
+    path: PathBuf,
+
+    /// 缓存条目
+    entries: Mutex<HashMap<PathBuf, CachedHash>>,
+
+    /// 实际读取文件计算哈希的次数
+    file_reads: AtomicU64,
+
+    /// 哈希算法
+    algorithm: HashAlgorithm,
+}
+
+impl HashCache {
+    /// 创建空缓存，持久化到 path
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            entries: Mutex::new(HashMap::new()),
+            file_reads: AtomicU64::new(0),
+            algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    /// 设置哈希算法
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// 从磁盘加载缓存，返回条目数（文件不存在或无法解析时从空缓存开始）
+    pub async fn load(&self) -> Result<usize> {
+        let content = match tokio::fs::read(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e).with_context(|| format!("无法读取哈希缓存: {:?}", self.path)),
+        };
+
+        let loaded: HashMap<PathBuf, CachedHash> = match serde_json::from_slice(&content) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!("哈希缓存已损坏，重新计算: {:?}: {}", self.path, e);
+                return Ok(0);
+            }
+        };
+
+        let count = loaded.len();
+        self.entries.lock().unwrap().extend(loaded);
+        debug!("已加载 {} 条哈希缓存", count);
+        Ok(count)
+    }
+
+    /// 保存缓存（不再存在的文件不写入）
+    pub async fn save(&self) -> Result<()> {
+        let content = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|path, _| path.is_file());
+            serde_json::to_vec(&*entries).context("无法序列化哈希缓存")?
+        };
+
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        write_atomic(&self.path, content)
+            .await
+            .with_context(|| format!("无法写入哈希缓存: {:?}", self.path))
+    }
+
+    /// 计算文件哈希，大小和修改时间与缓存一致时直接返回缓存结果
+    pub fn hash_file(&self, path: &Path) -> Result<String> {
+        let metadata =
+            std::fs::metadata(path).with_context(|| format!("无法获取文件元信息: {:?}", path))?;
+        let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+
+        if let Some(modified) = modified {
+            let entries = self.entries.lock().unwrap();
+            if let Some(cached) = entries.get(path) {
+                if cached.size == metadata.len()
+                    && cached.modified == modified
+                    && HashAlgorithm::of(&cached.hash) == self.algorithm
+                {
+                    return Ok(cached.hash.clone());
+                }
+            }
+        }
+
+        let content = std::fs::read(path).with_context(|| format!("无法读取文件: {:?}", path))?;
+        self.file_reads.fetch_add(1, Ordering::Relaxed);
+        let hash = self.algorithm.hash(&content);
+
+        // 没有修改时间的平台上无法判断是否变化，不缓存
+        if let Some(modified) = modified {
+            self.entries.lock().unwrap().insert(
+                path.to_path_buf(),
+                CachedHash {
+                    size: content.len() as u64,
+                    modified,
+                    hash: hash.clone(),
+                },
+            );
+        }
+
+        Ok(hash)
+    }
+
+    /// 实际读取文件计算哈希的次数
+    pub fn file_reads(&self) -> u64 {
+        self.file_reads.load(Ordering::Relaxed)
+    }
+}
+
+/// 同步状态快照对应的哈希缓存路径
+pub fn hash_cache_path(state_file: &Path) -> PathBuf {
+    state_file.with_file_name(HASH_CACHE_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_invalidated_when_file_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("a.md");
+        std::fs::write(&file, "v1").unwrap();
+
+        let cache = HashCache::new(temp_dir.path().join(HASH_CACHE_FILE_NAME));
+        let first = cache.hash_file(&file).unwrap();
+        assert_eq!(cache.hash_file(&file).unwrap(), first);
+        assert_eq!(cache.file_reads(), 1);
+
+        // 大小变化后重新读取
+        std::fs::write(&file, "version 2").unwrap();
+        let second = cache.hash_file(&file).unwrap();
+        assert_ne!(second, first);
+        assert_eq!(cache.file_reads(), 2);
+
+        // 持久化后新缓存直接命中
+        cache.save().await.unwrap();
+        let reloaded = HashCache::new(temp_dir.path().join(HASH_CACHE_FILE_NAME));
+        assert_eq!(reloaded.load().await.unwrap(), 1);
+        assert_eq!(reloaded.hash_file(&file).unwrap(), second);
+        assert_eq!(reloaded.file_reads(), 0);
+
+        // 切换算法后旧条目失效，按新算法重新计算
+        let blake3 = HashCache::new(temp_dir.path().join(HASH_CACHE_FILE_NAME))
+            .with_algorithm(HashAlgorithm::Blake3);
+        blake3.load().await.unwrap();
+        assert_eq!(
+            blake3.hash_file(&file).unwrap(),
+            HashAlgorithm::Blake3.hash(b"version 2")
+        );
+        assert_eq!(blake3.file_reads(), 1);
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::Result;
+use chrono::Utc;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+use crate::grpc_client::{GrpcClient, HeartbeatRequest, HeartbeatResponse};
+use crate::retry::RetryConfig;
+use crate::subscriber::apply_notified_changes;
+use crate::sync::{RemoteChangeSource, SyncEngine};
+
+/// 心跳流（守护进程使用 GrpcClient 的 Heartbeat 双向流，测试中可替换为模拟服务器）
+pub trait HeartbeatSource: Send + Sync {
+    /// 打开心跳流：从 requests 发送心跳，返回服务器的响应（发送端关闭即表示流已断开）
+    fn open_heartbeat(
+        &self,
+        requests: mpsc::Receiver<HeartbeatRequest>,
+    ) -> impl Future<Output = Result<mpsc::Receiver<HeartbeatResponse>>> + Send;
+}
+
+impl HeartbeatSource for GrpcClient {
+    fn open_heartbeat(
+        &self,
+        requests: mpsc::Receiver<HeartbeatRequest>,
+    ) -> impl Future<Output = Result<mpsc::Receiver<HeartbeatResponse>>> + Send {
+        self.heartbeat(requests)
+    }
+}
+
+/// 心跳保活任务
+///
+/// 按固定间隔向服务器发送心跳，使本设备保持在线；响应中带有待处理的变更时拉取并应用。
+/// 心跳流断开后按退避延迟重新建立。
+pub struct HeartbeatTask {
+    sync_engine: Arc<SyncEngine>,
+    interval: Duration,
+    backoff: RetryConfig,
+}
+
+impl HeartbeatTask {
+    /// 创建心跳任务
+    pub fn new(sync_engine: Arc<SyncEngine>, interval: Duration) -> Self {
+        Self {
+            sync_engine,
+            interval,
+            backoff: RetryConfig::default(),
+        }
+    }
+
+    /// 设置心跳流断开后的重连退避参数
+    pub fn with_backoff(mut self, backoff: RetryConfig) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// 持续发送心跳，直到同步引擎关闭
+    pub async fn run<S>(&self, source: &S)
+    where
+        S: HeartbeatSource + RemoteChangeSource,
+    {
+        let mut attempt = 0;
+
+        while !self.sync_engine.is_closed() {
+            let (request_tx, request_rx) = mpsc::channel(1);
+            match source.open_heartbeat(request_rx).await {
+                Ok(responses) => {
+                    info!("心跳流已建立，间隔 {} 秒", self.interval.as_secs());
+                    if self
+                        .keep_alive(source, request_tx, responses, &mut attempt)
+                        .await
+                    {
+                        return;
+                    }
+                    warn!("心跳流已断开");
+                }
+                Err(e) => warn!("建立心跳流失败: {:#}", e),
+            }
+
+            let delay = self.backoff.calculate_delay(attempt);
+            attempt += 1;
+            info!("{} 毫秒后重新建立心跳流", delay.as_millis());
+            sleep(delay).await;
+        }
+    }
+
+    /// 在一个心跳流上发送心跳并处理响应，同步引擎关闭时返回 true，流断开时返回 false
+    async fn keep_alive<S: RemoteChangeSource>(
+        &self,
+        source: &S,
+        request_tx: mpsc::Sender<HeartbeatRequest>,
+        mut responses: mpsc::Receiver<HeartbeatResponse>,
+        attempt: &mut usize,
+    ) -> bool {
+        let mut ticker = tokio::time::interval(self.interval);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    if self.sync_engine.is_closed() {
+                        return true;
+                    }
+                    let request = HeartbeatRequest {
+                        timestamp: Utc::now().timestamp(),
+                    };
+                    if request_tx.send(request).await.is_err() {
+                        return false;
+                    }
+                }
+                response = responses.recv() => match response {
+                    Some(response) => {
+                        *attempt = 0;
+                        self.handle_response(source, response).await;
+                    }
+                    None => return false,
+                },
+            }
+        }
+    }
+
+    /// 处理心跳响应：有游标之后的待处理变更时拉取并应用
+    async fn handle_response<S: RemoteChangeSource>(
+        &self,
+        source: &S,
+        response: HeartbeatResponse,
+    ) {
+        let cursor = self.sync_engine.version_cursor();
+        let pending = response
+            .pending_changes
+            .iter()
+            .filter(|change| change.version > cursor)
+            .count();
+        if pending == 0 {
+            debug!("心跳响应 ({}): 没有待处理的变更", response.timestamp);
+            return;
+        }
+
+        info!("心跳响应中有 {} 个待处理的变更", pending);
+        apply_notified_changes(&self.sync_engine, source).await;
+    }
+}
+
+/// 在后台运行心跳任务
+pub fn spawn_heartbeat_task<S>(task: HeartbeatTask, source: Arc<S>) -> tokio::task::JoinHandle<()>
+where
+    S: HeartbeatSource + RemoteChangeSource + 'static,
+{
+    tokio::spawn(async move { task.run(source.as_ref()).await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_client::{DownloadFileData, FileChange};
+    use crate::sync::test_support::create_engine;
+    use crate::transfer::TransferManager;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    #[derive(Default)]
+    struct MockState {
+        files: Vec<(i64, String, Vec<u8>)>,
+        pending: Vec<FileChange>,
+        heartbeats: usize,
+        last_heartbeat: Option<Instant>,
+    }
+
+    /// 模拟心跳服务器：收到心跳后刷新设备在线状态，并在响应中带上待处理的变更
+    struct MockHeartbeatServer {
+        state: Arc<Mutex<MockState>>,
+        online_timeout: Duration,
+    }
+
+    impl MockHeartbeatServer {
+        fn new(online_timeout: Duration) -> Self {
+            Self {
+                state: Arc::default(),
+                online_timeout,
+            }
+        }
+
+        /// 另一台设备上传了文件，下次心跳响应时通知
+        fn push(&self, version: i64, path: &str, content: &str) {
+            let mut state = self.state.lock().unwrap();
+            state
+                .files
+                .push((version, path.to_string(), content.as_bytes().to_vec()));
+            state.pending.push(FileChange {
+                file_path: path.to_string(),
+                file_hash: TransferManager::calculate_hash(content.as_bytes()).unwrap(),
+                file_size: content.len() as u64,
+                modified_at: 0,
+                version,
+                is_deleted: false,
+            });
+        }
+
+        /// 设备是否在线（超时内收到过心跳）
+        fn is_online(&self) -> bool {
+            self.state
+                .lock()
+                .unwrap()
+                .last_heartbeat
+                .is_some_and(|at| at.elapsed() < self.online_timeout)
+        }
+
+        fn heartbeats(&self) -> usize {
+            self.state.lock().unwrap().heartbeats
+        }
+    }
+
+    impl HeartbeatSource for MockHeartbeatServer {
+        async fn open_heartbeat(
+            &self,
+            mut requests: mpsc::Receiver<HeartbeatRequest>,
+        ) -> Result<mpsc::Receiver<HeartbeatResponse>> {
+            let (tx, rx) = mpsc::channel(16);
+            let state = self.state.clone();
+            tokio::spawn(async move {
+                while let Some(request) = requests.recv().await {
+                    let pending_changes = {
+                        let mut state = state.lock().unwrap();
+                        state.heartbeats += 1;
+                        state.last_heartbeat = Some(Instant::now());
+                        std::mem::take(&mut state.pending)
+                    };
+                    let response = HeartbeatResponse {
+                        timestamp: request.timestamp,
+                        pending_changes,
+                    };
+                    if tx.send(response).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Ok(rx)
+        }
+    }
+
+    impl RemoteChangeSource for MockHeartbeatServer {
+        async fn changes_since(&self, since_version: i64) -> Result<Vec<FileChange>> {
+            Ok(self
+                .state
+                .lock()
+                .unwrap()
+                .files
+                .iter()
+                .filter(|(version, _, _)| *version > since_version)
+                .map(|(version, path, content)| FileChange {
+                    file_path: path.clone(),
+                    file_hash: TransferManager::calculate_hash(content).unwrap(),
+                    file_size: content.len() as u64,
+                    modified_at: 0,
+                    version: *version,
+                    is_deleted: false,
+                })
+                .collect())
+        }
+
+        async fn download_latest(&self, file_path: String) -> Result<DownloadFileData> {
+            let state = self.state.lock().unwrap();
+            let (version, _, content) = state
+                .files
+                .iter()
+                .rev()
+                .find(|(_, path, _)| *path == file_path)
+                .unwrap();
+            Ok(DownloadFileData {
+                file_path,
+                file_hash: TransferManager::calculate_hash(content).unwrap(),
+                file_size: content.len() as u64,
+                content: content.clone(),
+                version: *version,
+                file_mode: None,
+                content_type: None,
+            })
+        }
+    }
+
+    /// 轮询直到条件成立（超时则测试失败）
+    async fn wait_until(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("等待条件超时");
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_keeps_device_online_and_applies_pending_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let engine = Arc::new(create_engine(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+        ));
+        let server = Arc::new(MockHeartbeatServer::new(Duration::from_millis(200)));
+        assert!(!server.is_online());
+
+        let task = HeartbeatTask::new(engine.clone(), Duration::from_millis(20));
+        let handle = spawn_heartbeat_task(task, server.clone());
+
+        // 持续心跳期间设备保持在线
+        wait_until(|| server.heartbeats() >= 3).await;
+        assert!(server.is_online());
+
+        // 心跳响应中的待处理变更被下载到本地
+        server.push(1, "settings.json", "{\"theme\":\"dark\"}");
+        let settings = claude_dir.join("settings.json");
+        wait_until(|| {
+            std::fs::read_to_string(&settings).is_ok_and(|c| c == "{\"theme\":\"dark\"}")
+        })
+        .await;
+        assert_eq!(engine.version_cursor(), 1);
+
+        // 停止心跳后超时变为离线
+        handle.abort();
+        wait_until(|| !server.is_online()).await;
+    }
+}
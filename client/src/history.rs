@@ -0,0 +1,204 @@
+use crate::grpc_client::{FileVersionInfo, SearchMatch};
+use anyhow::Result;
+
+/// 哈希前缀显示长度
+const HASH_PREFIX_LEN: usize = 12;
+
+/// 默认返回的版本数量
+pub const DEFAULT_HISTORY_LIMIT: i64 = 20;
+
+/// 校验版本号（服务器版本号从 1 开始，且为 32 位整数）
+pub fn validate_version_number(version: i64) -> Result<i32> {
+    if version < 1 {
+        anyhow::bail!("版本号必须大于 0: {}", version);
+    }
+
+    i32::try_from(version).map_err(|_| anyhow::anyhow!("版本号超出范围: {}", version))
+}
+
+/// 校验历史记录数量限制（未指定时使用默认值）
+pub fn validate_limit(limit: Option<i64>) -> Result<i32> {
+    match limit {
+        None => Ok(DEFAULT_HISTORY_LIMIT as i32),
+        Some(limit) if limit < 1 => anyhow::bail!("limit 必须大于 0: {}", limit),
+        Some(limit) => Ok(i32::try_from(limit).unwrap_or(i32::MAX)),
+    }
+}
+
+/// 将版本列表格式化为表格
+pub fn format_history_table(versions: &[FileVersionInfo]) -> String {
+    let mut table = format!(
+        "{:<8} {:<38} {:>12} {:<14} {}\n",
+        "版本", "设备", "大小", "哈希", "时间"
+    );
+    table.push_str(&"-".repeat(96));
+    table.push('\n');
+
+    if versions.is_empty() {
+        table.push_str("(无历史版本)\n");
+        return table;
+    }
+
+    for version in versions {
+        let hash_prefix: String = version.file_hash.chars().take(HASH_PREFIX_LEN).collect();
+        table.push_str(&format!(
+            "{:<8} {:<38} {:>12} {:<14} {}\n",
+            version.version_number,
+            version.device_id,
+            version.file_size,
+            hash_prefix,
+            version.created_at.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+
+    table
+}
+
+/// 格式化被清理的版本列表
+pub fn format_prune_table(versions: &[FileVersionInfo]) -> String {
+    let mut table = format!("{:<40} {:<8} {:>12} {}\n", "文件", "版本", "大小", "时间");
+    table.push_str(&"-".repeat(84));
+    table.push('\n');
+
+    if versions.is_empty() {
+        table.push_str("(没有需要清理的版本)\n");
+        return table;
+    }
+
+    for version in versions {
+        table.push_str(&format!(
+            "{:<40} {:<8} {:>12} {}\n",
+            version.file_path,
+            version.version_number,
+            version.file_size,
+            version.created_at.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+
+    table
+}
+
+/// 格式化搜索结果（内容匹配的结果在下一行显示匹配的行）
+pub fn format_search_results(matches: &[SearchMatch]) -> String {
+    let mut table = format!("{:<48} {:<8} {:>12} {}\n", "文件", "版本", "大小", "时间");
+    table.push_str(&"-".repeat(92));
+    table.push('\n');
+
+    if matches.is_empty() {
+        table.push_str("(没有匹配的文件)\n");
+        return table;
+    }
+
+    for found in matches {
+        table.push_str(&format!(
+            "{:<48} {:<8} {:>12} {}\n",
+            found.file_path,
+            found.version_number,
+            found.file_size,
+            found.created_at.format("%Y-%m-%d %H:%M:%S")
+        ));
+        if let Some(snippet) = &found.snippet {
+            table.push_str(&format!("    {}\n", snippet));
+        }
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn version(number: i64, hash: &str, size: u64) -> FileVersionInfo {
+        FileVersionInfo {
+            version_id: format!("v{}", number),
+            version_number: number,
+            file_path: "settings.json".to_string(),
+            file_hash: hash.to_string(),
+            file_size: size,
+            device_id: "laptop".to_string(),
+            created_at: chrono::Utc.with_ymd_and_hms(2024, 5, 1, 8, 30, 0).unwrap(),
+            file_mode: None,
+        }
+    }
+
+    #[test]
+    fn test_format_history_table() {
+        let table = format_history_table(&[
+            version(2, "abcdef0123456789abcdef", 2048),
+            version(1, "0123", 10),
+        ]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("版本"));
+        assert!(lines[2].starts_with("2 "));
+        assert!(lines[2].contains("laptop"));
+        assert!(lines[2].contains("2048"));
+        assert!(lines[2].contains("abcdef012345 "));
+        assert!(!lines[2].contains("abcdef0123456"));
+        assert!(lines[2].ends_with("2024-05-01 08:30:00"));
+        assert!(lines[3].contains(" 0123 "));
+
+        let empty = format_history_table(&[]);
+        assert!(empty.contains("(无历史版本)"));
+    }
+
+    #[test]
+    fn test_format_prune_table() {
+        let table = format_prune_table(&[version(3, "aa", 100), version(1, "bb", 10)]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].starts_with("settings.json "));
+        assert!(lines[2].contains(" 3 "));
+        assert!(lines[3].ends_with("2024-05-01 08:30:00"));
+
+        assert!(format_prune_table(&[]).contains("(没有需要清理的版本)"));
+    }
+
+    #[test]
+    fn test_format_search_results() {
+        let created_at = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 8, 30, 0).unwrap();
+        let matches = vec![
+            SearchMatch {
+                file_path: "agents/reviewer.md".to_string(),
+                version_number: 3,
+                file_size: 512,
+                created_at,
+                snippet: None,
+            },
+            SearchMatch {
+                file_path: "settings.json".to_string(),
+                version_number: 7,
+                file_size: 128,
+                created_at,
+                snippet: Some("\"theme\": \"dark\",".to_string()),
+            },
+        ];
+
+        let table = format_search_results(&matches);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].starts_with("agents/reviewer.md"));
+        assert!(lines[2].contains("2024-05-01 08:30:00"));
+        assert!(lines[3].starts_with("settings.json"));
+        assert_eq!(lines[4], "    \"theme\": \"dark\",");
+
+        assert!(format_search_results(&[]).contains("没有匹配的文件"));
+    }
+
+    #[test]
+    fn test_validate_version_number() {
+        assert_eq!(validate_version_number(1).unwrap(), 1);
+        assert_eq!(validate_version_number(42).unwrap(), 42);
+        assert!(validate_version_number(0).is_err());
+        assert!(validate_version_number(-3).is_err());
+        assert!(validate_version_number(i64::from(i32::MAX) + 1).is_err());
+
+        assert_eq!(validate_limit(None).unwrap(), DEFAULT_HISTORY_LIMIT as i32);
+        assert_eq!(validate_limit(Some(5)).unwrap(), 5);
+        assert!(validate_limit(Some(0)).is_err());
+    }
+}
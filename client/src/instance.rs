@@ -0,0 +1,277 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// 守护进程锁文件名（位于配置目录）
+pub const LOCK_FILE_NAME: &str = "daemon.lock";
+
+/// 无法解析的锁文件在修改后多久内仍视为被持有（可能是旧版本正在写入）
+const UNPARSABLE_LOCK_GRACE: Duration = Duration::from_secs(10);
+
+/// 锁文件内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockInfo {
+    /// 持有锁的进程 ID
+    pub pid: u32,
+
+    /// 进程启动时间
+    pub started_at: DateTime<Utc>,
+
+    /// 进程同步的 Claude 目录
+    pub claude_dir: PathBuf,
+}
+
+/// 守护进程单实例锁
+///
+/// 启动时先把本进程 PID 写入临时文件，再硬链接为锁文件（锁文件已存在时失败），
+/// 其他实例不会读到尚未写完的锁文件；锁文件已存在时检查持有者是否仍在运行，
+/// 已退出的进程留下的锁会被清理后重新获取。锁在 drop 时删除。
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    pid: u32,
+}
+
+impl InstanceLock {
+    /// 获取锁，另一个仍在运行的实例持有锁时返回错误
+    pub fn acquire(path: impl Into<PathBuf>, claude_dir: &Path) -> Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("无法创建锁文件目录: {:?}", parent))?;
+        }
+
+        let info = LockInfo {
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            claude_dir: claude_dir.to_path_buf(),
+        };
+        let content = serde_json::to_vec_pretty(&info)?;
+
+        // 写完整内容后再链接到锁文件路径，链接是原子的
+        let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        std::fs::write(&temp_path, &content)
+            .with_context(|| format!("无法写入锁文件: {:?}", temp_path))?;
+        let linked = Self::link_lock(&temp_path, &path);
+        let _ = std::fs::remove_file(&temp_path);
+        if !linked? {
+            anyhow::bail!("无法获取守护进程锁: {:?}", path);
+        }
+
+        info!("已获取守护进程锁: {:?} (PID {})", path, info.pid);
+        Ok(Self {
+            path,
+            pid: info.pid,
+        })
+    }
+
+    /// 将临时文件链接为锁文件，锁文件被持有时返回 false
+    fn link_lock(temp_path: &Path, path: &Path) -> Result<bool> {
+        // 清理一次过期锁后重试，仍失败说明另一个实例刚刚获取了锁
+        for _ in 0..2 {
+            match std::fs::hard_link(temp_path, path) {
+                Ok(()) => return Ok(true),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    Self::remove_if_stale(path)?;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("无法创建锁文件: {:?}", path));
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// 锁文件的持有者已退出时删除锁文件，持有者仍在运行时返回错误
+    fn remove_if_stale(path: &Path) -> Result<()> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            // 持有者刚好释放了锁
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e).with_context(|| format!("无法读取锁文件: {:?}", path)),
+        };
+
+        match serde_json::from_slice::<LockInfo>(&content) {
+            Ok(holder) if process_alive(holder.pid) => {
+                anyhow::bail!(
+                    "另一个守护进程正在运行 (PID {}, 同步目录 {:?}, 启动于 {})，锁文件: {:?}",
+                    holder.pid,
+                    holder.claude_dir,
+                    holder.started_at.with_timezone(&chrono::Local),
+                    path
+                );
+            }
+            Ok(holder) => warn!(
+                "清理已退出进程 (PID {}) 留下的锁文件: {:?}",
+                holder.pid, path
+            ),
+            Err(_) if Self::recently_modified(path) => {
+                anyhow::bail!("锁文件正在被另一个实例写入: {:?}", path);
+            }
+            Err(_) => warn!("清理无法解析的锁文件: {:?}", path),
+        }
+
+        // 删除前确认锁文件未被其他实例替换
+        if std::fs::read(path).ok().as_deref() == Some(content.as_slice()) {
+            std::fs::remove_file(path)
+                .with_context(|| format!("无法删除过期的锁文件: {:?}", path))?;
+        }
+        Ok(())
+    }
+
+    /// 锁文件是否在宽限期内被修改过
+    fn recently_modified(path: &Path) -> bool {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().unwrap_or_default() < UNPARSABLE_LOCK_GRACE)
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // 只删除本进程持有的锁
+        let owned = std::fs::read(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_slice::<LockInfo>(&content).ok())
+            .is_some_and(|holder| holder.pid == self.pid);
+        if owned {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                warn!("删除锁文件失败 {:?}: {}", self.path, e);
+            }
+        }
+    }
+}
+
+/// 默认的守护进程锁文件路径（配置文件所在目录）
+pub fn default_lock_path() -> Result<PathBuf> {
+    let config_path = crate::config::ClientConfig::config_path()?;
+    let config_dir = config_path.parent().context("配置文件路径没有父目录")?;
+    Ok(config_dir.join(LOCK_FILE_NAME))
+}
+
+/// 进程是否仍在运行
+fn process_alive(pid: u32) -> bool {
+    let mut system = sysinfo::System::new();
+    system.refresh_process_specifics(
+        sysinfo::Pid::from_u32(pid),
+        sysinfo::ProcessRefreshKind::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire_and_release() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(LOCK_FILE_NAME);
+        let claude_dir = temp_dir.path().join("claude");
+
+        let lock = InstanceLock::acquire(&path, &claude_dir).unwrap();
+        let holder: LockInfo = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+        assert_eq!(holder.claude_dir, claude_dir);
+
+        // 持有者仍在运行时拒绝获取
+        let error = InstanceLock::acquire(&path, &claude_dir).unwrap_err();
+        assert!(error.to_string().contains("另一个守护进程正在运行"));
+        assert!(path.exists());
+
+        // 释放后可以重新获取
+        drop(lock);
+        assert!(!path.exists());
+        let _lock = InstanceLock::acquire(&path, &claude_dir).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_from_exited_process_is_replaced() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(LOCK_FILE_NAME);
+        let claude_dir = temp_dir.path().join("claude");
+
+        // 启动一个立即退出的子进程，取得一个已不存在的 PID
+        let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+            .arg("--list")
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        assert!(!process_alive(dead_pid));
+
+        let stale = LockInfo {
+            pid: dead_pid,
+            started_at: Utc::now(),
+            claude_dir: claude_dir.clone(),
+        };
+        std::fs::write(&path, serde_json::to_vec(&stale).unwrap()).unwrap();
+
+        let _lock = InstanceLock::acquire(&path, &claude_dir).unwrap();
+        let holder: LockInfo = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+    }
+
+    #[test]
+    fn test_recent_unparsable_lock_is_held() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(LOCK_FILE_NAME);
+        let claude_dir = temp_dir.path().join("claude");
+
+        // 另一个实例刚创建锁文件、还没写入 PID
+        std::fs::write(&path, b"").unwrap();
+        let error = InstanceLock::acquire(&path, &claude_dir).unwrap_err();
+        assert!(error.to_string().contains("正在被另一个实例写入"));
+        assert!(path.exists());
+
+        // 宽限期过后按过期锁清理
+        let modified = std::time::SystemTime::now() - UNPARSABLE_LOCK_GRACE * 2;
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let _lock = InstanceLock::acquire(&path, &claude_dir).unwrap();
+        let holder: LockInfo = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(holder.pid, std::process::id());
+    }
+
+    #[test]
+    fn test_concurrent_acquire_grants_one_lock() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(LOCK_FILE_NAME);
+        let claude_dir = temp_dir.path().join("claude");
+
+        for _ in 0..20 {
+            // 同时启动的实例不会把刚创建的锁文件当作无法解析的过期锁删除
+            let barrier = std::sync::Barrier::new(8);
+            let locks: Vec<_> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..8)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            barrier.wait();
+                            InstanceLock::acquire(&path, &claude_dir).ok()
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .filter_map(|handle| handle.join().unwrap())
+                    .collect()
+            });
+            assert_eq!(locks.len(), 1);
+            drop(locks);
+            assert!(!path.exists());
+        }
+
+        // 没有残留的临时文件
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,14 +1,31 @@
 // Claude Sync Client Library
 
+pub mod audit;
 pub mod config;
 pub mod conflict;
 pub mod connection_pool;
+pub mod content_hash;
+pub mod control;
+pub mod delta;
+pub mod doctor;
 pub mod error;
 pub mod grpc_client;
+pub mod hash_cache;
+pub mod heartbeat;
+pub mod history;
+pub mod instance;
+pub mod logging;
+pub mod metrics_server;
 pub mod monitoring;
 pub mod network;
+pub mod paths;
+pub mod poller;
+pub mod power;
+pub mod reporter;
 pub mod retry;
 pub mod rules;
+pub mod status;
+pub mod subscriber;
 pub mod sync;
 pub mod token;
 pub mod transfer;
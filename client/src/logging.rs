@@ -0,0 +1,130 @@
+// 日志初始化
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::Level;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+use crate::config::LoggingConfig;
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// 人类可读的文本
+    Text,
+    /// 每行一个 JSON 对象，便于日志采集
+    Json,
+}
+
+/// 日志订阅器参数（由 `LoggingConfig` 解析得到）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSettings {
+    pub format: LogFormat,
+    pub level: Level,
+    /// 输出文件，为空时输出到标准输出
+    pub log_file: Option<PathBuf>,
+}
+
+impl LogSettings {
+    /// 从日志配置解析参数，verbose 时日志级别至少为 DEBUG
+    pub fn from_config(config: &LoggingConfig, verbose: bool) -> Result<Self> {
+        let format = match config.format.as_str() {
+            "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            other => anyhow::bail!("无效的日志格式: {}", other),
+        };
+        let level = Level::from_str(&config.level)
+            .map_err(|_| anyhow::anyhow!("无效的日志级别: {}", config.level))?;
+        let level = if verbose {
+            level.max(Level::DEBUG)
+        } else {
+            level
+        };
+
+        Ok(Self {
+            format,
+            level,
+            log_file: config.log_file.clone(),
+        })
+    }
+}
+
+/// 初始化全局日志订阅器
+///
+/// 输出到文件时返回后台写入线程的 guard，需要持有到程序退出以免丢失日志。
+pub fn init(settings: &LogSettings) -> Result<Option<WorkerGuard>> {
+    let (writer, guard) = match &settings.log_file {
+        Some(path) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("无法创建日志目录: {:?}", parent))?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("无法打开日志文件: {:?}", path))?;
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            (BoxMakeWriter::new(writer), Some(guard))
+        }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(settings.level)
+        .with_target(false)
+        .with_ansi(settings.log_file.is_none())
+        .with_writer(writer);
+    let result = match settings.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+    result.map_err(|e| anyhow::anyhow!("初始化日志失败: {}", e))?;
+
+    Ok(guard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logging_config(level: &str, format: &str) -> LoggingConfig {
+        LoggingConfig {
+            level: level.to_string(),
+            log_file: None,
+            format: format.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_settings_from_config() {
+        let settings = LogSettings::from_config(&logging_config("warn", "json"), false).unwrap();
+        assert_eq!(settings.format, LogFormat::Json);
+        assert_eq!(settings.level, Level::WARN);
+
+        let mut config = logging_config("info", "text");
+        config.log_file = Some(PathBuf::from("/tmp/claude-sync.log"));
+        let settings = LogSettings::from_config(&config, false).unwrap();
+        assert_eq!(settings.format, LogFormat::Text);
+        assert_eq!(settings.level, Level::INFO);
+        assert_eq!(settings.log_file, config.log_file);
+    }
+
+    #[test]
+    fn test_settings_verbose_raises_level() {
+        let settings = LogSettings::from_config(&logging_config("info", "text"), true).unwrap();
+        assert_eq!(settings.level, Level::DEBUG);
+
+        // 已经比 DEBUG 更详细时保持不变
+        let settings = LogSettings::from_config(&logging_config("trace", "text"), true).unwrap();
+        assert_eq!(settings.level, Level::TRACE);
+    }
+
+    #[test]
+    fn test_settings_reject_invalid_values() {
+        assert!(LogSettings::from_config(&logging_config("loud", "text"), false).is_err());
+        assert!(LogSettings::from_config(&logging_config("info", "xml"), false).is_err());
+    }
+}
@@ -1,28 +1,51 @@
+mod audit;
 mod config;
 mod conflict;
 mod connection_pool;
+mod content_hash;
+mod control;
+mod delta;
+mod doctor;
 mod error;
 mod grpc_client;
+mod hash_cache;
+mod heartbeat;
+mod history;
+mod instance;
+mod logging;
+mod metrics_server;
 mod monitoring;
 mod network;
+mod paths;
+mod poller;
+mod power;
+mod reporter;
 mod retry;
 mod rules;
+mod status;
+mod subscriber;
 mod sync;
 mod token;
 mod transfer;
 mod watcher;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use audit::AuditLog;
 use clap::{Parser, Subcommand};
 use config::ClientConfig;
-use conflict::{ConflictResolver, ResolutionStrategy};
+use conflict::{ArrayMergeMode, ConflictMarkerStyle, ConflictResolver, ResolutionStrategy};
+use delta::BaseStore;
 use indicatif::{ProgressBar, ProgressStyle};
-use monitoring::MonitoringManager;
+use logging::LogSettings;
+use monitoring::{MonitoringManager, SummaryFormat};
 use rules::RuleEngine;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use sync::SyncEngine;
+use std::time::Duration;
+use sync::{ConflictChoice, ForcedDirection, FullSyncProgress, SyncEngine};
 use token::TokenManager;
-use tracing::{info, Level};
+use tracing::{info, warn};
 use transfer::TransferManager;
 use uuid::Uuid;
 
@@ -33,6 +56,10 @@ use uuid::Uuid;
 #[command(version = "0.1.0")]
 #[command(about = "Sync Claude CLI configuration across multiple devices", long_about = None)]
 struct Cli {
+    /// 使用的服务器配置档（覆盖配置文件中的 active_profile）
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -42,6 +69,29 @@ enum Commands {
     /// 初始化配置
     ConfigInit,
 
+    /// 校验配置文件和同步规则
+    ConfigValidate,
+
+    /// 导出完整配置和同步规则（默认去掉密钥）
+    ConfigExport {
+        /// 导出文件路径
+        file: PathBuf,
+
+        /// 同时导出密钥（如 Token 加密密钥）
+        #[arg(long)]
+        include_secrets: bool,
+    },
+
+    /// 从导出文件导入配置，校验通过后覆盖当前配置
+    ConfigImport {
+        /// 导出文件路径
+        file: PathBuf,
+
+        /// 将导入的同步规则合并到现有规则中（ID 相同的规则以导入的为准）
+        #[arg(long)]
+        merge_rules: bool,
+    },
+
     /// 登录到服务器
     Login {
         /// 邮箱
@@ -73,13 +123,130 @@ enum Commands {
         /// 显示详细输出
         #[arg(short, long)]
         verbose: bool,
+
+        /// 选择性同步的文件或目录（可重复，相对路径基于 Claude 目录）
+        #[arg(long = "path")]
+        paths: Vec<PathBuf>,
+
+        /// 演练模式：只显示将执行的操作，不做任何修改
+        #[arg(long)]
+        dry_run: bool,
+
+        /// 全量同步只处理此时间之后修改的文件（如 2h、3d 或 RFC3339 时间戳）
+        #[arg(long)]
+        since: Option<String>,
+
+        /// 本次同步额外包含匹配的文件（Glob，可重复，优先于配置规则）
+        #[arg(long = "include", value_name = "GLOB")]
+        includes: Vec<String>,
+
+        /// 本次同步额外排除匹配的文件（Glob，可重复，优先于配置规则和 --include）
+        #[arg(long = "exclude", value_name = "GLOB")]
+        excludes: Vec<String>,
     },
 
     /// 查看设备列表
     ListDevices,
 
+    /// 查看文件的历史版本
+    History {
+        /// 文件路径（相对路径基于 Claude 目录）
+        path: String,
+
+        /// 最多显示的版本数
+        #[arg(short, long)]
+        limit: Option<i64>,
+    },
+
+    /// 将文件恢复到指定版本
+    Restore {
+        /// 文件路径（相对路径基于 Claude 目录）
+        path: String,
+
+        /// 版本号
+        #[arg(long)]
+        version: i64,
+    },
+
+    /// 清理服务器上的旧版本
+    Prune {
+        /// 每个文件至少保留的最新版本数
+        #[arg(long, default_value_t = 10)]
+        keep: u32,
+
+        /// 同时保留最近多少天内的所有版本
+        #[arg(long)]
+        days: Option<u32>,
+
+        /// 只列出将被清理的版本，不实际删除
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 在服务器上搜索文件（按路径前缀，可选搜索文本内容）
+    Search {
+        /// 路径前缀或要查找的文本（如配置键名）
+        query: String,
+
+        /// 同时搜索文本文件内容
+        #[arg(long)]
+        content: bool,
+
+        /// 最多返回的结果数
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+    },
+
+    /// 吊销设备（远程登出），使其所有 Token 立即失效
+    RevokeDevice {
+        /// 设备 ID
+        device_id: String,
+    },
+
+    /// 暂停运行中的同步守护进程（期间的变更在恢复后同步）
+    Pause,
+
+    /// 恢复运行中的同步守护进程
+    Resume,
+
     /// 查看同步状态
-    Status,
+    Status {
+        /// 以 JSON 格式输出（供脚本和 GUI 解析）
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// 重新计算本地文件哈希，校验是否与同步状态一致
+    Verify,
+
+    /// 交互式解决未处理的同步冲突
+    Resolve,
+
+    /// 列出未解决的冲突和已解决冲突残留的 .conflict 文件
+    Conflicts {
+        /// 删除已解决冲突残留的 .conflict 文件
+        #[arg(long)]
+        clean: bool,
+    },
+
+    /// 以本地版本为准强制上传文件（忽略冲突检测）
+    Push {
+        /// 文件路径（相对路径基于 Claude 目录）
+        path: PathBuf,
+    },
+
+    /// 以远程版本为准强制覆盖本地文件（忽略冲突检测）
+    Pull {
+        /// 文件路径（相对路径基于 Claude 目录）
+        path: PathBuf,
+    },
+
+    /// 查看最近自动解决的冲突
+    Audit {
+        /// 最多显示的记录数
+        #[arg(short, long, default_value_t = audit::DEFAULT_AUDIT_LIMIT)]
+        limit: usize,
+    },
 
     /// 管理同步规则
     Rules {
@@ -87,9 +254,24 @@ enum Commands {
         rule_command: RuleCommands,
     },
 
+    /// 查看和清理离线队列（服务器不可达期间等待重放的操作）
+    Queue {
+        #[command(subcommand)]
+        queue_command: QueueCommands,
+    },
+
+    /// 管理服务器配置档
+    Profile {
+        #[command(subcommand)]
+        profile_command: ProfileCommands,
+    },
+
     /// 检查健康状态
     HealthCheck,
 
+    /// 诊断配置、登录状态和服务器连接
+    Doctor,
+
     /// 导出性能指标
     Metrics {
         /// 输出格式 (json/prometheus)
@@ -99,6 +281,26 @@ enum Commands {
         /// 输出文件路径（可选，默认输出到控制台）
         #[arg(short, long)]
         output: Option<String>,
+
+        /// 性能摘要的输出格式 (text/json/compact)
+        #[arg(long, default_value = "text")]
+        output_format: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueCommands {
+    /// 列出离线队列中的操作
+    List,
+
+    /// 删除离线队列中的操作
+    Clear {
+        /// 要删除的操作序号（见 queue list，可指定多个）
+        indexes: Vec<usize>,
+
+        /// 清空整个队列
+        #[arg(long, conflicts_with = "indexes")]
+        all: bool,
     },
 }
 
@@ -140,21 +342,39 @@ enum RuleCommands {
     Recommended,
 }
 
+#[derive(Subcommand, Debug)]
+enum ProfileCommands {
+    /// 列出所有配置档
+    List,
+
+    /// 切换默认使用的配置档（default 表示顶层配置）
+    Use {
+        /// 配置档名称
+        name: String,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
+    if let Some(profile) = cli.profile.clone() {
+        config::select_profile(profile);
+    }
 
-    // 初始化日志
-    let log_level = if matches!(cli.command, Commands::Sync { verbose: true, .. }) {
-        Level::DEBUG
-    } else {
-        Level::INFO
-    };
-
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
-        .init();
+    // 初始化日志（配置文件尚不存在时使用默认日志配置，不在此处创建配置文件）
+    let verbose = matches!(cli.command, Commands::Sync { verbose: true, .. });
+    let logging_config = ClientConfig::config_path()
+        .ok()
+        .filter(|path| path.exists())
+        .and_then(|path| ClientConfig::load_from(&path).ok())
+        .unwrap_or_default()
+        .logging;
+    let log_settings = LogSettings::from_config(&logging_config, verbose).unwrap_or_else(|e| {
+        eprintln!("⚠️  {:#}，使用默认日志配置", e);
+        LogSettings::from_config(&ClientConfig::default().logging, verbose)
+            .expect("默认日志配置有效")
+    });
+    let _log_guard = logging::init(&log_settings)?;
 
     info!("🚀 Claude Sync Client v0.1.0");
 
@@ -162,6 +382,18 @@ async fn main() -> Result<()> {
         Commands::ConfigInit => {
             handle_config_init().await?;
         }
+        Commands::ConfigExport {
+            file,
+            include_secrets,
+        } => {
+            handle_config_export(&file, include_secrets)?;
+        }
+        Commands::ConfigImport { file, merge_rules } => {
+            handle_config_import(&file, merge_rules)?;
+        }
+        Commands::ConfigValidate => {
+            handle_config_validate()?;
+        }
         Commands::Login {
             email,
             password,
@@ -176,24 +408,90 @@ async fn main() -> Result<()> {
             mode,
             daemon,
             verbose,
+            paths,
+            dry_run,
+            since,
+            includes,
+            excludes,
         } => {
-            handle_sync(mode, daemon, verbose).await?;
+            let adhoc_rules = rules::adhoc_rules(&includes, &excludes)?;
+            handle_sync(mode, daemon, verbose, paths, dry_run, since, adhoc_rules).await?;
         }
         Commands::ListDevices => {
             handle_list_devices().await?;
         }
-        Commands::Status => {
-            handle_status().await?;
+        Commands::History { path, limit } => {
+            handle_history(path, limit).await?;
+        }
+        Commands::Restore { path, version } => {
+            handle_restore(path, version).await?;
+        }
+        Commands::Prune {
+            keep,
+            days,
+            dry_run,
+        } => {
+            handle_prune(keep, days, dry_run).await?;
+        }
+        Commands::Search {
+            query,
+            content,
+            limit,
+        } => {
+            handle_search(query, content, limit).await?;
+        }
+        Commands::RevokeDevice { device_id } => {
+            handle_revoke_device(device_id).await?;
+        }
+        Commands::Pause => {
+            handle_control(control::ControlCommand::Pause).await?;
+        }
+        Commands::Resume => {
+            handle_control(control::ControlCommand::Resume).await?;
+        }
+        Commands::Status { json } => {
+            handle_status(json).await?;
+        }
+        Commands::Verify => {
+            handle_verify().await?;
+        }
+        Commands::Resolve => {
+            handle_resolve().await?;
+        }
+        Commands::Conflicts { clean } => {
+            handle_conflicts(clean).await?;
+        }
+        Commands::Push { path } => {
+            handle_force_sync(path, ForcedDirection::Push).await?;
+        }
+        Commands::Pull { path } => {
+            handle_force_sync(path, ForcedDirection::Pull).await?;
+        }
+        Commands::Audit { limit } => {
+            handle_audit(limit)?;
+        }
+        Commands::Profile { profile_command } => {
+            handle_profile_command(profile_command)?;
         }
         Commands::Rules { rule_command } => {
             handle_rules(rule_command).await?;
         }
+        Commands::Queue { queue_command } => {
+            handle_queue(queue_command).await?;
+        }
         Commands::HealthCheck => {
             handle_health_check().await?;
         }
+        Commands::Doctor => {
+            handle_doctor().await?;
+        }
 
-        Commands::Metrics { format, output } => {
-            handle_metrics(format, output).await?;
+        Commands::Metrics {
+            format,
+            output,
+            output_format,
+        } => {
+            handle_metrics(format, output, output_format).await?;
         }
     }
 
@@ -221,6 +519,97 @@ async fn handle_config_init() -> Result<()> {
     Ok(())
 }
 
+/// 处理配置校验
+fn handle_config_validate() -> Result<()> {
+    let config_path = ClientConfig::config_path()?;
+    let results = doctor::run_config_validation(&config_path);
+
+    println!("📋 配置校验: {:?}\n", config_path);
+    doctor::print_report(&results);
+
+    let failures = results.iter().filter(|r| r.is_critical_failure()).count();
+    if failures > 0 {
+        anyhow::bail!("配置校验发现 {} 个问题", failures);
+    }
+
+    println!("\n✓ 配置和同步规则均有效");
+
+    Ok(())
+}
+
+/// 处理配置导出
+fn handle_config_export(file: &Path, include_secrets: bool) -> Result<()> {
+    let config = ClientConfig::load()?;
+    config.export_to(file, include_secrets)?;
+
+    println!("✓ 配置已导出: {:?}", file);
+    if !include_secrets {
+        println!("💡 提示: 密钥未导出，如需导出请使用 --include-secrets");
+    }
+
+    Ok(())
+}
+
+/// 处理配置导入
+fn handle_config_import(file: &Path, merge_rules: bool) -> Result<()> {
+    let config_path = ClientConfig::config_path()?;
+    let current = if config_path.exists() {
+        ClientConfig::load_from(&config_path)?
+    } else {
+        ClientConfig::default()
+    };
+
+    let config = ClientConfig::import_from(file, &current, merge_rules)?;
+    config.save(&config_path)?;
+
+    println!("✓ 配置已导入: {:?}", config_path);
+    println!("同步规则: {} 条", config.sync.rules.len());
+
+    Ok(())
+}
+
+/// 处理配置档命令
+fn handle_profile_command(command: ProfileCommands) -> Result<()> {
+    let config_path = ClientConfig::config_path()?;
+    let mut config = if config_path.exists() {
+        ClientConfig::load_from(&config_path)?
+    } else {
+        ClientConfig::default()
+    };
+    let active = config
+        .active_profile
+        .clone()
+        .unwrap_or_else(|| config::DEFAULT_PROFILE.to_string());
+
+    match command {
+        ProfileCommands::List => {
+            let marker = |name: &str| if name == active { "*" } else { " " };
+            println!(
+                "{} {:<20} {}",
+                marker(config::DEFAULT_PROFILE),
+                config::DEFAULT_PROFILE,
+                config.server.address
+            );
+            for (name, profile) in &config.profiles {
+                println!("{} {:<20} {}", marker(name), name, profile.server.address);
+            }
+        }
+        ProfileCommands::Use { name } => {
+            if name != config::DEFAULT_PROFILE && !config.profiles.contains_key(&name) {
+                anyhow::bail!("配置档不存在: {}", name);
+            }
+            let is_top_level =
+                name == config::DEFAULT_PROFILE && !config.profiles.contains_key(&name);
+            config.active_profile = (!is_top_level).then(|| name.clone());
+            config.save(&config_path)?;
+
+            println!("✓ 已切换到配置档: {}", name);
+        }
+    }
+
+    Ok(())
+}
+
 /// 处理登录
 async fn handle_login(
     email: Option<String>,
@@ -254,7 +643,7 @@ async fn handle_login(
     });
 
     // 创建 gRPC 客户端
-    let client = grpc_client::GrpcClient::new(config.server.address.clone()).await?;
+    let client = grpc_client::GrpcClient::new(&config.server).await?;
 
     // 调用登录 API
     let response = client
@@ -262,11 +651,7 @@ async fn handle_login(
         .await?;
 
     // 保存 Token
-    let token_manager = TokenManager::new(
-        config.auth.token_dir,
-        config.auth.encryption_key,
-        "dummy_jwt_secret".to_string(), // TODO: 从配置获取
-    );
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
 
     let tokens = token::TokenStorage {
         access_token: response.access_token.clone(),
@@ -292,11 +677,7 @@ async fn handle_logout() -> Result<()> {
     info!("登出...");
 
     let config = ClientConfig::load()?;
-    let token_manager = TokenManager::new(
-        config.auth.token_dir,
-        config.auth.encryption_key,
-        "dummy_jwt_secret".to_string(),
-    );
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
 
     // 检查是否已登录
     if !token_manager.has_tokens() {
@@ -315,53 +696,404 @@ async fn handle_logout() -> Result<()> {
 }
 
 /// 处理同步
-async fn handle_sync(mode: String, daemon: bool, _verbose: bool) -> Result<()> {
+async fn handle_sync(
+    mode: String,
+    daemon: bool,
+    verbose: bool,
+    paths: Vec<PathBuf>,
+    dry_run: bool,
+    since: Option<String>,
+    adhoc_rules: Vec<rules::SyncRule>,
+) -> Result<()> {
     info!("开始同步 (模式: {})", mode);
+    for rule in &adhoc_rules {
+        info!("本次同步的临时规则: {}", rule.name);
+    }
+
+    let since = match since {
+        Some(_) if mode != "full" => anyhow::bail!("--since 仅适用于全量同步（--mode full）"),
+        Some(since) => Some(watcher::parse_since(&since, chrono::Utc::now())?),
+        None => None,
+    };
 
     // 加载配置
     let config = Arc::new(ClientConfig::load()?);
     config.validate()?;
 
     // 检查登录状态
-    let token_manager = TokenManager::new(
-        config.auth.token_dir.clone(),
-        config.auth.encryption_key.clone(),
+    let token_manager = Arc::new(TokenManager::from_config(
+        &config.auth,
         "dummy_jwt_secret".to_string(),
-    );
+    )?);
 
     if !token_manager.has_tokens() {
         anyhow::bail!("未登录，请先运行 'claude-sync login'");
     }
 
+    // 同一配置目录只允许运行一个守护进程，锁在函数返回时释放
+    let _instance_lock = if daemon {
+        Some(instance::InstanceLock::acquire(
+            instance::default_lock_path()?,
+            &config.sync.claude_dir,
+        )?)
+    } else {
+        None
+    };
+
+    // 监控管理器（与守护进程的 /metrics 端点共享）
+    let monitoring = MonitoringManager::new(1000, 1000);
+
+    // 创建同步引擎
+    // 离线时照常同步，上传不做内容查询
+    let server = match connect_authenticated(&config).await {
+        Ok((client, _)) => Some(Arc::new(client)),
+        Err(e) => {
+            warn!("无法连接服务器，上传前不查询已有内容: {:#}", e);
+            None
+        }
+    };
+    let mut sync_engine = create_sync_engine(&config, &token_manager, server.clone())?
+        .with_dry_run(dry_run)
+        .with_adhoc_rules(adhoc_rules)
+        .with_monitoring(monitoring.clone());
+
+    // 守护进程在服务器不可达期间把本地修改写入持久化离线队列，网络恢复或重启后重放
+    let network = if daemon {
+        let offline_queue = retry::OfflineQueue::persistent(
+            network::OFFLINE_QUEUE_MAX_SIZE,
+            config.sync.offline_queue_file.clone(),
+        )?;
+        let network = Arc::new(
+            network::NetworkRecoveryManager::new(
+                config.server.address.clone(),
+                config.server.health_check_address.clone(),
+                retry::RetryConfig::default(),
+                config.performance.retry_delay,
+                0,
+            )
+            .with_offline_queue(offline_queue),
+        );
+        sync_engine = sync_engine.with_network(network.clone());
+        Some(network)
+    } else {
+        None
+    };
+
+    // 守护进程把上传完成的文件变更按批处理窗口合并后上报服务器
+    if let (true, Some(server)) = (daemon, &server) {
+        let (change_tx, change_rx) = tokio::sync::mpsc::unbounded_channel();
+        sync_engine = sync_engine.with_change_reports(change_tx);
+        let reporter = reporter::BatchReporter::new(
+            server.clone(),
+            config.sync_roots(),
+            Duration::from_secs(config.sync.batch_window),
+        )
+        .with_hash_algorithm(config.performance.hash_algorithm);
+        tokio::spawn(async move { reporter.run(change_rx).await });
+    }
+    let sync_engine = Arc::new(sync_engine);
+    if let Some(network) = &network {
+        let handler: Arc<dyn network::OfflineOperationHandler> = sync_engine.clone();
+        network.set_offline_handler(Arc::downgrade(&handler));
+    }
+
+    // 合并服务器上的全局规则和本设备规则（获取失败时只使用本地规则）
+    if let Some(server) = &server {
+        if let Err(e) = sync_engine.refresh_server_rules(server.as_ref()).await {
+            warn!("{:#}，只使用本地同步规则", e);
+        }
+    }
+
+    // 守护进程启动时等待 Claude 目录稳定（其他工具可能仍在写入配置）；
+    // 单次同步不等待，无法创建监控器时只记录警告
+    if daemon && config.sync.settle_quiet_period > 0 {
+        match watcher::wait_for_settle(
+            &config.sync.claude_dir,
+            Duration::from_millis(config.sync.settle_quiet_period),
+            Duration::from_millis(config.sync.settle_max_wait),
+        )
+        .await
+        {
+            Ok(watcher::SettleState::TimedOut) => warn!("等待目录稳定超时，继续同步"),
+            Ok(_) => {}
+            Err(e) => warn!("{:#}，跳过等待目录稳定", e),
+        }
+    }
+
+    // 加载上次的同步状态快照，并校对离线期间的变更
+    sync_engine.load_snapshot().await?;
+    let report = sync_engine.reconcile().await?;
+    if !report.pending.is_empty() || !report.removed.is_empty() {
+        println!(
+            "🔍 检测到离线期间的变更: {} 个待同步, {} 个已删除",
+            report.pending.len(),
+            report.removed.len()
+        );
+    }
+
+    match mode.as_str() {
+        "full" => {
+            pull_remote_changes(&config, &sync_engine).await?;
+
+            // 全量同步
+            println!("🔄 开始全量同步...");
+            if let Some(since) = since {
+                println!(
+                    "🕒 仅同步 {} 之后修改的文件",
+                    since.with_timezone(&chrono::Local)
+                );
+            }
+            // 详细日志模式或输出不是终端时不显示进度条，避免与日志交错
+            let progress_bar = if verbose || !std::io::stdout().is_terminal() {
+                ProgressBar::hidden()
+            } else {
+                create_progress_bar(0)
+            };
+            let summary = sync_engine
+                .run_full_sync_with_progress(since, |event| match event {
+                    FullSyncProgress::Started { total } => progress_bar.set_length(total as u64),
+                    FullSyncProgress::FileProcessed { .. } => progress_bar.inc(1),
+                })
+                .await;
+            progress_bar.finish_and_clear();
+            let summary = summary?;
+
+            println!("\n✓ 全量同步完成");
+            print_sync_summary(&summary);
+        }
+        "incremental" => {
+            // 增量同步（实时监控）
+            if daemon {
+                println!("🔄 后台监控模式（按 Ctrl+C 停止）");
+
+                // 在 Access Token 过期前自动刷新
+                let refresh_task = if config.auth.auto_refresh {
+                    let client = grpc_client::GrpcClient::new(&config.server).await?;
+                    Some(token::spawn_refresh_task(
+                        token_manager.clone(),
+                        Arc::new(client),
+                        config.auth.refresh_before as i64,
+                    ))
+                } else {
+                    None
+                };
+
+                // 暴露 /metrics 端点供 Prometheus 抓取
+                let metrics_task = if config.performance.metrics_address.is_empty() {
+                    None
+                } else {
+                    let (addr, handle) = metrics_server::MetricsServer::new(monitoring.clone())
+                        .spawn(&config.performance.metrics_address)
+                        .await?;
+                    println!("📈 指标端点: http://{}/metrics", addr);
+                    Some(handle)
+                };
+
+                pull_remote_changes(&config, &sync_engine).await?;
+
+                // 重放上次运行时未完成的离线操作，之后由网络监控在重新连接时处理
+                let network_task = match &network {
+                    Some(network) => {
+                        if let Err(e) = network.process_offline_queue().await {
+                            warn!("重放离线队列失败: {}", e.user_message());
+                        }
+                        Some(network.clone().spawn_network_monitor())
+                    }
+                    None => None,
+                };
+
+                // 接受 pause/resume 控制命令和 GUI 的传输进度订阅
+                let control_task = if config.sync.control_address.is_empty() {
+                    None
+                } else {
+                    let (_, handle) = control::spawn_control_server(
+                        &config.sync.control_address,
+                        sync_engine.control().clone(),
+                        sync_engine.subscribe_progress(),
+                    )
+                    .await?;
+                    Some(handle)
+                };
+
+                // 订阅服务器推送的远程变更（只上传模式或无法连接服务器时不订阅）
+                let subscriber_task = if config.sync.direction.allows_pull() {
+                    spawn_change_subscriber(&config, &token_manager, &sync_engine).await
+                } else {
+                    None
+                };
+
+                // 定期发送心跳，使本设备在服务器上保持在线
+                let heartbeat_task = if config.sync.heartbeat_interval > 0 {
+                    spawn_heartbeat(&config, &sync_engine).await
+                } else {
+                    None
+                };
+
+                // 电池供电或按流量计费的网络上自动暂停同步
+                let policy = power::PausePolicy {
+                    pause_on_battery: config.sync.pause_on_battery,
+                    pause_on_metered: config.sync.pause_on_metered,
+                };
+                let power_task = policy.is_enabled().then(|| {
+                    if !power::PLATFORM_SUPPORTED {
+                        warn!("当前构建未启用 power-management 特性或平台不支持，自动暂停不生效");
+                    }
+                    power::spawn_power_monitor_task(
+                        power::PowerMonitor::new(sync_engine.control().clone(), policy),
+                        power::PlatformStateSource,
+                        Duration::from_secs(config.sync.power_check_interval),
+                    )
+                });
+
+                sync_engine.sync_pending().await?;
+
+                // 轮询模式：按固定间隔全量同步（文件系统事件不可靠时使用）
+                let poller = (config.sync.sync_interval > 0).then(|| {
+                    Arc::new(poller::SyncPoller::new(
+                        sync_engine.clone(),
+                        Duration::from_secs(config.sync.sync_interval),
+                    ))
+                });
+                let poll_task = poller.clone().map(poller::spawn_poll_task);
+
+                // 未启用轮询时监控文件系统事件，防抖和批处理后交给增量同步
+                let watch_tasks = match &poller {
+                    Some(_) => None,
+                    None => Some(spawn_file_watcher(&config, &sync_engine)?),
+                };
+
+                if poller.is_some() {
+                    println!(
+                        "⏱️  定时同步模式，每 {} 秒同步一次（按 Ctrl+C 停止）",
+                        config.sync.sync_interval
+                    );
+                } else {
+                    println!("👀 实时同步模式，正在监控文件变更（按 Ctrl+C 停止）");
+                }
+                if subscriber_task.is_some() || heartbeat_task.is_some() {
+                    println!("📡 已连接服务器，正在接收其他设备的变更");
+                }
+                tokio::signal::ctrl_c().await?;
+                if let Some(poller) = &poller {
+                    info!("定时同步共执行 {} 次", poller.runs());
+                }
+                if let Some(poll_task) = poll_task {
+                    poll_task.abort();
+                }
+                if let Some((watch_task, sync_task)) = watch_tasks {
+                    watch_task.abort();
+                    sync_task.abort();
+                }
+                if let Some(power_task) = power_task {
+                    power_task.abort();
+                }
+                if let Some(subscriber_task) = subscriber_task {
+                    subscriber_task.abort();
+                }
+                if let Some(heartbeat_task) = heartbeat_task {
+                    heartbeat_task.abort();
+                }
+                if let Some(refresh_task) = refresh_task {
+                    refresh_task.abort();
+                }
+                if let Some(metrics_task) = metrics_task {
+                    metrics_task.abort();
+                }
+                if let Some(control_task) = control_task {
+                    control_task.abort();
+                }
+                if let Some(network_task) = network_task {
+                    network_task.abort();
+                }
+            } else {
+                println!("⚠️  增量同步需要后台模式运行");
+                println!("💡 使用: claude-sync sync --daemon");
+            }
+        }
+        "selective" => {
+            // 选择性同步
+            if paths.is_empty() {
+                anyhow::bail!("选择性同步需要指定至少一个 --path");
+            }
+
+            println!("🔄 选择性同步...");
+            let summary = sync_engine.run_selective_sync(paths).await?;
+
+            println!("\n✓ 选择性同步完成");
+            print_sync_summary(&summary);
+        }
+        _ => {
+            anyhow::bail!("无效的同步模式: {}", mode);
+        }
+    }
+
+    // 保存状态快照和性能指标（演练模式不写入）
+    sync_engine.close().await?;
+
+    Ok(())
+}
+
+/// 按配置和登录信息创建同步引擎
+///
+/// 提供服务器连接时，上传前先查询服务器是否已有相同内容，没有同步状态的文件先比较服务器上的哈希。
+fn create_sync_engine(
+    config: &Arc<ClientConfig>,
+    token_manager: &TokenManager,
+    server: Option<Arc<grpc_client::GrpcClient>>,
+) -> Result<SyncEngine> {
     // 获取用户和设备 ID
     let user_id = Uuid::parse_str(&token_manager.get_user_id()?)?;
     let device_id = Uuid::parse_str(&token_manager.get_device_id()?)?;
 
     // 创建规则引擎
-    let rule_engine = Arc::new(RuleEngine::from_rules(config.sync.rules.clone()));
+    let rule_engine = Arc::new(RuleEngine::from_config(config)?);
 
     // 创建传输管理器
-    let transfer_manager = Arc::new(TransferManager::new(
+    let mut transfer_manager = TransferManager::new(
         config.performance.max_concurrent_uploads,
         config.performance.max_concurrent_downloads,
         config.performance.upload_retries,
         config.performance.download_retries,
         config.performance.retry_delay,
-    ));
+        config.performance.chunk_size,
+    );
+    if config.performance.delta_upload {
+        transfer_manager = transfer_manager
+            .with_delta_bases(BaseStore::new(config.performance.delta_base_dir.clone()));
+    }
+    if let Some(server) = &server {
+        transfer_manager = transfer_manager
+            .with_content_index(server.clone())
+            .with_upload_target(server.clone());
+    }
+    let transfer_manager = Arc::new(transfer_manager);
 
     // 创建冲突解决器
-    let conflict_resolver = Arc::new(ConflictResolver::new(
-        match config.conflict.default_strategy.as_str() {
-            "keep_local" => ResolutionStrategy::KeepLocal,
-            "keep_remote" => ResolutionStrategy::KeepRemote,
-            "keep_newer" => ResolutionStrategy::KeepNewer,
-            _ => ResolutionStrategy::Manual,
-        },
-        config.conflict.auto_merge_text,
-        config.conflict.auto_merge_structured,
-    ));
+    let type_strategies = config
+        .conflict
+        .per_type_strategy
+        .iter()
+        .filter_map(|(file_type, strategy)| {
+            ResolutionStrategy::parse(strategy).map(|s| (file_type.clone(), s))
+        })
+        .collect();
+    let conflict_resolver = Arc::new(
+        ConflictResolver::new(
+            ResolutionStrategy::parse(&config.conflict.default_strategy)
+                .unwrap_or(ResolutionStrategy::Manual),
+            config.conflict.auto_merge_text,
+            config.conflict.auto_merge_structured,
+        )
+        .with_array_merge(if config.conflict.array_merge_by_key {
+            ArrayMergeMode::ByKey(config.conflict.array_merge_keys.clone())
+        } else {
+            ArrayMergeMode::TakeRemote
+        })
+        .with_type_strategies(type_strategies)
+        .with_audit_log(AuditLog::new(config.conflict.audit_log.clone()))
+        .with_marker_style(conflict_marker_style(&config.conflict)),
+    );
 
-    // 创建同步引擎
     let sync_engine = SyncEngine::new(
         config.clone(),
         rule_engine,
@@ -370,57 +1102,185 @@ async fn handle_sync(mode: String, daemon: bool, _verbose: bool) -> Result<()> {
         user_id,
         device_id,
     );
+    Ok(match server {
+        Some(server) => sync_engine.with_remote_hashes(server),
+        None => sync_engine,
+    })
+}
 
-    match mode.as_str() {
-        "full" => {
-            // 全量同步
-            println!("🔄 开始全量同步...");
-            let summary = sync_engine.run_full_sync().await?;
+/// 推送本地删除记录，再拉取并应用上次同步后的远程变更（无法连接服务器时跳过）
+async fn pull_remote_changes(config: &ClientConfig, sync_engine: &SyncEngine) -> Result<()> {
+    let client = match connect_authenticated(config).await {
+        Ok((client, _)) => client,
+        Err(e) => {
+            warn!("无法连接服务器，跳过远程变更: {:#}", e);
+            return Ok(());
+        }
+    };
 
-            println!("\n✓ 全量同步完成");
-            println!("成功: {}", summary.synced_count);
-            println!("失败: {}", summary.failed_count);
-            println!("冲突: {}", summary.conflict_count);
-
-            if !summary.conflicts.is_empty() {
-                println!("\n冲突文件:");
-                for path in &summary.conflicts {
-                    println!("  - {:?}", path);
-                }
-            }
+    // 推送失败时墓碑保留在状态快照中，下次同步重试
+    match sync_engine.push_tombstones(&client).await {
+        Ok(0) => {}
+        Ok(count) => println!("🗑️  已通知服务器 {} 个文件删除", count),
+        Err(e) => warn!("推送本地删除失败: {:#}", e),
+    }
 
-            if !summary.errors.is_empty() {
-                println!("\n错误:");
-                for (path, error) in &summary.errors {
-                    println!("  - {:?}: {}", path, error);
-                }
-            }
+    let summary = sync_engine.apply_remote_changes(&client).await?;
+    if summary.synced_count + summary.conflict_count + summary.failed_count > 0 {
+        println!("⬇️  远程变更:");
+        print_sync_summary(&summary);
+    }
+
+    Ok(())
+}
+
+/// 启动远程变更订阅任务（无法连接服务器时返回 None）
+async fn spawn_change_subscriber(
+    config: &ClientConfig,
+    token_manager: &TokenManager,
+    sync_engine: &Arc<SyncEngine>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let client = match connect_authenticated(config).await {
+        Ok((client, _)) => client,
+        Err(e) => {
+            warn!("无法连接服务器，不订阅远程变更通知: {:#}", e);
+            return None;
         }
-        "incremental" => {
-            // 增量同步（实时监控）
-            if daemon {
-                println!("🔄 后台监控模式（按 Ctrl+C 停止）");
-                // TODO: 启动文件监控和实时同步
-                println!("⚠️  实时同步功能需要等待 protobuf 代码生成");
-            } else {
-                println!("⚠️  增量同步需要后台模式运行");
-                println!("💡 使用: claude-sync sync --daemon");
-            }
+    };
+    let device_id = match token_manager.get_device_id().map(|id| Uuid::parse_str(&id)) {
+        Ok(Ok(device_id)) => device_id,
+        _ => {
+            warn!("无法读取本机设备 ID，不订阅远程变更通知");
+            return None;
         }
-        "selective" => {
-            // 选择性同步
-            println!("🔄 选择性同步...");
-            // TODO: 实现选择性同步
-            println!("⚠️  选择性同步功能开发中");
+    };
+
+    // 最大重连次数为 0 表示持续重试
+    let network = Arc::new(network::NetworkRecoveryManager::new(
+        config.server.address.clone(),
+        config.server.health_check_address.clone(),
+        retry::RetryConfig::default(),
+        config.performance.retry_delay,
+        0,
+    ));
+    let mut subscriber = subscriber::ChangeSubscriber::new(sync_engine.clone(), network, device_id)
+        .with_backoff(
+            retry::RetryConfig::default()
+                .with_initial_delay_ms(config.performance.retry_delay.max(1) * 1000),
+        );
+    // 服务器不推送规则变更，定时重新获取
+    if config.sync.rules_refresh_interval > 0 {
+        subscriber = subscriber
+            .with_rules_refresh_interval(Duration::from_secs(config.sync.rules_refresh_interval));
+    }
+    Some(subscriber::spawn_subscriber_task(
+        subscriber,
+        Arc::new(client),
+    ))
+}
+
+/// 启动心跳保活任务（无法连接服务器时返回 None）
+async fn spawn_heartbeat(
+    config: &ClientConfig,
+    sync_engine: &Arc<SyncEngine>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let client = match connect_authenticated(config).await {
+        Ok((client, _)) => client,
+        Err(e) => {
+            warn!("无法连接服务器，不发送心跳: {:#}", e);
+            return None;
         }
-        _ => {
-            anyhow::bail!("无效的同步模式: {}", mode);
+    };
+
+    let task = heartbeat::HeartbeatTask::new(
+        sync_engine.clone(),
+        Duration::from_secs(config.sync.heartbeat_interval),
+    )
+    .with_backoff(
+        retry::RetryConfig::default()
+            .with_initial_delay_ms(config.performance.retry_delay.max(1) * 1000),
+    );
+    Some(heartbeat::spawn_heartbeat_task(task, Arc::new(client)))
+}
+
+/// 启动文件监控，返回监控任务和处理文件事件的增量同步任务
+fn spawn_file_watcher(
+    config: &ClientConfig,
+    sync_engine: &Arc<SyncEngine>,
+) -> Result<(tokio::task::JoinHandle<()>, tokio::task::JoinHandle<()>)> {
+    let (event_tx, event_rx) = tokio::sync::mpsc::unbounded_channel();
+    let watch_task = watcher::FileWatcher::new(
+        config.sync.claude_dir.clone(),
+        event_tx,
+        config.performance.debounce_delay,
+        config.sync.batch_window,
+        config.get_exclude_paths(),
+        config.sync.exclude_patterns.clone(),
+    )
+    .with_additional_dirs(config.sync.additional_watch_dirs.clone())
+    .with_follow_symlinks(config.sync.follow_symlinks)
+    .with_max_pending_files(config.performance.max_pending_files)
+    .spawn()?;
+
+    let sync_engine = sync_engine.clone();
+    let sync_task = tokio::spawn(async move {
+        if let Err(e) = sync_engine.start_incremental_sync(event_rx).await {
+            warn!("增量同步失败: {:#}", e);
         }
+    });
+
+    Ok((watch_task, sync_task))
+}
+
+/// 向守护进程发送暂停/恢复命令
+async fn handle_control(command: control::ControlCommand) -> Result<()> {
+    let config = ClientConfig::load()?;
+    if config.sync.control_address.is_empty() {
+        anyhow::bail!("未配置守护进程控制端口 (sync.control_address)");
+    }
+
+    let status = control::send_command(&config.sync.control_address, command).await?;
+    match status.as_str() {
+        "paused" => println!("⏸️  同步已暂停，变更将在恢复后同步"),
+        _ => println!("▶️  同步运行中"),
     }
 
     Ok(())
 }
 
+/// 打印同步摘要
+fn print_sync_summary(summary: &sync::SyncSummary) {
+    if summary.dry_run {
+        println!("(dry run) 以下为计划执行的操作，未做任何修改");
+    }
+
+    println!("成功: {}", summary.synced_count);
+    println!("失败: {}", summary.failed_count);
+    println!("冲突: {}", summary.conflict_count);
+    println!("跳过: {}", summary.skipped_count);
+
+    if !summary.conflicts.is_empty() {
+        println!("\n冲突文件:");
+        for path in &summary.conflicts {
+            println!("  - {:?}", path);
+        }
+    }
+
+    if !summary.errors.is_empty() {
+        println!("\n错误:");
+        for (path, error) in &summary.errors {
+            println!("  - {:?}: {}", path, error);
+        }
+    }
+
+    if !summary.skipped.is_empty() {
+        println!("\n跳过的文件:");
+        for (path, reason) in &summary.skipped {
+            println!("  - {:?}: {}", path, reason);
+        }
+    }
+}
+
 /// 处理设备列表
 async fn handle_list_devices() -> Result<()> {
     info!("获取设备列表...");
@@ -431,20 +1291,186 @@ async fn handle_list_devices() -> Result<()> {
     Ok(())
 }
 
-/// 处理状态查询
-async fn handle_status() -> Result<()> {
-    info!("查询同步状态...");
+/// 处理设备吊销
+async fn handle_revoke_device(device_id: String) -> Result<()> {
+    let device_id = Uuid::parse_str(&device_id).context("无效的设备 ID")?;
+    info!("吊销设备: {}", device_id);
 
     let config = ClientConfig::load()?;
+    let (client, token_manager) = connect_authenticated(&config).await?;
 
-    let token_manager = TokenManager::new(
-        config.auth.token_dir,
-        config.auth.encryption_key,
-        "dummy_jwt_secret".to_string(),
+    let message = client.remove_device(device_id).await?;
+    println!("✓ {}", message);
+
+    // 吊销的是本机设备时，本地 Token 也已失效
+    if token_manager.get_device_id()? == device_id.to_string() {
+        token_manager.delete_tokens()?;
+        println!("⚠️  已吊销本机设备，请重新运行 'claude-sync login'");
+    }
+
+    Ok(())
+}
+
+/// 使用本地保存的 Token 建立已认证的 gRPC 连接
+async fn connect_authenticated(
+    config: &ClientConfig,
+) -> Result<(grpc_client::GrpcClient, TokenManager)> {
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
+
+    if !token_manager.has_tokens() {
+        anyhow::bail!("未登录，请先运行 'claude-sync login'");
+    }
+
+    let mut client = grpc_client::GrpcClient::new(&config.server).await?;
+    client.set_access_token(token_manager.get_access_token()?);
+
+    Ok((client, token_manager))
+}
+
+/// 处理文件历史查询
+async fn handle_history(path: String, limit: Option<i64>) -> Result<()> {
+    let limit = history::validate_limit(limit)?;
+
+    let config = ClientConfig::load()?;
+    let remote_path = paths::relativize(&config.sync.claude_dir, Path::new(&path))?;
+    info!("查询文件历史: {}", remote_path);
+
+    let (client, _) = connect_authenticated(&config).await?;
+    let versions = client.get_file_history(remote_path.clone(), limit).await?;
+
+    println!("{} 的历史版本:", remote_path);
+    print!("{}", history::format_history_table(&versions));
+
+    Ok(())
+}
+
+/// 处理文件搜索
+async fn handle_search(query: String, content: bool, limit: u32) -> Result<()> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        anyhow::bail!("搜索内容不能为空");
+    }
+    if limit == 0 {
+        anyhow::bail!("--limit 必须大于 0");
+    }
+
+    let config = ClientConfig::load()?;
+    info!("搜索文件: {} (包含内容: {})", query, content);
+
+    let (client, _) = connect_authenticated(&config).await?;
+    let matches = client.search_files(query, content, limit).await?;
+
+    print!("{}", history::format_search_results(&matches));
+    println!("共 {} 个结果", matches.len());
+
+    Ok(())
+}
+
+/// 处理旧版本清理
+async fn handle_prune(keep: u32, days: Option<u32>, dry_run: bool) -> Result<()> {
+    if keep == 0 {
+        anyhow::bail!("--keep 至少为 1（每个文件的最新版本始终保留）");
+    }
+
+    let config = ClientConfig::load()?;
+    info!("清理旧版本: 保留 {} 个, {:?} 天", keep, days);
+
+    let (client, _) = connect_authenticated(&config).await?;
+    let response = client
+        .prune_versions(keep, days.unwrap_or(0), dry_run)
+        .await?;
+
+    print!("{}", history::format_prune_table(&response.pruned_versions));
+    if dry_run {
+        println!(
+            "将清理 {} 个版本（未实际删除）",
+            response.pruned_versions.len()
+        );
+    } else {
+        println!(
+            "✓ 已清理 {} 个版本，删除 {} 个存储对象，释放 {} 字节",
+            response.pruned_versions.len(),
+            response.deleted_objects,
+            response.freed_bytes
+        );
+    }
+
+    Ok(())
+}
+
+/// 处理文件版本恢复
+async fn handle_restore(path: String, version: i64) -> Result<()> {
+    let version = history::validate_version_number(version)?;
+
+    let config = ClientConfig::load()?;
+    let remote_path = paths::relativize(&config.sync.claude_dir, Path::new(&path))?;
+    info!("恢复文件 {} 到版本 {}", remote_path, version);
+
+    let (client, _) = connect_authenticated(&config).await?;
+    let response = client
+        .restore_file_version(remote_path.clone(), version)
+        .await?;
+    if !response.success {
+        anyhow::bail!("恢复失败: {}", response.message);
+    }
+
+    // 下载恢复后的内容，校验通过后再写入本地
+    let data = client
+        .download_file(remote_path.clone(), Some(response.version_number))
+        .await?;
+    let local_path = paths::absolutize(&config.sync.claude_dir, &remote_path)?;
+    transfer::TransferManager::verify_download(&local_path, &data.content, &data.file_hash)?;
+
+    if let Some(parent) = local_path.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {:?}", parent))?;
+    }
+    transfer::write_atomic(&local_path, &data.content).await?;
+
+    println!("✓ {}", response.message);
+    println!(
+        "已将 {:?} 恢复到版本 {}",
+        local_path, response.version_number
     );
 
+    Ok(())
+}
+
+/// 处理状态查询
+async fn handle_status(json: bool) -> Result<()> {
+    info!("查询同步状态...");
+
+    let config = Arc::new(ClientConfig::load()?);
+
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
+
     if !token_manager.has_tokens() {
-        println!("⚠️  未登录");
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&status::StatusReport::logged_out())?
+            );
+        } else {
+            println!("⚠️  未登录");
+        }
+        return Ok(());
+    }
+
+    // 从同步状态快照统计各状态的文件数
+    let sync_engine = create_sync_engine(&config, &token_manager, None)?;
+    sync_engine.load_snapshot().await?;
+    let stats = status::SyncStats::from_states(
+        &sync_engine.get_all_sync_states().await,
+        sync_engine.pending_tombstones().await.len(),
+    );
+
+    if json {
+        let report = status::StatusReport::from_tokens(
+            &token_manager.load_tokens()?,
+            config.auth.refresh_before as i64,
+            chrono::Utc::now(),
+        )
+        .with_sync_stats(stats);
+        println!("{}", serde_json::to_string_pretty(&report)?);
         return Ok(());
     }
 
@@ -461,7 +1487,337 @@ async fn handle_status() -> Result<()> {
         println!("✓ Access Token 有效");
     }
 
-    // TODO: 显示同步状态统计
+    println!(
+        "\n同步状态: {} 个文件, {} 个已同步, {} 个待同步, {} 个冲突, {} 个失败, {} 个跳过",
+        stats.total_files,
+        stats.synced,
+        stats.pending,
+        stats.conflicts,
+        stats.failed,
+        stats.skipped
+    );
+    if stats.pending_deletions > 0 {
+        println!("待通知服务器的删除: {} 个", stats.pending_deletions);
+    }
+    if let Some(last_sync_time) = stats.last_sync_time {
+        println!(
+            "最近同步: {}",
+            last_sync_time
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    Ok(())
+}
+
+/// 校验本地文件与同步状态是否一致（有不一致时返回非零退出码）
+async fn handle_verify() -> Result<()> {
+    let config = Arc::new(ClientConfig::load()?);
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
+    if !token_manager.has_tokens() {
+        anyhow::bail!("未登录，请先运行 'claude-sync login'");
+    }
+
+    // 无法连接服务器时只校验本地文件
+    let server = match connect_authenticated(&config).await {
+        Ok((client, _)) => Some(Arc::new(client)),
+        Err(e) => {
+            warn!("无法连接服务器，不检查服务器上的新版本: {:#}", e);
+            None
+        }
+    };
+    let sync_engine = create_sync_engine(&config, &token_manager, server)?;
+    sync_engine.load_snapshot().await?;
+
+    let report = sync_engine.verify().await?;
+    let display_path = |path: &Path| {
+        path.strip_prefix(&config.sync.claude_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    for mismatch in &report.mismatched {
+        let newer = match mismatch.newer {
+            sync::NewerSide::Local => "本地较新",
+            sync::NewerSide::Server => "服务器较新",
+            sync::NewerSide::Both => "两侧都有修改",
+        };
+        println!("✗ {} ({})", display_path(&mismatch.path), newer);
+    }
+    for path in &report.missing {
+        println!("✗ {} (本地文件不存在)", display_path(path));
+    }
+
+    println!(
+        "\n校验完成: {} 个一致, {} 个不一致, {} 个缺失",
+        report.verified,
+        report.mismatched.len(),
+        report.missing.len()
+    );
+    if !report.mismatched.is_empty() || !report.missing.is_empty() {
+        anyhow::bail!("本地文件与同步状态不一致，运行 'claude-sync sync' 重新同步");
+    }
+
+    Ok(())
+}
+
+/// 交互式解决同步冲突
+async fn handle_conflicts(clean: bool) -> Result<()> {
+    let config = Arc::new(ClientConfig::load()?);
+    config.validate()?;
+
+    let token_manager = TokenManager::from_config(&config.auth, "dummy_jwt_secret".to_string())?;
+    if !token_manager.has_tokens() {
+        anyhow::bail!("未登录，请先运行 'claude-sync login'");
+    }
+
+    let sync_engine = create_sync_engine(&config, &token_manager, None)?;
+    sync_engine.load_snapshot().await?;
+    let display_path = |path: &Path| {
+        path.strip_prefix(&config.sync.claude_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    let conflicts = sync_engine.unresolved_conflicts().await;
+    if conflicts.is_empty() {
+        println!("✓ 没有未解决的冲突");
+    } else {
+        println!(
+            "⚠️  {} 个未解决的冲突（运行 'claude-sync resolve' 处理）:",
+            conflicts.len()
+        );
+        for state in &conflicts {
+            println!("  - {}", display_path(&state.path));
+        }
+    }
+
+    if clean {
+        let removed = sync_engine.clean_conflict_markers().await?;
+        for marker in &removed {
+            println!("🗑  {}", display_path(marker));
+        }
+        println!("✓ 已删除 {} 个残留的冲突标记文件", removed.len());
+        return Ok(());
+    }
+
+    let orphaned = sync_engine.orphaned_conflict_markers().await;
+    if !orphaned.is_empty() {
+        println!(
+            "\n{} 个已解决冲突残留的冲突标记文件（运行 'claude-sync conflicts --clean' 删除）:",
+            orphaned.len()
+        );
+        for marker in &orphaned {
+            println!("  - {}", display_path(marker));
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_resolve() -> Result<()> {
+    let config = Arc::new(ClientConfig::load()?);
+    config.validate()?;
+
+    let (client, token_manager) = connect_authenticated(&config).await?;
+    let client = Arc::new(client);
+    let sync_engine = create_sync_engine(&config, &token_manager, Some(client.clone()))?;
+    sync_engine.load_snapshot().await?;
+
+    let conflicts = sync_engine.unresolved_conflicts().await;
+    if conflicts.is_empty() {
+        println!("✓ 没有未解决的冲突");
+        return Ok(());
+    }
+
+    println!("发现 {} 个未解决的冲突", conflicts.len());
+    let options = ["保留本地版本", "保留远程版本", "在编辑器中合并", "跳过"];
+    let mut resolved = 0;
+
+    for state in &conflicts {
+        let display_path = state
+            .path
+            .strip_prefix(&config.sync.claude_dir)
+            .unwrap_or(&state.path);
+        println!("\n⚠️  {}", display_path.display());
+        if let Some(message) = &state.error_message {
+            println!("   {}", message);
+        }
+
+        let selection = dialoguer::Select::new()
+            .with_prompt("选择解决方式")
+            .items(&options[..])
+            .default(options.len() - 1)
+            .interact()?;
+        let choice = match selection {
+            0 => ConflictChoice::KeepLocal,
+            1 => ConflictChoice::KeepRemote,
+            2 => match edit_conflict(&state.path)? {
+                Some(content) => ConflictChoice::Edited(content),
+                None => {
+                    println!("未保存编辑，跳过");
+                    ConflictChoice::Skip
+                }
+            },
+            _ => ConflictChoice::Skip,
+        };
+        if choice == ConflictChoice::Skip {
+            continue;
+        }
+
+        match sync_engine
+            .resolve_conflict(client.as_ref(), &state.path, choice)
+            .await
+        {
+            Ok(_) => {
+                resolved += 1;
+                println!("✓ 已解决");
+            }
+            Err(e) => println!("❌ {:#}", e),
+        }
+    }
+
+    // 保存解决后的同步状态
+    sync_engine.close().await?;
+    println!("\n已解决 {}/{} 个冲突", resolved, conflicts.len());
+
+    Ok(())
+}
+
+/// 在编辑器中打开冲突内容（优先使用带冲突标记的 `.conflict` 文件），返回保存后的内容
+fn edit_conflict(file_path: &Path) -> Result<Option<String>> {
+    let marker_path = sync::conflict_marker_path(file_path);
+    let source = if marker_path.exists() {
+        marker_path
+    } else {
+        file_path.to_path_buf()
+    };
+    let content =
+        std::fs::read_to_string(&source).with_context(|| format!("无法读取文件: {:?}", source))?;
+
+    let mut editor = dialoguer::Editor::new();
+    if let Some(ext) = file_path.extension() {
+        editor.extension(&format!(".{}", ext.to_string_lossy()));
+    }
+    Ok(editor.edit(&content)?)
+}
+
+/// 强制按指定方向同步单个文件
+async fn handle_force_sync(path: PathBuf, direction: ForcedDirection) -> Result<()> {
+    let config = Arc::new(ClientConfig::load()?);
+    config.validate()?;
+
+    let file_path = if path.is_absolute() {
+        path
+    } else {
+        config.sync.claude_dir.join(path)
+    };
+
+    let (client, token_manager) = connect_authenticated(&config).await?;
+    let client = Arc::new(client);
+    let sync_engine = create_sync_engine(&config, &token_manager, Some(client.clone()))?;
+    sync_engine.load_snapshot().await?;
+
+    let result = sync_engine
+        .force_sync_file(client.as_ref(), &file_path, direction)
+        .await;
+    // 保存更新后的同步状态
+    sync_engine.close().await?;
+    result?;
+
+    match direction {
+        ForcedDirection::Push => println!("✓ 已上传本地版本: {}", file_path.display()),
+        ForcedDirection::Pull => println!("✓ 已使用远程版本覆盖本地: {}", file_path.display()),
+    }
+
+    Ok(())
+}
+
+/// 显示冲突自动解决审计日志
+fn handle_audit(limit: usize) -> Result<()> {
+    let config = ClientConfig::load()?;
+    let audit_log = AuditLog::new(config.conflict.audit_log.clone());
+    let entries = audit_log.recent(limit)?;
+
+    if entries.is_empty() {
+        println!("没有自动解决的冲突记录");
+        return Ok(());
+    }
+
+    println!("最近 {} 条自动解决的冲突:", entries.len());
+    for entry in &entries {
+        println!(
+            "  {} {:?} [{}] {}+{} -> {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.path,
+            entry.strategy,
+            &entry.local_hash[..entry.local_hash.len().min(8)],
+            &entry.remote_hash[..entry.remote_hash.len().min(8)],
+            &entry.merged_hash[..entry.merged_hash.len().min(8)]
+        );
+    }
+
+    Ok(())
+}
+
+/// 处理离线队列命令
+async fn handle_queue(command: QueueCommands) -> Result<()> {
+    let config = ClientConfig::load()?;
+    let queue: retry::OfflineQueue<network::OfflineOperation> = retry::OfflineQueue::persistent(
+        network::OFFLINE_QUEUE_MAX_SIZE,
+        config.sync.offline_queue_file.clone(),
+    )?;
+
+    match command {
+        QueueCommands::List => {
+            let operations = queue.items().await;
+            if operations.is_empty() {
+                println!("离线队列为空");
+                return Ok(());
+            }
+
+            println!("离线队列中有 {} 个操作:", operations.len());
+            println!("{:<5} {:<10} 目标", "序号", "类型");
+            println!("{}", "-".repeat(70));
+            for (index, operation) in operations.iter().enumerate() {
+                println!(
+                    "{:<5} {:<10} {}",
+                    index + 1,
+                    operation.kind(),
+                    operation.target()
+                );
+            }
+        }
+        QueueCommands::Clear { indexes, all } => {
+            // 运行中的守护进程持有同一队列并会重写文件，清理前需先停止
+            let _instance_lock = instance::InstanceLock::acquire(
+                instance::default_lock_path()?,
+                &config.sync.claude_dir,
+            )
+            .context("守护进程正在运行，请先停止后再清理离线队列")?;
+
+            if all {
+                let count = queue.len().await;
+                queue.clear().await;
+                println!("✓ 已清空离线队列（{} 个操作）", count);
+                return Ok(());
+            }
+            if indexes.is_empty() {
+                anyhow::bail!("请指定要删除的操作序号，或使用 --all 清空整个队列");
+            }
+
+            let count = queue.len().await;
+            if let Some(invalid) = indexes.iter().find(|&&index| index == 0 || index > count) {
+                anyhow::bail!("无效的序号: {}（队列中有 {} 个操作）", invalid, count);
+            }
+            let positions: Vec<usize> = indexes.iter().map(|index| index - 1).collect();
+            for operation in queue.remove(&positions).await {
+                println!("✓ 已删除: {} {}", operation.kind(), operation.target());
+            }
+        }
+    }
 
     Ok(())
 }
@@ -520,6 +1876,7 @@ async fn handle_rules(command: RuleCommands) -> Result<()> {
                 priority,
                 enabled: true,
                 description: None,
+                rewrite: None,
             };
 
             // 验证规则
@@ -582,12 +1939,45 @@ async fn handle_health_check() -> Result<()> {
     Ok(())
 }
 
+/// 处理诊断命令
+async fn handle_doctor() -> Result<()> {
+    info!("运行诊断检查...");
+
+    let config_path = ClientConfig::config_path()?;
+    let results = doctor::run_diagnostics(&config_path).await;
+
+    println!("🩺 Claude Sync 诊断\n");
+    doctor::print_report(&results);
+
+    let failures = results.iter().filter(|r| r.is_critical_failure()).count();
+    if failures > 0 {
+        anyhow::bail!("诊断发现 {} 个严重问题", failures);
+    }
+
+    println!("\n✓ 所有关键检查均已通过");
+
+    Ok(())
+}
+
 /// 处理性能指标导出
-async fn handle_metrics(format: String, output: Option<String>) -> Result<()> {
+async fn handle_metrics(
+    format: String,
+    output: Option<String>,
+    output_format: String,
+) -> Result<()> {
     info!("导出性能指标...");
+    let summary_format = SummaryFormat::parse(&output_format)
+        .ok_or_else(|| anyhow::anyhow!("不支持的摘要格式: {}", output_format))?;
 
-    // 创建监控管理器（实际使用时应该从共享状态获取）
+    // 读取同步引擎关闭时落盘的性能指标
+    let config = ClientConfig::load()?;
     let manager = MonitoringManager::new(1000, 1000);
+    let metrics_file = &config.performance.metrics_file;
+    if metrics_file.exists() {
+        manager.load_metrics(metrics_file).await?;
+    } else {
+        println!("⚠️  尚无性能指标记录: {:?}", metrics_file);
+    }
 
     // 根据格式导出指标
     let content = match format.as_str() {
@@ -609,12 +1999,28 @@ async fn handle_metrics(format: String, output: Option<String>) -> Result<()> {
         println!("{}", content);
     }
 
-    // 同时打印性能摘要
-    manager.print_performance_summary().await;
+    // 同时输出性能摘要
+    println!(
+        "{}",
+        manager.performance_summary().await.format(summary_format)?
+    );
 
     Ok(())
 }
 
+/// 按配置创建冲突标记风格
+fn conflict_marker_style(conflict: &config::ConflictConfig) -> ConflictMarkerStyle {
+    match conflict.conflict_marker_style.as_str() {
+        "diff3" => ConflictMarkerStyle::Diff3,
+        "custom" => ConflictMarkerStyle::Custom {
+            local: conflict.conflict_marker_labels.local.clone(),
+            base: conflict.conflict_marker_labels.base.clone(),
+            remote: conflict.conflict_marker_labels.remote.clone(),
+        },
+        _ => ConflictMarkerStyle::Git2,
+    }
+}
+
 /// 创建进度条
 fn create_progress_bar(len: u64) -> ProgressBar {
     let pb = indicatif::ProgressBar::new(len);
@@ -0,0 +1,89 @@
+use crate::monitoring::MonitoringManager;
+use anyhow::{Context, Result};
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Prometheus 文本格式的 Content-Type
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// 指标端点服务（守护进程模式下供 Prometheus 抓取）
+pub struct MetricsServer {
+    monitoring: MonitoringManager,
+}
+
+impl MetricsServer {
+    /// 创建指标端点服务，与同步引擎共享同一个监控管理器
+    pub fn new(monitoring: MonitoringManager) -> Self {
+        Self { monitoring }
+    }
+
+    /// 绑定地址并在后台启动服务，返回实际监听地址
+    pub async fn spawn(self, addr: &str) -> Result<(SocketAddr, JoinHandle<()>)> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("无法绑定指标端点地址: {}", addr))?;
+        let local_addr = listener.local_addr()?;
+
+        let app = Router::new()
+            .route("/metrics", get(metrics_handler))
+            .with_state(self.monitoring);
+
+        info!("✓ 指标端点已启动: http://{}/metrics", local_addr);
+
+        let handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("指标端点异常退出: {}", e);
+            }
+        });
+
+        Ok((local_addr, handle))
+    }
+}
+
+/// /metrics 处理器
+async fn metrics_handler(State(monitoring): State<MonitoringManager>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        monitoring.export_metrics_prometheus().await,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_endpoint_serves_live_metrics() {
+        let monitoring = MonitoringManager::new(100, 1000);
+        let (addr, handle) = MetricsServer::new(monitoring.clone())
+            .spawn("127.0.0.1:0")
+            .await
+            .unwrap();
+
+        // 服务启动后记录的指标也应可见
+        monitoring
+            .record_counter("files_synced", 42.0, vec![])
+            .await;
+
+        let response = reqwest::get(format!("http://{}/metrics", addr))
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        assert!(response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+
+        let body = response.text().await.unwrap();
+        assert!(body.contains("files_synced"), "{}", body);
+        assert!(body.contains("42"), "{}", body);
+
+        handle.abort();
+    }
+}
@@ -1,13 +1,15 @@
 use crate::error::ClientError;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{debug, info, span, warn, Level};
 
 /// 性能指标
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metric {
     /// 指标名称
     pub name: String,
@@ -26,7 +28,7 @@ pub struct Metric {
 }
 
 /// 指标类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MetricType {
     /// 计数器
     Counter,
@@ -42,7 +44,7 @@ pub enum MetricType {
 }
 
 /// 性能统计
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceStats {
     /// 同步总次数
     pub sync_total_count: u64,
@@ -81,14 +83,250 @@ pub struct PerformanceStats {
     pub last_updated: DateTime<Utc>,
 }
 
+/// 性能摘要的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    /// 多行文本
+    Text,
+    /// JSON
+    Json,
+    /// 单行摘要
+    Compact,
+}
+
+impl SummaryFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "compact" => Some(Self::Compact),
+            _ => None,
+        }
+    }
+}
+
+/// 同步持续时间分位数（毫秒）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DurationPercentiles {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+/// 性能摘要（性能统计加上派生的成功率和分位数）
+#[derive(Debug, Clone, Serialize)]
+pub struct PerformanceSummary {
+    #[serde(flatten)]
+    pub stats: PerformanceStats,
+
+    /// 同步成功率（百分比，没有同步记录时为 None）
+    pub success_rate: Option<f64>,
+
+    /// 同步持续时间分位数（没有同步记录时为 None）
+    pub sync_duration_percentiles: Option<DurationPercentiles>,
+}
+
+impl PerformanceSummary {
+    pub fn new(stats: PerformanceStats, percentiles: Option<DurationPercentiles>) -> Self {
+        let success_rate = (stats.sync_total_count > 0)
+            .then(|| (stats.sync_success_count as f64 / stats.sync_total_count as f64) * 100.0);
+        Self {
+            stats,
+            success_rate,
+            sync_duration_percentiles: percentiles,
+        }
+    }
+
+    /// 按指定格式输出
+    pub fn format(&self, format: SummaryFormat) -> Result<String, ClientError> {
+        match format {
+            SummaryFormat::Text => Ok(self.text_lines().join("\n")),
+            SummaryFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| ClientError::internal("无法序列化性能摘要", Some(Box::new(e)))),
+            SummaryFormat::Compact => Ok(self.compact_line()),
+        }
+    }
+
+    /// 多行文本格式的各行
+    fn text_lines(&self) -> Vec<String> {
+        let stats = &self.stats;
+        let mut lines = vec![
+            "========== 性能统计摘要 ==========".to_string(),
+            format!("同步总次数: {}", stats.sync_total_count),
+            format!("同步成功次数: {}", stats.sync_success_count),
+            format!("同步失败次数: {}", stats.sync_failure_count),
+        ];
+        if let Some(success_rate) = self.success_rate {
+            lines.push(format!("同步成功率: {:.1}%", success_rate));
+        }
+        lines.extend([
+            format!("文件上传总数: {}", stats.upload_total_count),
+            format!("文件下载总数: {}", stats.download_total_count),
+            format!("上传字节总数: {} bytes", stats.upload_total_bytes),
+            format!("下载字节总数: {} bytes", stats.download_total_bytes),
+            format!("平均同步持续时间: {:.2} ms", stats.avg_sync_duration_ms),
+        ]);
+        if let Some(p) = self.sync_duration_percentiles {
+            lines.push(format!(
+                "同步持续时间分位数: p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms",
+                p.p50, p.p95, p.p99
+            ));
+        }
+        lines.extend([
+            format!("平均上传速度: {:.2} bytes/s", stats.avg_upload_speed),
+            format!("平均下载速度: {:.2} bytes/s", stats.avg_download_speed),
+            format!("网络状态: {}", stats.network_status),
+            format!("最后更新: {}", stats.last_updated),
+            "==================================".to_string(),
+        ]);
+        lines
+    }
+
+    /// 单行格式（便于脚本和状态栏读取）
+    fn compact_line(&self) -> String {
+        let stats = &self.stats;
+        let mut line = format!(
+            "sync={}/{}/{}",
+            stats.sync_total_count, stats.sync_success_count, stats.sync_failure_count
+        );
+        if let Some(success_rate) = self.success_rate {
+            line.push_str(&format!(" success={:.1}%", success_rate));
+        }
+        line.push_str(&format!(
+            " up={}files/{}B down={}files/{}B avg={:.2}ms",
+            stats.upload_total_count,
+            stats.upload_total_bytes,
+            stats.download_total_count,
+            stats.download_total_bytes,
+            stats.avg_sync_duration_ms
+        ));
+        if let Some(p) = self.sync_duration_percentiles {
+            line.push_str(&format!(" p95={:.2}ms", p.p95));
+        }
+        line.push_str(&format!(" network={}", stats.network_status));
+        line
+    }
+}
+
+/// 落盘的指标快照（供 metrics 命令读取守护进程记录的数据）
+#[derive(Debug, Serialize, Deserialize)]
+struct MetricsSnapshot {
+    stats: PerformanceStats,
+    metrics: Vec<Metric>,
+}
+
+/// 默认直方图桶上界（毫秒）
+pub const DEFAULT_HISTOGRAM_BUCKETS: &[f64] = &[
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+/// 直方图累计数据
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramData {
+    /// 桶上界（升序，不含 +Inf）
+    pub bounds: Vec<f64>,
+
+    /// 各桶的累计计数（与 bounds 一一对应，值 <= 上界）
+    pub bucket_counts: Vec<u64>,
+
+    /// 观测值总和
+    pub sum: f64,
+
+    /// 观测次数
+    pub count: u64,
+}
+
+impl HistogramData {
+    /// 创建空直方图
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            bucket_counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// 记录一个观测值
+    pub fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.bucket_counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// 直方图键：指标名称 + 标签
+type HistogramKey = (String, Vec<(String, String)>);
+
+/// 指标环形缓冲区（超过数量或内存上限时从最旧的指标开始淘汰）
+#[derive(Debug, Default)]
+struct MetricBuffer {
+    /// 按记录顺序保存的指标
+    entries: VecDeque<Metric>,
+
+    /// 所有指标的估算内存占用（字节）
+    total_bytes: usize,
+}
+
+impl MetricBuffer {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            total_bytes: 0,
+        }
+    }
+
+    /// 追加指标，并淘汰最旧的指标直到满足数量和内存上限
+    fn push(&mut self, metric: Metric, max_metrics: usize, max_bytes: Option<usize>) {
+        self.total_bytes += metric_size(&metric);
+        self.entries.push_back(metric);
+
+        while self.entries.len() > max_metrics
+            || max_bytes.is_some_and(|max| self.total_bytes > max && self.entries.len() > 1)
+        {
+            let Some(oldest) = self.entries.pop_front() else {
+                break;
+            };
+            self.total_bytes -= metric_size(&oldest);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.total_bytes = 0;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Metric> {
+        self.entries.iter()
+    }
+}
+
+/// 估算单个指标占用的内存（结构体本身加上名称和标签字符串）
+fn metric_size(metric: &Metric) -> usize {
+    std::mem::size_of::<Metric>()
+        + metric.name.len()
+        + metric
+            .tags
+            .iter()
+            .map(|(k, v)| std::mem::size_of::<(String, String)>() + k.len() + v.len())
+            .sum::<usize>()
+}
+
 /// 监控管理器
 pub struct MonitoringManager {
     /// 性能指标
-    metrics: Arc<RwLock<Vec<Metric>>>,
+    metrics: Arc<RwLock<MetricBuffer>>,
 
     /// 最大指标数量
     max_metrics: usize,
 
+    /// 指标缓冲区的内存上限（字节，None 表示只按数量限制）
+    max_bytes: Option<usize>,
+
     /// 性能统计
     stats: Arc<RwLock<PerformanceStats>>,
 
@@ -97,14 +335,21 @@ pub struct MonitoringManager {
 
     /// 慢操作阈值（毫秒）
     slow_operation_threshold_ms: u64,
+
+    /// 直方图累计数据
+    histograms: Arc<RwLock<BTreeMap<HistogramKey, HistogramData>>>,
+
+    /// 直方图桶上界
+    histogram_buckets: Vec<f64>,
 }
 
 impl MonitoringManager {
     /// 创建新的监控管理器
     pub fn new(max_metrics: usize, slow_operation_threshold_ms: u64) -> Self {
         Self {
-            metrics: Arc::new(RwLock::new(Vec::with_capacity(max_metrics))),
+            metrics: Arc::new(RwLock::new(MetricBuffer::with_capacity(max_metrics))),
             max_metrics,
+            max_bytes: None,
             stats: Arc::new(RwLock::new(PerformanceStats {
                 sync_total_count: 0,
                 sync_success_count: 0,
@@ -121,23 +366,45 @@ impl MonitoringManager {
             })),
             enabled: Arc::new(RwLock::new(true)),
             slow_operation_threshold_ms,
+            histograms: Arc::new(RwLock::new(BTreeMap::new())),
+            histogram_buckets: DEFAULT_HISTOGRAM_BUCKETS.to_vec(),
         }
     }
 
+    /// 设置指标缓冲区的内存上限（按名称和标签长度估算）
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// 设置直方图桶上界
+    pub fn with_histogram_buckets(mut self, mut buckets: Vec<f64>) -> Self {
+        buckets.retain(|b| b.is_finite());
+        buckets.sort_by(|a, b| a.total_cmp(b));
+        buckets.dedup();
+        self.histogram_buckets = buckets;
+        self
+    }
+
     /// 记录指标
     pub async fn record_metric(&self, metric: Metric) {
         if !*self.enabled.read().await {
             return;
         }
 
-        let mut metrics = self.metrics.write().await;
-
-        // 如果达到最大容量，移除最旧的指标
-        if metrics.len() >= self.max_metrics {
-            metrics.remove(0);
+        if metric.metric_type == MetricType::Histogram {
+            let mut histograms = self.histograms.write().await;
+            histograms
+                .entry((metric.name.clone(), metric.tags.clone()))
+                .or_insert_with(|| HistogramData::new(&self.histogram_buckets))
+                .observe(metric.value);
         }
 
-        metrics.push(metric);
+        // 达到数量或内存上限时淘汰最旧的指标
+        self.metrics
+            .write()
+            .await
+            .push(metric, self.max_metrics, self.max_bytes);
     }
 
     /// 记录计数器
@@ -196,7 +463,7 @@ impl MonitoringManager {
 
     /// 获取所有指标
     pub async fn get_metrics(&self) -> Vec<Metric> {
-        self.metrics.read().await.clone()
+        self.metrics.read().await.iter().cloned().collect()
     }
 
     /// 获取指定名称的指标
@@ -205,9 +472,40 @@ impl MonitoringManager {
         metrics.iter().filter(|m| m.name == name).cloned().collect()
     }
 
+    /// 获取指定名称的直方图（按标签区分）
+    pub async fn get_histograms(&self, name: &str) -> Vec<(Vec<(String, String)>, HistogramData)> {
+        let histograms = self.histograms.read().await;
+        histograms
+            .iter()
+            .filter(|((n, _), _)| n == name)
+            .map(|((_, tags), data)| (tags.clone(), data.clone()))
+            .collect()
+    }
+
+    /// 计算指定指标的分位数（基于缓冲区中保留的原始值，nearest-rank）
+    pub async fn percentile(&self, name: &str, quantile: f64) -> Option<f64> {
+        let mut values: Vec<f64> = self
+            .metrics
+            .read()
+            .await
+            .iter()
+            .filter(|m| m.name == name)
+            .map(|m| m.value)
+            .collect();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(|a, b| a.total_cmp(b));
+        let rank = (quantile.clamp(0.0, 1.0) * values.len() as f64).ceil() as usize;
+        Some(values[rank.saturating_sub(1)])
+    }
+
     /// 清空指标
     pub async fn clear_metrics(&self) {
         self.metrics.write().await.clear();
+        self.histograms.write().await.clear();
         info!("性能指标已清空");
     }
 
@@ -297,16 +595,56 @@ impl MonitoringManager {
             .map_err(|e| ClientError::internal("无法序列化指标", Some(Box::new(e))))
     }
 
+    /// 将性能统计和指标以 JSON 格式写入文件
+    pub async fn save_metrics(&self, path: &Path) -> Result<(), ClientError> {
+        let snapshot = MetricsSnapshot {
+            stats: self.get_performance_stats().await,
+            metrics: self.get_metrics().await,
+        };
+        let content = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| ClientError::internal("无法序列化指标", Some(Box::new(e))))?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| {
+                ClientError::file(parent.display().to_string(), "无法创建指标目录", Some(e))
+            })?;
+        }
+
+        tokio::fs::write(path, content)
+            .await
+            .map_err(|e| ClientError::file(path.display().to_string(), "无法写入指标文件", Some(e)))
+    }
+
+    /// 从文件加载 save_metrics 写出的性能统计和指标
+    pub async fn load_metrics(&self, path: &Path) -> Result<(), ClientError> {
+        let content = tokio::fs::read_to_string(path).await.map_err(|e| {
+            ClientError::file(path.display().to_string(), "无法读取指标文件", Some(e))
+        })?;
+        let snapshot: MetricsSnapshot = serde_json::from_str(&content)
+            .map_err(|e| ClientError::internal("无法解析指标文件", Some(Box::new(e))))?;
+
+        *self.stats.write().await = snapshot.stats;
+
+        // 逐条回放以重建直方图
+        for metric in snapshot.metrics {
+            self.record_metric(metric).await;
+        }
+
+        Ok(())
+    }
+
     /// 导出指标为 Prometheus 格式
     pub async fn export_metrics_prometheus(&self) -> String {
         let metrics = self.get_metrics().await;
         let mut output = String::new();
 
-        // 按指标名称分组
-        let mut grouped_metrics: std::collections::HashMap<String, Vec<&Metric>> =
-            std::collections::HashMap::new();
+        // 按指标名称分组（直方图单独输出）
+        let mut grouped_metrics: BTreeMap<String, Vec<&Metric>> = BTreeMap::new();
 
         for metric in &metrics {
+            if metric.metric_type == MetricType::Histogram {
+                continue;
+            }
             grouped_metrics
                 .entry(metric.name.clone())
                 .or_default()
@@ -330,19 +668,60 @@ impl MonitoringManager {
 
             // 输出指标值（使用最新值）
             if let Some(latest) = group.last() {
-                let tags_str = if latest.tags.is_empty() {
-                    String::new()
-                } else {
-                    let tags: Vec<String> = latest
-                        .tags
-                        .iter()
-                        .map(|(k, v)| format!("{}=\"{}\"", k, v))
-                        .collect();
-                    format!("{{{}}}", tags.join(","))
-                };
-                output.push_str(&format!("{}{} {}\n", name, tags_str, latest.value));
+                output.push_str(&format!(
+                    "{}{} {}\n",
+                    name,
+                    format_labels(&latest.tags, None),
+                    latest.value
+                ));
+            }
+
+            output.push('\n');
+        }
+
+        // 直方图：输出 _bucket / _sum / _count
+        let histograms = self.histograms.read().await;
+        let mut last_name: Option<&str> = None;
+
+        for ((name, tags), data) in histograms.iter() {
+            if last_name != Some(name.as_str()) {
+                if last_name.is_some() {
+                    output.push('\n');
+                }
+                output.push_str(&format!("# HELP {} {}\n", name, name));
+                output.push_str(&format!("# TYPE {} histogram\n", name));
+                last_name = Some(name.as_str());
+            }
+
+            for (bound, count) in data.bounds.iter().zip(data.bucket_counts.iter()) {
+                output.push_str(&format!(
+                    "{}_bucket{} {}\n",
+                    name,
+                    format_labels(tags, Some(&bound.to_string())),
+                    count
+                ));
             }
+            output.push_str(&format!(
+                "{}_bucket{} {}\n",
+                name,
+                format_labels(tags, Some("+Inf")),
+                data.count
+            ));
+            output.push_str(&format!(
+                "{}_sum{} {}\n",
+                name,
+                format_labels(tags, None),
+                data.sum
+            ));
+            output.push_str(&format!(
+                "{}_count{} {}\n",
+                name,
+                format_labels(tags, None),
+                data.count
+            ));
+        }
 
+        if last_name.is_some() {
             output.push('\n');
         }
 
@@ -386,31 +765,24 @@ impl MonitoringManager {
         }
     }
 
-    /// 打印性能摘要
+    /// 生成性能摘要
+    pub async fn performance_summary(&self) -> PerformanceSummary {
+        let percentiles = match (
+            self.percentile("sync_duration_ms", 0.50).await,
+            self.percentile("sync_duration_ms", 0.95).await,
+            self.percentile("sync_duration_ms", 0.99).await,
+        ) {
+            (Some(p50), Some(p95), Some(p99)) => Some(DurationPercentiles { p50, p95, p99 }),
+            _ => None,
+        };
+        PerformanceSummary::new(self.get_performance_stats().await, percentiles)
+    }
+
+    /// 将性能摘要写入日志
     pub async fn print_performance_summary(&self) {
-        let stats = self.get_performance_stats().await;
-
-        info!("========== 性能统计摘要 ==========");
-        info!("同步总次数: {}", stats.sync_total_count);
-        info!("同步成功次数: {}", stats.sync_success_count);
-        info!("同步失败次数: {}", stats.sync_failure_count);
-
-        if stats.sync_total_count > 0 {
-            let success_rate =
-                (stats.sync_success_count as f64 / stats.sync_total_count as f64) * 100.0;
-            info!("同步成功率: {:.1}%", success_rate);
-        }
-
-        info!("文件上传总数: {}", stats.upload_total_count);
-        info!("文件下载总数: {}", stats.download_total_count);
-        info!("上传字节总数: {} bytes", stats.upload_total_bytes);
-        info!("下载字节总数: {} bytes", stats.download_total_bytes);
-        info!("平均同步持续时间: {:.2} ms", stats.avg_sync_duration_ms);
-        info!("平均上传速度: {:.2} bytes/s", stats.avg_upload_speed);
-        info!("平均下载速度: {:.2} bytes/s", stats.avg_download_speed);
-        info!("网络状态: {}", stats.network_status);
-        info!("最后更新: {}", stats.last_updated);
-        info!("==================================");
+        for line in self.performance_summary().await.text_lines() {
+            info!("{}", line);
+        }
     }
 }
 
@@ -419,13 +791,34 @@ impl Clone for MonitoringManager {
         Self {
             metrics: Arc::clone(&self.metrics),
             max_metrics: self.max_metrics,
+            max_bytes: self.max_bytes,
             stats: Arc::clone(&self.stats),
             enabled: Arc::clone(&self.enabled),
             slow_operation_threshold_ms: self.slow_operation_threshold_ms,
+            histograms: Arc::clone(&self.histograms),
+            histogram_buckets: self.histogram_buckets.clone(),
         }
     }
 }
 
+/// 格式化 Prometheus 标签，可附加 `le` 标签
+fn format_labels(tags: &[(String, String)], le: Option<&str>) -> String {
+    let mut labels: Vec<String> = tags
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v))
+        .collect();
+
+    if let Some(le) = le {
+        labels.push(format!("le=\"{}\"", le));
+    }
+
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
 /// 同步计时器
 pub struct SyncTimer {
     manager: MonitoringManager,
@@ -566,6 +959,97 @@ mod tests {
         assert_eq!(stats.download_total_bytes, 2048);
     }
 
+    fn fixed_summary() -> PerformanceSummary {
+        let stats = PerformanceStats {
+            sync_total_count: 4,
+            sync_success_count: 3,
+            sync_failure_count: 1,
+            upload_total_count: 5,
+            download_total_count: 2,
+            upload_total_bytes: 1024,
+            download_total_bytes: 2048,
+            avg_sync_duration_ms: 12.5,
+            avg_upload_speed: 512.0,
+            avg_download_speed: 256.0,
+            network_status: "online".to_string(),
+            last_updated: DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        };
+        PerformanceSummary::new(
+            stats,
+            Some(DurationPercentiles {
+                p50: 10.0,
+                p95: 20.0,
+                p99: 30.0,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_summary_text_format() {
+        let text = fixed_summary().format(SummaryFormat::Text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.first(), Some(&"========== 性能统计摘要 =========="));
+        assert!(lines.contains(&"同步成功率: 75.0%"));
+        assert!(lines.contains(&"上传字节总数: 1024 bytes"));
+        assert!(lines.contains(&"同步持续时间分位数: p50 10.00 ms, p95 20.00 ms, p99 30.00 ms"));
+        assert!(lines.contains(&"最后更新: 2024-01-02 03:04:05 UTC"));
+
+        // 没有同步记录时不输出成功率和分位数
+        let mut summary = fixed_summary();
+        summary.stats.sync_total_count = 0;
+        let summary = PerformanceSummary::new(summary.stats, None);
+        let text = summary.format(SummaryFormat::Text).unwrap();
+        assert!(!text.contains("同步成功率"));
+        assert!(!text.contains("分位数"));
+    }
+
+    #[test]
+    fn test_summary_json_format() {
+        let json = fixed_summary().format(SummaryFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["sync_total_count"], 4);
+        assert_eq!(value["upload_total_bytes"], 1024);
+        assert_eq!(value["success_rate"], 75.0);
+        assert_eq!(value["sync_duration_percentiles"]["p95"], 20.0);
+        assert_eq!(value["network_status"], "online");
+    }
+
+    #[test]
+    fn test_summary_compact_format() {
+        assert_eq!(
+            fixed_summary().format(SummaryFormat::Compact).unwrap(),
+            "sync=4/3/1 success=75.0% up=5files/1024B down=2files/2048B avg=12.50ms p95=20.00ms network=online"
+        );
+        assert_eq!(
+            SummaryFormat::parse("compact"),
+            Some(SummaryFormat::Compact)
+        );
+        assert_eq!(SummaryFormat::parse("xml"), None);
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_metrics() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("metrics.json");
+
+        let manager = MonitoringManager::new(100, 1000);
+        let mut timer = manager.record_sync_start().await;
+        timer.add_upload_bytes(512);
+        timer.complete(true).await;
+        manager.save_metrics(&path).await.unwrap();
+
+        let loaded = MonitoringManager::new(100, 1000);
+        loaded.load_metrics(&path).await.unwrap();
+
+        let stats = loaded.get_performance_stats().await;
+        assert_eq!(stats.sync_total_count, 1);
+        assert_eq!(stats.upload_total_bytes, 512);
+        assert_eq!(loaded.get_metrics_by_name("upload_bytes").await.len(), 1);
+        assert_eq!(loaded.get_histograms("sync_duration_ms").await.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_metrics_export() {
         let manager = MonitoringManager::new(100, 1000);
@@ -594,4 +1078,91 @@ mod tests {
             .await;
         assert!(!metrics.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_histogram_export() {
+        let manager =
+            MonitoringManager::new(100, 10_000).with_histogram_buckets(vec![100.0, 500.0, 1000.0]);
+
+        for duration in [50, 120, 480, 900, 3000] {
+            manager.update_sync_stats(true, duration, 0, 0).await;
+        }
+
+        let histograms = manager.get_histograms("sync_duration_ms").await;
+        assert_eq!(histograms.len(), 1);
+        let (_, data) = &histograms[0];
+        assert_eq!(data.bucket_counts, vec![1, 3, 4]);
+        assert_eq!(data.count, 5);
+        assert_eq!(data.sum, 4550.0);
+
+        let prometheus = manager.export_metrics_prometheus().await;
+        assert!(prometheus.contains("# TYPE sync_duration_ms histogram"));
+        assert!(prometheus.contains("sync_duration_ms_bucket{success=\"true\",le=\"100\"} 1"));
+        assert!(prometheus.contains("sync_duration_ms_bucket{success=\"true\",le=\"500\"} 3"));
+        assert!(prometheus.contains("sync_duration_ms_bucket{success=\"true\",le=\"1000\"} 4"));
+        assert!(prometheus.contains("sync_duration_ms_bucket{success=\"true\",le=\"+Inf\"} 5"));
+        assert!(prometheus.contains("sync_duration_ms_sum{success=\"true\"} 4550"));
+        assert!(prometheus.contains("sync_duration_ms_count{success=\"true\"} 5"));
+
+        // 分位数
+        assert_eq!(
+            manager.percentile("sync_duration_ms", 0.5).await,
+            Some(480.0)
+        );
+        assert_eq!(
+            manager.percentile("sync_duration_ms", 0.99).await,
+            Some(3000.0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eviction_keeps_newest_under_heavy_insertion() {
+        let manager = MonitoringManager::new(1000, 1000);
+
+        let started = Instant::now();
+        for i in 0..100_000 {
+            manager
+                .record_counter("ops", i as f64, vec![("run".to_string(), i.to_string())])
+                .await;
+        }
+        // 环形缓冲区淘汰为 O(1)，十万次插入应远低于该上限
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let metrics = manager.get_metrics().await;
+        assert_eq!(metrics.len(), 1000);
+        assert_eq!(metrics.first().unwrap().value, 99_000.0);
+        assert_eq!(metrics.last().unwrap().value, 99_999.0);
+        assert!(metrics.windows(2).all(|w| w[0].value < w[1].value));
+    }
+
+    #[tokio::test]
+    async fn test_memory_cap_evicts_oldest() {
+        let long_tag = "x".repeat(1024);
+        let one = metric_size(&Metric {
+            name: "upload".to_string(),
+            value: 0.0,
+            metric_type: MetricType::Counter,
+            timestamp: Utc::now(),
+            tags: vec![("path".to_string(), long_tag.clone())],
+        });
+        let manager = MonitoringManager::new(1000, 1000).with_max_bytes(one * 10);
+
+        for i in 0..50 {
+            manager
+                .record_counter(
+                    "upload",
+                    i as f64,
+                    vec![("path".to_string(), long_tag.clone())],
+                )
+                .await;
+        }
+
+        let metrics = manager.get_metrics().await;
+        assert_eq!(metrics.len(), 10);
+        assert_eq!(metrics.first().unwrap().value, 40.0);
+        assert!(manager.metrics.read().await.total_bytes <= one * 10);
+
+        manager.clear_metrics().await;
+        assert_eq!(manager.metrics.read().await.total_bytes, 0);
+    }
 }
@@ -1,7 +1,9 @@
 use crate::error::ClientError;
 use crate::retry::{OfflineQueue, RetryConfig, RetryExecutor};
-use std::sync::Arc;
-use std::time::Duration;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 use tracing::{debug, info, warn};
@@ -22,6 +24,147 @@ pub enum NetworkStatus {
     Unknown,
 }
 
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// 关闭（正常放行请求）
+    Closed,
+
+    /// 打开（冷却期内直接失败）
+    Open,
+
+    /// 半开（允许一个探测请求）
+    HalfOpen,
+}
+
+impl std::fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitState::Closed => write!(f, "closed"),
+            CircuitState::Open => write!(f, "open"),
+            CircuitState::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// 熔断器（连续失败达到阈值后在冷却期内快速失败）
+pub struct CircuitBreaker {
+    /// 打开熔断器的连续失败次数
+    failure_threshold: usize,
+
+    /// 冷却时间
+    cooldown: Duration,
+
+    /// 内部状态
+    inner: Mutex<BreakerInner>,
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: usize,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    /// 创建新的熔断器
+    pub fn new(failure_threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 获取当前状态（冷却期结束后视为半开）
+    pub fn state(&self) -> CircuitState {
+        let inner = self.lock();
+        match inner.state {
+            CircuitState::Open if self.cooldown_elapsed(&inner) => CircuitState::HalfOpen,
+            state => state,
+        }
+    }
+
+    fn cooldown_elapsed(&self, inner: &BreakerInner) -> bool {
+        inner
+            .opened_at
+            .map(|opened_at| opened_at.elapsed() >= self.cooldown)
+            .unwrap_or(true)
+    }
+
+    /// 请求放行，熔断器打开时快速失败
+    pub fn try_acquire(&self) -> Result<(), ClientError> {
+        let mut inner = self.lock();
+
+        if inner.state == CircuitState::Open && self.cooldown_elapsed(&inner) {
+            info!("熔断器冷却结束，进入半开状态");
+            inner.state = CircuitState::HalfOpen;
+            inner.probe_in_flight = false;
+        }
+
+        match inner.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::HalfOpen if !inner.probe_in_flight => {
+                inner.probe_in_flight = true;
+                Ok(())
+            }
+            CircuitState::HalfOpen => Err(ClientError::network(
+                "熔断器半开，正在等待探测请求结果",
+                None,
+            )),
+            CircuitState::Open => Err(ClientError::network(
+                format!(
+                    "熔断器已打开（连续失败 {} 次），暂停请求",
+                    inner.consecutive_failures
+                ),
+                None,
+            )),
+        }
+    }
+
+    /// 记录成功
+    pub fn record_success(&self) {
+        let mut inner = self.lock();
+        if inner.state != CircuitState::Closed {
+            info!("熔断器关闭，恢复正常请求");
+        }
+        inner.state = CircuitState::Closed;
+        inner.consecutive_failures = 0;
+        inner.opened_at = None;
+        inner.probe_in_flight = false;
+    }
+
+    /// 记录失败
+    pub fn record_failure(&self) {
+        let mut inner = self.lock();
+        inner.consecutive_failures += 1;
+        inner.probe_in_flight = false;
+
+        let should_open = inner.state == CircuitState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold;
+        if should_open {
+            if inner.state != CircuitState::Open {
+                warn!(
+                    "连续失败 {} 次，熔断器打开 {} 秒",
+                    inner.consecutive_failures,
+                    self.cooldown.as_secs()
+                );
+            }
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+        }
+    }
+}
+
 /// 网络恢复管理器
 pub struct NetworkRecoveryManager {
     /// 当前网络状态
@@ -47,10 +190,27 @@ pub struct NetworkRecoveryManager {
 
     /// 离线操作队列
     offline_queue: Arc<OfflineQueue<OfflineOperation>>,
+
+    /// 熔断器
+    circuit_breaker: CircuitBreaker,
+
+    /// 重新连接后执行离线操作的处理器
+    offline_handler: Mutex<Option<Weak<dyn OfflineOperationHandler>>>,
+}
+
+/// 离线操作处理器（如同步引擎），重新连接后由网络恢复管理器调用以重放离线队列
+///
+/// 管理器只持有弱引用，处理器本身通常也持有管理器。
+pub trait OfflineOperationHandler: Send + Sync {
+    /// 执行一个离线操作，失败的操作会重新放回队列
+    fn replay(&self, operation: OfflineOperation) -> BoxFuture<'_, Result<(), ClientError>>;
 }
 
+/// 离线队列最多保存的操作数
+pub const OFFLINE_QUEUE_MAX_SIZE: usize = 1000;
+
 /// 离线操作
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OfflineOperation {
     /// 文件上传
     FileUpload {
@@ -66,8 +226,41 @@ pub enum OfflineOperation {
     ReportChanges { changes: Vec<ChangeInfo> },
 }
 
+impl OfflineOperation {
+    /// 操作类型
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OfflineOperation::FileUpload { .. } => "upload",
+            OfflineOperation::FileDownload { .. } => "download",
+            OfflineOperation::ReportChanges { .. } => "report",
+        }
+    }
+
+    /// 操作目标（文件路径，变更上报为上报的所有文件）
+    pub fn target(&self) -> String {
+        match self {
+            OfflineOperation::FileUpload { path, size, .. } => {
+                format!("{} ({} bytes)", path, size)
+            }
+            OfflineOperation::FileDownload {
+                path,
+                version: Some(version),
+            } => format!("{} (版本 {})", path, version),
+            OfflineOperation::FileDownload {
+                path,
+                version: None,
+            } => path.clone(),
+            OfflineOperation::ReportChanges { changes } => changes
+                .iter()
+                .map(|change| change.file_path.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
 /// 变更信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChangeInfo {
     pub file_path: String,
     pub file_hash: String,
@@ -91,17 +284,65 @@ impl NetworkRecoveryManager {
             reconnect_interval_secs,
             max_reconnect_attempts,
             health_check_interval_secs: 30,
-            offline_queue: Arc::new(OfflineQueue::new(1000)),
+            offline_queue: Arc::new(OfflineQueue::new(OFFLINE_QUEUE_MAX_SIZE)),
+            circuit_breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            offline_handler: Mutex::new(None),
         }
     }
 
+    /// 设置熔断器参数
+    pub fn with_circuit_breaker(mut self, failure_threshold: usize, cooldown: Duration) -> Self {
+        self.circuit_breaker = CircuitBreaker::new(failure_threshold, cooldown);
+        self
+    }
+
+    /// 设置离线队列（如从文件恢复的持久化队列）
+    pub fn with_offline_queue(mut self, offline_queue: OfflineQueue<OfflineOperation>) -> Self {
+        self.offline_queue = Arc::new(offline_queue);
+        self
+    }
+
+    /// 设置离线操作处理器（管理器已被共享时也可设置）
+    pub fn set_offline_handler(&self, handler: Weak<dyn OfflineOperationHandler>) {
+        *self
+            .offline_handler
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(handler);
+    }
+
+    fn offline_handler(&self) -> Option<Arc<dyn OfflineOperationHandler>> {
+        self.offline_handler
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .and_then(Weak::upgrade)
+    }
+
     /// 获取当前网络状态
     pub async fn get_status(&self) -> NetworkStatus {
         *self.status.read().await
     }
 
+    /// 是否已知服务器不可达（离线或正在重连）
+    pub async fn is_offline(&self) -> bool {
+        matches!(
+            self.get_status().await,
+            NetworkStatus::Offline | NetworkStatus::Reconnecting
+        )
+    }
+
+    /// 离线队列中的操作数
+    pub async fn offline_queue_len(&self) -> usize {
+        self.offline_queue.len().await
+    }
+
+    /// 获取熔断器状态
+    pub fn get_circuit_state(&self) -> CircuitState {
+        self.circuit_breaker.state()
+    }
+
     /// 设置网络状态
-    async fn set_status(&self, status: NetworkStatus) {
+    pub(crate) async fn set_status(&self, status: NetworkStatus) {
         let mut current = self.status.write().await;
         if *current != status {
             info!("网络状态变更: {:?} -> {:?}", *current, status);
@@ -154,12 +395,28 @@ impl NetworkRecoveryManager {
         F: Fn() -> Fut + Clone,
         Fut: std::future::Future<Output = Result<T, ClientError>>,
     {
-        // 首先检查网络连接
-        self.ensure_online().await?;
+        // 熔断器打开时直接失败，避免持续请求已宕机的服务器
+        self.circuit_breaker.try_acquire()?;
 
-        // 执行操作
-        let executor = RetryExecutor::new(self.retry_config.clone());
-        executor.execute(operation, operation_name).await
+        let result = async {
+            // 首先检查网络连接
+            self.ensure_online().await?;
+
+            // 执行操作
+            let executor = RetryExecutor::new(self.retry_config.clone());
+            executor.execute(operation, operation_name).await
+        }
+        .await;
+
+        // 只有可重试的错误（网络、超时等）才计入熔断
+        match &result {
+            Err(err) if self.retry_config.is_retryable(err) => {
+                self.circuit_breaker.record_failure()
+            }
+            _ => self.circuit_breaker.record_success(),
+        }
+
+        result
     }
 
     /// 确保网络在线
@@ -246,8 +503,11 @@ impl NetworkRecoveryManager {
     }
 
     /// 处理离线队列
-    async fn process_offline_queue(&self) -> Result<(), ClientError> {
-        let operations = self.offline_queue.drain().await;
+    ///
+    /// 每个操作成功后才从队列（及持久化文件）中删除，失败的操作留在队列中等待下次重连，
+    /// 处理过程中进程退出时未完成的操作在重启后仍会重放。
+    pub async fn process_offline_queue(&self) -> Result<(), ClientError> {
+        let operations = self.offline_queue.items().await;
 
         if operations.is_empty() {
             return Ok(());
@@ -255,20 +515,27 @@ impl NetworkRecoveryManager {
 
         info!("处理离线队列中的 {} 个操作", operations.len());
 
-        for operation in operations {
-            if let Err(e) = self.process_operation(operation.clone()).await {
-                warn!("处理离线操作失败: {}", e.user_message());
-
-                // 将失败的操作重新放回队列
-                self.offline_queue.push(operation).await?;
+        // 已处理的操作从队首依次删除，之后的操作位置随之前移
+        let mut removed = 0;
+        for (index, operation) in operations.into_iter().enumerate() {
+            match self.process_operation(operation).await {
+                Ok(()) => {
+                    self.offline_queue.remove(&[index - removed]).await;
+                    removed += 1;
+                }
+                Err(e) => warn!("处理离线操作失败，保留在队列中: {}", e.user_message()),
             }
         }
 
         Ok(())
     }
 
-    /// 处理单个离线操作
+    /// 处理单个离线操作（设置了处理器时交由处理器执行）
     async fn process_operation(&self, operation: OfflineOperation) -> Result<(), ClientError> {
+        if let Some(handler) = self.offline_handler() {
+            return handler.replay(operation).await;
+        }
+
         match operation {
             OfflineOperation::FileUpload {
                 path,
@@ -363,6 +630,82 @@ mod tests {
         assert_eq!(manager.get_status().await, NetworkStatus::Online);
     }
 
+    #[tokio::test]
+    async fn test_circuit_breaker_states() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(50));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // 未达到阈值前保持关闭
+        for _ in 0..2 {
+            assert!(breaker.try_acquire().is_ok());
+            breaker.record_failure();
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // 达到阈值后打开，并快速失败
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        let err = breaker.try_acquire().unwrap_err();
+        assert!(matches!(err, ClientError::Network { .. }));
+
+        // 冷却后半开，只允许一个探测请求
+        sleep(Duration::from_millis(60)).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_ok());
+        assert!(breaker.try_acquire().is_err());
+
+        // 探测失败重新打开
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // 再次冷却后探测成功则关闭
+        sleep(Duration::from_millis(60)).await;
+        assert!(breaker.try_acquire().is_ok());
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_execute_fast_fails_when_open() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let manager = NetworkRecoveryManager::new(
+            "http://localhost:50051".to_string(),
+            "http://localhost:3000".to_string(),
+            RetryConfig::default().with_max_retries(0),
+            5,
+            3,
+        )
+        .with_circuit_breaker(2, Duration::from_secs(60));
+        manager.set_status(NetworkStatus::Online).await;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let operation = || {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(ClientError::network("服务器不可用", None))
+            }
+        };
+
+        for _ in 0..2 {
+            assert!(manager
+                .execute_with_recovery(operation, "test")
+                .await
+                .is_err());
+        }
+        assert_eq!(manager.get_circuit_state(), CircuitState::Open);
+
+        // 打开期间不再调用实际操作
+        assert!(manager
+            .execute_with_recovery(operation, "test")
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
     #[tokio::test]
     async fn test_offline_queue() {
         let manager = NetworkRecoveryManager::new(
@@ -385,4 +728,129 @@ mod tests {
             .is_ok());
         assert_eq!(manager.offline_queue.len().await, 1);
     }
+
+    #[tokio::test]
+    async fn test_offline_operations_recovered_after_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("offline_queue.ndjson");
+        let create_manager = || {
+            NetworkRecoveryManager::new(
+                "http://localhost:50051".to_string(),
+                "http://localhost:3000".to_string(),
+                RetryConfig::default(),
+                5,
+                3,
+            )
+            .with_offline_queue(OfflineQueue::persistent(10, path.clone()).unwrap())
+        };
+
+        let operations = vec![
+            OfflineOperation::FileUpload {
+                path: "agents/a.md".to_string(),
+                hash: "abc123".to_string(),
+                size: 1024,
+            },
+            OfflineOperation::FileDownload {
+                path: "settings.json".to_string(),
+                version: Some(3),
+            },
+            OfflineOperation::ReportChanges {
+                changes: vec![ChangeInfo {
+                    file_path: "skills/b.md".to_string(),
+                    file_hash: "def456".to_string(),
+                    file_size: 42,
+                }],
+            },
+        ];
+
+        let manager = create_manager();
+        for operation in &operations {
+            manager
+                .queue_offline_operation(operation.clone())
+                .await
+                .unwrap();
+        }
+        drop(manager);
+
+        let manager = create_manager();
+        assert_eq!(manager.offline_queue.items().await, operations);
+
+        // queue list 显示的类型和目标
+        let described: Vec<(&str, String)> = operations
+            .iter()
+            .map(|operation| (operation.kind(), operation.target()))
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                ("upload", "agents/a.md (1024 bytes)".to_string()),
+                ("download", "settings.json (版本 3)".to_string()),
+                ("report", "skills/b.md".to_string()),
+            ]
+        );
+    }
+
+    /// 重放时检查持久化文件，失败的操作返回错误
+    struct FailingHandler {
+        path: std::path::PathBuf,
+        replayed: Mutex<Vec<String>>,
+    }
+
+    impl OfflineOperationHandler for FailingHandler {
+        fn replay(&self, operation: OfflineOperation) -> BoxFuture<'_, Result<(), ClientError>> {
+            Box::pin(async move {
+                let OfflineOperation::FileUpload { path, .. } = operation else {
+                    return Ok(());
+                };
+                // 正在处理的操作仍保存在文件中
+                let persisted = std::fs::read_to_string(&self.path).unwrap();
+                assert!(persisted.contains(&path));
+                self.replayed.lock().unwrap().push(path.clone());
+
+                if path == "b.md" {
+                    return Err(ClientError::network("服务器不可达", None));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_offline_queue_keeps_operations_until_replayed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("offline_queue.ndjson");
+        let manager = NetworkRecoveryManager::new(
+            "http://localhost:50051".to_string(),
+            "http://localhost:3000".to_string(),
+            RetryConfig::default(),
+            5,
+            3,
+        )
+        .with_offline_queue(OfflineQueue::persistent(10, path.clone()).unwrap());
+        let upload = |path: &str| OfflineOperation::FileUpload {
+            path: path.to_string(),
+            hash: String::new(),
+            size: 0,
+        };
+        for name in ["a.md", "b.md", "c.md"] {
+            manager.queue_offline_operation(upload(name)).await.unwrap();
+        }
+
+        let handler = Arc::new(FailingHandler {
+            path: path.clone(),
+            replayed: Mutex::new(Vec::new()),
+        });
+        let weak: Arc<dyn OfflineOperationHandler> = handler.clone();
+        manager.set_offline_handler(Arc::downgrade(&weak));
+        manager.process_offline_queue().await.unwrap();
+
+        assert_eq!(
+            *handler.replayed.lock().unwrap(),
+            vec!["a.md", "b.md", "c.md"]
+        );
+        // 只有失败的操作留在队列和文件中
+        assert_eq!(manager.offline_queue.items().await, vec![upload("b.md")]);
+        let reloaded: OfflineQueue<OfflineOperation> = OfflineQueue::persistent(10, path).unwrap();
+        assert_eq!(reloaded.items().await, vec![upload("b.md")]);
+    }
 }
@@ -0,0 +1,424 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// 当前平台的文件系统默认是否大小写不敏感（macOS、Windows）
+pub fn platform_case_insensitive() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+/// 路径比较键（大小写不敏感时统一为小写）
+pub fn path_key(path: &Path, case_insensitive: bool) -> String {
+    let path = path.to_string_lossy();
+    if case_insensitive {
+        path.to_lowercase()
+    } else {
+        path.into_owned()
+    }
+}
+
+/// 路径前缀比较（按组件比较，可忽略大小写）
+pub fn starts_with(path: &Path, prefix: &Path, case_insensitive: bool) -> bool {
+    if !case_insensitive {
+        return path.starts_with(prefix);
+    }
+
+    let mut components = path.components();
+    prefix.components().all(|expected| {
+        components.next().is_some_and(|actual| {
+            actual.as_os_str().to_string_lossy().to_lowercase()
+                == expected.as_os_str().to_string_lossy().to_lowercase()
+        })
+    })
+}
+
+/// 路径的上级目录中是否有任一指定名称的目录（不比较最后一级的文件名）
+pub fn in_dir_named(path: &Path, names: &[String], case_insensitive: bool) -> bool {
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    parent.components().any(|component| {
+        let component = component.as_os_str().to_string_lossy();
+        names.iter().any(|name| {
+            if case_insensitive {
+                component.to_lowercase() == name.to_lowercase()
+            } else {
+                component == name.as_str()
+            }
+        })
+    })
+}
+
+/// 查找 root 下与服务器相对路径仅大小写不同的已有本地路径
+///
+/// 逐级读取目录项比较实际文件名，因此在大小写不敏感的文件系统上同样可用。
+/// 路径拼写完全一致或不存在时返回 None。
+pub fn find_case_variant(root: &Path, relative: &str) -> Option<PathBuf> {
+    let mut current = root.to_path_buf();
+    let mut differs = false;
+
+    for part in relative.split('/').filter(|part| !part.is_empty()) {
+        let mut exact = None;
+        let mut folded = None;
+
+        for entry in std::fs::read_dir(&current).ok()?.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == part {
+                exact = Some(name);
+                break;
+            }
+            if folded.is_none() && name.to_lowercase() == part.to_lowercase() {
+                folded = Some(name);
+            }
+        }
+
+        let name = match (exact, folded) {
+            (Some(name), _) => name,
+            (None, Some(name)) => {
+                differs = true;
+                name
+            }
+            (None, None) => return None,
+        };
+        current.push(name);
+    }
+
+    differs.then_some(current)
+}
+
+/// 找出仅大小写不同的路径组（每组按路径排序，组内至少两个路径）
+pub fn find_case_collisions<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> Vec<Vec<PathBuf>> {
+    let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        groups
+            .entry(path_key(path, true))
+            .or_default()
+            .push(path.clone());
+    }
+
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect()
+}
+
+/// 校验服务器相对路径：使用 / 分隔，不能是绝对路径，也不能包含 `.`、`..` 或空的路径段
+///
+/// 各设备上 Claude 目录的绝对路径不同（如 Windows 与 Linux），发给服务器的路径必须相对于同步根目录。
+pub fn validate_remote_path(remote_path: &str) -> Result<()> {
+    if remote_path.starts_with('/')
+        || remote_path.starts_with('\\')
+        || has_drive_prefix(remote_path)
+    {
+        anyhow::bail!("服务器路径不能是绝对路径: {:?}", remote_path);
+    }
+    if remote_path.contains('\\') {
+        anyhow::bail!("服务器路径必须使用 / 分隔: {:?}", remote_path);
+    }
+    if remote_path
+        .split('/')
+        .any(|part| part.is_empty() || part == "." || part == "..")
+    {
+        anyhow::bail!("无效的服务器路径: {:?}", remote_path);
+    }
+
+    Ok(())
+}
+
+/// 是否以 Windows 盘符开头（如 `C:`）
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// 本地路径转换为服务器相对路径（基于同步根目录，使用 / 分隔）
+///
+/// 绝对路径必须位于根目录中；相对路径按服务器路径处理，`\` 同样视为分隔符，
+/// 因此 Windows 设备记录的路径在其他平台上也能正确转换。
+pub fn relativize(root: &Path, path: &Path) -> Result<String> {
+    let relative = if path.is_absolute() {
+        path.strip_prefix(root)
+            .map_err(|_| anyhow::anyhow!("路径不在 Claude 目录中: {:?}", path))?
+    } else {
+        path
+    };
+
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.extend(
+                part.to_string_lossy()
+                    .split('\\')
+                    .filter(|part| *part != ".")
+                    .map(str::to_string),
+            ),
+            Component::CurDir => {}
+            _ => anyhow::bail!("无效的文件路径: {:?}", path),
+        }
+    }
+
+    let remote_path = parts.join("/");
+    validate_remote_path(&remote_path)?;
+    Ok(remote_path)
+}
+
+/// 服务器相对路径对应的本地路径（兼容使用 `\` 分隔的路径）
+pub fn absolutize(root: &Path, remote_path: &str) -> Result<PathBuf> {
+    let normalized = remote_path.replace('\\', "/");
+    validate_remote_path(&normalized)?;
+
+    Ok(normalized
+        .split('/')
+        .fold(root.to_path_buf(), |path, part| path.join(part)))
+}
+
+/// 附加同步目录在服务器路径中的标识前缀
+pub const ROOT_ID_PREFIX: char = '@';
+
+/// 同步根目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncRoot {
+    /// 根目录标识：主 Claude 目录为空，附加目录为 `@目录名`（各设备上保持一致）
+    pub id: String,
+    /// 本地路径
+    pub path: PathBuf,
+}
+
+/// 所有同步根目录（主 Claude 目录和附加监控目录）
+///
+/// 主目录中的文件在服务器上的路径保持不变，附加目录中的文件以 `@目录名/` 开头。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncRoots {
+    roots: Vec<SyncRoot>,
+}
+
+impl SyncRoots {
+    /// 创建同步根目录集合
+    pub fn new(claude_dir: PathBuf, additional_dirs: &[PathBuf]) -> Self {
+        let mut roots = vec![SyncRoot {
+            id: String::new(),
+            path: claude_dir,
+        }];
+        roots.extend(additional_dirs.iter().map(|dir| SyncRoot {
+            id: Self::root_id(dir),
+            path: dir.clone(),
+        }));
+        Self { roots }
+    }
+
+    /// 附加目录的标识（取目录名）
+    pub fn root_id(dir: &Path) -> String {
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        format!("{}{}", ROOT_ID_PREFIX, name)
+    }
+
+    /// 主 Claude 目录
+    pub fn main(&self) -> &SyncRoot {
+        &self.roots[0]
+    }
+
+    /// 附加目录（不含主目录）
+    pub fn additional(&self) -> &[SyncRoot] {
+        &self.roots[1..]
+    }
+
+    /// 所有根目录（主目录在前）
+    pub fn iter(&self) -> impl Iterator<Item = &SyncRoot> {
+        self.roots.iter()
+    }
+
+    /// 包含该绝对路径的根目录（嵌套时取最深的一个）
+    pub fn root_for(&self, path: &Path) -> Option<&SyncRoot> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(&root.path))
+            .max_by_key(|root| root.path.components().count())
+    }
+
+    /// 路径相对于所在根目录的部分（不在任何根目录中时原样返回）
+    pub fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        self.root_for(path)
+            .and_then(|root| path.strip_prefix(&root.path).ok())
+            .unwrap_or(path)
+    }
+
+    /// 本地路径对应的服务器相对路径（相对路径按服务器路径处理）
+    pub fn remote_path(&self, path: &Path) -> Result<String> {
+        if !path.is_absolute() {
+            return relativize(&self.main().path, path);
+        }
+
+        let root = self
+            .root_for(path)
+            .ok_or_else(|| anyhow::anyhow!("路径不在同步目录中: {:?}", path))?;
+        let relative = relativize(&root.path, path)?;
+        Ok(if root.id.is_empty() {
+            relative
+        } else {
+            format!("{}/{}", root.id, relative)
+        })
+    }
+
+    /// 服务器相对路径所属的根目录及根目录内的相对路径
+    pub fn split_remote<'a>(&self, remote_path: &'a str) -> (&SyncRoot, &'a str) {
+        if let Some((id, rest)) = remote_path.split_once('/') {
+            if let Some(root) = self.additional().iter().find(|root| root.id == id) {
+                return (root, rest);
+            }
+        }
+        (self.main(), remote_path)
+    }
+
+    /// 服务器相对路径对应的本地路径
+    pub fn local_path(&self, remote_path: &str) -> Result<PathBuf> {
+        let (root, relative) = self.split_remote(remote_path);
+        absolutize(&root.path, relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_with_case_insensitive() {
+        let path = Path::new("/home/u/.claude/Cache/a.json");
+        let prefix = Path::new("/home/u/.claude/cache");
+
+        assert!(!starts_with(path, prefix, false));
+        assert!(starts_with(path, prefix, true));
+        assert!(!starts_with(
+            Path::new("/home/u/.claude/cached/a"),
+            prefix,
+            true
+        ));
+    }
+
+    #[test]
+    fn test_find_case_variant() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("agents")).unwrap();
+        std::fs::write(root.join("agents").join("Helper.md"), "x").unwrap();
+
+        assert_eq!(
+            find_case_variant(root, "Agents/helper.md"),
+            Some(root.join("agents").join("Helper.md"))
+        );
+        assert_eq!(find_case_variant(root, "agents/Helper.md"), None);
+        assert_eq!(find_case_variant(root, "agents/other.md"), None);
+    }
+
+    #[test]
+    fn test_find_case_collisions() {
+        let paths = vec![
+            PathBuf::from("agents/a.md"),
+            PathBuf::from("Agents/a.md"),
+            PathBuf::from("agents/b.md"),
+        ];
+
+        assert_eq!(
+            find_case_collisions(&paths),
+            vec![vec![
+                PathBuf::from("Agents/a.md"),
+                PathBuf::from("agents/a.md")
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_sync_roots_map_paths() {
+        let roots = SyncRoots::new(
+            PathBuf::from("/home/u/.claude"),
+            &[PathBuf::from("/work/team-claude")],
+        );
+
+        assert_eq!(
+            roots
+                .remote_path(Path::new("/home/u/.claude/agents/a.md"))
+                .unwrap(),
+            "agents/a.md"
+        );
+        assert_eq!(
+            roots
+                .remote_path(Path::new("/work/team-claude/agents/a.md"))
+                .unwrap(),
+            "@team-claude/agents/a.md"
+        );
+        assert!(roots.remote_path(Path::new("/etc/passwd")).is_err());
+
+        assert_eq!(
+            roots.local_path("@team-claude/agents/a.md").unwrap(),
+            PathBuf::from("/work/team-claude/agents/a.md")
+        );
+        assert_eq!(
+            roots.local_path("agents/a.md").unwrap(),
+            PathBuf::from("/home/u/.claude/agents/a.md")
+        );
+        // 未配置的附加目录按主目录中的普通路径处理
+        assert_eq!(
+            roots.local_path("@other/a.md").unwrap(),
+            PathBuf::from("/home/u/.claude/@other/a.md")
+        );
+        assert_eq!(
+            roots.relative_path(Path::new("/work/team-claude/agents/a.md")),
+            Path::new("agents/a.md")
+        );
+    }
+
+    #[test]
+    fn test_relativize_absolutize_round_trip() {
+        let root = Path::new("/home/u/.claude");
+
+        // Windows 设备记录的相对路径与 Unix 路径转换为同一个服务器路径
+        for local in ["agents\\sub\\a.md", "agents/sub/a.md"] {
+            let remote = relativize(root, Path::new(local)).unwrap();
+            assert_eq!(remote, "agents/sub/a.md");
+            assert_eq!(
+                absolutize(root, &remote).unwrap(),
+                root.join("agents").join("sub").join("a.md")
+            );
+            assert_eq!(
+                absolutize(root, local).unwrap(),
+                root.join("agents").join("sub").join("a.md")
+            );
+        }
+
+        assert_eq!(
+            relativize(root, &root.join("settings.json")).unwrap(),
+            "settings.json"
+        );
+        assert!(relativize(root, Path::new("/etc/passwd")).is_err());
+        assert!(relativize(root, Path::new("../outside.md")).is_err());
+        assert!(relativize(root, Path::new("agents\\..\\..\\outside.md")).is_err());
+        assert!(relativize(root, Path::new("C:\\Users\\u\\.claude\\a.md")).is_err());
+        assert!(absolutize(root, "../outside.md").is_err());
+        assert!(absolutize(root, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_validate_remote_path_rejects_absolute() {
+        assert!(validate_remote_path("agents/a.md").is_ok());
+        assert!(validate_remote_path("@team-claude/agents/a.md").is_ok());
+
+        for path in [
+            "/home/u/.claude/a.md",
+            "C:/Users/u/.claude/a.md",
+            "C:\\Users\\u\\.claude\\a.md",
+            "\\\\server\\share\\a.md",
+            "agents\\a.md",
+            "agents//a.md",
+            "./a.md",
+            "",
+        ] {
+            assert!(validate_remote_path(path).is_err(), "{}", path);
+        }
+    }
+}
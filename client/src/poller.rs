@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{interval_at, Instant, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+use crate::sync::SyncEngine;
+
+/// 定时同步任务（轮询模式）
+///
+/// 按 sync_interval 定期执行一次全量同步，用于网络挂载等文件系统事件不可靠的目录。
+/// 全量同步只上传哈希变化的文件，未变化的文件由哈希缓存跳过读取。
+/// 同步暂停期间跳过，上一次同步耗时超过间隔时顺延而不补发。
+pub struct SyncPoller {
+    sync_engine: Arc<SyncEngine>,
+    interval: Duration,
+    runs: AtomicUsize,
+}
+
+impl SyncPoller {
+    /// 创建定时同步任务
+    pub fn new(sync_engine: Arc<SyncEngine>, interval: Duration) -> Self {
+        Self {
+            sync_engine,
+            interval,
+            runs: AtomicUsize::new(0),
+        }
+    }
+
+    /// 已执行的同步次数
+    pub fn runs(&self) -> usize {
+        self.runs.load(Ordering::SeqCst)
+    }
+
+    /// 持续定时同步，直到同步引擎关闭
+    pub async fn run(&self) {
+        info!("启动定时同步，间隔 {} 秒", self.interval.as_secs_f64());
+
+        // 启动时已经同步过一次，第一次定时同步在一个间隔之后
+        let mut ticker = interval_at(Instant::now() + self.interval, self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if self.sync_engine.is_closed() {
+                return;
+            }
+            if self.sync_engine.control().is_paused() {
+                debug!("同步已暂停，跳过本次定时同步");
+                continue;
+            }
+
+            // 轮询模式不接收文件事件，按修改时间检测忽略文件的变化
+            if let Err(e) = self.sync_engine.reload_rules_if_ignore_file_changed() {
+                warn!("重新加载忽略文件失败，继续使用旧规则: {:#}", e);
+            }
+
+            match self.sync_engine.run_full_sync().await {
+                Ok(summary) => debug!(
+                    "定时同步完成: {} 个已同步, {} 个跳过, {} 个失败",
+                    summary.synced_count, summary.skipped_count, summary.failed_count
+                ),
+                Err(e) => warn!("定时同步失败: {:#}", e),
+            }
+            self.runs.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// 在后台运行定时同步任务
+pub fn spawn_poll_task(poller: Arc<SyncPoller>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move { poller.run().await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::IGNORE_FILE_NAME;
+    use crate::sync::test_support::create_engine;
+
+    #[tokio::test]
+    async fn test_poller_syncs_on_interval() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let file = claude_dir.join("CLAUDE.md");
+        std::fs::write(&file, "# notes").unwrap();
+
+        let engine = Arc::new(create_engine(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+        ));
+        let poller = SyncPoller::new(engine.clone(), Duration::from_millis(200));
+
+        // 700 毫秒内在 200、400、600 毫秒各同步一次
+        let _ = tokio::time::timeout(Duration::from_millis(700), poller.run()).await;
+        assert_eq!(poller.runs(), 3);
+        assert!(engine.get_sync_state(&file).await.is_some());
+
+        // 暂停期间不同步
+        engine.control().pause();
+        let _ = tokio::time::timeout(Duration::from_millis(300), poller.run()).await;
+        assert_eq!(poller.runs(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_poller_reloads_changed_ignore_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("scratch")).unwrap();
+        let scratch = claude_dir.join("scratch").join("x.md");
+        std::fs::write(&scratch, "scratch").unwrap();
+        let ignore_file = claude_dir.join(IGNORE_FILE_NAME);
+        std::fs::write(&ignore_file, "scratch/\n").unwrap();
+
+        let engine = Arc::new(create_engine(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+        ));
+        engine.reload_rules().unwrap();
+        let poller = SyncPoller::new(engine.clone(), Duration::from_millis(100));
+
+        let _ = tokio::time::timeout(Duration::from_millis(150), poller.run()).await;
+        assert_eq!(poller.runs(), 1);
+        assert!(engine.get_sync_state(&scratch).await.is_none());
+
+        // 修改忽略文件后，下一次定时同步前重新加载规则
+        std::fs::write(&ignore_file, "# 不再忽略\n").unwrap();
+        let modified = std::time::SystemTime::now() + Duration::from_secs(1);
+        std::fs::File::options()
+            .write(true)
+            .open(&ignore_file)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        let _ = tokio::time::timeout(Duration::from_millis(150), poller.run()).await;
+        assert_eq!(poller.runs(), 2);
+        assert!(engine.get_sync_state(&scratch).await.is_some());
+    }
+}
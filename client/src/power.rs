@@ -0,0 +1,371 @@
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::control::SyncControl;
+
+/// 电源状态
+#[cfg_attr(not(feature = "power-management"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    /// 外接电源
+    Ac,
+    /// 电池供电
+    Battery,
+    /// 无法判断（台式机、平台不支持或未启用 power-management 特性）
+    Unknown,
+}
+
+/// 网络计费状态
+#[cfg_attr(not(feature = "power-management"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkCost {
+    /// 不限流量
+    Unmetered,
+    /// 按流量计费
+    Metered,
+    /// 无法判断
+    Unknown,
+}
+
+/// 设备的电源与网络状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceState {
+    pub power: PowerSource,
+    pub network: NetworkCost,
+}
+
+/// 自动暂停策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PausePolicy {
+    /// 电池供电时暂停
+    pub pause_on_battery: bool,
+    /// 按流量计费的网络上暂停
+    pub pause_on_metered: bool,
+}
+
+impl PausePolicy {
+    /// 是否启用了任何自动暂停条件
+    pub fn is_enabled(&self) -> bool {
+        self.pause_on_battery || self.pause_on_metered
+    }
+
+    /// 按策略判断当前状态下是否应暂停同步（状态未知时不暂停），返回暂停原因
+    pub fn pause_reason(&self, state: &DeviceState) -> Option<&'static str> {
+        if self.pause_on_battery && state.power == PowerSource::Battery {
+            Some("电池供电")
+        } else if self.pause_on_metered && state.network == NetworkCost::Metered {
+            Some("按流量计费的网络")
+        } else {
+            None
+        }
+    }
+}
+
+/// 当前构建是否能查询平台的电源与网络状态
+pub const PLATFORM_SUPPORTED: bool = cfg!(all(
+    feature = "power-management",
+    any(target_os = "linux", target_os = "macos")
+));
+
+/// 电源与网络状态来源（默认查询平台接口，测试中可替换）
+pub trait DeviceStateSource: Send + Sync {
+    fn current_state(&self) -> DeviceState;
+}
+
+/// 查询当前平台的电源与网络状态
+///
+/// 未启用 `power-management` 特性或平台不支持时始终返回未知状态，自动暂停不生效。
+pub struct PlatformStateSource;
+
+impl DeviceStateSource for PlatformStateSource {
+    fn current_state(&self) -> DeviceState {
+        DeviceState {
+            power: platform::power_source(),
+            network: platform::network_cost(),
+        }
+    }
+}
+
+/// 按电源与网络状态自动暂停/恢复同步
+///
+/// 只在判断结果变化时操作暂停开关：进入电池或计费网络时暂停，恢复外接电源或不限流量网络时
+/// 恢复由本监控暂停的同步。用户手动暂停或恢复后，直到状态再次变化前不会被覆盖。
+pub struct PowerMonitor {
+    control: SyncControl,
+    policy: PausePolicy,
+    /// 上次判断的暂停原因
+    last_reason: Option<&'static str>,
+    /// 当前的暂停是否由本监控发起
+    paused_by_policy: bool,
+}
+
+impl PowerMonitor {
+    /// 创建监控
+    pub fn new(control: SyncControl, policy: PausePolicy) -> Self {
+        Self {
+            control,
+            policy,
+            last_reason: None,
+            paused_by_policy: false,
+        }
+    }
+
+    /// 根据最新状态更新暂停开关
+    pub fn update(&mut self, state: &DeviceState) {
+        let reason = self.policy.pause_reason(state);
+        if reason == self.last_reason {
+            return;
+        }
+        debug!("设备状态变化: {:?}", state);
+
+        match reason {
+            Some(reason) => {
+                if !self.control.is_paused() {
+                    info!("检测到{}，暂停同步", reason);
+                    self.control.pause();
+                    self.paused_by_policy = true;
+                }
+            }
+            None => {
+                if self.paused_by_policy && self.control.is_paused() {
+                    info!("已恢复外接电源或不限流量网络，恢复同步");
+                    self.control.resume();
+                }
+                self.paused_by_policy = false;
+            }
+        }
+        self.last_reason = reason;
+    }
+}
+
+/// 在后台按间隔检查电源与网络状态
+pub fn spawn_power_monitor_task<S>(
+    mut monitor: PowerMonitor,
+    source: S,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()>
+where
+    S: DeviceStateSource + 'static,
+{
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            monitor.update(&source.current_state());
+        }
+    })
+}
+
+#[cfg(all(feature = "power-management", target_os = "linux"))]
+mod platform {
+    use super::{NetworkCost, PowerSource};
+    use std::path::Path;
+
+    /// sysfs 中的电源设备目录
+    const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+    pub fn power_source() -> PowerSource {
+        power_source_from(Path::new(POWER_SUPPLY_DIR))
+    }
+
+    /// 外接电源在线即为 AC；有外接电源设备但离线，或电池正在放电时为电池供电
+    pub(super) fn power_source_from(dir: &Path) -> PowerSource {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return PowerSource::Unknown;
+        };
+
+        let read = |path: &Path, name: &str| {
+            std::fs::read_to_string(path.join(name))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut on_battery = false;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match read(&path, "type").as_str() {
+                "Mains" if read(&path, "online") == "1" => return PowerSource::Ac,
+                "Mains" => on_battery = true,
+                "Battery" if read(&path, "status") == "Discharging" => on_battery = true,
+                _ => {}
+            }
+        }
+
+        if on_battery {
+            PowerSource::Battery
+        } else {
+            PowerSource::Unknown
+        }
+    }
+
+    /// 通过 NetworkManager 的 Metered 属性判断（1/3 为计费，2/4 为不计费）
+    pub fn network_cost() -> NetworkCost {
+        let output = std::process::Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.NetworkManager",
+                "/org/freedesktop/NetworkManager",
+                "org.freedesktop.NetworkManager",
+                "Metered",
+            ])
+            .output();
+
+        match output {
+            Ok(output) if output.status.success() => {
+                match String::from_utf8_lossy(&output.stdout).trim() {
+                    "u 1" | "u 3" => NetworkCost::Metered,
+                    "u 2" | "u 4" => NetworkCost::Unmetered,
+                    _ => NetworkCost::Unknown,
+                }
+            }
+            _ => NetworkCost::Unknown,
+        }
+    }
+}
+
+#[cfg(all(feature = "power-management", target_os = "macos"))]
+mod platform {
+    use super::{NetworkCost, PowerSource};
+
+    pub fn power_source() -> PowerSource {
+        match std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+        {
+            Ok(output) if output.status.success() => {
+                let output = String::from_utf8_lossy(&output.stdout);
+                if output.contains("'AC Power'") {
+                    PowerSource::Ac
+                } else if output.contains("'Battery Power'") {
+                    PowerSource::Battery
+                } else {
+                    PowerSource::Unknown
+                }
+            }
+            _ => PowerSource::Unknown,
+        }
+    }
+
+    pub fn network_cost() -> NetworkCost {
+        NetworkCost::Unknown
+    }
+}
+
+#[cfg(not(all(
+    feature = "power-management",
+    any(target_os = "linux", target_os = "macos")
+)))]
+mod platform {
+    use super::{NetworkCost, PowerSource};
+
+    pub fn power_source() -> PowerSource {
+        PowerSource::Unknown
+    }
+
+    pub fn network_cost() -> NetworkCost {
+        NetworkCost::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(power: PowerSource, network: NetworkCost) -> DeviceState {
+        DeviceState { power, network }
+    }
+
+    #[test]
+    fn test_pause_decision() {
+        let battery_only = PausePolicy {
+            pause_on_battery: true,
+            pause_on_metered: false,
+        };
+        let both = PausePolicy {
+            pause_on_battery: true,
+            pause_on_metered: true,
+        };
+
+        let ac = state(PowerSource::Ac, NetworkCost::Unmetered);
+        let on_battery = state(PowerSource::Battery, NetworkCost::Unmetered);
+        let metered = state(PowerSource::Ac, NetworkCost::Metered);
+        let unknown = state(PowerSource::Unknown, NetworkCost::Unknown);
+
+        assert_eq!(battery_only.pause_reason(&ac), None);
+        assert_eq!(battery_only.pause_reason(&on_battery), Some("电池供电"));
+        assert_eq!(battery_only.pause_reason(&metered), None);
+        assert_eq!(both.pause_reason(&metered), Some("按流量计费的网络"));
+
+        // 状态未知（平台不支持）时不暂停
+        assert_eq!(both.pause_reason(&unknown), None);
+        // 未启用策略时从不暂停
+        assert!(!PausePolicy::default().is_enabled());
+        assert_eq!(PausePolicy::default().pause_reason(&on_battery), None);
+    }
+
+    #[test]
+    fn test_monitor_pauses_and_resumes_on_transitions() {
+        let control = SyncControl::new();
+        let mut monitor = PowerMonitor::new(
+            control.clone(),
+            PausePolicy {
+                pause_on_battery: true,
+                pause_on_metered: true,
+            },
+        );
+
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Unmetered));
+        assert!(!control.is_paused());
+
+        // 拔掉电源后暂停，接回后恢复
+        monitor.update(&state(PowerSource::Battery, NetworkCost::Unmetered));
+        assert!(control.is_paused());
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Unmetered));
+        assert!(!control.is_paused());
+
+        // 用户在计费网络上手动恢复后，状态不变时不再暂停
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Metered));
+        assert!(control.is_paused());
+        control.resume();
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Metered));
+        assert!(!control.is_paused());
+
+        // 用户手动暂停的同步不会被自动恢复
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Unmetered));
+        control.pause();
+        monitor.update(&state(PowerSource::Battery, NetworkCost::Unmetered));
+        monitor.update(&state(PowerSource::Ac, NetworkCost::Unmetered));
+        assert!(control.is_paused());
+    }
+
+    #[cfg(all(feature = "power-management", target_os = "linux"))]
+    #[test]
+    fn test_linux_power_supply_detection() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let dir = temp_dir.path().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, value) in files {
+                std::fs::write(dir.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+
+        assert_eq!(
+            platform::power_source_from(temp_dir.path()),
+            PowerSource::Unknown
+        );
+
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(
+            platform::power_source_from(temp_dir.path()),
+            PowerSource::Battery
+        );
+
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert_eq!(
+            platform::power_source_from(temp_dir.path()),
+            PowerSource::Ac
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use crate::content_hash::HashAlgorithm;
+use crate::grpc_client::{FileChange, GrpcClient, ReportChangesResponse};
+use crate::paths::SyncRoots;
+use crate::watcher::{FileEvent, FileEventType};
+
+/// 变更上报目标（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+pub trait ChangeReporter: Send + Sync {
+    /// 一次上报一批文件变更
+    fn report(
+        &self,
+        changes: Vec<FileChange>,
+    ) -> impl Future<Output = Result<ReportChangesResponse>> + Send;
+}
+
+impl ChangeReporter for GrpcClient {
+    fn report(
+        &self,
+        changes: Vec<FileChange>,
+    ) -> impl Future<Output = Result<ReportChangesResponse>> + Send {
+        self.report_changes(changes)
+    }
+}
+
+impl<R: ChangeReporter> ChangeReporter for Arc<R> {
+    fn report(
+        &self,
+        changes: Vec<FileChange>,
+    ) -> impl Future<Output = Result<ReportChangesResponse>> + Send {
+        (**self).report(changes)
+    }
+}
+
+/// 批量变更上报器
+///
+/// 收到第一个事件后等待一个批处理窗口，窗口内的事件按路径合并（保留最后一次），
+/// 然后通过一次 ReportChanges 请求发送。
+pub struct BatchReporter<R> {
+    reporter: R,
+    roots: SyncRoots,
+    batch_window: Duration,
+    hash_algorithm: HashAlgorithm,
+}
+
+impl<R: ChangeReporter> BatchReporter<R> {
+    /// 创建批量上报器
+    pub fn new(reporter: R, roots: SyncRoots, batch_window: Duration) -> Self {
+        Self {
+            reporter,
+            roots,
+            batch_window,
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    /// 设置上报哈希使用的算法（与同步引擎的配置一致）
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// 持续消费文件事件直到通道关闭，返回成功发送的上报次数
+    pub async fn run(&self, mut event_rx: mpsc::UnboundedReceiver<FileEvent>) -> usize {
+        let mut reports = 0;
+
+        while let Some(first) = event_rx.recv().await {
+            let mut pending = BTreeMap::new();
+            pending.insert(first.path.clone(), first);
+
+            let window = tokio::time::sleep(self.batch_window);
+            tokio::pin!(window);
+            let mut closed = false;
+
+            loop {
+                tokio::select! {
+                    _ = &mut window => break,
+                    event = event_rx.recv() => match event {
+                        Some(event) => {
+                            pending.insert(event.path.clone(), event);
+                        }
+                        None => {
+                            closed = true;
+                            break;
+                        }
+                    },
+                }
+            }
+
+            match self.flush(pending.into_values().collect()).await {
+                Ok(true) => reports += 1,
+                Ok(false) => {}
+                // 上报失败不影响后续事件，文件内容仍由同步引擎上传
+                Err(e) => warn!("批量上报变更失败: {:#}", e),
+            }
+
+            if closed {
+                break;
+            }
+        }
+
+        reports
+    }
+
+    /// 发送一批事件，没有可上报的变更时返回 false
+    async fn flush(&self, events: Vec<FileEvent>) -> Result<bool> {
+        let mut changes = Vec::with_capacity(events.len());
+        for event in &events {
+            if let Some(change) = self.to_file_change(event).await? {
+                changes.push(change);
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(false);
+        }
+
+        let count = changes.len();
+        let response = self.reporter.report(changes).await?;
+        if !response.success {
+            anyhow::bail!("服务器拒绝变更上报: {}", response.message);
+        }
+
+        info!("已批量上报 {} 个文件变更", count);
+        if !response.conflicts_detected.is_empty() {
+            warn!("服务器检测到冲突: {:?}", response.conflicts_detected);
+        }
+
+        Ok(true)
+    }
+
+    /// 将文件事件转换为上报的变更（不在同步目录中的路径返回 None）
+    async fn to_file_change(&self, event: &FileEvent) -> Result<Option<FileChange>> {
+        let file_path = match self.roots.remote_path(&event.path) {
+            Ok(path) => path,
+            Err(e) => {
+                debug!("跳过无法上报的路径 {:?}: {}", event.path, e);
+                return Ok(None);
+            }
+        };
+
+        let mut change = FileChange {
+            file_path,
+            file_hash: String::new(),
+            file_size: 0,
+            modified_at: event.timestamp.timestamp(),
+            version: 0,
+            is_deleted: true,
+        };
+
+        // 事件合并后文件可能已被删除，以当前磁盘状态为准
+        if event.event_type != FileEventType::Remove && event.path.is_file() {
+            let content = read_file(&event.path).await?;
+            change.file_hash = self.hash_algorithm.hash(&content);
+            change.file_size = content.len() as u64;
+            change.is_deleted = false;
+        }
+
+        Ok(Some(change))
+    }
+}
+
+/// 读取文件内容
+async fn read_file(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .with_context(|| format!("无法读取文件: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockReporter {
+        batches: Mutex<Vec<Vec<FileChange>>>,
+    }
+
+    impl ChangeReporter for &MockReporter {
+        async fn report(&self, changes: Vec<FileChange>) -> Result<ReportChangesResponse> {
+            self.batches.lock().unwrap().push(changes);
+            Ok(ReportChangesResponse {
+                success: true,
+                message: String::new(),
+                conflicts_detected: vec![],
+                pending_uploads: vec![],
+            })
+        }
+    }
+
+    fn event(path: PathBuf, event_type: FileEventType) -> FileEvent {
+        FileEvent {
+            path,
+            event_type,
+            timestamp: Utc::now(),
+            is_dir: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_edits_produce_one_batched_report() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().to_path_buf();
+        let mock = MockReporter::default();
+        let reporter = BatchReporter::new(
+            &mock,
+            SyncRoots::new(claude_dir.clone(), &[]),
+            Duration::from_millis(200),
+        )
+        .with_hash_algorithm(HashAlgorithm::Blake3);
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        for i in 0..50 {
+            let path = claude_dir.join(format!("note-{}.md", i % 10));
+            std::fs::write(&path, format!("edit {}", i)).unwrap();
+            event_tx.send(event(path, FileEventType::Modify)).unwrap();
+        }
+        let removed = claude_dir.join("removed.md");
+        event_tx
+            .send(event(removed, FileEventType::Remove))
+            .unwrap();
+
+        let (reports, _) = tokio::join!(reporter.run(event_rx), async {
+            // 窗口结束后才关闭通道，确保是按窗口而不是按关闭触发上报
+            tokio::time::sleep(Duration::from_millis(400)).await;
+            assert_eq!(mock.batches.lock().unwrap().len(), 1);
+            drop(event_tx);
+        });
+
+        assert_eq!(reports, 1);
+        let batches = mock.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+
+        let batch = &batches[0];
+        assert_eq!(batch.len(), 11);
+        let note = batch.iter().find(|c| c.file_path == "note-9.md").unwrap();
+        assert_eq!(note.file_hash, HashAlgorithm::Blake3.hash(b"edit 49"));
+        assert!(!note.is_deleted);
+        let removed = batch.iter().find(|c| c.file_path == "removed.md").unwrap();
+        assert!(removed.is_deleted);
+    }
+}
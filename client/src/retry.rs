@@ -1,6 +1,14 @@
-use crate::error::ClientError;
-use anyhow::Result;
+use crate::error::{ClientError, DEFAULT_RETRYABLE_CODES};
+use anyhow::{Context, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 use tokio::time::sleep;
 use tracing::{debug, info, warn};
 
@@ -21,6 +29,9 @@ pub struct RetryConfig {
 
     /// 随机化因子（0.0 - 1.0）
     pub jitter_factor: f64,
+
+    /// 可重试的 gRPC 状态码（网络错误和超时始终重试）
+    pub retryable_codes: HashSet<tonic::Code>,
 }
 
 impl Default for RetryConfig {
@@ -31,6 +42,7 @@ impl Default for RetryConfig {
             max_delay_ms: 30000,
             multiplier: 2.0,
             jitter_factor: 0.1,
+            retryable_codes: HashSet::from(DEFAULT_RETRYABLE_CODES),
         }
     }
 }
@@ -59,15 +71,35 @@ impl RetryConfig {
         self
     }
 
+    /// 设置可重试的 gRPC 状态码
+    pub fn with_retryable_codes(mut self, codes: impl IntoIterator<Item = tonic::Code>) -> Self {
+        self.retryable_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// 按配置判断错误是否可重试
+    pub fn is_retryable(&self, err: &ClientError) -> bool {
+        err.is_retryable_with(|code| self.retryable_codes.contains(&code))
+    }
+
     /// 计算重试延迟（指数退避 + 随机抖动）
     pub fn calculate_delay(&self, attempt: usize) -> Duration {
+        self.calculate_delay_with_rng(attempt, &mut rand::thread_rng())
+    }
+
+    /// 使用指定的随机数生成器计算重试延迟，结果落在 [base, base * (1 + jitter_factor)]
+    pub fn calculate_delay_with_rng<R: Rng + ?Sized>(
+        &self,
+        attempt: usize,
+        rng: &mut R,
+    ) -> Duration {
         // 指数退避
         let delay_ms = (self.initial_delay_ms as f64 * self.multiplier.powi(attempt as i32))
             .min(self.max_delay_ms as f64) as u64;
 
         // 添加随机抖动
-        let jitter = (delay_ms as f64 * self.jitter_factor * (rand::random::<f64>() - 0.5) * 2.0)
-            .abs() as i64;
+        let jitter =
+            (delay_ms as f64 * self.jitter_factor * (rng.gen::<f64>() - 0.5) * 2.0).abs() as i64;
 
         let final_delay_ms = (delay_ms as i64 + jitter).max(0) as u64;
 
@@ -108,6 +140,8 @@ pub enum RetryResult<T> {
 pub struct RetryExecutor {
     config: RetryConfig,
     strategy: RetryStrategy,
+    /// 抖动使用的随机数生成器（为空时使用线程随机数生成器）
+    rng: Option<Mutex<StdRng>>,
 }
 
 impl RetryExecutor {
@@ -116,6 +150,28 @@ impl RetryExecutor {
         Self {
             config,
             strategy: RetryStrategy::ExponentialBackoff,
+            rng: None,
+        }
+    }
+
+    /// 使用固定种子生成抖动，使退避延迟可复现
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// 计算第 attempt 次重试前的延迟
+    pub fn next_delay(&self, attempt: usize) -> Duration {
+        match self.strategy {
+            RetryStrategy::ExponentialBackoff | RetryStrategy::Custom => match &self.rng {
+                Some(rng) => {
+                    let mut rng = rng.lock().unwrap_or_else(|e| e.into_inner());
+                    self.config.calculate_delay_with_rng(attempt, &mut *rng)
+                }
+                None => self.config.calculate_delay(attempt),
+            },
+            RetryStrategy::FixedDelay => Duration::from_millis(self.config.initial_delay_ms),
+            RetryStrategy::Immediate => Duration::from_millis(0),
         }
     }
 
@@ -149,7 +205,7 @@ impl RetryExecutor {
                     last_error = Some(err.clone());
 
                     // 检查是否可重试
-                    if !err.is_retryable() {
+                    if !self.config.is_retryable(&err) {
                         debug!("操作 '{}' 不可重试: {}", operation_name, err);
                         return Err(err);
                     }
@@ -165,14 +221,7 @@ impl RetryExecutor {
                     }
 
                     // 计算延迟
-                    let delay = match self.strategy {
-                        RetryStrategy::ExponentialBackoff => self.config.calculate_delay(attempt),
-                        RetryStrategy::FixedDelay => {
-                            Duration::from_millis(self.config.initial_delay_ms)
-                        }
-                        RetryStrategy::Immediate => Duration::from_millis(0),
-                        RetryStrategy::Custom => self.config.calculate_delay(attempt),
-                    };
+                    let delay = self.next_delay(attempt);
 
                     warn!(
                         "操作 '{}' 失败 (尝试 {}/{}): {}. {} 毫秒后重试...",
@@ -213,18 +262,65 @@ impl RetryExecutor {
 }
 
 /// 离线队列（用于网络恢复时处理）
+///
+/// 设置了持久化文件时，每个项目以一行 JSON 追加写入（NDJSON），
+/// 删除或清空时原子重写文件，客户端重启后可以恢复未处理的项目。
+/// 处理项目时先读取副本，处理成功后再删除，中途退出不会丢失未处理的项目。
 pub struct OfflineQueue<T> {
     queue: std::sync::Arc<tokio::sync::Mutex<Vec<T>>>,
     max_size: usize,
+    persist_path: Option<PathBuf>,
 }
 
-impl<T> OfflineQueue<T> {
+impl<T: Serialize + DeserializeOwned> OfflineQueue<T> {
     /// 创建新的离线队列
     pub fn new(max_size: usize) -> Self {
         Self {
             queue: std::sync::Arc::new(tokio::sync::Mutex::new(Vec::new())),
             max_size,
+            persist_path: None,
+        }
+    }
+
+    /// 创建持久化到文件的离线队列，并加载文件中已有的项目
+    ///
+    /// 无法解析的行会被跳过；超出 max_size 的项目被丢弃，文件随之重写。
+    pub fn persistent(max_size: usize, path: PathBuf) -> Result<Self> {
+        let mut items = Vec::new();
+        let mut dropped = 0;
+
+        if path.exists() {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("无法读取离线队列文件: {:?}", path))?;
+
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(line) {
+                    Ok(item) if items.len() < max_size => items.push(item),
+                    Ok(_) => dropped += 1,
+                    Err(e) => {
+                        warn!("跳过无法解析的离线队列项 {:?}:{}: {}", path, index + 1, e);
+                        dropped += 1;
+                    }
+                }
+            }
         }
+
+        if dropped > 0 {
+            warn!("离线队列文件中有 {} 项被丢弃", dropped);
+            crate::transfer::write_atomic_sync(&path, Self::to_ndjson(&items)?)?;
+        }
+        if !items.is_empty() {
+            info!("已从 {:?} 恢复 {} 个离线操作", path, items.len());
+        }
+
+        Ok(Self {
+            queue: std::sync::Arc::new(tokio::sync::Mutex::new(items)),
+            max_size,
+            persist_path: Some(path),
+        })
     }
 
     /// 添加项目到队列
@@ -238,16 +334,16 @@ impl<T> OfflineQueue<T> {
             ));
         }
 
+        if let Some(path) = &self.persist_path {
+            Self::append(path, &item)
+                .await
+                .map_err(|e| ClientError::internal(format!("无法持久化离线操作: {:#}", e), None))?;
+        }
+
         queue.push(item);
         Ok(())
     }
 
-    /// 从队列中取出所有项目
-    pub async fn drain(&self) -> Vec<T> {
-        let mut queue = self.queue.lock().await;
-        std::mem::take(&mut *queue)
-    }
-
     /// 获取队列大小
     pub async fn len(&self) -> usize {
         self.queue.lock().await.len()
@@ -258,9 +354,76 @@ impl<T> OfflineQueue<T> {
         self.queue.lock().await.is_empty()
     }
 
+    /// 队列中所有项目的副本（按入队顺序）
+    pub async fn items(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.queue.lock().await.clone()
+    }
+
+    /// 按位置（从 0 开始）删除项目，返回被删除的项目（越界的位置被忽略）
+    pub async fn remove(&self, indexes: &[usize]) -> Vec<T> {
+        let mut queue = self.queue.lock().await;
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *queue)
+            .into_iter()
+            .enumerate()
+            .partition(|(index, _)| indexes.contains(index));
+        *queue = kept.into_iter().map(|(_, item)| item).collect();
+        self.rewrite(&queue).await;
+        removed.into_iter().map(|(_, item)| item).collect()
+    }
+
     /// 清空队列
     pub async fn clear(&self) {
-        self.queue.lock().await.clear();
+        let mut queue = self.queue.lock().await;
+        self.rewrite(&[]).await;
+        queue.clear();
+    }
+
+    /// 追加一个项目到持久化文件
+    async fn append(path: &Path, item: &T) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut line = serde_json::to_string(item).context("无法序列化离线操作")?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("无法打开离线队列文件: {:?}", path))?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// 原子重写持久化文件（失败只记录警告，内存中的队列不受影响）
+    async fn rewrite(&self, items: &[T]) {
+        let Some(path) = self.persist_path.as_ref().filter(|path| path.exists()) else {
+            return;
+        };
+
+        let result = match Self::to_ndjson(items) {
+            Ok(content) => crate::transfer::write_atomic(path, content).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            warn!("无法重写离线队列文件 {:?}: {:#}", path, e);
+        }
+    }
+
+    /// 序列化为每行一个 JSON 的文本
+    fn to_ndjson(items: &[T]) -> Result<String> {
+        let mut content = String::new();
+        for item in items {
+            content.push_str(&serde_json::to_string(item).context("无法序列化离线操作")?);
+            content.push('\n');
+        }
+        Ok(content)
     }
 }
 
@@ -283,6 +446,45 @@ mod tests {
         assert!(delay3.as_millis() >= delay2.as_millis());
     }
 
+    #[test]
+    fn test_seeded_delay_sequence() {
+        let config = RetryConfig::new()
+            .with_initial_delay_ms(100)
+            .with_max_delay_ms(5000)
+            .with_max_retries(10);
+
+        let delays = |seed: u64| -> Vec<u64> {
+            let executor = RetryExecutor::new(config.clone()).with_seed(seed);
+            (0..10)
+                .map(|attempt| executor.next_delay(attempt).as_millis() as u64)
+                .collect()
+        };
+
+        // 相同种子产生完全相同的序列
+        let sequence = delays(42);
+        assert_eq!(sequence, delays(42));
+
+        let mut previous = 0;
+        for (attempt, &delay) in sequence.iter().enumerate() {
+            let base = (100.0 * 2f64.powi(attempt as i32)).min(5000.0) as u64;
+            let upper = (base as f64 * (1.0 + config.jitter_factor)) as u64;
+            assert!(
+                delay >= base && delay <= upper,
+                "attempt {}: {} not in [{}, {}]",
+                attempt,
+                delay,
+                base,
+                upper
+            );
+
+            // 未达到上限前延迟单调递增
+            if base < config.max_delay_ms {
+                assert!(delay >= previous);
+            }
+            previous = delay;
+        }
+    }
+
     #[tokio::test]
     async fn test_retry_executor_success() {
         let config = RetryConfig::new().with_max_retries(3);
@@ -349,6 +551,42 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_retryable_codes_are_configurable() {
+        use std::sync::atomic::{AtomicI32, Ordering};
+
+        let config = RetryConfig::new()
+            .with_max_retries(2)
+            .with_initial_delay_ms(1)
+            .with_retryable_codes([tonic::Code::Aborted]);
+        let executor = RetryExecutor::new(config);
+
+        let attempts = |code: tonic::Code| {
+            let count = AtomicI32::new(0);
+            let executor = &executor;
+            async move {
+                let result = executor
+                    .execute(
+                        || {
+                            count.fetch_add(1, Ordering::SeqCst);
+                            async move {
+                                Err::<(), _>(ClientError::from(tonic::Status::new(code, "失败")))
+                            }
+                        },
+                        "test_operation",
+                    )
+                    .await;
+                assert!(result.is_err());
+                count.load(Ordering::SeqCst)
+            }
+        };
+
+        // 配置的状态码重试到上限，未配置的立即失败
+        assert_eq!(attempts(tonic::Code::Aborted).await, 3);
+        assert_eq!(attempts(tonic::Code::Unavailable).await, 1);
+        assert_eq!(attempts(tonic::Code::InvalidArgument).await, 1);
+    }
+
     #[tokio::test]
     async fn test_offline_queue() {
         let queue = OfflineQueue::new(10);
@@ -361,9 +599,9 @@ mod tests {
         assert_eq!(queue.len().await, 5);
         assert!(!queue.is_empty().await);
 
-        // 取出所有项目
-        let items = queue.drain().await;
-        assert_eq!(items.len(), 5);
+        // 清空队列
+        assert_eq!(queue.items().await.len(), 5);
+        queue.clear().await;
         assert!(queue.is_empty().await);
 
         // 测试队列满
@@ -376,4 +614,59 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_offline_queue_survives_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("queue").join("offline_queue.ndjson");
+
+        let queue = OfflineQueue::persistent(3, path.clone()).unwrap();
+        for item in ["a", "b", "c"] {
+            queue.push(item.to_string()).await.unwrap();
+        }
+        assert!(queue.push("d".to_string()).await.is_err());
+        drop(queue);
+
+        // 模拟重启：从同一文件构造新队列
+        let queue: OfflineQueue<String> = OfflineQueue::persistent(3, path.clone()).unwrap();
+        assert_eq!(queue.len().await, 3);
+        assert_eq!(queue.items().await, vec!["a", "b", "c"]);
+
+        // 删除已处理的项目后再次重启，不会重复处理
+        queue.remove(&[0, 1]).await;
+        queue.push("e".to_string()).await.unwrap();
+        drop(queue);
+        let queue: OfflineQueue<String> = OfflineQueue::persistent(3, path.clone()).unwrap();
+        assert_eq!(queue.items().await, vec!["c", "e"]);
+
+        // 损坏的行被跳过，超出上限的项目被丢弃
+        std::fs::write(&path, "\"x\"\nnot json\n\"y\"\n\"z\"\n").unwrap();
+        let queue: OfflineQueue<String> = OfflineQueue::persistent(2, path.clone()).unwrap();
+        assert_eq!(queue.items().await, vec!["x", "y"]);
+    }
+
+    #[tokio::test]
+    async fn test_offline_queue_remove_and_clear() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("offline_queue.ndjson");
+
+        let queue = OfflineQueue::persistent(10, path.clone()).unwrap();
+        for item in ["a", "b", "c", "d"] {
+            queue.push(item.to_string()).await.unwrap();
+        }
+
+        // 按位置删除，越界的位置被忽略
+        assert_eq!(queue.remove(&[1, 3, 9]).await, vec!["b", "d"]);
+        assert_eq!(queue.items().await, vec!["a", "c"]);
+        drop(queue);
+
+        // 删除结果已写入文件
+        let queue: OfflineQueue<String> = OfflineQueue::persistent(10, path.clone()).unwrap();
+        assert_eq!(queue.items().await, vec!["a", "c"]);
+
+        queue.clear().await;
+        drop(queue);
+        let queue: OfflineQueue<String> = OfflineQueue::persistent(10, path.clone()).unwrap();
+        assert!(queue.is_empty().await);
+    }
 }
@@ -1,7 +1,20 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::path::Path;
-use tracing::debug;
+use tracing::{debug, info, warn};
+
+use crate::config::ClientConfig;
+use crate::grpc_client::ServerRule;
+
+/// Claude 目录中的忽略文件名（gitignore 语法）
+pub const IGNORE_FILE_NAME: &str = ".claudesyncignore";
+
+/// 命令行临时规则的 ID 前缀
+pub const ADHOC_RULE_PREFIX: &str = "adhoc-";
+
+/// 服务器规则的 ID 前缀
+pub const SERVER_RULE_PREFIX: &str = "server-";
 
 /// 同步规则
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +45,10 @@ pub struct SyncRule {
 
     /// 描述（可选）
     pub description: Option<String>,
+
+    /// 服务器路径改写模板（仅正则包含规则，可引用捕获组，如 `agents/$1`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite: Option<String>,
 }
 
 /// 规则类型
@@ -53,33 +70,121 @@ pub enum PatternType {
 }
 
 /// 规则引擎
+#[derive(Clone)]
 pub struct RuleEngine {
     /// 规则列表
     rules: Vec<SyncRule>,
+    /// 匹配时是否忽略大小写
+    case_insensitive: bool,
 }
 
 impl RuleEngine {
     /// 创建新的规则引擎
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            case_insensitive: false,
+        }
     }
 
     /// 从规则列表创建
     pub fn from_rules(rules: Vec<SyncRule>) -> Self {
         // 按优先级排序（从高到低）
         let mut sorted_rules = rules;
-        sorted_rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        sorted_rules.sort_by_key(|rule| Reverse(rule.priority));
 
         Self {
             rules: sorted_rules,
+            case_insensitive: false,
+        }
+    }
+
+    /// 从客户端配置创建：配置中的规则加上 Claude 目录中的忽略文件
+    ///
+    /// 忽略文件的规则优先级低于所有配置规则，文件中靠后的模式覆盖靠前的模式。
+    pub fn from_config(config: &ClientConfig) -> Result<Self> {
+        let mut rules = config.sync.rules.clone();
+        let ignore_rules = load_ignore_file(&config.sync.claude_dir)?;
+
+        let floor = rules.iter().map(|rule| rule.priority).min().unwrap_or(0);
+        let count = ignore_rules.len() as i32;
+        for (index, mut rule) in ignore_rules.into_iter().enumerate() {
+            rule.priority = floor - count + index as i32;
+            rules.push(rule);
+        }
+
+        Ok(Self::from_rules(rules).with_case_insensitive(config.case_insensitive_paths()))
+    }
+
+    /// 设置匹配时是否忽略大小写（大小写不敏感的平台上应开启）
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// 添加服务器规则，优先级高于所有本地规则（配置规则和忽略文件）
+    ///
+    /// 服务器规则之间保持原有的优先级顺序，优先级相同时本设备规则优先于全局规则；
+    /// 无效的规则跳过并记录警告。
+    pub fn with_server_rules(mut self, rules: &[ServerRule]) -> Self {
+        let ceiling = self
+            .rules
+            .iter()
+            .map(|rule| rule.priority)
+            .max()
+            .unwrap_or(0);
+        let floor = rules.iter().map(|rule| rule.priority).min().unwrap_or(0);
+        for server_rule in rules {
+            match server_sync_rule(server_rule) {
+                Ok(mut rule) => {
+                    rule.priority = ceiling
+                        + 1
+                        + (server_rule.priority - floor) * 2
+                        + server_rule.device_specific as i32;
+                    self.rules.push(rule);
+                }
+                Err(e) => warn!("忽略无效的服务器规则 {}: {:#}", server_rule.rule_name, e),
+            }
+        }
+        self.rules.sort_by_key(|rule| Reverse(rule.priority));
+        self
+    }
+
+    /// 添加命令行临时规则，优先级高于所有已有规则（排除规则高于包含规则）
+    pub fn with_adhoc_rules(mut self, rules: &[SyncRule]) -> Self {
+        let ceiling = self
+            .rules
+            .iter()
+            .map(|rule| rule.priority)
+            .max()
+            .unwrap_or(0);
+        for rule in rules {
+            let mut rule = rule.clone();
+            rule.priority = match rule.rule_type {
+                RuleType::Include => ceiling + 1,
+                RuleType::Exclude => ceiling + 2,
+            };
+            self.rules.push(rule);
         }
+        self.rules.sort_by_key(|rule| Reverse(rule.priority));
+        self
+    }
+
+    /// 是否有命令行临时规则或服务器规则匹配该路径（这些规则优先于本地配置规则）
+    pub fn overrides_local_rules(&self, path: &Path) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.enabled
+                && (rule.id.starts_with(ADHOC_RULE_PREFIX)
+                    || rule.id.starts_with(SERVER_RULE_PREFIX))
+                && self.match_pattern(&rule.pattern_type, &rule.pattern, path)
+        })
     }
 
     /// 添加规则
     pub fn add_rule(&mut self, rule: SyncRule) {
         self.rules.push(rule);
         // 重新排序
-        self.rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+        self.rules.sort_by_key(|rule| Reverse(rule.priority));
     }
 
     /// 移除规则
@@ -149,19 +254,63 @@ impl RuleEngine {
         should_sync
     }
 
+    /// 按优先级最高的匹配改写规则改写服务器路径，没有规则匹配时返回 None
+    pub fn rewrite_path(&self, remote_path: &str) -> Option<String> {
+        self.rules.iter().find_map(|rule| {
+            let template = rule
+                .rewrite
+                .as_deref()
+                .filter(|_| rule.enabled && rule.pattern_type == PatternType::Regex)?;
+            let re = regex::RegexBuilder::new(&rule.pattern)
+                .case_insensitive(self.case_insensitive)
+                .build()
+                .ok()?;
+            if !re.is_match(remote_path) {
+                return None;
+            }
+
+            let rewritten = re.replace(remote_path, template).into_owned();
+            debug!(
+                "路径改写: {} -> {} (规则: {})",
+                remote_path, rewritten, rule.name
+            );
+            Some(rewritten)
+        })
+    }
+
     /// 匹配模式
     fn match_pattern(&self, pattern_type: &PatternType, pattern: &str, path: &Path) -> bool {
         match pattern_type {
             PatternType::Glob => {
-                if let Ok(glob_pattern) = glob::Pattern::new(pattern) {
-                    glob_pattern.matches_path(path)
-                } else {
-                    debug!("无效的 Glob 模式: {}", pattern);
-                    false
-                }
+                // glob crate 不支持花括号，先展开为多个模式；
+                // 同时匹配完整相对路径和文件名，使 *.json 能匹配 agents/x.json
+                let path_str = path.to_string_lossy().replace('\\', "/");
+                let file_name = path.file_name().map(|name| name.to_string_lossy());
+                let options = glob::MatchOptions {
+                    case_sensitive: !self.case_insensitive,
+                    ..Default::default()
+                };
+
+                expand_braces(pattern)
+                    .iter()
+                    .any(|expanded| match glob::Pattern::new(expanded) {
+                        Ok(glob_pattern) => {
+                            glob_pattern.matches_with(&path_str, options)
+                                || file_name
+                                    .as_deref()
+                                    .is_some_and(|name| glob_pattern.matches_with(name, options))
+                        }
+                        Err(_) => {
+                            debug!("无效的 Glob 模式: {}", pattern);
+                            false
+                        }
+                    })
             }
             PatternType::Regex => {
-                if let Ok(re) = regex::Regex::new(pattern) {
+                if let Ok(re) = regex::RegexBuilder::new(pattern)
+                    .case_insensitive(self.case_insensitive)
+                    .build()
+                {
                     let path_str = path.to_string_lossy();
                     re.is_match(&path_str)
                 } else {
@@ -192,8 +341,10 @@ impl RuleEngine {
         // 验证模式格式
         match rule.pattern_type {
             PatternType::Glob => {
-                glob::Pattern::new(&rule.pattern)
-                    .with_context(|| format!("无效的 Glob 模式: {}", rule.pattern))?;
+                for expanded in expand_braces(&rule.pattern) {
+                    glob::Pattern::new(&expanded)
+                        .with_context(|| format!("无效的 Glob 模式: {}", rule.pattern))?;
+                }
             }
             PatternType::Regex => {
                 regex::Regex::new(&rule.pattern)
@@ -201,6 +352,27 @@ impl RuleEngine {
             }
         }
 
+        // 验证改写模板
+        if let Some(template) = &rule.rewrite {
+            if rule.pattern_type != PatternType::Regex || rule.rule_type != RuleType::Include {
+                anyhow::bail!("只有正则包含规则支持 rewrite");
+            }
+            if template.is_empty() {
+                anyhow::bail!("rewrite 模板不能为空");
+            }
+
+            let re = regex::Regex::new(&rule.pattern)?;
+            for group in template_groups(template) {
+                let exists = match group.parse::<usize>() {
+                    Ok(index) => index < re.captures_len(),
+                    Err(_) => re.capture_names().flatten().any(|name| name == group),
+                };
+                if !exists {
+                    anyhow::bail!("rewrite 模板引用了不存在的捕获组: ${}", group);
+                }
+            }
+        }
+
         // 验证优先级范围
         if rule.priority < -100 || rule.priority > 100 {
             anyhow::bail!("规则优先级必须在 -100 到 100 之间");
@@ -221,6 +393,7 @@ impl RuleEngine {
             priority: -100,
             enabled: true,
             description: Some("默认包含所有文件的兜底规则".to_string()),
+            rewrite: None,
         }]
     }
 
@@ -238,6 +411,7 @@ impl RuleEngine {
                 priority: 50,
                 enabled: true,
                 description: Some("同步 agents 目录中的 Markdown 文件".to_string()),
+                rewrite: None,
             },
             SyncRule {
                 id: "include-skills".to_string(),
@@ -249,6 +423,7 @@ impl RuleEngine {
                 priority: 50,
                 enabled: true,
                 description: Some("同步 skills 目录中的 Markdown 文件".to_string()),
+                rewrite: None,
             },
             SyncRule {
                 id: "include-plugins".to_string(),
@@ -260,6 +435,7 @@ impl RuleEngine {
                 priority: 50,
                 enabled: true,
                 description: Some("同步 plugins 目录的所有文件".to_string()),
+                rewrite: None,
             },
             SyncRule {
                 id: "include-config".to_string(),
@@ -271,6 +447,7 @@ impl RuleEngine {
                 priority: 60,
                 enabled: true,
                 description: Some("同步配置文件".to_string()),
+                rewrite: None,
             },
             // 排除规则
             SyncRule {
@@ -283,6 +460,7 @@ impl RuleEngine {
                 priority: 100,
                 enabled: true,
                 description: Some("排除临时文件".to_string()),
+                rewrite: None,
             },
             SyncRule {
                 id: "exclude-backup".to_string(),
@@ -294,6 +472,7 @@ impl RuleEngine {
                 priority: 100,
                 enabled: true,
                 description: Some("排除备份文件".to_string()),
+                rewrite: None,
             },
             SyncRule {
                 id: "exclude-swap".to_string(),
@@ -305,6 +484,7 @@ impl RuleEngine {
                 priority: 100,
                 enabled: true,
                 description: Some("排除 Vim 交换文件".to_string()),
+                rewrite: None,
             },
         ]
     }
@@ -318,6 +498,222 @@ impl Default for RuleEngine {
 
 // ===== 辅助函数 =====
 
+/// 展开 Glob 模式中的花括号，例如 `*.{json,toml}` -> `*.json`、`*.toml`
+///
+/// 支持多组和嵌套的花括号；未闭合的花括号按字面量处理
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(start) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+
+    let mut depth = 0;
+    let mut end = None;
+    let mut commas = Vec::new();
+    for (offset, c) in pattern[start..].char_indices() {
+        let index = start + offset;
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(index);
+                    break;
+                }
+            }
+            ',' if depth == 1 => commas.push(index),
+            _ => {}
+        }
+    }
+
+    let Some(end) = end else {
+        return vec![pattern.to_string()];
+    };
+
+    let prefix = &pattern[..start];
+    let suffix = &pattern[end + 1..];
+
+    let mut bounds = vec![start];
+    bounds.extend(commas);
+    bounds.push(end);
+
+    bounds
+        .windows(2)
+        .flat_map(|window| {
+            let alternative = &pattern[window[0] + 1..window[1]];
+            expand_braces(&format!("{}{}{}", prefix, alternative, suffix))
+        })
+        .collect()
+}
+
+/// 改写模板中引用的捕获组（`$1`、`$name`、`${name}`，`$$` 为字面量 `$`）
+fn template_groups(template: &str) -> Vec<&str> {
+    let mut groups = Vec::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find('$') {
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+        {
+            groups.push(name);
+            rest = after;
+        } else {
+            // 与 regex 的替换语法一致：取最长的 [A-Za-z0-9_] 序列作为组名
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if len > 0 {
+                groups.push(&rest[..len]);
+            }
+            rest = &rest[len..];
+        }
+    }
+
+    groups
+}
+
+/// 根据 sync 命令的 `--include`/`--exclude` 创建临时规则
+///
+/// 只对本次运行生效，不写入配置；模式按 Glob 校验，优先级由 [`RuleEngine::with_adhoc_rules`] 分配。
+pub fn adhoc_rules(includes: &[String], excludes: &[String]) -> Result<Vec<SyncRule>> {
+    let includes = includes.iter().map(|pattern| (RuleType::Include, pattern));
+    let excludes = excludes.iter().map(|pattern| (RuleType::Exclude, pattern));
+
+    includes
+        .chain(excludes)
+        .enumerate()
+        .map(|(index, (rule_type, pattern))| {
+            let flag = match rule_type {
+                RuleType::Include => "--include",
+                RuleType::Exclude => "--exclude",
+            };
+            let rule = SyncRule {
+                id: format!("{}{}", ADHOC_RULE_PREFIX, index + 1),
+                name: format!("{} {}", flag, pattern),
+                rule_type,
+                pattern: pattern.clone(),
+                pattern_type: PatternType::Glob,
+                file_type: None,
+                priority: 0,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            };
+            RuleEngine::validate_rule(&rule).with_context(|| format!("无效的 {} 参数", flag))?;
+            Ok(rule)
+        })
+        .collect()
+}
+
+/// 将服务器规则转换为本地规则（模式按 Glob 校验，优先级由 [`RuleEngine::with_server_rules`] 分配）
+fn server_sync_rule(rule: &ServerRule) -> Result<SyncRule> {
+    let rule_type = match rule.rule_type.as_str() {
+        "include" => RuleType::Include,
+        "exclude" => RuleType::Exclude,
+        other => anyhow::bail!("未知的规则类型: {}", other),
+    };
+    let sync_rule = SyncRule {
+        id: format!("{}{}", SERVER_RULE_PREFIX, rule.rule_id),
+        name: rule.rule_name.clone(),
+        rule_type,
+        pattern: rule.pattern.clone(),
+        pattern_type: PatternType::Glob,
+        file_type: rule
+            .file_type
+            .clone()
+            .filter(|file_type| !file_type.is_empty()),
+        priority: 0,
+        enabled: true,
+        description: None,
+        rewrite: None,
+    };
+    RuleEngine::validate_rule(&sync_rule)?;
+    Ok(sync_rule)
+}
+
+/// 读取 Claude 目录中的忽略文件并转换为规则（文件不存在时返回空列表）
+pub fn load_ignore_file(claude_dir: &Path) -> Result<Vec<SyncRule>> {
+    let path = claude_dir.join(IGNORE_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("无法读取忽略文件: {:?}", path))?;
+
+    let rules: Vec<SyncRule> = parse_ignore_file(&content)
+        .into_iter()
+        .filter(|rule| match RuleEngine::validate_rule(rule) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("忽略文件中的无效模式 {}: {}", rule.name, e);
+                false
+            }
+        })
+        .collect();
+
+    info!("从 {:?} 加载了 {} 条忽略规则", path, rules.len());
+    Ok(rules)
+}
+
+/// 解析 gitignore 语法的忽略文件
+///
+/// 支持注释、空行、`!` 取反、末尾 `/` 的目录模式；不含 `/` 的模式在任意层级匹配，
+/// 含 `/` 的模式相对 Claude 目录匹配。返回规则的优先级均为 0，由调用方重新分配。
+pub fn parse_ignore_file(content: &str) -> Vec<SyncRule> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+
+            let (rule_type, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (RuleType::Include, pattern),
+                None => (RuleType::Exclude, line),
+            };
+            // \# 和 \! 表示字面量开头
+            let pattern = match pattern.strip_prefix('\\') {
+                Some(rest) if rest.starts_with('#') || rest.starts_with('!') => rest,
+                _ => pattern,
+            };
+
+            let dir_only = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            let anchored = pattern.contains('/');
+            let pattern = pattern.trim_start_matches('/');
+            if pattern.is_empty() {
+                return None;
+            }
+
+            let glob = match (dir_only, anchored) {
+                (true, true) => format!("{}/**", pattern),
+                (true, false) => format!("**/{}/**", pattern),
+                (false, true) => format!("{{{0},{0}/**}}", pattern),
+                (false, false) => format!("{{{0},**/{0}/**}}", pattern),
+            };
+
+            Some(SyncRule {
+                id: format!("ignore-{}", index + 1),
+                name: format!("{}:{}", IGNORE_FILE_NAME, index + 1),
+                rule_type,
+                pattern: glob,
+                pattern_type: PatternType::Glob,
+                file_type: None,
+                priority: 0,
+                enabled: true,
+                description: Some(line.to_string()),
+                rewrite: None,
+            })
+        })
+        .collect()
+}
+
 /// 识别文件类型
 pub fn detect_file_type(path: &Path) -> String {
     if let Some(ext) = path.extension() {
@@ -340,6 +736,65 @@ pub fn detect_file_type(path: &Path) -> String {
     }
 }
 
+/// 未知类型文件的 MIME 类型
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// 识别文件的 MIME 类型
+///
+/// 按 `detect_file_type` 的类型映射，同一类型下按扩展名细分；无法识别时根据文件开头的内容判断，
+/// 可以按 UTF-8 解码且不含 NUL 的内容视为纯文本。
+pub fn detect_mime_type(path: &Path, head: &[u8]) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let mime = match (detect_file_type(path).as_str(), ext.as_str()) {
+        ("text", "md") => "text/markdown",
+        ("text", "rst") => "text/x-rst",
+        ("text", _) | ("log", _) => "text/plain",
+        ("json", _) => "application/json",
+        ("yaml", _) => "application/yaml",
+        ("toml", _) => "application/toml",
+        ("xml", _) => "application/xml",
+        ("pdf", _) => "application/pdf",
+        ("image", "png") => "image/png",
+        ("image", "jpg" | "jpeg") => "image/jpeg",
+        ("image", "gif") => "image/gif",
+        ("image", "bmp") => "image/bmp",
+        ("image", "ico") => "image/vnd.microsoft.icon",
+        ("archive", "zip") => "application/zip",
+        ("archive", "gz") => "application/gzip",
+        ("archive", "tar") => "application/x-tar",
+        ("archive", "7z") => "application/x-7z-compressed",
+        ("archive", "rar") => "application/vnd.rar",
+        ("binary", _) => DEFAULT_MIME_TYPE,
+        ("sh", _) => "application/x-sh",
+        ("js" | "mjs", _) => "text/javascript",
+        ("ts", _) => "text/x-typescript",
+        ("py", _) => "text/x-python",
+        ("html" | "htm", _) => "text/html",
+        ("css", _) => "text/css",
+        ("csv", _) => "text/csv",
+        _ => "",
+    };
+    if !mime.is_empty() {
+        return mime;
+    }
+
+    // 开头可能截断在多字节字符中间，末尾不完整的字符不算无效
+    let is_text = !head.contains(&0)
+        && match std::str::from_utf8(head) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        };
+    if is_text {
+        "text/plain"
+    } else {
+        DEFAULT_MIME_TYPE
+    }
+}
+
 /// 检查文件是否是文本文件
 pub fn is_text_file(path: &Path) -> bool {
     if let Some(ext) = path.extension() {
@@ -385,6 +840,7 @@ mod tests {
             priority: 0,
             enabled: true,
             description: None,
+            rewrite: None,
         });
 
         // 添加排除规则（优先级更高）
@@ -398,6 +854,7 @@ mod tests {
             priority: 10,
             enabled: true,
             description: None,
+            rewrite: None,
         });
 
         // 测试包含规则
@@ -409,6 +866,41 @@ mod tests {
         assert!(!engine.should_sync(&temp_path, Some("text")));
     }
 
+    #[test]
+    fn test_detect_mime_type() {
+        for (name, expected) in [
+            ("CLAUDE.md", "text/markdown"),
+            ("notes.txt", "text/plain"),
+            ("settings.json", "application/json"),
+            ("config.YAML", "application/yaml"),
+            ("pyproject.toml", "application/toml"),
+            ("icon.png", "image/png"),
+            ("photo.jpeg", "image/jpeg"),
+            ("backup.zip", "application/zip"),
+            ("report.pdf", "application/pdf"),
+            ("hooks/pre-commit.sh", "application/x-sh"),
+            ("tool.exe", "application/octet-stream"),
+        ] {
+            assert_eq!(detect_mime_type(Path::new(name), b""), expected, "{}", name);
+        }
+
+        // 无法按扩展名识别时根据内容判断
+        assert_eq!(
+            detect_mime_type(Path::new("Makefile"), "构建脚本\n".as_bytes()),
+            "text/plain"
+        );
+        assert_eq!(
+            detect_mime_type(Path::new("cache.bin"), &[0x00, 0xff, 0x10]),
+            "application/octet-stream"
+        );
+        // 截断在多字节字符中间的开头仍视为文本
+        let head = "中文".as_bytes();
+        assert_eq!(
+            detect_mime_type(Path::new("notes"), &head[..4]),
+            "text/plain"
+        );
+    }
+
     #[test]
     fn test_detect_file_type() {
         assert_eq!(detect_file_type(Path::new("test.md")), "text");
@@ -438,6 +930,7 @@ mod tests {
             priority: 0,
             enabled: true,
             description: None,
+            rewrite: None,
         };
 
         assert!(RuleEngine::validate_rule(&valid_rule).is_ok());
@@ -452,6 +945,61 @@ mod tests {
         assert!(RuleEngine::validate_rule(&invalid_rule).is_err());
     }
 
+    fn rewrite_rule(pattern: &str, rewrite: &str) -> SyncRule {
+        SyncRule {
+            id: "rewrite-agents".to_string(),
+            name: "映射本地 agents".to_string(),
+            rule_type: RuleType::Include,
+            pattern: pattern.to_string(),
+            pattern_type: PatternType::Regex,
+            file_type: None,
+            priority: 10,
+            enabled: true,
+            description: None,
+            rewrite: Some(rewrite.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_with_capture_groups() {
+        let rule = rewrite_rule(r"^local-agents/(?P<name>.+)\.md$", "agents/${name}.md");
+        assert!(RuleEngine::validate_rule(&rule).is_ok());
+
+        let engine = RuleEngine::from_rules(vec![rule]);
+        assert_eq!(
+            engine.rewrite_path("local-agents/reviewer.md").as_deref(),
+            Some("agents/reviewer.md")
+        );
+        assert_eq!(engine.rewrite_path("agents/reviewer.md"), None);
+
+        let numbered = rewrite_rule(r"^local-(\w+)/(.+)$", "$1/$2");
+        assert!(RuleEngine::validate_rule(&numbered).is_ok());
+        assert_eq!(
+            RuleEngine::from_rules(vec![numbered])
+                .rewrite_path("local-skills/a/b.md")
+                .as_deref(),
+            Some("skills/a/b.md")
+        );
+    }
+
+    #[test]
+    fn test_rewrite_rejects_unknown_group() {
+        // $2 和 $tail 都不存在；$1x 按 regex 语法是名为 1x 的组
+        for template in ["agents/$2", "agents/${tail}", "agents/$1x"] {
+            let rule = rewrite_rule(r"^local-agents/(.+)$", template);
+            let error = RuleEngine::validate_rule(&rule).unwrap_err();
+            assert!(error.to_string().contains("不存在的捕获组"), "{}", template);
+        }
+
+        // $$ 是字面量，不引用捕获组
+        assert!(RuleEngine::validate_rule(&rewrite_rule(r"^(.+)$", "$$/${1}")).is_ok());
+
+        // Glob 规则不支持改写
+        let mut glob = rewrite_rule("local-agents/**", "agents");
+        glob.pattern_type = PatternType::Glob;
+        assert!(RuleEngine::validate_rule(&glob).is_err());
+    }
+
     #[test]
     fn test_recommended_rules() {
         let rules = RuleEngine::recommended_rules();
@@ -467,4 +1015,308 @@ mod tests {
         let temp_path = PathBuf::from("test.tmp");
         assert!(!engine.should_sync(&temp_path, None));
     }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(expand_braces("*.md"), vec!["*.md"]);
+        assert_eq!(
+            expand_braces("*.{json,toml,yaml,yml}"),
+            vec!["*.json", "*.toml", "*.yaml", "*.yml"]
+        );
+        assert_eq!(
+            expand_braces("{agents,skills}/*.{md,txt}"),
+            vec!["agents/*.md", "agents/*.txt", "skills/*.md", "skills/*.txt"]
+        );
+        assert_eq!(expand_braces("a{b,c{d,e}}"), vec!["ab", "acd", "ace"]);
+        assert_eq!(expand_braces("broken{json"), vec!["broken{json"]);
+    }
+
+    #[test]
+    fn test_recommended_config_rule_matches_nested_files() {
+        let config_rule = RuleEngine::recommended_rules()
+            .into_iter()
+            .find(|rule| rule.id == "include-config")
+            .unwrap();
+        let engine = RuleEngine::new();
+
+        for path in [
+            "settings.json",
+            "skills/foo.json",
+            "plugins/deep/nested/config.toml",
+            "agents/meta.yaml",
+            "agents/meta.yml",
+        ] {
+            assert!(
+                engine.match_pattern(
+                    &config_rule.pattern_type,
+                    &config_rule.pattern,
+                    Path::new(path)
+                ),
+                "配置规则应匹配 {}",
+                path
+            );
+        }
+
+        assert!(!engine.match_pattern(
+            &config_rule.pattern_type,
+            &config_rule.pattern,
+            Path::new("agents/readme.md")
+        ));
+        assert!(RuleEngine::validate_rule(&config_rule).is_ok());
+    }
+
+    #[test]
+    fn test_glob_matches_file_name_in_nested_path() {
+        let engine = RuleEngine::from_rules(vec![
+            SyncRule {
+                id: "exclude-all".to_string(),
+                name: "默认排除".to_string(),
+                rule_type: RuleType::Exclude,
+                pattern: "**".to_string(),
+                pattern_type: PatternType::Glob,
+                file_type: None,
+                priority: -100,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            },
+            SyncRule {
+                id: "include-json".to_string(),
+                name: "包含 JSON".to_string(),
+                rule_type: RuleType::Include,
+                pattern: "*.json".to_string(),
+                pattern_type: PatternType::Glob,
+                file_type: None,
+                priority: 10,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            },
+        ]);
+
+        assert!(engine.should_sync(Path::new("agents/x.json"), None));
+        assert!(engine.should_sync(Path::new("x.json"), None));
+        assert!(!engine.should_sync(Path::new("agents/x.md"), None));
+    }
+
+    #[test]
+    fn test_case_insensitive_matching() {
+        let rules = vec![
+            SyncRule {
+                id: "exclude-agents".to_string(),
+                name: "排除 agents".to_string(),
+                rule_type: RuleType::Exclude,
+                pattern: "agents/**".to_string(),
+                pattern_type: PatternType::Glob,
+                file_type: None,
+                priority: 10,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            },
+            SyncRule {
+                id: "exclude-log".to_string(),
+                name: "排除日志".to_string(),
+                rule_type: RuleType::Exclude,
+                pattern: r"\.log$".to_string(),
+                pattern_type: PatternType::Regex,
+                file_type: None,
+                priority: 10,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            },
+        ];
+
+        let sensitive = RuleEngine::from_rules(rules.clone());
+        assert!(sensitive.should_sync(Path::new("Agents/a.md"), None));
+        assert!(sensitive.should_sync(Path::new("debug.LOG"), None));
+
+        let insensitive = RuleEngine::from_rules(rules).with_case_insensitive(true);
+        assert!(!insensitive.should_sync(Path::new("Agents/a.md"), None));
+        assert!(!insensitive.should_sync(Path::new("debug.LOG"), None));
+    }
+
+    #[test]
+    fn test_parse_ignore_file() {
+        let rules =
+            parse_ignore_file("# 注释\n\n*.log\nbuild/\n/agents/tmp\n!keep.log\n\\#literal\n");
+
+        let patterns: Vec<_> = rules.iter().map(|rule| rule.pattern.as_str()).collect();
+        assert_eq!(
+            patterns,
+            vec![
+                "{*.log,**/*.log/**}",
+                "**/build/**",
+                "{agents/tmp,agents/tmp/**}",
+                "{keep.log,**/keep.log/**}",
+                "{#literal,**/#literal/**}",
+            ]
+        );
+        assert_eq!(rules[3].rule_type, RuleType::Include);
+        assert_eq!(rules[0].name, ".claudesyncignore:3");
+    }
+
+    fn write_ignore_file(dir: &Path, content: &str) -> ClientConfig {
+        std::fs::write(dir.join(IGNORE_FILE_NAME), content).unwrap();
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = dir.to_path_buf();
+        config.sync.case_insensitive = Some(false);
+        config
+    }
+
+    #[test]
+    fn test_ignore_file_excludes_matching_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = write_ignore_file(temp_dir.path(), "*.log\nbuild/\n!keep.log\n");
+
+        let engine = RuleEngine::from_config(&config).unwrap();
+        assert!(!engine.should_sync(Path::new("debug.log"), None));
+        assert!(!engine.should_sync(Path::new("agents/debug.log"), None));
+        assert!(!engine.should_sync(Path::new("build/out.md"), None));
+        assert!(!engine.should_sync(Path::new("agents/build/out.md"), None));
+        assert!(engine.should_sync(Path::new("keep.log"), None));
+        assert!(engine.should_sync(Path::new("agents/a.md"), None));
+    }
+
+    #[test]
+    fn test_config_rules_override_ignore_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = write_ignore_file(temp_dir.path(), "agents/\n");
+        config.sync.rules.push(SyncRule {
+            id: "include-agents-md".to_string(),
+            name: "包含 agents 文档".to_string(),
+            rule_type: RuleType::Include,
+            pattern: "agents/*.md".to_string(),
+            pattern_type: PatternType::Glob,
+            file_type: None,
+            priority: 0,
+            enabled: true,
+            description: None,
+            rewrite: None,
+        });
+
+        let engine = RuleEngine::from_config(&config).unwrap();
+        let ignore_rule = engine
+            .get_rules()
+            .iter()
+            .find(|rule| rule.id == "ignore-1")
+            .unwrap();
+        assert!(ignore_rule.priority < 0);
+
+        assert!(engine.should_sync(Path::new("agents/a.md"), None));
+        assert!(!engine.should_sync(Path::new("agents/a.json"), None));
+    }
+
+    #[test]
+    fn test_adhoc_exclude_overrides_config_include() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = write_ignore_file(temp_dir.path(), "");
+        config.sync.rules.push(SyncRule {
+            id: "include-md".to_string(),
+            name: "包含 Markdown".to_string(),
+            rule_type: RuleType::Include,
+            pattern: "**/*.md".to_string(),
+            pattern_type: PatternType::Glob,
+            file_type: None,
+            priority: 100,
+            enabled: true,
+            description: None,
+            rewrite: None,
+        });
+
+        let rules = adhoc_rules(
+            &["agents/*".to_string()],
+            &["agents/draft-*.md".to_string()],
+        )
+        .unwrap();
+        let engine = RuleEngine::from_config(&config)
+            .unwrap()
+            .with_adhoc_rules(&rules);
+
+        assert!(!engine.should_sync(Path::new("agents/draft-1.md"), None));
+        assert!(engine.should_sync(Path::new("agents/a.md"), None));
+        assert!(engine.overrides_local_rules(Path::new("agents/draft-1.md")));
+        assert!(!engine.overrides_local_rules(Path::new("CLAUDE.md")));
+
+        // 没有临时规则时配置照常生效
+        let engine = RuleEngine::from_config(&config).unwrap();
+        assert!(engine.should_sync(Path::new("agents/draft-1.md"), None));
+
+        // 模式在同步开始前校验
+        let err = adhoc_rules(&[], &["agents/[".to_string()]).unwrap_err();
+        assert!(format!("{:#}", err).contains("--exclude"));
+    }
+
+    fn server_rule(
+        id: &str,
+        rule_type: &str,
+        pattern: &str,
+        priority: i32,
+        device: bool,
+    ) -> ServerRule {
+        ServerRule {
+            rule_id: id.to_string(),
+            rule_name: format!("{} {}", rule_type, pattern),
+            rule_type: rule_type.to_string(),
+            pattern: pattern.to_string(),
+            file_type: None,
+            priority,
+            device_specific: device,
+        }
+    }
+
+    #[test]
+    fn test_server_device_exclude_overrides_local_include() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = write_ignore_file(temp_dir.path(), "");
+        config.sync.rules.push(SyncRule {
+            id: "include-md".to_string(),
+            name: "包含 Markdown".to_string(),
+            rule_type: RuleType::Include,
+            pattern: "**/*.md".to_string(),
+            pattern_type: PatternType::Glob,
+            file_type: None,
+            priority: 100,
+            enabled: true,
+            description: None,
+            rewrite: None,
+        });
+
+        let rules = vec![
+            server_rule("1", "include", "agents/*", 0, false),
+            server_rule("2", "exclude", "agents/private-*.md", 10, true),
+            server_rule("3", "bogus", "**/*", 50, false),
+        ];
+        let engine = RuleEngine::from_config(&config)
+            .unwrap()
+            .with_server_rules(&rules);
+
+        // 本设备的排除规则覆盖本地包含规则
+        assert!(!engine.should_sync(Path::new("agents/private-1.md"), None));
+        assert!(engine.should_sync(Path::new("agents/a.md"), None));
+        assert!(engine.overrides_local_rules(Path::new("agents/private-1.md")));
+        assert!(!engine.overrides_local_rules(Path::new("CLAUDE.md")));
+
+        // 优先级相同时本设备规则优先于全局规则
+        let rules = vec![
+            server_rule("1", "include", "agents/*", 0, false),
+            server_rule("2", "exclude", "agents/*", 0, true),
+        ];
+        let engine = RuleEngine::from_config(&config)
+            .unwrap()
+            .with_server_rules(&rules);
+        assert!(!engine.should_sync(Path::new("agents/a.md"), None));
+
+        // 临时规则仍然优先于服务器规则
+        let engine =
+            engine.with_adhoc_rules(&adhoc_rules(&["agents/a.md".to_string()], &[]).unwrap());
+        assert!(engine.should_sync(Path::new("agents/a.md"), None));
+    }
+
+    #[test]
+    fn test_missing_ignore_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert!(load_ignore_file(temp_dir.path()).unwrap().is_empty());
+    }
 }
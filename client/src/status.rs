@@ -0,0 +1,219 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::sync::{FileSyncState, SyncStatus};
+use crate::token::TokenStorage;
+
+/// `status --json` 的输出
+///
+/// 字段名是脚本和 GUI 依赖的稳定接口：只新增字段，不修改或删除已有字段。
+/// 未登录时除 `logged_in` 外的字段均为 null。
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    /// 是否已登录
+    pub logged_in: bool,
+
+    /// 用户 ID
+    pub user_id: Option<String>,
+
+    /// 设备 ID
+    pub device_id: Option<String>,
+
+    /// Access Token 状态
+    pub access_token: Option<TokenStatus>,
+
+    /// Refresh Token 状态
+    pub refresh_token: Option<TokenStatus>,
+
+    /// 本地同步状态统计
+    pub sync: Option<SyncStats>,
+}
+
+/// Token 过期状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStatus {
+    /// 过期时间（RFC 3339）
+    pub expires_at: DateTime<Utc>,
+
+    /// 是否已过期
+    pub expired: bool,
+
+    /// 是否即将过期、需要刷新
+    pub refresh_due: bool,
+}
+
+impl TokenStatus {
+    fn new(expires_at: i64, refresh_before: i64, now: DateTime<Utc>) -> Self {
+        Self {
+            expires_at: DateTime::from_timestamp(expires_at, 0).unwrap_or_default(),
+            expired: now.timestamp() >= expires_at,
+            refresh_due: expires_at - now.timestamp() < refresh_before,
+        }
+    }
+}
+
+/// 本地同步状态统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncStats {
+    /// 有同步状态记录的文件数
+    pub total_files: usize,
+
+    /// 已同步
+    pub synced: usize,
+
+    /// 等待同步（含正在同步）
+    pub pending: usize,
+
+    /// 未解决的冲突
+    pub conflicts: usize,
+
+    /// 同步失败
+    pub failed: usize,
+
+    /// 已跳过
+    pub skipped: usize,
+
+    /// 尚未通知服务器的本地删除
+    pub pending_deletions: usize,
+
+    /// 最近一次同步时间
+    pub last_sync_time: Option<DateTime<Utc>>,
+}
+
+impl SyncStats {
+    /// 按状态统计文件数
+    pub fn from_states(states: &[FileSyncState], pending_deletions: usize) -> Self {
+        let mut stats = Self {
+            total_files: states.len(),
+            pending_deletions,
+            ..Self::default()
+        };
+        for state in states {
+            match state.status {
+                SyncStatus::Synced => stats.synced += 1,
+                SyncStatus::Pending | SyncStatus::Syncing => stats.pending += 1,
+                SyncStatus::Conflict => stats.conflicts += 1,
+                SyncStatus::Failed => stats.failed += 1,
+                SyncStatus::Skipped => stats.skipped += 1,
+            }
+        }
+        stats.last_sync_time = states.iter().filter_map(|state| state.last_sync_time).max();
+        stats
+    }
+}
+
+impl StatusReport {
+    /// 未登录
+    pub fn logged_out() -> Self {
+        Self {
+            logged_in: false,
+            user_id: None,
+            device_id: None,
+            access_token: None,
+            refresh_token: None,
+            sync: None,
+        }
+    }
+
+    /// 根据保存的 Token 生成登录状态（refresh_before 为提前刷新的秒数）
+    pub fn from_tokens(tokens: &TokenStorage, refresh_before: i64, now: DateTime<Utc>) -> Self {
+        Self {
+            logged_in: true,
+            user_id: Some(tokens.user_id.clone()),
+            device_id: Some(tokens.device_id.clone()),
+            access_token: Some(TokenStatus::new(
+                tokens.access_expires_at,
+                refresh_before,
+                now,
+            )),
+            refresh_token: Some(TokenStatus::new(
+                tokens.refresh_expires_at,
+                refresh_before,
+                now,
+            )),
+            sync: None,
+        }
+    }
+
+    /// 附加同步状态统计
+    pub fn with_sync_stats(mut self, stats: SyncStats) -> Self {
+        self.sync = Some(stats);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn state(path: &str, status: SyncStatus, last_sync_time: Option<i64>) -> FileSyncState {
+        FileSyncState {
+            path: PathBuf::from(path),
+            local_hash: None,
+            remote_hash: None,
+            status,
+            last_sync_time: last_sync_time.and_then(|t| DateTime::from_timestamp(t, 0)),
+            error_message: None,
+            size: None,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn test_status_json_schema() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let tokens = TokenStorage {
+            access_token: "access".to_string(),
+            refresh_token: "refresh".to_string(),
+            device_id: "device-1".to_string(),
+            user_id: "user-1".to_string(),
+            access_expires_at: now.timestamp() + 60,
+            refresh_expires_at: now.timestamp() + 86400,
+        };
+        let states = [
+            state("a.md", SyncStatus::Synced, Some(1_699_999_000)),
+            state("b.md", SyncStatus::Synced, Some(1_699_999_900)),
+            state("c.md", SyncStatus::Conflict, None),
+            state("d.md", SyncStatus::Pending, None),
+        ];
+
+        let report = StatusReport::from_tokens(&tokens, 300, now)
+            .with_sync_stats(SyncStats::from_states(&states, 1));
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["logged_in"], true);
+        assert_eq!(json["user_id"], "user-1");
+        assert_eq!(json["device_id"], "device-1");
+        assert_eq!(json["access_token"]["expires_at"], "2023-11-14T22:14:20Z");
+        assert_eq!(json["access_token"]["expired"], false);
+        assert_eq!(json["access_token"]["refresh_due"], true);
+        assert_eq!(json["refresh_token"]["refresh_due"], false);
+        assert_eq!(
+            json["sync"],
+            serde_json::json!({
+                "total_files": 4,
+                "synced": 2,
+                "pending": 1,
+                "conflicts": 1,
+                "failed": 0,
+                "skipped": 0,
+                "pending_deletions": 1,
+                "last_sync_time": "2023-11-14T22:11:40Z",
+            })
+        );
+
+        // 未登录时保留所有字段，值为 null
+        let json = serde_json::to_value(StatusReport::logged_out()).unwrap();
+        assert_eq!(json["logged_in"], false);
+        for field in [
+            "user_id",
+            "device_id",
+            "access_token",
+            "refresh_token",
+            "sync",
+        ] {
+            assert!(json[field].is_null(), "{} 应为 null", field);
+        }
+    }
+}
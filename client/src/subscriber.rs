@@ -0,0 +1,378 @@
+use anyhow::Result;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval_at, sleep, Instant, Interval, MissedTickBehavior};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::error::ClientError;
+use crate::grpc_client::{ChangeNotification, GrpcClient};
+use crate::network::NetworkRecoveryManager;
+use crate::retry::RetryConfig;
+use crate::sync::{RemoteChangeSource, RuleSource, SyncEngine};
+
+/// 远程变更通知来源（守护进程使用 GrpcClient 的 SubscribeChanges 流，测试中可替换为模拟服务器）
+pub trait ChangeNotificationSource: Send + Sync {
+    /// 打开变更通知流（发送端关闭即表示流已断开）
+    fn subscribe(&self) -> impl Future<Output = Result<mpsc::Receiver<ChangeNotification>>> + Send;
+}
+
+impl ChangeNotificationSource for GrpcClient {
+    fn subscribe(&self) -> impl Future<Output = Result<mpsc::Receiver<ChangeNotification>>> + Send {
+        self.subscribe_changes(Vec::new())
+    }
+}
+
+/// 远程变更订阅器
+///
+/// 收到其他设备的变更通知后拉取游标之后的远程变更并下载对应文件；
+/// 服务器不推送规则变更，订阅时和订阅期间按固定间隔重新获取服务器规则。
+/// 通知流断开后按退避延迟重新订阅，订阅请求通过网络恢复管理器重试。
+pub struct ChangeSubscriber {
+    sync_engine: Arc<SyncEngine>,
+    network: Arc<NetworkRecoveryManager>,
+    device_id: Uuid,
+    backoff: RetryConfig,
+    rules_refresh_interval: Option<Duration>,
+}
+
+impl ChangeSubscriber {
+    /// 创建订阅器（device_id 为本机设备，本机产生的通知会被忽略）
+    pub fn new(
+        sync_engine: Arc<SyncEngine>,
+        network: Arc<NetworkRecoveryManager>,
+        device_id: Uuid,
+    ) -> Self {
+        Self {
+            sync_engine,
+            network,
+            device_id,
+            backoff: RetryConfig::default(),
+            rules_refresh_interval: None,
+        }
+    }
+
+    /// 设置通知流断开后的重连退避参数
+    pub fn with_backoff(mut self, backoff: RetryConfig) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// 订阅期间按固定间隔重新获取服务器规则（不设置时只在每次订阅时获取）
+    pub fn with_rules_refresh_interval(mut self, interval: Duration) -> Self {
+        self.rules_refresh_interval = Some(interval);
+        self
+    }
+
+    /// 持续订阅变更通知，直到同步引擎关闭
+    pub async fn run<S>(&self, source: &S)
+    where
+        S: ChangeNotificationSource + RemoteChangeSource + RuleSource,
+    {
+        let mut attempt = 0;
+
+        while !self.sync_engine.is_closed() {
+            let subscription = self
+                .network
+                .execute_with_recovery(
+                    || async move {
+                        source.subscribe().await.map_err(|e| {
+                            ClientError::network(format!("订阅变更通知失败: {:#}", e), None)
+                        })
+                    },
+                    "订阅变更通知",
+                )
+                .await;
+
+            match subscription {
+                Ok(mut notifications) => {
+                    info!("已订阅远程变更通知");
+
+                    // 补上断开期间错过的规则和变更
+                    self.refresh_rules(source).await;
+                    self.pull(source).await;
+
+                    let mut rules_ticker = self.rules_refresh_interval.map(|interval| {
+                        let mut ticker = interval_at(Instant::now() + interval, interval);
+                        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                        ticker
+                    });
+                    loop {
+                        let notification = tokio::select! {
+                            notification = notifications.recv() => match notification {
+                                Some(notification) => notification,
+                                None => break,
+                            },
+                            _ = tick(&mut rules_ticker) => {
+                                self.refresh_rules(source).await;
+                                continue;
+                            }
+                        };
+                        attempt = 0;
+                        if !self.is_remote(&notification) {
+                            continue;
+                        }
+
+                        // 合并已到达的通知，一次拉取即可应用全部变更
+                        while let Ok(queued) = notifications.try_recv() {
+                            debug!("合并变更通知: {}", queued.file_path);
+                        }
+                        self.pull(source).await;
+                    }
+
+                    warn!("变更通知流已断开");
+                }
+                Err(e) => warn!("订阅变更通知失败: {}", e.user_message()),
+            }
+
+            let delay = self.backoff.calculate_delay(attempt);
+            attempt += 1;
+            info!("{} 毫秒后重新订阅变更通知", delay.as_millis());
+            sleep(delay).await;
+        }
+    }
+
+    /// 是否为其他设备产生的变更
+    fn is_remote(&self, notification: &ChangeNotification) -> bool {
+        if notification.device_id == self.device_id {
+            debug!("忽略本机产生的变更通知: {}", notification.file_path);
+            return false;
+        }
+
+        info!(
+            "收到远程变更通知: {} ({})",
+            notification.file_path, notification.change_type
+        );
+        true
+    }
+
+    /// 重新获取服务器同步规则（失败时保留当前规则）
+    async fn refresh_rules<S: RuleSource>(&self, source: &S) {
+        if let Err(e) = self.sync_engine.refresh_server_rules(source).await {
+            warn!("{:#}", e);
+        }
+    }
+
+    /// 拉取并应用远程变更
+    async fn pull<S: RemoteChangeSource>(&self, source: &S) {
+        apply_notified_changes(&self.sync_engine, source).await;
+    }
+}
+
+/// 等待下一次规则刷新（未设置刷新间隔时永不返回）
+async fn tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// 收到服务器通知后拉取并应用远程变更（失败的变更保留在游标之后，下次通知时重试）
+pub(crate) async fn apply_notified_changes<S: RemoteChangeSource>(
+    sync_engine: &SyncEngine,
+    source: &S,
+) {
+    match sync_engine.apply_remote_changes(source).await {
+        Ok(summary) if summary.failed_count > 0 => {
+            warn!("{} 个远程变更应用失败，稍后重试", summary.failed_count)
+        }
+        Ok(summary) => debug!("已应用 {} 个远程变更", summary.synced_count),
+        Err(e) => warn!("拉取远程变更失败: {:#}", e),
+    }
+}
+
+/// 在后台运行变更订阅器
+pub fn spawn_subscriber_task<S>(
+    subscriber: ChangeSubscriber,
+    source: Arc<S>,
+) -> tokio::task::JoinHandle<()>
+where
+    S: ChangeNotificationSource + RemoteChangeSource + RuleSource + 'static,
+{
+    tokio::spawn(async move { subscriber.run(source.as_ref()).await })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc_client::{DownloadFileData, FileChange, ServerRule};
+    use crate::network::NetworkStatus;
+    use crate::sync::test_support::create_engine;
+    use crate::transfer::TransferManager;
+    use std::sync::Mutex;
+
+    /// 模拟服务器：保存文件变更，并通过通知流推送给订阅者
+    #[derive(Default)]
+    struct MockStreamServer {
+        files: Mutex<Vec<(i64, String, Vec<u8>)>>,
+        downloads: Mutex<Vec<String>>,
+        streams: Mutex<Vec<mpsc::Sender<ChangeNotification>>>,
+        subscriptions: Mutex<usize>,
+        rule_fetches: Mutex<usize>,
+    }
+
+    impl MockStreamServer {
+        /// 保存另一台设备上传的文件，并向当前订阅者推送通知
+        async fn push(&self, version: i64, path: &str, content: &str, device_id: Uuid) {
+            self.files.lock().unwrap().push((
+                version,
+                path.to_string(),
+                content.as_bytes().to_vec(),
+            ));
+            let streams = self.streams.lock().unwrap().clone();
+            for stream in streams {
+                stream
+                    .send(ChangeNotification {
+                        file_path: path.to_string(),
+                        device_id,
+                        change_type: "modified".to_string(),
+                        timestamp: 0,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+
+        fn rule_fetches(&self) -> usize {
+            *self.rule_fetches.lock().unwrap()
+        }
+
+        /// 断开所有通知流
+        fn drop_streams(&self) {
+            self.streams.lock().unwrap().clear();
+        }
+
+        fn subscriptions(&self) -> usize {
+            *self.subscriptions.lock().unwrap()
+        }
+    }
+
+    impl ChangeNotificationSource for MockStreamServer {
+        async fn subscribe(&self) -> Result<mpsc::Receiver<ChangeNotification>> {
+            let (tx, rx) = mpsc::channel(16);
+            self.streams.lock().unwrap().push(tx);
+            *self.subscriptions.lock().unwrap() += 1;
+            Ok(rx)
+        }
+    }
+
+    impl RemoteChangeSource for MockStreamServer {
+        async fn changes_since(&self, since_version: i64) -> Result<Vec<FileChange>> {
+            Ok(self
+                .files
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(version, _, _)| *version > since_version)
+                .map(|(version, path, content)| FileChange {
+                    file_path: path.clone(),
+                    file_hash: TransferManager::calculate_hash(content).unwrap(),
+                    file_size: content.len() as u64,
+                    modified_at: 0,
+                    version: *version,
+                    is_deleted: false,
+                })
+                .collect())
+        }
+
+        async fn download_latest(&self, file_path: String) -> Result<DownloadFileData> {
+            self.downloads.lock().unwrap().push(file_path.clone());
+            let files = self.files.lock().unwrap();
+            let (version, _, content) = files
+                .iter()
+                .rev()
+                .find(|(_, path, _)| *path == file_path)
+                .unwrap();
+            Ok(DownloadFileData {
+                file_path,
+                file_hash: TransferManager::calculate_hash(content).unwrap(),
+                file_size: content.len() as u64,
+                content: content.clone(),
+                version: *version,
+                file_mode: None,
+                content_type: None,
+            })
+        }
+    }
+
+    impl RuleSource for MockStreamServer {
+        async fn list_rules(&self, _device_id: Uuid) -> Result<Vec<ServerRule>> {
+            *self.rule_fetches.lock().unwrap() += 1;
+            Ok(Vec::new())
+        }
+    }
+
+    /// 轮询直到条件成立（超时则测试失败）
+    async fn wait_until(mut condition: impl FnMut() -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition() {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("等待条件超时");
+    }
+
+    #[tokio::test]
+    async fn test_pushed_change_is_downloaded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let engine = Arc::new(create_engine(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+        ));
+        let network = Arc::new(NetworkRecoveryManager::new(
+            "http://localhost:50051".to_string(),
+            "http://localhost:3000".to_string(),
+            RetryConfig::default(),
+            5,
+            3,
+        ));
+        network.set_status(NetworkStatus::Online).await;
+
+        let device_id = Uuid::new_v4();
+        let subscriber = ChangeSubscriber::new(engine.clone(), network, device_id)
+            .with_backoff(RetryConfig::default().with_initial_delay_ms(10))
+            .with_rules_refresh_interval(Duration::from_millis(200));
+        let server = Arc::new(MockStreamServer::default());
+        let handle = spawn_subscriber_task(subscriber, server.clone());
+        wait_until(|| server.subscriptions() == 1).await;
+        wait_until(|| server.rule_fetches() >= 1).await;
+
+        // 其他设备的变更被下载到本地
+        let remote_file = claude_dir.join("agents").join("remote.md");
+        server
+            .push(1, "agents/remote.md", "from laptop", Uuid::new_v4())
+            .await;
+        wait_until(|| std::fs::read_to_string(&remote_file).is_ok_and(|c| c == "from laptop"))
+            .await;
+        assert_eq!(
+            *server.downloads.lock().unwrap(),
+            vec!["agents/remote.md".to_string()]
+        );
+        assert_eq!(engine.version_cursor(), 1);
+
+        // 通知流断开后重新订阅，继续接收变更
+        server.drop_streams();
+        wait_until(|| server.subscriptions() == 2).await;
+        server
+            .push(2, "agents/remote.md", "edited on laptop", Uuid::new_v4())
+            .await;
+        wait_until(|| std::fs::read_to_string(&remote_file).is_ok_and(|c| c == "edited on laptop"))
+            .await;
+
+        // 订阅期间定时重新获取规则，不拉取文件
+        let downloads = server.downloads.lock().unwrap().len();
+        let rule_fetches = server.rule_fetches();
+        wait_until(|| server.rule_fetches() > rule_fetches).await;
+        assert_eq!(server.downloads.lock().unwrap().len(), downloads);
+
+        handle.abort();
+    }
+}
@@ -1,17 +1,212 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
+use crate::audit::AuditEntry;
 use crate::config::ClientConfig;
-use crate::conflict::{ConflictResolver, ConflictType};
-use crate::rules::RuleEngine;
-use crate::transfer::TransferManager;
-use crate::watcher::{FileEvent, FileEventType, FileScanner};
+use crate::conflict::{ConflictResolver, ConflictType, ResolutionStrategy};
+use crate::connection_pool::ConnectionPool;
+use crate::content_hash::{hash_like, HashAlgorithm};
+use crate::control::SyncControl;
+use crate::error::ClientError;
+use crate::grpc_client::{DownloadFileData, FileChange, GrpcClient, ServerRule};
+use crate::hash_cache::{hash_cache_path, HashCache};
+use crate::monitoring::{MonitoringManager, OperationTimer};
+use crate::network::{
+    NetworkRecoveryManager, NetworkStatus, OfflineOperation, OfflineOperationHandler,
+};
+use crate::paths::SyncRoots;
+use crate::reporter::ChangeReporter;
+use crate::rules::{RuleEngine, SyncRule, IGNORE_FILE_NAME};
+use crate::transfer::{
+    detect_content_type, file_mode, set_file_mode, write_atomic, TransferManager, TransferProgress,
+    UploadRequest,
+};
+use crate::watcher::{file_size_skip_reason, FileEvent, FileEventType, FileScanner, SettleState};
+
+/// 远程变更来源（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+pub trait RemoteChangeSource: Send + Sync {
+    /// 获取版本游标之后的远程变更
+    fn changes_since(
+        &self,
+        since_version: i64,
+    ) -> impl Future<Output = Result<Vec<FileChange>>> + Send;
+
+    /// 下载文件的最新版本（路径为服务器上的相对路径）
+    fn download_latest(
+        &self,
+        file_path: String,
+    ) -> impl Future<Output = Result<DownloadFileData>> + Send;
+}
+
+impl RemoteChangeSource for GrpcClient {
+    fn changes_since(
+        &self,
+        since_version: i64,
+    ) -> impl Future<Output = Result<Vec<FileChange>>> + Send {
+        self.fetch_changes(since_version, Vec::new())
+    }
+
+    fn download_latest(
+        &self,
+        file_path: String,
+    ) -> impl Future<Output = Result<DownloadFileData>> + Send {
+        self.download_file(file_path, None)
+    }
+}
+
+/// 服务器同步规则来源（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+pub trait RuleSource: Send + Sync {
+    /// 获取适用于该设备的规则（全局规则和本设备专属规则）
+    fn list_rules(
+        &self,
+        device_id: uuid::Uuid,
+    ) -> impl Future<Output = Result<Vec<ServerRule>>> + Send;
+}
+
+impl RuleSource for GrpcClient {
+    fn list_rules(
+        &self,
+        device_id: uuid::Uuid,
+    ) -> impl Future<Output = Result<Vec<ServerRule>>> + Send {
+        GrpcClient::list_rules(self, device_id)
+    }
+}
+
+/// 查询服务器上文件的当前内容哈希（本地同步状态丢失时判断是否需要重新上传）
+///
+/// 同步引擎以 trait 对象持有，因此返回装箱的 future。
+pub trait RemoteHashLookup: Send + Sync {
+    /// 服务器相对路径对应的最新内容哈希（服务器上没有该文件时为 None）
+    fn remote_hash(&self, file_path: String) -> BoxFuture<'_, Result<Option<String>>>;
+}
+
+impl RemoteHashLookup for GrpcClient {
+    fn remote_hash(&self, file_path: String) -> BoxFuture<'_, Result<Option<String>>> {
+        Box::pin(async move {
+            let versions = self.get_file_history(file_path, 1).await?;
+            Ok(versions.into_iter().next().map(|version| version.file_hash))
+        })
+    }
+}
+
+/// 同步状态快照（落盘格式）
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncSnapshot {
+    /// 已应用的远程变更版本游标
+    version_cursor: i64,
+
+    /// 各文件的同步状态
+    states: Vec<FileSyncState>,
+
+    /// 尚未通知服务器的本地删除记录
+    #[serde(default)]
+    tombstones: Vec<Tombstone>,
+}
+
+/// 删除记录（墓碑）：本地已删除、等待通知服务器的文件
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tombstone {
+    /// 服务器上的相对路径
+    pub file_path: String,
+
+    /// 删除前最后一次同步的内容哈希
+    pub last_hash: Option<String>,
+
+    /// 删除时间
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// 兼容旧版本只保存状态列表的快照文件
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotFile {
+    Current(SyncSnapshot),
+    Legacy(Vec<FileSyncState>),
+}
+
+/// 文件相对于 Claude 目录的路径（目录之外的文件只保留文件名）
+fn claude_relative_path(claude_dir: &Path, file_path: &Path) -> PathBuf {
+    match file_path.strip_prefix(claude_dir) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => PathBuf::from(file_path.file_name().unwrap_or_default()),
+    }
+}
+
+/// 冲突副本路径（本地、远程），保持文件在 Claude 目录中的相对层级
+fn conflict_copy_paths(
+    conflict_dir: &Path,
+    claude_dir: &Path,
+    file_path: &Path,
+    timestamp: &str,
+) -> [PathBuf; 2] {
+    let base = conflict_dir.join(claude_relative_path(claude_dir, file_path));
+    let name = base
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    ["local", "remote"].map(|side| base.with_file_name(format!("{}.{}.{}", name, side, timestamp)))
+}
+
+/// 写入冲突标记的文件路径（与原文件同目录，扩展名替换为 `.conflict`）
+pub fn conflict_marker_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("conflict")
+}
+
+/// 是否为冲突标记文件（标记文件只在本地供解决冲突使用，自身不同步）
+pub fn is_conflict_marker(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "conflict")
+}
+
+/// keep_both 策略下远程版本的保存路径（与原文件同目录，如 `settings (remote).json`）
+pub fn keep_both_path(file_path: &Path) -> PathBuf {
+    let stem = file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match file_path.extension() {
+        Some(extension) => format!("{} (remote).{}", stem, extension.to_string_lossy()),
+        None => format!("{} (remote)", stem),
+    };
+    file_path.with_file_name(name)
+}
+
+/// 冲突解决后删除对应的 `.conflict` 标记文件（不存在时忽略）
+async fn remove_conflict_marker(file_path: &Path) {
+    let marker_path = conflict_marker_path(file_path);
+    match tokio::fs::remove_file(&marker_path).await {
+        Ok(()) => debug!("已删除冲突标记文件: {:?}", marker_path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("无法删除冲突标记文件 {:?}: {}", marker_path, e),
+    }
+}
+
+/// 远程删除的文件在回收目录中的路径：`<冲突目录>/trash/<相对路径>.<时间戳>`
+fn trash_path(
+    conflict_dir: &Path,
+    claude_dir: &Path,
+    file_path: &Path,
+    timestamp: &str,
+) -> PathBuf {
+    let mut path = conflict_dir
+        .join("trash")
+        .join(claude_relative_path(claude_dir, file_path))
+        .into_os_string();
+    path.push(format!(".{}", timestamp));
+    PathBuf::from(path)
+}
 
 /// 同步状态
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -26,6 +221,8 @@ pub enum SyncStatus {
     Failed,
     /// 冲突
     Conflict,
+    /// 已跳过（如文件大小超出范围）
+    Skipped,
 }
 
 /// 文件同步状态
@@ -48,6 +245,14 @@ pub struct FileSyncState {
 
     /// 错误消息（如果同步失败）
     pub error_message: Option<String>,
+
+    /// 记录状态时的文件大小
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    /// 记录状态时的修改时间
+    #[serde(default)]
+    pub modified: Option<DateTime<Utc>>,
 }
 
 /// 同步模式
@@ -61,13 +266,51 @@ pub enum SyncMode {
     Selective,
 }
 
+/// 手动解决冲突时的选择
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictChoice {
+    /// 保留本地版本并重新上传
+    KeepLocal,
+    /// 使用远程版本覆盖本地
+    KeepRemote,
+    /// 使用编辑后的内容并重新上传
+    Edited(String),
+    /// 暂不处理
+    Skip,
+}
+
+/// 强制同步方向（push / pull 命令）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForcedDirection {
+    /// 以本地为准上传
+    Push,
+    /// 以远程为准覆盖本地
+    Pull,
+}
+
+impl ForcedDirection {
+    /// 审计日志中记录的策略名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForcedDirection::Push => "force_push",
+            ForcedDirection::Pull => "force_pull",
+        }
+    }
+}
+
+/// 传输进度广播通道容量（订阅者落后超过该数量时丢弃旧事件）
+const PROGRESS_CHANNEL_CAPACITY: usize = 256;
+
+/// 文件等待稳定超时后的最多重试次数，之后直接上传当前内容（如持续追加的会话日志）
+const MAX_SETTLE_RETRIES: u32 = 5;
+
 /// 同步引擎
 pub struct SyncEngine {
     /// 客户端配置
     config: Arc<ClientConfig>,
 
-    /// 规则引擎
-    rule_engine: Arc<RuleEngine>,
+    /// 规则引擎（忽略文件变化时整体替换）
+    rule_engine: RwLock<Arc<RuleEngine>>,
 
     /// 传输管理器
     transfer_manager: Arc<TransferManager>,
@@ -78,11 +321,65 @@ pub struct SyncEngine {
     /// 文件同步状态缓存
     sync_states: Arc<tokio::sync::Mutex<HashMap<PathBuf, FileSyncState>>>,
 
+    /// 待推送的删除记录（按服务器相对路径索引）
+    tombstones: tokio::sync::Mutex<BTreeMap<String, Tombstone>>,
+
     /// 用户 ID
     user_id: uuid::Uuid,
 
     /// 设备 ID
     device_id: uuid::Uuid,
+
+    /// 演练模式（只记录计划执行的操作，不做任何写入）
+    dry_run: bool,
+
+    /// 监控管理器
+    monitoring: Option<MonitoringManager>,
+
+    /// 连接池
+    connection_pool: Option<Arc<ConnectionPool>>,
+
+    /// 是否已关闭
+    closed: AtomicBool,
+
+    /// 传输进度广播
+    progress_tx: broadcast::Sender<TransferProgress>,
+
+    /// 累计上传字节数（用于统计每轮同步的传输量）
+    uploaded_bytes: AtomicU64,
+
+    /// 累计下载字节数
+    downloaded_bytes: AtomicU64,
+
+    /// 已应用的远程变更版本游标（随状态快照持久化）
+    version_cursor: AtomicI64,
+
+    /// 暂停开关（由守护进程控制端口切换）
+    control: SyncControl,
+
+    /// 同步根目录（Claude 目录和附加监控目录）
+    roots: SyncRoots,
+
+    /// 上次加载规则时忽略文件的修改时间（文件不存在时为 None）
+    ignore_file_modified: std::sync::Mutex<Option<SystemTime>>,
+
+    /// 没有同步状态的文件上传前查询服务器哈希（为 None 时直接上传）
+    remote_hashes: Option<Arc<dyn RemoteHashLookup>>,
+
+    /// 本地文件哈希缓存（与状态快照一起加载和保存）
+    hash_cache: HashCache,
+
+    /// 网络恢复管理器（设置后服务器不可达期间的本地修改进入离线队列）
+    network: Option<Arc<NetworkRecoveryManager>>,
+
+    /// 上传完成的文件变更发送到批量上报器（为 None 时不上报）
+    change_reports: Option<mpsc::UnboundedSender<FileEvent>>,
+
+    /// 命令行的临时同步规则（重新加载规则时保留）
+    adhoc_rules: Vec<SyncRule>,
+
+    /// 从服务器获取的同步规则（重新加载规则时保留）
+    server_rules: RwLock<Vec<ServerRule>>,
 }
 
 impl SyncEngine {
@@ -96,16 +393,216 @@ impl SyncEngine {
         device_id: uuid::Uuid,
     ) -> Self {
         Self {
+            roots: config.sync_roots(),
+            ignore_file_modified: std::sync::Mutex::new(ignore_file_modified(
+                &config.sync.claude_dir,
+            )),
+            hash_cache: HashCache::new(hash_cache_path(&config.sync.state_file))
+                .with_algorithm(config.performance.hash_algorithm),
             config,
-            rule_engine,
+            rule_engine: RwLock::new(rule_engine),
             transfer_manager,
             conflict_resolver,
             sync_states: Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            tombstones: tokio::sync::Mutex::new(BTreeMap::new()),
             user_id,
             device_id,
+            dry_run: false,
+            monitoring: None,
+            connection_pool: None,
+            closed: AtomicBool::new(false),
+            progress_tx: broadcast::channel(PROGRESS_CHANNEL_CAPACITY).0,
+            uploaded_bytes: AtomicU64::new(0),
+            downloaded_bytes: AtomicU64::new(0),
+            version_cursor: AtomicI64::new(0),
+            control: SyncControl::new(),
+            remote_hashes: None,
+            network: None,
+            change_reports: None,
+            adhoc_rules: Vec::new(),
+            server_rules: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// 重新加载同步规则（配置规则和忽略文件）
+    pub fn reload_rules(&self) -> Result<()> {
+        *self.ignore_file_modified.lock().unwrap() =
+            ignore_file_modified(&self.config.sync.claude_dir);
+        let server_rules = self.server_rules.read().unwrap().clone();
+        let rule_engine = RuleEngine::from_config(&self.config)?
+            .with_server_rules(&server_rules)
+            .with_adhoc_rules(&self.adhoc_rules);
+        info!("同步规则已重新加载: {} 条", rule_engine.get_rules().len());
+        *self.rule_engine.write().unwrap() = Arc::new(rule_engine);
+        Ok(())
+    }
+
+    /// 从服务器获取本设备适用的同步规则并重新加载，返回服务器规则数量
+    ///
+    /// 服务器规则优先于本地配置规则和忽略文件，命令行临时规则仍然优先于服务器规则。
+    pub async fn refresh_server_rules<S: RuleSource>(&self, source: &S) -> Result<usize> {
+        let rules = source
+            .list_rules(self.device_id)
+            .await
+            .context("获取服务器同步规则失败")?;
+        let count = rules.len();
+        *self.server_rules.write().unwrap() = rules;
+        self.reload_rules()?;
+        info!("已获取服务器同步规则: {} 条", count);
+        Ok(count)
+    }
+
+    /// 忽略文件在上次加载后被修改、创建或删除时重新加载规则，返回是否重新加载
+    ///
+    /// 轮询模式下守护进程不接收文件事件，由定时任务每次同步前调用。
+    pub fn reload_rules_if_ignore_file_changed(&self) -> Result<bool> {
+        let modified = ignore_file_modified(&self.config.sync.claude_dir);
+        if *self.ignore_file_modified.lock().unwrap() == modified {
+            return Ok(false);
+        }
+        info!("忽略文件已变化，重新加载同步规则");
+        self.reload_rules()?;
+        Ok(true)
+    }
+
+    /// 按配置的算法计算内容哈希
+    fn hash_content(&self, content: &[u8]) -> String {
+        self.config.performance.hash_algorithm.hash(content)
+    }
+
+    /// 检查文件是否符合同步规则（配置规则按完整路径，规则引擎按相对路径）
+    ///
+    /// 命令行临时规则或服务器规则匹配时跳过按完整路径的配置规则检查，由规则引擎决定。
+    fn matches_sync_rules(&self, path: &Path) -> bool {
+        if is_conflict_marker(path) {
+            return false;
+        }
+
+        let file_type = crate::rules::detect_file_type(path);
+        let relative = self.roots.relative_path(path);
+        let rule_engine = self.rule_engine.read().unwrap().clone();
+        if !rule_engine.overrides_local_rules(relative)
+            && !self.config.apply_rules(path, &file_type)
+        {
+            return false;
+        }
+
+        rule_engine.should_sync(relative, Some(&file_type))
+    }
+
+    /// 本地文件在服务器上的路径（应用同步规则中的 rewrite 改写）
+    fn remote_path(&self, file_path: &Path) -> Result<String> {
+        let remote_path = self.roots.remote_path(file_path)?;
+        let rule_engine = self.rule_engine.read().unwrap().clone();
+        match rule_engine.rewrite_path(&remote_path) {
+            Some(rewritten) => {
+                crate::paths::validate_remote_path(&rewritten)
+                    .with_context(|| format!("规则改写后的路径无效: {}", rewritten))?;
+                Ok(rewritten)
+            }
+            None => Ok(remote_path),
+        }
+    }
+
+    /// 订阅上传/下载的逐文件传输进度
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<TransferProgress> {
+        self.progress_tx.subscribe()
+    }
+
+    /// 将传输进度转发到广播通道的回调
+    fn progress_callback(&self) -> impl Fn(TransferProgress) + Clone + Send + 'static {
+        let tx = self.progress_tx.clone();
+        move |progress| {
+            // 没有订阅者时发送失败，忽略即可
+            let _ = tx.send(progress);
+        }
+    }
+
+    /// 开始计时一个操作（未设置监控管理器时不计时）
+    fn start_operation(&self, operation: &str) -> Option<OperationTimer> {
+        self.monitoring
+            .as_ref()
+            .map(|monitoring| OperationTimer::new(monitoring.clone(), operation))
+    }
+
+    /// 设置监控管理器
+    pub fn with_monitoring(mut self, monitoring: MonitoringManager) -> Self {
+        self.monitoring = Some(monitoring);
+        self
+    }
+
+    /// 设置暂停开关
+    pub fn with_control(mut self, control: SyncControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// 暂停开关
+    pub fn control(&self) -> &SyncControl {
+        &self.control
+    }
+
+    /// 设置网络恢复管理器
+    ///
+    /// 需要同时将同步引擎设为管理器的离线操作处理器，网络恢复后才会重放离线期间的修改。
+    pub fn with_network(mut self, network: Arc<NetworkRecoveryManager>) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// 设置变更上报通道（接收端通常由 BatchReporter 消费）
+    pub fn with_change_reports(mut self, change_reports: mpsc::UnboundedSender<FileEvent>) -> Self {
+        self.change_reports = Some(change_reports);
+        self
+    }
+
+    /// 设置本次运行的临时同步规则（优先级高于配置规则，不写入配置）
+    pub fn with_adhoc_rules(mut self, rules: Vec<SyncRule>) -> Self {
+        let rule_engine = self.rule_engine.get_mut().unwrap();
+        *rule_engine = Arc::new((**rule_engine).clone().with_adhoc_rules(&rules));
+        self.adhoc_rules = rules;
+        self
+    }
+
+    /// 设置连接池
+    pub fn with_connection_pool(mut self, connection_pool: Arc<ConnectionPool>) -> Self {
+        self.connection_pool = Some(connection_pool);
+        self
+    }
+
+    /// 设置服务器哈希查询，本地没有同步状态的文件与服务器内容相同时不再上传
+    pub fn with_remote_hashes(mut self, remote_hashes: Arc<dyn RemoteHashLookup>) -> Self {
+        self.remote_hashes = Some(remote_hashes);
+        self
+    }
+
+    /// 查询服务器上文件的当前哈希（未设置查询或查询失败时返回 None）
+    async fn lookup_remote_hash(&self, file_path: &Path) -> Option<String> {
+        let lookup = self.remote_hashes.as_ref()?;
+        let remote_path = self.remote_path(file_path).ok()?;
+        match lookup.remote_hash(remote_path).await {
+            Ok(hash) => hash,
+            Err(e) => {
+                warn!(
+                    "查询服务器文件哈希失败，按新文件上传: {:?}: {:#}",
+                    file_path, e
+                );
+                None
+            }
         }
     }
 
+    /// 设置演练模式
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// 是否为演练模式
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
     /// 启动增量同步
     pub async fn start_incremental_sync(
         &self,
@@ -113,44 +610,392 @@ impl SyncEngine {
     ) -> Result<()> {
         info!("启动增量同步模式");
 
+        // 暂停期间收到的事件按顺序排队，恢复后补发
+        let mut queued: Vec<FileEvent> = Vec::new();
+
+        // 编辑器可能仍在写入：新建和修改事件先并发等待文件稳定，不阻塞其他文件的事件
+        let quiet_period = std::time::Duration::from_millis(self.config.sync.write_settle_window);
+        let max_wait = std::time::Duration::from_millis(self.config.sync.write_settle_max_wait);
+        let settle = |event: FileEvent, retries: u32| -> BoxFuture<'static, _> {
+            Box::pin(async move {
+                let state =
+                    crate::watcher::wait_for_file_stable(&event.path, quiet_period, max_wait).await;
+                (event, retries, state)
+            })
+        };
+        let mut settling: stream::FuturesUnordered<
+            BoxFuture<'static, (FileEvent, u32, SettleState)>,
+        > = stream::FuturesUnordered::new();
+        let mut settling_paths: HashSet<PathBuf> = HashSet::new();
+        let mut channel_closed = false;
+
         loop {
-            match event_rx.recv().await {
-                Some(event) => {
-                    if let Err(e) = self.handle_file_event(event).await {
-                        error!("处理文件事件失败: {}", e);
+            if channel_closed && settling.is_empty() {
+                break;
+            }
+
+            let unsettled = tokio::select! {
+                // 先补发排队事件，保证事件顺序
+                biased;
+
+                _ = self.control.resumed(), if !queued.is_empty() => {
+                    info!("同步已恢复，处理暂停期间的 {} 个事件", queued.len());
+                    let mut pending = Vec::new();
+                    for event in queued.drain(..) {
+                        pending.extend(self.dispatch_file_event(event).await);
                     }
+                    pending
                 }
-                None => {
-                    info!("文件事件通道已关闭");
-                    break;
+                Some((event, retries, state)) = settling.next(), if !settling.is_empty() => {
+                    // 超时后继续等待而不是丢弃事件，写入结束后不一定还有新的变更事件
+                    if state == SettleState::TimedOut
+                        && retries < MAX_SETTLE_RETRIES
+                        && !self.is_closed()
+                    {
+                        warn!("文件仍在写入，继续等待: {:?}", event.path);
+                        settling.push(settle(event, retries + 1));
+                        continue;
+                    }
+                    if state == SettleState::TimedOut {
+                        warn!("文件持续写入，上传当前内容: {:?}", event.path);
+                    }
+                    settling_paths.remove(&event.path);
+
+                    if self.control.is_paused() {
+                        queued.push(event);
+                    } else if !event.path.exists() {
+                        debug!("文件在等待稳定期间被删除: {:?}", event.path);
+                    } else {
+                        self.process_file_event(event).await;
+                    }
+                    continue;
+                }
+                event = event_rx.recv(), if !channel_closed => match event {
+                    Some(event) if self.control.is_paused() => {
+                        debug!("同步已暂停，事件排队: {:?}", event.path);
+                        queued.push(event);
+                        continue;
+                    }
+                    Some(event) => self.dispatch_file_event(event).await.into_iter().collect(),
+                    None => {
+                        info!("文件事件通道已关闭");
+                        channel_closed = true;
+                        continue;
+                    }
+                },
+            };
+
+            // 同一文件已在等待稳定时合并事件，稳定后上传的是最新内容
+            for event in unsettled {
+                if settling_paths.insert(event.path.clone()) {
+                    settling.push(settle(event, 0));
+                } else {
+                    debug!("文件正在等待稳定，合并事件: {:?}", event.path);
                 }
             }
         }
 
+        if !queued.is_empty() {
+            warn!("同步处于暂停状态，{} 个事件未处理", queued.len());
+        }
+
         Ok(())
     }
 
     /// 执行全量同步
     pub async fn run_full_sync(&self) -> Result<SyncSummary> {
-        info!("开始全量同步");
+        self.run_full_sync_since(None).await
+    }
+
+    /// 执行全量同步，只同步在 since 之后修改的文件（更早的文件计入跳过）
+    pub async fn run_full_sync_since(&self, since: Option<DateTime<Utc>>) -> Result<SyncSummary> {
+        self.run_full_sync_with_progress(since, |_| {}).await
+    }
+
+    /// 执行全量同步，扫描完成后和每处理完一个文件时调用 on_progress
+    pub async fn run_full_sync_with_progress<F>(
+        &self,
+        since: Option<DateTime<Utc>>,
+        on_progress: F,
+    ) -> Result<SyncSummary>
+    where
+        F: Fn(FullSyncProgress) + Sync,
+    {
+        match since {
+            Some(since) => info!("开始全量同步（仅 {} 之后修改的文件）", since),
+            None => info!("开始全量同步"),
+        }
 
         let scanner = FileScanner::new(
             self.config.sync.claude_dir.clone(),
             self.config.get_exclude_paths(),
             self.config.sync.exclude_patterns.clone(),
             self.config.sync.include_types.clone(),
-        );
+        )
+        .with_hash_algorithm(self.config.performance.hash_algorithm)
+        .with_exclude_dir_names(self.config.sync.exclude_dir_names.clone())
+        .with_additional_dirs(self.config.sync.additional_watch_dirs.clone())
+        .with_size_limits(
+            self.config.sync.min_file_size,
+            self.config.sync.max_file_size,
+        )
+        .with_follow_symlinks(self.config.sync.follow_symlinks)
+        .with_modified_since(since);
 
         // 扫描所有文件
-        let files = scanner.scan()?;
+        let (files, mut skipped) = scanner.scan_with_skipped()?;
+        let (files, excluded): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|path| self.matches_sync_rules(path));
+        skipped.extend(
+            excluded
+                .into_iter()
+                .map(|path| (path, "被同步规则排除".to_string())),
+        );
+        let (files, collided) = self.split_case_collisions(files);
 
         info!("全量同步: 找到 {} 个文件", files.len());
 
-        let mut summary = SyncSummary::default();
+        // 开始传输前检查预计上传量，超出上限时不上传任何文件
+        let estimate = self.estimate_upload(&files).await;
+        info!(
+            "预计上传 {} 个文件, {} 字节",
+            estimate.files, estimate.bytes
+        );
+        if let Some(limit) = self.config.sync.max_total_upload {
+            if estimate.bytes > limit {
+                return Err(ClientError::quota_exceeded(
+                    format!(
+                        "预计上传 {} 字节，超过上限 {} 字节 (sync.max_total_upload)",
+                        estimate.bytes, limit
+                    ),
+                    None,
+                    Some(limit),
+                )
+                .into());
+            }
+        }
+
+        let reads_before = self.hash_cache.file_reads();
+        let mut summary = self.sync_files_with_progress(files, &on_progress).await;
+        debug!(
+            "全量同步读取了 {} 个文件计算哈希，其余使用哈希缓存",
+            self.hash_cache.file_reads() - reads_before
+        );
+        summary.skipped_count += skipped.len();
+        summary.skipped.extend(skipped);
+        for (path, reason) in collided {
+            if self.config.sync.case_collision == "merge" {
+                summary.skipped_count += 1;
+                summary.skipped.push((path, reason));
+            } else {
+                summary.conflict_count += 1;
+                summary.conflicts.push(path.clone());
+                summary.errors.push((path, reason));
+            }
+        }
+        summary.sort_entries();
+
+        info!(
+            "全量同步完成: {} 成功, {} 失败, {} 冲突, {} 跳过",
+            summary.synced_count,
+            summary.failed_count,
+            summary.conflict_count,
+            summary.skipped_count
+        );
+
+        Ok(summary)
+    }
+
+    /// 估算同步这些文件需要上传的文件数和字节数
+    ///
+    /// 未同步过、上次未同步成功或大小/修改时间与同步状态不一致的文件计入估算。
+    pub async fn estimate_upload(&self, files: &[PathBuf]) -> UploadEstimate {
+        let mut estimate = UploadEstimate::default();
+        if !self.config.sync.direction.allows_push() {
+            return estimate;
+        }
+
+        let states = self.sync_states.lock().await;
+        for path in files {
+            let Some((size, modified)) = file_fingerprint(path) else {
+                continue;
+            };
+            let unchanged = states.get(path).is_some_and(|state| {
+                state.status == SyncStatus::Synced
+                    && state.size == Some(size)
+                    && state.modified == Some(modified)
+            });
+            if !unchanged {
+                estimate.files += 1;
+                estimate.bytes += size;
+            }
+        }
+
+        estimate
+    }
+
+    /// 拆出本地仅大小写不同的文件：每组保留排序后的第一个，其余文件返回原因
+    ///
+    /// 服务器按忽略大小写的规范路径存储，同组文件只能同步其中一个。
+    fn split_case_collisions(&self, files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<(PathBuf, String)>) {
+        let mut collided = Vec::new();
+        for group in crate::paths::find_case_collisions(&files) {
+            let kept = &group[0];
+            for path in &group[1..] {
+                warn!("路径仅大小写不同: {:?} 与 {:?}", path, kept);
+                collided.push((path.clone(), format!("与 {:?} 仅大小写不同", kept)));
+            }
+        }
+
+        let files = files
+            .into_iter()
+            .filter(|path| !collided.iter().any(|(collided, _)| collided == path))
+            .collect();
+        (files, collided)
+    }
+
+    /// 执行选择性同步，仅同步指定的文件或目录（相对路径基于 Claude 目录）
+    pub async fn run_selective_sync(&self, paths: Vec<PathBuf>) -> Result<SyncSummary> {
+        info!("开始选择性同步: {:?}", paths);
 
-        // 批量同步文件
-        for file_path in files {
-            match self.sync_file(&file_path).await {
+        let claude_dir = &self.config.sync.claude_dir;
+        let mut files = BTreeSet::new();
+
+        for path in paths {
+            // 含 `..` 的路径拼接后仍以 Claude 目录开头，前缀检查无法拦截
+            if path
+                .components()
+                .any(|component| matches!(component, Component::ParentDir))
+            {
+                warn!("路径包含 ..，跳过: {:?}", path);
+                continue;
+            }
+
+            let root = if path.is_absolute() {
+                path
+            } else {
+                claude_dir.join(path)
+            };
+
+            if self.roots.root_for(&root).is_none() {
+                warn!("路径不在同步目录中，跳过: {:?}", root);
+                continue;
+            }
+
+            if root.is_dir() {
+                let scanner = FileScanner::new(
+                    root,
+                    self.config.get_exclude_paths(),
+                    self.config.sync.exclude_patterns.clone(),
+                    self.config.sync.include_types.clone(),
+                )
+                .with_hash_algorithm(self.config.performance.hash_algorithm)
+                .with_exclude_dir_names(self.config.sync.exclude_dir_names.clone())
+                .with_follow_symlinks(self.config.sync.follow_symlinks);
+                files.extend(scanner.scan()?);
+            } else if root.is_file() {
+                if self.config.should_exclude(&root) {
+                    debug!("文件被排除，跳过: {:?}", root);
+                    continue;
+                }
+                files.insert(root);
+            } else {
+                warn!("路径不存在，跳过: {:?}", root);
+            }
+        }
+
+        // 应用同步规则
+        let files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| self.matches_sync_rules(path))
+            .collect();
+
+        info!("选择性同步: 找到 {} 个文件", files.len());
+
+        let summary = self.sync_files(files).await;
+
+        info!(
+            "选择性同步完成: {} 成功, {} 失败, {} 冲突",
+            summary.synced_count, summary.failed_count, summary.conflict_count
+        );
+
+        Ok(summary)
+    }
+
+    /// 批量同步文件并汇总结果
+    async fn sync_files(&self, files: Vec<PathBuf>) -> SyncSummary {
+        self.sync_files_with_progress(files, &|_| {}).await
+    }
+
+    /// 批量同步文件并汇总结果，每处理完一个文件报告一次进度
+    async fn sync_files_with_progress<F>(
+        &self,
+        mut files: Vec<PathBuf>,
+        on_progress: &F,
+    ) -> SyncSummary
+    where
+        F: Fn(FullSyncProgress) + Sync,
+    {
+        let mut summary = SyncSummary {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+
+        // 固定处理顺序，保证运行结果可复现
+        files.sort();
+        files.dedup();
+        on_progress(FullSyncProgress::Started { total: files.len() });
+
+        // 每轮批量同步计为一次同步，记录耗时和传输字节数
+        let mut timer = match &self.monitoring {
+            Some(monitoring) => Some(monitoring.record_sync_start().await),
+            None => None,
+        };
+        let uploaded_before = self.uploaded_bytes.load(Ordering::Relaxed);
+        let downloaded_before = self.downloaded_bytes.load(Ordering::Relaxed);
+
+        // 并发同步文件（并发数与上传并发一致），结果在汇总后统一排序
+        let concurrency = self.config.performance.max_concurrent_uploads.max(1);
+        let quota_exhausted = AtomicBool::new(false);
+        let quota_exhausted = &quota_exhausted;
+        let results: Vec<(PathBuf, Result<FileSyncState>)> = stream::iter(files)
+            .map(|file_path| async move {
+                // 服务器配额用尽后不再上传剩余文件，其同步状态保持不变
+                let result = if quota_exhausted.load(Ordering::SeqCst) {
+                    Ok(FileSyncState {
+                        path: file_path.clone(),
+                        local_hash: None,
+                        remote_hash: None,
+                        status: SyncStatus::Skipped,
+                        last_sync_time: None,
+                        error_message: Some(QUOTA_SKIP_REASON.to_string()),
+                        size: None,
+                        modified: None,
+                    })
+                } else {
+                    let result = self.sync_file(&file_path).await;
+                    if result.as_ref().err().and_then(quota_error).is_some() {
+                        warn!("服务器存储配额已用尽，停止上传剩余文件");
+                        quota_exhausted.store(true, Ordering::SeqCst);
+                    }
+                    result
+                };
+                on_progress(FullSyncProgress::FileProcessed {
+                    path: file_path.clone(),
+                });
+                (file_path, result)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        for (file_path, result) in results {
+            if let Some(timer) = timer.as_mut() {
+                timer.increment_file_count();
+            }
+
+            match result {
                 Ok(state) => match state.status {
                     SyncStatus::Synced => {
                         summary.synced_count += 1;
@@ -165,49 +1010,402 @@ impl SyncEngine {
                             .errors
                             .push((file_path, state.error_message.unwrap_or_default()));
                     }
+                    SyncStatus::Skipped => {
+                        summary.skipped_count += 1;
+                        summary
+                            .skipped
+                            .push((file_path, state.error_message.unwrap_or_default()));
+                    }
                     _ => {}
                 },
                 Err(e) => {
                     error!("同步文件失败 {:?}: {}", file_path, e);
                     summary.failed_count += 1;
+                    if let Some(quota) = quota_error(&e) {
+                        summary.errors.push((file_path, quota.user_message()));
+                    }
                 }
             }
         }
 
-        info!(
-            "全量同步完成: {} 成功, {} 失败, {} 冲突",
-            summary.synced_count, summary.failed_count, summary.conflict_count
-        );
+        if let Some(mut timer) = timer {
+            timer.add_upload_bytes(self.uploaded_bytes.load(Ordering::Relaxed) - uploaded_before);
+            timer.add_download_bytes(
+                self.downloaded_bytes.load(Ordering::Relaxed) - downloaded_before,
+            );
+            timer.complete(summary.failed_count == 0).await;
+        }
 
-        Ok(summary)
+        summary.sort_entries();
+        summary
     }
 
-    /// 处理文件事件
-    async fn handle_file_event(&self, event: FileEvent) -> Result<()> {
-        debug!(
-            "处理文件事件: {:?}, 类型: {:?}",
-            event.path, event.event_type
-        );
+    /// 从快照文件加载同步状态
+    pub async fn load_snapshot(&self) -> Result<usize> {
+        self.hash_cache.load().await?;
 
-        // 检查是否应该排除
-        if self.config.should_exclude(&event.path) {
-            debug!("文件被排除，跳过: {:?}", event.path);
-            return Ok(());
+        let state_file = &self.config.sync.state_file;
+        if !state_file.exists() {
+            return Ok(0);
         }
 
-        // 检查规则
-        let file_type = crate::rules::detect_file_type(&event.path);
-        if !self.config.apply_rules(&event.path, &file_type) {
-            debug!("文件不匹配同步规则，跳过: {:?}", event.path);
-            return Ok(());
+        let content = tokio::fs::read_to_string(state_file)
+            .await
+            .with_context(|| format!("无法读取同步状态快照: {:?}", state_file))?;
+
+        let snapshot = match serde_json::from_str(&content).context("无法解析同步状态快照")?
+        {
+            SnapshotFile::Current(snapshot) => snapshot,
+            SnapshotFile::Legacy(states) => SyncSnapshot {
+                version_cursor: 0,
+                states,
+                tombstones: Vec::new(),
+            },
+        };
+        self.version_cursor
+            .store(snapshot.version_cursor, Ordering::SeqCst);
+
+        let mut tombstones = self.tombstones.lock().await;
+        for tombstone in snapshot.tombstones {
+            tombstones.insert(tombstone.file_path.clone(), tombstone);
         }
+        drop(tombstones);
 
-        match event.event_type {
-            FileEventType::Create | FileEventType::Modify => {
-                self.sync_file(&event.path).await?;
-            }
-            FileEventType::Remove => {
-                self.handle_file_removal(&event.path).await?;
+        let count = snapshot.states.len();
+        let mut states = self.sync_states.lock().await;
+        for state in snapshot.states {
+            states.insert(state.path.clone(), state);
+        }
+
+        debug!("已加载 {} 条同步状态", count);
+
+        Ok(count)
+    }
+
+    /// 保存同步状态快照
+    pub async fn save_snapshot(&self) -> Result<()> {
+        let state_file = &self.config.sync.state_file;
+
+        // 确保状态目录存在
+        if let Some(parent) = state_file.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let snapshot = SyncSnapshot {
+            version_cursor: self.version_cursor(),
+            states: self.get_all_sync_states().await,
+            tombstones: self.pending_tombstones().await,
+        };
+        let content = serde_json::to_string_pretty(&snapshot).context("无法序列化同步状态快照")?;
+
+        write_atomic(state_file, content)
+            .await
+            .with_context(|| format!("无法写入同步状态快照: {:?}", state_file))?;
+        self.hash_cache.save().await?;
+
+        Ok(())
+    }
+
+    /// 关闭同步引擎：保存状态快照、写出性能指标并归还连接
+    ///
+    /// 重复调用不会产生任何效果。
+    pub async fn close(&self) -> Result<()> {
+        if self.closed.swap(true, Ordering::SeqCst) {
+            debug!("同步引擎已关闭，忽略重复调用");
+            return Ok(());
+        }
+
+        info!("关闭同步引擎");
+
+        if !self.dry_run {
+            self.save_snapshot().await?;
+
+            if let Some(monitoring) = &self.monitoring {
+                monitoring
+                    .save_metrics(&self.config.performance.metrics_file)
+                    .await?;
+            }
+        }
+
+        if let Some(pool) = &self.connection_pool {
+            pool.shutdown().await;
+        }
+
+        Ok(())
+    }
+
+    /// 是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// 校验本地文件是否与同步状态一致（只读，不修改同步状态）
+    ///
+    /// 逐个重新计算哈希，不依赖大小和修改时间。设置了服务器哈希查询时同时检查服务器上是否有新版本。
+    pub async fn verify(&self) -> Result<VerifyReport> {
+        let mut states: Vec<FileSyncState> =
+            self.sync_states.lock().await.values().cloned().collect();
+        states.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut report = VerifyReport::default();
+        for state in states {
+            if !state.path.is_file() {
+                report.missing.push(state.path);
+                continue;
+            }
+
+            let local_hash = TransferManager::calculate_file_hash(
+                &state.path,
+                self.config.performance.hash_algorithm,
+            )
+            .await?;
+            let local_changed = !state
+                .local_hash
+                .as_deref()
+                .is_some_and(|recorded| content_matches(&state.path, &local_hash, recorded));
+
+            // 未解决的冲突说明服务器已有新版本，否则与上次同步时记录的远程哈希比较
+            let server_changed = state.status == SyncStatus::Conflict
+                || match self.remote_hashes {
+                    Some(_) => self
+                        .lookup_remote_hash(&state.path)
+                        .await
+                        .is_some_and(|hash| state.remote_hash.as_ref() != Some(&hash)),
+                    None => false,
+                };
+
+            let newer = match (local_changed, server_changed) {
+                (false, false) => {
+                    report.verified += 1;
+                    continue;
+                }
+                (true, false) => NewerSide::Local,
+                (false, true) => NewerSide::Server,
+                (true, true) => NewerSide::Both,
+            };
+            report.mismatched.push(VerifyMismatch {
+                path: state.path,
+                recorded_hash: state.local_hash,
+                local_hash,
+                newer,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// 对比快照与磁盘状态，修复离线期间产生的偏差
+    ///
+    /// 大小或修改时间不一致时重新计算哈希，内容有变化的文件标记为等待同步。
+    pub async fn reconcile(&self) -> Result<ReconcileReport> {
+        info!("开始校对同步状态");
+
+        let scanner = FileScanner::new(
+            self.config.sync.claude_dir.clone(),
+            self.config.get_exclude_paths(),
+            self.config.sync.exclude_patterns.clone(),
+            self.config.sync.include_types.clone(),
+        )
+        .with_hash_algorithm(self.config.performance.hash_algorithm)
+        .with_exclude_dir_names(self.config.sync.exclude_dir_names.clone())
+        .with_additional_dirs(self.config.sync.additional_watch_dirs.clone())
+        .with_size_limits(
+            self.config.sync.min_file_size,
+            self.config.sync.max_file_size,
+        )
+        .with_follow_symlinks(self.config.sync.follow_symlinks);
+
+        let disk_files: HashSet<PathBuf> = scanner
+            .scan()?
+            .into_iter()
+            .filter(|path| self.matches_sync_rules(path))
+            .collect();
+
+        let mut report = ReconcileReport::default();
+
+        {
+            let mut states = self.sync_states.lock().await;
+
+            for (path, state) in states.iter_mut() {
+                let Some((size, modified)) = file_fingerprint(path) else {
+                    report.removed.push(path.clone());
+                    continue;
+                };
+
+                let unchanged = if state.size == Some(size) && state.modified == Some(modified) {
+                    true
+                } else {
+                    // 元数据不一致，重新计算哈希确认内容是否变化
+                    let hash = self.hash_cache.hash_file(path)?;
+                    let same = state
+                        .local_hash
+                        .as_deref()
+                        .is_some_and(|recorded| content_matches(path, &hash, recorded));
+                    state.local_hash = Some(hash);
+                    state.size = Some(size);
+                    state.modified = Some(modified);
+                    same
+                };
+
+                if !unchanged
+                    || matches!(
+                        state.status,
+                        SyncStatus::Pending | SyncStatus::Syncing | SyncStatus::Failed
+                    )
+                {
+                    state.status = SyncStatus::Pending;
+                    report.pending.push(path.clone());
+                } else {
+                    report.unchanged += 1;
+                }
+            }
+
+            // 快照中不存在的新文件
+            for path in disk_files {
+                if states.contains_key(&path) {
+                    continue;
+                }
+
+                let (size, modified) = match file_fingerprint(&path) {
+                    Some((size, modified)) => (Some(size), Some(modified)),
+                    None => (None, None),
+                };
+
+                states.insert(
+                    path.clone(),
+                    FileSyncState {
+                        path: path.clone(),
+                        local_hash: Some(self.hash_cache.hash_file(&path)?),
+                        remote_hash: None,
+                        status: SyncStatus::Pending,
+                        last_sync_time: None,
+                        error_message: None,
+                        size,
+                        modified,
+                    },
+                );
+                report.pending.push(path);
+            }
+        }
+
+        for path in &report.removed {
+            self.handle_file_removal(path).await?;
+        }
+
+        report.pending.sort();
+        report.removed.sort();
+
+        info!(
+            "校对完成: {} 个等待同步, {} 个已删除, {} 个未变化",
+            report.pending.len(),
+            report.removed.len(),
+            report.unchanged
+        );
+
+        Ok(report)
+    }
+
+    /// 同步所有等待中的文件
+    pub async fn sync_pending(&self) -> Result<SyncSummary> {
+        let mut pending: Vec<PathBuf> = self
+            .sync_states
+            .lock()
+            .await
+            .values()
+            .filter(|state| state.status == SyncStatus::Pending)
+            .map(|state| state.path.clone())
+            .collect();
+        pending.sort();
+
+        info!("同步等待中的文件: {} 个", pending.len());
+
+        Ok(self.sync_files(pending).await)
+    }
+
+    /// 分发文件事件
+    ///
+    /// 需要上传的新建和修改事件返回给调用方，等文件稳定后再由 process_file_event 处理。
+    async fn dispatch_file_event(&self, event: FileEvent) -> Option<FileEvent> {
+        let offline = match &self.network {
+            Some(network) => network.is_offline().await,
+            None => false,
+        };
+        let uploads = matches!(
+            event.event_type,
+            FileEventType::Create | FileEventType::Modify
+        );
+        if uploads && !offline && self.accepts_local_change(&event.path) {
+            return Some(event);
+        }
+
+        self.process_file_event(event).await;
+        None
+    }
+
+    /// 处理文件事件，服务器不可达时本地修改加入离线队列而不是逐个失败
+    async fn process_file_event(&self, event: FileEvent) {
+        let network = self.network.as_ref().filter(|_| {
+            matches!(
+                event.event_type,
+                FileEventType::Create | FileEventType::Modify
+            )
+        });
+
+        if let Some(network) = network {
+            if network.is_offline().await {
+                // 排除和规则检查在入队前进行，被忽略的文件在网络恢复后也不会上传
+                if self.accepts_local_change(&event.path) {
+                    self.queue_offline_upload(network, &event.path).await;
+                }
+                return;
+            }
+        }
+
+        let path = event.path.clone();
+        let Err(e) = self.handle_file_event(event).await else {
+            return;
+        };
+        match network {
+            // 标记为离线，之后的修改直接排队，直到网络恢复管理器重新连接
+            Some(network) if is_network_error(&e) => {
+                warn!("服务器不可达，本地修改将在网络恢复后同步: {:#}", e);
+                network.set_status(NetworkStatus::Offline).await;
+                self.queue_offline_upload(network, &path).await;
+            }
+            _ => error!("处理文件事件失败: {}", e),
+        }
+    }
+
+    /// 将本地修改加入离线队列
+    async fn queue_offline_upload(&self, network: &NetworkRecoveryManager, file_path: &Path) {
+        let operation = OfflineOperation::FileUpload {
+            path: file_path.to_string_lossy().into_owned(),
+            hash: self.hash_cache.hash_file(file_path).unwrap_or_default(),
+            size: std::fs::metadata(file_path).map(|m| m.len()).unwrap_or(0),
+        };
+        match network.queue_offline_operation(operation).await {
+            Ok(()) => debug!("服务器不可达，修改已加入离线队列: {:?}", file_path),
+            Err(e) => error!("无法将修改加入离线队列: {:?}: {:#}", file_path, e),
+        }
+    }
+
+    /// 处理文件事件
+    async fn handle_file_event(&self, event: FileEvent) -> Result<()> {
+        debug!(
+            "处理文件事件: {:?}, 类型: {:?}",
+            event.path, event.event_type
+        );
+
+        if !self.accepts_local_change(&event.path) {
+            return Ok(());
+        }
+
+        match event.event_type {
+            FileEventType::Create | FileEventType::Modify => {
+                self.sync_file(&event.path).await?;
+            }
+            FileEventType::Remove => {
+                self.handle_file_removal(&event.path).await?;
             }
             FileEventType::Rename => {
                 // TODO: 处理重命名
@@ -218,36 +1416,105 @@ impl SyncEngine {
         Ok(())
     }
 
+    /// 本地变更是否需要上报（排除规则、同步规则和同步方向）
+    ///
+    /// 忽略文件本身变化时先重新加载规则。
+    fn accepts_local_change(&self, file_path: &Path) -> bool {
+        // 检查是否应该排除
+        if self.config.should_exclude(file_path) {
+            debug!("文件被排除，跳过: {:?}", file_path);
+            return false;
+        }
+
+        // 忽略文件变化时重新加载规则
+        if file_path == self.config.sync.claude_dir.join(IGNORE_FILE_NAME) {
+            if let Err(e) = self.reload_rules() {
+                warn!("重新加载忽略文件失败，继续使用旧规则: {:#}", e);
+            }
+        }
+
+        // 检查规则
+        if !self.matches_sync_rules(file_path) {
+            debug!("文件不匹配同步规则，跳过: {:?}", file_path);
+            return false;
+        }
+
+        // 只拉取模式下本地修改和删除都不上报
+        if !self.config.sync.direction.allows_push() {
+            debug!("只拉取模式，忽略本地变更: {:?}", file_path);
+            return false;
+        }
+
+        true
+    }
+
     /// 同步单个文件
     pub async fn sync_file(&self, file_path: &Path) -> Result<FileSyncState> {
         info!("同步文件: {:?}", file_path);
 
-        // 计算本地哈希
-        let scanner = FileScanner::new(
-            self.config.sync.claude_dir.clone(),
-            self.config.get_exclude_paths(),
-            self.config.sync.exclude_patterns.clone(),
-            self.config.sync.include_types.clone(),
-        );
+        let timer = self.start_operation("sync_file");
+        let result = self.sync_file_inner(file_path).await;
+        if let Some(timer) = timer {
+            timer.complete().await;
+        }
+
+        result
+    }
+
+    /// 同步单个文件（不计时）
+    async fn sync_file_inner(&self, file_path: &Path) -> Result<FileSyncState> {
+        // 大小超出范围的文件不同步
+        let file_size = std::fs::metadata(file_path)
+            .with_context(|| format!("无法获取文件元信息: {:?}", file_path))?
+            .len();
+        if let Some(reason) = file_size_skip_reason(
+            file_size,
+            self.config.sync.min_file_size,
+            self.config.sync.max_file_size,
+        ) {
+            info!("跳过文件 {:?}: {}", file_path, reason);
+            return Ok(FileSyncState {
+                path: file_path.to_path_buf(),
+                local_hash: None,
+                remote_hash: None,
+                status: SyncStatus::Skipped,
+                last_sync_time: None,
+                error_message: Some(reason),
+                size: Some(file_size),
+                modified: None,
+            });
+        }
 
-        let local_hash = scanner.hash_file(file_path)?;
+        // 计算本地哈希（大小和修改时间未变化时使用缓存）
+        let local_hash = self.hash_cache.hash_file(file_path)?;
 
-        // 检查远程状态
-        // TODO: 调用 gRPC 客户端查询远程文件状态
-        let remote_hash: Option<String> = None;
+        // 远程哈希来自上次同步或已应用的远程变更
+        let previous = self.get_sync_state(file_path).await;
+        let remote_hash = match &previous {
+            Some(state) => state.remote_hash.clone(),
+            // 没有同步状态（如客户端重置后）时以服务器上的当前内容为准，相同则无需上传
+            None => self.lookup_remote_hash(file_path).await,
+        };
+        let remote_diverged = previous.is_some_and(|state| state.status == SyncStatus::Conflict);
 
         // 判断同步方向
         let sync_action = if let Some(remote) = &remote_hash {
-            if &local_hash == remote {
-                // 哈希相同，无需同步
-                return Ok(FileSyncState {
+            if content_matches(file_path, &local_hash, remote) {
+                // 内容相同，无需同步
+                let state = FileSyncState {
                     path: file_path.to_path_buf(),
                     local_hash: Some(local_hash),
                     remote_hash: remote_hash.clone(),
                     status: SyncStatus::Synced,
                     last_sync_time: Some(Utc::now()),
                     error_message: None,
-                });
+                    size: None,
+                    modified: None,
+                };
+                return Ok(self.update_sync_state(file_path, state).await);
+            } else if !remote_diverged {
+                // 远程自上次同步后未变化，只有本地修改
+                SyncAction::Upload
             } else {
                 // 哈希不同，需要检测冲突
                 SyncAction::NeedSync
@@ -257,9 +1524,22 @@ impl SyncEngine {
             SyncAction::Upload
         };
 
+        if !self.config.sync.direction.allows_push() {
+            debug!("只拉取模式，不上传本地修改: {:?}", file_path);
+            return Ok(FileSyncState {
+                path: file_path.to_path_buf(),
+                local_hash: Some(local_hash),
+                remote_hash,
+                status: SyncStatus::Skipped,
+                last_sync_time: None,
+                error_message: Some("只拉取模式，不上传本地修改".to_string()),
+                size: Some(file_size),
+                modified: None,
+            });
+        }
+
         match sync_action {
             SyncAction::Upload => self.upload_file(file_path, &local_hash).await,
-            SyncAction::Download => self.download_file(file_path).await,
             SyncAction::NeedSync => {
                 self.resolve_and_sync(file_path, &local_hash, remote_hash.as_ref().unwrap())
                     .await
@@ -271,52 +1551,80 @@ impl SyncEngine {
                 status: SyncStatus::Synced,
                 last_sync_time: Some(Utc::now()),
                 error_message: None,
+                size: None,
+                modified: None,
             }),
         }
     }
 
     /// 上传文件
     async fn upload_file(&self, file_path: &Path, local_hash: &str) -> Result<FileSyncState> {
-        info!("上传文件: {:?}", file_path);
-
-        // TODO: 调用传输管理器上传文件
-        // TODO: 调用 gRPC 客户端上报文件变更
-
-        let state = FileSyncState {
-            path: file_path.to_path_buf(),
-            local_hash: Some(local_hash.to_string()),
-            remote_hash: Some(local_hash.to_string()),
-            status: SyncStatus::Synced,
-            last_sync_time: Some(Utc::now()),
-            error_message: None,
-        };
-
-        // 更新状态缓存
-        self.update_sync_state(file_path, state.clone()).await;
+        if self.dry_run {
+            info!("[dry run] 将上传文件: {:?}", file_path);
+        } else {
+            info!("上传文件: {:?}", file_path);
 
-        Ok(state)
-    }
+            let metadata = tokio::fs::metadata(file_path)
+                .await
+                .with_context(|| format!("无法读取文件元数据: {:?}", file_path))?;
+            let remote_path = self.remote_path(file_path)?;
+            let request = UploadRequest {
+                file_path: file_path.to_path_buf(),
+                remote_path: remote_path.clone(),
+                user_id: self.user_id,
+                device_id: self.device_id,
+                file_hash: local_hash.to_string(),
+                file_size: metadata.len(),
+                upload_id: None,
+                file_mode: if self.config.sync.preserve_mode {
+                    file_mode(&metadata)
+                } else {
+                    None
+                },
+                content_type: detect_content_type(file_path).await?.to_string(),
+                base_hash: self
+                    .get_sync_state(file_path)
+                    .await
+                    .and_then(|state| state.remote_hash),
+            };
+            let timer = self.start_operation("upload_file");
+            let progress = self
+                .transfer_manager
+                .upload_file(request, self.progress_callback())
+                .await;
+            if let Some(timer) = timer {
+                timer.complete().await;
+            }
+            self.uploaded_bytes
+                .fetch_add(progress?.transferred_bytes, Ordering::Relaxed);
 
-    /// 下载文件
-    async fn download_file(&self, file_path: &Path) -> Result<FileSyncState> {
-        info!("下载文件: {:?}", file_path);
+            // 删除后又重新创建的文件不再需要通知删除
+            self.tombstones.lock().await.remove(&remote_path);
 
-        // TODO: 调用传输管理器下载文件
-        // TODO: 重新计算本地哈希
+            // 上报器已停止时忽略，文件内容已经上传
+            if let Some(change_reports) = &self.change_reports {
+                let _ = change_reports.send(FileEvent {
+                    path: file_path.to_path_buf(),
+                    event_type: FileEventType::Modify,
+                    timestamp: Utc::now(),
+                    is_dir: false,
+                });
+            }
+        }
 
         let state = FileSyncState {
             path: file_path.to_path_buf(),
-            local_hash: None,
-            remote_hash: None,
+            local_hash: Some(local_hash.to_string()),
+            remote_hash: Some(local_hash.to_string()),
             status: SyncStatus::Synced,
             last_sync_time: Some(Utc::now()),
             error_message: None,
+            size: None,
+            modified: None,
         };
 
         // 更新状态缓存
-        self.update_sync_state(file_path, state.clone()).await;
-
-        Ok(state)
+        Ok(self.update_sync_state(file_path, state).await)
     }
 
     /// 解决冲突并同步
@@ -332,6 +1640,10 @@ impl SyncEngine {
         let local_content = tokio::fs::read_to_string(file_path).await?;
         let remote_content = String::new(); // TODO: 从远程下载
 
+        // 在解决冲突前保留两端的副本
+        self.save_conflict_copies(file_path, &local_content, &remote_content)
+            .await?;
+
         // 尝试自动合并
         let merge_result = self.conflict_resolver.resolve(
             file_path,
@@ -344,15 +1656,41 @@ impl SyncEngine {
         match merge_result {
             crate::conflict::MergeResult::Merged(merged_content) => {
                 // 写入合并后的内容
-                tokio::fs::write(file_path, merged_content).await?;
+                if self.dry_run {
+                    info!("[dry run] 将写入自动合并结果: {:?}", file_path);
+                } else {
+                    write_atomic(file_path, merged_content).await?;
+                }
 
                 // 重新上传
                 self.upload_file(file_path, local_hash).await
             }
+            crate::conflict::MergeResult::KeepBoth => {
+                // 此路径拿不到远程内容，不能写出并上传空的远程副本，改为记录冲突由用户解决
+                warn!(
+                    "无法获取远程内容，不保留两个版本，标记为冲突: {:?}",
+                    file_path
+                );
+                let state = FileSyncState {
+                    path: file_path.to_path_buf(),
+                    local_hash: Some(local_hash.to_string()),
+                    remote_hash: Some(remote_hash.to_string()),
+                    status: SyncStatus::Conflict,
+                    last_sync_time: Some(Utc::now()),
+                    error_message: Some("存在未解决的冲突".to_string()),
+                    size: None,
+                    modified: None,
+                };
+                Ok(self.update_sync_state(file_path, state).await)
+            }
             crate::conflict::MergeResult::Conflict(conflict_content) => {
                 // 写入冲突标记
-                let conflict_path = file_path.with_extension("conflict");
-                tokio::fs::write(&conflict_path, conflict_content).await?;
+                let conflict_path = conflict_marker_path(file_path);
+                if self.dry_run {
+                    info!("[dry run] 将写入冲突文件: {:?}", conflict_path);
+                } else {
+                    write_atomic(&conflict_path, conflict_content).await?;
+                }
 
                 let state = FileSyncState {
                     path: file_path.to_path_buf(),
@@ -361,12 +1699,12 @@ impl SyncEngine {
                     status: SyncStatus::Conflict,
                     last_sync_time: Some(Utc::now()),
                     error_message: Some("存在未解决的冲突".to_string()),
+                    size: None,
+                    modified: None,
                 };
 
                 // 更新状态缓存
-                self.update_sync_state(file_path, state.clone()).await;
-
-                Ok(state)
+                Ok(self.update_sync_state(file_path, state).await)
             }
             _ => {
                 // 其他结果，使用默认策略
@@ -376,7 +1714,11 @@ impl SyncEngine {
 
                 match default_result {
                     crate::conflict::MergeResult::Merged(content) => {
-                        tokio::fs::write(file_path, content).await?;
+                        if self.dry_run {
+                            info!("[dry run] 将按默认策略写入: {:?}", file_path);
+                        } else {
+                            write_atomic(file_path, content).await?;
+                        }
                         self.upload_file(file_path, local_hash).await
                     }
                     _ => Ok(FileSyncState {
@@ -386,87 +1728,3829 @@ impl SyncEngine {
                         status: SyncStatus::Conflict,
                         last_sync_time: Some(Utc::now()),
                         error_message: Some("使用默认策略后仍存在冲突".to_string()),
+                        size: None,
+                        modified: None,
                     }),
                 }
             }
         }
     }
 
-    /// 处理文件删除
-    async fn handle_file_removal(&self, file_path: &Path) -> Result<()> {
-        info!("处理文件删除: {:?}", file_path);
+    /// 按 keep_conflict_copy 配置将本地和远程版本各保存一份到冲突目录
+    ///
+    /// 副本保持文件相对于 Claude 目录的层级，命名为 `<文件名>.local.<时间戳>` 和
+    /// `<文件名>.remote.<时间戳>`。返回写入的副本路径。
+    async fn save_conflict_copies(
+        &self,
+        file_path: &Path,
+        local_content: &str,
+        remote_content: &str,
+    ) -> Result<Vec<PathBuf>> {
+        if !self.config.conflict.keep_conflict_copy {
+            return Ok(Vec::new());
+        }
 
-        // TODO: 调用 gRPC 客户端通知服务器文件已删除
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let copies = conflict_copy_paths(
+            &self.config.conflict.conflict_dir,
+            &self.config.sync.claude_dir,
+            file_path,
+            &timestamp,
+        );
 
-        // 从状态缓存中移除
-        let mut states = self.sync_states.lock().await;
-        states.remove(file_path);
+        if self.dry_run {
+            info!("[dry run] 将保存冲突副本: {:?}", copies);
+            return Ok(Vec::new());
+        }
 
-        Ok(())
-    }
+        if let Some(parent) = copies[0].parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("无法创建冲突目录: {:?}", parent))?;
+        }
 
-    /// 更新同步状态
-    async fn update_sync_state(&self, file_path: &Path, state: FileSyncState) {
-        let mut states = self.sync_states.lock().await;
-        states.insert(file_path.to_path_buf(), state);
-    }
+        for (path, content) in copies.iter().zip([local_content, remote_content]) {
+            write_atomic(path, content).await?;
+        }
 
-    /// 获取同步状态
-    pub async fn get_sync_state(&self, file_path: &Path) -> Option<FileSyncState> {
-        let states = self.sync_states.lock().await;
-        states.get(file_path).cloned()
+        info!("已保存冲突副本: {:?}", copies);
+
+        Ok(copies.to_vec())
     }
 
-    /// 获取所有同步状态
-    pub async fn get_all_sync_states(&self) -> Vec<FileSyncState> {
-        let states = self.sync_states.lock().await;
-        states.values().cloned().collect()
+    /// 当前的远程变更版本游标
+    pub fn version_cursor(&self) -> i64 {
+        self.version_cursor.load(Ordering::SeqCst)
     }
-}
 
-/// 同步操作
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum SyncAction {
-    /// 上传
-    Upload,
-    /// 下载
-    Download,
-    /// 需要同步（可能冲突）
-    NeedSync,
-    /// 无需操作
-    NoAction,
-}
+    /// 拉取游标之后的远程变更并应用到本地
+    ///
+    /// 游标只推进到第一个应用失败的变更之前，失败的变更会在下次拉取时重试。
+    pub async fn apply_remote_changes<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+    ) -> Result<SyncSummary> {
+        if !self.config.sync.direction.allows_pull() {
+            debug!("只上传模式，跳过远程变更");
+            return Ok(SyncSummary {
+                dry_run: self.dry_run,
+                ..Default::default()
+            });
+        }
 
-/// 同步摘要
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct SyncSummary {
-    /// 成功同步的文件数
-    pub synced_count: usize,
+        let since = self.version_cursor();
+        let mut changes = source
+            .changes_since(since)
+            .await
+            .context("获取远程变更失败")?;
+        changes.retain(|change| change.version > since);
+        changes.sort_by_key(|change| change.version);
 
-    /// 失败的文件数
-    pub failed_count: usize,
+        // 同一文件的多个版本只应用最新的一个：下载得到的总是最新内容，
+        // 逐个应用时旧版本的哈希校验必然失败，游标会一直停在旧版本之前
+        let latest: HashMap<String, i64> = changes
+            .iter()
+            .map(|change| (change.file_path.clone(), change.version))
+            .collect();
+        changes.retain(|change| latest.get(&change.file_path) == Some(&change.version));
 
-    /// 冲突的文件数
-    pub conflict_count: usize,
+        info!("获取到 {} 个远程变更（游标: {}）", changes.len(), since);
 
-    /// 冲突文件列表
-    pub conflicts: Vec<PathBuf>,
+        let mut summary = SyncSummary {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        let mut cursor = since;
+        let mut blocked = false;
 
-    /// 错误列表
-    pub errors: Vec<(PathBuf, String)>,
-}
+        for change in changes {
+            match self.apply_remote_change(source, &change).await {
+                Ok(state) => match state.status {
+                    SyncStatus::Conflict => {
+                        summary.conflict_count += 1;
+                        summary.conflicts.push(state.path);
+                    }
+                    _ => summary.synced_count += 1,
+                },
+                Err(e) => {
+                    error!("应用远程变更失败 {}: {:#}", change.file_path, e);
+                    summary.failed_count += 1;
+                    summary
+                        .errors
+                        .push((PathBuf::from(&change.file_path), format!("{:#}", e)));
+                    blocked = true;
+                }
+            }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+            if !blocked {
+                cursor = change.version;
+            }
+        }
+
+        if !self.dry_run {
+            self.version_cursor.store(cursor, Ordering::SeqCst);
+        }
+
+        summary.sort_entries();
+        Ok(summary)
+    }
+
+    /// 应用单个远程变更：本地未修改时下载覆盖，两端都修改时标记为冲突
+    async fn apply_remote_change<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        change: &FileChange,
+    ) -> Result<FileSyncState> {
+        let remote_path = self.roots.remote_path(Path::new(&change.file_path))?;
+        let mut file_path = self.roots.local_path(&remote_path)?;
+
+        // 另一台设备上的路径可能只在大小写上与本地不同（如 Agents/ 与 agents/）
+        let (root, relative) = self.roots.split_remote(&remote_path);
+        if let Some(existing) = crate::paths::find_case_variant(&root.path, relative) {
+            if self.config.sync.case_collision == "merge" {
+                info!(
+                    "远程路径 {} 与本地 {:?} 仅大小写不同，合并到本地路径",
+                    remote_path, existing
+                );
+                file_path = existing;
+            } else {
+                warn!(
+                    "远程路径 {} 与本地 {:?} 仅大小写不同",
+                    remote_path, existing
+                );
+                let state = FileSyncState {
+                    path: file_path.clone(),
+                    local_hash: None,
+                    remote_hash: Some(change.file_hash.clone()),
+                    status: SyncStatus::Conflict,
+                    last_sync_time: Some(Utc::now()),
+                    error_message: Some(format!("与本地文件 {:?} 仅大小写不同", existing)),
+                    size: Some(change.file_size),
+                    modified: None,
+                };
+                return Ok(self.update_sync_state(&file_path, state).await);
+            }
+        }
+
+        let local_content = if file_path.exists() {
+            Some(
+                tokio::fs::read(&file_path)
+                    .await
+                    .with_context(|| format!("无法读取文件: {:?}", file_path))?,
+            )
+        } else {
+            None
+        };
+        let local_hash = local_content
+            .as_deref()
+            .map(|content| self.hash_content(content));
+
+        if change.is_deleted {
+            return self
+                .apply_remote_deletion(&file_path, &remote_path, local_hash)
+                .await;
+        }
+
+        if local_hash.is_none() && self.tombstones.lock().await.contains_key(&remote_path) {
+            return self
+                .apply_remote_edit_of_deleted(source, change, &file_path, &remote_path)
+                .await;
+        }
+
+        let mut state = FileSyncState {
+            path: file_path.clone(),
+            local_hash: Some(change.file_hash.clone()),
+            remote_hash: Some(change.file_hash.clone()),
+            status: SyncStatus::Synced,
+            last_sync_time: Some(Utc::now()),
+            error_message: None,
+            size: Some(change.file_size),
+            modified: None,
+        };
+
+        // 按各自哈希的算法比较内容，切换哈希算法后未修改的文件不会被当作冲突
+        let same_as = |hash: &str| {
+            local_content
+                .as_deref()
+                .is_some_and(|content| hash_like(hash, content) == hash)
+        };
+        if same_as(&change.file_hash) {
+            debug!("远程变更与本地内容一致: {:?}", file_path);
+            return Ok(self.update_sync_state(&file_path, state).await);
+        }
+
+        let last_synced_hash = self
+            .get_sync_state(&file_path)
+            .await
+            .and_then(|state| state.local_hash);
+        if local_hash.is_some() && !last_synced_hash.as_deref().is_some_and(same_as) {
+            let local_newer = match self.conflict_resolver.strategy_for(&file_path) {
+                ResolutionStrategy::KeepBoth => {
+                    return self
+                        .keep_both_versions(source, change, &file_path, remote_path, local_hash)
+                        .await;
+                }
+                ResolutionStrategy::KeepNewer => {
+                    crate::conflict::local_is_newer(&file_path, remote_modified_time(change))
+                }
+                _ => None,
+            };
+            match local_newer {
+                Some(true) => {
+                    // 本地较新，保留本地修改并等待上传
+                    info!("本地版本较新，保留本地修改: {:?}", file_path);
+                    state.local_hash = local_hash;
+                    state.status = SyncStatus::Pending;
+                    return Ok(self.update_sync_state(&file_path, state).await);
+                }
+                Some(false) => info!("远程版本较新，覆盖本地修改: {:?}", file_path),
+                None => {
+                    warn!("本地和远程均有修改: {:?}", file_path);
+                    state.local_hash = local_hash;
+                    state.status = SyncStatus::Conflict;
+                    state.error_message = Some("本地和远程均有修改".to_string());
+                    return Ok(self.update_sync_state(&file_path, state).await);
+                }
+            }
+        }
+
+        if self.dry_run {
+            info!("[dry run] 将下载远程变更: {:?}", file_path);
+            return Ok(state);
+        }
+
+        info!("下载远程变更: {:?} (版本 {})", file_path, change.version);
+        let data = self
+            .download_remote_change(source, change, remote_path)
+            .await?;
+        self.write_remote_content(&file_path, &data).await?;
+
+        Ok(self.update_sync_state(&file_path, state).await)
+    }
+
+    /// 下载远程变更对应的文件内容并校验哈希
+    async fn download_remote_change<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        change: &FileChange,
+        remote_path: String,
+    ) -> Result<DownloadFileData> {
+        let data = source.download_latest(remote_path).await?;
+        TransferManager::verify_download(
+            Path::new(&change.file_path),
+            &data.content,
+            &change.file_hash,
+        )?;
+
+        self.downloaded_bytes
+            .fetch_add(data.content.len() as u64, Ordering::Relaxed);
+        Ok(data)
+    }
+
+    /// 写入下载的远程内容（自动创建父目录，启用 preserve_mode 时恢复权限位）
+    async fn write_remote_content(&self, file_path: &Path, data: &DownloadFileData) -> Result<()> {
+        if let Some(parent) = file_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("无法创建目录: {:?}", parent))?;
+        }
+        write_atomic(file_path, &data.content).await?;
+        self.transfer_manager
+            .remember_base(file_path, &data.content)
+            .await;
+
+        match data.file_mode {
+            Some(mode) if self.config.sync.preserve_mode => set_file_mode(file_path, mode).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// 按 keep_both 策略解决本地和远程均有修改的冲突
+    ///
+    /// 本地文件保持不变，远程版本写入 `keep_both_path` 指向的副本。两个文件都记为待上传：
+    /// 本地版本覆盖服务器上的版本，远程副本作为新文件同步到其他设备。
+    async fn keep_both_versions<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        change: &FileChange,
+        file_path: &Path,
+        remote_path: String,
+        local_hash: Option<String>,
+    ) -> Result<FileSyncState> {
+        let copy_path = keep_both_path(file_path);
+        let state = FileSyncState {
+            path: file_path.to_path_buf(),
+            local_hash,
+            remote_hash: Some(change.file_hash.clone()),
+            status: SyncStatus::Pending,
+            last_sync_time: Some(Utc::now()),
+            error_message: Some(format!("已保留两个版本，远程版本保存为 {:?}", copy_path)),
+            size: None,
+            modified: None,
+        };
+
+        if self.dry_run {
+            info!("[dry run] 将保留两个版本，远程版本写入: {:?}", copy_path);
+            return Ok(state);
+        }
+
+        let data = self
+            .download_remote_change(source, change, remote_path)
+            .await?;
+        write_atomic(&copy_path, &data.content).await?;
+        info!("保留两个版本，远程版本已保存为: {:?}", copy_path);
+
+        let copy_state = FileSyncState {
+            path: copy_path.clone(),
+            local_hash: Some(change.file_hash.clone()),
+            remote_hash: None,
+            status: SyncStatus::Pending,
+            last_sync_time: Some(Utc::now()),
+            error_message: None,
+            size: None,
+            modified: None,
+        };
+        self.update_sync_state(&copy_path, copy_state).await;
+
+        Ok(self.update_sync_state(file_path, state).await)
+    }
+
+    /// 应用远程删除
+    ///
+    /// 本地文件自上次同步后未修改时直接删除（启用 keep_conflict_copy 时移入回收目录），
+    /// 本地有修改时按 ModifyDelete 冲突交给冲突解决器处理。启用 safe_delete 时，
+    /// 本地内容与上次同步的哈希不一致的文件不会被删除，冲突解决器选择删除时也记为冲突。
+    async fn apply_remote_deletion(
+        &self,
+        file_path: &Path,
+        remote_path: &str,
+        local_hash: Option<String>,
+    ) -> Result<FileSyncState> {
+        let mut state = FileSyncState {
+            path: file_path.to_path_buf(),
+            local_hash: None,
+            remote_hash: None,
+            status: SyncStatus::Synced,
+            last_sync_time: Some(Utc::now()),
+            error_message: None,
+            size: None,
+            modified: None,
+        };
+
+        if let Some(local_hash) = local_hash {
+            let last_synced_hash = self
+                .get_sync_state(file_path)
+                .await
+                .and_then(|state| state.local_hash);
+
+            let unchanged = last_synced_hash
+                .as_deref()
+                .is_some_and(|recorded| content_matches(file_path, &local_hash, recorded));
+            if !unchanged {
+                let local_content = tokio::fs::read(file_path)
+                    .await
+                    .with_context(|| format!("无法读取文件: {:?}", file_path))?;
+                let local_content = String::from_utf8_lossy(&local_content);
+
+                match self.conflict_resolver.resolve(
+                    file_path,
+                    &local_content,
+                    "",
+                    None,
+                    ConflictType::ModifyDelete,
+                )? {
+                    crate::conflict::MergeResult::Merged(content) if !content.is_empty() => {
+                        info!("保留本地修改，忽略远程删除: {:?}", file_path);
+                        state.local_hash = Some(local_hash);
+                        state.status = SyncStatus::Pending;
+                        state.error_message = Some("远程已删除，等待重新上传本地修改".to_string());
+                        return Ok(self.update_sync_state(file_path, state).await);
+                    }
+                    crate::conflict::MergeResult::Merged(_) if !self.config.sync.safe_delete => {}
+                    _ => {
+                        warn!("本地修改与远程删除冲突: {:?}", file_path);
+                        state.local_hash = Some(local_hash);
+                        state.status = SyncStatus::Conflict;
+                        state.error_message = Some("本地修改与远程删除冲突".to_string());
+                        return Ok(self.update_sync_state(file_path, state).await);
+                    }
+                }
+            }
+
+            if self.dry_run {
+                info!("[dry run] 将删除本地文件: {:?}", file_path);
+                return Ok(state);
+            }
+            self.remove_local_file(file_path).await?;
+        } else {
+            debug!("远程删除的文件本地已不存在: {:?}", file_path);
+        }
+
+        if !self.dry_run {
+            self.sync_states.lock().await.remove(file_path);
+            self.tombstones.lock().await.remove(remote_path);
+        }
+
+        Ok(state)
+    }
+
+    /// 远程修改了本地已删除（尚未通知服务器）的文件，按 ModifyDelete 冲突处理
+    async fn apply_remote_edit_of_deleted<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        change: &FileChange,
+        file_path: &Path,
+        remote_path: &str,
+    ) -> Result<FileSyncState> {
+        let mut state = FileSyncState {
+            path: file_path.to_path_buf(),
+            local_hash: None,
+            remote_hash: Some(change.file_hash.clone()),
+            status: SyncStatus::Synced,
+            last_sync_time: Some(Utc::now()),
+            error_message: None,
+            size: Some(change.file_size),
+            modified: None,
+        };
+
+        if self.dry_run {
+            info!("[dry run] 远程修改与本地删除冲突: {:?}", file_path);
+            state.status = SyncStatus::Conflict;
+            return Ok(state);
+        }
+
+        let data = self
+            .download_remote_change(source, change, remote_path.to_string())
+            .await?;
+        let remote_content = String::from_utf8_lossy(&data.content);
+
+        match self.conflict_resolver.resolve(
+            file_path,
+            "",
+            &remote_content,
+            None,
+            ConflictType::ModifyDelete,
+        )? {
+            crate::conflict::MergeResult::Merged(merged) if merged.is_empty() => {
+                // 保留本地删除，墓碑稍后推送到服务器
+                info!("保留本地删除，忽略远程修改: {:?}", file_path);
+                state.status = SyncStatus::Pending;
+                state.error_message = Some("本地已删除，等待通知服务器".to_string());
+                Ok(state)
+            }
+            crate::conflict::MergeResult::Merged(_) => {
+                info!("恢复本地已删除的文件: {:?}", file_path);
+                self.tombstones.lock().await.remove(remote_path);
+                self.write_remote_content(file_path, &data).await?;
+                state.local_hash = Some(change.file_hash.clone());
+                Ok(self.update_sync_state(file_path, state).await)
+            }
+            _ => {
+                // 不再推送删除，避免覆盖远程修改；远程版本保存到冲突目录供用户处理
+                warn!("远程修改与本地删除冲突: {:?}", file_path);
+                self.tombstones.lock().await.remove(remote_path);
+                self.save_conflict_copies(file_path, "", &remote_content)
+                    .await?;
+                state.status = SyncStatus::Conflict;
+                state.error_message = Some("远程修改与本地删除冲突".to_string());
+                Ok(self.update_sync_state(file_path, state).await)
+            }
+        }
+    }
+
+    /// 删除本地文件，启用 keep_conflict_copy 时移入冲突目录下的回收目录
+    async fn remove_local_file(&self, file_path: &Path) -> Result<()> {
+        if !self.config.conflict.keep_conflict_copy {
+            tokio::fs::remove_file(file_path)
+                .await
+                .with_context(|| format!("无法删除文件: {:?}", file_path))?;
+            info!("已删除本地文件: {:?}", file_path);
+            return Ok(());
+        }
+
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let trash = trash_path(
+            &self.config.conflict.conflict_dir,
+            &self.config.sync.claude_dir,
+            file_path,
+            &timestamp,
+        );
+        if let Some(parent) = trash.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("无法创建回收目录: {:?}", parent))?;
+        }
+
+        if tokio::fs::rename(file_path, &trash).await.is_err() {
+            // 跨文件系统时无法重命名，改为复制后删除
+            tokio::fs::copy(file_path, &trash)
+                .await
+                .with_context(|| format!("无法移动文件到回收目录: {:?}", trash))?;
+            tokio::fs::remove_file(file_path)
+                .await
+                .with_context(|| format!("无法删除文件: {:?}", file_path))?;
+        }
+
+        info!("已将本地文件移入回收目录: {:?}", trash);
+        Ok(())
+    }
+
+    /// 处理文件删除：已同步过的文件记录墓碑，等待推送到服务器
+    async fn handle_file_removal(&self, file_path: &Path) -> Result<()> {
+        info!("处理文件删除: {:?}", file_path);
+
+        // 从状态缓存中移除
+        let previous = self.sync_states.lock().await.remove(file_path);
+
+        // 从未上传过的文件服务器上不存在，无需通知
+        let Some(last_hash) = previous.and_then(|state| state.remote_hash) else {
+            return Ok(());
+        };
+
+        if self.dry_run {
+            info!("[dry run] 将通知服务器文件已删除: {:?}", file_path);
+            return Ok(());
+        }
+
+        let remote_path = self.remote_path(file_path)?;
+        self.tombstones.lock().await.insert(
+            remote_path.clone(),
+            Tombstone {
+                file_path: remote_path,
+                last_hash: Some(last_hash),
+                deleted_at: Utc::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// 尚未推送到服务器的删除记录
+    pub async fn pending_tombstones(&self) -> Vec<Tombstone> {
+        self.tombstones.lock().await.values().cloned().collect()
+    }
+
+    /// 将删除记录通过一次变更上报推送到服务器，返回推送的数量
+    ///
+    /// 推送成功后清除对应的墓碑；失败时墓碑保留，下次继续推送。
+    pub async fn push_tombstones<R: ChangeReporter>(&self, reporter: &R) -> Result<usize> {
+        if !self.config.sync.direction.allows_push() {
+            return Ok(0);
+        }
+
+        let tombstones = self.pending_tombstones().await;
+        if tombstones.is_empty() {
+            return Ok(0);
+        }
+
+        let changes = tombstones
+            .iter()
+            .map(|tombstone| FileChange {
+                file_path: tombstone.file_path.clone(),
+                file_hash: tombstone.last_hash.clone().unwrap_or_default(),
+                file_size: 0,
+                modified_at: tombstone.deleted_at.timestamp(),
+                version: 0,
+                is_deleted: true,
+            })
+            .collect();
+
+        let response = reporter.report(changes).await.context("推送删除记录失败")?;
+        if !response.success {
+            anyhow::bail!("服务器拒绝删除记录: {}", response.message);
+        }
+        if !response.conflicts_detected.is_empty() {
+            warn!("服务器检测到删除冲突: {:?}", response.conflicts_detected);
+        }
+
+        // 推送期间可能产生了新的删除记录，只清除已推送的部分
+        let mut pending = self.tombstones.lock().await;
+        for tombstone in &tombstones {
+            if pending.get(&tombstone.file_path) == Some(tombstone) {
+                pending.remove(&tombstone.file_path);
+            }
+        }
+
+        info!("已通知服务器 {} 个文件删除", tombstones.len());
+        Ok(tombstones.len())
+    }
+
+    /// 所有未解决的冲突（按路径排序）
+    pub async fn unresolved_conflicts(&self) -> Vec<FileSyncState> {
+        let mut conflicts: Vec<_> = self
+            .sync_states
+            .lock()
+            .await
+            .values()
+            .filter(|state| state.status == SyncStatus::Conflict)
+            .cloned()
+            .collect();
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+        conflicts
+    }
+
+    /// 已解决冲突残留的 `.conflict` 标记文件（按路径排序）
+    ///
+    /// 原文件已同步、且没有其他共用该标记文件的文件仍处于冲突状态时视为残留。
+    pub async fn orphaned_conflict_markers(&self) -> Vec<PathBuf> {
+        let states = self.sync_states.lock().await;
+        let unresolved: HashSet<PathBuf> = states
+            .values()
+            .filter(|state| state.status == SyncStatus::Conflict)
+            .map(|state| conflict_marker_path(&state.path))
+            .collect();
+
+        let mut markers: Vec<_> = states
+            .values()
+            .filter(|state| state.status == SyncStatus::Synced)
+            .map(|state| conflict_marker_path(&state.path))
+            .filter(|marker| !unresolved.contains(marker) && marker.exists())
+            .collect();
+        markers.sort();
+        markers.dedup();
+        markers
+    }
+
+    /// 删除已解决冲突残留的 `.conflict` 标记文件，返回删除的文件（演练模式下只返回不删除）
+    pub async fn clean_conflict_markers(&self) -> Result<Vec<PathBuf>> {
+        let markers = self.orphaned_conflict_markers().await;
+        if self.dry_run {
+            return Ok(markers);
+        }
+
+        for marker in &markers {
+            tokio::fs::remove_file(marker)
+                .await
+                .with_context(|| format!("无法删除冲突标记文件: {:?}", marker))?;
+            info!("已删除残留的冲突标记文件: {:?}", marker);
+        }
+        Ok(markers)
+    }
+
+    /// 按用户的选择解决一个冲突
+    ///
+    /// 保留本地或编辑后的内容会重新上传，保留远程会下载最新版本覆盖本地；
+    /// 解决后删除对应的 `.conflict` 标记文件。选择跳过时状态保持不变。
+    pub async fn resolve_conflict<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        file_path: &Path,
+        choice: ConflictChoice,
+    ) -> Result<FileSyncState> {
+        let state = match self.get_sync_state(file_path).await {
+            Some(state) if state.status == SyncStatus::Conflict => state,
+            _ => anyhow::bail!("文件没有未解决的冲突: {:?}", file_path),
+        };
+
+        let resolved = match choice {
+            ConflictChoice::Skip => return Ok(state),
+            ConflictChoice::KeepLocal => {
+                info!("保留本地版本: {:?}", file_path);
+                let content = tokio::fs::read(file_path)
+                    .await
+                    .with_context(|| format!("无法读取文件: {:?}", file_path))?;
+                let local_hash = self.hash_content(&content);
+                self.upload_file(file_path, &local_hash).await?
+            }
+            ConflictChoice::Edited(content) => {
+                if crate::conflict::contains_conflict_markers(&content) {
+                    anyhow::bail!("编辑后的内容仍包含冲突标记: {:?}", file_path);
+                }
+                info!("使用编辑后的内容: {:?}", file_path);
+                write_atomic(file_path, &content).await?;
+                let local_hash = self.hash_content(content.as_bytes());
+                self.upload_file(file_path, &local_hash).await?
+            }
+            ConflictChoice::KeepRemote => {
+                info!("保留远程版本: {:?}", file_path);
+                self.replace_with_remote(source, file_path).await?
+            }
+        };
+        remove_conflict_marker(file_path).await;
+
+        Ok(resolved)
+    }
+
+    /// 忽略哈希和冲突状态，按指定方向强制同步单个文件
+    ///
+    /// push 上传本地内容，pull 下载最新版本覆盖本地；完成后清除冲突状态和
+    /// `.conflict` 标记文件，并写入冲突审计日志。
+    pub async fn force_sync_file<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        file_path: &Path,
+        direction: ForcedDirection,
+    ) -> Result<FileSyncState> {
+        if self.roots.root_for(file_path).is_none() {
+            anyhow::bail!("路径不在同步目录中: {:?}", file_path);
+        }
+
+        let previous_remote_hash = self
+            .get_sync_state(file_path)
+            .await
+            .and_then(|state| state.remote_hash);
+        let local_hash = match tokio::fs::read(file_path).await {
+            Ok(content) => Some(self.hash_content(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("无法读取文件: {:?}", file_path)),
+        };
+
+        let state = match direction {
+            ForcedDirection::Push => {
+                let Some(local_hash) = &local_hash else {
+                    anyhow::bail!("本地文件不存在: {:?}", file_path);
+                };
+                info!("强制上传: {:?}", file_path);
+                self.upload_file(file_path, local_hash).await?
+            }
+            ForcedDirection::Pull => {
+                info!("强制下载: {:?}", file_path);
+                self.replace_with_remote(source, file_path).await?
+            }
+        };
+        remove_conflict_marker(file_path).await;
+
+        let remote_hash = match direction {
+            ForcedDirection::Push => previous_remote_hash,
+            ForcedDirection::Pull => state.remote_hash.clone(),
+        };
+        self.conflict_resolver.record_audit(&AuditEntry {
+            path: file_path.to_path_buf(),
+            strategy: direction.as_str().to_string(),
+            timestamp: Utc::now(),
+            local_hash: local_hash.unwrap_or_default(),
+            remote_hash: remote_hash.unwrap_or_default(),
+            merged_hash: state.local_hash.clone().unwrap_or_default(),
+        });
+
+        Ok(state)
+    }
+
+    /// 下载最新的远程版本覆盖本地文件，并标记为已同步
+    async fn replace_with_remote<R: RemoteChangeSource>(
+        &self,
+        source: &R,
+        file_path: &Path,
+    ) -> Result<FileSyncState> {
+        let remote_path = self.remote_path(file_path)?;
+        let data = source.download_latest(remote_path).await?;
+        let actual_hash = hash_like(&data.file_hash, &data.content);
+        if actual_hash != data.file_hash {
+            anyhow::bail!(
+                "下载内容校验失败: 期望 {}, 实际 {}",
+                data.file_hash,
+                actual_hash
+            );
+        }
+        self.downloaded_bytes
+            .fetch_add(data.content.len() as u64, Ordering::Relaxed);
+        self.write_remote_content(file_path, &data).await?;
+
+        let state = FileSyncState {
+            path: file_path.to_path_buf(),
+            local_hash: Some(actual_hash.clone()),
+            remote_hash: Some(actual_hash),
+            status: SyncStatus::Synced,
+            last_sync_time: Some(Utc::now()),
+            error_message: None,
+            size: None,
+            modified: None,
+        };
+        Ok(self.update_sync_state(file_path, state).await)
+    }
+
+    /// 更新同步状态（同时记录文件大小和修改时间）
+    async fn update_sync_state(&self, file_path: &Path, mut state: FileSyncState) -> FileSyncState {
+        // 演练模式不修改状态缓存
+        if self.dry_run {
+            return state;
+        }
+
+        if let Some((size, modified)) = file_fingerprint(file_path) {
+            state.size = Some(size);
+            state.modified = Some(modified);
+        }
+
+        let mut states = self.sync_states.lock().await;
+        states.insert(file_path.to_path_buf(), state.clone());
+        state
+    }
+
+    /// 获取同步状态
+    pub async fn get_sync_state(&self, file_path: &Path) -> Option<FileSyncState> {
+        let states = self.sync_states.lock().await;
+        states.get(file_path).cloned()
+    }
+
+    /// 获取所有同步状态
+    pub async fn get_all_sync_states(&self) -> Vec<FileSyncState> {
+        let states = self.sync_states.lock().await;
+        states.values().cloned().collect()
+    }
+}
+
+/// 同步操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncAction {
+    /// 上传
+    Upload,
+    /// 需要同步（可能冲突）
+    NeedSync,
+    /// 无需操作
+    NoAction,
+}
+
+/// 同步状态校对结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// 需要同步的文件（离线期间新增或修改）
+    pub pending: Vec<PathBuf>,
+
+    /// 离线期间被删除的文件
+    pub removed: Vec<PathBuf>,
+
+    /// 未变化的文件数
+    pub unchanged: usize,
+}
+
+/// 不一致的文件中哪一侧的内容更新
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NewerSide {
+    /// 上次同步后本地文件被修改
+    Local,
+    /// 上次同步后服务器上有新版本
+    Server,
+    /// 两侧都有修改（下次同步会产生冲突）
+    Both,
+}
+
+/// 与同步状态不一致的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyMismatch {
+    /// 文件路径
+    pub path: PathBuf,
+
+    /// 同步状态中记录的本地哈希
+    pub recorded_hash: Option<String>,
+
+    /// 重新计算的本地哈希
+    pub local_hash: String,
+
+    /// 哪一侧的内容更新
+    pub newer: NewerSide,
+}
+
+/// 网络恢复后重放离线期间的本地修改
+impl OfflineOperationHandler for SyncEngine {
+    fn replay(&self, operation: OfflineOperation) -> BoxFuture<'_, Result<(), ClientError>> {
+        Box::pin(async move {
+            let OfflineOperation::FileUpload { path, .. } = operation else {
+                debug!("同步引擎不处理该离线操作: {:?}", operation);
+                return Ok(());
+            };
+
+            // 文件在离线期间被删除时由删除事件处理
+            let file_path = PathBuf::from(&path);
+            if !file_path.exists() {
+                return Ok(());
+            }
+
+            // 入队后规则可能已变化（如持久化队列跨越重启），重放前重新检查
+            if !self.accepts_local_change(&file_path) {
+                return Ok(());
+            }
+            self.sync_file(&file_path).await.map(|_| ()).map_err(|e| {
+                match e.downcast::<ClientError>() {
+                    Ok(e) => e,
+                    Err(e) => ClientError::sync(path, format!("{:#}", e)),
+                }
+            })
+        })
+    }
+}
+
+/// 本地文件完整性校验结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// 与同步状态不一致的文件
+    pub mismatched: Vec<VerifyMismatch>,
+
+    /// 同步状态中存在但本地已不存在的文件
+    pub missing: Vec<PathBuf>,
+
+    /// 与同步状态一致的文件数
+    pub verified: usize,
+}
+
+/// 预计上传量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadEstimate {
+    /// 需要上传的文件数
+    pub files: usize,
+
+    /// 需要上传的总字节数
+    pub bytes: u64,
+}
+
+/// 服务器配额用尽后未上传的文件的跳过原因
+const QUOTA_SKIP_REASON: &str = "服务器存储配额已用尽，未上传";
+
+/// 错误是否为服务器存储配额不足
+fn quota_error(error: &anyhow::Error) -> Option<&ClientError> {
+    error
+        .downcast_ref::<ClientError>()
+        .filter(|e| matches!(e, ClientError::QuotaExceeded { .. }))
+}
+
+/// 错误是否为网络不可达（连接失败、超时等可重试的错误）
+fn is_network_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ClientError>()
+        .is_some_and(ClientError::is_retryable)
+}
+
+/// 文件内容是否与记录的哈希一致
+///
+/// current_hash 是按当前配置算法计算的哈希；记录的哈希使用其它算法时（切换算法前
+/// 同步的文件），按记录哈希的算法重新计算文件哈希再比较。
+fn content_matches(path: &Path, current_hash: &str, recorded_hash: &str) -> bool {
+    if current_hash == recorded_hash {
+        return true;
+    }
+    let algorithm = HashAlgorithm::of(recorded_hash);
+    algorithm != HashAlgorithm::of(current_hash)
+        && algorithm
+            .hash_file(path)
+            .is_ok_and(|hash| hash == recorded_hash)
+}
+
+/// 忽略文件的修改时间（文件不存在时为 None）
+fn ignore_file_modified(claude_dir: &Path) -> Option<SystemTime> {
+    std::fs::metadata(claude_dir.join(IGNORE_FILE_NAME))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// 远程变更的修改时间（未记录时为 None）
+fn remote_modified_time(change: &FileChange) -> Option<DateTime<Utc>> {
+    (change.modified_at > 0)
+        .then(|| DateTime::from_timestamp(change.modified_at, 0))
+        .flatten()
+}
+
+/// 获取文件大小和修改时间，文件不存在时返回 None
+fn file_fingerprint(path: &Path) -> Option<(u64, DateTime<Utc>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    let modified = metadata.modified().ok()?;
+    Some((metadata.len(), DateTime::<Utc>::from(modified)))
+}
+
+/// 全量同步进度事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FullSyncProgress {
+    /// 扫描完成，共有 total 个文件待处理
+    Started { total: usize },
+    /// 处理完一个文件（无论成功与否）
+    FileProcessed { path: PathBuf },
+}
+
+/// 同步摘要
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncSummary {
+    /// 成功同步的文件数
+    pub synced_count: usize,
+
+    /// 失败的文件数
+    pub failed_count: usize,
+
+    /// 冲突的文件数
+    pub conflict_count: usize,
+
+    /// 冲突文件列表
+    pub conflicts: Vec<PathBuf>,
+
+    /// 错误列表
+    pub errors: Vec<(PathBuf, String)>,
+
+    /// 跳过的文件数
+    #[serde(default)]
+    pub skipped_count: usize,
+
+    /// 跳过的文件及原因
+    #[serde(default)]
+    pub skipped: Vec<(PathBuf, String)>,
+
+    /// 是否为演练模式
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl SyncSummary {
+    /// 按路径排序冲突和错误列表，使汇总结果与处理顺序无关
+    pub fn sort_entries(&mut self) {
+        self.conflicts.sort();
+        self.errors.sort();
+        self.skipped.sort();
+    }
+}
+
+/// 测试用的同步引擎工厂（各模块的测试共用）
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::audit::AuditLog;
+    use crate::transfer::{UploadTarget, DEFAULT_CHUNK_SIZE};
+
+    /// 使用默认配置创建引擎
+    pub(crate) fn create_engine(claude_dir: &Path, state_file: PathBuf) -> SyncEngine {
+        engine_with_config(claude_dir, state_file, |_| {}, None)
+    }
+
+    /// 调整默认配置后创建引擎
+    ///
+    /// 与 main 中的 create_sync_engine 一样按配置构建规则、传输和冲突解决器；
+    /// 冲突审计日志默认写在状态文件旁边，避免测试写入用户目录。
+    pub(crate) fn engine_with_config(
+        claude_dir: &Path,
+        state_file: PathBuf,
+        configure: impl FnOnce(&mut ClientConfig),
+        upload_target: Option<Arc<dyn UploadTarget>>,
+    ) -> SyncEngine {
+        let mut config = ClientConfig::default();
+        config.sync.claude_dir = claude_dir.to_path_buf();
+        config.conflict.audit_log = state_file.with_file_name("audit.jsonl");
+        config.sync.state_file = state_file;
+        configure(&mut config);
+
+        let mut transfer_manager = TransferManager::new(
+            config.performance.max_concurrent_uploads,
+            1,
+            0,
+            0,
+            0,
+            DEFAULT_CHUNK_SIZE,
+        );
+        if let Some(upload_target) = upload_target {
+            transfer_manager = transfer_manager.with_upload_target(upload_target);
+        }
+
+        let type_strategies = config
+            .conflict
+            .per_type_strategy
+            .iter()
+            .filter_map(|(file_type, strategy)| {
+                ResolutionStrategy::parse(strategy).map(|s| (file_type.clone(), s))
+            })
+            .collect();
+        let conflict_resolver = ConflictResolver::new(
+            ResolutionStrategy::parse(&config.conflict.default_strategy)
+                .unwrap_or(ResolutionStrategy::Manual),
+            config.conflict.auto_merge_text,
+            config.conflict.auto_merge_structured,
+        )
+        .with_type_strategies(type_strategies)
+        .with_audit_log(AuditLog::new(config.conflict.audit_log.clone()));
+
+        let rule_engine = RuleEngine::from_rules(config.sync.rules.clone());
+
+        SyncEngine::new(
+            Arc::new(config),
+            Arc::new(rule_engine),
+            Arc::new(transfer_manager),
+            Arc::new(conflict_resolver),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::{create_engine, engine_with_config};
+    use super::*;
+    use crate::config::SyncDirection;
+    use crate::hash_cache::HASH_CACHE_FILE_NAME;
+    use crate::transfer::{UploadChunks, UploadRequest, UploadTarget};
+
+    #[test]
+    fn test_sync_status() {
+        let status1 = SyncStatus::Syncing;
+        let status2 = SyncStatus::Syncing;
+        assert_eq!(status1, status2);
+
+        let status3 = SyncStatus::Synced;
+        assert_ne!(status1, status3);
+    }
+
+    #[test]
+    fn test_summary_is_order_independent() {
+        let paths: Vec<PathBuf> = ["c.md", "a.md", "b.md"].iter().map(PathBuf::from).collect();
+
+        let build = |order: &[usize]| {
+            let mut summary = SyncSummary::default();
+            for &i in order {
+                summary.conflicts.push(paths[i].clone());
+                summary
+                    .errors
+                    .push((paths[i].clone(), format!("error {}", i)));
+            }
+            summary.sort_entries();
+            summary
+        };
+
+        let forward = build(&[0, 1, 2]);
+        let reversed = build(&[2, 1, 0]);
+        assert_eq!(forward.conflicts, reversed.conflicts);
+        assert_eq!(forward.errors, reversed.errors);
+        assert_eq!(forward.conflicts[0], PathBuf::from("a.md"));
+    }
+
+    #[tokio::test]
+    async fn test_full_sync_reports_progress_per_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        for name in ["a.md", "b.json", "agents/c.md"] {
+            std::fs::write(claude_dir.join(name), name).unwrap();
+        }
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("sync_state.json"));
+        let events = std::sync::Mutex::new(Vec::new());
+        let summary = engine
+            .run_full_sync_with_progress(None, |event| events.lock().unwrap().push(event))
+            .await
+            .unwrap();
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events[0], FullSyncProgress::Started { total: 3 });
+        let processed: BTreeSet<PathBuf> = events[1..]
+            .iter()
+            .map(|event| match event {
+                FullSyncProgress::FileProcessed { path } => path.clone(),
+                other => panic!("unexpected event: {:?}", other),
+            })
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(processed.len(), 3);
+        assert!(processed.contains(&claude_dir.join("agents/c.md")));
+        assert_eq!(summary.synced_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_detects_offline_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let state_file = temp_dir.path().join("sync_state.json");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let changed = claude_dir.join("changed.md");
+        let unchanged = claude_dir.join("unchanged.md");
+        std::fs::write(&changed, "original").unwrap();
+        std::fs::write(&unchanged, "stable").unwrap();
+
+        // 第一次运行：全量同步并保存快照
+        let engine = create_engine(&claude_dir, state_file.clone());
+        engine.run_full_sync().await.unwrap();
+        engine.save_snapshot().await.unwrap();
+
+        // 离线期间修改、新增文件
+        std::fs::write(&changed, "modified while offline").unwrap();
+        let added = claude_dir.join("added.md");
+        std::fs::write(&added, "new").unwrap();
+
+        // 重新启动：加载快照并校对
+        let engine = create_engine(&claude_dir, state_file);
+        assert_eq!(engine.load_snapshot().await.unwrap(), 2);
+        let report = engine.reconcile().await.unwrap();
+
+        assert_eq!(report.pending, vec![added.clone(), changed.clone()]);
+        assert!(report.removed.is_empty());
+        assert_eq!(report.unchanged, 1);
+
+        let state = engine.get_sync_state(&changed).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Pending);
+        let state = engine.get_sync_state(&unchanged).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+
+        // 同步后状态恢复
+        let summary = engine.sync_pending().await.unwrap();
+        assert_eq!(summary.synced_count, 2);
+        let report = engine.reconcile().await.unwrap();
+        assert!(report.pending.is_empty());
+        assert_eq!(report.unchanged, 3);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_touched_but_unchanged() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let file = claude_dir.join("touched.md");
+        std::fs::write(&file, "same").unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("sync_state.json"));
+        engine.run_full_sync().await.unwrap();
+
+        // 修改时间变化但内容相同，不应标记为等待同步
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(&file, "same").unwrap();
+
+        // 删除的文件应被识别
+        let removed = claude_dir.join("removed.md");
+        std::fs::write(&removed, "gone").unwrap();
+        engine.sync_file(&removed).await.unwrap();
+        std::fs::remove_file(&removed).unwrap();
+
+        let report = engine.reconcile().await.unwrap();
+        assert!(report.pending.is_empty());
+        assert_eq!(report.removed, vec![removed.clone()]);
+        assert_eq!(report.unchanged, 1);
+        assert!(engine.get_sync_state(&removed).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_selective_sync_subdir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let agents_dir = claude_dir.join("agents");
+        let skills_dir = claude_dir.join("skills");
+        std::fs::create_dir_all(&agents_dir).unwrap();
+        std::fs::create_dir_all(&skills_dir).unwrap();
+
+        let agent = agents_dir.join("agent.md");
+        let skill = skills_dir.join("skill.md");
+        let root_file = claude_dir.join("settings.json");
+        std::fs::write(&agent, "agent").unwrap();
+        std::fs::write(&skill, "skill").unwrap();
+        std::fs::write(&root_file, "{}").unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("sync_state.json"));
+        let summary = engine
+            .run_selective_sync(vec![PathBuf::from("agents")])
+            .await
+            .unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert!(engine.get_sync_state(&agent).await.is_some());
+        assert!(engine.get_sync_state(&skill).await.is_none());
+        assert!(engine.get_sync_state(&root_file).await.is_none());
+
+        // 显式指定文件，同时忽略 Claude 目录之外的路径
+        let outside = temp_dir.path().join("outside.md");
+        std::fs::write(&outside, "outside").unwrap();
+        let summary = engine
+            .run_selective_sync(vec![root_file.clone(), outside.clone()])
+            .await
+            .unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert!(engine.get_sync_state(&root_file).await.is_some());
+        assert!(engine.get_sync_state(&outside).await.is_none());
+
+        // 通过 .. 指向 Claude 目录之外的路径同样被忽略
+        let summary = engine
+            .run_selective_sync(vec![
+                PathBuf::from("../outside.md"),
+                claude_dir.join("agents/../../outside.md"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(summary.synced_count, 0);
+        assert!(engine.get_sync_state(&outside).await.is_none());
+        assert!(engine
+            .get_sync_state(&claude_dir.join("../outside.md"))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_sync_since_skips_older_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let cutoff = Utc::now() - chrono::Duration::hours(2);
+        let old = claude_dir.join("old.md");
+        let recent = claude_dir.join("recent.md");
+        std::fs::write(&old, "old").unwrap();
+        std::fs::write(&recent, "recent").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified((cutoff - chrono::Duration::hours(1)).into())
+            .unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        let summary = engine.run_full_sync_since(Some(cutoff)).await.unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.skipped_count, 1);
+        assert_eq!(summary.skipped[0].0, old);
+        assert!(engine.get_sync_state(&recent).await.is_some());
+        assert!(engine.get_sync_state(&old).await.is_none());
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_full_sync_matches_sequential() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        for dir in ["agents", "skills", "commands"] {
+            std::fs::create_dir_all(claude_dir.join(dir)).unwrap();
+            for i in 0..15 {
+                // 每隔几个文件放一个超出大小上限的文件，产生跳过项
+                let content = if i % 4 == 0 {
+                    "x".repeat(64)
+                } else {
+                    format!("{} {}", dir, i)
+                };
+                std::fs::write(claude_dir.join(dir).join(format!("{}.md", i)), content).unwrap();
+            }
+        }
+
+        let run = |concurrency: usize, state_file: &str| {
+            let engine = engine_with_config(
+                &claude_dir,
+                temp_dir.path().join(state_file),
+                |config| {
+                    config.sync.max_file_size = Some(32);
+                    config.performance.max_concurrent_uploads = concurrency;
+                },
+                None,
+            );
+            let scan_dir = claude_dir.clone();
+            async move {
+                // 绕过扫描阶段的大小过滤，让跳过项由 sync_file 产生
+                let mut files = FileScanner::new(scan_dir, vec![], vec![], vec![])
+                    .scan()
+                    .unwrap();
+                files.reverse();
+                let summary = engine.sync_files(files).await;
+                engine.close().await.unwrap();
+                summary
+            }
+        };
+
+        let sequential = run(1, "sequential.json").await;
+        let concurrent = run(8, "concurrent.json").await;
+
+        assert_eq!(sequential.synced_count, 33);
+        assert_eq!(sequential.skipped_count, 12);
+        assert_eq!(
+            serde_json::to_value(&sequential).unwrap(),
+            serde_json::to_value(&concurrent).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_full_sync() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        std::fs::write(claude_dir.join("agents").join("a.md"), "a").unwrap();
+        std::fs::write(claude_dir.join("settings.json"), "{}").unwrap();
+
+        let snapshot = |dir: &Path| -> Vec<(PathBuf, Vec<u8>)> {
+            let mut entries: Vec<_> = walkdir::WalkDir::new(dir)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| (e.path().to_path_buf(), std::fs::read(e.path()).unwrap()))
+                .collect();
+            entries.sort();
+            entries
+        };
+        let before = snapshot(temp_dir.path());
+
+        let engine =
+            create_engine(&claude_dir, temp_dir.path().join("sync_state.json")).with_dry_run(true);
+        let summary = engine.run_full_sync().await.unwrap();
+
+        // 计数准确，但不修改文件系统和状态缓存
+        assert!(summary.dry_run);
+        assert_eq!(summary.synced_count, 2);
+        assert_eq!(summary.failed_count, 0);
+        assert!(engine.get_all_sync_states().await.is_empty());
+        assert_eq!(snapshot(temp_dir.path()), before);
+    }
+
+    #[tokio::test]
+    async fn test_sync_skips_files_out_of_size_range() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let empty = claude_dir.join("empty.md");
+        let normal = claude_dir.join("normal.md");
+        let huge = claude_dir.join("model.bin");
+        std::fs::write(&empty, "").unwrap();
+        std::fs::write(&normal, "content").unwrap();
+        std::fs::write(&huge, vec![0u8; 4096]).unwrap();
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("sync_state.json"),
+            |config| {
+                config.sync.include_types = vec![];
+                config.sync.min_file_size = Some(1);
+                config.sync.max_file_size = Some(1024);
+            },
+            None,
+        );
+
+        let summary = engine.run_full_sync().await.unwrap();
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.skipped_count, 2);
+        assert_eq!(summary.skipped[0].0, empty);
+        assert_eq!(summary.skipped[1].0, huge);
+        assert!(engine.get_sync_state(&huge).await.is_none());
+
+        // 文件事件等直接同步单个文件时同样跳过
+        let state = engine.sync_file(&huge).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Skipped);
+        assert!(state.error_message.unwrap().contains("上限"));
+        assert!(engine.get_sync_state(&huge).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_progress_events_in_order() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        // 9MB 文件按 4MB 分块上传，产生多个进度事件
+        let file = claude_dir.join("large.jsonl");
+        std::fs::write(&file, vec![b'x'; 9 * 1024 * 1024]).unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("sync_state.json"));
+        let mut rx = engine.subscribe_progress();
+        engine.sync_file(&file).await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(progress) = rx.try_recv() {
+            events.push(progress);
+        }
+
+        assert!(events.len() >= 3);
+        assert!(events.iter().all(|p| p.file_path == file));
+        assert!(events
+            .windows(2)
+            .all(|w| w[0].transferred_bytes <= w[1].transferred_bytes));
+        assert!(events[..events.len() - 1].iter().all(|p| !p.is_completed));
+
+        let last = events.last().unwrap();
+        assert!(last.is_completed);
+        assert_eq!(last.progress_percent(), 100.0);
+        assert!(last.transfer_rate() >= 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_close_persists_and_is_idempotent() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::write(claude_dir.join("a.md"), "a").unwrap();
+
+        let state_file = temp_dir.path().join("sync_state.json");
+        let metrics_file = temp_dir.path().join("metrics.json");
+
+        let monitoring = MonitoringManager::new(100, 1000);
+        monitoring.record_counter("close_test", 1.0, vec![]).await;
+
+        let server = ClientConfig::default().server;
+        let pool = Arc::new(ConnectionPool::new(
+            server.address.clone(),
+            crate::connection_pool::PoolConfig::from_server_config(&server).unwrap(),
+        ));
+
+        let engine = engine_with_config(
+            &claude_dir,
+            state_file.clone(),
+            |config| config.performance.metrics_file = metrics_file.clone(),
+            None,
+        )
+        .with_monitoring(monitoring)
+        .with_connection_pool(pool.clone());
+
+        engine.run_full_sync().await.unwrap();
+        engine.close().await.unwrap();
+
+        assert!(engine.is_closed());
+        assert!(pool.is_shutdown().await);
+        let snapshot = std::fs::read_to_string(&state_file).unwrap();
+        assert!(snapshot.contains("a.md"));
+        let metrics = std::fs::read_to_string(&metrics_file).unwrap();
+        assert!(metrics.contains("close_test"));
+
+        // 第二次关闭不再写入
+        std::fs::remove_file(&state_file).unwrap();
+        std::fs::remove_file(&metrics_file).unwrap();
+        engine.close().await.unwrap();
+        drop(engine);
+        assert!(!state_file.exists());
+        assert!(!metrics_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_drop_without_close_keeps_existing_snapshot() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let state_file = temp_dir.path().join("state.json");
+        let snapshot = r#"{"version_cursor":42,"states":[],"tombstones":[]}"#;
+        std::fs::write(&state_file, snapshot).unwrap();
+
+        // 未加载快照就出错返回的引擎不会用空状态覆盖已有快照
+        drop(create_engine(&claude_dir, state_file.clone()));
+
+        assert_eq!(std::fs::read_to_string(&state_file).unwrap(), snapshot);
+    }
+
+    #[tokio::test]
+    async fn test_full_sync_records_metrics() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        std::fs::write(claude_dir.join("agents").join("a.md"), "agent a").unwrap();
+        std::fs::write(claude_dir.join("settings.json"), "{}").unwrap();
+
+        let monitoring = MonitoringManager::new(100, 1000);
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"))
+            .with_monitoring(monitoring.clone());
+
+        engine.run_full_sync().await.unwrap();
+
+        let stats = monitoring.get_performance_stats().await;
+        assert_eq!(stats.sync_total_count, 1);
+        assert_eq!(stats.sync_success_count, 1);
+        assert_eq!(stats.upload_total_bytes, "agent a".len() as u64 + 2);
+        assert!(!monitoring
+            .get_histograms("operation_upload_file")
+            .await
+            .is_empty());
+        assert!(monitoring
+            .export_metrics_prometheus()
+            .await
+            .contains("upload_bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_adhoc_rules_override_config_rules() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        let draft = claude_dir.join("agents").join("draft.md");
+        let settings = claude_dir.join("settings.json");
+        std::fs::write(claude_dir.join("CLAUDE.md"), "rules").unwrap();
+        std::fs::write(&draft, "draft").unwrap();
+        std::fs::write(&settings, "{}").unwrap();
+
+        let mut rules = Vec::new();
+        for (id, rule_type, pattern) in [
+            ("include-md", crate::rules::RuleType::Include, "**/*.md"),
+            ("exclude-json", crate::rules::RuleType::Exclude, "**/*.json"),
+        ] {
+            rules.push(SyncRule {
+                id: id.to_string(),
+                name: id.to_string(),
+                rule_type,
+                pattern: pattern.to_string(),
+                pattern_type: crate::rules::PatternType::Glob,
+                file_type: None,
+                priority: 10,
+                enabled: true,
+                description: None,
+                rewrite: None,
+            });
+        }
+
+        let adhoc = crate::rules::adhoc_rules(
+            &["settings.json".to_string()],
+            &["agents/draft.md".to_string()],
+        )
+        .unwrap();
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.sync.rules = rules,
+            None,
+        )
+        .with_adhoc_rules(adhoc);
+
+        let summary = engine.run_full_sync().await.unwrap();
+        assert_eq!(summary.synced_count, 2);
+        assert!(engine.get_sync_state(&draft).await.is_none());
+        assert!(engine.get_sync_state(&settings).await.is_some());
+
+        // 重新加载规则后临时规则仍然生效，且不会写入配置
+        engine.reload_rules().unwrap();
+        assert!(!engine.matches_sync_rules(&draft));
+        assert!(engine.matches_sync_rules(&settings));
+        assert_eq!(engine.config.sync.rules.len(), 2);
+    }
+
+    /// 返回固定规则列表的模拟服务器
+    struct StaticRules(Vec<ServerRule>);
+
+    impl RuleSource for StaticRules {
+        async fn list_rules(&self, _device_id: uuid::Uuid) -> Result<Vec<ServerRule>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_rules_override_local_rules() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        let private = claude_dir.join("agents").join("private.md");
+        let public = claude_dir.join("agents").join("public.md");
+        std::fs::write(&private, "private").unwrap();
+        std::fs::write(&public, "public").unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        assert!(engine.matches_sync_rules(&private));
+
+        let exclude = ServerRule {
+            rule_id: "7".to_string(),
+            rule_name: "本设备不同步私有 Agent".to_string(),
+            rule_type: "exclude".to_string(),
+            pattern: "agents/private.md".to_string(),
+            file_type: None,
+            priority: 0,
+            device_specific: true,
+        };
+        let count = engine
+            .refresh_server_rules(&StaticRules(vec![exclude]))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(!engine.matches_sync_rules(&private));
+        assert!(engine.matches_sync_rules(&public));
+
+        // 忽略文件变化重新加载规则时保留服务器规则
+        engine.reload_rules().unwrap();
+        assert!(!engine.matches_sync_rules(&private));
+
+        // 服务器删除规则后恢复本地规则
+        engine
+            .refresh_server_rules(&StaticRules(Vec::new()))
+            .await
+            .unwrap();
+        assert!(engine.matches_sync_rules(&private));
+    }
+
+    #[tokio::test]
+    async fn test_ignore_file_rules_reload_on_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("scratch")).unwrap();
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        let scratch = claude_dir.join("scratch").join("x.md");
+        std::fs::write(&scratch, "scratch").unwrap();
+        std::fs::write(claude_dir.join("agents").join("a.md"), "agent a").unwrap();
+        let ignore_file = claude_dir.join(IGNORE_FILE_NAME);
+        std::fs::write(&ignore_file, "# 临时文件\nscratch/\n").unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        engine.reload_rules().unwrap();
+
+        // 忽略文件本身也会同步到其他设备
+        let summary = engine.run_full_sync().await.unwrap();
+        assert_eq!(summary.synced_count, 2);
+        assert!(summary
+            .skipped
+            .iter()
+            .any(|(path, reason)| path == &scratch && reason == "被同步规则排除"));
+        assert!(engine.get_sync_state(&scratch).await.is_none());
+
+        // 守护进程收到忽略文件的变更事件后重新加载规则
+        std::fs::write(&ignore_file, "# 不再忽略\n").unwrap();
+        for path in [ignore_file, scratch.clone()] {
+            engine
+                .handle_file_event(FileEvent {
+                    path,
+                    event_type: FileEventType::Modify,
+                    timestamp: Utc::now(),
+                    is_dir: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        let state = engine.get_sync_state(&scratch).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        engine.close().await.unwrap();
+    }
 
     #[test]
-    fn test_sync_status() {
-        let status1 = SyncStatus::Syncing;
-        let status2 = SyncStatus::Syncing;
-        assert_eq!(status1, status2);
+    fn test_conflict_copy_paths() {
+        let [local, remote] = conflict_copy_paths(
+            Path::new("/conflicts"),
+            Path::new("/home/u/.claude"),
+            Path::new("/home/u/.claude/agents/a.md"),
+            "20261016T120000.000Z",
+        );
+        assert_eq!(
+            local,
+            PathBuf::from("/conflicts/agents/a.md.local.20261016T120000.000Z")
+        );
+        assert_eq!(
+            remote,
+            PathBuf::from("/conflicts/agents/a.md.remote.20261016T120000.000Z")
+        );
+
+        // Claude 目录之外的文件只保留文件名
+        let [local, _] = conflict_copy_paths(
+            Path::new("/conflicts"),
+            Path::new("/home/u/.claude"),
+            Path::new("/etc/other.json"),
+            "ts",
+        );
+        assert_eq!(local, PathBuf::from("/conflicts/other.json.local.ts"));
+    }
+
+    fn create_conflict_engine(
+        claude_dir: &Path,
+        conflict_dir: &Path,
+        keep_conflict_copy: bool,
+    ) -> SyncEngine {
+        engine_with_config(
+            claude_dir,
+            claude_dir.with_file_name("state.json"),
+            |config| {
+                config.conflict.conflict_dir = conflict_dir.to_path_buf();
+                config.conflict.keep_conflict_copy = keep_conflict_copy;
+            },
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_keep_conflict_copy_preserves_both_sides() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        // 冲突目录尚不存在，应自动创建
+        let conflict_dir = temp_dir.path().join("conflicts");
+        let file = claude_dir.join("agents").join("a.md");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+
+        let engine = create_conflict_engine(&claude_dir, &conflict_dir, true);
+        let copies = engine
+            .save_conflict_copies(&file, "local edit", "remote edit")
+            .await
+            .unwrap();
+
+        assert_eq!(copies.len(), 2);
+        for copy in &copies {
+            assert_eq!(copy.parent().unwrap(), conflict_dir.join("agents"));
+        }
+        let local_name = copies[0].file_name().unwrap().to_string_lossy();
+        let remote_name = copies[1].file_name().unwrap().to_string_lossy();
+        assert!(local_name.starts_with("a.md.local."));
+        assert!(remote_name.starts_with("a.md.remote."));
+        assert_eq!(std::fs::read_to_string(&copies[0]).unwrap(), "local edit");
+        assert_eq!(std::fs::read_to_string(&copies[1]).unwrap(), "remote edit");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_copies_skipped_when_disabled() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let conflict_dir = temp_dir.path().join("conflicts");
+        let file = claude_dir.join("a.md");
+
+        let engine = create_conflict_engine(&claude_dir, &conflict_dir, false);
+        let copies = engine
+            .save_conflict_copies(&file, "local", "remote")
+            .await
+            .unwrap();
+
+        assert!(copies.is_empty());
+        assert!(!conflict_dir.exists());
+    }
+
+    /// 模拟服务器：保存带版本号的文件变更，并记录请求
+    #[derive(Default)]
+    struct MockRemote {
+        files: std::sync::Mutex<Vec<(i64, String, Vec<u8>)>>,
+        requested_cursors: std::sync::Mutex<Vec<i64>>,
+        downloads: std::sync::Mutex<Vec<String>>,
+        deleted_versions: std::sync::Mutex<HashSet<i64>>,
+        modes: std::sync::Mutex<HashMap<String, u32>>,
+        modified_at: std::sync::Mutex<HashMap<i64, i64>>,
+    }
+
+    impl MockRemote {
+        fn push(&self, version: i64, path: &str, content: &str) {
+            self.files.lock().unwrap().push((
+                version,
+                path.to_string(),
+                content.as_bytes().to_vec(),
+            ));
+        }
+
+        /// 记录一次远程删除
+        fn delete(&self, version: i64, path: &str) {
+            self.push(version, path, "");
+            self.deleted_versions.lock().unwrap().insert(version);
+        }
+    }
+
+    impl RemoteHashLookup for MockRemote {
+        fn remote_hash(&self, file_path: String) -> BoxFuture<'_, Result<Option<String>>> {
+            let latest = self
+                .files
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, path, _)| *path == file_path)
+                .max_by_key(|(version, _, _)| *version)
+                .map(|(_, _, content)| TransferManager::calculate_hash(content).unwrap());
+            Box::pin(async move { Ok(latest) })
+        }
+    }
+
+    impl RemoteChangeSource for MockRemote {
+        async fn changes_since(&self, since_version: i64) -> Result<Vec<FileChange>> {
+            self.requested_cursors.lock().unwrap().push(since_version);
+            let deleted = self.deleted_versions.lock().unwrap().clone();
+            let modified_at = self.modified_at.lock().unwrap().clone();
+            Ok(self
+                .files
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(version, _, _)| *version > since_version)
+                .map(|(version, path, content)| FileChange {
+                    file_path: path.clone(),
+                    file_hash: TransferManager::calculate_hash(content).unwrap(),
+                    file_size: content.len() as u64,
+                    modified_at: modified_at.get(version).copied().unwrap_or(0),
+                    version: *version,
+                    is_deleted: deleted.contains(version),
+                })
+                .collect())
+        }
+
+        async fn download_latest(&self, file_path: String) -> Result<DownloadFileData> {
+            self.downloads.lock().unwrap().push(file_path.clone());
+            let files = self.files.lock().unwrap();
+            let (version, _, content) = files
+                .iter()
+                .rev()
+                .find(|(_, path, _)| *path == file_path)
+                .unwrap();
+            let file_mode = self.modes.lock().unwrap().get(&file_path).copied();
+            Ok(DownloadFileData {
+                file_path,
+                file_hash: TransferManager::calculate_hash(content).unwrap(),
+                file_size: content.len() as u64,
+                content: content.clone(),
+                version: *version,
+                file_mode,
+                content_type: None,
+            })
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executable_mode_round_trips() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("pre-commit.sh");
+        std::fs::write(&source, "#!/bin/sh\nexit 0\n").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // 上传端记录的权限位随下载返回
+        let remote = MockRemote::default();
+        remote.push(1, "hooks/pre-commit.sh", "#!/bin/sh\nexit 0\n");
+        let mode = file_mode(&std::fs::metadata(&source).unwrap()).unwrap();
+        remote
+            .modes
+            .lock()
+            .unwrap()
+            .insert("hooks/pre-commit.sh".to_string(), mode);
+
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        let hook = claude_dir.join("hooks").join("pre-commit.sh");
+        let restored = std::fs::metadata(&hook).unwrap().permissions().mode();
+        assert_eq!(restored & 0o7777, 0o755);
+
+        // 关闭 preserve_mode 时不恢复权限位
+        let other_dir = temp_dir.path().join("other");
+        std::fs::create_dir_all(&other_dir).unwrap();
+        let engine = engine_with_config(
+            &other_dir,
+            temp_dir.path().join("other-state.json"),
+            |config| config.sync.preserve_mode = false,
+            None,
+        );
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        let hook = other_dir.join("hooks").join("pre-commit.sh");
+        let mode = std::fs::metadata(&hook).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0);
+    }
+
+    /// 创建一个处于冲突状态的文件（带 `.conflict` 标记文件）
+    async fn create_conflict(engine: &SyncEngine, file_path: &Path) {
+        std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        std::fs::write(file_path, "local").unwrap();
+        std::fs::write(
+            conflict_marker_path(file_path),
+            "<<<<<<< LOCAL\nlocal\n=======\nremote\n>>>>>>> REMOTE",
+        )
+        .unwrap();
+        let state = FileSyncState {
+            path: file_path.to_path_buf(),
+            local_hash: Some(TransferManager::calculate_hash(b"local").unwrap()),
+            remote_hash: Some(TransferManager::calculate_hash(b"remote").unwrap()),
+            status: SyncStatus::Conflict,
+            last_sync_time: None,
+            error_message: Some("存在未解决的冲突".to_string()),
+            size: None,
+            modified: None,
+        };
+        engine.update_sync_state(file_path, state).await;
+    }
+
+    #[tokio::test]
+    async fn test_conflict_marker_is_never_uploaded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        // 不按扩展名过滤，扫描结果包含标记文件
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config.sync.include_types.clear();
+                config.conflict.conflict_dir = temp_dir.path().join("conflicts");
+            },
+            None,
+        );
+        let file = claude_dir.join("agents").join("a.md");
+        create_conflict(&engine, &file).await;
+        let marker = conflict_marker_path(&file);
+
+        let summary = engine.run_full_sync().await.unwrap();
+        assert!(summary.skipped.iter().any(|(path, _)| path == &marker));
+        assert!(engine.get_sync_state(&marker).await.is_none());
+
+        engine
+            .handle_file_event(FileEvent {
+                path: marker.clone(),
+                event_type: FileEventType::Modify,
+                timestamp: Utc::now(),
+                is_dir: false,
+            })
+            .await
+            .unwrap();
+        assert!(engine.get_sync_state(&marker).await.is_none());
+        assert_eq!(engine.uploaded_bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_clean_removes_markers_only_after_resolution() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        let file = claude_dir.join("settings.json");
+        create_conflict(&engine, &file).await;
+        let marker = conflict_marker_path(&file);
+
+        // 冲突未解决时保留标记文件
+        assert!(engine.clean_conflict_markers().await.unwrap().is_empty());
+        assert!(marker.exists());
+
+        // 手动编辑解决冲突并同步后，标记文件成为残留
+        let mut state = engine.get_sync_state(&file).await.unwrap();
+        state.status = SyncStatus::Synced;
+        engine.update_sync_state(&file, state).await;
+
+        // 共用标记文件的同名文件仍有冲突时不删除
+        let other = claude_dir.join("settings.md");
+        create_conflict(&engine, &other).await;
+        assert!(engine.orphaned_conflict_markers().await.is_empty());
+        let mut state = engine.get_sync_state(&other).await.unwrap();
+        state.status = SyncStatus::Synced;
+        engine.update_sync_state(&other, state).await;
+
+        assert_eq!(
+            engine.orphaned_conflict_markers().await,
+            vec![marker.clone()]
+        );
+        assert_eq!(
+            engine.clean_conflict_markers().await.unwrap(),
+            vec![marker.clone()]
+        );
+        assert!(!marker.exists());
+        assert!(file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_conflict_choices_write_expected_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        let remote = MockRemote::default();
+        remote.push(1, "agents/remote.md", "remote");
+
+        let local = claude_dir.join("agents").join("local.md");
+        let theirs = claude_dir.join("agents").join("remote.md");
+        let edited = claude_dir.join("agents").join("edited.md");
+        let skipped = claude_dir.join("agents").join("skipped.md");
+        for path in [&local, &theirs, &edited, &skipped] {
+            create_conflict(&engine, path).await;
+        }
+        assert_eq!(engine.unresolved_conflicts().await.len(), 4);
+
+        let state = engine
+            .resolve_conflict(&remote, &local, ConflictChoice::KeepLocal)
+            .await
+            .unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), "local");
+        assert!(!conflict_marker_path(&local).exists());
+
+        let state = engine
+            .resolve_conflict(&remote, &theirs, ConflictChoice::KeepRemote)
+            .await
+            .unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(std::fs::read_to_string(&theirs).unwrap(), "remote");
+        assert_eq!(
+            state.local_hash,
+            Some(TransferManager::calculate_hash(b"remote").unwrap())
+        );
+
+        // 仍带冲突标记的编辑结果被拒绝，文件保持冲突状态
+        let unfinished = "<<<<<<< LOCAL\nlocal\n=======\nremote\n>>>>>>> REMOTE";
+        assert!(engine
+            .resolve_conflict(&remote, &edited, ConflictChoice::Edited(unfinished.into()))
+            .await
+            .is_err());
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "local");
+        let state = engine
+            .resolve_conflict(&remote, &edited, ConflictChoice::Edited("merged".into()))
+            .await
+            .unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "merged");
+
+        let state = engine
+            .resolve_conflict(&remote, &skipped, ConflictChoice::Skip)
+            .await
+            .unwrap();
+        assert_eq!(state.status, SyncStatus::Conflict);
+        assert!(conflict_marker_path(&skipped).exists());
+
+        let remaining = engine.unresolved_conflicts().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].path, skipped);
+
+        // 已解决的文件不能再次解决
+        assert!(engine
+            .resolve_conflict(&remote, &local, ConflictChoice::KeepRemote)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reset_client_does_not_reupload_identical_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        let same = claude_dir.join("agents").join("same.md");
+        let changed = claude_dir.join("agents").join("changed.md");
+        std::fs::write(&same, "shared").unwrap();
+        std::fs::write(&changed, "local edit").unwrap();
+
+        let remote = Arc::new(MockRemote::default());
+        remote.push(1, "agents/same.md", "shared");
+        remote.push(2, "agents/changed.md", "server copy");
+
+        // 客户端没有任何同步状态（状态文件不存在）
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"))
+            .with_remote_hashes(remote.clone());
+
+        let state = engine.sync_file(&same).await.unwrap();
+        let shared_hash = TransferManager::calculate_hash(b"shared").unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(state.remote_hash, Some(shared_hash));
+        assert_eq!(engine.uploaded_bytes.load(Ordering::Relaxed), 0);
+
+        // 内容确实不同的文件照常上传
+        let state = engine.sync_file(&changed).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(engine.uploaded_bytes.load(Ordering::Relaxed), 10);
+    }
+
+    #[tokio::test]
+    async fn test_verify_flags_local_modification() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        let edited = claude_dir.join("agents").join("edited.md");
+        let untouched = claude_dir.join("agents").join("untouched.md");
+        let removed = claude_dir.join("agents").join("removed.md");
+        let server_side = claude_dir.join("agents").join("server.md");
+        for path in [&edited, &untouched, &removed, &server_side] {
+            std::fs::write(path, "v1").unwrap();
+        }
+
+        let remote = Arc::new(MockRemote::default());
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"))
+            .with_remote_hashes(remote.clone());
+        for path in [&edited, &untouched, &removed, &server_side] {
+            let state = engine.sync_file(path).await.unwrap();
+            assert_eq!(state.status, SyncStatus::Synced);
+        }
+
+        // 同步后本地修改、删除，服务器上另一个文件有新版本
+        std::fs::write(&edited, "v2 local").unwrap();
+        std::fs::remove_file(&removed).unwrap();
+        remote.push(1, "agents/server.md", "v2 remote");
+
+        let report = engine.verify().await.unwrap();
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.missing, vec![removed]);
+        let mismatched: Vec<(PathBuf, NewerSide)> = report
+            .mismatched
+            .iter()
+            .map(|mismatch| (mismatch.path.clone(), mismatch.newer))
+            .collect();
+        assert_eq!(
+            mismatched,
+            vec![
+                (edited.clone(), NewerSide::Local),
+                (server_side, NewerSide::Server)
+            ]
+        );
+        assert_eq!(
+            report.mismatched[0].local_hash,
+            TransferManager::calculate_hash(b"v2 local").unwrap()
+        );
+
+        // 校验不修改同步状态
+        let state = engine.get_sync_state(&edited).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+    }
+
+    #[tokio::test]
+    async fn test_force_push_uploads_despite_conflict() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.conflict.audit_log = audit_path.clone(),
+            None,
+        );
+        let remote = MockRemote::default();
+        remote.push(1, "agents/a.md", "remote");
+
+        // 远程内容不同且处于冲突状态，普通同步不会上传
+        let file = claude_dir.join("agents").join("a.md");
+        create_conflict(&engine, &file).await;
+
+        let state = engine
+            .force_sync_file(&remote, &file, ForcedDirection::Push)
+            .await
+            .unwrap();
+        let local_hash = TransferManager::calculate_hash(b"local").unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(state.remote_hash, Some(local_hash.clone()));
+        assert_eq!(engine.uploaded_bytes.load(Ordering::Relaxed), 5);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "local");
+        assert!(!conflict_marker_path(&file).exists());
+        assert!(engine.unresolved_conflicts().await.is_empty());
+        assert!(remote.downloads.lock().unwrap().is_empty());
+
+        let entries = crate::audit::AuditLog::new(audit_path).recent(10).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].strategy, "force_push");
+        assert_eq!(entries[0].merged_hash, local_hash);
+        assert_eq!(
+            entries[0].remote_hash,
+            TransferManager::calculate_hash(b"remote").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_pull_overwrites_local_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        let remote = MockRemote::default();
+        remote.push(1, "agents/a.md", "remote");
+
+        // 本地修改尚未上传，普通同步会上传本地版本
+        let file = claude_dir.join("agents").join("a.md");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "local edit").unwrap();
+
+        let state = engine
+            .force_sync_file(&remote, &file, ForcedDirection::Pull)
+            .await
+            .unwrap();
+        let remote_hash = TransferManager::calculate_hash(b"remote").unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(state.local_hash, Some(remote_hash.clone()));
+        assert_eq!(state.remote_hash, Some(remote_hash));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "remote");
+        assert_eq!(*remote.downloads.lock().unwrap(), vec!["agents/a.md"]);
+
+        // 同步目录外的路径被拒绝
+        let outside = temp_dir.path().join("outside.md");
+        std::fs::write(&outside, "x").unwrap();
+        assert!(engine
+            .force_sync_file(&remote, &outside, ForcedDirection::Push)
+            .await
+            .is_err());
+    }
+
+    fn create_direction_engine(temp_dir: &Path, direction: SyncDirection) -> SyncEngine {
+        let claude_dir = temp_dir.join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        engine_with_config(
+            &claude_dir,
+            temp_dir.join("state.json"),
+            |config| config.sync.direction = direction,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_pull_direction_ignores_local_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = create_direction_engine(temp_dir.path(), SyncDirection::Pull);
+        let claude_dir = temp_dir.path().join("claude");
+
+        // 远程变更照常应用
+        let remote = MockRemote::default();
+        remote.push(1, "agents/a.md", "remote a");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.synced_count, 1);
+        let file = claude_dir.join("agents").join("a.md");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "remote a");
+
+        // 本地修改不触发上传
+        let mut progress = engine.subscribe_progress();
+        std::fs::write(&file, "local edit").unwrap();
+        engine
+            .handle_file_event(FileEvent {
+                path: file.clone(),
+                event_type: FileEventType::Modify,
+                timestamp: Utc::now(),
+                is_dir: false,
+            })
+            .await
+            .unwrap();
+        std::fs::write(claude_dir.join("new.md"), "new").unwrap();
+        let summary = engine.run_full_sync().await.unwrap();
+
+        assert!(progress.try_recv().is_err());
+        assert_eq!(summary.synced_count, 0);
+        assert_eq!(summary.skipped_count, 2);
+        let state = engine.get_sync_state(&file).await.unwrap();
+        assert_eq!(
+            state.remote_hash,
+            Some(TransferManager::calculate_hash(b"remote a").unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_direction_skips_remote_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let engine = create_direction_engine(temp_dir.path(), SyncDirection::Push);
+
+        let remote = MockRemote::default();
+        remote.push(1, "agents/a.md", "remote a");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.synced_count, 0);
+        assert!(remote.requested_cursors.lock().unwrap().is_empty());
+        assert_eq!(engine.version_cursor(), 0);
+        assert!(!temp_dir.path().join("claude").join("agents").exists());
+    }
+
+    #[tokio::test]
+    async fn test_additional_watch_dir_syncs_under_root_id() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let team_dir = temp_dir.path().join("team");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::create_dir_all(team_dir.join("cache")).unwrap();
+        std::fs::write(team_dir.join("shared.md"), "shared").unwrap();
+        std::fs::write(team_dir.join("cache").join("c.json"), "{}").unwrap();
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config.sync.exclude_dirs = vec!["cache".to_string()];
+                config.sync.additional_watch_dirs = vec![team_dir.clone()];
+            },
+            None,
+        );
+
+        // 全量同步扫描附加目录，排除目录同样生效
+        let summary = engine.run_full_sync().await.unwrap();
+        assert_eq!(summary.synced_count, 1);
+        assert!(engine
+            .get_sync_state(&team_dir.join("shared.md"))
+            .await
+            .is_some());
+
+        // 远程变更按目录标识写回附加目录
+        let remote = MockRemote::default();
+        remote.push(1, "@team/remote.md", "from another device");
+        engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(team_dir.join("remote.md")).unwrap(),
+            "from another device"
+        );
+        assert!(!claude_dir.join("@team").exists());
+    }
+
+    #[tokio::test]
+    async fn test_apply_remote_changes_after_cursor() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let state_file = temp_dir.path().join("state.json");
+
+        let remote = MockRemote::default();
+        remote.push(3, "agents/a.md", "remote a");
+        remote.push(7, "settings.json", "{}");
+
+        let engine = create_engine(&claude_dir, state_file.clone());
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.synced_count, 2);
+        assert_eq!(engine.version_cursor(), 7);
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("agents").join("a.md")).unwrap(),
+            "remote a"
+        );
+
+        // 已下载且未修改的文件不会被重新上传
+        let mut progress = engine.subscribe_progress();
+        let state = engine
+            .sync_file(&claude_dir.join("agents").join("a.md"))
+            .await
+            .unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert!(progress.try_recv().is_err());
+
+        // 游标持久化到状态快照
+        engine.save_snapshot().await.unwrap();
+        drop(engine);
+        let engine = create_engine(&claude_dir, state_file);
+        engine.load_snapshot().await.unwrap();
+        assert_eq!(engine.version_cursor(), 7);
+
+        // 只应用游标之后的变更
+        remote.push(9, "skills/b.md", "remote b");
+        remote.downloads.lock().unwrap().clear();
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(*remote.requested_cursors.lock().unwrap(), vec![0, 7]);
+        assert_eq!(*remote.downloads.lock().unwrap(), vec!["skills/b.md"]);
+        assert_eq!(engine.version_cursor(), 9);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_apply_multiple_versions_of_same_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let remote = MockRemote::default();
+        remote.push(2, "agents/a.md", "v1");
+        remote.push(3, "settings.json", "{}");
+        remote.push(5, "agents/a.md", "v2");
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 只下载最新版本，游标越过全部变更
+        assert_eq!(summary.failed_count, 0);
+        assert_eq!(summary.synced_count, 2);
+        assert_eq!(
+            *remote.downloads.lock().unwrap(),
+            vec!["settings.json", "agents/a.md"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("agents").join("a.md")).unwrap(),
+            "v2"
+        );
+        assert_eq!(engine.version_cursor(), 5);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_change_conflicts_with_local_edit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let file = claude_dir.join("a.md");
+
+        let remote = MockRemote::default();
+        remote.push(1, "a.md", "v1");
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 本地修改后只上传本地变更
+        std::fs::write(&file, "local edit").unwrap();
+        let mut progress = engine.subscribe_progress();
+        engine.sync_file(&file).await.unwrap();
+        assert!(progress.try_recv().is_ok());
+
+        // 两端都修改时不覆盖本地文件
+        std::fs::write(&file, "another local edit").unwrap();
+        remote.push(2, "a.md", "v2");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.conflict_count, 1);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "another local edit"
+        );
+        assert_eq!(engine.version_cursor(), 2);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_keep_both_saves_remote_version_beside_local() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let file = claude_dir.join("settings.json");
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config
+                    .conflict
+                    .per_type_strategy
+                    .insert("json".to_string(), "keep_both".to_string());
+            },
+            None,
+        );
+
+        let remote = MockRemote::default();
+        remote.push(1, "settings.json", "{\"theme\": \"light\"}");
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 两端都修改后保留两个版本
+        std::fs::write(&file, "{\"theme\": \"dark\"}").unwrap();
+        remote.push(2, "settings.json", "{\"theme\": \"solarized\"}");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.conflict_count, 0);
+
+        let copy = claude_dir.join("settings (remote).json");
+        assert_eq!(keep_both_path(&file), copy);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "{\"theme\": \"dark\"}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&copy).unwrap(),
+            "{\"theme\": \"solarized\"}"
+        );
 
-        let status3 = SyncStatus::Synced;
-        assert_ne!(status1, status3);
+        // 两个文件都已解决，等待上传
+        for path in [&file, &copy] {
+            let state = engine.get_sync_state(path).await.unwrap();
+            assert_eq!(state.status, SyncStatus::Pending);
+        }
+        assert!(engine.unresolved_conflicts().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keep_both_without_remote_content_records_conflict() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let file = claude_dir.join("settings.json");
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config.conflict.conflict_dir = temp_dir.path().join("conflicts");
+                config
+                    .conflict
+                    .per_type_strategy
+                    .insert("json".to_string(), "keep_both".to_string());
+            },
+            None,
+        );
+        create_conflict(&engine, &file).await;
+
+        // 本地同步路径拿不到远程内容，不写出也不上传空的远程副本
+        let state = engine.sync_file(&file).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Conflict);
+        assert!(!keep_both_path(&file).exists());
+        assert_eq!(engine.uploaded_bytes.load(Ordering::Relaxed), 0);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "local");
+    }
+
+    #[tokio::test]
+    async fn test_keep_newer_compares_modification_times() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let file = claude_dir.join("settings.json");
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config
+                    .conflict
+                    .per_type_strategy
+                    .insert("json".to_string(), "keep_newer".to_string());
+            },
+            None,
+        );
+
+        let remote = MockRemote::default();
+        remote.push(1, "settings.json", "{\"theme\": \"light\"}");
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 远程版本早于本地修改：保留本地并等待上传
+        std::fs::write(&file, "{\"theme\": \"dark\"}").unwrap();
+        remote.push(2, "settings.json", "{\"theme\": \"solarized\"}");
+        remote.modified_at.lock().unwrap().insert(2, 1);
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.conflict_count, 0);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "{\"theme\": \"dark\"}"
+        );
+        let state = engine.get_sync_state(&file).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Pending);
+
+        // 远程版本晚于本地修改：覆盖本地
+        let future = (Utc::now() + chrono::Duration::days(1)).timestamp();
+        remote.push(3, "settings.json", "{\"theme\": \"nord\"}");
+        remote.modified_at.lock().unwrap().insert(3, future);
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.conflict_count, 0);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "{\"theme\": \"nord\"}"
+        );
+
+        // 远程时间未知时无法比较，记为冲突
+        std::fs::write(&file, "{\"theme\": \"mine\"}").unwrap();
+        remote.push(4, "settings.json", "{\"theme\": \"theirs\"}");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.conflict_count, 1);
+    }
+
+    /// 模拟变更上报：记录每次上报的变更
+    #[derive(Default)]
+    struct MockReporter {
+        batches: std::sync::Mutex<Vec<Vec<FileChange>>>,
+    }
+
+    impl ChangeReporter for MockReporter {
+        async fn report(
+            &self,
+            changes: Vec<FileChange>,
+        ) -> Result<crate::grpc_client::ReportChangesResponse> {
+            self.batches.lock().unwrap().push(changes);
+            Ok(crate::grpc_client::ReportChangesResponse {
+                success: true,
+                message: String::new(),
+                conflicts_detected: vec![],
+                pending_uploads: vec![],
+            })
+        }
+    }
+
+    fn create_deletion_engine(temp_dir: &Path, keep_conflict_copy: bool) -> SyncEngine {
+        let claude_dir = temp_dir.join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        engine_with_config(
+            &claude_dir,
+            temp_dir.join("state.json"),
+            |config| {
+                config.conflict.conflict_dir = temp_dir.join("conflicts");
+                config.conflict.keep_conflict_copy = keep_conflict_copy;
+            },
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_local_deletion_is_pushed_as_tombstone() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let file = claude_dir.join("agents").join("a.md");
+
+        let engine = create_deletion_engine(temp_dir.path(), false);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "v1").unwrap();
+        engine.sync_file(&file).await.unwrap();
+
+        // 从未同步过的文件删除时不需要通知服务器
+        let unsynced = claude_dir.join("draft.md");
+        engine.handle_file_removal(&unsynced).await.unwrap();
+
+        std::fs::remove_file(&file).unwrap();
+        engine
+            .handle_file_event(FileEvent {
+                path: file.clone(),
+                event_type: FileEventType::Remove,
+                timestamp: Utc::now(),
+                is_dir: false,
+            })
+            .await
+            .unwrap();
+
+        // 墓碑随状态快照持久化
+        engine.close().await.unwrap();
+        drop(engine);
+        let engine = create_deletion_engine(temp_dir.path(), false);
+        engine.load_snapshot().await.unwrap();
+        let tombstones = engine.pending_tombstones().await;
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].file_path, "agents/a.md");
+
+        let reporter = MockReporter::default();
+        assert_eq!(engine.push_tombstones(&reporter).await.unwrap(), 1);
+        assert_eq!(engine.push_tombstones(&reporter).await.unwrap(), 0);
+        assert!(engine.pending_tombstones().await.is_empty());
+        engine.close().await.unwrap();
+
+        let batches = reporter.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        let change = &batches[0][0];
+        assert_eq!(change.file_path, "agents/a.md");
+        assert!(change.is_deleted);
+        assert_eq!(
+            change.file_hash,
+            TransferManager::calculate_hash(b"v1").unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_deletion_is_applied_locally() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        let remote = MockRemote::default();
+        remote.push(1, "agents/a.md", "v1");
+        remote.push(2, "b.md", "v1");
+
+        let engine = create_deletion_engine(temp_dir.path(), true);
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        remote.delete(3, "agents/a.md");
+        remote.delete(4, "missing.md");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.synced_count, 2);
+        assert_eq!(summary.conflict_count, 0);
+        let file = claude_dir.join("agents").join("a.md");
+        assert!(!file.exists());
+        assert!(engine.get_sync_state(&file).await.is_none());
+        assert!(claude_dir.join("b.md").exists());
+
+        // keep_conflict_copy 时删除的文件移入回收目录
+        let trash = temp_dir
+            .path()
+            .join("conflicts")
+            .join("trash")
+            .join("agents");
+        let names = entry_names(&trash);
+        assert_eq!(names.len(), 1);
+        assert!(names[0].starts_with("a.md."));
+        assert_eq!(
+            std::fs::read_to_string(trash.join(&names[0])).unwrap(),
+            "v1"
+        );
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_remote_deletion_conflicts_with_local_edit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("claude").join("a.md");
+        let remote = MockRemote::default();
+        remote.push(1, "a.md", "v1");
+
+        let engine = create_deletion_engine(temp_dir.path(), false);
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        std::fs::write(&file, "local edit").unwrap();
+        remote.delete(2, "a.md");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.conflict_count, 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "local edit");
+        let state = engine.get_sync_state(&file).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Conflict);
+        assert_eq!(
+            state.error_message.as_deref(),
+            Some("本地修改与远程删除冲突")
+        );
+        engine.close().await.unwrap();
+    }
+
+    /// 按指定冲突策略和 safe_delete 设置创建引擎
+    fn create_safe_delete_engine(
+        temp_dir: &Path,
+        strategy: ResolutionStrategy,
+        safe_delete: bool,
+    ) -> SyncEngine {
+        let claude_dir = temp_dir.join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        engine_with_config(
+            &claude_dir,
+            temp_dir.join("state.json"),
+            |config| {
+                config.sync.safe_delete = safe_delete;
+                config.conflict.default_strategy = strategy.as_str().to_string();
+                config.conflict.conflict_dir = temp_dir.join("conflicts");
+                config.conflict.keep_conflict_copy = false;
+            },
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_safe_delete_removes_unmodified_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("claude").join("a.md");
+        let remote = MockRemote::default();
+        remote.push(1, "a.md", "v1");
+
+        let engine =
+            create_safe_delete_engine(temp_dir.path(), ResolutionStrategy::KeepLocal, true);
+        engine.apply_remote_changes(&remote).await.unwrap();
+        assert!(file.exists());
+
+        // 本地内容与上次同步的哈希一致，直接删除
+        remote.delete(2, "a.md");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.conflict_count, 0);
+        assert!(!file.exists());
+        assert!(engine.get_sync_state(&file).await.is_none());
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_safe_delete_protects_unsynced_local_changes() {
+        for safe_delete in [true, false] {
+            let temp_dir = tempfile::tempdir().unwrap();
+            let claude_dir = temp_dir.path().join("claude");
+            let remote = MockRemote::default();
+            remote.push(1, "a.md", "v1");
+
+            // 保留本地策略下清空的文件被解决为空内容，未启用安全删除时会按远程删除移除
+            let engine = create_safe_delete_engine(
+                temp_dir.path(),
+                ResolutionStrategy::KeepLocal,
+                safe_delete,
+            );
+            engine.apply_remote_changes(&remote).await.unwrap();
+            let edited = claude_dir.join("a.md");
+            std::fs::write(&edited, "").unwrap();
+            // 本地新建、从未同步过的同名文件
+            let unsynced = claude_dir.join("b.md");
+            std::fs::write(&unsynced, "").unwrap();
+
+            remote.delete(2, "a.md");
+            remote.delete(3, "b.md");
+            let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+            for file in [&edited, &unsynced] {
+                if safe_delete {
+                    assert!(file.exists(), "{:?}", file);
+                    let state = engine.get_sync_state(file).await.unwrap();
+                    assert_eq!(state.status, SyncStatus::Conflict);
+                    assert_eq!(
+                        state.error_message.as_deref(),
+                        Some("本地修改与远程删除冲突")
+                    );
+                } else {
+                    assert!(!file.exists(), "{:?}", file);
+                }
+            }
+            if safe_delete {
+                assert_eq!(summary.conflict_count, 2);
+            }
+            engine.close().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_edit_conflicts_with_local_deletion() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("claude").join("a.md");
+        let remote = MockRemote::default();
+        remote.push(1, "a.md", "v1");
+
+        let engine = create_deletion_engine(temp_dir.path(), true);
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 本地删除尚未推送时，另一台设备修改了同一文件
+        std::fs::remove_file(&file).unwrap();
+        engine.handle_file_removal(&file).await.unwrap();
+        remote.push(2, "a.md", "remote edit");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.conflict_count, 1);
+        assert!(!file.exists());
+        // 不再推送删除，远程修改保存在冲突目录中
+        assert!(engine.pending_tombstones().await.is_empty());
+        let names = entry_names(&temp_dir.path().join("conflicts"));
+        let remote_copy = names
+            .iter()
+            .find(|name| name.starts_with("a.md.remote."))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("conflicts").join(remote_copy)).unwrap(),
+            "remote edit"
+        );
+        engine.close().await.unwrap();
+    }
+
+    fn create_case_engine(
+        claude_dir: &Path,
+        state_file: PathBuf,
+        case_collision: &str,
+    ) -> SyncEngine {
+        engine_with_config(
+            claude_dir,
+            state_file,
+            |config| config.sync.case_collision = case_collision.to_string(),
+            None,
+        )
+    }
+
+    /// 目录下实际存在的文件名（不受文件系统大小写折叠影响）
+    fn entry_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_case_only_remote_path_is_flagged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        // 设备 A 上传 agents/helper.md，设备 B 上传 Agents/helper.md
+        let remote = MockRemote::default();
+        remote.push(1, "agents/helper.md", "device a");
+        let engine = create_case_engine(&claude_dir, temp_dir.path().join("state.json"), "flag");
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        remote.push(2, "Agents/helper.md", "device b");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        assert_eq!(summary.conflict_count, 1);
+        assert_eq!(
+            summary.conflicts,
+            vec![claude_dir.join("Agents").join("helper.md")]
+        );
+        assert_eq!(entry_names(&claude_dir), vec!["agents"]);
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("agents").join("helper.md")).unwrap(),
+            "device a"
+        );
+        assert_eq!(*remote.downloads.lock().unwrap(), vec!["agents/helper.md"]);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_case_only_remote_path_is_merged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let remote = MockRemote::default();
+        remote.push(1, "agents/helper.md", "device a");
+        let engine = create_case_engine(&claude_dir, temp_dir.path().join("state.json"), "merge");
+        engine.apply_remote_changes(&remote).await.unwrap();
+
+        remote.push(2, "Agents/helper.md", "device b");
+        let summary = engine.apply_remote_changes(&remote).await.unwrap();
+
+        // 远程变更写入本地已有的拼写，不产生重复目录
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.conflict_count, 0);
+        assert_eq!(entry_names(&claude_dir), vec!["agents"]);
+        assert_eq!(
+            std::fs::read_to_string(claude_dir.join("agents").join("helper.md")).unwrap(),
+            "device b"
+        );
+        assert_eq!(engine.version_cursor(), 2);
+        engine.close().await.unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_full_sync_flags_local_case_collisions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        std::fs::create_dir_all(claude_dir.join("Agents")).unwrap();
+        std::fs::write(claude_dir.join("agents").join("a.md"), "lower").unwrap();
+        std::fs::write(claude_dir.join("Agents").join("a.md"), "upper").unwrap();
+
+        let engine = create_case_engine(&claude_dir, temp_dir.path().join("state.json"), "flag");
+        let summary = engine.run_full_sync().await.unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.conflict_count, 1);
+        assert_eq!(
+            summary.conflicts,
+            vec![claude_dir.join("agents").join("a.md")]
+        );
+        engine.close().await.unwrap();
+
+        let engine = create_case_engine(&claude_dir, temp_dir.path().join("state2.json"), "merge");
+        let summary = engine.run_full_sync().await.unwrap();
+
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.conflict_count, 0);
+        assert_eq!(summary.skipped_count, 1);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_legacy_snapshot() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let state_file = temp_dir.path().join("state.json");
+        std::fs::write(
+            &state_file,
+            r#"[{"path":"a.md","local_hash":"h","remote_hash":"h","status":"Synced","last_sync_time":null,"error_message":null}]"#,
+        )
+        .unwrap();
+
+        let engine = create_engine(&claude_dir, state_file);
+        assert_eq!(engine.load_snapshot().await.unwrap(), 1);
+        assert_eq!(engine.version_cursor(), 0);
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_queues_events_until_resume() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let file = claude_dir.join("a.md");
+
+        let engine = Arc::new(create_engine(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+        ));
+        let control = engine.control().clone();
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+
+        let runner = {
+            let engine = engine.clone();
+            tokio::spawn(async move { engine.start_incremental_sync(event_rx).await })
+        };
+
+        // 暂停后编辑文件，事件不会被处理
+        control.pause();
+        std::fs::write(&file, "edited while paused").unwrap();
+        event_tx
+            .send(FileEvent {
+                path: file.clone(),
+                event_type: FileEventType::Modify,
+                timestamp: Utc::now(),
+                is_dir: false,
+            })
+            .unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(engine.get_sync_state(&file).await.is_none());
+
+        // 恢复后补发排队的事件
+        control.resume();
+        let mut synced = false;
+        for _ in 0..50 {
+            if let Some(state) = engine.get_sync_state(&file).await {
+                assert_eq!(state.status, SyncStatus::Synced);
+                synced = true;
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(synced, "恢复后事件应被处理");
+
+        drop(event_tx);
+        runner.await.unwrap().unwrap();
+        engine.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_estimate_upload_counts_new_and_modified_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let new_file = claude_dir.join("new.md");
+        let synced = claude_dir.join("synced.md");
+        let modified = claude_dir.join("modified.md");
+        std::fs::write(&synced, vec![b's'; 20]).unwrap();
+        std::fs::write(&modified, vec![b'm'; 30]).unwrap();
+
+        let engine = create_engine(&claude_dir, temp_dir.path().join("state.json"));
+        for path in [&synced, &modified] {
+            engine.sync_file(path).await.unwrap();
+        }
+        std::fs::write(&new_file, vec![b'n'; 10]).unwrap();
+        std::fs::write(&modified, vec![b'm'; 35]).unwrap();
+
+        // 已同步且未修改的文件不计入
+        let files = vec![new_file.clone(), synced.clone(), modified.clone()];
+        let estimate = engine.estimate_upload(&files).await;
+        assert_eq!(
+            estimate,
+            UploadEstimate {
+                files: 2,
+                bytes: 45
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_total_upload_aborts_before_uploading() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::write(claude_dir.join("a.md"), vec![b'a'; 30]).unwrap();
+        std::fs::write(claude_dir.join("b.md"), vec![b'b'; 30]).unwrap();
+
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.sync.max_total_upload = Some(50),
+            None,
+        );
+
+        let error = engine.run_full_sync().await.unwrap_err();
+        let error = error.downcast_ref::<ClientError>().unwrap();
+        assert_eq!(error.error_code(), "QUOTA_EXCEEDED");
+        assert!(engine
+            .get_sync_state(&claude_dir.join("a.md"))
+            .await
+            .is_none());
+        assert!(engine
+            .get_sync_state(&claude_dir.join("b.md"))
+            .await
+            .is_none());
+    }
+
+    /// 模拟服务器配额：已用量超过上限后拒绝上传
+    struct QuotaTarget {
+        limit: u64,
+        uploaded: std::sync::Mutex<Vec<(String, u64)>>,
+    }
+
+    impl UploadTarget for QuotaTarget {
+        fn upload(
+            &self,
+            request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            let remote_path = request.remote_path.clone();
+            let size = request.file_size;
+            Box::pin(async move {
+                // 与服务器一样按声明的大小检查配额
+                let used: u64 = self.uploaded.lock().unwrap().iter().map(|(_, s)| s).sum();
+                if used + size > self.limit {
+                    return Err(ClientError::quota_exceeded(
+                        "用户存储空间已满",
+                        Some(used),
+                        Some(self.limit),
+                    )
+                    .into());
+                }
+                while let Some(chunk) = chunks.recv().await {
+                    chunk?;
+                }
+                let mut uploaded = self.uploaded.lock().unwrap();
+                uploaded.push((remote_path, size));
+                Ok(uploaded.len() as i64)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quota_exceeded_stops_remaining_uploads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        for name in ["a.md", "b.md", "c.md"] {
+            std::fs::write(claude_dir.join(name), vec![b'x'; 40]).unwrap();
+        }
+
+        let target = Arc::new(QuotaTarget {
+            limit: 50,
+            uploaded: std::sync::Mutex::new(Vec::new()),
+        });
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.performance.max_concurrent_uploads = 1,
+            Some(target.clone()),
+        );
+
+        let summary = engine.run_full_sync().await.unwrap();
+        assert_eq!(summary.synced_count, 1);
+        assert_eq!(summary.failed_count, 1);
+        assert_eq!(
+            summary.skipped,
+            vec![(claude_dir.join("c.md"), QUOTA_SKIP_REASON.to_string())]
+        );
+        assert_eq!(summary.errors.len(), 1);
+        assert_eq!(summary.errors[0].0, claude_dir.join("b.md"));
+        assert!(summary.errors[0].1.contains("40"));
+        assert_eq!(
+            *target.uploaded.lock().unwrap(),
+            vec![("a.md".to_string(), 40)]
+        );
+
+        // 未上传的文件不记录同步状态，本地内容保持不变
+        let uploaded = engine
+            .get_sync_state(&claude_dir.join("a.md"))
+            .await
+            .unwrap();
+        assert_eq!(uploaded.status, SyncStatus::Synced);
+        for name in ["b.md", "c.md"] {
+            let path = claude_dir.join(name);
+            assert!(engine.get_sync_state(&path).await.is_none());
+            assert_eq!(std::fs::read(&path).unwrap(), vec![b'x'; 40]);
+        }
+    }
+
+    /// 记录上传元数据中 MIME 类型的模拟服务器
+    #[derive(Default)]
+    struct ContentTypeTarget {
+        content_types: std::sync::Mutex<HashMap<String, String>>,
+    }
+
+    impl UploadTarget for ContentTypeTarget {
+        fn upload(
+            &self,
+            request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            let request = request.clone();
+            Box::pin(async move {
+                while let Some(chunk) = chunks.recv().await {
+                    chunk?;
+                }
+                let mut content_types = self.content_types.lock().unwrap();
+                content_types.insert(request.remote_path, request.content_type);
+                Ok(content_types.len() as i64)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_sends_detected_content_type() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        std::fs::write(claude_dir.join("CLAUDE.md"), "# 项目说明\n").unwrap();
+        std::fs::write(claude_dir.join("settings.json"), "{}").unwrap();
+        std::fs::write(claude_dir.join("icon.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        std::fs::write(claude_dir.join("NOTES"), "plain text").unwrap();
+        std::fs::write(claude_dir.join("blob"), [0x00, 0xff, 0xfe]).unwrap();
+
+        let target = Arc::new(ContentTypeTarget::default());
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.sync.include_types.clear(),
+            Some(target.clone()),
+        );
+        engine.run_full_sync().await.unwrap();
+
+        let content_types = target.content_types.lock().unwrap().clone();
+        let expected: HashMap<String, String> = [
+            ("CLAUDE.md", "text/markdown"),
+            ("settings.json", "application/json"),
+            ("icon.png", "image/png"),
+            ("NOTES", "text/plain"),
+            ("blob", "application/octet-stream"),
+        ]
+        .into_iter()
+        .map(|(path, mime)| (path.to_string(), mime.to_string()))
+        .collect();
+        assert_eq!(content_types, expected);
+    }
+
+    /// 记录上传内容和完成时间的模拟服务器
+    #[derive(Default)]
+    struct RecordingTarget {
+        uploads: std::sync::Mutex<Vec<(Vec<u8>, std::time::Instant)>>,
+    }
+
+    impl UploadTarget for RecordingTarget {
+        fn upload(
+            &self,
+            _request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            Box::pin(async move {
+                let mut content = Vec::new();
+                while let Some(chunk) = chunks.recv().await {
+                    content.extend(chunk?.data);
+                }
+                let mut uploads = self.uploads.lock().unwrap();
+                uploads.push((content, std::time::Instant::now()));
+                Ok(uploads.len() as i64)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uploaded_changes_are_batch_reported() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let path = claude_dir.join("CLAUDE.md");
+        std::fs::write(&path, "# 项目说明\n").unwrap();
+
+        let (change_tx, change_rx) = mpsc::unbounded_channel();
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.performance.hash_algorithm = HashAlgorithm::Blake3,
+            Some(Arc::new(RecordingTarget::default())),
+        )
+        .with_change_reports(change_tx);
+        let roots = engine.config.sync_roots();
+
+        engine.sync_file(&path).await.unwrap();
+        drop(engine);
+
+        let mock = Arc::new(MockReporter::default());
+        let reporter = crate::reporter::BatchReporter::new(
+            mock.clone(),
+            roots,
+            std::time::Duration::from_millis(10),
+        )
+        .with_hash_algorithm(HashAlgorithm::Blake3);
+        assert_eq!(reporter.run(change_rx).await, 1);
+
+        let batches = mock.batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0][0].file_path, "CLAUDE.md");
+        assert_eq!(
+            batches[0][0].file_hash,
+            HashAlgorithm::Blake3.hash("# 项目说明\n".as_bytes())
+        );
+    }
+
+    fn modify_event(path: &Path) -> FileEvent {
+        FileEvent {
+            path: path.to_path_buf(),
+            event_type: FileEventType::Modify,
+            timestamp: Utc::now(),
+            is_dir: false,
+        }
+    }
+
+    /// 通过增量同步处理一组文件事件，等待中的文件全部处理后返回
+    async fn run_file_events(engine: &SyncEngine, events: Vec<FileEvent>) {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        for event in events {
+            event_tx.send(event).unwrap();
+        }
+        drop(event_tx);
+        engine.start_incremental_sync(event_rx).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_waits_until_file_stops_changing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let path = claude_dir.join("CLAUDE.md");
+        std::fs::write(&path, "# 项目说明\n").unwrap();
+
+        let target = Arc::new(RecordingTarget::default());
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.sync.write_settle_window = 100,
+            Some(target.clone()),
+        );
+
+        // 模拟编辑器分多次写入，防抖结束时文件仍在变化
+        let writer = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                for line in 0..10 {
+                    let mut file = std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(&path)
+                        .unwrap();
+                    std::io::Write::write_all(&mut file, format!("line {}\n", line).as_bytes())
+                        .unwrap();
+                    tokio::time::sleep(std::time::Duration::from_millis(30)).await;
+                }
+                std::time::Instant::now()
+            })
+        };
+
+        run_file_events(&engine, vec![modify_event(&path)]).await;
+        let finished_writing = writer.await.unwrap();
+
+        // 只在写入结束后上传一次完整内容
+        let uploads = target.uploads.lock().unwrap();
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].0, std::fs::read(&path).unwrap());
+        assert!(uploads[0].1 >= finished_writing);
+    }
+
+    #[tokio::test]
+    async fn test_upload_keeps_waiting_after_settle_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let path = claude_dir.join("CLAUDE.md");
+        std::fs::write(&path, "# 项目说明\n").unwrap();
+
+        let target = Arc::new(RecordingTarget::default());
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config.sync.write_settle_window = 60;
+                config.sync.write_settle_max_wait = 100;
+            },
+            Some(target.clone()),
+        );
+
+        // 写入持续时间超过最长等待时间
+        let writer = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                for line in 0..15 {
+                    let mut file = std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(&path)
+                        .unwrap();
+                    std::io::Write::write_all(&mut file, format!("line {}\n", line).as_bytes())
+                        .unwrap();
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+                std::time::Instant::now()
+            })
+        };
+
+        run_file_events(&engine, vec![modify_event(&path)]).await;
+        let finished_writing = writer.await.unwrap();
+
+        // 等待超时后没有丢弃事件，写入结束后上传完整内容
+        let uploads = target.uploads.lock().unwrap();
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].0, std::fs::read(&path).unwrap());
+        assert!(uploads[0].1 >= finished_writing);
+    }
+
+    #[tokio::test]
+    async fn test_unsettled_file_does_not_delay_other_uploads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let session = claude_dir.join("session.md");
+        let notes = claude_dir.join("notes.md");
+        std::fs::write(&session, "").unwrap();
+
+        let target = Arc::new(RecordingTarget::default());
+        let engine = Arc::new(engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| {
+                config.sync.write_settle_window = 60;
+                config.sync.write_settle_max_wait = 100;
+            },
+            Some(target.clone()),
+        ));
+
+        // 会话日志持续追加，远超过最长等待时间和重试次数
+        let writing = Arc::new(AtomicBool::new(true));
+        let writer = {
+            let session = session.clone();
+            let writing = writing.clone();
+            tokio::spawn(async move {
+                while writing.load(Ordering::SeqCst) {
+                    let mut file = std::fs::OpenOptions::new()
+                        .append(true)
+                        .open(&session)
+                        .unwrap();
+                    std::io::Write::write_all(&mut file, b"{}\n").unwrap();
+                    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                }
+            })
+        };
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let sync_task = {
+            let engine = engine.clone();
+            tokio::spawn(async move { engine.start_incremental_sync(event_rx).await })
+        };
+        event_tx.send(modify_event(&session)).unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        std::fs::write(&notes, "notes").unwrap();
+        event_tx.send(modify_event(&notes)).unwrap();
+
+        // 其他文件不等待会话日志，稳定后立即上传
+        let uploaded = |content: &[u8]| {
+            target
+                .uploads
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|(uploaded, _)| uploaded == content)
+        };
+        tokio::time::timeout(std::time::Duration::from_millis(500), async {
+            while !uploaded(b"notes") {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("其他文件被持续写入的文件阻塞");
+
+        // 重试次数用尽后上传持续写入文件的当前内容
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while target.uploads.lock().unwrap().len() < 2 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("持续写入的文件未在重试次数用尽后上传");
+        assert!(writing.load(Ordering::SeqCst));
+
+        writing.store(false, Ordering::SeqCst);
+        writer.await.unwrap();
+        drop(event_tx);
+        sync_task.await.unwrap().unwrap();
+        assert_eq!(target.uploads.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_switching_hash_algorithm_keeps_unchanged_files_synced() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let path = claude_dir.join("CLAUDE.md");
+        std::fs::write(&path, "# 项目说明\n").unwrap();
+
+        let target = Arc::new(RecordingTarget::default());
+        let engine_with_algorithm = |algorithm: HashAlgorithm| {
+            engine_with_config(
+                &claude_dir,
+                temp_dir.path().join("state.json"),
+                |config| config.performance.hash_algorithm = algorithm,
+                Some(target.clone()),
+            )
+        };
+
+        // 使用 SHA-256 同步并保存状态
+        let engine = engine_with_algorithm(HashAlgorithm::Sha256);
+        let state = engine.sync_file(&path).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        let sha256 = state.local_hash.unwrap();
+        assert_eq!(HashAlgorithm::of(&sha256), HashAlgorithm::Sha256);
+        engine.save_snapshot().await.unwrap();
+        assert_eq!(target.uploads.lock().unwrap().len(), 1);
+
+        // 切换到 BLAKE3 后，未修改的文件按记录哈希的算法比较，不会重新上传
+        let engine = engine_with_algorithm(HashAlgorithm::Blake3);
+        engine.load_snapshot().await.unwrap();
+        let state = engine.sync_file(&path).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(target.uploads.lock().unwrap().len(), 1);
+        let report = engine.verify().await.unwrap();
+        assert_eq!(report.verified, 1);
+        assert!(report.mismatched.is_empty());
+
+        // 修改后的文件被检测到，并以 BLAKE3 哈希上传
+        std::fs::write(&path, "# 项目说明\n\n新增内容\n").unwrap();
+        let state = engine.sync_file(&path).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        let blake3 = state.local_hash.unwrap();
+        assert_eq!(
+            blake3,
+            HashAlgorithm::Blake3.hash(&std::fs::read(&path).unwrap())
+        );
+        let uploads = target.uploads.lock().unwrap();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[1].0, std::fs::read(&path).unwrap());
+    }
+
+    /// 模拟可断开的服务器：离线时上传返回网络错误
+    struct FlakyTarget {
+        online: AtomicBool,
+        attempts: AtomicU64,
+        uploaded: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl UploadTarget for FlakyTarget {
+        fn upload(
+            &self,
+            request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            let remote_path = request.remote_path.clone();
+            Box::pin(async move {
+                self.attempts.fetch_add(1, Ordering::SeqCst);
+                if !self.online.load(Ordering::SeqCst) {
+                    return Err(ClientError::network("服务器不可用", None).into());
+                }
+                while let Some(chunk) = chunks.recv().await {
+                    chunk?;
+                }
+                let mut uploaded = self.uploaded.lock().unwrap();
+                uploaded.push(remote_path);
+                Ok(uploaded.len() as i64)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edits_while_offline_are_queued_and_replayed() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+
+        let target = Arc::new(FlakyTarget {
+            online: AtomicBool::new(false),
+            attempts: AtomicU64::new(0),
+            uploaded: std::sync::Mutex::new(Vec::new()),
+        });
+
+        // 健康检查端点，服务器恢复后才开始监听
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let health_address = format!("http://{}", listener.local_addr().unwrap());
+        let network = Arc::new(NetworkRecoveryManager::new(
+            "http://127.0.0.1:50051".to_string(),
+            health_address,
+            crate::retry::RetryConfig::default(),
+            0,
+            0,
+        ));
+
+        let engine = Arc::new(
+            engine_with_config(
+                &claude_dir,
+                temp_dir.path().join("state.json"),
+                |_| {},
+                Some(target.clone()),
+            )
+            .with_network(network.clone()),
+        );
+        let handler: Arc<dyn OfflineOperationHandler> = engine.clone();
+        network.set_offline_handler(Arc::downgrade(&handler));
+
+        // 离线期间的修改：第一次上传失败后标记离线，之后的修改直接排队
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let files: Vec<PathBuf> = ["a.md", "b.md", "c.md"]
+            .iter()
+            .map(|name| claude_dir.join(name))
+            .collect();
+        for path in &files {
+            std::fs::write(path, path.to_string_lossy().as_bytes()).unwrap();
+            event_tx
+                .send(FileEvent {
+                    path: path.clone(),
+                    event_type: FileEventType::Modify,
+                    timestamp: Utc::now(),
+                    is_dir: false,
+                })
+                .unwrap();
+        }
+        drop(event_tx);
+        engine.start_incremental_sync(event_rx).await.unwrap();
+
+        assert_eq!(target.attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(network.get_status().await, NetworkStatus::Offline);
+        assert_eq!(network.offline_queue_len().await, 3);
+        for path in &files {
+            assert!(engine.get_sync_state(path).await.is_none());
+        }
+
+        // 服务器恢复：网络监控重新连接后重放离线队列
+        target.online.store(true, Ordering::SeqCst);
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let _ = socket
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .await;
+            }
+        });
+        let monitor = network.clone().spawn_network_monitor();
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while network.offline_queue_len().await > 0
+                || target.uploaded.lock().unwrap().len() < files.len()
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("离线队列未在网络恢复后处理");
+        monitor.abort();
+
+        assert_eq!(network.get_status().await, NetworkStatus::Online);
+        for path in &files {
+            let state = engine.get_sync_state(path).await.unwrap();
+            assert_eq!(state.status, SyncStatus::Synced);
+        }
+        let mut uploaded = target.uploaded.lock().unwrap().clone();
+        uploaded.sort();
+        assert_eq!(uploaded, vec!["a.md", "b.md", "c.md"]);
+    }
+
+    #[tokio::test]
+    async fn test_ignored_file_edited_offline_is_not_uploaded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(&claude_dir).unwrap();
+        let notes = claude_dir.join("notes.md");
+        let secret = claude_dir.join("secret.md");
+
+        let target = Arc::new(FlakyTarget {
+            online: AtomicBool::new(false),
+            attempts: AtomicU64::new(0),
+            uploaded: std::sync::Mutex::new(Vec::new()),
+        });
+        let network = Arc::new(NetworkRecoveryManager::new(
+            "http://127.0.0.1:50051".to_string(),
+            "http://127.0.0.1:9".to_string(),
+            crate::retry::RetryConfig::default(),
+            0,
+            0,
+        ));
+        network.set_status(NetworkStatus::Offline).await;
+
+        let engine = Arc::new(
+            engine_with_config(
+                &claude_dir,
+                temp_dir.path().join("state.json"),
+                |_| {},
+                Some(target.clone()),
+            )
+            .with_network(network.clone()),
+        );
+        let handler: Arc<dyn OfflineOperationHandler> = engine.clone();
+        network.set_offline_handler(Arc::downgrade(&handler));
+
+        // 网络中断前已排队的修改，之后才被忽略文件排除
+        std::fs::write(&secret, "token").unwrap();
+        network
+            .queue_offline_operation(OfflineOperation::FileUpload {
+                path: secret.to_string_lossy().into_owned(),
+                hash: String::new(),
+                size: 5,
+            })
+            .await
+            .unwrap();
+        std::fs::write(claude_dir.join(IGNORE_FILE_NAME), "secret.md\n").unwrap();
+        engine.reload_rules().unwrap();
+
+        // 离线期间的修改：被忽略的文件不入队
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        for path in [&notes, &secret] {
+            std::fs::write(path, "offline edit").unwrap();
+            event_tx
+                .send(FileEvent {
+                    path: path.clone(),
+                    event_type: FileEventType::Modify,
+                    timestamp: Utc::now(),
+                    is_dir: false,
+                })
+                .unwrap();
+        }
+        drop(event_tx);
+        engine.start_incremental_sync(event_rx).await.unwrap();
+        assert_eq!(network.offline_queue_len().await, 2);
+
+        // 恢复后重放时再次检查规则，只上传未被忽略的文件
+        target.online.store(true, Ordering::SeqCst);
+        network.process_offline_queue().await.unwrap();
+        assert_eq!(network.offline_queue_len().await, 0);
+        assert_eq!(*target.uploaded.lock().unwrap(), vec!["notes.md"]);
+        assert!(engine.get_sync_state(&secret).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_rule_changes_upload_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("local-agents")).unwrap();
+        let agent = claude_dir.join("local-agents").join("reviewer.md");
+        std::fs::write(&agent, "review").unwrap();
+
+        let rule = crate::rules::SyncRule {
+            id: "rewrite-agents".to_string(),
+            name: "映射本地 agents".to_string(),
+            rule_type: crate::rules::RuleType::Include,
+            pattern: r"^local-agents/(.+)$".to_string(),
+            pattern_type: crate::rules::PatternType::Regex,
+            file_type: None,
+            priority: 10,
+            enabled: true,
+            description: None,
+            rewrite: Some("agents/$1".to_string()),
+        };
+        let target = Arc::new(QuotaTarget {
+            limit: u64::MAX,
+            uploaded: std::sync::Mutex::new(Vec::new()),
+        });
+        let engine = engine_with_config(
+            &claude_dir,
+            temp_dir.path().join("state.json"),
+            |config| config.sync.rules = vec![rule],
+            Some(target.clone()),
+        );
+
+        let state = engine.sync_file(&agent).await.unwrap();
+        assert_eq!(state.status, SyncStatus::Synced);
+        assert_eq!(
+            *target.uploaded.lock().unwrap(),
+            vec![("agents/reviewer.md".to_string(), 6)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_second_full_sync_reads_no_unchanged_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let claude_dir = temp_dir.path().join("claude");
+        std::fs::create_dir_all(claude_dir.join("agents")).unwrap();
+        for i in 0..50 {
+            std::fs::write(
+                claude_dir.join("agents").join(format!("{}.md", i)),
+                format!("agent {}", i).repeat(1000),
+            )
+            .unwrap();
+        }
+        let state_file = temp_dir.path().join("state.json");
+
+        let engine = create_engine(&claude_dir, state_file.clone());
+        let started = std::time::Instant::now();
+        let summary = engine.run_full_sync().await.unwrap();
+        let first = started.elapsed();
+        assert_eq!(summary.synced_count, 50);
+        assert_eq!(engine.hash_cache.file_reads(), 50);
+
+        // 未修改的文件第二次扫描不再读取
+        let started = std::time::Instant::now();
+        engine.run_full_sync().await.unwrap();
+        let second = started.elapsed();
+        assert_eq!(engine.hash_cache.file_reads(), 50);
+        println!("首次全量同步 {:?}，第二次 {:?}", first, second);
+
+        // 修改一个文件只重新读取该文件
+        std::fs::write(claude_dir.join("agents").join("0.md"), "edited").unwrap();
+        engine.run_full_sync().await.unwrap();
+        assert_eq!(engine.hash_cache.file_reads(), 51);
+        engine.close().await.unwrap();
+        assert!(temp_dir.path().join(HASH_CACHE_FILE_NAME).exists());
+
+        // 重启后从磁盘加载缓存，仍然不需要读取
+        let restarted = create_engine(&claude_dir, state_file);
+        restarted.load_snapshot().await.unwrap();
+        restarted.run_full_sync().await.unwrap();
+        assert_eq!(restarted.hash_cache.file_reads(), 0);
     }
 }
@@ -1,10 +1,14 @@
+use crate::config::{AuthConfig, TokenStoreKind};
+use crate::grpc_client::{GrpcClient, TokenRefreshResponse};
 use anyhow::{Context, Result};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::future::Future;
 use std::path::PathBuf;
-use tracing::{debug, info, warn};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 /// Token 存储结构
@@ -58,7 +62,62 @@ pub struct Claims {
     pub jti: Uuid,
 }
 
+/// 系统密钥环中 Token 条目的服务名
+pub const KEYRING_SERVICE: &str = "claude-sync";
+
+/// 密钥存储（系统密钥环，测试中可替换为内存实现）
+pub trait SecretStore: Send + Sync {
+    /// 读取密钥，条目不存在时返回 None
+    fn get(&self) -> Result<Option<String>>;
+
+    /// 写入密钥（覆盖已有条目）
+    fn set(&self, secret: &str) -> Result<()>;
+
+    /// 删除密钥（条目不存在时不报错）
+    fn delete(&self) -> Result<()>;
+}
+
+/// 系统密钥环中的一个条目
+pub struct KeyringStore {
+    entry: keyring::Entry,
+}
+
+impl KeyringStore {
+    /// 打开 service/user 对应的密钥环条目
+    pub fn new(service: &str, user: &str) -> Result<Self> {
+        let entry = keyring::Entry::new(service, user)
+            .with_context(|| format!("无法打开密钥环条目: {}/{}", service, user))?;
+        Ok(Self { entry })
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn get(&self) -> Result<Option<String>> {
+        match self.entry.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e).context("无法从密钥环读取 Token"),
+        }
+    }
+
+    fn set(&self, secret: &str) -> Result<()> {
+        self.entry
+            .set_password(secret)
+            .context("无法将 Token 写入密钥环")
+    }
+
+    fn delete(&self) -> Result<()> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e).context("无法从密钥环删除 Token"),
+        }
+    }
+}
+
 /// Token 管理器
+///
+/// 默认将 Token 保存为 token 目录下的 JSON 文件（可选加密）；
+/// 设置密钥环后改为保存到系统密钥环，密钥环中没有 Token 时仍读取旧的 Token 文件。
 pub struct TokenManager {
     /// Token 存储目录
     token_dir: PathBuf,
@@ -66,6 +125,9 @@ pub struct TokenManager {
     /// 加密密钥（可选）
     encryption_key: Option<String>,
 
+    /// 系统密钥环（设置后 Token 不再写入文件）
+    keyring: Option<Box<dyn SecretStore>>,
+
     /// JWT 密钥（用于解码，仅用于验证）
     #[allow(dead_code)]
     jwt_secret: String,
@@ -77,12 +139,47 @@ impl TokenManager {
         Self {
             token_dir,
             encryption_key,
+            keyring: None,
+            jwt_secret,
+        }
+    }
+
+    /// 按认证配置创建 Token 管理器（token_store = "keyring" 时使用系统密钥环）
+    pub fn from_config(auth: &AuthConfig, jwt_secret: String) -> Result<Self> {
+        let manager = Self::new(
+            auth.token_dir.clone(),
+            auth.encryption_key.clone(),
             jwt_secret,
+        );
+
+        match auth.token_store {
+            TokenStoreKind::File => Ok(manager),
+            TokenStoreKind::Keyring => {
+                // 不同 profile 的 token 目录不同，以目录区分密钥环条目
+                let user = auth.token_dir.to_string_lossy();
+                let keyring = KeyringStore::new(KEYRING_SERVICE, &user)?;
+                Ok(manager.with_keyring(Box::new(keyring)))
+            }
         }
     }
 
+    /// 设置系统密钥环
+    pub fn with_keyring(mut self, keyring: Box<dyn SecretStore>) -> Self {
+        self.keyring = Some(keyring);
+        self
+    }
+
     /// 保存 Token
     pub fn save_tokens(&self, tokens: TokenStorage) -> Result<()> {
+        if let Some(keyring) = &self.keyring {
+            let content = serde_json::to_string(&tokens).context("无法序列化 Token")?;
+            keyring.set(&content)?;
+            info!("Token 已保存到系统密钥环");
+
+            // 不在文件中保留旧的 Token
+            return self.delete_token_file();
+        }
+
         // 确保目录存在
         fs::create_dir_all(&self.token_dir)
             .with_context(|| format!("无法创建 token 目录: {:?}", self.token_dir))?;
@@ -110,6 +207,15 @@ impl TokenManager {
 
     /// 加载 Token
     pub fn load_tokens(&self) -> Result<TokenStorage> {
+        if let Some(keyring) = &self.keyring {
+            if let Some(content) = keyring.get()? {
+                let tokens: TokenStorage =
+                    serde_json::from_str(&content).context("无法解析密钥环中的 Token")?;
+                debug!("已从系统密钥环加载 Token");
+                return Ok(tokens);
+            }
+        }
+
         let token_file = self.token_file()?;
 
         if !token_file.exists() {
@@ -137,6 +243,16 @@ impl TokenManager {
 
     /// 删除 Token
     pub fn delete_tokens(&self) -> Result<()> {
+        if let Some(keyring) = &self.keyring {
+            keyring.delete()?;
+            info!("Token 已从系统密钥环删除");
+        }
+
+        self.delete_token_file()
+    }
+
+    /// 删除 Token 文件
+    fn delete_token_file(&self) -> Result<()> {
         let token_file = self.token_file()?;
 
         if token_file.exists() {
@@ -150,7 +266,16 @@ impl TokenManager {
 
     /// 检查 Token 是否存在
     pub fn has_tokens(&self) -> bool {
-        self.token_file().map(|p| p.exists()).unwrap_or(false)
+        let in_keyring = self.keyring.as_ref().is_some_and(|keyring| {
+            keyring
+                .get()
+                .unwrap_or_else(|e| {
+                    warn!("读取密钥环失败: {:#}", e);
+                    None
+                })
+                .is_some()
+        });
+        in_keyring || self.token_file().map(|p| p.exists()).unwrap_or(false)
     }
 
     /// 检查 Access Token 是否需要刷新
@@ -373,6 +498,91 @@ impl TokenManager {
     }
 }
 
+// ===== 自动刷新 =====
+
+/// 自动刷新的检查间隔（秒）
+const REFRESH_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Token 刷新接口（守护进程使用 GrpcClient，测试中可替换）
+pub trait TokenRefresher: Send + Sync {
+    /// 使用 Refresh Token 换取新的 Access Token
+    fn refresh(
+        &self,
+        refresh_token: String,
+    ) -> impl Future<Output = Result<TokenRefreshResponse>> + Send;
+}
+
+impl TokenRefresher for GrpcClient {
+    fn refresh(
+        &self,
+        refresh_token: String,
+    ) -> impl Future<Output = Result<TokenRefreshResponse>> + Send {
+        self.refresh_token(refresh_token)
+    }
+}
+
+/// 自动刷新结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshOutcome {
+    /// 未到刷新时间
+    NotNeeded,
+    /// 已刷新
+    Refreshed,
+    /// Refresh Token 已过期，需要重新登录
+    ReloginRequired,
+}
+
+/// 在 Access Token 即将过期时刷新并保存新的 Token
+pub async fn refresh_if_needed<R: TokenRefresher>(
+    token_manager: &TokenManager,
+    refresher: &R,
+    refresh_before: i64,
+) -> Result<RefreshOutcome> {
+    if token_manager.is_refresh_expired()? {
+        return Ok(RefreshOutcome::ReloginRequired);
+    }
+
+    if !token_manager.needs_refresh(refresh_before)? {
+        return Ok(RefreshOutcome::NotNeeded);
+    }
+
+    let refresh_token = token_manager.get_refresh_token()?;
+    let response = refresher
+        .refresh(refresh_token)
+        .await
+        .context("刷新 Access Token 失败")?;
+
+    token_manager.update_access_token(response.access_token, response.expires_at)?;
+
+    Ok(RefreshOutcome::Refreshed)
+}
+
+/// 启动后台 Token 自动刷新任务（Refresh Token 过期时停止）
+pub fn spawn_refresh_task<R: TokenRefresher + 'static>(
+    token_manager: Arc<TokenManager>,
+    refresher: Arc<R>,
+    refresh_before: i64,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(REFRESH_CHECK_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match refresh_if_needed(&token_manager, refresher.as_ref(), refresh_before).await {
+                Ok(RefreshOutcome::NotNeeded) => debug!("Access Token 仍然有效"),
+                Ok(RefreshOutcome::Refreshed) => info!("Access Token 已自动刷新"),
+                Ok(RefreshOutcome::ReloginRequired) => {
+                    error!("Refresh Token 已过期，请运行 'claude-sync login' 重新登录");
+                    break;
+                }
+                Err(e) => warn!("自动刷新 Token 失败，稍后重试: {:#}", e),
+            }
+        }
+    })
+}
+
 // ===== 辅助函数 =====
 
 /// Base64 编码/解码辅助函数
@@ -393,6 +603,187 @@ mod base64_helper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 模拟的刷新服务
+    struct MockRefresher {
+        calls: AtomicUsize,
+    }
+
+    impl TokenRefresher for MockRefresher {
+        fn refresh(
+            &self,
+            refresh_token: String,
+        ) -> impl Future<Output = Result<TokenRefreshResponse>> + Send {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                assert_eq!(refresh_token, "refresh");
+                Ok(TokenRefreshResponse {
+                    access_token: "refreshed_access".to_string(),
+                    expires_at: Utc::now().timestamp() + 3600,
+                    message: String::new(),
+                })
+            }
+        }
+    }
+
+    /// 内存中的密钥环（克隆共享同一条目）
+    #[derive(Clone, Default)]
+    struct MockKeyring {
+        secret: Arc<std::sync::Mutex<Option<String>>>,
+    }
+
+    impl SecretStore for MockKeyring {
+        fn get(&self) -> Result<Option<String>> {
+            Ok(self.secret.lock().unwrap().clone())
+        }
+
+        fn set(&self, secret: &str) -> Result<()> {
+            *self.secret.lock().unwrap() = Some(secret.to_string());
+            Ok(())
+        }
+
+        fn delete(&self) -> Result<()> {
+            *self.secret.lock().unwrap() = None;
+            Ok(())
+        }
+    }
+
+    fn save_tokens(manager: &TokenManager, access_expires_at: i64, refresh_expires_at: i64) {
+        manager
+            .save_tokens(TokenStorage {
+                access_token: "access".to_string(),
+                refresh_token: "refresh".to_string(),
+                device_id: "device".to_string(),
+                user_id: "user".to_string(),
+                access_expires_at,
+                refresh_expires_at,
+            })
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refresh_near_expiry() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let manager = TokenManager::new(temp_dir.path().to_path_buf(), None, String::new());
+        let refresher = MockRefresher {
+            calls: AtomicUsize::new(0),
+        };
+        let now = Utc::now().timestamp();
+
+        // 距离过期还很久，不刷新
+        save_tokens(&manager, now + 3600, now + 86400);
+        let outcome = refresh_if_needed(&manager, &refresher, 300).await.unwrap();
+        assert_eq!(outcome, RefreshOutcome::NotNeeded);
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 0);
+
+        // 即将过期，刷新并替换存储的 Access Token
+        save_tokens(&manager, now + 60, now + 86400);
+        let outcome = refresh_if_needed(&manager, &refresher, 300).await.unwrap();
+        assert_eq!(outcome, RefreshOutcome::Refreshed);
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 1);
+
+        let tokens = manager.load_tokens().unwrap();
+        assert_eq!(tokens.access_token, "refreshed_access");
+        assert_eq!(tokens.refresh_token, "refresh");
+        assert!(tokens.access_expires_at > now + 3000);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_expired_stops_task() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let manager = Arc::new(TokenManager::new(
+            temp_dir.path().to_path_buf(),
+            None,
+            String::new(),
+        ));
+        let refresher = Arc::new(MockRefresher {
+            calls: AtomicUsize::new(0),
+        });
+        let now = Utc::now().timestamp();
+        save_tokens(&manager, now - 60, now - 10);
+
+        let outcome = refresh_if_needed(&manager, refresher.as_ref(), 300)
+            .await
+            .unwrap();
+        assert_eq!(outcome, RefreshOutcome::ReloginRequired);
+
+        // 后台任务在需要重新登录时自行退出
+        let handle = spawn_refresh_task(manager, refresher.clone(), 300);
+        tokio::time::timeout(std::time::Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(refresher.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_keyring_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let keyring = MockKeyring::default();
+        let manager = TokenManager::new(temp_dir.path().to_path_buf(), None, String::new())
+            .with_keyring(Box::new(keyring.clone()));
+        assert!(!manager.has_tokens());
+
+        save_tokens(&manager, 100, 200);
+        assert!(keyring.secret.lock().unwrap().is_some());
+        assert!(!temp_dir.path().join("tokens.json").exists());
+        assert!(manager.has_tokens());
+
+        let tokens = manager.load_tokens().unwrap();
+        assert_eq!(tokens.access_token, "access");
+        assert_eq!(tokens.access_expires_at, 100);
+        assert_eq!(tokens.refresh_expires_at, 200);
+
+        manager.delete_tokens().unwrap();
+        assert!(keyring.secret.lock().unwrap().is_none());
+        assert!(!manager.has_tokens());
+        assert!(manager.load_tokens().is_err());
+    }
+
+    #[test]
+    fn test_keyring_reads_existing_token_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let token_file = temp_dir.path().join("tokens.json");
+
+        // 文件后端（加密）仍可正常读写
+        let file_manager = TokenManager::new(
+            temp_dir.path().to_path_buf(),
+            Some("file_key".to_string()),
+            String::new(),
+        );
+        save_tokens(&file_manager, 100, 200);
+        assert!(token_file.exists());
+        assert!(!std::fs::read_to_string(&token_file)
+            .unwrap()
+            .contains("access"));
+        assert_eq!(file_manager.load_tokens().unwrap().access_expires_at, 100);
+
+        // 启用密钥环后先读取已有的 Token 文件，再次保存时迁移到密钥环
+        let keyring = MockKeyring::default();
+        let manager = TokenManager::new(
+            temp_dir.path().to_path_buf(),
+            Some("file_key".to_string()),
+            String::new(),
+        )
+        .with_keyring(Box::new(keyring.clone()));
+        assert!(manager.has_tokens());
+        manager
+            .update_access_token("new_access".to_string(), 150)
+            .unwrap();
+        assert!(!token_file.exists());
+        assert!(keyring
+            .secret
+            .lock()
+            .unwrap()
+            .as_deref()
+            .unwrap()
+            .contains("new_access"));
+        assert_eq!(manager.load_tokens().unwrap().access_expires_at, 150);
+
+        // 文件后端看不到密钥环中的 Token
+        assert!(!file_manager.has_tokens());
+    }
 
     #[test]
     fn test_validate_token_format() {
@@ -1,13 +1,49 @@
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::Semaphore;
-use tracing::{debug, info};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+use crate::content_hash::{hash_like, HashAlgorithm};
+use crate::delta::{apply_delta, compute_delta, encoded_len, BaseStore, DeltaOp};
+use crate::error::ClientError;
+use crate::grpc_client::{DownloadFileData, GrpcClient};
+
+/// 默认分块大小（4MB）
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// 允许的最小分块大小（64KB）
+pub const MIN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 允许的最大分块大小（64MB）
+pub const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// 识别 MIME 类型时读取的文件开头字节数
+const MIME_SNIFF_LEN: u64 = 512;
+
+/// 上传分块流中等待发送的最大分块数
+///
+/// 流式上传时内存中最多保留这么多个已读取的分块（外加正在读取的一个），与文件大小无关。
+pub const UPLOAD_CHANNEL_CAPACITY: usize = 2;
+
+/// 计算文件哈希时每次读取的字节数
+const HASH_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// 使用增量上传的最大文件大小（16MB）
+///
+/// 计算增量需要把文件和基准完整读入内存，更大的文件总是流式完整上传，也不保存基准。
+pub const DELTA_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
 /// 文件传输进度
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferProgress {
@@ -85,6 +121,9 @@ pub struct UploadRequest {
     /// 文件路径
     pub file_path: PathBuf,
 
+    /// 服务器上的相对路径
+    pub remote_path: String,
+
     /// 用户 ID
     pub user_id: Uuid,
 
@@ -99,14 +138,53 @@ pub struct UploadRequest {
 
     /// 上传 ID（用于断点续传）
     pub upload_id: Option<String>,
+
+    /// Unix 权限位（不同步权限或非 Unix 平台时为 None）
+    pub file_mode: Option<u32>,
+
+    /// MIME 类型（随上传元数据发送，服务器与对象一起保存）
+    pub content_type: String,
+
+    /// 服务器上当前版本的哈希（用作增量上传的基准，首次上传时为 None）
+    pub base_hash: Option<String>,
+}
+
+/// 上传分块
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadChunk {
+    /// 分块在文件中的偏移量
+    pub offset: u64,
+
+    /// 分块内容
+    pub data: Vec<u8>,
+}
+
+/// 从磁盘逐块读取的上传分块流
+///
+/// 读取失败或读完后哈希校验失败时以一个错误结束，接收方应放弃本次上传。
+pub type UploadChunks = mpsc::Receiver<Result<UploadChunk>>;
+
+/// 实际上传的内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadPayload {
+    /// 完整文件内容（分块上传）
+    Full,
+    /// 基于服务器上已有版本的增量
+    Delta {
+        base_hash: String,
+        ops: Vec<DeltaOp>,
+    },
 }
 
 /// 文件下载请求
 #[derive(Debug, Clone)]
 pub struct DownloadRequest {
-    /// 文件路径
+    /// 本地文件路径
     pub file_path: PathBuf,
 
+    /// 服务器上的相对路径
+    pub remote_path: String,
+
     /// 用户 ID
     pub user_id: Uuid,
 
@@ -114,6 +192,70 @@ pub struct DownloadRequest {
     pub version_number: Option<i64>,
 }
 
+/// 下载数据来源（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+pub trait DownloadSource: Send + Sync {
+    /// 下载文件内容及服务器声明的哈希（版本号为 None 时下载最新版本）
+    fn download(
+        &self,
+        file_path: String,
+        version_number: Option<i64>,
+    ) -> impl Future<Output = Result<DownloadFileData>> + Send;
+}
+
+impl DownloadSource for GrpcClient {
+    fn download(
+        &self,
+        file_path: String,
+        version_number: Option<i64>,
+    ) -> impl Future<Output = Result<DownloadFileData>> + Send {
+        self.download_file(file_path, version_number)
+    }
+}
+
+/// 服务器已保存内容的查询接口（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+///
+/// 传输管理器以 trait 对象持有，因此返回装箱的 future。
+pub trait ContentIndex: Send + Sync {
+    /// 服务器是否已保存哈希为 file_hash 的内容
+    fn has_content(&self, file_hash: String) -> BoxFuture<'_, Result<bool>>;
+}
+
+impl ContentIndex for GrpcClient {
+    fn has_content(&self, file_hash: String) -> BoxFuture<'_, Result<bool>> {
+        Box::pin(GrpcClient::has_content(self, file_hash))
+    }
+}
+
+/// 上传目标（守护进程使用 GrpcClient，测试中可替换为模拟服务器）
+///
+/// 传输管理器以 trait 对象持有，因此返回装箱的 future。
+pub trait UploadTarget: Send + Sync {
+    /// 按顺序发送分块流中的文件内容，返回服务器创建的版本号
+    fn upload(&self, request: &UploadRequest, chunks: UploadChunks) -> BoxFuture<'_, Result<i64>>;
+}
+
+impl UploadTarget for GrpcClient {
+    fn upload(&self, request: &UploadRequest, chunks: UploadChunks) -> BoxFuture<'_, Result<i64>> {
+        let request = request.clone();
+        Box::pin(async move {
+            let response = self
+                .upload_file(
+                    request.remote_path,
+                    request.file_hash,
+                    request.file_size,
+                    request.file_mode,
+                    request.content_type,
+                    chunks,
+                )
+                .await?;
+            if !response.success {
+                anyhow::bail!("服务器拒绝上传: {}", response.message);
+            }
+            Ok(response.version_number)
+        })
+    }
+}
+
 /// 文件传输管理器
 pub struct TransferManager {
     /// 最大并发上传数
@@ -132,13 +274,25 @@ pub struct TransferManager {
     chunk_size: usize,
 
     /// 重试次数
+    #[allow(dead_code)]
     upload_retries: usize,
 
     /// 下载重试次数
+    #[allow(dead_code)]
     download_retries: usize,
 
     /// 重试延迟（秒）
+    #[allow(dead_code)]
     retry_delay: Duration,
+
+    /// 增量上传的基准内容（为 None 时总是完整上传）
+    delta_bases: Option<BaseStore>,
+
+    /// 上传前查询服务器是否已有相同内容（为 None 时总是传输）
+    content_index: Option<Arc<dyn ContentIndex>>,
+
+    /// 完整上传的提交目标（为 None 时只在本地记录进度）
+    upload_target: Option<Arc<dyn UploadTarget>>,
 }
 
 impl TransferManager {
@@ -149,24 +303,162 @@ impl TransferManager {
         upload_retries: usize,
         download_retries: usize,
         retry_delay: u64,
+        chunk_size: usize,
     ) -> Self {
         Self {
             max_concurrent_uploads,
             max_concurrent_downloads,
             upload_semaphore: Semaphore::new(max_concurrent_uploads),
             download_semaphore: Semaphore::new(max_concurrent_downloads),
-            chunk_size: 4 * 1024 * 1024, // 4MB
+            chunk_size,
             upload_retries,
             download_retries,
             retry_delay: Duration::from_secs(retry_delay),
+            delta_bases: None,
+            content_index: None,
+            upload_target: None,
+        }
+    }
+
+    /// 上传前查询服务器，已有相同内容时跳过传输
+    pub fn with_content_index(mut self, content_index: Arc<dyn ContentIndex>) -> Self {
+        self.content_index = Some(content_index);
+        self
+    }
+
+    /// 完整上传时将文件内容提交到上传目标
+    pub fn with_upload_target(mut self, upload_target: Arc<dyn UploadTarget>) -> Self {
+        self.upload_target = Some(upload_target);
+        self
+    }
+
+    /// 服务器是否已有该内容（查询失败时按没有处理）
+    async fn server_has_content(&self, file_hash: &str) -> bool {
+        let Some(index) = &self.content_index else {
+            return false;
+        };
+        match index.has_content(file_hash.to_string()).await {
+            Ok(exists) => exists,
+            Err(e) => {
+                warn!("查询服务器内容失败，继续上传: {:#}", e);
+                false
+            }
         }
     }
 
+    /// 启用增量上传，基准内容保存在 store 中
+    pub fn with_delta_bases(mut self, store: BaseStore) -> Self {
+        self.delta_bases = Some(store);
+        self
+    }
+
+    /// 记录文件最新同步的内容，作为下次增量上传的基准
+    pub async fn remember_base(&self, file_path: &Path, content: &[u8]) {
+        if let Some(store) = &self.delta_bases {
+            if let Err(e) = store.save(file_path, content).await {
+                warn!("保存增量基准失败: {:?}: {}", file_path, e);
+            }
+        }
+    }
+
+    /// 读取可用的增量基准，文件超过增量大小上限或没有匹配的基准时返回 None
+    async fn load_delta_base(&self, request: &UploadRequest) -> Option<Vec<u8>> {
+        let (Some(store), Some(base_hash)) = (&self.delta_bases, &request.base_hash) else {
+            return None;
+        };
+        if request.file_size > DELTA_MAX_FILE_SIZE {
+            debug!(
+                "文件超过增量上传大小上限，完整上传: {:?}",
+                request.file_path
+            );
+            return None;
+        }
+        let base = store.load(&request.file_path, base_hash).await;
+        if base.is_none() {
+            debug!("没有可用的增量基准，完整上传: {:?}", request.file_path);
+        }
+        base
+    }
+
+    /// 流式上传后记录基准：只保存不超过增量大小上限的文件，内容已变化时跳过
+    async fn remember_streamed_base(&self, request: &UploadRequest) {
+        if self.delta_bases.is_none() || request.file_size > DELTA_MAX_FILE_SIZE {
+            return;
+        }
+        match Self::read_verified(request).await {
+            Ok(content) => self.remember_base(&request.file_path, &content).await,
+            Err(e) => debug!("不记录增量基准: {:?}: {:#}", request.file_path, e),
+        }
+    }
+
+    /// 选择上传方式：有可用基准且增量不超过完整内容的一半时上传增量
+    pub async fn prepare_payload(&self, request: &UploadRequest, content: &[u8]) -> UploadPayload {
+        match self.load_delta_base(request).await {
+            Some(base) => Self::delta_payload(request, &base, content),
+            None => UploadPayload::Full,
+        }
+    }
+
+    /// 根据基准内容计算增量，增量不划算或重建校验失败时完整上传
+    fn delta_payload(request: &UploadRequest, base: &[u8], content: &[u8]) -> UploadPayload {
+        let Some(base_hash) = &request.base_hash else {
+            return UploadPayload::Full;
+        };
+
+        let ops = compute_delta(base, content);
+        if encoded_len(&ops) * 2 > content.len() {
+            return UploadPayload::Full;
+        }
+
+        // 上传前在本地重建一次，确保服务器按同一基准能得到相同内容
+        let rebuilt_hash =
+            apply_delta(base, &ops).map(|rebuilt| hash_like(&request.file_hash, &rebuilt));
+        if rebuilt_hash.ok().as_deref() != Some(request.file_hash.as_str()) {
+            warn!("增量重建校验失败，改为完整上传: {:?}", request.file_path);
+            return UploadPayload::Full;
+        }
+
+        UploadPayload::Delta {
+            base_hash: base_hash.clone(),
+            ops,
+        }
+    }
+
+    /// 分块大小（字节）
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// 按分块大小切分文件内容，返回 (偏移量, 分块数据)
+    pub fn split_chunks<'a>(&self, content: &'a [u8]) -> impl Iterator<Item = (u64, &'a [u8])> {
+        let chunk_size = self.chunk_size;
+        content
+            .chunks(chunk_size)
+            .enumerate()
+            .map(move |(i, chunk)| ((i * chunk_size) as u64, chunk))
+    }
+
+    /// 按顺序重组分块，偏移量不连续时返回错误
+    pub fn assemble_chunks<I, B>(chunks: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = (u64, B)>,
+        B: AsRef<[u8]>,
+    {
+        let mut content = Vec::new();
+        for (offset, data) in chunks {
+            if offset != content.len() as u64 {
+                anyhow::bail!("分块偏移量不连续: 期望 {}, 实际 {}", content.len(), offset);
+            }
+            content.extend_from_slice(data.as_ref());
+        }
+        Ok(content)
+    }
+
     /// 上传文件（带进度回调）
     pub async fn upload_file<F>(
         &self,
         request: UploadRequest,
-        progress_callback: F,
+        mut progress_callback: F,
     ) -> Result<TransferProgress>
     where
         F: Fn(TransferProgress) + Send + 'static,
@@ -188,32 +480,69 @@ impl TransferManager {
             error_message: None,
         };
 
-        // 读取文件
-        let file_content = tokio::fs::read(&request.file_path)
-            .await
-            .with_context(|| format!("无法读取文件: {:?}", request.file_path))?;
-
-        // 验证文件哈希
-        let actual_hash = Self::calculate_hash(&file_content)?;
-        if actual_hash != request.file_hash {
-            anyhow::bail!(
-                "文件哈希不匹配: 期望 {}, 实际 {}",
-                request.file_hash,
-                actual_hash
-            );
-        }
-
-        // 分块上传
-        let total_chunks = file_content.len().div_ceil(self.chunk_size);
-
-        for (i, chunk) in file_content.chunks(self.chunk_size).enumerate() {
-            // TODO: 实际上传到服务器的逻辑
-            // 这里需要调用 gRPC 客户端的上传方法
+        // 有可用基准时才读入完整内容计算增量，否则从磁盘流式上传
+        let delta_base = self.load_delta_base(&request).await;
+        let file_content = match &delta_base {
+            Some(_) => Some(Self::read_verified(&request).await?),
+            None => None,
+        };
 
-            progress.transferred_bytes += chunk.len() as u64;
+        // 服务器已有相同内容时只需提交元数据，新版本关联到已有对象
+        if self.server_has_content(&request.file_hash).await {
+            // 不传输内容，先确认本地文件确实是声明的哈希
+            if file_content.is_none() {
+                let actual_hash = Self::calculate_file_hash(
+                    &request.file_path,
+                    HashAlgorithm::of(&request.file_hash),
+                )
+                .await?;
+                check_hash(&request.file_hash, &actual_hash)?;
+            }
+
+            // TODO: 通过 gRPC 客户端发送只含元数据的上传
+            debug!("服务器已有相同内容，跳过传输: {:?}", request.file_path);
+            match &file_content {
+                Some(content) => self.remember_base(&request.file_path, content).await,
+                None => self.remember_streamed_base(&request).await,
+            }
+
+            progress.total_bytes = 0;
+            progress.is_completed = true;
+            progress.completed_at = Some(Utc::now());
             progress_callback(progress.clone());
 
-            debug!("上传分块 {}/{}: {} 字节", i + 1, total_chunks, chunk.len());
+            info!("文件上传完成（内容已存在）: {:?}", request.file_path);
+            return Ok(progress);
+        }
+
+        let payload = match (&delta_base, &file_content) {
+            (Some(base), Some(content)) => Self::delta_payload(&request, base, content),
+            _ => UploadPayload::Full,
+        };
+        match payload {
+            UploadPayload::Full => {
+                // 服务器拒绝（如配额不足）时直接返回错误，不记录增量基准
+                self.stream_upload(&request, &mut progress, &mut progress_callback)
+                    .await?;
+            }
+            UploadPayload::Delta { base_hash, ops } => {
+                // TODO: 通过 gRPC 客户端发送增量
+                let delta_size = encoded_len(&ops) as u64;
+                progress.total_bytes = delta_size;
+                progress.transferred_bytes = delta_size;
+                progress_callback(progress.clone());
+
+                debug!(
+                    "增量上传: {} 字节（完整 {} 字节，基准 {}）",
+                    delta_size,
+                    request.file_size,
+                    &base_hash[..base_hash.len().min(8)]
+                );
+            }
+        }
+        match &file_content {
+            Some(content) => self.remember_base(&request.file_path, content).await,
+            None => self.remember_streamed_base(&request).await,
         }
 
         progress.is_completed = true;
@@ -228,13 +557,110 @@ impl TransferManager {
         Ok(progress)
     }
 
+    /// 读取完整文件内容并校验哈希
+    async fn read_verified(request: &UploadRequest) -> Result<Vec<u8>> {
+        let content = tokio::fs::read(&request.file_path)
+            .await
+            .with_context(|| format!("无法读取文件: {:?}", request.file_path))?;
+        check_hash(&request.file_hash, &hash_like(&request.file_hash, &content))?;
+        Ok(content)
+    }
+
+    /// 从磁盘逐块读取文件并发送给上传目标，读完后校验累计的哈希
+    ///
+    /// 读取和发送并行进行，分块流有界，内存占用与文件大小无关。
+    async fn stream_upload<F>(
+        &self,
+        request: &UploadRequest,
+        progress: &mut TransferProgress,
+        progress_callback: &mut F,
+    ) -> Result<()>
+    where
+        F: Fn(TransferProgress) + Send,
+    {
+        let (tx, mut rx) = mpsc::channel(UPLOAD_CHANNEL_CAPACITY);
+        let upload = async {
+            match &self.upload_target {
+                Some(target) => target.upload(request, rx).await.map(|_| ()),
+                None => {
+                    // TODO: 没有上传目标时只读取分块
+                    while let Some(chunk) = rx.recv().await {
+                        chunk?;
+                    }
+                    Ok(())
+                }
+            }
+        };
+        let send = async move {
+            let result = self
+                .send_chunks(request, &tx, progress, progress_callback)
+                .await;
+            // 读取失败或哈希不匹配时让接收方放弃本次上传
+            if let Err(e) = &result {
+                let _ = tx.send(Err(anyhow::anyhow!("{:#}", e))).await;
+            }
+            result
+        };
+
+        let (sent, uploaded) = tokio::join!(send, upload);
+        sent?;
+        uploaded.with_context(|| format!("上传失败: {:?}", request.file_path))
+    }
+
+    /// 按分块大小读取文件并发送到分块流，同时累计哈希
+    async fn send_chunks<F>(
+        &self,
+        request: &UploadRequest,
+        tx: &mpsc::Sender<Result<UploadChunk>>,
+        progress: &mut TransferProgress,
+        progress_callback: &mut F,
+    ) -> Result<()>
+    where
+        F: Fn(TransferProgress) + Send,
+    {
+        let mut file = File::open(&request.file_path)
+            .await
+            .with_context(|| format!("无法读取文件: {:?}", request.file_path))?;
+        let total_chunks = request.file_size.div_ceil(self.chunk_size as u64);
+        let mut hasher = HashAlgorithm::of(&request.file_hash).hasher();
+        let mut offset = 0;
+
+        for i in 1.. {
+            let mut data = Vec::with_capacity(self.chunk_size);
+            (&mut file)
+                .take(self.chunk_size as u64)
+                .read_to_end(&mut data)
+                .await
+                .with_context(|| format!("无法读取文件: {:?}", request.file_path))?;
+            if data.is_empty() {
+                break;
+            }
+
+            hasher.update(&data);
+            let len = data.len() as u64;
+            // 接收方已结束（如服务器拒绝上传）时停止读取，由上传结果报告错误
+            if tx.send(Ok(UploadChunk { offset, data })).await.is_err() {
+                return Ok(());
+            }
+            offset += len;
+
+            progress.transferred_bytes += len;
+            progress_callback(progress.clone());
+            debug!("上传分块 {}/{}: {} 字节", i, total_chunks, len);
+        }
+
+        check_hash(&request.file_hash, &hasher.finalize())
+    }
+
     /// 下载文件（带进度回调）
-    pub async fn download_file<F>(
+    pub async fn download_file<S, F>(
         &self,
+        source: &S,
         request: DownloadRequest,
         progress_callback: F,
     ) -> Result<TransferProgress>
     where
+        S: DownloadSource,
         F: Fn(TransferProgress) + Send + 'static,
     {
         // 获取下载许可
@@ -254,8 +680,20 @@ impl TransferManager {
             error_message: None,
         };
 
-        // TODO: 实际从服务器下载的逻辑
-        // 这里需要调用 gRPC 客户端的下载方法
+        let data = source
+            .download(request.remote_path.clone(), request.version_number)
+            .await?;
+        progress.total_bytes = data.content.len() as u64;
+        progress.transferred_bytes = progress.total_bytes;
+
+        // 校验通过后才写入，失败时保留原文件
+        if let Err(e) = Self::verify_download(&request.file_path, &data.content, &data.file_hash) {
+            warn!("{}", e);
+            progress.is_failed = true;
+            progress.error_message = Some(e.to_string());
+            progress_callback(progress);
+            return Err(e.into());
+        }
 
         // 确保父目录存在
         if let Some(parent) = request.file_path.parent() {
@@ -263,10 +701,8 @@ impl TransferManager {
                 .await
                 .with_context(|| format!("无法创建目录: {:?}", parent))?;
         }
+        write_atomic(&request.file_path, &data.content).await?;
 
-        // 模拟下载（实际应该从 gRPC 流接收数据）
-        progress.total_bytes = 1024 * 1024; // 1MB 示例
-        progress.transferred_bytes = progress.total_bytes;
         progress.is_completed = true;
         progress.completed_at = Some(Utc::now());
         progress_callback(progress.clone());
@@ -276,66 +712,81 @@ impl TransferManager {
         Ok(progress)
     }
 
+    /// 按服务器声明哈希的算法校验下载内容
+    pub fn verify_download(
+        path: &Path,
+        content: &[u8],
+        expected_hash: &str,
+    ) -> std::result::Result<(), ClientError> {
+        let actual_hash = hash_like(expected_hash, content);
+        if actual_hash == expected_hash {
+            return Ok(());
+        }
+
+        Err(ClientError::file(
+            path.display().to_string(),
+            format!(
+                "下载内容校验失败: 期望 {}, 实际 {}",
+                expected_hash, actual_hash
+            ),
+            None,
+        ))
+    }
+
     /// 批量上传文件
+    ///
+    /// 所有上传共用管理器的上传信号量，与其他同时进行的上传一起受 max_concurrent_uploads 限制；
+    /// 同时只创建与并发数相同的任务，批量很大时不会一次性为每个请求创建任务。
+    /// 结果按请求顺序返回。
     pub async fn batch_upload<F>(
-        &self,
+        self: &Arc<Self>,
         requests: Vec<UploadRequest>,
         progress_callback: F,
     ) -> Vec<Result<TransferProgress>>
     where
         F: Fn(TransferProgress) + Clone + Send + 'static,
     {
-        let mut handles = Vec::new();
-
-        for request in requests {
-            let manager = self.clone_manager();
-            let callback = progress_callback.clone();
-
-            let handle = tokio::spawn(async move { manager.upload_file(request, callback).await });
-
-            handles.push(handle);
-        }
-
-        // 等待所有上传完成
-        let mut results = Vec::new();
-        for handle in handles {
-            results.push(handle.await.unwrap_or_else(|e| Err(e.into())));
-        }
-
-        results
+        futures_util::stream::iter(requests)
+            .map(|request| {
+                let manager = Arc::clone(self);
+                let callback = progress_callback.clone();
+                tokio::spawn(async move { manager.upload_file(request, callback).await })
+            })
+            .buffered(self.max_concurrent_uploads.max(1))
+            .map(|result| result.unwrap_or_else(|e| Err(e.into())))
+            .collect()
+            .await
     }
 
-    /// 批量下载文件
-    pub async fn batch_download<F>(
-        &self,
+    /// 批量下载文件（并发限制与 `batch_upload` 相同，使用下载信号量）
+    pub async fn batch_download<S, F>(
+        self: &Arc<Self>,
+        source: Arc<S>,
         requests: Vec<DownloadRequest>,
         progress_callback: F,
     ) -> Vec<Result<TransferProgress>>
     where
+        S: DownloadSource + 'static,
         F: Fn(TransferProgress) + Clone + Send + 'static,
     {
-        let mut handles = Vec::new();
-
-        for request in requests {
-            let manager = self.clone_manager();
-            let callback = progress_callback.clone();
-
-            let handle =
-                tokio::spawn(async move { manager.download_file(request, callback).await });
-
-            handles.push(handle);
-        }
-
-        // 等待所有下载完成
-        let mut results = Vec::new();
-        for handle in handles {
-            results.push(handle.await.unwrap_or_else(|e| Err(e.into())));
-        }
-
-        results
+        futures_util::stream::iter(requests)
+            .map(|request| {
+                let manager = Arc::clone(self);
+                let source = source.clone();
+                let callback = progress_callback.clone();
+                tokio::spawn(async move {
+                    manager
+                        .download_file(source.as_ref(), request, callback)
+                        .await
+                })
+            })
+            .buffered(self.max_concurrent_downloads.max(1))
+            .map(|result| result.unwrap_or_else(|e| Err(e.into())))
+            .collect()
+            .await
     }
 
-    /// 计算文件哈希
+    /// 计算文件哈希（SHA-256）
     pub fn calculate_hash(content: &[u8]) -> Result<String> {
         let mut hasher = Sha256::new();
         hasher.update(content);
@@ -343,27 +794,24 @@ impl TransferManager {
         Ok(format!("{:x}", result))
     }
 
-    /// 计算文件哈希（异步）
-    pub async fn calculate_file_hash(path: &Path) -> Result<String> {
-        let content = tokio::fs::read(path)
+    /// 按指定算法计算文件哈希（异步，逐块读取）
+    pub async fn calculate_file_hash(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+        let mut file = File::open(path)
             .await
             .with_context(|| format!("无法读取文件: {:?}", path))?;
-
-        Self::calculate_hash(&content)
-    }
-
-    /// 克隆管理器（用于并发）
-    fn clone_manager(&self) -> Self {
-        Self {
-            max_concurrent_uploads: self.max_concurrent_uploads,
-            max_concurrent_downloads: self.max_concurrent_downloads,
-            upload_semaphore: Semaphore::new(self.max_concurrent_uploads),
-            download_semaphore: Semaphore::new(self.max_concurrent_downloads),
-            chunk_size: self.chunk_size,
-            upload_retries: self.upload_retries,
-            download_retries: self.download_retries,
-            retry_delay: self.retry_delay,
+        let mut hasher = algorithm.hasher();
+        let mut buffer = vec![0; HASH_READ_BUFFER_SIZE];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .await
+                .with_context(|| format!("无法读取文件: {:?}", path))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
         }
+        Ok(hasher.finalize())
     }
 }
 
@@ -389,7 +837,7 @@ impl ResumableTransfer {
 
         let content = serde_json::to_string_pretty(state).context("无法序列化传输状态")?;
 
-        tokio::fs::write(&self.state_file, content)
+        write_atomic(&self.state_file, content)
             .await
             .with_context(|| format!("无法写入传输状态: {:?}", self.state_file))?;
 
@@ -423,10 +871,554 @@ impl ResumableTransfer {
     }
 }
 
+/// 校验实际哈希与期望的文件哈希一致
+fn check_hash(expected: &str, actual: &str) -> Result<()> {
+    if actual != expected {
+        anyhow::bail!("文件哈希不匹配: 期望 {}, 实际 {}", expected, actual);
+    }
+    Ok(())
+}
+
+/// 原子写入使用的临时文件路径：与目标文件同目录（保证 rename 不跨文件系统），
+/// 以 .tmp 结尾以便被默认排除规则忽略
+fn atomic_temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, Uuid::new_v4().simple()))
+}
+
+/// 原子写入本地文件：先写入临时文件，完成后重命名覆盖目标；失败时原文件保持不变
+pub async fn write_atomic(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    write_atomic_from_reader(path, content.as_ref())
+        .await
+        .map(|_| ())
+}
+
+/// 从读取器原子写入本地文件，返回写入的字节数
+pub async fn write_atomic_from_reader<R>(path: &Path, mut reader: R) -> Result<u64>
+where
+    R: AsyncRead + Unpin,
+{
+    let temp_path = atomic_temp_path(path);
+
+    let result = async {
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        let written = tokio::io::copy(&mut reader, &mut file).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        // 保留原文件的权限
+        if let Ok(metadata) = tokio::fs::metadata(path).await {
+            tokio::fs::set_permissions(&temp_path, metadata.permissions()).await?;
+        }
+
+        tokio::fs::rename(&temp_path, path).await?;
+        Ok::<_, std::io::Error>(written)
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+
+    result.with_context(|| format!("无法写入文件: {:?}", path))
+}
+
+/// 同步版本的原子写入（用于无法进入异步上下文的场景）
+pub fn write_atomic_sync(path: &Path, content: impl AsRef<[u8]>) -> Result<()> {
+    use std::io::Write;
+
+    let temp_path = atomic_temp_path(path);
+
+    let result = (|| {
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(content.as_ref())?;
+        file.sync_all()?;
+        drop(file);
+
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+
+        std::fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result.with_context(|| format!("无法写入文件: {:?}", path))
+}
+
+/// 按扩展名和文件开头的内容识别 MIME 类型
+pub async fn detect_content_type(path: &Path) -> Result<&'static str> {
+    let file = File::open(path)
+        .await
+        .with_context(|| format!("无法打开文件: {:?}", path))?;
+    let mut head = Vec::with_capacity(MIME_SNIFF_LEN as usize);
+    file.take(MIME_SNIFF_LEN)
+        .read_to_end(&mut head)
+        .await
+        .with_context(|| format!("无法读取文件: {:?}", path))?;
+    Ok(crate::rules::detect_mime_type(path, &head))
+}
+
+/// 读取文件的 Unix 权限位（非 Unix 平台返回 None）
+pub fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+/// 设置文件的 Unix 权限位（非 Unix 平台不做任何操作）
+pub async fn set_file_mode(path: &Path, mode: u32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+            .await
+            .with_context(|| format!("无法设置文件权限: {:?}", path))?;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, mode);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// 返回固定内容和声明哈希的模拟服务器
+    struct MockServer {
+        content: Vec<u8>,
+        declared_hash: String,
+    }
+
+    impl DownloadSource for MockServer {
+        async fn download(
+            &self,
+            file_path: String,
+            version_number: Option<i64>,
+        ) -> Result<DownloadFileData> {
+            Ok(DownloadFileData {
+                file_path,
+                file_hash: self.declared_hash.clone(),
+                file_size: self.content.len() as u64,
+                content: self.content.clone(),
+                version: version_number.unwrap_or(1),
+                file_mode: None,
+                content_type: None,
+            })
+        }
+    }
+
+    fn download_request(file_path: &Path) -> DownloadRequest {
+        DownloadRequest {
+            file_path: file_path.to_path_buf(),
+            remote_path: "settings.json".to_string(),
+            user_id: Uuid::new_v4(),
+            version_number: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_writes_verified_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("nested").join("settings.json");
+        let server = MockServer {
+            content: b"{\"theme\": \"dark\"}".to_vec(),
+            declared_hash: TransferManager::calculate_hash(b"{\"theme\": \"dark\"}").unwrap(),
+        };
+
+        let manager = TransferManager::new(1, 1, 0, 0, 0, DEFAULT_CHUNK_SIZE);
+        let progress = manager
+            .download_file(&server, download_request(&path), |_| {})
+            .await
+            .unwrap();
+
+        assert!(progress.is_completed);
+        assert_eq!(progress.transferred_bytes, server.content.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), server.content);
+    }
+
+    #[tokio::test]
+    async fn test_download_hash_mismatch_keeps_existing_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("settings.json");
+        std::fs::write(&path, "original").unwrap();
+
+        let server = MockServer {
+            content: b"corrupted".to_vec(),
+            declared_hash: TransferManager::calculate_hash(b"expected").unwrap(),
+        };
+
+        let failures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = failures.clone();
+        let manager = TransferManager::new(1, 1, 0, 0, 0, DEFAULT_CHUNK_SIZE);
+        let err = manager
+            .download_file(&server, download_request(&path), move |progress| {
+                if progress.is_failed {
+                    recorded.lock().unwrap().push(progress);
+                }
+            })
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::File { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+        assert_eq!(failures.lock().unwrap().len(), 1);
+
+        // 没有残留的临时文件
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    fn upload_request(
+        file_path: &Path,
+        content: &[u8],
+        base_hash: Option<String>,
+    ) -> UploadRequest {
+        UploadRequest {
+            file_path: file_path.to_path_buf(),
+            remote_path: file_path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+            user_id: Uuid::new_v4(),
+            device_id: Uuid::new_v4(),
+            file_hash: TransferManager::calculate_hash(content).unwrap(),
+            file_size: content.len() as u64,
+            upload_id: None,
+            file_mode: None,
+            content_type: crate::rules::detect_mime_type(file_path, content).to_string(),
+            base_hash,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_small_edit_uploads_delta() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("CLAUDE.md");
+        let manager = TransferManager::new(1, 1, 0, 0, 0, DEFAULT_CHUNK_SIZE)
+            .with_delta_bases(BaseStore::new(temp_dir.path().join("bases")));
+
+        let v1: String = (0..200).map(|i| format!("- rule {}\n", i)).collect();
+        std::fs::write(&path, &v1).unwrap();
+        manager
+            .upload_file(upload_request(&path, v1.as_bytes(), None), |_| {})
+            .await
+            .unwrap();
+
+        let v2 = v1.replace("- rule 42\n", "- rule 42 (updated)\n");
+        std::fs::write(&path, &v2).unwrap();
+        let v1_hash = TransferManager::calculate_hash(v1.as_bytes()).unwrap();
+        let request = upload_request(&path, v2.as_bytes(), Some(v1_hash.clone()));
+
+        // 服务器按同一基准重建的内容哈希与新内容一致
+        let UploadPayload::Delta { base_hash, ops } =
+            manager.prepare_payload(&request, v2.as_bytes()).await
+        else {
+            panic!("小改动应使用增量上传");
+        };
+        assert_eq!(base_hash, v1_hash);
+        let rebuilt = apply_delta(v1.as_bytes(), &ops).unwrap();
+        assert_eq!(
+            TransferManager::calculate_hash(&rebuilt).unwrap(),
+            request.file_hash
+        );
+
+        let progress = manager.upload_file(request, |_| {}).await.unwrap();
+        assert!(progress.is_completed);
+        assert!(progress.transferred_bytes * 2 <= v2.len() as u64);
+    }
+
+    /// 记录已上传内容哈希的模拟服务器
+    #[derive(Default)]
+    struct MockContentIndex {
+        stored: std::sync::Mutex<std::collections::HashSet<String>>,
+    }
+
+    impl ContentIndex for MockContentIndex {
+        fn has_content(&self, file_hash: String) -> BoxFuture<'_, Result<bool>> {
+            Box::pin(async move { Ok(self.stored.lock().unwrap().contains(&file_hash)) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_content_is_transferred_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let index = Arc::new(MockContentIndex::default());
+        let manager = TransferManager::new(1, 1, 0, 0, 0, DEFAULT_CHUNK_SIZE)
+            .with_content_index(index.clone());
+
+        let content = b"{\"model\": \"opus\"}";
+        let mut transferred = 0;
+        for name in ["settings.json", "copy.json"] {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let request = upload_request(&path, content, None);
+            let hash = request.file_hash.clone();
+
+            let progress = manager.upload_file(request, |_| {}).await.unwrap();
+            assert!(progress.is_completed);
+            transferred += progress.transferred_bytes;
+            // 模拟服务器保存上传的内容
+            index.stored.lock().unwrap().insert(hash);
+        }
+
+        assert_eq!(transferred, content.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_first_upload_sends_full_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("CLAUDE.md");
+        let manager = TransferManager::new(1, 1, 0, 0, 0, DEFAULT_CHUNK_SIZE)
+            .with_delta_bases(BaseStore::new(temp_dir.path().join("bases")));
+        let content: String = (0..200).map(|i| format!("- rule {}\n", i)).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        // 首次上传没有基准；声明的基准在本地不存在时同样完整上传
+        let request = upload_request(&path, content.as_bytes(), None);
+        assert_eq!(
+            manager.prepare_payload(&request, content.as_bytes()).await,
+            UploadPayload::Full
+        );
+        let request = upload_request(&path, content.as_bytes(), Some("unknown".to_string()));
+        assert_eq!(
+            manager.prepare_payload(&request, content.as_bytes()).await,
+            UploadPayload::Full
+        );
+
+        let progress = manager.upload_file(request, |_| {}).await.unwrap();
+        assert_eq!(progress.transferred_bytes, content.len() as u64);
+    }
+
+    /// 记录同时进行的上传数的模拟服务器
+    #[derive(Default)]
+    struct ConcurrencyTarget {
+        active: std::sync::atomic::AtomicUsize,
+        max_active: std::sync::atomic::AtomicUsize,
+    }
+
+    impl UploadTarget for ConcurrencyTarget {
+        fn upload(
+            &self,
+            _request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            use std::sync::atomic::Ordering;
+            Box::pin(async move {
+                let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_active.fetch_max(active, Ordering::SeqCst);
+                while let Some(chunk) = chunks.recv().await {
+                    chunk?;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                self.active.fetch_sub(1, Ordering::SeqCst);
+                Ok(1)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_upload_shares_concurrency_limit() {
+        use std::sync::atomic::Ordering;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let requests: Vec<_> = (0..50)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file-{}.md", i));
+                let content = format!("content {}", i);
+                std::fs::write(&path, &content).unwrap();
+                upload_request(&path, content.as_bytes(), None)
+            })
+            .collect();
+
+        let target = Arc::new(ConcurrencyTarget::default());
+        let manager = Arc::new(
+            TransferManager::new(2, 2, 0, 0, 0, DEFAULT_CHUNK_SIZE)
+                .with_upload_target(target.clone()),
+        );
+
+        // 同时进行的另一批上传共用同一个并发限制
+        let (first, second) = tokio::join!(
+            manager.batch_upload(requests[..25].to_vec(), |_| {}),
+            manager.batch_upload(requests[25..].to_vec(), |_| {}),
+        );
+
+        assert!(first.iter().chain(&second).all(|result| result.is_ok()));
+        assert_eq!(target.max_active.load(Ordering::SeqCst), 2);
+        assert_eq!(target.active.load(Ordering::SeqCst), 0);
+    }
+
+    /// 缓慢接收分块的模拟服务器，记录读取进度领先接收进度的最大字节数
+    #[derive(Default)]
+    struct SlowTarget {
+        read_bytes: Arc<std::sync::atomic::AtomicU64>,
+        max_read_ahead: std::sync::atomic::AtomicU64,
+        max_chunk: std::sync::atomic::AtomicUsize,
+        received_hash: std::sync::Mutex<Option<String>>,
+    }
+
+    impl UploadTarget for SlowTarget {
+        fn upload(
+            &self,
+            _request: &UploadRequest,
+            mut chunks: UploadChunks,
+        ) -> BoxFuture<'_, Result<i64>> {
+            use std::sync::atomic::Ordering;
+            Box::pin(async move {
+                let mut hasher = Sha256::new();
+                let mut received = 0;
+                while let Some(chunk) = chunks.recv().await {
+                    let chunk = chunk?;
+                    assert_eq!(chunk.offset, received);
+                    received += chunk.data.len() as u64;
+                    hasher.update(&chunk.data);
+                    self.max_chunk.fetch_max(chunk.data.len(), Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+
+                    let read_ahead = self
+                        .read_bytes
+                        .load(Ordering::SeqCst)
+                        .saturating_sub(received);
+                    self.max_read_ahead.fetch_max(read_ahead, Ordering::SeqCst);
+                }
+                *self.received_hash.lock().unwrap() = Some(format!("{:x}", hasher.finalize()));
+                Ok(1)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_upload_streams_bounded_chunks() {
+        use std::sync::atomic::Ordering;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("large.bin");
+        let content: Vec<u8> = (0..MIN_CHUNK_SIZE * 128 + 7)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let target = Arc::new(SlowTarget::default());
+        let manager =
+            TransferManager::new(1, 1, 0, 0, 0, MIN_CHUNK_SIZE).with_upload_target(target.clone());
+        let read_bytes = target.read_bytes.clone();
+        let progress = manager
+            .upload_file(upload_request(&path, &content, None), move |p| {
+                read_bytes.store(p.transferred_bytes, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+
+        assert!(progress.is_completed);
+        assert_eq!(progress.transferred_bytes, content.len() as u64);
+        assert_eq!(
+            target.received_hash.lock().unwrap().as_deref(),
+            Some(TransferManager::calculate_hash(&content).unwrap().as_str())
+        );
+
+        // 每次只读取一个分块，且读取最多领先接收方通道容量个分块
+        assert_eq!(target.max_chunk.load(Ordering::SeqCst), MIN_CHUNK_SIZE);
+        assert!(
+            target.max_read_ahead.load(Ordering::SeqCst)
+                <= ((UPLOAD_CHANNEL_CAPACITY + 1) * MIN_CHUNK_SIZE) as u64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_upload_with_delta_streams_bounded_chunks() {
+        use std::sync::atomic::Ordering;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("session.jsonl");
+        let store = BaseStore::new(temp_dir.path().join("bases"));
+        let target = Arc::new(SlowTarget::default());
+        let manager = TransferManager::new(1, 1, 0, 0, 0, MIN_CHUNK_SIZE)
+            .with_delta_bases(store.clone())
+            .with_upload_target(target.clone());
+        let max_read_ahead = ((UPLOAD_CHANNEL_CAPACITY + 1) * MIN_CHUNK_SIZE) as u64;
+
+        // 首次上传没有基准：流式上传，完成后保存基准
+        let v1: Vec<u8> = (0..MIN_CHUNK_SIZE * 128 + 7)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &v1).unwrap();
+        let read_bytes = target.read_bytes.clone();
+        manager
+            .upload_file(upload_request(&path, &v1, None), move |p| {
+                read_bytes.store(p.transferred_bytes, Ordering::SeqCst);
+            })
+            .await
+            .unwrap();
+        let v1_hash = TransferManager::calculate_hash(&v1).unwrap();
+        assert_eq!(
+            target.received_hash.lock().unwrap().as_deref(),
+            Some(v1_hash.as_str())
+        );
+        assert!(target.max_read_ahead.load(Ordering::SeqCst) <= max_read_ahead);
+        assert!(store.load(&path, &v1_hash).await.is_some());
+
+        // 有基准但超过增量大小上限：仍然流式完整上传，不保存新基准
+        let mut v2 = v1.clone();
+        v2.resize(DELTA_MAX_FILE_SIZE as usize + 1, b'x');
+        std::fs::write(&path, &v2).unwrap();
+        let read_bytes = target.read_bytes.clone();
+        let progress = manager
+            .upload_file(
+                upload_request(&path, &v2, Some(v1_hash.clone())),
+                move |p| {
+                    read_bytes.store(p.transferred_bytes, Ordering::SeqCst);
+                },
+            )
+            .await
+            .unwrap();
+        let v2_hash = TransferManager::calculate_hash(&v2).unwrap();
+        assert_eq!(progress.transferred_bytes, v2.len() as u64);
+        assert_eq!(
+            target.received_hash.lock().unwrap().as_deref(),
+            Some(v2_hash.as_str())
+        );
+        assert_eq!(target.max_chunk.load(Ordering::SeqCst), MIN_CHUNK_SIZE);
+        assert!(target.max_read_ahead.load(Ordering::SeqCst) <= max_read_ahead);
+        assert!(store.load(&path, &v2_hash).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_upload_hash_mismatch_aborts() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("large.bin");
+        let content = vec![7u8; MIN_CHUNK_SIZE * 3];
+        std::fs::write(&path, &content).unwrap();
+
+        let target = Arc::new(SlowTarget::default());
+        let manager =
+            TransferManager::new(1, 1, 0, 0, 0, MIN_CHUNK_SIZE).with_upload_target(target.clone());
+        // 声明的哈希与磁盘上的内容不一致（读取期间文件被修改）
+        let request = upload_request(&path, b"stale content", None);
+
+        let err = manager.upload_file(request, |_| {}).await.unwrap_err();
+        assert!(err.to_string().contains("文件哈希不匹配"));
+        // 接收方收到错误，没有完成上传
+        assert!(target.received_hash.lock().unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn test_calculate_hash() {
         let content = b"Hello, World!";
@@ -436,6 +1428,33 @@ mod tests {
         assert!(!hash.is_empty());
     }
 
+    #[test]
+    fn test_chunks_round_trip_at_custom_size() {
+        let manager = TransferManager::new(1, 1, 0, 0, 0, MIN_CHUNK_SIZE);
+        assert_eq!(manager.chunk_size(), MIN_CHUNK_SIZE);
+
+        // 小于一个分块
+        let small = b"small file".to_vec();
+        let chunks: Vec<_> = manager.split_chunks(&small).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(TransferManager::assemble_chunks(chunks).unwrap(), small);
+
+        // 跨越多个分块，最后一个分块不满
+        let large: Vec<u8> = (0..MIN_CHUNK_SIZE * 5 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let chunks: Vec<_> = manager.split_chunks(&large).collect();
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks[..5].iter().all(|(_, c)| c.len() == MIN_CHUNK_SIZE));
+        assert_eq!(chunks[5].1.len(), 123);
+        assert_eq!(TransferManager::assemble_chunks(chunks).unwrap(), large);
+
+        // 缺失分块时无法重组
+        let mut chunks: Vec<_> = manager.split_chunks(&large).collect();
+        chunks.remove(2);
+        assert!(TransferManager::assemble_chunks(chunks).is_err());
+    }
+
     #[test]
     fn test_transfer_progress() {
         let progress = TransferProgress {
@@ -461,4 +1480,59 @@ mod tests {
 
         assert_eq!(completed.progress_percent(), 100.0);
     }
+
+    /// 读取若干字节后返回错误，模拟下载中断
+    struct FailingReader {
+        remaining: Vec<u8>,
+    }
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if self.remaining.is_empty() {
+                return std::task::Poll::Ready(Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "连接中断",
+                )));
+            }
+            let chunk: Vec<u8> = self.remaining.drain(..).collect();
+            buf.put_slice(&chunk);
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_replaces_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join(".claude.json");
+        std::fs::write(&target, r#"{"old":true}"#).unwrap();
+
+        write_atomic(&target, r#"{"new":true}"#).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), r#"{"new":true}"#);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic_failure_preserves_original() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let target = temp_dir.path().join(".claude.json");
+        std::fs::write(&target, r#"{"projects":{}}"#).unwrap();
+
+        let reader = FailingReader {
+            remaining: br#"{"proj"#.to_vec(),
+        };
+        let result = write_atomic_from_reader(&target, reader).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            std::fs::read_to_string(&target).unwrap(),
+            r#"{"projects":{}}"#
+        );
+        // 临时文件已清理
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
 }
@@ -2,14 +2,17 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use notify::{Event, EventKind, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc;
 use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tracing::{debug, info, warn};
 
+use crate::content_hash::HashAlgorithm;
+
 /// 文件事件
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct FileEvent {
@@ -44,6 +47,9 @@ pub struct FileWatcher {
     /// 监控目录
     watch_dir: PathBuf,
 
+    /// 附加监控目录
+    additional_dirs: Vec<PathBuf>,
+
     /// 事件发送器
     event_tx: mpsc::UnboundedSender<FileEvent>,
 
@@ -58,6 +64,12 @@ pub struct FileWatcher {
 
     /// 排除模式
     exclude_patterns: Vec<String>,
+
+    /// 是否跟随符号链接
+    follow_symlinks: bool,
+
+    /// 同时防抖的文件数上限
+    max_pending_files: usize,
 }
 
 impl FileWatcher {
@@ -72,56 +84,111 @@ impl FileWatcher {
     ) -> Self {
         Self {
             watch_dir,
+            additional_dirs: Vec::new(),
             event_tx,
             debounce_delay,
             batch_window,
             exclude_dirs,
             exclude_patterns,
+            follow_symlinks: false,
+            max_pending_files: DEFAULT_MAX_PENDING_FILES,
         }
     }
 
+    /// 设置是否跟随符号链接
+    ///
+    /// notify 在递归监控时会进入符号链接目录，不跟随时由 should_exclude 过滤这些事件
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// 设置附加监控目录（与主目录使用相同的排除规则）
+    pub fn with_additional_dirs(mut self, additional_dirs: Vec<PathBuf>) -> Self {
+        self.additional_dirs = additional_dirs;
+        self
+    }
+
+    /// 设置同时防抖的文件数上限（超出的文件事件合并排队）
+    pub fn with_max_pending_files(mut self, max_pending_files: usize) -> Self {
+        self.max_pending_files = max_pending_files;
+        self
+    }
+
+    /// 所有监控目录（主目录在前）
+    fn watch_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.watch_dir).chain(&self.additional_dirs)
+    }
+
     /// 启动监控
+    ///
+    /// notify 在自己的线程中回调，原始事件经通道转发到异步任务，过滤排除路径后交给去重器。
+    /// 返回的任务持有 notify watcher，任务结束时停止监控。
     pub fn spawn(self) -> Result<tokio::task::JoinHandle<()>> {
         use notify::recommended_watcher;
 
         // 创建事件去重器，使用 Arc<TokioMutex<>> 包装以支持共享可变访问
-        let deduplicator = Arc::new(TokioMutex::new(EventDeduplicator::new(
-            self.debounce_delay,
-            self.batch_window,
-            self.event_tx.clone(),
-        )));
-
-        let deduplicator_clone = deduplicator.clone();
+        let deduplicator = Arc::new(TokioMutex::new(
+            EventDeduplicator::new(
+                Duration::from_millis(self.debounce_delay),
+                Duration::from_secs(self.batch_window),
+                self.event_tx.clone(),
+            )
+            .with_max_pending_files(self.max_pending_files),
+        ));
 
         // 创建 notify watcher
-        let mut watcher = recommended_watcher(move |res: notify::Result<Event>| {
-            if let Ok(event) = res {
-                // 使用 try_lock 避免在同步上下文中阻塞
-                if let Ok(_dedup) = deduplicator_clone.try_lock() {
-                    // 注意：这里需要处理不可变引用，因为 try_lock 返回的是 MutexGuard
-                    // 但 handle_event 需要 &mut self
-                    // 这是一个临时解决方案，实际需要重构 EventDeduplicator
-                    warn!("处理文件事件: {:?}", event.paths);
-                }
+        let (raw_tx, mut raw_rx) = mpsc::unbounded_channel::<Event>();
+        let mut watcher = recommended_watcher(move |res: notify::Result<Event>| match res {
+            Ok(event) => {
+                let _ = raw_tx.send(event);
             }
+            Err(e) => warn!("文件监控错误: {}", e),
         })
         .context("创建文件监控器失败")?;
 
         // 监控目录
-        watcher
-            .watch(&self.watch_dir, RecursiveMode::Recursive)
-            .with_context(|| format!("无法监控目录: {:?}", self.watch_dir))?;
-
-        info!("开始监控目录: {:?}", self.watch_dir);
+        for dir in self.watch_dirs() {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .with_context(|| format!("无法监控目录: {:?}", dir))?;
+            info!("开始监控目录: {:?}", dir);
+        }
 
         // 启动去重器的批处理任务
-        let handle = EventDeduplicator::spawn_batch_processor_wrapper(deduplicator);
+        let batch_handle = EventDeduplicator::spawn_batch_processor_wrapper(deduplicator.clone());
+
+        Ok(tokio::spawn(async move {
+            let _watcher = watcher;
+
+            while let Some(mut event) = raw_rx.recv().await {
+                event.paths.retain(|path| !self.should_exclude(path));
+                if event.paths.is_empty() {
+                    continue;
+                }
 
-        Ok(handle)
+                if let Err(e) = deduplicator.lock().await.handle_event(event) {
+                    debug!("跳过文件事件: {}", e);
+                }
+            }
+
+            batch_handle.abort();
+        }))
     }
 
     /// 检查路径是否应该被排除
     fn should_exclude(&self, path: &Path) -> bool {
+        // 不跟随符号链接时，跳过经由符号链接访问到的路径
+        let root = self
+            .watch_dirs()
+            .filter(|dir| path.starts_with(dir))
+            .max_by_key(|dir| dir.components().count())
+            .unwrap_or(&self.watch_dir);
+        if !self.follow_symlinks && is_symlinked_path(root, path) {
+            debug!("跳过符号链接: {:?}", path);
+            return true;
+        }
+
         // 检查排除目录
         for exclude_dir in &self.exclude_dirs {
             if path.starts_with(exclude_dir) {
@@ -144,56 +211,83 @@ impl FileWatcher {
     }
 }
 
+/// 默认同时防抖的文件数上限
+pub const DEFAULT_MAX_PENDING_FILES: usize = 1024;
+
 /// 事件去重器
+///
+/// 防抖先于批处理：同一路径在防抖延迟内的连续事件只保留最后一个，
+/// 防抖结束后事件进入批处理队列，由批处理器按批处理窗口统一发出。
+/// 批处理窗口为 0 时不做批处理，防抖结束后立即发送。
+///
+/// 同时运行的防抖定时器数量受信号量限制，超出的路径合并到等待队列，
+/// 由到期的定时器依次接手，大量文件同时变化时不会为每个路径各创建一个任务。
 struct EventDeduplicator {
-    /// 防抖延迟（毫秒）
-    debounce_delay: u64,
+    /// 防抖延迟
+    debounce_delay: Duration,
 
-    /// 批处理窗口（秒）
-    batch_window: u64,
+    /// 批处理窗口
+    batch_window: Duration,
 
     /// 事件发送器
     event_tx: mpsc::UnboundedSender<FileEvent>,
 
-    /// 待处理的事件（路径 -> 事件信息）
-    pending_events: HashMap<PathBuf, PendingEvent>,
+    /// 待处理的事件（与防抖定时器共享）
+    pending: Arc<std::sync::Mutex<PendingEvents>>,
+
+    /// 防抖定时器许可
+    timer_permits: Arc<Semaphore>,
 
-    /// 批处理队列中的事件
-    batch_queue: Vec<FileEvent>,
+    /// 批处理队列中的事件（防抖定时器写入，flush_batch 取出）
+    batch_queue: Arc<std::sync::Mutex<Vec<FileEvent>>>,
 
     /// 上次批处理时间
     last_batch_time: Option<DateTime<Utc>>,
 }
 
+/// 待处理的事件
+#[derive(Default)]
+struct PendingEvents {
+    /// 路径 -> 事件信息
+    events: HashMap<PathBuf, PendingEvent>,
+
+    /// 等待空闲定时器的路径（按到达顺序）
+    overflow: VecDeque<PathBuf>,
+}
+
 /// 待处理的事件信息
 struct PendingEvent {
-    /// 文件事件
+    /// 文件事件（同一路径只保留最后一个）
     event: FileEvent,
 
-    /// 最后更新时间
-    last_update: DateTime<Utc>,
-
-    /// 防抖定时器句柄
-    debounce_handle: Option<tokio::task::JoinHandle<()>>,
+    /// 防抖截止时间（每个新事件都会推后）
+    deadline: tokio::time::Instant,
 }
 
 impl EventDeduplicator {
     /// 创建新的去重器
     fn new(
-        debounce_delay: u64,
-        batch_window: u64,
+        debounce_delay: Duration,
+        batch_window: Duration,
         event_tx: mpsc::UnboundedSender<FileEvent>,
     ) -> Self {
         Self {
             debounce_delay,
             batch_window,
             event_tx,
-            pending_events: HashMap::new(),
-            batch_queue: Vec::new(),
+            pending: Arc::default(),
+            timer_permits: Arc::new(Semaphore::new(DEFAULT_MAX_PENDING_FILES)),
+            batch_queue: Arc::new(std::sync::Mutex::new(Vec::new())),
             last_batch_time: None,
         }
     }
 
+    /// 设置同时运行的防抖定时器数量上限
+    fn with_max_pending_files(mut self, max_pending_files: usize) -> Self {
+        self.timer_permits = Arc::new(Semaphore::new(max_pending_files));
+        self
+    }
+
     /// 处理文件系统事件
     fn handle_event(&mut self, event: Event) -> Result<()> {
         // 跳过不需要的事件类型
@@ -246,45 +340,97 @@ impl EventDeduplicator {
     /// 添加到待处理队列
     fn add_to_pending(&mut self, event: FileEvent) {
         let path = event.path.clone();
-        let now = Utc::now();
+        let deadline = tokio::time::Instant::now() + self.debounce_delay;
 
-        // 取消之前的防抖定时器
-        if let Some(prev) = self.pending_events.get(&path) {
-            if let Some(handle) = &prev.debounce_handle {
-                handle.abort();
-            }
-        }
+        let mut pending = self.pending.lock().unwrap();
 
-        // 创建新的防抖定时器
-        let debounce_handle = self.spawn_debounce_timer(path.clone(), event.clone());
+        // 路径已在等待中：替换为最新事件并推后截止时间，由原定时器处理
+        if let Some(existing) = pending.events.get_mut(&path) {
+            existing.event = event;
+            existing.deadline = deadline;
+            return;
+        }
 
-        // 更新待处理事件
-        let pending = PendingEvent {
-            event,
-            last_update: now,
-            debounce_handle: Some(debounce_handle),
-        };
+        pending
+            .events
+            .insert(path.clone(), PendingEvent { event, deadline });
 
-        self.pending_events.insert(path, pending);
+        match self.timer_permits.clone().try_acquire_owned() {
+            Ok(permit) => self.spawn_debounce_timer(path, permit),
+            Err(_) => {
+                debug!("防抖定时器已满，排队等待: {:?}", path);
+                pending.overflow.push_back(path);
+            }
+        }
     }
 
     /// 创建防抖定时器
-    fn spawn_debounce_timer(
-        &self,
-        _path: PathBuf,
-        event: FileEvent,
-    ) -> tokio::task::JoinHandle<()> {
-        let delay = Duration::from_millis(self.debounce_delay);
+    ///
+    /// 定时器持有一个许可，路径到期发出事件后继续接手等待队列中的下一个路径，
+    /// 队列为空时释放许可并退出。
+    fn spawn_debounce_timer(&self, path: PathBuf, permit: OwnedSemaphorePermit) {
+        let pending = self.pending.clone();
+        let batch_window = self.batch_window;
         let event_tx = self.event_tx.clone();
+        let batch_queue = self.batch_queue.clone();
 
         tokio::spawn(async move {
-            tokio::time::sleep(delay).await;
+            let mut path = path;
+            let mut permit = Some(permit);
 
-            // 防抖延迟后发送事件
+            loop {
+                let due = {
+                    let mut pending = pending.lock().unwrap();
+                    match pending.events.get(&path) {
+                        Some(entry) if entry.deadline > tokio::time::Instant::now() => {
+                            Err(entry.deadline)
+                        }
+                        _ => {
+                            let event = pending.events.remove(&path).map(|entry| entry.event);
+                            let next = pending.overflow.pop_front();
+                            if next.is_none() {
+                                // 在锁内释放许可，新事件不会在许可释放前进入无人处理的等待队列
+                                permit.take();
+                            }
+                            Ok((event, next))
+                        }
+                    }
+                };
+
+                match due {
+                    Err(deadline) => tokio::time::sleep_until(deadline).await,
+                    Ok((event, next)) => {
+                        if let Some(event) = event {
+                            Self::emit(event, batch_window, &event_tx, &batch_queue);
+                        }
+                        match next {
+                            Some(next) => path = next,
+                            None => return,
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// 发出防抖结束的事件
+    fn emit(
+        event: FileEvent,
+        batch_window: Duration,
+        event_tx: &mpsc::UnboundedSender<FileEvent>,
+        batch_queue: &std::sync::Mutex<Vec<FileEvent>>,
+    ) {
+        if batch_window.is_zero() {
             if let Err(e) = event_tx.send(event) {
                 warn!("发送文件事件失败: {}", e);
             }
-        })
+            return;
+        }
+
+        // 防抖延迟后进入批处理队列，同一路径在一个批次内只保留最后一个事件
+        let mut queue = batch_queue.lock().unwrap();
+        queue.retain(|queued| queued.path != event.path);
+        queue.push(event);
     }
 
     /// 启动批处理器（包装 Arc<TokioMutex<>>）
@@ -297,41 +443,32 @@ impl EventDeduplicator {
                 dedup.batch_window
             };
 
-            let mut interval = tokio::time::interval(Duration::from_secs(batch_window));
-
-            loop {
-                interval.tick().await;
-
-                // 批量发送待处理的事件
-                let mut dedup = deduplicator.lock().await;
-                dedup.flush_batch().await;
+            if batch_window.is_zero() {
+                return;
             }
-        })
-    }
 
-    /// 启动批处理器
-    fn spawn_batch_processor(mut self) -> tokio::task::JoinHandle<()> {
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(self.batch_window));
+            let mut interval = tokio::time::interval(batch_window);
 
             loop {
                 interval.tick().await;
 
                 // 批量发送待处理的事件
-                self.flush_batch().await;
+                let mut dedup = deduplicator.lock().await;
+                dedup.flush_batch().await;
             }
         })
     }
 
     /// 批量发送事件
     async fn flush_batch(&mut self) {
-        if self.batch_queue.is_empty() {
+        let events = std::mem::take(&mut *self.batch_queue.lock().unwrap());
+        if events.is_empty() {
             return;
         }
 
-        debug!("批量发送 {} 个文件事件", self.batch_queue.len());
+        debug!("批量发送 {} 个文件事件", events.len());
 
-        for event in self.batch_queue.drain(..) {
+        for event in events {
             if let Err(e) = self.event_tx.send(event) {
                 warn!("发送文件事件失败: {}", e);
             }
@@ -341,19 +478,228 @@ impl EventDeduplicator {
     }
 }
 
+/// 目录稳定状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettleState {
+    /// 静默期内没有新事件，目录已稳定
+    Settled,
+    /// 仍有活动，继续等待
+    Waiting,
+    /// 超过最长等待时间
+    TimedOut,
+}
+
+/// 目录稳定检测器（启动时等待其他工具写完配置文件）
+#[derive(Debug, Clone)]
+pub struct SettleDetector {
+    /// 静默期
+    quiet_period: Duration,
+
+    /// 最长等待时间
+    max_wait: Duration,
+
+    /// 开始等待的时间
+    started_at: Instant,
+
+    /// 最后一次活动时间
+    last_activity: Instant,
+}
+
+impl SettleDetector {
+    /// 创建新的稳定检测器
+    pub fn new(quiet_period: Duration, max_wait: Duration, now: Instant) -> Self {
+        Self {
+            quiet_period,
+            max_wait,
+            started_at: now,
+            last_activity: now,
+        }
+    }
+
+    /// 记录一次文件活动
+    pub fn record_activity(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// 检查当前状态
+    pub fn poll(&self, now: Instant) -> SettleState {
+        if now.duration_since(self.last_activity) >= self.quiet_period {
+            SettleState::Settled
+        } else if now.duration_since(self.started_at) >= self.max_wait {
+            SettleState::TimedOut
+        } else {
+            SettleState::Waiting
+        }
+    }
+
+    /// 距离下一次需要检查的剩余时间
+    pub fn time_until_deadline(&self, now: Instant) -> Duration {
+        let quiet_deadline = self.last_activity + self.quiet_period;
+        let max_deadline = self.started_at + self.max_wait;
+        quiet_deadline
+            .min(max_deadline)
+            .saturating_duration_since(now)
+    }
+}
+
+/// 等待目录在静默期内没有任何变更（最多等待 max_wait）
+pub async fn wait_for_settle(
+    dir: &Path,
+    quiet_period: Duration,
+    max_wait: Duration,
+) -> Result<SettleState> {
+    use notify::recommended_watcher;
+
+    if quiet_period.is_zero() {
+        return Ok(SettleState::Settled);
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = recommended_watcher(move |res: notify::Result<Event>| {
+        if res.is_ok() {
+            let _ = tx.send(());
+        }
+    })
+    .context("创建文件监控器失败")?;
+    watcher
+        .watch(dir, RecursiveMode::Recursive)
+        .with_context(|| format!("无法监控目录: {:?}", dir))?;
+
+    let mut detector = SettleDetector::new(quiet_period, max_wait, Instant::now());
+
+    loop {
+        let state = detector.poll(Instant::now());
+        if state != SettleState::Waiting {
+            return Ok(state);
+        }
+
+        let timeout = detector.time_until_deadline(Instant::now());
+        if let Ok(Some(())) = tokio::time::timeout(timeout, rx.recv()).await {
+            detector.record_activity(Instant::now());
+        }
+    }
+}
+
+/// 等待文件在静默期内大小和修改时间都不再变化（最多等待 max_wait）
+///
+/// 防抖只保证一段时间内没有文件事件，编辑器分多次写入时仍可能读到写了一半的内容；
+/// 最后修改时间早于静默期的文件直接视为已稳定。
+pub async fn wait_for_file_stable(
+    path: &Path,
+    quiet_period: Duration,
+    max_wait: Duration,
+) -> SettleState {
+    if quiet_period.is_zero() {
+        return SettleState::Settled;
+    }
+
+    let mut last = write_snapshot(path);
+    let idle = last
+        .and_then(|(_, modified)| modified)
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if idle.is_some_and(|idle| idle >= quiet_period) && !is_locked_for_writing(path) {
+        return SettleState::Settled;
+    }
+
+    let poll_interval = (quiet_period / 4).max(Duration::from_millis(10));
+    let mut detector = SettleDetector::new(quiet_period, max_wait, Instant::now());
+
+    loop {
+        let now = Instant::now();
+        let state = detector.poll(now);
+        if state != SettleState::Waiting {
+            return state;
+        }
+
+        tokio::time::sleep(poll_interval.min(detector.time_until_deadline(now))).await;
+        let current = write_snapshot(path);
+        if current != last || is_locked_for_writing(path) {
+            debug!("文件仍在写入: {:?}", path);
+            detector.record_activity(Instant::now());
+            last = current;
+        }
+    }
+}
+
+/// 文件大小和修改时间（文件不存在时为 None）
+fn write_snapshot(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    std::fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.len(), metadata.modified().ok()))
+}
+
+/// 文件是否被其他进程独占打开（Windows 上编辑器保存期间会拒绝共享读取）
+#[cfg(windows)]
+fn is_locked_for_writing(path: &Path) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    matches!(
+        std::fs::File::open(path),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION)
+    )
+}
+
+#[cfg(not(windows))]
+fn is_locked_for_writing(_path: &Path) -> bool {
+    false
+}
+
+/// 检查 root 之下的路径（或其任一上级目录）是否为符号链接
+pub fn is_symlinked_path(root: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+
+    let mut current = root.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        if std::fs::symlink_metadata(&current)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false)
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// 被跳过的文件及原因
+pub type SkippedFiles = Vec<(PathBuf, String)>;
+
 /// 文件扫描器（用于全量同步）
 pub struct FileScanner {
     /// 扫描目录
     scan_dir: PathBuf,
 
+    /// 附加扫描目录
+    additional_dirs: Vec<PathBuf>,
+
     /// 排除目录
     exclude_dirs: Vec<PathBuf>,
 
     /// 排除模式
     exclude_patterns: Vec<String>,
 
+    /// 任意层级按名称排除的目录
+    exclude_dir_names: Vec<String>,
+
     /// 包含的文件类型
     include_types: Vec<String>,
+
+    /// 最小文件大小（字节）
+    min_file_size: Option<u64>,
+
+    /// 最大文件大小（字节）
+    max_file_size: Option<u64>,
+
+    /// 是否跟随符号链接
+    follow_symlinks: bool,
+
+    /// 只扫描在此时间之后修改的文件
+    modified_since: Option<DateTime<Utc>>,
+
+    /// 内容哈希算法
+    hash_algorithm: HashAlgorithm,
 }
 
 impl FileScanner {
@@ -366,22 +712,94 @@ impl FileScanner {
     ) -> Self {
         Self {
             scan_dir,
+            additional_dirs: Vec::new(),
             exclude_dirs,
             exclude_patterns,
+            exclude_dir_names: Vec::new(),
             include_types,
+            min_file_size: None,
+            max_file_size: None,
+            follow_symlinks: false,
+            modified_since: None,
+            hash_algorithm: HashAlgorithm::default(),
         }
     }
 
+    /// 设置附加扫描目录（排除目录需包含各目录下对应的路径）
+    pub fn with_additional_dirs(mut self, additional_dirs: Vec<PathBuf>) -> Self {
+        self.additional_dirs = additional_dirs;
+        self
+    }
+
+    /// 设置任意层级按名称排除的目录，扫描时不进入这些目录
+    pub fn with_exclude_dir_names(mut self, exclude_dir_names: Vec<String>) -> Self {
+        self.exclude_dir_names = exclude_dir_names;
+        self
+    }
+
+    /// 设置文件大小范围，超出范围的文件不会被扫描到
+    pub fn with_size_limits(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_file_size = min;
+        self.max_file_size = max;
+        self
+    }
+
+    /// 设置是否跟随符号链接（跟随时会检测并跳过循环链接）
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// 设置修改时间下限，更早修改的文件作为跳过的文件返回
+    pub fn with_modified_since(mut self, since: Option<DateTime<Utc>>) -> Self {
+        self.modified_since = since;
+        self
+    }
+
+    /// 设置内容哈希算法
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
     /// 扫描所有文件
     pub fn scan(&self) -> Result<Vec<PathBuf>> {
-        let mut files = Vec::new();
+        Ok(self.scan_with_skipped()?.0)
+    }
 
-        for entry in walkdir::WalkDir::new(&self.scan_dir)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
+    /// 扫描所有文件，同时返回因大小或修改时间超出范围而跳过的文件及原因
+    pub fn scan_with_skipped(&self) -> Result<(Vec<PathBuf>, SkippedFiles)> {
+        let mut files = Vec::new();
+        let mut skipped = Vec::new();
+
+        let walkers = std::iter::once(&self.scan_dir)
+            .chain(&self.additional_dirs)
+            .map(|dir| {
+                walkdir::WalkDir::new(dir)
+                    .follow_links(self.follow_symlinks)
+                    .into_iter()
+                    .filter_entry(|entry| !self.is_excluded_dir_name(entry))
+            });
+        for entry in walkers.flatten() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.loop_ancestor().is_some() => {
+                    warn!("检测到符号链接循环，跳过: {:?}", e.path());
+                    continue;
+                }
+                Err(e) => {
+                    debug!("无法访问路径，跳过: {}", e);
+                    continue;
+                }
+            };
             let path = entry.path();
 
+            // 不跟随时显式跳过符号链接（包括指向文件的链接）
+            if !self.follow_symlinks && entry.path_is_symlink() {
+                debug!("跳过符号链接: {:?}", path);
+                continue;
+            }
+
             // 跳过目录
             if path.is_dir() {
                 continue;
@@ -392,30 +810,62 @@ impl FileScanner {
                 continue;
             }
 
-            // 检查文件类型
+            // 检查文件类型和大小
             if !self.should_include(path) {
+                if let Some(reason) = self.size_skip_reason(path) {
+                    skipped.push((path.to_path_buf(), reason));
+                }
+                continue;
+            }
+
+            // 检查修改时间
+            if let Some(reason) = self.modified_skip_reason(path) {
+                skipped.push((path.to_path_buf(), reason));
                 continue;
             }
 
             files.push(path.to_path_buf());
         }
 
-        info!("扫描完成，共找到 {} 个文件", files.len());
+        // 按路径排序，保证每次扫描的处理顺序一致
+        files.sort();
+        skipped.sort();
+
+        info!(
+            "扫描完成，共找到 {} 个文件，{} 个文件因大小或修改时间被跳过",
+            files.len(),
+            skipped.len()
+        );
 
-        Ok(files)
+        Ok((files, skipped))
     }
 
-    /// 计算文件哈希
-    pub fn hash_file(&self, path: &Path) -> Result<String> {
-        use sha2::{Digest, Sha256};
+    /// 文件大小超出配置范围时返回跳过原因
+    pub fn size_skip_reason(&self, path: &Path) -> Option<String> {
+        if self.min_file_size.is_none() && self.max_file_size.is_none() {
+            return None;
+        }
 
-        let content = std::fs::read(path).with_context(|| format!("无法读取文件: {:?}", path))?;
+        let size = std::fs::metadata(path).ok()?.len();
+        file_size_skip_reason(size, self.min_file_size, self.max_file_size)
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(&content);
-        let result = hasher.finalize();
+    /// 文件修改时间早于下限时返回跳过原因
+    fn modified_skip_reason(&self, path: &Path) -> Option<String> {
+        let since = self.modified_since?;
+        let modified = file_modified_time(path)?;
+        (modified <= since).then(|| {
+            format!(
+                "修改时间 {} 早于 {}",
+                modified.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            )
+        })
+    }
 
-        Ok(format!("{:x}", result))
+    /// 计算文件哈希
+    pub fn hash_file(&self, path: &Path) -> Result<String> {
+        self.hash_algorithm.hash_file(path)
     }
 
     /// 获取文件元信息
@@ -423,11 +873,7 @@ impl FileScanner {
         let metadata =
             std::fs::metadata(path).with_context(|| format!("无法获取文件元信息: {:?}", path))?;
 
-        let modified = std::fs::metadata(path)
-            .and_then(|m| m.modified())
-            .ok()
-            .and_then(|t| DateTime::from_timestamp(t_secs(&t), 0))
-            .unwrap_or_else(Utc::now);
+        let modified = file_modified_time(path).unwrap_or_else(Utc::now);
 
         let hash = self.hash_file(path)?;
 
@@ -436,9 +882,20 @@ impl FileScanner {
             size: metadata.len(),
             modified,
             hash,
+            mode: crate::transfer::file_mode(&metadata),
         })
     }
 
+    /// 是否为按名称排除的目录（扫描根目录本身不排除）
+    fn is_excluded_dir_name(&self, entry: &walkdir::DirEntry) -> bool {
+        entry.depth() > 0
+            && entry.file_type().is_dir()
+            && self
+                .exclude_dir_names
+                .iter()
+                .any(|name| entry.file_name() == name.as_str())
+    }
+
     /// 检查是否应该排除此路径
     fn should_exclude(&self, path: &Path) -> bool {
         // 检查排除目录
@@ -460,8 +917,12 @@ impl FileScanner {
         false
     }
 
-    /// 检查是否应该包含此文件类型
+    /// 检查是否应该包含此文件（类型和大小）
     fn should_include(&self, path: &Path) -> bool {
+        if self.size_skip_reason(path).is_some() {
+            return false;
+        }
+
         // 如果没有指定文件类型，则包含所有文件
         if self.include_types.is_empty() {
             return true;
@@ -477,6 +938,19 @@ impl FileScanner {
     }
 }
 
+/// 判断文件大小是否超出范围，超出时返回原因
+pub fn file_size_skip_reason(size: u64, min: Option<u64>, max: Option<u64>) -> Option<String> {
+    match (min, max) {
+        (_, Some(max)) if size > max => {
+            Some(format!("文件大小 {} 字节超过上限 {} 字节", size, max))
+        }
+        (Some(min), _) if size < min => {
+            Some(format!("文件大小 {} 字节低于下限 {} 字节", size, min))
+        }
+        _ => None,
+    }
+}
+
 /// 文件信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -491,6 +965,49 @@ pub struct FileInfo {
 
     /// 文件哈希（SHA-256）
     pub hash: String,
+
+    /// Unix 权限位（非 Unix 平台为 None）
+    pub mode: Option<u32>,
+}
+
+/// 获取文件修改时间（精确到秒）
+pub fn file_modified_time(path: &Path) -> Option<DateTime<Utc>> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| DateTime::from_timestamp(t_secs(&t), 0))
+}
+
+/// 解析 `--since` 参数：相对时长（如 `30m`、`2h`、`3d`、`1w`）或 RFC3339 时间戳
+pub fn parse_since(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow::anyhow!("时长缺少单位（s/m/h/d/w）: {}", value))?;
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: i64 = amount.parse().with_context(|| {
+        format!(
+            "无效的时间: {}（示例: 2h、3d 或 2024-01-01T00:00:00Z）",
+            value
+        )
+    })?;
+
+    let duration = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => anyhow::bail!("无效的时长单位: {}（支持 s/m/h/d/w）", unit),
+    }
+    .ok_or_else(|| anyhow::anyhow!("时长过大: {}", value))?;
+
+    Ok(now - duration)
 }
 
 /// 辅助函数：转换 system time 到秒
@@ -532,6 +1049,236 @@ mod tests {
         assert_eq!(files[0], test_file);
     }
 
+    #[test]
+    fn test_scan_skips_nested_excluded_dir_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let nested = root.join("plugins/a/b/node_modules/pkg");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("index.json"), "{}").unwrap();
+        std::fs::create_dir_all(root.join("agents")).unwrap();
+        std::fs::write(root.join("agents/node_modules"), "same name as a dir").unwrap();
+        std::fs::write(root.join("agents/a.md"), "# a").unwrap();
+
+        let scanner = FileScanner::new(root.to_path_buf(), vec![], vec![], vec![])
+            .with_exclude_dir_names(vec!["node_modules".to_string()]);
+        let files = scanner.scan().unwrap();
+
+        assert_eq!(
+            files,
+            vec![root.join("agents/a.md"), root.join("agents/node_modules")]
+        );
+    }
+
+    #[test]
+    fn test_scan_order_is_stable() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in [
+            "zeta.md",
+            "alpha.md",
+            "agents/b.md",
+            "agents/a.md",
+            "skills/x/SKILL.md",
+        ] {
+            let path = temp_dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, name).unwrap();
+        }
+
+        let scanner = FileScanner::new(temp_dir.path().to_path_buf(), vec![], vec![], vec![]);
+        let first = scanner.scan().unwrap();
+        let second = scanner.scan().unwrap();
+
+        assert_eq!(first, second);
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first, sorted);
+    }
+
+    #[test]
+    fn test_scan_size_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("empty.md"), "").unwrap();
+        std::fs::write(temp_dir.path().join("small.md"), "hello").unwrap();
+        std::fs::write(temp_dir.path().join("huge.bin"), vec![0u8; 2048]).unwrap();
+
+        let scanner = FileScanner::new(temp_dir.path().to_path_buf(), vec![], vec![], vec![])
+            .with_size_limits(Some(1), Some(1024));
+        let (files, skipped) = scanner.scan_with_skipped().unwrap();
+
+        assert_eq!(files, vec![temp_dir.path().join("small.md")]);
+        assert_eq!(skipped.len(), 2);
+        assert_eq!(skipped[0].0, temp_dir.path().join("empty.md"));
+        assert!(skipped[0].1.contains("下限"));
+        assert_eq!(skipped[1].0, temp_dir.path().join("huge.bin"));
+        assert!(skipped[1].1.contains("上限"));
+
+        // 边界值本身在范围内
+        assert_eq!(file_size_skip_reason(1024, Some(1), Some(1024)), None);
+        assert_eq!(file_size_skip_reason(0, None, None), None);
+    }
+
+    fn set_modified(path: &Path, modified: DateTime<Utc>) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified.into())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_scan_modified_since() {
+        let temp_dir = TempDir::new().unwrap();
+        let cutoff = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let before = temp_dir.path().join("before.md");
+        let at_cutoff = temp_dir.path().join("at.md");
+        let after = temp_dir.path().join("after.md");
+        for (path, offset) in [(&before, -60), (&at_cutoff, 0), (&after, 60)] {
+            std::fs::write(path, "x").unwrap();
+            set_modified(path, cutoff + chrono::Duration::seconds(offset));
+        }
+
+        let scanner = FileScanner::new(temp_dir.path().to_path_buf(), vec![], vec![], vec![])
+            .with_modified_since(Some(cutoff));
+        let (files, skipped) = scanner.scan_with_skipped().unwrap();
+
+        assert_eq!(files, vec![after]);
+        let skipped: Vec<_> = skipped.into_iter().map(|(path, _)| path).collect();
+        assert_eq!(skipped, vec![at_cutoff, before]);
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = DateTime::parse_from_rfc3339("2024-06-10T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            parse_since("2h", now).unwrap(),
+            now - chrono::Duration::hours(2)
+        );
+        assert_eq!(
+            parse_since("3d", now).unwrap(),
+            now - chrono::Duration::days(3)
+        );
+        assert_eq!(
+            parse_since("2024-06-01T08:00:00+08:00", now).unwrap(),
+            DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap()
+        );
+
+        assert!(parse_since("2", now).is_err());
+        assert!(parse_since("2y", now).is_err());
+        assert!(parse_since("yesterday", now).is_err());
+    }
+
+    #[test]
+    fn test_settle_after_quiet_period() {
+        let start = Instant::now();
+        let mut detector =
+            SettleDetector::new(Duration::from_secs(2), Duration::from_secs(30), start);
+
+        assert_eq!(
+            detector.poll(start + Duration::from_secs(1)),
+            SettleState::Waiting
+        );
+
+        // 静默期内出现新活动，重新计时
+        detector.record_activity(start + Duration::from_secs(1));
+        assert_eq!(
+            detector.poll(start + Duration::from_secs(2)),
+            SettleState::Waiting
+        );
+        assert_eq!(
+            detector.poll(start + Duration::from_secs(3)),
+            SettleState::Settled
+        );
+    }
+
+    /// 每 20 毫秒追加一行，持续 duration
+    fn spawn_writer(path: PathBuf, duration: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let started = Instant::now();
+            while started.elapsed() < duration {
+                let mut file = std::fs::OpenOptions::new()
+                    .append(true)
+                    .open(&path)
+                    .unwrap();
+                writeln!(file, "line").unwrap();
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_file_stable() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("CLAUDE.md");
+        std::fs::write(&path, "").unwrap();
+
+        // 持续写入超过最长等待时间
+        let writer = spawn_writer(path.clone(), Duration::from_millis(600));
+        let state = wait_for_file_stable(
+            &path,
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+        )
+        .await;
+        assert_eq!(state, SettleState::TimedOut);
+
+        // 写入结束后静默期内不再变化
+        writer.await.unwrap();
+        let started = Instant::now();
+        let state =
+            wait_for_file_stable(&path, Duration::from_millis(100), Duration::from_secs(5)).await;
+        assert_eq!(state, SettleState::Settled);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        // 最后修改时间早于静默期的文件无需等待
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let started = Instant::now();
+        let state =
+            wait_for_file_stable(&path, Duration::from_millis(100), Duration::from_secs(5)).await;
+        assert_eq!(state, SettleState::Settled);
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_settle_times_out_under_activity() {
+        let start = Instant::now();
+        let mut detector =
+            SettleDetector::new(Duration::from_secs(2), Duration::from_secs(5), start);
+
+        // 持续活动时一直等待，直到超过最长等待时间
+        for second in 1..5 {
+            let now = start + Duration::from_secs(second);
+            detector.record_activity(now);
+            assert_eq!(detector.poll(now), SettleState::Waiting);
+        }
+
+        let now = start + Duration::from_secs(5);
+        detector.record_activity(now);
+        assert_eq!(detector.poll(now), SettleState::TimedOut);
+        assert_eq!(detector.time_until_deadline(now), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_settle_quiet_dir() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let state = wait_for_settle(
+            temp_dir.path(),
+            Duration::from_millis(50),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(state, SettleState::Settled);
+    }
+
     #[test]
     fn test_file_hash() {
         let temp_dir = TempDir::new().unwrap();
@@ -550,4 +1297,282 @@ mod tests {
         // 相同文件应该有相同哈希
         assert_eq!(hash1, hash2);
     }
+
+    /// 创建 root/b.md、root/sub/c.md，以及指向 root 之外目录的 root/agents 链接、
+    /// 指向文件的 root/link.md 链接和指向 root 的循环链接 root/sub/loop
+    #[cfg(unix)]
+    fn create_symlink_tree(temp_dir: &TempDir) -> PathBuf {
+        use std::os::unix::fs::symlink;
+
+        let root = temp_dir.path().join("root");
+        let external = temp_dir.path().join("external");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::create_dir_all(&external).unwrap();
+        std::fs::write(root.join("b.md"), "b").unwrap();
+        std::fs::write(root.join("sub").join("c.md"), "c").unwrap();
+        std::fs::write(external.join("a.md"), "a").unwrap();
+
+        symlink(&external, root.join("agents")).unwrap();
+        symlink(root.join("b.md"), root.join("link.md")).unwrap();
+        symlink(&root, root.join("sub").join("loop")).unwrap();
+
+        root
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_symlinks_by_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = create_symlink_tree(&temp_dir);
+
+        let scanner = FileScanner::new(root.clone(), vec![], vec![], vec![]);
+        let files = scanner.scan().unwrap();
+
+        assert_eq!(
+            files,
+            vec![root.join("b.md"), root.join("sub").join("c.md")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_follows_symlinks_without_looping() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = create_symlink_tree(&temp_dir);
+
+        let scanner =
+            FileScanner::new(root.clone(), vec![], vec![], vec![]).with_follow_symlinks(true);
+        let files = scanner.scan().unwrap();
+
+        assert_eq!(
+            files,
+            vec![
+                root.join("agents").join("a.md"),
+                root.join("b.md"),
+                root.join("link.md"),
+                root.join("sub").join("c.md"),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_watcher_excludes_symlinked_paths_unless_following() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = create_symlink_tree(&temp_dir);
+        let linked = root.join("agents").join("a.md");
+
+        assert!(is_symlinked_path(&root, &linked));
+        assert!(is_symlinked_path(
+            &root,
+            &root.join("sub").join("loop").join("b.md")
+        ));
+        assert!(!is_symlinked_path(&root, &root.join("sub").join("c.md")));
+
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let watcher = FileWatcher::new(root.clone(), tx, 100, 1, vec![], vec![]);
+        assert!(watcher.should_exclude(&linked));
+        assert!(!watcher.should_exclude(&root.join("b.md")));
+
+        let watcher = watcher.with_follow_symlinks(true);
+        assert!(!watcher.should_exclude(&linked));
+    }
+
+    #[test]
+    fn test_scan_additional_dirs_with_per_root_excludes() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_dir = temp_dir.path().join("claude");
+        let team_dir = temp_dir.path().join("team");
+        for dir in [&main_dir, &team_dir] {
+            std::fs::create_dir_all(dir.join("cache")).unwrap();
+            std::fs::write(dir.join("a.md"), "a").unwrap();
+            std::fs::write(dir.join("cache").join("c.json"), "{}").unwrap();
+        }
+
+        let scanner = FileScanner::new(
+            main_dir.clone(),
+            vec![main_dir.join("cache"), team_dir.join("cache")],
+            vec![],
+            vec![],
+        )
+        .with_additional_dirs(vec![team_dir.clone()]);
+
+        assert_eq!(
+            scanner.scan().unwrap(),
+            vec![main_dir.join("a.md"), team_dir.join("a.md")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watcher_detects_edits_in_additional_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let main_dir = temp_dir.path().join("claude");
+        let team_dir = temp_dir.path().join("team");
+        std::fs::create_dir_all(&main_dir).unwrap();
+        std::fs::create_dir_all(team_dir.join("cache")).unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handle = FileWatcher::new(
+            main_dir.clone(),
+            tx,
+            20,
+            0,
+            vec![main_dir.join("cache"), team_dir.join("cache")],
+            vec![],
+        )
+        .with_additional_dirs(vec![team_dir.clone()])
+        .spawn()
+        .unwrap();
+
+        // 排除目录中的修改不产生事件
+        std::fs::write(team_dir.join("cache").join("c.json"), "{}").unwrap();
+        let edited = team_dir.join("agents.md");
+        std::fs::write(&edited, "team agent").unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.path, edited);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        while let Ok(event) = rx.try_recv() {
+            assert_eq!(event.path, edited);
+        }
+
+        handle.abort();
+    }
+
+    fn modify_event(path: &Path) -> FileEvent {
+        FileEvent {
+            path: path.to_path_buf(),
+            event_type: FileEventType::Modify,
+            timestamp: Utc::now(),
+            is_dir: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rapid_edits_within_debounce_collapse_to_one_event() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut dedup =
+            EventDeduplicator::new(Duration::from_millis(100), Duration::from_secs(1), tx);
+
+        let path = PathBuf::from("/claude/CLAUDE.md");
+        for _ in 0..5 {
+            dedup.add_to_pending(modify_event(&path));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // 防抖结束前没有事件进入批处理队列
+        dedup.flush_batch().await;
+        assert!(rx.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        dedup.flush_batch().await;
+        assert_eq!(rx.try_recv().unwrap().path, path);
+        assert!(rx.try_recv().is_err());
+        assert!(dedup.pending.lock().unwrap().events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_debounced_events_flush_at_batch_boundary() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let dedup = Arc::new(TokioMutex::new(EventDeduplicator::new(
+            Duration::from_millis(20),
+            Duration::from_millis(300),
+            tx,
+        )));
+        let processor = EventDeduplicator::spawn_batch_processor_wrapper(dedup.clone());
+
+        {
+            let mut dedup = dedup.lock().await;
+            for name in ["a.md", "b.md", "c.md"] {
+                dedup.add_to_pending(modify_event(&PathBuf::from("/claude").join(name)));
+            }
+        }
+
+        // 防抖已结束，但事件停留在队列中等待批处理窗口
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(rx.try_recv().is_err());
+        assert_eq!(dedup.lock().await.batch_queue.lock().unwrap().len(), 3);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut flushed = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            flushed.push(event.path);
+        }
+        flushed.sort();
+        assert_eq!(
+            flushed,
+            vec![
+                PathBuf::from("/claude/a.md"),
+                PathBuf::from("/claude/b.md"),
+                PathBuf::from("/claude/c.md"),
+            ]
+        );
+
+        processor.abort();
+    }
+
+    #[tokio::test]
+    async fn test_zero_batch_window_sends_after_debounce() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut dedup = EventDeduplicator::new(Duration::from_millis(20), Duration::ZERO, tx);
+
+        dedup.add_to_pending(modify_event(Path::new("/claude/a.md")));
+        let event = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.path, PathBuf::from("/claude/a.md"));
+    }
+
+    #[tokio::test]
+    async fn test_event_burst_is_bounded_and_keeps_last_event_per_path() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let max_pending = 32;
+        let mut dedup = EventDeduplicator::new(Duration::from_millis(50), Duration::ZERO, tx)
+            .with_max_pending_files(max_pending);
+
+        // 1000 个文件各 10 个事件，每个文件的最后一个事件是删除
+        let paths: Vec<PathBuf> = (0..1000)
+            .map(|i| PathBuf::from(format!("/claude/projects/{}.jsonl", i)))
+            .collect();
+        for round in 0..10 {
+            for path in &paths {
+                let mut event = modify_event(path);
+                if round == 9 {
+                    event.event_type = FileEventType::Remove;
+                }
+                dedup.add_to_pending(event);
+            }
+        }
+
+        // 只有 max_pending 个定时器在运行，其余路径合并后排队
+        {
+            let pending = dedup.pending.lock().unwrap();
+            assert_eq!(pending.events.len(), paths.len());
+            assert_eq!(pending.overflow.len(), paths.len() - max_pending);
+        }
+        assert_eq!(dedup.timer_permits.available_permits(), 0);
+
+        let mut received = HashMap::new();
+        while received.len() < paths.len() {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(received.insert(event.path, event.event_type).is_none());
+        }
+        assert!(received
+            .values()
+            .all(|event_type| *event_type == FileEventType::Remove));
+
+        // 所有定时器退出并归还许可
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(rx.try_recv().is_err());
+        assert!(dedup.pending.lock().unwrap().events.is_empty());
+        assert_eq!(dedup.timer_permits.available_permits(), max_pending);
+    }
 }
@@ -1,13 +1,26 @@
-use crate::state::SyncState;
+use crate::config::ConfigManager;
+use crate::state::{FileProgress, SyncState};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use serde_json::Value;
 use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
 use tokio::sync::Mutex;
-use tauri::State;
+use tauri::{AppHandle, Manager, State};
+
+/// 逐文件传输进度事件名
+pub const PROGRESS_EVENT: &str = "sync-progress";
+
+/// 守护进程控制端口的默认地址（与 claude-sync 的 sync.control_address 默认值一致）
+const DEFAULT_CONTROL_ADDRESS: &str = "127.0.0.1:9466";
 
 #[tauri::command]
 pub async fn start_sync(
     mode: String,
+    app: AppHandle,
     sync_state: State<'_, Arc<Mutex<SyncState>>>,
+    config_manager: State<'_, Arc<Mutex<ConfigManager>>>,
 ) -> Result<String, String> {
     let mut state = sync_state.lock().await;
 
@@ -21,6 +34,17 @@ pub async fn start_sync(
     // TODO: 启动实际的同步任务
     // 这里需要集成 client 模块中的同步引擎
 
+    // 订阅守护进程同步引擎的传输进度并转发给前端
+    let control_address = config_manager
+        .lock()
+        .await
+        .get_config()
+        .await
+        .ok()
+        .and_then(|config| config["sync"]["control_address"].as_str().map(String::from))
+        .unwrap_or_else(|| DEFAULT_CONTROL_ADDRESS.to_string());
+    tauri::async_runtime::spawn(subscribe_progress(app, control_address));
+
     Ok(format!("已启动 {} 模式同步", mode))
 }
 
@@ -54,3 +78,91 @@ pub async fn get_sync_status(
 
     Ok(status)
 }
+
+#[tauri::command]
+pub async fn get_transfer_progress(
+    sync_state: State<'_, Arc<Mutex<SyncState>>>,
+) -> Result<Vec<FileProgress>, String> {
+    let state = sync_state.lock().await;
+    Ok(state.transfers.values().cloned().collect())
+}
+
+/// 守护进程推送的传输进度（`TransferProgress` 的 JSON 形式）
+#[derive(Debug, Deserialize)]
+struct TransferEvent {
+    file_path: String,
+    total_bytes: u64,
+    transferred_bytes: u64,
+    started_at: DateTime<Utc>,
+    is_completed: bool,
+    is_failed: bool,
+}
+
+impl From<TransferEvent> for FileProgress {
+    fn from(event: TransferEvent) -> Self {
+        let percent = if event.total_bytes == 0 {
+            100.0
+        } else {
+            event.transferred_bytes as f64 / event.total_bytes as f64 * 100.0
+        };
+        let elapsed = (Utc::now() - event.started_at).num_milliseconds() as f64 / 1000.0;
+        let rate = if elapsed > 0.0 {
+            event.transferred_bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        Self {
+            file_path: event.file_path,
+            percent,
+            rate,
+            completed: event.is_completed,
+            failed: event.is_failed,
+        }
+    }
+}
+
+/// 通过控制端口订阅守护进程的传输进度，直到同步停止或连接断开
+async fn subscribe_progress(app: AppHandle, control_address: String) {
+    let stream = match TcpStream::connect(&control_address).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            tracing::warn!("无法连接守护进程控制端口 {}: {}", control_address, e);
+            return;
+        }
+    };
+    let (reader, mut writer) = stream.into_split();
+    if let Err(e) = writer.write_all(b"progress\n").await {
+        tracing::warn!("订阅传输进度失败: {}", e);
+        return;
+    }
+
+    // 第一行是守护进程状态，之后每行一个进度事件
+    let mut lines = BufReader::new(reader).lines();
+    match lines.next_line().await {
+        Ok(Some(status)) if status.starts_with("ok ") => {}
+        other => {
+            tracing::warn!("守护进程拒绝进度订阅: {:?}", other);
+            return;
+        }
+    }
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !app.state::<Arc<Mutex<SyncState>>>().lock().await.is_syncing {
+            break;
+        }
+        match serde_json::from_str::<TransferEvent>(&line) {
+            Ok(event) => forward_progress(&app, event.into()).await,
+            Err(e) => tracing::warn!("无法解析传输进度: {}", e),
+        }
+    }
+}
+
+/// 记录传输进度并推送给前端
+pub async fn forward_progress(app: &AppHandle, progress: FileProgress) {
+    let sync_state = app.state::<Arc<Mutex<SyncState>>>();
+    sync_state.lock().await.record_transfer(progress.clone());
+
+    if let Err(e) = app.emit_all(PROGRESS_EVENT, progress) {
+        tracing::warn!("推送传输进度失败: {}", e);
+    }
+}
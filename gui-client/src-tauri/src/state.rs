@@ -1,4 +1,17 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// 单个文件的传输进度（推送给前端）
+#[derive(Debug, Clone, Serialize)]
+pub struct FileProgress {
+    pub file_path: String,
+    pub percent: f64,
+    /// 传输速率（字节/秒）
+    pub rate: f64,
+    pub completed: bool,
+    pub failed: bool,
+}
 
 #[derive(Debug, Clone)]
 pub struct SyncState {
@@ -8,6 +21,7 @@ pub struct SyncState {
     pub synced_count: usize,
     pub failed_count: usize,
     pub progress: f64,
+    pub transfers: BTreeMap<String, FileProgress>,
 }
 
 impl SyncState {
@@ -19,6 +33,7 @@ impl SyncState {
             synced_count: 0,
             failed_count: 0,
             progress: 0.0,
+            transfers: BTreeMap::new(),
         }
     }
 
@@ -26,12 +41,17 @@ impl SyncState {
         self.is_syncing = false;
         self.sync_mode = None;
         self.progress = 0.0;
+        self.transfers.clear();
     }
 
     pub fn update_progress(&mut self, progress: f64) {
         self.progress = progress.min(100.0).max(0.0);
     }
 
+    pub fn record_transfer(&mut self, progress: FileProgress) {
+        self.transfers.insert(progress.file_path.clone(), progress);
+    }
+
     pub fn increment_synced(&mut self) {
         self.synced_count += 1;
     }
@@ -7,6 +7,7 @@ const state = {
     syncStatus: null,
     rules: [],
     devices: [],
+    transfers: {},
 };
 
 // Initialize app
@@ -15,9 +16,28 @@ async function init() {
     await checkAuthStatus();
     setupEventListeners();
     setupNavigation();
+    await listenTransferProgress();
     updateUI();
 }
 
+// Subscribe to per-file transfer progress events
+async function listenTransferProgress() {
+    try {
+        const transfers = await window.__TAURI__.invoke('get_transfer_progress');
+        transfers.forEach(progress => {
+            state.transfers[progress.file_path] = progress;
+        });
+        renderTransfers();
+
+        await window.__TAURI__.event.listen('sync-progress', event => {
+            state.transfers[event.payload.file_path] = event.payload;
+            renderTransfers();
+        });
+    } catch (error) {
+        console.error('Failed to subscribe transfer progress:', error);
+    }
+}
+
 // Load configuration
 async function loadConfig() {
     try {
@@ -426,6 +446,28 @@ function renderDevices() {
     `).join('');
 }
 
+// Render per-file transfer progress
+function renderTransfers() {
+    const container = document.getElementById('fileList');
+    const transfers = Object.values(state.transfers);
+
+    if (transfers.length === 0) {
+        container.innerHTML = '<div class="empty-state">暂无文件</div>';
+        return;
+    }
+
+    container.innerHTML = transfers.map(progress => `
+        <div class="file-item">
+            <div class="file-info">
+                <div class="file-name">${escapeHtml(progress.file_path)}</div>
+            </div>
+            <div class="file-status">
+                ${progress.failed ? '失败' : progress.completed ? '完成' : Math.round(progress.percent) + '% · ' + formatRate(progress.rate)}
+            </div>
+        </div>
+    `).join('');
+}
+
 // Load settings
 function loadSettings() {
     if (!state.config) return;
@@ -513,6 +555,13 @@ function formatTime(date) {
     return date.toLocaleDateString('zh-CN');
 }
 
+// Format transfer rate
+function formatRate(bytesPerSecond) {
+    if (bytesPerSecond < 1024) return Math.round(bytesPerSecond) + ' B/s';
+    if (bytesPerSecond < 1048576) return (bytesPerSecond / 1024).toFixed(1) + ' KB/s';
+    return (bytesPerSecond / 1048576).toFixed(1) + ' MB/s';
+}
+
 // Escape HTML
 function escapeHtml(text) {
     const div = document.createElement('div');
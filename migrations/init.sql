@@ -13,7 +13,8 @@ CREATE TABLE users (
     password_hash VARCHAR(255) NOT NULL,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
-    is_active BOOLEAN DEFAULT true
+    is_active BOOLEAN DEFAULT true,
+    totp_secret TEXT
 );
 
 -- 设备表
@@ -61,17 +62,28 @@ CREATE TABLE file_versions (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
     user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
     file_path TEXT NOT NULL, -- 相对于 .claude 根目录的路径
-    file_hash VARCHAR(64) NOT NULL, -- SHA-256
+    file_hash VARCHAR(128) NOT NULL, -- SHA-256，或带 blake3: 前缀的 BLAKE3
     file_size BIGINT NOT NULL,
     storage_path TEXT NOT NULL, -- 对象存储中的路径
     version_number INTEGER NOT NULL,
     device_id UUID NOT NULL REFERENCES devices(id), -- 创建此版本的设备
     parent_version_id UUID REFERENCES file_versions(id), -- 父版本
     is_deleted BOOLEAN DEFAULT false,
+    file_mode INTEGER, -- Unix 权限位（如 0755），NULL 表示未记录
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     UNIQUE(user_id, file_path, version_number)
 );
 
+-- 文件分块清单表（内容定义分块，按顺序重组文件）
+CREATE TABLE file_chunks (
+    file_version_id UUID NOT NULL REFERENCES file_versions(id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    chunk_hash VARCHAR(64) NOT NULL, -- SHA-256
+    chunk_offset BIGINT NOT NULL,
+    chunk_size INTEGER NOT NULL,
+    PRIMARY KEY (file_version_id, chunk_index)
+);
+
 -- 文件同步状态表
 CREATE TABLE sync_states (
     id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
@@ -119,6 +131,23 @@ CREATE TABLE sync_sessions (
     error_message TEXT
 );
 
+-- 文本内容索引表（按内容哈希去重，二进制文件不写入，用于内容搜索）
+CREATE TABLE file_content_index (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    file_hash VARCHAR(128) NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (user_id, file_hash)
+);
+
+-- 存储对象引用计数表（同一内容可被多个文件版本共享，计数归零后才删除对象）
+CREATE TABLE storage_objects (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    file_hash VARCHAR(128) NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0 CHECK (ref_count >= 0),
+    PRIMARY KEY (user_id, file_hash)
+);
+
 -- === 索引优化 ===
 
 -- 用户表索引
@@ -146,6 +175,18 @@ CREATE INDEX idx_file_versions_user_path ON file_versions(user_id, file_path);
 CREATE INDEX idx_file_versions_hash ON file_versions(file_hash);
 CREATE INDEX idx_file_versions_user_device ON file_versions(user_id, device_id);
 CREATE INDEX idx_file_versions_created_at ON file_versions(created_at DESC);
+-- 当前版本索引（按文件取最新版本号）
+CREATE INDEX idx_file_versions_current ON file_versions(user_id, file_path, version_number DESC);
+-- 忽略大小写的路径索引（合并仅大小写不同的路径）
+CREATE INDEX idx_file_versions_user_path_lower ON file_versions(user_id, LOWER(file_path));
+-- 路径搜索（三元组索引支持 ILIKE 前缀和子串匹配）
+CREATE INDEX idx_file_versions_path_trgm ON file_versions USING gin (file_path gin_trgm_ops);
+
+-- 内容搜索索引
+CREATE INDEX idx_file_content_index_trgm ON file_content_index USING gin (content gin_trgm_ops);
+
+-- 文件分块索引
+CREATE INDEX idx_file_chunks_hash ON file_chunks(chunk_hash);
 
 -- 同步状态索引
 CREATE INDEX idx_sync_states_device_status ON sync_states(device_id, sync_status);
@@ -178,6 +219,30 @@ CREATE TRIGGER update_users_updated_at
     FOR EACH ROW
     EXECUTE FUNCTION update_updated_at_column();
 
+-- 插入和删除文件版本时维护存储对象引用计数
+CREATE OR REPLACE FUNCTION update_storage_object_refs()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO storage_objects (user_id, file_hash, ref_count)
+        VALUES (NEW.user_id, NEW.file_hash, 1)
+        ON CONFLICT (user_id, file_hash)
+        DO UPDATE SET ref_count = storage_objects.ref_count + 1;
+        RETURN NEW;
+    END IF;
+
+    UPDATE storage_objects
+    SET ref_count = ref_count - 1
+    WHERE user_id = OLD.user_id AND file_hash = OLD.file_hash;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER update_file_versions_object_refs
+    AFTER INSERT OR DELETE ON file_versions
+    FOR EACH ROW
+    EXECUTE FUNCTION update_storage_object_refs();
+
 -- 清理过期 Token
 CREATE OR REPLACE FUNCTION cleanup_expired_tokens()
 RETURNS void AS $$
@@ -287,6 +352,8 @@ COMMENT ON TABLE file_versions IS '文件版本历史表';
 COMMENT ON TABLE sync_states IS '文件同步状态表';
 COMMENT ON TABLE conflicts IS '冲突记录表';
 COMMENT ON TABLE sync_sessions IS '同步会话表';
+COMMENT ON TABLE file_content_index IS '文本文件内容索引表';
+COMMENT ON TABLE storage_objects IS '存储对象引用计数表';
 
 COMMENT ON COLUMN users.password_hash IS 'bcrypt 哈希后的密码';
 COMMENT ON COLUMN devices.device_fingerprint IS '设备唯一指纹（SHA-256 哈希）';
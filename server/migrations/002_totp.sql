@@ -0,0 +1,2 @@
+-- 两步验证：TOTP 密钥（Base32 编码，NULL 表示未启用）
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret TEXT;
@@ -0,0 +1,2 @@
+-- 忽略大小写的文件路径查找（跨平台设备合并仅大小写不同的路径）
+CREATE INDEX IF NOT EXISTS idx_file_versions_user_path_lower ON file_versions(user_id, LOWER(file_path));
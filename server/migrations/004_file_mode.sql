@@ -0,0 +1,2 @@
+-- 文件权限位（可执行的 hook/脚本需要在其他设备上保留 +x），NULL 表示未记录
+ALTER TABLE file_versions ADD COLUMN IF NOT EXISTS file_mode INTEGER;
@@ -0,0 +1,15 @@
+-- 文件路径与文本内容搜索（三元组索引支持 ILIKE 前缀和子串匹配）
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS idx_file_versions_path_trgm ON file_versions USING gin (file_path gin_trgm_ops);
+
+-- 文本内容索引（按内容哈希去重，二进制文件不写入）
+CREATE TABLE IF NOT EXISTS file_content_index (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    file_hash VARCHAR(64) NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (user_id, file_hash)
+);
+
+CREATE INDEX IF NOT EXISTS idx_file_content_index_trgm ON file_content_index USING gin (content gin_trgm_ops);
@@ -0,0 +1,39 @@
+-- 存储对象引用计数（内容按哈希去重，同一对象可被多个文件版本共享，计数归零后才删除对象）
+CREATE TABLE IF NOT EXISTS storage_objects (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    file_hash VARCHAR(64) NOT NULL,
+    ref_count INTEGER NOT NULL DEFAULT 0 CHECK (ref_count >= 0),
+    PRIMARY KEY (user_id, file_hash)
+);
+
+-- 插入和删除文件版本时维护引用计数
+CREATE OR REPLACE FUNCTION update_storage_object_refs()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO storage_objects (user_id, file_hash, ref_count)
+        VALUES (NEW.user_id, NEW.file_hash, 1)
+        ON CONFLICT (user_id, file_hash)
+        DO UPDATE SET ref_count = storage_objects.ref_count + 1;
+        RETURN NEW;
+    END IF;
+
+    UPDATE storage_objects
+    SET ref_count = ref_count - 1
+    WHERE user_id = OLD.user_id AND file_hash = OLD.file_hash;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS update_file_versions_object_refs ON file_versions;
+CREATE TRIGGER update_file_versions_object_refs
+    AFTER INSERT OR DELETE ON file_versions
+    FOR EACH ROW
+    EXECUTE FUNCTION update_storage_object_refs();
+
+-- 按已有版本回填引用计数
+INSERT INTO storage_objects (user_id, file_hash, ref_count)
+SELECT user_id, file_hash, COUNT(*)
+FROM file_versions
+GROUP BY user_id, file_hash
+ON CONFLICT (user_id, file_hash) DO UPDATE SET ref_count = EXCLUDED.ref_count;
@@ -0,0 +1,4 @@
+-- 内容哈希可带算法前缀（blake3:<hex>），放宽哈希列长度
+ALTER TABLE file_versions ALTER COLUMN file_hash TYPE VARCHAR(128);
+ALTER TABLE file_content_index ALTER COLUMN file_hash TYPE VARCHAR(128);
+ALTER TABLE storage_objects ALTER COLUMN file_hash TYPE VARCHAR(128);
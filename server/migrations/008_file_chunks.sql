@@ -0,0 +1,11 @@
+-- 文件分块清单表（内容定义分块，按顺序重组文件）
+CREATE TABLE IF NOT EXISTS file_chunks (
+    file_version_id UUID NOT NULL REFERENCES file_versions(id) ON DELETE CASCADE,
+    chunk_index INTEGER NOT NULL,
+    chunk_hash VARCHAR(64) NOT NULL, -- SHA-256
+    chunk_offset BIGINT NOT NULL,
+    chunk_size INTEGER NOT NULL,
+    PRIMARY KEY (file_version_id, chunk_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_file_chunks_hash ON file_chunks(chunk_hash);
@@ -0,0 +1,2 @@
+-- 当前版本索引（按文件取最新版本号）
+CREATE INDEX IF NOT EXISTS idx_file_versions_current ON file_versions(user_id, file_path, version_number DESC);
@@ -1,13 +1,80 @@
 use crate::cache::Cache;
-use crate::config::Config;
+use crate::config::{Config, LoginLimitConfig};
 use crate::db::{DbPool, TokenRepository, UserRepository};
+use crate::error::ServiceError;
 use crate::models::{Claims, TokenType};
 use anyhow::Result;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use totp_rs::{Algorithm, Secret, TOTP};
 use tracing::{info, warn};
 use uuid::Uuid;
 
+/// TOTP 验证码位数
+const TOTP_DIGITS: usize = 6;
+
+/// TOTP 时间步长（秒）
+const TOTP_STEP: u64 = 30;
+
+/// 允许的时间漂移步数
+const TOTP_SKEW: u8 = 1;
+
+/// 两步验证挑战的有效期（秒）
+const TOTP_CHALLENGE_TTL: u64 = 300;
+
+/// 单个挑战允许的最大错误次数
+const TOTP_MAX_ATTEMPTS: u32 = 5;
+
+/// 登录失败次数过多，锁定期间拒绝登录
+#[derive(Debug)]
+pub struct TooManyAttempts {
+    pub retry_after: u64,
+}
+
+impl std::fmt::Display for TooManyAttempts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Too many login attempts, retry after {} seconds",
+            self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for TooManyAttempts {}
+
+/// 登录限流的计数维度及各自的失败阈值（邮箱不区分大小写，IP 可选）
+///
+/// 已知客户端 IP 时 (邮箱, IP) 按 `max_attempts` 计数，其他来源无法用少量错误密码锁定指定账号；
+/// 邮箱本身按更高的 `account_max_attempts` 合计所有来源，轮换 IP 也无法无限猜测密码；
+/// IP 维度单独限制同一来源对所有账号的尝试次数。
+fn login_limit_keys(
+    email: &str,
+    client_ip: Option<IpAddr>,
+    limit: &LoginLimitConfig,
+) -> Vec<(String, u32)> {
+    let email = email.trim().to_lowercase();
+    match client_ip {
+        Some(ip) => vec![
+            (format!("email:{}:ip:{}", email, ip), limit.max_attempts),
+            (format!("email:{}", email), limit.account_max_attempts),
+            (format!("ip:{}", ip), limit.max_attempts),
+        ],
+        None => vec![(format!("email:{}", email), limit.max_attempts)],
+    }
+}
+
+/// 邮箱不存在时用于校验的密码哈希（与真实哈希使用相同的计算成本）
+fn dummy_password_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| {
+        bcrypt::hash("dummy password", bcrypt::DEFAULT_COST).expect("invalid bcrypt cost")
+    })
+}
+
 /// JWT 认证服务
 pub struct AuthService {
     pool: DbPool,
@@ -71,6 +138,9 @@ impl AuthService {
     }
 
     /// 用户登录
+    ///
+    /// 启用了两步验证的用户不会立即获得 Token，而是返回一个挑战，
+    /// 需要通过 [`AuthService::verify_totp`] 提交动态验证码完成登录。
     pub async fn login(
         &self,
         email: String,
@@ -78,50 +148,169 @@ impl AuthService {
         device_name: String,
         device_type: &str,
         device_fingerprint: String,
-    ) -> Result<LoginResult> {
+        client_ip: Option<IpAddr>,
+    ) -> Result<LoginOutcome> {
         info!("Login attempt for: {}", email);
 
-        // 查找用户
-        let user_row = UserRepository::find_by_email(self.pool.inner(), &email)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Invalid email or password"))?;
+        // 锁定期间直接拒绝，不再校验密码
+        let limit_keys = login_limit_keys(&email, client_ip, &self.config.login_limit);
+        self.check_login_lockout(&limit_keys).await?;
+
+        // 查找用户并验证密码
+        let user_row = match UserRepository::find_by_email(self.pool.inner(), &email).await? {
+            Some(user_row) if bcrypt::verify(&password, &user_row.password_hash)? => user_row,
+            found => {
+                if found.is_some() {
+                    warn!("Failed password verification for: {}", email);
+                } else {
+                    // 邮箱不存在时同样执行一次 bcrypt 校验，避免通过响应时间判断账号是否存在
+                    let _ = bcrypt::verify(&password, dummy_password_hash());
+                }
+                self.record_login_failure(&limit_keys).await?;
+                return Err(anyhow::anyhow!("Invalid email or password"));
+            }
+        };
 
         if !user_row.is_active {
             return Err(anyhow::anyhow!("User account is inactive"));
         }
 
-        // 验证密码
-        if !bcrypt::verify(&password, &user_row.password_hash)? {
-            warn!("Failed password verification for: {}", email);
-            return Err(anyhow::anyhow!("Invalid email or password"));
+        // 密码正确后清除失败计数
+        for (key, _) in &limit_keys {
+            self.cache.reset_login_attempts(key).await?;
+        }
+
+        let pending = PendingLogin {
+            user_id: user_row.id,
+            device_name,
+            device_type: device_type.to_string(),
+            device_fingerprint,
+            expires_at: (Utc::now() + Duration::seconds(TOTP_CHALLENGE_TTL as i64)).timestamp(),
+            attempts: 0,
+        };
+
+        // 启用两步验证时先下发挑战
+        if UserRepository::find_totp_secret(self.pool.inner(), &user_row.id)
+            .await?
+            .is_some()
+        {
+            let challenge_id = Uuid::new_v4();
+            self.save_pending_login(&challenge_id, &pending).await?;
+
+            info!("TOTP challenge issued for user: {}", user_row.id);
+
+            return Ok(LoginOutcome::TotpRequired(TotpChallenge {
+                challenge_id,
+                expires_at: chrono::DateTime::from_timestamp(pending.expires_at, 0)
+                    .unwrap_or_else(Utc::now),
+            }));
+        }
+
+        self.complete_login(&pending)
+            .await
+            .map(LoginOutcome::Authenticated)
+    }
+
+    /// 任一限流维度处于锁定状态时返回 [`TooManyAttempts`]
+    async fn check_login_lockout(&self, limit_keys: &[(String, u32)]) -> Result<()> {
+        let mut retry_after = None;
+        for (key, _) in limit_keys {
+            if let Some(remaining) = self.cache.login_lockout_remaining(key).await? {
+                retry_after = retry_after.max(Some(remaining));
+            }
+        }
+
+        match retry_after {
+            Some(retry_after) => Err(TooManyAttempts { retry_after }.into()),
+            None => Ok(()),
         }
+    }
+
+    /// 记录登录失败，达到阈值后按失败次数指数延长锁定时间
+    async fn record_login_failure(&self, limit_keys: &[(String, u32)]) -> Result<()> {
+        let limit = &self.config.login_limit;
+        let window = std::time::Duration::from_secs(limit.attempt_window);
+
+        for (key, max_attempts) in limit_keys {
+            let failures = self.cache.record_login_failure(key, window).await?;
+            if let Some(lockout) = limit.lockout_duration_after(failures as u32, *max_attempts) {
+                warn!(
+                    "Login locked for {} after {} failures ({} seconds)",
+                    key, failures, lockout
+                );
+                self.cache
+                    .lock_login(key, std::time::Duration::from_secs(lockout))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 提交两步验证码，完成登录
+    pub async fn verify_totp(&self, challenge_id: Uuid, code: &str) -> Result<LoginResult> {
+        let key = Self::totp_challenge_key(&challenge_id);
+        let mut pending: PendingLogin = match self.cache.get(&key).await? {
+            Some(value) => serde_json::from_str(&value)?,
+            None => return Err(anyhow::anyhow!("TOTP challenge expired or not found")),
+        };
+
+        let secret = UserRepository::find_totp_secret(self.pool.inner(), &pending.user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("TOTP is not enabled for this user"))?;
+
+        if !verify_totp_code(&secret, code, Utc::now().timestamp() as u64)? {
+            warn!("Invalid TOTP code for user: {}", pending.user_id);
+
+            // 超过最大尝试次数后作废挑战，需重新输入密码
+            pending.attempts += 1;
+            if pending.attempts >= TOTP_MAX_ATTEMPTS {
+                self.cache.delete(&key).await?;
+            } else {
+                self.save_pending_login(&challenge_id, &pending).await?;
+            }
+
+            return Err(anyhow::anyhow!("Invalid TOTP code"));
+        }
+
+        // 挑战只能使用一次
+        self.cache.delete(&key).await?;
+
+        self.complete_login(&pending).await
+    }
+
+    /// 登录验证通过后登记设备并签发 Token
+    async fn complete_login(&self, pending: &PendingLogin) -> Result<LoginResult> {
+        let user_id = pending.user_id;
 
         // 查找或创建设备
         use crate::db::DeviceRepository;
-        let device =
-            match DeviceRepository::find_by_fingerprint(self.pool.inner(), &device_fingerprint)
+        let device = match DeviceRepository::find_by_fingerprint(
+            self.pool.inner(),
+            &pending.device_fingerprint,
+        )
+        .await?
+        {
+            Some(dev) => {
+                // 更新最后在线时间
+                DeviceRepository::update_last_seen(self.pool.inner(), &dev.id).await?;
+                dev
+            }
+            None => {
+                // 注册新设备
+                DeviceRepository::create(
+                    self.pool.inner(),
+                    &user_id,
+                    &pending.device_name,
+                    &pending.device_type,
+                    &pending.device_fingerprint,
+                )
                 .await?
-            {
-                Some(dev) => {
-                    // 更新最后在线时间
-                    DeviceRepository::update_last_seen(self.pool.inner(), &dev.id).await?;
-                    dev
-                }
-                None => {
-                    // 注册新设备
-                    DeviceRepository::create(
-                        self.pool.inner(),
-                        &user_row.id,
-                        &device_name,
-                        device_type,
-                        &device_fingerprint,
-                    )
-                    .await?
-                }
-            };
+            }
+        };
 
         // 生成 Token
-        let (access_token, refresh_token) = self.generate_tokens(user_row.id, Some(device.id))?;
+        let (access_token, refresh_token) = self.generate_tokens(user_id, Some(device.id))?;
 
         // 保存 Refresh Token 到数据库
         let token_hash = Self::hash_token(&refresh_token);
@@ -131,7 +320,7 @@ impl AuthService {
 
         TokenRepository::save(
             self.pool.inner(),
-            &user_row.id,
+            &user_id,
             Some(&device.id),
             &token_hash,
             &token_prefix,
@@ -140,15 +329,15 @@ impl AuthService {
         .await?;
 
         // 更新用户最后登录时间
-        UserRepository::update_last_login(self.pool.inner(), &user_row.id).await?;
+        UserRepository::update_last_login(self.pool.inner(), &user_id).await?;
 
         // 设备上线
-        self.cache.device_online(&device.id, &user_row.id).await?;
+        self.cache.device_online(&device.id, &user_id).await?;
 
-        info!("User logged in successfully: {}", user_row.id);
+        info!("User logged in successfully: {}", user_id);
 
         Ok(LoginResult {
-            user_id: user_row.id,
+            user_id,
             device_id: device.id,
             access_token,
             refresh_token,
@@ -224,9 +413,56 @@ impl AuthService {
             return Err(anyhow::anyhow!("Token has been revoked"));
         }
 
+        // 检查签发设备是否已被吊销
+        if let Some(device_id) = claims.device_id {
+            let revoked_at = self.cache.device_revoked_at(&device_id).await?;
+            if is_issued_before_revocation(claims.iat, revoked_at) {
+                return Err(anyhow::anyhow!("Device has been revoked"));
+            }
+        }
+
         Ok(claims)
     }
 
+    /// 吊销设备（远程登出）
+    ///
+    /// 停用设备、撤销其全部 Refresh Token，并在 Redis 中记录吊销时间，
+    /// 使该设备尚未过期的 Access Token 立即失效。返回撤销的 Token 数量。
+    /// 设备不存在或不属于该用户时返回对应的 [`ServiceError`]。
+    pub async fn revoke_device(&self, user_id: Uuid, device_id: Uuid) -> Result<u64> {
+        use crate::db::DeviceRepository;
+
+        let device = DeviceRepository::find_by_id(self.pool.inner(), &device_id)
+            .await?
+            .ok_or_else(|| ServiceError::not_found("Device not found"))?;
+        if device.user_id != user_id {
+            return Err(ServiceError::permission_denied(
+                "Device does not belong to the authenticated user",
+            )
+            .into());
+        }
+
+        DeviceRepository::delete(self.pool.inner(), &device.id).await?;
+        let revoked = TokenRepository::revoke_by_device(self.pool.inner(), &device.id).await?;
+
+        // 黑名单只需保留到最后一个 Access Token 过期
+        self.cache
+            .revoke_device(
+                &device.id,
+                Utc::now().timestamp(),
+                self.config.access_token_expiration(),
+            )
+            .await?;
+        self.cache.device_offline(&device.id, &user_id).await?;
+
+        info!(
+            "Device {} revoked for user {} ({} tokens)",
+            device.id, user_id, revoked
+        );
+
+        Ok(revoked)
+    }
+
     /// 撤销 Token（加入黑名单）
     pub async fn revoke_token(&self, jti: Uuid, expires_at: i64) -> Result<()> {
         self.cache.revoke_token(&jti, expires_at).await?;
@@ -284,6 +520,23 @@ impl AuthService {
         Ok(token_data.claims)
     }
 
+    /// 保存待完成的登录，过期时间与挑战一致
+    async fn save_pending_login(&self, challenge_id: &Uuid, pending: &PendingLogin) -> Result<()> {
+        let ttl = (pending.expires_at - Utc::now().timestamp()).max(1) as u64;
+        self.cache
+            .set(
+                &Self::totp_challenge_key(challenge_id),
+                &serde_json::to_string(pending)?,
+                Some(std::time::Duration::from_secs(ttl)),
+            )
+            .await
+    }
+
+    /// 两步验证挑战的缓存键
+    fn totp_challenge_key(challenge_id: &Uuid) -> String {
+        format!("auth:totp_challenge:{}", challenge_id)
+    }
+
     /// 哈希 Token
     fn hash_token(token: &str) -> String {
         use sha2::{Digest, Sha256};
@@ -310,8 +563,53 @@ impl AuthService {
     }
 }
 
+/// Token 是否签发于设备吊销之前
+///
+/// 时间精度为秒，与吊销同一秒签发的 Token 视为吊销后重新注册签发的，仍然有效。
+fn is_issued_before_revocation(issued_at: usize, revoked_at: Option<i64>) -> bool {
+    revoked_at.is_some_and(|revoked_at| (issued_at as i64) < revoked_at)
+}
+
+/// 验证 TOTP 动态验证码
+///
+/// `secret` 为 Base32 编码的密钥，允许前后各 [`TOTP_SKEW`] 个时间步长的漂移。
+pub fn verify_totp_code(secret: &str, code: &str, timestamp: u64) -> Result<bool> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {:?}", e))?;
+    let totp = TOTP::new(Algorithm::SHA1, TOTP_DIGITS, TOTP_SKEW, TOTP_STEP, secret)
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {}", e))?;
+
+    Ok(totp.check(code.trim(), timestamp))
+}
+
 // ===== 返回类型 =====
 
+/// 登录结果：直接签发 Token，或要求两步验证
+#[derive(Debug, Clone)]
+pub enum LoginOutcome {
+    Authenticated(LoginResult),
+    TotpRequired(TotpChallenge),
+}
+
+/// 两步验证挑战
+#[derive(Debug, Clone)]
+pub struct TotpChallenge {
+    pub challenge_id: Uuid,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// 等待两步验证的登录请求（保存在 Redis 中）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingLogin {
+    user_id: Uuid,
+    device_name: String,
+    device_type: String,
+    device_fingerprint: String,
+    expires_at: i64,
+    attempts: u32,
+}
+
 /// 登录结果
 #[derive(Debug, Clone)]
 pub struct LoginResult {
@@ -353,4 +651,282 @@ mod tests {
         assert!(AuthService::validate_password("short").is_err());
         assert!(AuthService::validate_password("longenoughpassword").is_ok());
     }
+
+    #[test]
+    fn test_is_issued_before_revocation() {
+        assert!(!is_issued_before_revocation(1_000, None));
+        assert!(is_issued_before_revocation(999, Some(1_000)));
+
+        // 吊销后立即重新注册（同一秒内）签发的 Token 不受影响
+        assert!(!is_issued_before_revocation(1_000, Some(1_000)));
+        assert!(!is_issued_before_revocation(1_001, Some(1_000)));
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 PostgreSQL 和 Redis 连接
+    async fn test_revoke_device_rejects_access_token() {
+        use crate::cache::RedisPool;
+
+        let config = Config::from_env().unwrap();
+        let pool = DbPool::from_config(&config).await.unwrap();
+        let redis_pool = RedisPool::from_config(&config.redis.url).await.unwrap();
+        let cache = Cache::new(redis_pool.inner().clone(), &config.redis);
+        let auth = AuthService::new(pool, cache, config);
+
+        let suffix = Uuid::new_v4().simple().to_string();
+        let email = format!("revoke-{}@example.com", suffix);
+        auth.register(
+            format!("revoke-{}", suffix),
+            email.clone(),
+            "longenoughpassword".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let result = match auth
+            .login(
+                email,
+                "longenoughpassword".to_string(),
+                "laptop".to_string(),
+                "desktop",
+                suffix,
+                None,
+            )
+            .await
+            .unwrap()
+        {
+            LoginOutcome::Authenticated(result) => result,
+            LoginOutcome::TotpRequired(_) => panic!("TOTP should not be required"),
+        };
+
+        assert!(auth.verify_access_token(&result.access_token).await.is_ok());
+
+        let revoked = auth
+            .revoke_device(result.user_id, result.device_id)
+            .await
+            .unwrap();
+        assert_eq!(revoked, 1);
+
+        // 已签发的 Access Token 和 Refresh Token 都应失效
+        assert!(auth
+            .verify_access_token(&result.access_token)
+            .await
+            .is_err());
+        assert!(auth.refresh_token(result.refresh_token).await.is_err());
+    }
+
+    fn test_login_limit() -> LoginLimitConfig {
+        LoginLimitConfig {
+            max_attempts: 3,
+            account_max_attempts: 10,
+            attempt_window: 900,
+            lockout_base: 60,
+            lockout_max: 600,
+        }
+    }
+
+    #[test]
+    fn test_login_limit_keys() {
+        let limit = test_login_limit();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        assert_eq!(
+            login_limit_keys(" User@Example.com", Some(ip), &limit),
+            vec![
+                ("email:user@example.com:ip:203.0.113.7".to_string(), 3),
+                ("email:user@example.com".to_string(), 10),
+                ("ip:203.0.113.7".to_string(), 3),
+            ]
+        );
+        assert_eq!(
+            login_limit_keys("user@example.com", None, &limit),
+            vec![("email:user@example.com".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_failures_from_many_ips_lock_the_account() {
+        let limit = test_login_limit();
+        let mut failures: std::collections::HashMap<String, u32> = Default::default();
+        let mut locked = std::collections::BTreeSet::new();
+
+        // 每个 IP 只失败两次，不会触发 (邮箱, IP) 和 IP 维度的锁定
+        for i in 0..5 {
+            let ip = IpAddr::from([203, 0, 113, i]);
+            for _ in 0..2 {
+                for (key, max_attempts) in login_limit_keys("user@example.com", Some(ip), &limit) {
+                    let count = failures.entry(key.clone()).or_default();
+                    *count += 1;
+                    if limit.lockout_duration_after(*count, max_attempts).is_some() {
+                        locked.insert(key);
+                    }
+                }
+            }
+        }
+
+        // 所有来源合计达到邮箱阈值后锁定账号
+        assert_eq!(
+            locked.into_iter().collect::<Vec<_>>(),
+            vec!["email:user@example.com".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_dummy_password_hash_rejects_passwords() {
+        assert!(!bcrypt::verify("longenoughpassword", dummy_password_hash()).unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 PostgreSQL 和 Redis 连接
+    async fn test_login_lockout_after_repeated_failures() {
+        use crate::cache::RedisPool;
+
+        let mut config = Config::from_env().unwrap();
+        config.login_limit.max_attempts = 3;
+        config.login_limit.lockout_base = 60;
+        let pool = DbPool::from_config(&config).await.unwrap();
+        let redis_pool = RedisPool::from_config(&config.redis.url).await.unwrap();
+        let cache = Cache::new(redis_pool.inner().clone(), &config.redis);
+        let auth = AuthService::new(pool, cache.clone(), config);
+
+        let suffix = Uuid::new_v4().simple().to_string();
+        let email = format!("lockout-{}@example.com", suffix);
+        auth.register(
+            format!("lockout-{}", suffix),
+            email.clone(),
+            "longenoughpassword".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let attempt = |password: &str| {
+            auth.login(
+                email.clone(),
+                password.to_string(),
+                "laptop".to_string(),
+                "desktop",
+                suffix.clone(),
+                None,
+            )
+        };
+
+        // 成功登录清除之前的失败计数
+        for _ in 0..2 {
+            assert!(attempt("wrong password").await.is_err());
+        }
+        assert!(attempt("longenoughpassword").await.is_ok());
+        for _ in 0..2 {
+            assert!(attempt("wrong password").await.is_err());
+        }
+        assert!(attempt("longenoughpassword").await.is_ok());
+
+        // 连续失败达到阈值后锁定，正确的密码也被拒绝
+        for _ in 0..3 {
+            assert!(attempt("wrong password").await.is_err());
+        }
+        let err = attempt("longenoughpassword").await.unwrap_err();
+        let locked = err.downcast_ref::<TooManyAttempts>().unwrap();
+        assert!(locked.retry_after > 0 && locked.retry_after <= 60);
+
+        // 锁定解除后成功登录清除计数
+        let key = format!("email:{}", email);
+        cache
+            .delete(&format!("login:lockout:{}", key))
+            .await
+            .unwrap();
+        assert!(attempt("longenoughpassword").await.is_ok());
+        assert_eq!(cache.login_lockout_remaining(&key).await.unwrap(), None);
+        assert!(!cache
+            .exists(&format!("login:attempts:{}", key))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 PostgreSQL 和 Redis 连接
+    async fn test_login_lockout_across_ips() {
+        use crate::cache::RedisPool;
+
+        let mut config = Config::from_env().unwrap();
+        config.login_limit.max_attempts = 3;
+        config.login_limit.account_max_attempts = 6;
+        config.login_limit.lockout_base = 60;
+        let pool = DbPool::from_config(&config).await.unwrap();
+        let redis_pool = RedisPool::from_config(&config.redis.url).await.unwrap();
+        let cache = Cache::new(redis_pool.inner().clone(), &config.redis);
+        let auth = AuthService::new(pool, cache, config);
+
+        let suffix = Uuid::new_v4().simple().to_string();
+        let email = format!("rotate-{}@example.com", suffix);
+        auth.register(
+            format!("rotate-{}", suffix),
+            email.clone(),
+            "longenoughpassword".to_string(),
+        )
+        .await
+        .unwrap();
+
+        let attempt = |password: &str, ip: IpAddr| {
+            auth.login(
+                email.clone(),
+                password.to_string(),
+                "laptop".to_string(),
+                "desktop",
+                suffix.clone(),
+                Some(ip),
+            )
+        };
+
+        // 每个 IP 都低于单来源阈值，合计达到邮箱阈值后锁定
+        for i in 0..3 {
+            let ip = IpAddr::from([198, 51, 100, i]);
+            for _ in 0..2 {
+                let err = attempt("wrong password", ip).await.unwrap_err();
+                assert!(err.downcast_ref::<TooManyAttempts>().is_none());
+            }
+        }
+        let err = attempt("wrong password", IpAddr::from([198, 51, 100, 99]))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<TooManyAttempts>().is_some());
+    }
+
+    const TEST_SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+    const TEST_TIME: u64 = 1_700_000_010;
+
+    fn code_at(timestamp: u64) -> String {
+        let secret = Secret::Encoded(TEST_SECRET.to_string()).to_bytes().unwrap();
+        TOTP::new(Algorithm::SHA1, TOTP_DIGITS, TOTP_SKEW, TOTP_STEP, secret)
+            .unwrap()
+            .generate(timestamp)
+    }
+
+    #[test]
+    fn test_verify_totp_code() {
+        let code = code_at(TEST_TIME);
+        assert_eq!(code.len(), 6);
+        assert!(verify_totp_code(TEST_SECRET, &code, TEST_TIME).unwrap());
+
+        // 错误的验证码
+        let wrong = format!("{:06}", (code.parse::<u32>().unwrap() + 1) % 1_000_000);
+        assert!(!verify_totp_code(TEST_SECRET, &wrong, TEST_TIME).unwrap());
+        assert!(!verify_totp_code(TEST_SECRET, "abcdef", TEST_TIME).unwrap());
+
+        // 无效的密钥
+        assert!(verify_totp_code("not base32!", &code, TEST_TIME).is_err());
+    }
+
+    #[test]
+    fn test_verify_totp_code_drift() {
+        // 前后一个时间步长内的漂移可以接受
+        let previous = code_at(TEST_TIME - TOTP_STEP);
+        let next = code_at(TEST_TIME + TOTP_STEP);
+        assert!(verify_totp_code(TEST_SECRET, &previous, TEST_TIME).unwrap());
+        assert!(verify_totp_code(TEST_SECRET, &next, TEST_TIME).unwrap());
+
+        // 超出允许范围的漂移被拒绝
+        let stale = code_at(TEST_TIME - 3 * TOTP_STEP);
+        let early = code_at(TEST_TIME + 3 * TOTP_STEP);
+        assert!(!verify_totp_code(TEST_SECRET, &stale, TEST_TIME).unwrap());
+        assert!(!verify_totp_code(TEST_SECRET, &early, TEST_TIME).unwrap());
+    }
 }
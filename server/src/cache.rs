@@ -1,9 +1,11 @@
 use anyhow::Result;
-use deadpool_redis::{Config as RedisConfig, Pool, Runtime};
+use deadpool_redis::{Config as PoolConfig, Pool, Runtime};
 use redis::AsyncCommands;
 use std::time::Duration;
 use tracing::info;
 
+use crate::config::RedisConfig;
+
 /// Redis 连接池
 #[derive(Clone)]
 pub struct RedisPool {
@@ -15,7 +17,7 @@ impl RedisPool {
     pub async fn from_config(redis_url: &str) -> Result<Self> {
         info!("Connecting to Redis...");
 
-        let cfg = RedisConfig::from_url(redis_url);
+        let cfg = PoolConfig::from_url(redis_url);
         let pool = cfg
             .create_pool(Some(Runtime::Tokio1))
             .map_err(|e| anyhow::anyhow!("Failed to create Redis pool: {}", e))?;
@@ -55,19 +57,39 @@ impl RedisPool {
 #[derive(Clone)]
 pub struct Cache {
     pool: Pool,
+    /// 键前缀
+    key_prefix: String,
+    /// 在线设备集合的过期时间
+    online_device_ttl: Duration,
+    /// 变更队列的过期时间
+    change_queue_ttl: Duration,
+    /// Token 黑名单的最短保留时间
+    token_blacklist_ttl: Duration,
 }
 
 impl Cache {
-    /// 创建新的 Cache 实例
-    pub fn new(pool: Pool) -> Self {
-        Self { pool }
+    /// 创建新的 Cache 实例（键前缀和过期时间来自 Redis 配置）
+    pub fn new(pool: Pool, config: &RedisConfig) -> Self {
+        Self {
+            pool,
+            key_prefix: config.key_prefix.clone(),
+            online_device_ttl: Duration::from_secs(config.online_device_ttl),
+            change_queue_ttl: Duration::from_secs(config.change_queue_ttl),
+            token_blacklist_ttl: Duration::from_secs(config.token_blacklist_ttl),
+        }
+    }
+
+    /// 加上部署前缀的完整键名
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
     }
+
     /// ===== Token 黑名单操作 =====
     /// 将 Token 加入黑名单
     pub async fn revoke_token(&self, jti: &uuid::Uuid, expires_at: i64) -> Result<()> {
-        let key = format!("token:blacklist:{}", jti);
-        let ttl = expires_at - chrono::Utc::now().timestamp();
-        let ttl = ttl.max(0) as u64;
+        let key = self.key(&format!("token:blacklist:{}", jti));
+        let ttl = (expires_at - chrono::Utc::now().timestamp()).max(0) as u64;
+        let ttl = ttl.max(self.token_blacklist_ttl.as_secs()).max(1);
 
         let mut conn = self.pool.get().await?;
         conn.set_ex::<_, _, ()>(&key, "1", ttl).await?;
@@ -77,29 +99,53 @@ impl Cache {
 
     /// 检查 Token 是否在黑名单中
     pub async fn is_token_revoked(&self, jti: &uuid::Uuid) -> Result<bool> {
-        let key = format!("token:blacklist:{}", jti);
+        let key = self.key(&format!("token:blacklist:{}", jti));
         let mut conn = self.pool.get().await?;
         let exists: bool = conn.exists(&key).await?;
         Ok(exists)
     }
+
+    /// 吊销设备：在此时间之前签发给该设备的 Token 全部失效
+    pub async fn revoke_device(
+        &self,
+        device_id: &uuid::Uuid,
+        revoked_at: i64,
+        ttl: Duration,
+    ) -> Result<()> {
+        let key = self.key(&format!("device:revoked:{}", device_id));
+        let mut conn = self.pool.get().await?;
+        conn.set_ex::<_, _, ()>(&key, revoked_at, ttl.as_secs())
+            .await?;
+
+        Ok(())
+    }
+
+    /// 获取设备的吊销时间
+    pub async fn device_revoked_at(&self, device_id: &uuid::Uuid) -> Result<Option<i64>> {
+        let key = self.key(&format!("device:revoked:{}", device_id));
+        let mut conn = self.pool.get().await?;
+        let revoked_at: Option<i64> = conn.get(&key).await?;
+        Ok(revoked_at)
+    }
     /// ===== 在线设备管理 =====
     /// 设备上线
     pub async fn device_online(&self, device_id: &uuid::Uuid, user_id: &uuid::Uuid) -> Result<()> {
-        let key = format!("device:online:{}", user_id);
+        let key = self.key(&format!("device:online:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         // 添加到在线设备集合
         conn.sadd::<_, _, ()>(&key, device_id.to_string()).await?;
 
-        // 设置过期时间（30 分钟）
-        conn.expire::<_, ()>(&key, 1800).await?;
+        // 设备长时间未上报心跳后自动从在线集合中移除
+        conn.expire::<_, ()>(&key, self.online_device_ttl.as_secs() as i64)
+            .await?;
 
         Ok(())
     }
 
     /// 设备离线
     pub async fn device_offline(&self, device_id: &uuid::Uuid, user_id: &uuid::Uuid) -> Result<()> {
-        let key = format!("device:online:{}", user_id);
+        let key = self.key(&format!("device:online:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         conn.srem::<_, _, ()>(&key, device_id.to_string()).await?;
@@ -109,7 +155,7 @@ impl Cache {
 
     /// 获取用户所有在线设备
     pub async fn get_online_devices(&self, user_id: &uuid::Uuid) -> Result<Vec<uuid::Uuid>> {
-        let key = format!("device:online:{}", user_id);
+        let key = self.key(&format!("device:online:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         let devices: Vec<String> = conn.smembers(&key).await?;
@@ -128,7 +174,7 @@ impl Cache {
         device_id: &uuid::Uuid,
         user_id: &uuid::Uuid,
     ) -> Result<bool> {
-        let key = format!("device:online:{}", user_id);
+        let key = self.key(&format!("device:online:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         let is_member: bool = conn.sismember(&key, device_id.to_string()).await?;
@@ -142,14 +188,51 @@ impl Cache {
         user_id: &uuid::Uuid,
         change: &FileChangeNotification,
     ) -> Result<()> {
-        let key = format!("changes:{}", user_id);
+        let key = self.key(&format!("changes:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         let value = serde_json::to_string(change)?;
         conn.rpush::<_, _, ()>(&key, value).await?;
 
-        // 限制队列长度（最多保留 1000 条）
-        conn.ltrim::<_, ()>(&key, -1000, -1).await?;
+        // 限制队列长度（最多保留 1000 条），长期未消费的队列自动过期
+        redis::pipe()
+            .ltrim(&key, -1000, -1)
+            .ignore()
+            .expire(&key, self.change_queue_ttl.as_secs() as i64)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// 批量添加文件变更到队列（一次往返写入整批变更）
+    pub async fn push_file_changes(
+        &self,
+        user_id: &uuid::Uuid,
+        changes: &[FileChangeNotification],
+    ) -> Result<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+
+        let key = self.key(&format!("changes:{}", user_id));
+        let mut conn = self.pool.get().await?;
+
+        let values = changes
+            .iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        redis::pipe()
+            .rpush(&key, values)
+            .ignore()
+            .ltrim(&key, -1000, -1)
+            .ignore()
+            .expire(&key, self.change_queue_ttl.as_secs() as i64)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
 
         Ok(())
     }
@@ -160,7 +243,7 @@ impl Cache {
         user_id: &uuid::Uuid,
         count: usize,
     ) -> Result<Vec<FileChangeNotification>> {
-        let key = format!("changes:{}", user_id);
+        let key = self.key(&format!("changes:{}", user_id));
         let mut conn = self.pool.get().await?;
 
         let count_nonzero = std::num::NonZero::new(count);
@@ -183,9 +266,10 @@ impl Cache {
         let mut conn = self.pool.get().await?;
 
         if let Some(ttl) = ttl {
-            conn.set_ex::<_, _, ()>(key, value, ttl.as_secs()).await?;
+            conn.set_ex::<_, _, ()>(self.key(key), value, ttl.as_secs())
+                .await?;
         } else {
-            conn.set::<_, _, ()>(key, value).await?;
+            conn.set::<_, _, ()>(self.key(key), value).await?;
         }
 
         Ok(())
@@ -194,28 +278,29 @@ impl Cache {
     /// 获取缓存
     pub async fn get(&self, key: &str) -> Result<Option<String>> {
         let mut conn = self.pool.get().await?;
-        let value: Option<String> = conn.get(key).await?;
+        let value: Option<String> = conn.get(self.key(key)).await?;
         Ok(value)
     }
 
     /// 删除缓存
     pub async fn delete(&self, key: &str) -> Result<()> {
         let mut conn = self.pool.get().await?;
-        conn.del::<_, ()>(key).await?;
+        conn.del::<_, ()>(self.key(key)).await?;
         Ok(())
     }
 
     /// 检查键是否存在
     pub async fn exists(&self, key: &str) -> Result<bool> {
         let mut conn = self.pool.get().await?;
-        let exists: bool = conn.exists(key).await?;
+        let exists: bool = conn.exists(self.key(key)).await?;
         Ok(exists)
     }
 
     /// 设置过期时间
     pub async fn expire(&self, key: &str, ttl: Duration) -> Result<()> {
         let mut conn = self.pool.get().await?;
-        conn.expire::<_, ()>(key, ttl.as_secs() as i64).await?;
+        conn.expire::<_, ()>(self.key(key), ttl.as_secs() as i64)
+            .await?;
         Ok(())
     }
 
@@ -225,29 +310,74 @@ impl Cache {
             return Ok(());
         }
 
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
         let mut conn = self.pool.get().await?;
         conn.del::<_, ()>(keys).await?;
         Ok(())
     }
+    /// ===== 登录限流 =====
+    /// 记录一次登录失败，返回窗口内的累计失败次数
+    pub async fn record_login_failure(&self, key: &str, window: Duration) -> Result<i64> {
+        let attempts_key = self.key(&format!("login:attempts:{}", key));
+        let mut conn = self.pool.get().await?;
+
+        let (count,): (i64,) = redis::pipe()
+            .atomic()
+            .incr(&attempts_key, 1)
+            .expire(&attempts_key, window.as_secs() as i64)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// 锁定登录
+    pub async fn lock_login(&self, key: &str, duration: Duration) -> Result<()> {
+        let lockout_key = self.key(&format!("login:lockout:{}", key));
+        let mut conn = self.pool.get().await?;
+        conn.set_ex::<_, _, ()>(&lockout_key, 1, duration.as_secs())
+            .await?;
+        Ok(())
+    }
+
+    /// 登录锁定的剩余时间（秒），未锁定时返回 None
+    pub async fn login_lockout_remaining(&self, key: &str) -> Result<Option<u64>> {
+        let lockout_key = self.key(&format!("login:lockout:{}", key));
+        let mut conn = self.pool.get().await?;
+        let ttl: i64 = conn.ttl(&lockout_key).await?;
+        Ok((ttl > 0).then_some(ttl as u64))
+    }
+
+    /// 清除登录失败计数和锁定
+    pub async fn reset_login_attempts(&self, key: &str) -> Result<()> {
+        let mut conn = self.pool.get().await?;
+        conn.del::<_, ()>(&[
+            self.key(&format!("login:attempts:{}", key)),
+            self.key(&format!("login:lockout:{}", key)),
+        ])
+        .await?;
+        Ok(())
+    }
     /// ===== 计数器操作 =====
     /// 增加计数器
     pub async fn incr(&self, key: &str) -> Result<i64> {
         let mut conn = self.pool.get().await?;
-        let value: i64 = conn.incr(key, 1).await?;
+        let value: i64 = conn.incr(self.key(key), 1).await?;
         Ok(value)
     }
 
     /// 减少计数器
     pub async fn decr(&self, key: &str) -> Result<i64> {
         let mut conn = self.pool.get().await?;
-        let value: i64 = conn.decr(key, 1).await?;
+        let value: i64 = conn.decr(self.key(key), 1).await?;
         Ok(value)
     }
 
     /// 获取计数器值
     pub async fn get_counter(&self, key: &str) -> Result<i64> {
         let mut conn = self.pool.get().await?;
-        let value: i64 = conn.get(key).await?;
+        let value: i64 = conn.get(self.key(key)).await?;
         Ok(value)
     }
 }
@@ -273,6 +403,93 @@ pub enum ChangeType {
 mod tests {
     use super::*;
 
+    fn test_cache(key_prefix: &str) -> Cache {
+        let config = crate::config::Config::from_env().unwrap();
+        let mut redis = config.redis;
+        redis.key_prefix = key_prefix.to_string();
+        redis.online_device_ttl = 120;
+        redis.change_queue_ttl = 600;
+        redis.token_blacklist_ttl = 300;
+        let pool = PoolConfig::from_url(redis.url.clone())
+            .create_pool(Some(Runtime::Tokio1))
+            .unwrap();
+        Cache::new(pool, &redis)
+    }
+
+    #[test]
+    fn test_keys_are_prefixed() {
+        assert_eq!(test_cache("").key("changes:1"), "changes:1");
+        assert_eq!(
+            test_cache("staging:").key("device:online:1"),
+            "staging:device:online:1"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 Redis 连接
+    async fn test_written_keys_have_prefix_and_ttl() {
+        let prefix = format!("test-{}:", uuid::Uuid::new_v4().simple());
+        let cache = test_cache(&prefix);
+        let user_id = uuid::Uuid::new_v4();
+        let device_id = uuid::Uuid::new_v4();
+
+        cache.device_online(&device_id, &user_id).await.unwrap();
+        cache
+            .push_file_changes(
+                &user_id,
+                &[FileChangeNotification {
+                    file_path: "agents/a.md".to_string(),
+                    device_id,
+                    change_type: ChangeType::Modified,
+                    timestamp: 0,
+                }],
+            )
+            .await
+            .unwrap();
+        // 已过期的 Token 仍按最短保留时间加入黑名单
+        let jti = uuid::Uuid::new_v4();
+        cache
+            .revoke_token(&jti, chrono::Utc::now().timestamp() - 10)
+            .await
+            .unwrap();
+
+        let mut conn = cache.pool.get().await.unwrap();
+        let ttl = |key: String| {
+            let mut cmd = redis::cmd("TTL");
+            cmd.arg(key);
+            cmd
+        };
+        let online: i64 = ttl(format!("{}device:online:{}", prefix, user_id))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        let changes: i64 = ttl(format!("{}changes:{}", prefix, user_id))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        let blacklist: i64 = ttl(format!("{}token:blacklist:{}", prefix, jti))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!((1..=120).contains(&online));
+        assert!((1..=600).contains(&changes));
+        assert!((1..=300).contains(&blacklist));
+        assert!(cache.is_token_revoked(&jti).await.unwrap());
+
+        // 不带前缀的键不存在
+        let unprefixed: bool = conn.exists(format!("changes:{}", user_id)).await.unwrap();
+        assert!(!unprefixed);
+
+        cache
+            .delete_multiple(&[
+                format!("device:online:{}", user_id),
+                format!("changes:{}", user_id),
+                format!("token:blacklist:{}", jti),
+            ])
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     #[ignore] // 需要 Redis 连接
     async fn test_cache_operations() {
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// 内容定义分块（FastCDC）参数
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkerConfig {
+    pub min_size: usize,
+    pub avg_size: usize,
+    pub max_size: usize,
+}
+
+impl Default for ChunkerConfig {
+    fn default() -> Self {
+        Self {
+            min_size: 2 * 1024,
+            avg_size: 8 * 1024,
+            max_size: 64 * 1024,
+        }
+    }
+}
+
+/// 文件中的一个分块
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRef {
+    pub hash: String, // SHA-256
+    pub offset: u64,
+    pub size: u32,
+}
+
+/// Gear 哈希表（splitmix64 生成，保证各版本一致）
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// 将数据切分为内容定义的分块
+pub fn chunk_data(data: &[u8], config: &ChunkerConfig) -> Vec<ChunkRef> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let length = cut_point(&data[offset..], config);
+        let chunk = &data[offset..offset + length];
+
+        chunks.push(ChunkRef {
+            hash: hash_chunk(chunk),
+            offset: offset as u64,
+            size: length as u32,
+        });
+
+        offset += length;
+    }
+
+    chunks
+}
+
+/// 找出不在已知集合中的分块（同一文件内重复的分块只保留一次）
+pub fn missing_chunks<'a>(chunks: &'a [ChunkRef], known: &HashSet<String>) -> Vec<&'a ChunkRef> {
+    let mut seen = HashSet::new();
+    chunks
+        .iter()
+        .filter(|chunk| !known.contains(&chunk.hash) && seen.insert(chunk.hash.as_str()))
+        .collect()
+}
+
+/// 计算分块哈希
+pub fn hash_chunk(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// 计算下一个切分点（FastCDC 归一化分块）
+fn cut_point(data: &[u8], config: &ChunkerConfig) -> usize {
+    let len = data.len();
+    if len <= config.min_size {
+        return len;
+    }
+
+    let max = config.max_size.min(len);
+    let normal = config.avg_size.min(max);
+
+    let bits = config.avg_size.max(2).ilog2();
+    // 平均大小之前使用更严格的掩码，之后放宽，使分块大小集中在平均值附近
+    let mask_strict = high_bits_mask(bits + 1);
+    let mask_loose = high_bits_mask(bits.saturating_sub(1));
+
+    let mut hash: u64 = 0;
+    let mut i = config.min_size;
+
+    while i < normal {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & mask_strict == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+
+    while i < max {
+        hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+        if hash & mask_loose == 0 {
+            return i + 1;
+        }
+        i += 1;
+    }
+
+    max
+}
+
+/// 高位掩码（高位依赖更长的字节窗口）
+fn high_bits_mask(bits: u32) -> u64 {
+    match bits {
+        0 => 0,
+        64.. => u64::MAX,
+        _ => !0u64 << (64 - bits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 生成确定性的伪随机数据
+    fn sample_data(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let config = ChunkerConfig::default();
+        let data = sample_data(512 * 1024, 1);
+        let chunks = chunk_data(&data, &config);
+
+        // 分块连续覆盖整个文件，且大小在限制范围内
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.offset, offset);
+            assert!(chunk.size as usize <= config.max_size);
+            if i + 1 < chunks.len() {
+                assert!(chunk.size as usize >= config.min_size);
+            }
+            offset += chunk.size as u64;
+        }
+        assert_eq!(offset, data.len() as u64);
+
+        assert!(chunk_data(&[], &config).is_empty());
+    }
+
+    #[test]
+    fn test_append_only_uploads_tail_chunks() {
+        let config = ChunkerConfig::default();
+        let original = sample_data(256 * 1024, 7);
+        let original_chunks = chunk_data(&original, &config);
+        let known: HashSet<String> = original_chunks.iter().map(|c| c.hash.clone()).collect();
+
+        let mut appended = original.clone();
+        appended.extend(sample_data(10 * 1024, 42));
+        let new_chunks = chunk_data(&appended, &config);
+
+        let to_upload = missing_chunks(&new_chunks, &known);
+        let last_original_offset = original_chunks.last().unwrap().offset;
+
+        // 只有原文件最后一个分块之后的内容需要上传
+        assert!(!to_upload.is_empty());
+        assert!(to_upload.iter().all(|c| c.offset >= last_original_offset));
+        let uploaded_bytes: u64 = to_upload.iter().map(|c| c.size as u64).sum();
+        assert!(uploaded_bytes < (original.len() / 4) as u64);
+    }
+
+    #[test]
+    fn test_duplicate_chunks_deduplicated() {
+        let config = ChunkerConfig::default();
+        let block = sample_data(64 * 1024, 3);
+        let mut data = block.clone();
+        data.extend(&block);
+        data.extend(&block);
+
+        let chunks = chunk_data(&data, &config);
+        let unique: HashSet<&str> = chunks.iter().map(|c| c.hash.as_str()).collect();
+        let to_upload = missing_chunks(&chunks, &HashSet::new());
+
+        assert_eq!(to_upload.len(), unique.len());
+        assert!(unique.len() < chunks.len());
+    }
+}
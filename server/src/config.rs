@@ -1,3 +1,5 @@
+use crate::scan::{ScanAction, SCANNER_NAMES};
+use crate::storage_backend::{LocalBackend, STORAGE_BACKENDS};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -10,6 +12,7 @@ pub struct Config {
     pub minio: MinioConfig,
     pub jwt: JwtConfig,
     pub sync: SyncConfig,
+    pub login_limit: LoginLimitConfig,
     pub logging: LoggingConfig,
 }
 
@@ -36,18 +39,26 @@ pub struct DatabaseConfig {
 pub struct RedisConfig {
     pub url: String,
     pub max_connections: u32,
-    pub connection_timeout: u64, // seconds
-    pub command_timeout: u64,    // seconds
+    pub connection_timeout: u64,  // seconds
+    pub command_timeout: u64,     // seconds
+    pub key_prefix: String,       // 所有键的前缀，多个部署共用一个 Redis 时用于隔离
+    pub online_device_ttl: u64,   // seconds，在线设备集合的过期时间
+    pub change_queue_ttl: u64,    // seconds，变更队列在最后一次写入后保留的时间
+    pub token_blacklist_ttl: u64, // seconds，Token 黑名单的最短保留时间（至少保留到 Token 过期）
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MinioConfig {
+    pub backend: String,    // 存储后端：s3（MinIO/S3）或 local（本地文件系统）
+    pub local_path: String, // local 后端的根目录
     pub endpoint: String,
     pub access_key: String,
     pub secret_key: String,
     pub bucket: String,
     pub region: String,
     pub timeout: u64, // seconds
+    pub encryption_enabled: bool,
+    pub encryption_key: String, // 主密钥，用于包装每个用户的数据密钥
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,6 +69,12 @@ pub struct JwtConfig {
     pub issuer: String,
 }
 
+/// 允许的最小传输分块大小（64KB）
+pub const MIN_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// 允许的最大传输分块大小（64MB）
+pub const MAX_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncConfig {
     pub max_file_size: u64, // bytes
@@ -65,6 +82,55 @@ pub struct SyncConfig {
     pub compression_enabled: bool,
     pub version_retention_days: u32,
     pub max_versions_per_file: u32,
+    pub allowed_file_types: Vec<String>, // 允许上传的扩展名/文件类型，空表示不限制
+    pub content_scanners: Vec<String>,   // 上传前运行的内容扫描器，空表示不扫描
+    pub content_scan_action: String,     // 扫描命中时 "reject"（拒绝上传）或 "flag"（记录警告）
+}
+
+impl SyncConfig {
+    /// 检查文件是否在允许上传的类型列表中（匹配扩展名或文件类型）
+    pub fn is_file_type_allowed(&self, file_path: &str, file_type: &str) -> bool {
+        if self.allowed_file_types.is_empty() {
+            return true;
+        }
+
+        let extension = std::path::Path::new(file_path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        self.allowed_file_types.iter().any(|allowed| {
+            extension.as_deref() == Some(allowed.as_str())
+                || (!file_type.is_empty() && file_type.eq_ignore_ascii_case(allowed))
+        })
+    }
+}
+
+/// 登录限流配置（按邮箱和 IP 分别计数）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginLimitConfig {
+    pub max_attempts: u32,         // 触发锁定前允许的连续失败次数
+    pub account_max_attempts: u32, // 同一邮箱来自所有 IP 的失败次数合计阈值
+    pub attempt_window: u64,       // seconds，失败计数在最后一次失败后保留的时间
+    pub lockout_base: u64,         // seconds，首次锁定时长，之后每次失败翻倍
+    pub lockout_max: u64,          // seconds
+}
+
+impl LoginLimitConfig {
+    /// 累计失败次数对应的锁定时长（秒），未达到阈值时返回 None
+    pub fn lockout_duration(&self, failures: u32) -> Option<u64> {
+        self.lockout_duration_after(failures, self.max_attempts)
+    }
+
+    /// 按指定阈值计算累计失败次数对应的锁定时长（秒）
+    pub fn lockout_duration_after(&self, failures: u32, max_attempts: u32) -> Option<u64> {
+        let exponent = failures.checked_sub(max_attempts)?;
+        let factor = 1u64.checked_shl(exponent).unwrap_or(u64::MAX);
+        Some(
+            self.lockout_base
+                .saturating_mul(factor)
+                .min(self.lockout_max),
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,14 +173,29 @@ impl Config {
                 connection_timeout: Self::get_env("REDIS_CONNECTION_TIMEOUT", "5".to_string())
                     .parse()?,
                 command_timeout: Self::get_env("REDIS_COMMAND_TIMEOUT", "5".to_string()).parse()?,
+                key_prefix: Self::get_env("REDIS_KEY_PREFIX", String::new()),
+                online_device_ttl: Self::get_env("REDIS_ONLINE_DEVICE_TTL", "1800".to_string())
+                    .parse()?,
+                change_queue_ttl: Self::get_env("REDIS_CHANGE_QUEUE_TTL", "604800".to_string())
+                    .parse()?,
+                token_blacklist_ttl: Self::get_env("REDIS_TOKEN_BLACKLIST_TTL", "60".to_string())
+                    .parse()?,
             },
             minio: MinioConfig {
+                backend: Self::get_env("STORAGE_BACKEND", "s3".to_string()),
+                local_path: Self::get_env("STORAGE_LOCAL_PATH", "./data/storage".to_string()),
                 endpoint: Self::get_env("MINIO_ENDPOINT", "localhost:9000".to_string()),
                 access_key: Self::get_env("MINIO_ACCESS_KEY", "minioadmin".to_string()),
                 secret_key: Self::get_env("MINIO_SECRET_KEY", "minioadmin".to_string()),
                 bucket: Self::get_env("MINIO_BUCKET", "claude-sync".to_string()),
                 region: Self::get_env("MINIO_REGION", "us-east-1".to_string()),
                 timeout: Self::get_env("MINIO_TIMEOUT", "30".to_string()).parse()?,
+                encryption_enabled: Self::get_env(
+                    "STORAGE_ENCRYPTION_ENABLED",
+                    "false".to_string(),
+                )
+                .parse()?,
+                encryption_key: Self::get_env("STORAGE_ENCRYPTION_KEY", String::new()),
             },
             jwt: JwtConfig {
                 secret: Self::get_env("JWT_SECRET", "your-secret-key-change-it".to_string()),
@@ -139,6 +220,24 @@ impl Config {
                     .parse()?,
                 max_versions_per_file: Self::get_env("MAX_VERSIONS_PER_FILE", "100".to_string())
                     .parse()?,
+                allowed_file_types: Self::parse_list(&Self::get_env(
+                    "ALLOWED_FILE_TYPES",
+                    String::new(),
+                )),
+                content_scanners: Self::parse_list(&Self::get_env(
+                    "CONTENT_SCANNERS",
+                    String::new(),
+                )),
+                content_scan_action: Self::get_env("CONTENT_SCAN_ACTION", "reject".to_string()),
+            },
+            login_limit: LoginLimitConfig {
+                max_attempts: Self::get_env("LOGIN_MAX_ATTEMPTS", "5".to_string()).parse()?,
+                account_max_attempts: Self::get_env("LOGIN_ACCOUNT_MAX_ATTEMPTS", "20".to_string())
+                    .parse()?,
+                attempt_window: Self::get_env("LOGIN_ATTEMPT_WINDOW", "900".to_string()).parse()?,
+                lockout_base: Self::get_env("LOGIN_LOCKOUT_SECONDS", "60".to_string()).parse()?,
+                lockout_max: Self::get_env("LOGIN_LOCKOUT_MAX_SECONDS", "3600".to_string())
+                    .parse()?,
             },
             logging: LoggingConfig {
                 level: Self::get_env("RUST_LOG", "info".to_string()),
@@ -167,16 +266,80 @@ impl Config {
             return Err(anyhow::anyhow!("DATABASE_URL cannot be empty"));
         }
 
+        // 验证存储后端
+        if !STORAGE_BACKENDS.contains(&self.minio.backend.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Unknown STORAGE_BACKEND: {} (available: {})",
+                self.minio.backend,
+                STORAGE_BACKENDS.join(", ")
+            ));
+        }
+        if self.minio.backend == LocalBackend::NAME && self.minio.local_path.is_empty() {
+            return Err(anyhow::anyhow!(
+                "STORAGE_LOCAL_PATH cannot be empty when STORAGE_BACKEND is local"
+            ));
+        }
+
+        // 验证存储加密主密钥
+        if self.minio.encryption_enabled && self.minio.encryption_key.len() < 32 {
+            return Err(anyhow::anyhow!(
+                "STORAGE_ENCRYPTION_KEY must be at least 32 characters long when encryption is enabled"
+            ));
+        }
+
         // 验证文件大小
         if self.sync.max_file_size == 0 {
             return Err(anyhow::anyhow!("MAX_FILE_SIZE must be greater than 0"));
         }
 
         // 验证分块大小
-        if self.sync.chunk_size == 0 || self.sync.chunk_size > self.sync.max_file_size {
+        if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&self.sync.chunk_size) {
+            return Err(anyhow::anyhow!(
+                "CHUNK_SIZE must be between {} and {} bytes",
+                MIN_CHUNK_SIZE,
+                MAX_CHUNK_SIZE
+            ));
+        }
+        if self.sync.chunk_size > self.sync.max_file_size {
             return Err(anyhow::anyhow!("Invalid CHUNK_SIZE"));
         }
 
+        // 验证内容扫描
+        if let Some(name) = self
+            .sync
+            .content_scanners
+            .iter()
+            .find(|name| !SCANNER_NAMES.contains(&name.as_str()))
+        {
+            return Err(anyhow::anyhow!(
+                "Unknown content scanner in CONTENT_SCANNERS: {} (available: {})",
+                name,
+                SCANNER_NAMES.join(", ")
+            ));
+        }
+        if ScanAction::parse(&self.sync.content_scan_action).is_none() {
+            return Err(anyhow::anyhow!(
+                "CONTENT_SCAN_ACTION must be \"reject\" or \"flag\""
+            ));
+        }
+
+        // 验证登录限流
+        if self.login_limit.max_attempts == 0 {
+            return Err(anyhow::anyhow!("LOGIN_MAX_ATTEMPTS must be greater than 0"));
+        }
+        if self.login_limit.account_max_attempts < self.login_limit.max_attempts {
+            return Err(anyhow::anyhow!(
+                "LOGIN_ACCOUNT_MAX_ATTEMPTS must not be less than LOGIN_MAX_ATTEMPTS"
+            ));
+        }
+        if self.login_limit.lockout_base == 0
+            || self.login_limit.lockout_base > self.login_limit.lockout_max
+        {
+            return Err(anyhow::anyhow!(
+                "LOGIN_LOCKOUT_SECONDS must be between 1 and LOGIN_LOCKOUT_MAX_SECONDS"
+            ));
+        }
+
         Ok(())
     }
 
@@ -185,6 +348,15 @@ impl Config {
         std::env::var(key).unwrap_or(default)
     }
 
+    /// 解析逗号分隔的列表（去除空白和前导点，统一小写）
+    fn parse_list(value: &str) -> Vec<String> {
+        value
+            .split(',')
+            .map(|item| item.trim().trim_start_matches('.').to_lowercase())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
     /// 服务器地址
     pub fn server_address(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
@@ -233,4 +405,102 @@ mod tests {
         config.jwt.secret = "short".to_string();
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_allowed_file_types() {
+        let mut config = Config::from_env().unwrap();
+        config.sync.allowed_file_types = Config::parse_list(" .MD, json ,, yaml");
+        assert_eq!(config.sync.allowed_file_types, vec!["md", "json", "yaml"]);
+
+        assert!(config.sync.is_file_type_allowed("agents/a.md", "text"));
+        assert!(config.sync.is_file_type_allowed("settings.JSON", ""));
+        assert!(!config.sync.is_file_type_allowed("bin/tool.exe", "binary"));
+        assert!(!config.sync.is_file_type_allowed("Makefile", ""));
+
+        // 按文件类型匹配
+        config.sync.allowed_file_types = vec!["text".to_string()];
+        assert!(config.sync.is_file_type_allowed("notes.txt", "text"));
+
+        // 空列表不限制
+        config.sync.allowed_file_types.clear();
+        assert!(config.sync.is_file_type_allowed("bin/tool.exe", "binary"));
+    }
+
+    #[test]
+    fn test_login_lockout_duration() {
+        let limit = LoginLimitConfig {
+            max_attempts: 3,
+            account_max_attempts: 10,
+            attempt_window: 900,
+            lockout_base: 60,
+            lockout_max: 600,
+        };
+
+        assert_eq!(limit.lockout_duration(0), None);
+        assert_eq!(limit.lockout_duration(2), None);
+        assert_eq!(limit.lockout_duration(3), Some(60));
+        assert_eq!(limit.lockout_duration(4), Some(120));
+        assert_eq!(limit.lockout_duration(5), Some(240));
+        assert_eq!(limit.lockout_duration(7), Some(600));
+        assert_eq!(limit.lockout_duration(200), Some(600));
+
+        // 邮箱合计计数使用更高的阈值
+        assert_eq!(limit.lockout_duration_after(9, 10), None);
+        assert_eq!(limit.lockout_duration_after(10, 10), Some(60));
+    }
+
+    #[test]
+    fn test_chunk_size_validation() {
+        let mut config = Config::from_env().unwrap();
+        config.jwt.secret = "a".repeat(32);
+        config.sync.chunk_size = MIN_CHUNK_SIZE;
+        assert!(config.validate().is_ok());
+
+        config.sync.chunk_size = 1024;
+        assert!(config.validate().is_err());
+
+        config.sync.chunk_size = MAX_CHUNK_SIZE + 1;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_content_scan_validation() {
+        let mut config = Config::from_env().unwrap();
+        config.jwt.secret = "a".repeat(32);
+        config.sync.content_scanners = vec!["secrets".to_string()];
+        assert!(config.validate().is_ok());
+
+        config.sync.content_scan_action = "quarantine".to_string();
+        assert!(config.validate().is_err());
+
+        config.sync.content_scan_action = "flag".to_string();
+        config.sync.content_scanners.push("antivirus".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_storage_backend_validation() {
+        let mut config = Config::from_env().unwrap();
+        config.jwt.secret = "a".repeat(32);
+        config.minio.backend = "local".to_string();
+        assert!(config.validate().is_ok());
+
+        config.minio.local_path.clear();
+        assert!(config.validate().is_err());
+
+        config.minio.backend = "azure".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_storage_encryption_key_validation() {
+        let mut config = Config::from_env().unwrap();
+        config.jwt.secret = "a".repeat(32);
+        config.minio.encryption_enabled = true;
+        config.minio.encryption_key = "short".to_string();
+        assert!(config.validate().is_err());
+
+        config.minio.encryption_key = "k".repeat(32);
+        assert!(config.validate().is_ok());
+    }
 }
@@ -0,0 +1,89 @@
+use sha2::{Digest, Sha256};
+
+/// BLAKE3 哈希的前缀（SHA-256 哈希不带前缀）
+pub const BLAKE3_PREFIX: &str = "blake3:";
+
+/// 内容哈希算法
+///
+/// 客户端上传的哈希自带算法标记：`blake3:<hex>` 为 BLAKE3，没有前缀的为 SHA-256。
+/// 服务器按哈希自身的标记校验上传内容，客户端切换算法后旧版本的哈希仍然有效。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// 哈希字符串使用的算法
+    pub fn of(hash: &str) -> Self {
+        if hash.starts_with(BLAKE3_PREFIX) {
+            Self::Blake3
+        } else {
+            Self::Sha256
+        }
+    }
+
+    /// 创建增量哈希计算器
+    pub fn hasher(self) -> ContentHasher {
+        match self {
+            Self::Sha256 => ContentHasher::Sha256(Sha256::new()),
+            Self::Blake3 => ContentHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    /// 计算内容哈希（带算法标记）
+    pub fn hash(self, content: &[u8]) -> String {
+        let mut hasher = self.hasher();
+        hasher.update(content);
+        hasher.finalize()
+    }
+}
+
+/// 增量哈希计算器（上传分块到达时逐块累计）
+pub enum ContentHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl ContentHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(data),
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// 结束计算，返回带算法标记的哈希
+    pub fn finalize(self) -> String {
+        match self {
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Blake3(hasher) => format!("{}{}", BLAKE3_PREFIX, hasher.finalize().to_hex()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithm_detected_from_hash() {
+        let content = b"# CLAUDE.md\n";
+
+        let sha256 = HashAlgorithm::Sha256.hash(content);
+        assert_eq!(sha256.len(), 64);
+        assert_eq!(HashAlgorithm::of(&sha256), HashAlgorithm::Sha256);
+
+        let blake3 = HashAlgorithm::Blake3.hash(content);
+        assert!(blake3.starts_with(BLAKE3_PREFIX));
+        assert_eq!(HashAlgorithm::of(&blake3), HashAlgorithm::Blake3);
+
+        let mut hasher = HashAlgorithm::Blake3.hasher();
+        for chunk in content.chunks(5) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), blake3);
+    }
+}
@@ -4,7 +4,9 @@ use std::time::Duration;
 use tracing::info;
 use uuid::Uuid;
 
+use crate::chunking::ChunkRef;
 use crate::config::Config;
+use crate::models::{ConflictType, DeviceDivergence};
 
 /// 数据库连接池
 #[derive(Clone)]
@@ -166,6 +168,38 @@ impl UserRepository {
 
         Ok(())
     }
+
+    /// 获取用户的 TOTP 密钥（未启用两步验证时为 None）
+    pub async fn find_totp_secret(pool: &sqlx::PgPool, user_id: &Uuid) -> Result<Option<String>> {
+        let secret: Option<Option<String>> =
+            sqlx::query_scalar(r#"SELECT totp_secret FROM users WHERE id = $1"#)
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await?;
+
+        Ok(secret.flatten())
+    }
+
+    /// 设置或清除用户的 TOTP 密钥
+    pub async fn set_totp_secret(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        secret: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET totp_secret = $2, updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(secret)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
 }
 
 /// 设备查询辅助结构
@@ -210,6 +244,23 @@ impl DeviceRepository {
         Ok(device)
     }
 
+    /// 根据 ID 查找设备
+    pub async fn find_by_id(pool: &sqlx::PgPool, device_id: &Uuid) -> Result<Option<DeviceRow>> {
+        let device = sqlx::query_as::<_, DeviceRow>(
+            r#"
+            SELECT id, user_id, device_name, device_type, device_fingerprint,
+                   last_seen, created_at, is_active
+            FROM devices
+            WHERE id = $1
+            "#,
+        )
+        .bind(device_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(device)
+    }
+
     /// 创建新设备
     pub async fn create(
         pool: &sqlx::PgPool,
@@ -334,6 +385,22 @@ impl TokenRepository {
         Ok(())
     }
 
+    /// 撤销设备的所有 Token，返回撤销数量
+    pub async fn revoke_by_device(pool: &sqlx::PgPool, device_id: &Uuid) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE access_tokens
+            SET is_revoked = true
+            WHERE device_id = $1 AND is_revoked = false
+            "#,
+        )
+        .bind(device_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// 更新 Token 最后使用时间
     pub async fn update_last_used(pool: &sqlx::PgPool, token_id: &Uuid) -> Result<()> {
         sqlx::query(
@@ -365,6 +432,556 @@ impl TokenRepository {
     }
 }
 
+/// 同步状态查询辅助结构
+pub struct SyncStateRepository;
+
+impl SyncStateRepository {
+    /// 查询每台设备各文件的本地版本与当前最新版本
+    pub async fn find_device_versions(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+    ) -> Result<Vec<DeviceFileVersionRow>> {
+        // heads 使用 idx_file_versions_current 取每个文件的最新版本
+        let rows = sqlx::query_as::<_, DeviceFileVersionRow>(
+            r#"
+            WITH heads AS (
+                SELECT DISTINCT ON (file_path) file_path, version_number AS head_version
+                FROM file_versions
+                WHERE user_id = $1
+                ORDER BY file_path, version_number DESC
+            )
+            SELECT d.id AS device_id, d.device_name, d.last_seen,
+                   h.file_path, h.head_version, lv.version_number AS device_version
+            FROM devices d
+            LEFT JOIN heads h ON true
+            LEFT JOIN sync_states s
+                   ON s.user_id = $1 AND s.device_id = d.id AND s.file_path = h.file_path
+            LEFT JOIN file_versions lv ON lv.id = s.local_version_id
+            WHERE d.user_id = $1 AND d.is_active = true
+            ORDER BY d.last_seen DESC, d.id, h.file_path
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// 生成设备同步偏差报告
+    pub async fn device_divergence(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+    ) -> Result<Vec<DeviceDivergence>> {
+        let rows = Self::find_device_versions(pool, user_id).await?;
+        Ok(summarize_device_divergence(&rows))
+    }
+}
+
+/// 文件版本操作
+pub struct FileVersionRepository;
+
+impl FileVersionRepository {
+    /// 查找与给定路径仅大小写不同的已存储路径（最早存储的拼写优先）
+    ///
+    /// 不同平台的设备上传同一文件时可能使用不同大小写，统一使用已有拼写可以避免重复文件。
+    pub async fn find_canonical_path(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_path: &str,
+    ) -> Result<Option<String>> {
+        let path = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT file_path
+            FROM file_versions
+            WHERE user_id = $1 AND LOWER(file_path) = LOWER($2)
+            ORDER BY created_at ASC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_path)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(path)
+    }
+
+    /// 解析文件的存储路径：已存在仅大小写不同的路径时沿用该路径
+    pub async fn resolve_path(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_path: &str,
+    ) -> Result<String> {
+        Ok(Self::find_canonical_path(pool, user_id, file_path)
+            .await?
+            .unwrap_or_else(|| file_path.to_string()))
+    }
+
+    /// 查找文件的最新版本
+    pub async fn find_head(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_path: &str,
+    ) -> Result<Option<FileHeadRow>> {
+        let head = sqlx::query_as::<_, FileHeadRow>(
+            r#"
+            SELECT id, version_number, file_hash, is_deleted
+            FROM file_versions
+            WHERE user_id = $1 AND file_path = $2
+            ORDER BY version_number DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_path)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(head)
+    }
+
+    /// 按版本号查找文件版本 ID
+    pub async fn find_version_id(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_path: &str,
+        version_number: i32,
+    ) -> Result<Option<Uuid>> {
+        let id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT id
+            FROM file_versions
+            WHERE user_id = $1 AND file_path = $2 AND version_number = $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_path)
+        .bind(version_number)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// 记录文件删除：在最新版本之上追加一个 is_deleted 版本（墓碑）
+    ///
+    /// 文件不存在或最新版本已是删除状态时不做任何操作，返回新版本号。
+    pub async fn record_deletion(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        device_id: &Uuid,
+        file_path: &str,
+    ) -> Result<Option<i32>> {
+        let version = sqlx::query_scalar::<_, i32>(
+            r#"
+            WITH head AS (
+                SELECT id, file_path, file_hash, storage_path, version_number, is_deleted, file_mode
+                FROM file_versions
+                WHERE user_id = $1 AND file_path = $3
+                ORDER BY version_number DESC
+                LIMIT 1
+            )
+            INSERT INTO file_versions
+                (user_id, file_path, file_hash, file_size, storage_path,
+                 version_number, device_id, parent_version_id, is_deleted, file_mode)
+            SELECT $1, file_path, file_hash, 0, storage_path, version_number + 1, $2, id, true,
+                   file_mode
+            FROM head
+            WHERE NOT COALESCE(is_deleted, false)
+            RETURNING version_number
+            "#,
+        )
+        .bind(user_id)
+        .bind(device_id)
+        .bind(file_path)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(version)
+    }
+
+    /// 列出用户的所有文件版本（用于按保留策略清理）
+    ///
+    /// 仍被同步状态或冲突记录引用的版本标记为 pinned，清理时必须保留。
+    pub async fn list_for_pruning(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+    ) -> Result<Vec<PrunableVersionRow>> {
+        let rows = sqlx::query_as::<_, PrunableVersionRow>(
+            r#"
+            SELECT v.id, v.file_path, v.version_number, v.file_hash, v.file_size, v.device_id,
+                   v.created_at, v.file_mode,
+                   EXISTS (
+                       SELECT 1 FROM sync_states s
+                       WHERE s.local_version_id = v.id OR s.remote_version_id = v.id
+                   ) OR EXISTS (
+                       SELECT 1 FROM conflicts c
+                       WHERE v.id IN (c.base_version_id, c.local_version_id,
+                                      c.remote_version_id, c.resolved_version_id)
+                   ) AS pinned
+            FROM file_versions v
+            WHERE v.user_id = $1
+            ORDER BY v.file_path, v.version_number DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// 删除指定版本，保留版本中指向被删除版本的父版本引用置空
+    pub async fn delete_versions(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        version_ids: &[Uuid],
+    ) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE file_versions
+            SET parent_version_id = NULL
+            WHERE user_id = $1 AND parent_version_id = ANY($2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(version_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        let result = sqlx::query("DELETE FROM file_versions WHERE user_id = $1 AND id = ANY($2)")
+            .bind(user_id)
+            .bind(version_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 删除单个版本，返回被删除版本的内容哈希和大小（版本不存在时返回 None）
+    ///
+    /// 内容的引用计数由 file_versions 上的触发器减少，调用方再用 lock_unreferenced 确认是否可删除对象。
+    pub async fn delete_version(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        version_id: &Uuid,
+    ) -> Result<Option<DeletedVersionRow>> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE file_versions
+            SET parent_version_id = NULL
+            WHERE user_id = $1 AND parent_version_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(version_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let deleted = sqlx::query_as::<_, DeletedVersionRow>(
+            r#"
+            DELETE FROM file_versions
+            WHERE user_id = $1 AND id = $2
+            RETURNING file_hash, file_size
+            "#,
+        )
+        .bind(user_id)
+        .bind(version_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(deleted)
+    }
+
+    /// 锁定引用计数已归零的内容记录并返回这些哈希
+    ///
+    /// 行锁持有到事务结束：引用同一内容的新版本（触发器增加计数）和并发的清理都要等待，
+    /// 调用方在事务内删除存储对象后再用 delete_unreferenced 删除计数记录。
+    pub async fn lock_unreferenced(
+        conn: &mut sqlx::PgConnection,
+        user_id: &Uuid,
+        file_hashes: &[String],
+    ) -> Result<Vec<String>> {
+        let hashes = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT file_hash FROM storage_objects
+            WHERE user_id = $1 AND file_hash = ANY($2) AND ref_count = 0
+            FOR UPDATE
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_hashes)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        Ok(hashes)
+    }
+
+    /// 删除存储对象已被删除的计数记录
+    pub async fn delete_unreferenced(
+        conn: &mut sqlx::PgConnection,
+        user_id: &Uuid,
+        file_hashes: &[String],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            DELETE FROM storage_objects
+            WHERE user_id = $1 AND file_hash = ANY($2) AND ref_count = 0
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_hashes)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// 冲突记录操作
+pub struct ConflictRepository;
+
+impl ConflictRepository {
+    /// 创建一条未解决的冲突记录，返回冲突 ID
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_path: &str,
+        base_version_id: &Uuid,
+        local_version_id: &Uuid,
+        remote_version_id: &Uuid,
+        conflict_type: &ConflictType,
+        conflict_data: &serde_json::Value,
+    ) -> Result<Uuid> {
+        let id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO conflicts
+                (user_id, file_path, base_version_id, local_version_id, remote_version_id,
+                 conflict_type, conflict_data)
+            VALUES ($1, $2, $3, $4, $5, $6, $7::jsonb)
+            RETURNING id
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_path)
+        .bind(base_version_id)
+        .bind(local_version_id)
+        .bind(remote_version_id)
+        .bind(conflict_type.as_str())
+        .bind(conflict_data.to_string())
+        .fetch_one(pool)
+        .await?;
+
+        Ok(id)
+    }
+}
+
+/// 文件分块清单操作
+pub struct ChunkRepository;
+
+impl ChunkRepository {
+    /// 保存文件版本的分块清单（覆盖已有清单）
+    pub async fn save_manifest(
+        pool: &sqlx::PgPool,
+        file_version_id: &Uuid,
+        chunks: &[ChunkRef],
+    ) -> Result<()> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("DELETE FROM file_chunks WHERE file_version_id = $1")
+            .bind(file_version_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO file_chunks (file_version_id, chunk_index, chunk_hash, chunk_offset, chunk_size)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+            )
+            .bind(file_version_id)
+            .bind(index as i32)
+            .bind(&chunk.hash)
+            .bind(chunk.offset as i64)
+            .bind(chunk.size as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// 按顺序读取文件版本的分块清单
+    pub async fn find_manifest(
+        pool: &sqlx::PgPool,
+        file_version_id: &Uuid,
+    ) -> Result<Vec<ChunkRef>> {
+        let rows = sqlx::query_as::<_, FileChunkRow>(
+            r#"
+            SELECT chunk_hash, chunk_offset, chunk_size
+            FROM file_chunks
+            WHERE file_version_id = $1
+            ORDER BY chunk_index
+            "#,
+        )
+        .bind(file_version_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ChunkRef::from).collect())
+    }
+}
+
+/// 文件搜索操作
+pub struct SearchRepository;
+
+impl SearchRepository {
+    /// 写入文本内容索引（相同内容只索引一次）
+    pub async fn index_content(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_hash: &str,
+        content: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO file_content_index (user_id, file_hash, content)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, file_hash) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(file_hash)
+        .bind(content)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// 删除内容索引（存储对象被清理时调用）
+    pub async fn delete_content(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        file_hashes: &[String],
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM file_content_index WHERE user_id = $1 AND file_hash = ANY($2)",
+        )
+        .bind(user_id)
+        .bind(file_hashes)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// 按路径模式搜索各文件的最新版本（已删除的文件不返回）
+    pub async fn search_paths(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        pattern: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchRow>> {
+        let rows = sqlx::query_as::<_, SearchRow>(
+            r#"
+            SELECT file_path, version_number, file_hash, file_size, created_at, NULL::TEXT AS content
+            FROM (
+                SELECT DISTINCT ON (file_path)
+                       file_path, version_number, file_hash, file_size, created_at, is_deleted
+                FROM file_versions
+                WHERE user_id = $1 AND file_path ILIKE $2 ESCAPE '\'
+                ORDER BY file_path, version_number DESC
+            ) heads
+            WHERE NOT COALESCE(is_deleted, false)
+            ORDER BY file_path
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// 按内容模式搜索已索引的文本文件（返回内容匹配的所有版本）
+    pub async fn search_content(
+        pool: &sqlx::PgPool,
+        user_id: &Uuid,
+        pattern: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchRow>> {
+        let rows = sqlx::query_as::<_, SearchRow>(
+            r#"
+            SELECT v.file_path, v.version_number, v.file_hash, v.file_size, v.created_at, c.content
+            FROM file_versions v
+            JOIN file_content_index c ON c.user_id = v.user_id AND c.file_hash = v.file_hash
+            WHERE v.user_id = $1 AND c.content ILIKE $2 ESCAPE '\'
+            ORDER BY v.file_path, v.version_number DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+}
+
+/// 按设备汇总落后的版本数（从未同步过的文件视为落后全部版本）
+pub fn summarize_device_divergence(rows: &[DeviceFileVersionRow]) -> Vec<DeviceDivergence> {
+    let mut report: Vec<DeviceDivergence> = Vec::new();
+
+    for row in rows {
+        if report.last().map(|d| d.device_id) != Some(row.device_id) {
+            report.push(DeviceDivergence {
+                device_id: row.device_id,
+                device_name: row.device_name.clone(),
+                last_seen: row.last_seen,
+                tracked_files: 0,
+                files_behind: 0,
+                versions_behind: 0,
+                max_lag: 0,
+            });
+        }
+
+        let Some(head_version) = row.head_version else {
+            continue;
+        };
+
+        let entry = report.last_mut().expect("entry pushed above");
+        let lag = (head_version - row.device_version.unwrap_or(0)).max(0) as i64;
+
+        entry.tracked_files += 1;
+        if lag > 0 {
+            entry.files_behind += 1;
+            entry.versions_behind += lag;
+            entry.max_lag = entry.max_lag.max(lag);
+        }
+    }
+
+    report
+}
+
 // ===== 数据行结构 =====
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -403,6 +1020,71 @@ pub struct TokenRow {
     pub is_revoked: bool,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeviceFileVersionRow {
+    pub device_id: Uuid,
+    pub device_name: String,
+    pub last_seen: chrono::DateTime<chrono::Utc>,
+    pub file_path: Option<String>,
+    pub head_version: Option<i32>,
+    pub device_version: Option<i32>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FileHeadRow {
+    pub id: Uuid,
+    pub version_number: i32,
+    pub file_hash: String,
+    pub is_deleted: Option<bool>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PrunableVersionRow {
+    pub id: Uuid,
+    pub file_path: String,
+    pub version_number: i32,
+    pub file_hash: String,
+    pub file_size: i64,
+    pub device_id: Uuid,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub file_mode: Option<i32>,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeletedVersionRow {
+    pub file_hash: String,
+    pub file_size: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SearchRow {
+    pub file_path: String,
+    pub version_number: i32,
+    pub file_hash: String,
+    pub file_size: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// 内容匹配时的文本内容（路径匹配时为 None）
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FileChunkRow {
+    pub chunk_hash: String,
+    pub chunk_offset: i64,
+    pub chunk_size: i32,
+}
+
+impl From<FileChunkRow> for ChunkRef {
+    fn from(row: FileChunkRow) -> Self {
+        ChunkRef {
+            hash: row.chunk_hash,
+            offset: row.chunk_offset as u64,
+            size: row.chunk_size as u32,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -412,4 +1094,60 @@ mod tests {
     async fn test_user_crud() {
         // 测试用户 CRUD 操作
     }
+
+    fn version_row(
+        device_id: Uuid,
+        file_path: Option<&str>,
+        head_version: Option<i32>,
+        device_version: Option<i32>,
+    ) -> DeviceFileVersionRow {
+        DeviceFileVersionRow {
+            device_id,
+            device_name: format!("device-{}", device_id),
+            last_seen: chrono::Utc::now(),
+            file_path: file_path.map(|p| p.to_string()),
+            head_version,
+            device_version,
+        }
+    }
+
+    #[test]
+    fn test_device_divergence() {
+        let up_to_date = Uuid::new_v4();
+        let behind = Uuid::new_v4();
+        let never_synced = Uuid::new_v4();
+        let no_files = Uuid::new_v4();
+
+        let rows = vec![
+            version_row(up_to_date, Some("a.md"), Some(5), Some(5)),
+            version_row(up_to_date, Some("b.json"), Some(2), Some(2)),
+            version_row(behind, Some("a.md"), Some(5), Some(2)),
+            version_row(behind, Some("b.json"), Some(2), Some(1)),
+            version_row(never_synced, Some("a.md"), Some(5), None),
+            version_row(never_synced, Some("b.json"), Some(2), None),
+            version_row(no_files, None, None, None),
+        ];
+
+        let report = summarize_device_divergence(&rows);
+        assert_eq!(report.len(), 4);
+
+        assert_eq!(report[0].device_id, up_to_date);
+        assert_eq!(report[0].tracked_files, 2);
+        assert_eq!(report[0].files_behind, 0);
+        assert_eq!(report[0].versions_behind, 0);
+
+        assert_eq!(report[1].device_id, behind);
+        assert_eq!(report[1].files_behind, 2);
+        assert_eq!(report[1].versions_behind, 4);
+        assert_eq!(report[1].max_lag, 3);
+
+        assert_eq!(report[2].device_id, never_synced);
+        assert_eq!(report[2].files_behind, 2);
+        assert_eq!(report[2].versions_behind, 7);
+        assert_eq!(report[2].max_lag, 5);
+
+        assert_eq!(report[3].device_id, no_files);
+        assert_eq!(report[3].tracked_files, 0);
+        assert_eq!(report[3].versions_behind, 0);
+    }
 }
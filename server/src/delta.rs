@@ -0,0 +1,73 @@
+use crate::proto::claude_sync::{delta_op, DeltaOp};
+use anyhow::{bail, Result};
+
+/// 按增量操作从基准内容重建完整文件内容
+pub fn apply_delta(base: &[u8], ops: &[DeltaOp]) -> Result<Vec<u8>> {
+    let mut content = Vec::with_capacity(base.len());
+
+    for op in ops {
+        match &op.op {
+            Some(delta_op::Op::Copy(copy)) => {
+                let range = usize::try_from(copy.offset)
+                    .ok()
+                    .zip(usize::try_from(copy.length).ok())
+                    .and_then(|(offset, length)| Some(offset..offset.checked_add(length)?))
+                    .filter(|range| range.end <= base.len());
+                let Some(range) = range else {
+                    bail!(
+                        "Delta copy out of range: offset={}, length={}, base size={}",
+                        copy.offset,
+                        copy.length,
+                        base.len()
+                    );
+                };
+                content.extend_from_slice(&base[range]);
+            }
+            Some(delta_op::Op::Insert(data)) => content.extend_from_slice(data),
+            None => bail!("Empty delta operation"),
+        }
+    }
+
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::claude_sync::DeltaCopy;
+    use crate::storage::StorageService;
+
+    fn copy(offset: u64, length: u64) -> DeltaOp {
+        DeltaOp {
+            op: Some(delta_op::Op::Copy(DeltaCopy { offset, length })),
+        }
+    }
+
+    fn insert(data: &[u8]) -> DeltaOp {
+        DeltaOp {
+            op: Some(delta_op::Op::Insert(data.to_vec())),
+        }
+    }
+
+    #[test]
+    fn test_apply_delta_reconstructs_target() {
+        let base = b"# Agent\nmodel: sonnet\ntools: all\n";
+        let target = b"# Agent\nmodel: opus\ntools: all\n";
+        let ops = vec![copy(0, 8), insert(b"model: opus\n"), copy(22, 11)];
+
+        let content = apply_delta(base, &ops).unwrap();
+        assert_eq!(content, target);
+        assert!(StorageService::verify_hash(
+            &content,
+            &StorageService::hash_file(target)
+        ));
+    }
+
+    #[test]
+    fn test_apply_delta_rejects_invalid_ops() {
+        let base = b"short";
+        assert!(apply_delta(base, &[copy(2, 10)]).is_err());
+        assert!(apply_delta(base, &[copy(u64::MAX, 2)]).is_err());
+        assert!(apply_delta(base, &[DeltaOp { op: None }]).is_err());
+    }
+}
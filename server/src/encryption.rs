@@ -0,0 +1,138 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// 加密对象的文件头，用于区分加密前写入的明文对象
+const ENCRYPTED_HEADER: &[u8] = b"CSENC1";
+
+/// AES-GCM nonce 长度
+const NONCE_LEN: usize = 12;
+
+/// 信封加密：主密钥包装每个用户的数据密钥，数据密钥加密文件内容
+#[derive(Clone)]
+pub struct EnvelopeEncryption {
+    master_key: [u8; 32],
+}
+
+impl EnvelopeEncryption {
+    /// 从主密钥字符串创建
+    pub fn new(master_key: &str) -> Self {
+        Self {
+            master_key: derive_key(master_key),
+        }
+    }
+
+    /// 生成新的数据密钥
+    pub fn generate_data_key() -> [u8; 32] {
+        Aes256Gcm::generate_key(&mut OsRng).into()
+    }
+
+    /// 使用主密钥包装数据密钥
+    pub fn wrap_key(&self, data_key: &[u8; 32]) -> Result<Vec<u8>> {
+        seal(&self.master_key, data_key)
+    }
+
+    /// 使用主密钥解开数据密钥
+    pub fn unwrap_key(&self, wrapped_key: &[u8]) -> Result<[u8; 32]> {
+        let key = open(&self.master_key, wrapped_key)?;
+        key.try_into()
+            .map_err(|_| anyhow::anyhow!("Invalid data key length"))
+    }
+
+    /// 使用数据密钥加密文件内容（带文件头）
+    pub fn encrypt(data_key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut result = ENCRYPTED_HEADER.to_vec();
+        result.extend(seal(data_key, plaintext)?);
+        Ok(result)
+    }
+
+    /// 使用数据密钥解密文件内容
+    pub fn decrypt(data_key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+        let sealed = data
+            .strip_prefix(ENCRYPTED_HEADER)
+            .ok_or_else(|| anyhow::anyhow!("Data is not encrypted"))?;
+        open(data_key, sealed)
+    }
+
+    /// 检查数据是否为加密对象
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(ENCRYPTED_HEADER)
+    }
+}
+
+/// 加密并在密文前附加随机 nonce
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
+
+    let mut result = nonce.to_vec();
+    result.extend_from_slice(&ciphertext);
+    Ok(result)
+}
+
+/// 分离 nonce 并解密
+fn open(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
+    if data.len() < NONCE_LEN {
+        return Err(anyhow::anyhow!("Ciphertext too short"));
+    }
+
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(key.into());
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
+}
+
+/// 从字符串派生 32 字节密钥
+fn derive_key(key: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(key.as_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let encryption = EnvelopeEncryption::new("master-key-for-tests-0123456789ab");
+        let data_key = EnvelopeEncryption::generate_data_key();
+
+        // 数据密钥包装后不以明文保存
+        let wrapped = encryption.wrap_key(&data_key).unwrap();
+        assert_ne!(&wrapped[NONCE_LEN..NONCE_LEN + 32], &data_key[..]);
+        assert_eq!(encryption.unwrap_key(&wrapped).unwrap(), data_key);
+
+        let plaintext = b"{\"model\": \"claude\"}";
+        let stored = EnvelopeEncryption::encrypt(&data_key, plaintext).unwrap();
+        assert!(EnvelopeEncryption::is_encrypted(&stored));
+        assert!(!stored.windows(plaintext.len()).any(|w| w == plaintext));
+        assert_eq!(
+            EnvelopeEncryption::decrypt(&data_key, &stored).unwrap(),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let encryption = EnvelopeEncryption::new("master-key-for-tests-0123456789ab");
+        let other = EnvelopeEncryption::new("another-master-key-0123456789abcd");
+
+        let wrapped = encryption
+            .wrap_key(&EnvelopeEncryption::generate_data_key())
+            .unwrap();
+        assert!(other.unwrap_key(&wrapped).is_err());
+
+        assert!(!EnvelopeEncryption::is_encrypted(b"plain data"));
+        assert!(EnvelopeEncryption::decrypt(&[0u8; 32], b"plain data").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+/// 错误原因的 metadata 键（客户端据此区分错误类型）
+pub const ERROR_REASON_METADATA: &str = "x-error-reason";
+
+/// 冲突文件路径的 metadata 键
+pub const ERROR_PATH_METADATA: &str = "x-error-path";
+
+/// 存储配额的 metadata 键（字节数）
+pub const QUOTA_USED_METADATA: &str = "x-quota-used";
+pub const QUOTA_LIMIT_METADATA: &str = "x-quota-limit";
+
+/// gRPC 服务错误
+///
+/// 每个变体对应一个 gRPC 状态码，并通过 `x-error-reason` metadata 携带结构化原因。
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    /// 请求参数无效
+    #[error("{0}")]
+    InvalidArgument(String),
+
+    /// 资源不存在
+    #[error("{0}")]
+    NotFound(String),
+
+    /// 与服务器上的版本冲突
+    #[error("Conflict on {path}: {message}")]
+    Conflict { path: String, message: String },
+
+    /// 操作的前提条件不满足（如增量基准不存在）
+    #[error("{0}")]
+    FailedPrecondition(String),
+
+    /// 存储配额不足
+    #[error("Storage quota exceeded: {used} of {limit} bytes used")]
+    QuotaExceeded { used: u64, limit: u64 },
+
+    /// 未认证
+    #[error("{0}")]
+    Unauthenticated(String),
+
+    /// 已认证但无权访问（如设备不属于当前用户）
+    #[error("{0}")]
+    PermissionDenied(String),
+
+    /// 内容校验失败
+    #[error("{0}")]
+    DataLoss(String),
+
+    /// 上传内容未通过内容扫描（如包含密钥）
+    #[error("{0}")]
+    ContentRejected(String),
+
+    /// 服务器内部错误
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ServiceError {
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        Self::InvalidArgument(message.into())
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(message.into())
+    }
+
+    pub fn failed_precondition(message: impl Into<String>) -> Self {
+        Self::FailedPrecondition(message.into())
+    }
+
+    pub fn unauthenticated(message: impl Into<String>) -> Self {
+        Self::Unauthenticated(message.into())
+    }
+
+    pub fn permission_denied(message: impl Into<String>) -> Self {
+        Self::PermissionDenied(message.into())
+    }
+
+    pub fn data_loss(message: impl Into<String>) -> Self {
+        Self::DataLoss(message.into())
+    }
+
+    /// 包装数据库、存储等内部错误
+    pub fn internal(error: impl std::fmt::Display) -> Self {
+        Self::Internal(error.to_string())
+    }
+
+    /// 对应的 gRPC 状态码
+    pub fn code(&self) -> Code {
+        match self {
+            Self::InvalidArgument(_) => Code::InvalidArgument,
+            Self::NotFound(_) => Code::NotFound,
+            Self::Conflict { .. } | Self::FailedPrecondition(_) => Code::FailedPrecondition,
+            Self::QuotaExceeded { .. } => Code::ResourceExhausted,
+            Self::Unauthenticated(_) => Code::Unauthenticated,
+            Self::PermissionDenied(_) => Code::PermissionDenied,
+            Self::DataLoss(_) => Code::DataLoss,
+            Self::ContentRejected(_) => Code::PermissionDenied,
+            Self::Internal(_) => Code::Internal,
+        }
+    }
+
+    /// 结构化错误原因
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Self::InvalidArgument(_) => "INVALID_ARGUMENT",
+            Self::NotFound(_) => "NOT_FOUND",
+            Self::Conflict { .. } => "CONFLICT",
+            Self::FailedPrecondition(_) => "FAILED_PRECONDITION",
+            Self::QuotaExceeded { .. } => "QUOTA_EXCEEDED",
+            Self::Unauthenticated(_) => "UNAUTHENTICATED",
+            Self::PermissionDenied(_) => "PERMISSION_DENIED",
+            Self::DataLoss(_) => "DATA_LOSS",
+            Self::ContentRejected(_) => "CONTENT_REJECTED",
+            Self::Internal(_) => "INTERNAL",
+        }
+    }
+}
+
+impl From<ServiceError> for Status {
+    fn from(error: ServiceError) -> Self {
+        if let ServiceError::Internal(message) = &error {
+            tracing::error!("Internal error: {}", message);
+        }
+
+        let mut status = Status::new(error.code(), error.to_string());
+        let metadata = status.metadata_mut();
+        metadata.insert(
+            ERROR_REASON_METADATA,
+            MetadataValue::from_static(error.reason()),
+        );
+        match &error {
+            ServiceError::Conflict { path, .. } => {
+                if let Ok(value) = path.parse() {
+                    metadata.insert(ERROR_PATH_METADATA, value);
+                }
+            }
+            ServiceError::QuotaExceeded { used, limit } => {
+                metadata.insert(QUOTA_USED_METADATA, MetadataValue::from(*used));
+                metadata.insert(QUOTA_LIMIT_METADATA, MetadataValue::from(*limit));
+            }
+            _ => {}
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_found_status() {
+        let status = Status::from(ServiceError::not_found("Device not found"));
+        assert_eq!(status.code(), Code::NotFound);
+        assert_eq!(status.message(), "Device not found");
+        assert_eq!(
+            status.metadata().get(ERROR_REASON_METADATA).unwrap(),
+            "NOT_FOUND"
+        );
+    }
+
+    #[test]
+    fn test_quota_and_conflict_metadata() {
+        let status = Status::from(ServiceError::QuotaExceeded {
+            used: 900,
+            limit: 1000,
+        });
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get(QUOTA_USED_METADATA).unwrap(), "900");
+        assert_eq!(status.metadata().get(QUOTA_LIMIT_METADATA).unwrap(), "1000");
+
+        let status = Status::from(ServiceError::Conflict {
+            path: "agents/a.md".to_string(),
+            message: "based on version 2, server is at version 3".to_string(),
+        });
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(
+            status.metadata().get(ERROR_REASON_METADATA).unwrap(),
+            "CONFLICT"
+        );
+        assert_eq!(
+            status.metadata().get(ERROR_PATH_METADATA).unwrap(),
+            "agents/a.md"
+        );
+    }
+}
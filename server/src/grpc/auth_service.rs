@@ -1,4 +1,4 @@
-use crate::auth::AuthService as LocalAuthService;
+use crate::auth::{AuthService as LocalAuthService, LoginOutcome, LoginResult, TooManyAttempts};
 use crate::cache::Cache;
 use crate::config::Config;
 use crate::db::DbPool;
@@ -7,6 +7,7 @@ use crate::proto::claude_sync::{
     LoginResponse as ProtoLoginResponse, LogoutRequest, LogoutResponse, RefreshTokenRequest,
     RefreshTokenResponse, RegisterRequest as ProtoRegisterRequest,
     RegisterResponse as ProtoRegisterResponse, RevokeTokenRequest, RevokeTokenResponse,
+    VerifyTotpRequest,
 };
 use std::str::FromStr;
 use tonic::{Request, Response, Status};
@@ -54,6 +55,7 @@ impl AuthServiceTrait for AuthGrpcService {
         &self,
         request: Request<ProtoLoginRequest>,
     ) -> Result<Response<ProtoLoginResponse>, Status> {
+        let client_ip = request.remote_addr().map(|addr| addr.ip());
         let req = request.into_inner();
 
         match self
@@ -64,18 +66,23 @@ impl AuthServiceTrait for AuthGrpcService {
                 req.device_name,
                 &req.device_type,
                 req.device_fingerprint,
+                client_ip,
             )
             .await
         {
-            Ok(result) => Ok(Response::new(ProtoLoginResponse {
+            Ok(LoginOutcome::Authenticated(result)) => Ok(Response::new(login_response(result))),
+            Ok(LoginOutcome::TotpRequired(challenge)) => Ok(Response::new(ProtoLoginResponse {
                 success: true,
-                message: "Login successful".to_string(),
-                access_token: result.access_token,
-                refresh_token: result.refresh_token,
-                expires_at: result.expires_at.timestamp(),
-                user_id: result.user_id.to_string(),
-                device_id: result.device_id.to_string(),
+                message: "TOTP verification required".to_string(),
+                expires_at: challenge.expires_at.timestamp(),
+                totp_required: true,
+                totp_challenge: challenge.challenge_id.to_string(),
+                ..Default::default()
             })),
+            Err(e) if e.is::<TooManyAttempts>() => {
+                tracing::warn!("Login rejected: {}", e);
+                Err(Status::resource_exhausted(e.to_string()))
+            }
             Err(e) => {
                 tracing::error!("Login failed: {}", e);
                 Err(Status::unauthenticated(e.to_string()))
@@ -83,6 +90,24 @@ impl AuthServiceTrait for AuthGrpcService {
         }
     }
 
+    async fn verify_totp(
+        &self,
+        request: Request<VerifyTotpRequest>,
+    ) -> Result<Response<ProtoLoginResponse>, Status> {
+        let req = request.into_inner();
+
+        let challenge_id = uuid::Uuid::from_str(&req.challenge_id)
+            .map_err(|_| Status::invalid_argument("Invalid challenge ID"))?;
+
+        match self.auth_service.verify_totp(challenge_id, &req.code).await {
+            Ok(result) => Ok(Response::new(login_response(result))),
+            Err(e) => {
+                tracing::error!("TOTP verification failed: {}", e);
+                Err(Status::unauthenticated(e.to_string()))
+            }
+        }
+    }
+
     async fn refresh_token(
         &self,
         request: Request<RefreshTokenRequest>,
@@ -147,6 +172,21 @@ impl AuthServiceTrait for AuthGrpcService {
     }
 }
 
+/// 将登录结果转换为 gRPC 响应
+fn login_response(result: LoginResult) -> ProtoLoginResponse {
+    ProtoLoginResponse {
+        success: true,
+        message: "Login successful".to_string(),
+        access_token: result.access_token,
+        refresh_token: result.refresh_token,
+        expires_at: result.expires_at.timestamp(),
+        user_id: result.user_id.to_string(),
+        device_id: result.device_id.to_string(),
+        totp_required: false,
+        totp_challenge: String::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,20 +1,32 @@
+use crate::auth::AuthService;
+use crate::cache::Cache;
+use crate::config::Config;
 use crate::db::DbPool;
+use crate::error::ServiceError;
 use crate::proto::claude_sync::{
     device_service_server::DeviceService, ListDevicesRequest, ListDevicesResponse,
     RegisterDeviceRequest, RegisterDeviceResponse, RemoveDeviceRequest, RemoveDeviceResponse,
     UpdateDeviceRequest, UpdateDeviceResponse,
 };
+use std::str::FromStr;
 use tonic::{Request, Response, Status};
 
 /// DeviceService gRPC 实现
 pub struct DeviceGrpcService {
     pool: DbPool,
+    auth_service: AuthService,
 }
 
 impl DeviceGrpcService {
     /// 创建新的 gRPC 服务实例
-    pub fn new(pool: DbPool) -> Self {
-        Self { pool }
+    pub fn new(pool: DbPool, cache: Cache, config: Config) -> Self {
+        let auth_service = AuthService::new(pool.clone(), cache, config);
+        Self { pool, auth_service }
+    }
+
+    /// 从请求头的 Bearer Token 中解析调用者身份
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<crate::models::Claims, Status> {
+        super::authenticate(&self.auth_service, request).await
     }
 }
 
@@ -53,12 +65,27 @@ impl DeviceService for DeviceGrpcService {
 
     async fn remove_device(
         &self,
-        _request: Request<RemoveDeviceRequest>,
+        request: Request<RemoveDeviceRequest>,
     ) -> Result<Response<RemoveDeviceResponse>, Status> {
-        // TODO: 实现设备删除逻辑
+        let claims = self.authenticate(&request).await?;
+        let req = request.into_inner();
+
+        let device_id = uuid::Uuid::from_str(&req.device_id)
+            .map_err(|_| ServiceError::invalid_argument("Invalid device ID"))?;
+
+        // 移除设备即吊销其全部 Token（设备不存在返回 NOT_FOUND，不属于当前用户返回 PERMISSION_DENIED）
+        let revoked = self
+            .auth_service
+            .revoke_device(claims.user_id, device_id)
+            .await
+            .map_err(|e| match e.downcast::<ServiceError>() {
+                Ok(error) => error,
+                Err(e) => ServiceError::internal(e),
+            })?;
+
         Ok(Response::new(RemoveDeviceResponse {
             success: true,
-            message: "Device removal not yet implemented".to_string(),
+            message: format!("Device revoked ({} tokens invalidated)", revoked),
         }))
     }
 }
@@ -1,3 +1,8 @@
+use crate::auth::AuthService;
+use crate::error::ServiceError;
+use crate::models::Claims;
+use tonic::{Request, Status};
+
 pub mod auth_service;
 pub mod device_service;
 pub mod notification_service;
@@ -7,3 +12,21 @@ pub use auth_service::AuthGrpcService;
 pub use device_service::DeviceGrpcService;
 pub use notification_service::NotificationGrpcService;
 pub use sync_service::FileSyncGrpcService;
+
+/// 从请求头的 Bearer Token 中解析调用者身份
+pub async fn authenticate<T>(
+    auth_service: &AuthService,
+    request: &Request<T>,
+) -> Result<Claims, Status> {
+    let token = request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ServiceError::unauthenticated("Missing access token"))?;
+
+    auth_service
+        .verify_access_token(token)
+        .await
+        .map_err(|e| ServiceError::unauthenticated(e.to_string()).into())
+}
@@ -1,14 +1,27 @@
-use crate::cache::Cache;
-use crate::db::DbPool;
+use crate::auth::AuthService;
+use crate::cache::{Cache, ChangeType, FileChangeNotification};
+use crate::config::{Config, SyncConfig};
+use crate::content_hash::HashAlgorithm;
+use crate::db::{ConflictRepository, DbPool, DeviceRepository, FileHeadRow, FileVersionRepository};
+use crate::delta::apply_delta;
+use crate::error::ServiceError;
+use crate::models::{Claims, ConflictType};
 use crate::proto::claude_sync::{
-    file_sync_service_server::FileSyncService, full_sync_response, incremental_sync_response,
+    download_file_response, file_sync_service_server::FileSyncService, full_sync_response,
+    incremental_sync_response, upload_file_request, DeleteVersionRequest, DeleteVersionResponse,
     DownloadFileRequest, DownloadFileResponse, FetchChangesRequest, FetchChangesResponse,
-    FullSyncRequest, FullSyncResponse, GetFileHistoryRequest, GetFileHistoryResponse,
-    IncrementalSyncRequest, IncrementalSyncResponse, ReportChangesRequest, ReportChangesResponse,
-    ResolveConflictRequest, ResolveConflictResponse, RestoreFileVersionRequest,
-    RestoreFileVersionResponse, SyncComplete, SyncProgress, UploadFileRequest, UploadFileResponse,
+    FileChunk, FileInfo, FileVersion, FullSyncRequest, FullSyncResponse, GetFileHistoryRequest,
+    GetFileHistoryResponse, HasContentRequest, HasContentResponse, IncrementalSyncRequest,
+    IncrementalSyncResponse, PruneVersionsRequest, PruneVersionsResponse, ReportChangesRequest,
+    ReportChangesResponse, ResolveConflictRequest, ResolveConflictResponse,
+    RestoreFileVersionRequest, RestoreFileVersionResponse, SearchFilesRequest, SearchFilesResponse,
+    SearchMatch, SyncComplete, SyncProgress, UploadFileRequest, UploadFileResponse,
 };
-use crate::storage::StorageService;
+use crate::retention::{delete_version, prune_versions, RetentionPolicy};
+use crate::scan::ContentRejected;
+use crate::search::{index_content, indexable_text, search_files, snippet};
+use crate::storage::{normalize_content_type, StorageService};
+use crate::upload::UploadSpool;
 use std::pin::Pin;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
@@ -18,30 +31,323 @@ pub struct FileSyncGrpcService {
     pool: DbPool,
     cache: Cache,
     storage: StorageService,
+    sync_config: SyncConfig,
+    auth_service: AuthService,
 }
 
 impl FileSyncGrpcService {
+    /// 检测单个变更是否与服务器上的最新版本冲突，冲突时写入冲突记录
+    async fn process_file_change(
+        &self,
+        user_id: &uuid::Uuid,
+        device_id: &uuid::Uuid,
+        change: &FileInfo,
+    ) -> anyhow::Result<FileChangeResult> {
+        let pool = self.pool.inner();
+        let file_path =
+            FileVersionRepository::resolve_path(pool, user_id, &change.file_path).await?;
+        let head = FileVersionRepository::find_head(pool, user_id, &file_path).await?;
+
+        let result = detect_conflict(change, head.as_ref());
+        let (FileChangeResult::Conflict(conflict_type), Some(head)) = (&result, head) else {
+            return Ok(result);
+        };
+
+        // 本地内容尚未上传，冲突记录的本地版本指向客户端的基准版本，本地哈希记录在冲突详情中
+        let base_version_id =
+            FileVersionRepository::find_version_id(pool, user_id, &file_path, change.version)
+                .await?
+                .unwrap_or(head.id);
+        let conflict_data = serde_json::json!({
+            "device_id": device_id,
+            "base_version": change.version,
+            "remote_version": head.version_number,
+            "local_hash": change.file_hash,
+            "local_size": change.file_size,
+            "local_deleted": change.is_deleted,
+        });
+        let conflict_id = ConflictRepository::create(
+            pool,
+            user_id,
+            &file_path,
+            &base_version_id,
+            &base_version_id,
+            &head.id,
+            conflict_type,
+            &conflict_data,
+        )
+        .await?;
+
+        tracing::info!(
+            "Conflict {} on {}: device {} based on version {}, server is at version {}",
+            conflict_id,
+            file_path,
+            device_id,
+            change.version,
+            head.version_number
+        );
+
+        Ok(result)
+    }
+
+    /// 查找设备所属的用户
+    async fn device_user_id(&self, device_id: &str) -> Result<uuid::Uuid, ServiceError> {
+        let device_id = uuid::Uuid::parse_str(device_id)
+            .map_err(|_| ServiceError::invalid_argument("Invalid device ID"))?;
+        let device = DeviceRepository::find_by_id(self.pool.inner(), &device_id)
+            .await
+            .map_err(ServiceError::internal)?
+            .ok_or_else(|| ServiceError::not_found("Device not found"))?;
+        Ok(device.user_id)
+    }
+
+    /// 查找设备所属的用户，并确认设备属于 Token 对应的用户
+    async fn authorized_user_id(
+        &self,
+        claims: &Claims,
+        device_id: &str,
+    ) -> Result<uuid::Uuid, ServiceError> {
+        let user_id = self.device_user_id(device_id).await?;
+        check_device_owner(claims, &user_id)?;
+        Ok(user_id)
+    }
+
     /// 创建新的服务实例
-    pub fn new(pool: DbPool, cache: Cache, storage: StorageService) -> Self {
+    pub fn new(pool: DbPool, cache: Cache, storage: StorageService, config: Config) -> Self {
+        let sync_config = config.sync.clone();
+        let auth_service = AuthService::new(pool.clone(), cache.clone(), config);
         Self {
             pool,
             cache,
             storage,
+            sync_config,
+            auth_service,
+        }
+    }
+}
+
+/// 设备必须属于 Token 对应的用户，否则拒绝访问
+fn check_device_owner(claims: &Claims, device_user_id: &uuid::Uuid) -> Result<(), ServiceError> {
+    if claims.user_id != *device_user_id {
+        return Err(ServiceError::permission_denied(
+            "Device does not belong to the authenticated user",
+        ));
+    }
+    Ok(())
+}
+
+/// 检查上传文件是否符合服务器的文件大小和文件类型策略
+fn check_upload_allowed(sync_config: &SyncConfig, metadata: &FileInfo) -> Result<(), String> {
+    if metadata.file_size < 0 || metadata.file_size as u64 > sync_config.max_file_size {
+        return Err(format!(
+            "Invalid file size for {}: {} bytes (max {})",
+            metadata.file_path, metadata.file_size, sync_config.max_file_size
+        ));
+    }
+
+    if sync_config.is_file_type_allowed(&metadata.file_path, &metadata.file_type) {
+        return Ok(());
+    }
+
+    Err(format!(
+        "File type not allowed by server policy: {} (allowed: {})",
+        metadata.file_path,
+        sync_config.allowed_file_types.join(", ")
+    ))
+}
+
+/// 单个文件变更的冲突检测结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileChangeResult {
+    /// 首次写入或基于服务器最新版本的修改
+    Success,
+    /// 基于旧版本的修改，服务器上已有其他设备写入的新版本
+    Conflict(ConflictType),
+}
+
+/// 比较变更的基准版本与服务器上的最新版本
+///
+/// `change.version` 是客户端上次同步到的版本号，0 表示客户端没有记录基准版本，此时不做检测。
+/// 内容与最新版本相同的变更视为已同步，不算冲突。
+pub(crate) fn detect_conflict(change: &FileInfo, head: Option<&FileHeadRow>) -> FileChangeResult {
+    let Some(head) = head else {
+        return FileChangeResult::Success;
+    };
+    if change.version <= 0 || change.version == head.version_number {
+        return FileChangeResult::Success;
+    }
+
+    let head_deleted = head.is_deleted.unwrap_or(false);
+    match (change.is_deleted, head_deleted) {
+        (true, true) => FileChangeResult::Success,
+        (false, false) if change.file_hash == head.file_hash => FileChangeResult::Success,
+        (false, false) => FileChangeResult::Conflict(ConflictType::ModifyModify),
+        _ => FileChangeResult::Conflict(ConflictType::ModifyDelete),
+    }
+}
+
+/// 规范化客户端上报的文件路径
+///
+/// 统一使用 `/` 分隔、去掉 `.` 和多余分隔符并拒绝 `..`，保留原有大小写；
+/// 仅大小写不同的路径由 `FileVersionRepository::resolve_path` 合并到已存储的拼写。
+pub(crate) fn canonical_file_path(file_path: &str) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for part in file_path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return Err(format!("Invalid file path: {}", file_path)),
+            part => parts.push(part),
         }
     }
+
+    if parts.is_empty() {
+        return Err(format!("Invalid file path: {}", file_path));
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// 将一次上报的全部变更合并为一批通知（同一路径只保留最后一条）
+fn build_change_notifications(
+    device_id: uuid::Uuid,
+    changes: &[FileInfo],
+    timestamp: i64,
+) -> Result<Vec<FileChangeNotification>, String> {
+    let mut notifications: Vec<FileChangeNotification> = Vec::with_capacity(changes.len());
+
+    for change in changes {
+        let file_path = canonical_file_path(&change.file_path)?;
+        let change_type = if change.is_deleted {
+            ChangeType::Deleted
+        } else {
+            ChangeType::Modified
+        };
+
+        notifications.retain(|existing| existing.file_path != file_path);
+        notifications.push(FileChangeNotification {
+            file_path,
+            device_id,
+            change_type,
+            timestamp,
+        });
+    }
+
+    Ok(notifications)
+}
+
+/// 下载响应流：先发送文件元数据，再按配置的分块大小依次发送内容
+fn download_responses(
+    metadata: FileInfo,
+    content: &[u8],
+    chunk_size: u64,
+) -> Vec<DownloadFileResponse> {
+    let chunk_size = chunk_size.max(1) as usize;
+    let chunks = content
+        .chunks(chunk_size)
+        .enumerate()
+        .map(|(i, data)| DownloadFileResponse {
+            payload: Some(download_file_response::Payload::Chunk(FileChunk {
+                chunk_number: i as i64,
+                data: data.to_vec(),
+                offset: (i * chunk_size) as i64,
+            })),
+        });
+
+    std::iter::once(DownloadFileResponse {
+        payload: Some(download_file_response::Payload::Metadata(metadata)),
+    })
+    .chain(chunks)
+    .collect()
+}
+
+/// 校验上传内容与声明的哈希一致
+fn verify_content(content: &[u8], metadata: &FileInfo) -> Result<(), ServiceError> {
+    if StorageService::verify_hash(content, &metadata.file_hash) {
+        return Ok(());
+    }
+    Err(ServiceError::data_loss(format!(
+        "Uploaded content does not match hash {} for {}",
+        metadata.file_hash, metadata.file_path
+    )))
 }
 
 #[tonic::async_trait]
 impl FileSyncService for FileSyncGrpcService {
     async fn report_changes(
         &self,
-        _request: Request<ReportChangesRequest>,
+        request: Request<ReportChangesRequest>,
     ) -> Result<Response<ReportChangesResponse>, Status> {
-        // TODO: 实现变更上报逻辑
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let req = request.into_inner();
+
+        let device_id = uuid::Uuid::parse_str(&req.device_id)
+            .map_err(|_| ServiceError::invalid_argument("Invalid device ID"))?;
+        let device = DeviceRepository::find_by_id(self.pool.inner(), &device_id)
+            .await
+            .map_err(ServiceError::internal)?
+            .ok_or_else(|| ServiceError::not_found("Device not found"))?;
+        check_device_owner(&claims, &device.user_id)?;
+
+        // 基于旧版本的变更记为冲突，不通知其他设备
+        let mut accepted = Vec::with_capacity(req.changes.len());
+        let mut conflicts = Vec::new();
+        for mut change in req.changes {
+            change.file_path =
+                canonical_file_path(&change.file_path).map_err(ServiceError::invalid_argument)?;
+            match self
+                .process_file_change(&device.user_id, &device_id, &change)
+                .await
+                .map_err(ServiceError::internal)?
+            {
+                FileChangeResult::Success => accepted.push(change),
+                FileChangeResult::Conflict(_) => conflicts.push(change.file_path),
+            }
+        }
+
+        // 整批变更合并为一次通知，其他设备通过变更队列获取
+        let notifications =
+            build_change_notifications(device_id, &accepted, chrono::Utc::now().timestamp())
+                .map_err(ServiceError::invalid_argument)?;
+
+        // 删除记录为墓碑版本，避免已删除的文件在其他设备同步时重新出现
+        let pool = self.pool.inner();
+        for notification in notifications
+            .iter()
+            .filter(|n| matches!(n.change_type, ChangeType::Deleted))
+        {
+            let file_path =
+                FileVersionRepository::resolve_path(pool, &device.user_id, &notification.file_path)
+                    .await
+                    .map_err(ServiceError::internal)?;
+            FileVersionRepository::record_deletion(pool, &device.user_id, &device_id, &file_path)
+                .await
+                .map_err(ServiceError::internal)?;
+        }
+
+        self.cache
+            .push_file_changes(&device.user_id, &notifications)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to queue change notifications: {}", e);
+                ServiceError::internal("Failed to queue change notifications")
+            })?;
+
+        tracing::debug!(
+            "Device {} reported {} changes ({} notifications, {} conflicts)",
+            device_id,
+            accepted.len() + conflicts.len(),
+            notifications.len(),
+            conflicts.len()
+        );
+
         Ok(Response::new(ReportChangesResponse {
             success: true,
-            message: "Report changes not yet implemented".to_string(),
-            conflicts_detected: vec![],
+            message: format!(
+                "{} changes reported, {} conflicts",
+                notifications.len(),
+                conflicts.len()
+            ),
+            conflicts_detected: conflicts,
             pending_uploads: vec![],
         }))
     }
@@ -66,27 +372,174 @@ impl FileSyncService for FileSyncGrpcService {
 
     async fn upload_file(
         &self,
-        _request: Request<tonic::Streaming<UploadFileRequest>>,
+        request: Request<tonic::Streaming<UploadFileRequest>>,
     ) -> Result<Response<UploadFileResponse>, Status> {
-        // TODO: 实现文件上传逻辑
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let mut stream = request.into_inner();
+
+        // 第一条消息必须是文件元数据
+        let mut metadata = match stream.message().await? {
+            Some(UploadFileRequest {
+                payload: Some(upload_file_request::Payload::Metadata(metadata)),
+            }) => metadata,
+            _ => {
+                return Err(ServiceError::invalid_argument(
+                    "First upload message must contain file metadata",
+                )
+                .into())
+            }
+        };
+
+        metadata.file_path =
+            canonical_file_path(&metadata.file_path).map_err(ServiceError::invalid_argument)?;
+        check_upload_allowed(&self.sync_config, &metadata)
+            .map_err(ServiceError::invalid_argument)?;
+        // 只保存权限位，客户端可能带上文件类型位（如 S_IFREG）
+        metadata.file_mode &= 0o7777;
+
+        let user_id = self
+            .authorized_user_id(&claims, &metadata.device_id)
+            .await?;
+
+        // 后续消息为文件分块，或一条基于已上传版本的增量；服务器已有该内容时只发送元数据
+        // 分块直接写入临时文件，收到的字节数超过声明大小时立即拒绝
+        let mut spool = None;
+        let mut delta = None;
+        while let Some(message) = stream.message().await? {
+            match message.payload {
+                Some(upload_file_request::Payload::Chunk(chunk)) if delta.is_none() => {
+                    if spool.is_none() {
+                        // 声明大小已由 check_upload_allowed 检查
+                        spool = Some(UploadSpool::new(
+                            metadata.file_size as u64,
+                            HashAlgorithm::of(&metadata.file_hash),
+                        )?);
+                    }
+                    if let Some(spool) = &mut spool {
+                        spool.write_chunk(&chunk).await?;
+                    }
+                }
+                Some(upload_file_request::Payload::Delta(file_delta))
+                    if delta.is_none() && spool.is_none() =>
+                {
+                    delta = Some(file_delta)
+                }
+                _ => {
+                    return Err(ServiceError::invalid_argument(
+                        "Upload must contain either file chunks or a single delta",
+                    )
+                    .into())
+                }
+            }
+        }
+
+        let content = match (delta, spool) {
+            (Some(delta), _) => {
+                // 基准内容不存在时客户端应改为完整上传
+                let base = self
+                    .storage
+                    .download_file(&user_id, &delta.base_hash)
+                    .await
+                    .map_err(|_| {
+                        ServiceError::failed_precondition(format!(
+                            "Delta base not found: {}",
+                            delta.base_hash
+                        ))
+                    })?;
+                let content = apply_delta(&base, &delta.ops)
+                    .map_err(|e| ServiceError::invalid_argument(e.to_string()))?;
+                verify_content(&content, &metadata)?;
+                Some(content)
+            }
+            // 分块的大小和哈希在临时文件中逐块累计校验
+            (None, Some(spool)) => Some(spool.finish(&metadata.file_hash).await?),
+            (None, None) if metadata.file_size > 0 => {
+                // 只有元数据：新版本直接关联到已保存的对象
+                let exists = self
+                    .storage
+                    .file_exists(&user_id, &metadata.file_hash)
+                    .await
+                    .map_err(ServiceError::internal)?;
+                if !exists {
+                    return Err(ServiceError::failed_precondition(format!(
+                        "Content not found on server: {}",
+                        metadata.file_hash
+                    ))
+                    .into());
+                }
+                None
+            }
+            (None, None) => {
+                verify_content(&[], &metadata)?;
+                Some(Vec::new())
+            }
+        };
+
+        if let Some(content) = content {
+            // 文本内容写入搜索索引，二进制文件只能按路径搜索
+            let text = indexable_text(&content).map(str::to_owned);
+            // 对象按内容哈希存储，相同内容只保存一份
+            // 内容扫描拒绝时返回 CONTENT_REJECTED，其余为内部错误
+            let content_type = normalize_content_type(&metadata.content_type);
+            self.storage
+                .store_content(&user_id, &metadata.file_hash, content, content_type)
+                .await
+                .map_err(|e| match e.downcast::<ContentRejected>() {
+                    Ok(rejected) => ServiceError::from(rejected),
+                    Err(e) => ServiceError::internal(e),
+                })?;
+            if let Some(text) = text {
+                index_content(self.pool.inner(), &user_id, &metadata.file_hash, &text).await;
+            }
+        }
+
+        // TODO: 记录新版本
         Ok(Response::new(UploadFileResponse {
             success: true,
-            message: "File upload not yet implemented".to_string(),
+            message: "File content stored, version recording not yet implemented".to_string(),
             version_id: String::new(),
             version_number: 0,
         }))
     }
 
+    async fn has_content(
+        &self,
+        request: Request<HasContentRequest>,
+    ) -> Result<Response<HasContentResponse>, Status> {
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let req = request.into_inner();
+        let user_id = self.authorized_user_id(&claims, &req.device_id).await?;
+
+        let exists = self
+            .storage
+            .file_exists(&user_id, &req.file_hash)
+            .await
+            .map_err(ServiceError::internal)?;
+
+        Ok(Response::new(HasContentResponse { exists }))
+    }
+
     type DownloadFileStream =
         Pin<Box<dyn tokio_stream::Stream<Item = Result<DownloadFileResponse, Status>> + Send>>;
 
     async fn download_file(
         &self,
-        _request: Request<DownloadFileRequest>,
+        request: Request<DownloadFileRequest>,
     ) -> Result<Response<Self::DownloadFileStream>, Status> {
-        // TODO: 实现文件下载逻辑
-        let (tx, rx) = tokio::sync::mpsc::channel(1);
-        let _ = tx.send(Ok(DownloadFileResponse { payload: None })).await;
+        let req = request.into_inner();
+        let metadata = FileInfo {
+            file_path: canonical_file_path(&req.file_path)
+                .map_err(ServiceError::invalid_argument)?,
+            version: req.version_number,
+            ..Default::default()
+        };
+
+        // TODO: 从存储读取文件内容，目前只返回元数据
+        let responses = download_responses(metadata, &[], self.sync_config.chunk_size);
+        let (tx, rx) = tokio::sync::mpsc::channel(responses.len());
+        for response in responses {
+            let _ = tx.send(Ok(response)).await;
+        }
         Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
     }
 
@@ -164,6 +617,117 @@ impl FileSyncService for FileSyncGrpcService {
             restored_file: None,
         }))
     }
+
+    async fn prune_versions(
+        &self,
+        request: Request<PruneVersionsRequest>,
+    ) -> Result<Response<PruneVersionsResponse>, Status> {
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let req = request.into_inner();
+        if req.keep_versions == 0 {
+            return Err(ServiceError::invalid_argument("keep_versions must be at least 1").into());
+        }
+        let user_id = self.authorized_user_id(&claims, &req.device_id).await?;
+
+        let policy = RetentionPolicy {
+            keep_versions: req.keep_versions as usize,
+            retention: (req.retention_days > 0)
+                .then(|| chrono::Duration::days(req.retention_days as i64)),
+        };
+        let report = prune_versions(
+            self.pool.inner(),
+            &self.storage,
+            &user_id,
+            &policy,
+            req.dry_run,
+        )
+        .await
+        .map_err(|e| ServiceError::internal(format!("Failed to prune versions: {}", e)))?;
+
+        Ok(Response::new(PruneVersionsResponse {
+            pruned_versions: report
+                .pruned
+                .into_iter()
+                .map(|version| FileVersion {
+                    version_id: version.id.to_string(),
+                    version_number: version.version_number,
+                    file_path: version.file_path,
+                    file_hash: version.file_hash,
+                    file_size: version.file_size,
+                    device_id: version.device_id.to_string(),
+                    created_at: version.created_at.timestamp(),
+                    file_mode: version.file_mode.unwrap_or(0) as u32,
+                })
+                .collect(),
+            deleted_objects: report.deleted_objects as u32,
+            freed_bytes: report.freed_bytes,
+        }))
+    }
+
+    async fn delete_version(
+        &self,
+        request: Request<DeleteVersionRequest>,
+    ) -> Result<Response<DeleteVersionResponse>, Status> {
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let req = request.into_inner();
+        let user_id = self.authorized_user_id(&claims, &req.device_id).await?;
+        let version_id = uuid::Uuid::parse_str(&req.version_id)
+            .map_err(|_| ServiceError::invalid_argument("Invalid version ID"))?;
+
+        // 只能删除本用户的版本，其他用户的版本 ID 按不存在处理
+        let deleted = delete_version(self.pool.inner(), &self.storage, &user_id, &version_id)
+            .await
+            .map_err(|e| ServiceError::internal(format!("Failed to delete version: {}", e)))?
+            .ok_or_else(|| ServiceError::not_found(format!("Version not found: {}", version_id)))?;
+
+        Ok(Response::new(DeleteVersionResponse {
+            file_hash: deleted.file_hash,
+            file_size: deleted.file_size,
+            object_deleted: deleted.object_deleted,
+        }))
+    }
+
+    async fn search_files(
+        &self,
+        request: Request<SearchFilesRequest>,
+    ) -> Result<Response<SearchFilesResponse>, Status> {
+        let claims = super::authenticate(&self.auth_service, &request).await?;
+        let req = request.into_inner();
+        let query = req.query.trim();
+        if query.is_empty() {
+            return Err(ServiceError::invalid_argument("Search query must not be empty").into());
+        }
+        let user_id = self.authorized_user_id(&claims, &req.device_id).await?;
+
+        let rows = search_files(
+            self.pool.inner(),
+            &user_id,
+            query,
+            req.include_content,
+            req.limit,
+        )
+        .await
+        .map_err(|e| ServiceError::internal(format!("Failed to search files: {}", e)))?;
+
+        Ok(Response::new(SearchFilesResponse {
+            matches: rows
+                .into_iter()
+                .map(|row| SearchMatch {
+                    snippet: row
+                        .content
+                        .as_deref()
+                        .and_then(|content| snippet(content, query))
+                        .unwrap_or_default(),
+                    content_match: row.content.is_some(),
+                    file_path: row.file_path,
+                    version_number: row.version_number,
+                    file_hash: row.file_hash,
+                    file_size: row.file_size,
+                    created_at: row.created_at.timestamp(),
+                })
+                .collect(),
+        }))
+    }
 }
 
 #[cfg(test)]
@@ -181,4 +745,200 @@ mod tests {
     async fn test_upload_file() {
         // 测试文件上传
     }
+
+    fn file_info(file_path: &str, file_type: &str) -> FileInfo {
+        FileInfo {
+            file_path: file_path.to_string(),
+            file_hash: String::new(),
+            file_size: 0,
+            modified_at: 0,
+            version: 0,
+            device_id: String::new(),
+            is_deleted: false,
+            file_type: file_type.to_string(),
+            file_mode: 0,
+            content_type: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_upload_file_type_policy() {
+        let mut sync_config = crate::config::Config::from_env().unwrap().sync;
+        sync_config.allowed_file_types = vec!["md".to_string(), "json".to_string()];
+
+        // 允许的类型
+        assert!(check_upload_allowed(&sync_config, &file_info("agents/a.md", "text")).is_ok());
+        assert!(check_upload_allowed(&sync_config, &file_info("settings.json", "json")).is_ok());
+
+        // 不允许的类型
+        let message =
+            check_upload_allowed(&sync_config, &file_info("bin/tool.exe", "binary")).unwrap_err();
+        assert!(message.contains("bin/tool.exe"));
+
+        // 声明大小超过上限或为负数
+        let mut too_large = file_info("agents/a.md", "text");
+        too_large.file_size = sync_config.max_file_size as i64 + 1;
+        assert!(check_upload_allowed(&sync_config, &too_large).is_err());
+        too_large.file_size = -1;
+        assert!(check_upload_allowed(&sync_config, &too_large).is_err());
+    }
+
+    /// 按偏移量重组下载响应中的分块
+    fn reassemble(responses: &[DownloadFileResponse]) -> Vec<u8> {
+        let mut content = Vec::new();
+        for response in responses {
+            if let Some(download_file_response::Payload::Chunk(chunk)) = &response.payload {
+                assert_eq!(chunk.offset as usize, content.len());
+                content.extend_from_slice(&chunk.data);
+            }
+        }
+        content
+    }
+
+    #[test]
+    fn test_download_responses_round_trip_at_custom_size() {
+        let chunk_size = crate::config::MIN_CHUNK_SIZE;
+
+        // 小于一个分块：元数据 + 一个分块
+        let small = b"small file".to_vec();
+        let responses = download_responses(file_info("a.md", "text"), &small, chunk_size);
+        assert_eq!(responses.len(), 2);
+        assert!(matches!(
+            responses[0].payload,
+            Some(download_file_response::Payload::Metadata(_))
+        ));
+        assert_eq!(reassemble(&responses), small);
+
+        // 跨越多个分块
+        let large: Vec<u8> = (0..chunk_size as usize * 3 + 7)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let responses = download_responses(file_info("b.bin", "binary"), &large, chunk_size);
+        assert_eq!(responses.len(), 5);
+        assert_eq!(reassemble(&responses), large);
+    }
+
+    fn head(version_number: i32, file_hash: &str, is_deleted: bool) -> FileHeadRow {
+        FileHeadRow {
+            id: uuid::Uuid::new_v4(),
+            version_number,
+            file_hash: file_hash.to_string(),
+            is_deleted: Some(is_deleted),
+        }
+    }
+
+    fn change(version: i32, file_hash: &str, is_deleted: bool) -> FileInfo {
+        let mut change = file_info("agents/helper.md", "text");
+        change.version = version;
+        change.file_hash = file_hash.to_string();
+        change.is_deleted = is_deleted;
+        change
+    }
+
+    #[test]
+    fn test_detect_conflict_accepts_first_write_and_fast_forward() {
+        assert_eq!(
+            detect_conflict(&change(0, "a", false), None),
+            FileChangeResult::Success
+        );
+        // 基于最新版本的修改
+        assert_eq!(
+            detect_conflict(&change(3, "b", false), Some(&head(3, "a", false))),
+            FileChangeResult::Success
+        );
+        // 未记录基准版本
+        assert_eq!(
+            detect_conflict(&change(0, "b", false), Some(&head(3, "a", false))),
+            FileChangeResult::Success
+        );
+        // 内容已与最新版本一致
+        assert_eq!(
+            detect_conflict(&change(2, "a", false), Some(&head(3, "a", false))),
+            FileChangeResult::Success
+        );
+    }
+
+    #[test]
+    fn test_detect_conflict_on_stale_base_version() {
+        assert_eq!(
+            detect_conflict(&change(2, "b", false), Some(&head(3, "a", false))),
+            FileChangeResult::Conflict(ConflictType::ModifyModify)
+        );
+        assert_eq!(
+            detect_conflict(&change(2, "b", false), Some(&head(3, "a", true))),
+            FileChangeResult::Conflict(ConflictType::ModifyDelete)
+        );
+        assert_eq!(
+            detect_conflict(&change(2, "", true), Some(&head(3, "a", false))),
+            FileChangeResult::Conflict(ConflictType::ModifyDelete)
+        );
+        assert_eq!(
+            detect_conflict(&change(2, "", true), Some(&head(3, "a", true))),
+            FileChangeResult::Success
+        );
+    }
+
+    #[test]
+    fn test_canonical_file_path() {
+        assert_eq!(
+            canonical_file_path("agents/helper.md").unwrap(),
+            "agents/helper.md"
+        );
+        assert_eq!(
+            canonical_file_path("./Agents//helper.md").unwrap(),
+            "Agents/helper.md"
+        );
+        assert_eq!(
+            canonical_file_path("\\Agents\\helper.md").unwrap(),
+            "Agents/helper.md"
+        );
+        assert!(canonical_file_path("agents/../../etc/passwd").is_err());
+        assert!(canonical_file_path("./").is_err());
+    }
+
+    #[test]
+    fn test_build_change_notifications_batches_changes() {
+        let device_id = uuid::Uuid::new_v4();
+        let mut changes: Vec<FileInfo> = (0..50)
+            .map(|i| file_info(&format!("agents/{}.md", i), "text"))
+            .collect();
+        // 同一文件的多次修改只通知一次
+        changes.push(file_info("./agents/0.md", "text"));
+        let mut deleted = file_info("agents/1.md", "text");
+        deleted.is_deleted = true;
+        changes.push(deleted);
+
+        let notifications = build_change_notifications(device_id, &changes, 42).unwrap();
+
+        assert_eq!(notifications.len(), 50);
+        assert!(notifications
+            .iter()
+            .all(|n| n.device_id == device_id && n.timestamp == 42));
+        let last = notifications.last().unwrap();
+        assert_eq!(last.file_path, "agents/1.md");
+        assert!(matches!(last.change_type, ChangeType::Deleted));
+
+        assert!(build_change_notifications(device_id, &[file_info("../x", "text")], 0).is_err());
+    }
+
+    #[test]
+    fn test_device_owner_check() {
+        let user_id = uuid::Uuid::new_v4();
+        let claims = Claims {
+            exp: 0,
+            iat: 0,
+            iss: String::new(),
+            sub: user_id.to_string(),
+            user_id,
+            device_id: None,
+            token_type: crate::models::TokenType::Access,
+            jti: uuid::Uuid::new_v4(),
+        };
+
+        assert!(check_device_owner(&claims, &user_id).is_ok());
+        // 其他用户的设备
+        let err = check_device_owner(&claims, &uuid::Uuid::new_v4()).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        assert_eq!(err.reason(), "PERMISSION_DENIED");
+    }
 }
@@ -26,11 +26,32 @@ struct HealthStatus {
     message: String,
 }
 
+/// 服务器信息响应（供客户端预先过滤文件）
+#[derive(Debug, Serialize)]
+struct ServerInfoResponse {
+    version: String,
+    max_file_size: u64,
+    chunk_size: u64,
+    allowed_file_types: Vec<String>,
+}
+
+impl ServerInfoResponse {
+    fn from_config(sync_config: &crate::config::SyncConfig) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            max_file_size: sync_config.max_file_size,
+            chunk_size: sync_config.chunk_size,
+            allowed_file_types: sync_config.allowed_file_types.clone(),
+        }
+    }
+}
+
 /// 健康检查服务
 pub struct HealthCheckService {
     pool: Arc<crate::db::DbPool>,
     redis_pool: Arc<crate::cache::RedisPool>,
     storage: Arc<crate::storage::StorageService>,
+    sync_config: crate::config::SyncConfig,
 }
 
 impl HealthCheckService {
@@ -39,11 +60,13 @@ impl HealthCheckService {
         pool: Arc<crate::db::DbPool>,
         redis_pool: Arc<crate::cache::RedisPool>,
         storage: Arc<crate::storage::StorageService>,
+        sync_config: crate::config::SyncConfig,
     ) -> Self {
         Self {
             pool,
             redis_pool,
             storage,
+            sync_config,
         }
     }
 
@@ -55,6 +78,7 @@ impl HealthCheckService {
         let app = Router::new()
             .route("/health", get(health_handler))
             .route("/ready", get(ready_handler))
+            .route("/server-info", get(server_info_handler))
             .with_state(Arc::new(self));
 
         // 启动服务器
@@ -147,3 +171,29 @@ async fn ready_handler(State(service): State<Arc<HealthCheckService>>) -> impl I
         }
     }
 }
+
+/// 服务器信息处理器
+async fn server_info_handler(State(service): State<Arc<HealthCheckService>>) -> impl IntoResponse {
+    Json(ServerInfoResponse::from_config(&service.sync_config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_info_reports_file_type_policy() {
+        let mut sync_config = crate::config::Config::from_env().unwrap().sync;
+        sync_config.allowed_file_types = vec!["md".to_string(), "json".to_string()];
+
+        let info = ServerInfoResponse::from_config(&sync_config);
+        assert_eq!(info.allowed_file_types, vec!["md", "json"]);
+        assert_eq!(info.max_file_size, sync_config.max_file_size);
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(
+            json["allowed_file_types"],
+            serde_json::json!(["md", "json"])
+        );
+    }
+}
@@ -1,14 +1,24 @@
 mod auth;
 mod cache;
+mod chunking;
 mod config;
+mod content_hash;
 mod db;
+mod delta;
+mod encryption;
+mod error;
 mod grpc;
 mod health;
 mod models;
 // proto 模块由 build.rs 在构建时生成到 src/proto/
 mod proto;
+mod retention;
+mod scan;
+mod search;
 mod server;
 mod storage;
+mod storage_backend;
+mod upload;
 
 use anyhow::Result;
 use server::GrpcServer;
@@ -49,6 +59,7 @@ async fn main() -> Result<()> {
         Arc::new(pool.clone()),
         Arc::new(redis_pool.clone()),
         Arc::new(storage.clone()),
+        config.sync.clone(),
     );
 
     let health_addr_for_log = health_check_addr.clone();
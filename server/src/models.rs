@@ -208,6 +208,7 @@ pub struct FileVersion {
     pub device_id: Uuid,
     pub parent_version_id: Option<Uuid>,
     pub is_deleted: bool,
+    pub file_mode: Option<i32>, // Unix 权限位，NULL 表示未记录
     pub created_at: DateTime<Utc>,
 }
 
@@ -236,6 +237,18 @@ pub enum SyncStatus {
     Error,
 }
 
+/// 设备同步偏差报告（设备版本相对于当前最新版本的落后程度）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DeviceDivergence {
+    pub device_id: Uuid,
+    pub device_name: String,
+    pub last_seen: DateTime<Utc>,
+    pub tracked_files: i64,   // 用户的文件总数
+    pub files_behind: i64,    // 设备版本落后于最新版本的文件数
+    pub versions_behind: i64, // 所有文件落后的版本数之和
+    pub max_lag: i64,         // 单个文件最大落后版本数
+}
+
 // ===== 冲突模型 =====
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -262,6 +275,16 @@ pub enum ConflictType {
     BinaryConflict,
 }
 
+impl ConflictType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictType::ModifyModify => "modify_modify",
+            ConflictType::ModifyDelete => "modify_delete",
+            ConflictType::BinaryConflict => "binary_conflict",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ResolutionStatus {
@@ -0,0 +1,410 @@
+use crate::db::{DeletedVersionRow, FileVersionRepository, PrunableVersionRow, SearchRepository};
+use crate::storage::StorageService;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeSet, HashSet};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// 文件版本保留策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// 每个文件至少保留的最新版本数（至少为 1，最新版本始终保留）
+    pub keep_versions: usize,
+    /// 该时长内创建的版本都保留
+    pub retention: Option<Duration>,
+}
+
+/// 清理结果
+#[derive(Debug, Clone, Default)]
+pub struct PruneReport {
+    /// 被清理（dry run 时为将被清理）的版本
+    pub pruned: Vec<PrunableVersionRow>,
+    /// 删除的存储对象数
+    pub deleted_objects: usize,
+    /// 删除的存储对象总大小
+    pub freed_bytes: i64,
+}
+
+/// 删除单个版本的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedVersion {
+    /// 版本的内容哈希
+    pub file_hash: String,
+    /// 版本的文件大小
+    pub file_size: i64,
+    /// 是否删除了存储对象（仍被其他版本引用时保留）
+    pub object_deleted: bool,
+}
+
+/// 文件版本的删除和内容引用计数（PostgreSQL 中由 file_versions 上的触发器维护）
+#[async_trait]
+pub trait VersionRefs: Send + Sync {
+    /// 删除版本并减少其内容的引用计数，版本不存在时返回 None
+    async fn delete_version(
+        &self,
+        user_id: &Uuid,
+        version_id: &Uuid,
+    ) -> Result<Option<DeletedVersionRow>>;
+
+    /// 删除引用计数已归零的内容的存储对象和内容索引，返回已删除对象的哈希
+    ///
+    /// 删除对象期间锁定计数记录，同一内容不会在删除过程中被新版本引用。
+    async fn delete_unreferenced(
+        &self,
+        storage: &StorageService,
+        user_id: &Uuid,
+        file_hashes: &[String],
+    ) -> Result<Vec<String>>;
+}
+
+#[async_trait]
+impl VersionRefs for sqlx::PgPool {
+    async fn delete_version(
+        &self,
+        user_id: &Uuid,
+        version_id: &Uuid,
+    ) -> Result<Option<DeletedVersionRow>> {
+        FileVersionRepository::delete_version(self, user_id, version_id).await
+    }
+
+    async fn delete_unreferenced(
+        &self,
+        storage: &StorageService,
+        user_id: &Uuid,
+        file_hashes: &[String],
+    ) -> Result<Vec<String>> {
+        let mut tx = self.begin().await?;
+        let hashes =
+            FileVersionRepository::lock_unreferenced(&mut tx, user_id, file_hashes).await?;
+
+        let mut deleted = Vec::with_capacity(hashes.len());
+        for hash in hashes {
+            match storage.delete_file(user_id, &hash).await {
+                Ok(()) => deleted.push(hash),
+                // 计数记录保留，下次清理时重试
+                Err(e) => warn!("Failed to delete object {}: {}", hash, e),
+            }
+        }
+
+        FileVersionRepository::delete_unreferenced(&mut tx, user_id, &deleted).await?;
+        tx.commit().await?;
+
+        if let Err(e) = SearchRepository::delete_content(self, user_id, &deleted).await {
+            warn!(
+                "Failed to delete content index for unreferenced objects: {}",
+                e
+            );
+        }
+        Ok(deleted)
+    }
+}
+
+/// 删除单个版本，内容的最后一个引用被删除时才删除存储对象
+///
+/// 内容按哈希去重，多个版本可能共享同一个存储对象，直接删除对象会破坏其他版本。
+pub async fn delete_version(
+    refs: &dyn VersionRefs,
+    storage: &StorageService,
+    user_id: &Uuid,
+    version_id: &Uuid,
+) -> Result<Option<DeletedVersion>> {
+    let Some(row) = refs.delete_version(user_id, version_id).await? else {
+        return Ok(None);
+    };
+
+    let object_deleted = !refs
+        .delete_unreferenced(storage, user_id, std::slice::from_ref(&row.file_hash))
+        .await?
+        .is_empty();
+
+    info!(
+        "Deleted version {} for user {} (object {} {})",
+        version_id,
+        user_id,
+        row.file_hash,
+        if object_deleted { "deleted" } else { "kept" }
+    );
+
+    Ok(Some(DeletedVersion {
+        file_hash: row.file_hash,
+        file_size: row.file_size,
+        object_deleted,
+    }))
+}
+
+/// 选出按保留策略可以清理的版本
+///
+/// versions 需按文件路径分组、组内按版本号降序排列（与 list_for_pruning 的顺序一致）。
+/// 仍被同步状态或冲突记录引用的版本不清理。
+pub fn select_prunable<'a>(
+    versions: &'a [PrunableVersionRow],
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Vec<&'a PrunableVersionRow> {
+    let keep_versions = policy.keep_versions.max(1);
+    let cutoff = policy.retention.map(|retention| now - retention);
+
+    versions
+        .chunk_by(|a, b| a.file_path == b.file_path)
+        .flat_map(|file_versions| file_versions.iter().skip(keep_versions))
+        .filter(|version| !version.pinned)
+        .filter(|version| cutoff.is_none_or(|cutoff| version.created_at < cutoff))
+        .collect()
+}
+
+/// 清理后不再被任何剩余版本引用的内容哈希（同一内容可能被多个版本共享）
+pub fn unreferenced_hashes(
+    pruned: &[&PrunableVersionRow],
+    versions: &[PrunableVersionRow],
+) -> Vec<String> {
+    let pruned_ids: HashSet<Uuid> = pruned.iter().map(|version| version.id).collect();
+    let remaining: HashSet<&str> = versions
+        .iter()
+        .filter(|version| !pruned_ids.contains(&version.id))
+        .map(|version| version.file_hash.as_str())
+        .collect();
+
+    pruned
+        .iter()
+        .map(|version| version.file_hash.as_str())
+        .filter(|hash| !remaining.contains(hash))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// 按保留策略清理用户的旧版本，并删除不再被引用的存储对象
+///
+/// 分块上传的块对象按块哈希跨版本共享，这里不做清理。
+pub async fn prune_versions(
+    pool: &sqlx::PgPool,
+    storage: &StorageService,
+    user_id: &Uuid,
+    policy: &RetentionPolicy,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let versions = FileVersionRepository::list_for_pruning(pool, user_id).await?;
+    let pruned = select_prunable(&versions, policy, Utc::now());
+    let hashes = unreferenced_hashes(&pruned, &versions);
+
+    let mut report = PruneReport {
+        pruned: pruned.iter().map(|version| (*version).clone()).collect(),
+        ..Default::default()
+    };
+    if dry_run || pruned.is_empty() {
+        return Ok(report);
+    }
+
+    let ids: Vec<Uuid> = pruned.iter().map(|version| version.id).collect();
+    let deleted = FileVersionRepository::delete_versions(pool, user_id, &ids).await?;
+
+    // 列出版本之后可能有新上传复用了相同内容，只删除引用计数已归零的对象
+    let unreferenced = pool.delete_unreferenced(storage, user_id, &hashes).await?;
+    report.deleted_objects = unreferenced.len();
+    report.freed_bytes = unreferenced
+        .iter()
+        .filter_map(|hash| pruned.iter().find(|version| &version.file_hash == hash))
+        .map(|version| version.file_size)
+        .sum();
+
+    info!(
+        "Pruned {} versions for user {}, deleted {} objects ({} bytes)",
+        deleted, user_id, report.deleted_objects, report.freed_bytes
+    );
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(
+        file_path: &str,
+        version_number: i32,
+        hash: &str,
+        age_days: i64,
+    ) -> PrunableVersionRow {
+        PrunableVersionRow {
+            id: Uuid::new_v4(),
+            file_path: file_path.to_string(),
+            version_number,
+            file_hash: hash.to_string(),
+            file_size: 10,
+            device_id: Uuid::nil(),
+            created_at: Utc::now() - Duration::days(age_days),
+            file_mode: None,
+            pinned: false,
+        }
+    }
+
+    fn numbers(pruned: &[&PrunableVersionRow]) -> Vec<(String, i32)> {
+        pruned
+            .iter()
+            .map(|version| (version.file_path.clone(), version.version_number))
+            .collect()
+    }
+
+    #[test]
+    fn test_keep_most_recent_versions_per_file() {
+        let mut versions = vec![
+            version("a.md", 4, "a4", 1),
+            version("a.md", 3, "a3", 2),
+            version("a.md", 2, "a2", 3),
+            version("a.md", 1, "a1", 4),
+            version("b.json", 1, "b1", 30),
+        ];
+        versions[3].pinned = true;
+
+        let policy = RetentionPolicy {
+            keep_versions: 2,
+            retention: None,
+        };
+        let pruned = select_prunable(&versions, &policy, Utc::now());
+        assert_eq!(numbers(&pruned), vec![("a.md".to_string(), 2)]);
+
+        // 保留窗口内的版本即使超出数量也保留，最新版本始终保留
+        let policy = RetentionPolicy {
+            keep_versions: 0,
+            retention: Some(Duration::days(3) - Duration::hours(1)),
+        };
+        let pruned = select_prunable(&versions, &policy, Utc::now());
+        assert_eq!(numbers(&pruned), vec![("a.md".to_string(), 2)]);
+    }
+
+    /// 内存中的版本表和引用计数（与数据库触发器的行为一致）
+    #[derive(Default)]
+    struct MemoryRefs {
+        versions: std::sync::Mutex<std::collections::HashMap<Uuid, String>>,
+        ref_counts: std::sync::Mutex<std::collections::HashMap<String, i32>>,
+    }
+
+    impl MemoryRefs {
+        fn add_version(&self, file_hash: &str) -> Uuid {
+            let id = Uuid::new_v4();
+            self.versions
+                .lock()
+                .unwrap()
+                .insert(id, file_hash.to_string());
+            *self
+                .ref_counts
+                .lock()
+                .unwrap()
+                .entry(file_hash.to_string())
+                .or_default() += 1;
+            id
+        }
+    }
+
+    #[async_trait]
+    impl VersionRefs for MemoryRefs {
+        async fn delete_version(
+            &self,
+            _user_id: &Uuid,
+            version_id: &Uuid,
+        ) -> Result<Option<DeletedVersionRow>> {
+            let Some(file_hash) = self.versions.lock().unwrap().remove(version_id) else {
+                return Ok(None);
+            };
+            *self.ref_counts.lock().unwrap().get_mut(&file_hash).unwrap() -= 1;
+            Ok(Some(DeletedVersionRow {
+                file_hash,
+                file_size: 10,
+            }))
+        }
+
+        async fn delete_unreferenced(
+            &self,
+            storage: &StorageService,
+            user_id: &Uuid,
+            file_hashes: &[String],
+        ) -> Result<Vec<String>> {
+            let released: Vec<String> = {
+                let ref_counts = self.ref_counts.lock().unwrap();
+                file_hashes
+                    .iter()
+                    .filter(|hash| ref_counts.get(*hash) == Some(&0))
+                    .cloned()
+                    .collect()
+            };
+            for hash in &released {
+                storage.delete_file(user_id, hash).await?;
+                self.ref_counts.lock().unwrap().remove(hash);
+            }
+            Ok(released)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_version_keeps_shared_object() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = crate::config::Config::from_env().unwrap();
+        config.minio.backend = "local".to_string();
+        config.minio.local_path = temp_dir.path().to_string_lossy().into_owned();
+        let storage = StorageService::from_config(&config).await.unwrap();
+
+        let user_id = Uuid::new_v4();
+        let data = b"shared content".to_vec();
+        let hash = StorageService::hash_file(&data);
+        storage
+            .store_content(&user_id, &hash, data.clone(), None)
+            .await
+            .unwrap();
+
+        // 两个版本共享同一个对象
+        let refs = MemoryRefs::default();
+        let v1 = refs.add_version(&hash);
+        let v2 = refs.add_version(&hash);
+
+        let deleted = delete_version(&refs, &storage, &user_id, &v1)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!deleted.object_deleted);
+        assert_eq!(storage.download_file(&user_id, &hash).await.unwrap(), data);
+
+        // 已删除的版本不能再次删除
+        assert!(delete_version(&refs, &storage, &user_id, &v1)
+            .await
+            .unwrap()
+            .is_none());
+
+        let deleted = delete_version(&refs, &storage, &user_id, &v2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(deleted.object_deleted);
+        assert!(!storage.file_exists(&user_id, &hash).await.unwrap());
+    }
+
+    #[test]
+    fn test_shared_content_is_not_deleted() {
+        // v2 与 v4 内容相同，清理 v2 不能删除仍被 v4 引用的对象
+        let versions = vec![
+            version("a.md", 4, "shared", 1),
+            version("a.md", 3, "a3", 2),
+            version("a.md", 2, "shared", 3),
+            version("a.md", 1, "a1", 4),
+            version("b.md", 1, "a1", 4),
+        ];
+        let policy = RetentionPolicy {
+            keep_versions: 2,
+            retention: None,
+        };
+
+        let pruned = select_prunable(&versions, &policy, Utc::now());
+        assert_eq!(pruned.len(), 2);
+        // a1 仍被 b.md 引用，shared 仍被 a.md v4 引用
+        assert!(unreferenced_hashes(&pruned, &versions).is_empty());
+
+        let policy = RetentionPolicy {
+            keep_versions: 1,
+            retention: None,
+        };
+        let pruned = select_prunable(&versions, &policy, Utc::now());
+        assert_eq!(unreferenced_hashes(&pruned, &versions), vec!["a3"]);
+    }
+}
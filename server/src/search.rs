@@ -0,0 +1,193 @@
+use crate::db::{SearchRepository, SearchRow};
+use anyhow::Result;
+use tracing::warn;
+use uuid::Uuid;
+
+/// 写入内容索引的最大文件大小（更大的文件只能按路径搜索）
+pub const MAX_INDEXED_SIZE: usize = 256 * 1024;
+
+/// 默认和最大返回结果数
+pub const DEFAULT_SEARCH_LIMIT: u32 = 50;
+pub const MAX_SEARCH_LIMIT: u32 = 500;
+
+/// 匹配行摘要的最大字符数
+const SNIPPET_MAX_CHARS: usize = 120;
+
+/// 可写入内容索引的文本（二进制、非 UTF-8 或过大的内容返回 None）
+pub fn indexable_text(content: &[u8]) -> Option<&str> {
+    if content.len() > MAX_INDEXED_SIZE || content.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+/// 转义 LIKE 模式中的通配符（与 `ESCAPE '\'` 配合使用）
+fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 路径前缀匹配模式
+pub fn prefix_pattern(query: &str) -> String {
+    format!("{}%", escape_like(query))
+}
+
+/// 内容子串匹配模式
+pub fn contains_pattern(query: &str) -> String {
+    format!("%{}%", escape_like(query))
+}
+
+/// 返回结果数（0 表示默认值）
+pub fn clamp_limit(limit: u32) -> u32 {
+    match limit {
+        0 => DEFAULT_SEARCH_LIMIT,
+        limit => limit.min(MAX_SEARCH_LIMIT),
+    }
+}
+
+/// 内容中第一个包含查询词（忽略大小写）的行，过长时截断
+pub fn snippet(content: &str, query: &str) -> Option<String> {
+    let query = query.to_lowercase();
+    let line = content
+        .lines()
+        .find(|line| line.to_lowercase().contains(&query))?
+        .trim();
+
+    if line.chars().count() <= SNIPPET_MAX_CHARS {
+        return Some(line.to_string());
+    }
+    let truncated: String = line.chars().take(SNIPPET_MAX_CHARS).collect();
+    Some(format!("{}…", truncated))
+}
+
+/// 将上传的文本内容写入索引（索引失败不影响上传）
+pub async fn index_content(pool: &sqlx::PgPool, user_id: &Uuid, file_hash: &str, text: &str) {
+    if let Err(e) = SearchRepository::index_content(pool, user_id, file_hash, text).await {
+        warn!("Failed to index content {}: {}", file_hash, e);
+    }
+}
+
+/// 搜索用户的文件：路径前缀匹配各文件的最新版本，include_content 时再匹配已索引文本内容的所有版本
+pub async fn search_files(
+    pool: &sqlx::PgPool,
+    user_id: &Uuid,
+    query: &str,
+    include_content: bool,
+    limit: u32,
+) -> Result<Vec<SearchRow>> {
+    let limit = clamp_limit(limit) as i64;
+
+    let mut rows =
+        SearchRepository::search_paths(pool, user_id, &prefix_pattern(query), limit).await?;
+    if include_content {
+        let remaining = limit - rows.len() as i64;
+        if remaining > 0 {
+            rows.extend(
+                SearchRepository::search_content(
+                    pool,
+                    user_id,
+                    &contains_pattern(query),
+                    remaining,
+                )
+                .await?,
+            );
+        }
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 与 Postgres `ILIKE ... ESCAPE '\'` 语义一致的简易匹配（仅用于测试模式转义）
+    fn ilike(value: &str, pattern: &str) -> bool {
+        fn matches(value: &[char], pattern: &[char]) -> bool {
+            match pattern.split_first() {
+                None => value.is_empty(),
+                Some(('%', rest)) => (0..=value.len()).any(|i| matches(&value[i..], rest)),
+                Some(('_', rest)) => !value.is_empty() && matches(&value[1..], rest),
+                Some(('\\', rest)) => {
+                    let (literal, rest) = rest.split_first().unwrap();
+                    value.first() == Some(literal) && matches(&value[1..], rest)
+                }
+                Some((c, rest)) => value.first() == Some(c) && matches(&value[1..], rest),
+            }
+        }
+        let value: Vec<char> = value.to_lowercase().chars().collect();
+        let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+        matches(&value, &pattern)
+    }
+
+    #[test]
+    fn test_path_prefix_search() {
+        let paths = [
+            "agents/code_review.md",
+            "agents/codeXreview.md",
+            "Agents/planner.md",
+            "commands/agents.md",
+            "settings.json",
+        ];
+        let search = |query: &str| -> Vec<&str> {
+            let pattern = prefix_pattern(query);
+            paths
+                .iter()
+                .copied()
+                .filter(|path| ilike(path, &pattern))
+                .collect()
+        };
+
+        assert_eq!(
+            search("agents/"),
+            vec![
+                "agents/code_review.md",
+                "agents/codeXreview.md",
+                "Agents/planner.md"
+            ]
+        );
+        // 下划线按字面匹配，不是单字符通配符
+        assert_eq!(search("agents/code_"), vec!["agents/code_review.md"]);
+        assert_eq!(search("100%"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_content_search_on_indexed_text_file() {
+        let content =
+            b"{\n  \"theme\": \"dark\",\n  \"mcpServers\": {\n    \"github\": {}\n  }\n}\n";
+        let text = indexable_text(content).unwrap();
+
+        assert!(ilike(text, &contains_pattern("mcpservers")));
+        assert!(!ilike(text, &contains_pattern("mcp_servers")));
+        assert_eq!(
+            snippet(text, "MCPSERVERS").as_deref(),
+            Some("\"mcpServers\": {")
+        );
+        assert_eq!(snippet(text, "missing"), None);
+
+        let long_line = "x".repeat(500);
+        let snippet = snippet(&long_line, "x").unwrap();
+        assert_eq!(snippet.chars().count(), SNIPPET_MAX_CHARS + 1);
+    }
+
+    #[test]
+    fn test_binary_and_large_files_are_not_indexed() {
+        assert!(indexable_text(b"PNG\x00\x01\x02").is_none());
+        assert!(indexable_text(&[0xff, 0xfe, 0x41]).is_none());
+        assert!(indexable_text(&vec![b'a'; MAX_INDEXED_SIZE + 1]).is_none());
+        assert_eq!(indexable_text(b"# CLAUDE.md"), Some("# CLAUDE.md"));
+    }
+
+    #[test]
+    fn test_clamp_limit() {
+        assert_eq!(clamp_limit(0), DEFAULT_SEARCH_LIMIT);
+        assert_eq!(clamp_limit(10), 10);
+        assert_eq!(clamp_limit(10_000), MAX_SEARCH_LIMIT);
+    }
+}
@@ -32,7 +32,7 @@ impl GrpcServer {
 
         // 连接 Redis
         let redis_pool = RedisPool::from_config(&config.redis.url).await?;
-        let cache = Cache::new(redis_pool.inner().clone());
+        let cache = Cache::new(redis_pool.inner().clone(), &config.redis);
 
         // 连接 MinIO
         let storage = StorageService::from_config(&config).await?;
@@ -71,10 +71,15 @@ impl GrpcServer {
         let auth_service =
             AuthGrpcService::new(self.pool.clone(), self.cache.clone(), self.config.clone());
 
-        let device_service = DeviceGrpcService::new(self.pool.clone());
+        let device_service =
+            DeviceGrpcService::new(self.pool.clone(), self.cache.clone(), self.config.clone());
 
-        let sync_service =
-            FileSyncGrpcService::new(self.pool.clone(), self.cache.clone(), self.storage);
+        let sync_service = FileSyncGrpcService::new(
+            self.pool.clone(),
+            self.cache.clone(),
+            self.storage,
+            self.config.clone(),
+        );
 
         let notification_service = NotificationGrpcService::new(self.pool, self.cache);
 
@@ -1,54 +1,62 @@
+use crate::chunking::{self, ChunkRef, ChunkerConfig};
 use crate::config::Config;
+use crate::content_hash::HashAlgorithm;
+use crate::encryption::EnvelopeEncryption;
+use crate::scan::ContentScanHook;
+use crate::storage_backend::{backend_from_config, StorageBackend};
 use anyhow::Result;
-use s3::bucket::Bucket;
-use s3::creds::Credentials;
 use sha2::{Digest, Sha256};
-use tracing::{debug, info};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-/// MinIO/S3 对象存储服务
+/// 未知类型对象的 MIME 类型
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// 对象存储服务（后端由 STORAGE_BACKEND 选择）
 #[derive(Clone)]
 pub struct StorageService {
-    bucket: Bucket,
-    bucket_name: String,
+    backend: Arc<dyn StorageBackend>,
+    encryption: Option<EnvelopeEncryption>,
+    data_keys: Arc<RwLock<HashMap<Uuid, [u8; 32]>>>,
+    content_scan: Option<Arc<ContentScanHook>>,
 }
 
 impl StorageService {
     /// 从配置创建存储服务
     pub async fn from_config(config: &Config) -> Result<Self> {
-        info!("Connecting to MinIO/S3 storage...");
-
-        // 配置 S3 凭据
-        let credentials = Credentials::new(
-            Some(&config.minio.access_key),
-            Some(&config.minio.secret_key),
-            None,
-            None,
-            None,
-        )?;
-
-        // 创建 Bucket 配置
-        let region = s3::Region::Custom {
-            region: config.minio.region.clone(),
-            endpoint: config.minio.endpoint.clone(),
-        };
+        let backend = backend_from_config(&config.minio).await?;
 
-        let bucket = Bucket::new(&config.minio.bucket, region, credentials)?.with_path_style();
-
-        let bucket_name = config.minio.bucket.clone();
+        let encryption = if config.minio.encryption_enabled {
+            info!("✓ Storage encryption at rest enabled");
+            Some(EnvelopeEncryption::new(&config.minio.encryption_key))
+        } else {
+            None
+        };
 
-        // 验证连接
-        // 注意：rust-s3 没有直接列出 buckets 的方法，我们通过尝试访问来验证
-        info!("✓ Storage configured successfully");
+        let content_scan = ContentScanHook::from_config(&config.sync)?.map(Arc::new);
+        if content_scan.is_some() {
+            info!(
+                "✓ Upload content scanning enabled: {} ({})",
+                config.sync.content_scanners.join(", "),
+                config.sync.content_scan_action
+            );
+        }
 
         Ok(Self {
-            bucket,
-            bucket_name,
+            backend,
+            encryption,
+            data_keys: Arc::new(RwLock::new(HashMap::new())),
+            content_scan,
         })
     }
 
     /// ===== 文件操作 =====
     /// 上传文件
+    ///
+    /// 启用内容扫描时先扫描明文，被拒绝时返回 `ContentRejected` 错误。
     pub async fn upload_file(
         &self,
         user_id: &Uuid,
@@ -65,29 +73,57 @@ impl StorageService {
             data.len()
         );
 
-        let content_type = content_type.unwrap_or_else(|| "application/octet-stream".to_string());
-        self.bucket
-            .put_object_with_content_type(&storage_path.full_path(), &data, &content_type)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to upload file: {}", e))?;
+        if let Some(scan) = &self.content_scan {
+            let findings = scan.check(&data)?;
+            if !findings.is_empty() {
+                warn!(
+                    "Upload flagged by content scan: user_id={}, hash={}, findings={:?}",
+                    user_id, file_hash, findings
+                );
+            }
+        }
+
+        let data = self.encrypt_for_user(user_id, data).await?;
+
+        let content_type = content_type.unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string());
+        self.backend
+            .upload(&storage_path.full_path(), &data, &content_type)
+            .await?;
 
         debug!("✓ File uploaded successfully");
 
         Ok(storage_path)
     }
 
+    /// 保存内容（同一用户已有相同哈希的对象时不再重复写入），返回是否实际写入
+    pub async fn store_content(
+        &self,
+        user_id: &Uuid,
+        file_hash: &str,
+        data: Vec<u8>,
+        content_type: Option<String>,
+    ) -> Result<bool> {
+        if self.file_exists(user_id, file_hash).await? {
+            debug!(
+                "Content already stored, skipping upload: user_id={}, hash={}",
+                user_id, file_hash
+            );
+            return Ok(false);
+        }
+
+        self.upload_file(user_id, file_hash, data, content_type)
+            .await?;
+        Ok(true)
+    }
+
     /// 下载文件
     pub async fn download_file(&self, user_id: &Uuid, file_hash: &str) -> Result<Vec<u8>> {
         let storage_path = self.generate_storage_path(user_id, file_hash);
 
         debug!("Downloading file: user_id={}, hash={}", user_id, file_hash);
 
-        let response = self
-            .bucket
-            .get_object(&storage_path.full_path())
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to download file: {}", e))?;
-        let data = response.bytes().to_vec();
+        let data = self.backend.download(&storage_path.full_path()).await?;
+        let data = self.decrypt_for_user(user_id, data).await?;
 
         debug!("✓ File downloaded successfully: {} bytes", data.len());
 
@@ -100,10 +136,7 @@ impl StorageService {
 
         debug!("Deleting file: user_id={}, hash={}", user_id, file_hash);
 
-        self.bucket
-            .delete_object(&storage_path.full_path())
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to delete file: {}", e))?;
+        self.backend.delete(&storage_path.full_path()).await?;
 
         debug!("✓ File deleted successfully");
 
@@ -113,18 +146,158 @@ impl StorageService {
     /// 检查文件是否存在
     pub async fn file_exists(&self, user_id: &Uuid, file_hash: &str) -> Result<bool> {
         let storage_path = self.generate_storage_path(user_id, file_hash);
+        self.backend.exists(&storage_path.full_path()).await
+    }
+
+    /// 上传时记录的 MIME 类型（未记录时为 application/octet-stream）
+    pub async fn content_type(&self, user_id: &Uuid, file_hash: &str) -> Result<String> {
+        let storage_path = self.generate_storage_path(user_id, file_hash);
+        Ok(self
+            .backend
+            .content_type(&storage_path.full_path())
+            .await?
+            .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string()))
+    }
+
+    /// ===== 分块存储 =====
+    /// 按内容定义分块上传文件，已存在的分块不会重复上传
+    pub async fn upload_chunked(&self, user_id: &Uuid, data: &[u8]) -> Result<ChunkedUploadResult> {
+        let chunks = chunking::chunk_data(data, &ChunkerConfig::default());
+
+        // 查询哪些分块已存在
+        let mut known = HashSet::new();
+        for chunk in &chunks {
+            if !known.contains(&chunk.hash)
+                && self
+                    .backend
+                    .exists(&StoragePath::chunk_path(user_id, &chunk.hash))
+                    .await?
+            {
+                known.insert(chunk.hash.clone());
+            }
+        }
 
-        match self.bucket.head_object(&storage_path.full_path()).await {
-            Ok(_result) => Ok(true),
-            Err(e) => {
-                let err_str = e.to_string();
-                if err_str.contains("404") || err_str.contains("Not Found") {
-                    Ok(false)
-                } else {
-                    Err(anyhow::anyhow!("Failed to check file existence: {}", e))
-                }
+        let missing = chunking::missing_chunks(&chunks, &known);
+        for chunk in &missing {
+            let start = chunk.offset as usize;
+            let chunk_data = data[start..start + chunk.size as usize].to_vec();
+            let chunk_data = self.encrypt_for_user(user_id, chunk_data).await?;
+
+            self.backend
+                .upload(
+                    &StoragePath::chunk_path(user_id, &chunk.hash),
+                    &chunk_data,
+                    "application/octet-stream",
+                )
+                .await?;
+        }
+
+        debug!(
+            "✓ Chunked upload: {} chunks, {} uploaded, {} reused",
+            chunks.len(),
+            missing.len(),
+            chunks.len() - missing.len()
+        );
+
+        Ok(ChunkedUploadResult {
+            uploaded_chunks: missing.len(),
+            uploaded_bytes: missing.iter().map(|c| c.size as u64).sum(),
+            manifest: chunks,
+        })
+    }
+
+    /// 按分块清单下载并重组文件
+    pub async fn download_chunked(&self, user_id: &Uuid, manifest: &[ChunkRef]) -> Result<Vec<u8>> {
+        let total_size: u64 = manifest.iter().map(|c| c.size as u64).sum();
+        let mut data = Vec::with_capacity(total_size as usize);
+
+        for chunk in manifest {
+            let chunk_data = self
+                .backend
+                .download(&StoragePath::chunk_path(user_id, &chunk.hash))
+                .await?;
+            let chunk_data = self.decrypt_for_user(user_id, chunk_data).await?;
+
+            if chunking::hash_chunk(&chunk_data) != chunk.hash {
+                return Err(anyhow::anyhow!("Chunk hash mismatch: {}", chunk.hash));
+            }
+
+            data.extend_from_slice(&chunk_data);
+        }
+
+        debug!(
+            "✓ Chunked download: {} chunks, {} bytes",
+            manifest.len(),
+            data.len()
+        );
+
+        Ok(data)
+    }
+
+    /// 启用加密时使用用户数据密钥加密
+    async fn encrypt_for_user(&self, user_id: &Uuid, data: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.encryption {
+            Some(encryption) => {
+                let data_key = self.user_data_key(encryption, user_id).await?;
+                EnvelopeEncryption::encrypt(&data_key, &data)
             }
+            None => Ok(data),
+        }
+    }
+
+    /// 加密对象透明解密（未加密的历史对象原样返回）
+    async fn decrypt_for_user(&self, user_id: &Uuid, data: Vec<u8>) -> Result<Vec<u8>> {
+        if !EnvelopeEncryption::is_encrypted(&data) {
+            return Ok(data);
+        }
+
+        let encryption = self.encryption.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("File is encrypted but storage encryption is disabled")
+        })?;
+        let data_key = self.user_data_key(encryption, user_id).await?;
+        EnvelopeEncryption::decrypt(&data_key, &data)
+    }
+
+    /// 获取用户的数据密钥，不存在时生成并以包装形式保存到存储中
+    async fn user_data_key(
+        &self,
+        encryption: &EnvelopeEncryption,
+        user_id: &Uuid,
+    ) -> Result<[u8; 32]> {
+        if let Some(key) = self.data_keys.read().await.get(user_id) {
+            return Ok(*key);
+        }
+
+        let mut data_keys = self.data_keys.write().await;
+        if let Some(key) = data_keys.get(user_id) {
+            return Ok(*key);
         }
+
+        let key_path = StoragePath::data_key_path(user_id);
+        let data_key = if self.backend.exists(&key_path).await? {
+            let wrapped = self
+                .backend
+                .download(&key_path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to load data key: {:#}", e))?;
+            encryption.unwrap_key(&wrapped)?
+        } else {
+            // 密钥不存在时才生成，避免覆盖已有密钥导致数据无法解密
+            debug!("Generating data key for user {}", user_id);
+            let data_key = EnvelopeEncryption::generate_data_key();
+            self.backend
+                .upload(
+                    &key_path,
+                    &encryption.wrap_key(&data_key)?,
+                    "application/octet-stream",
+                )
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to store data key: {:#}", e))?;
+            data_key
+        };
+
+        data_keys.insert(*user_id, data_key);
+        Ok(data_key)
     }
 
     /// ===== 辅助方法 =====
@@ -140,13 +313,28 @@ impl StorageService {
         format!("{:x}", hasher.finalize())
     }
 
-    /// 验证文件哈希
+    /// 验证文件哈希（按期望哈希自身的算法计算，支持 SHA-256 和 `blake3:` 前缀的 BLAKE3）
     pub fn verify_hash(data: &[u8], expected_hash: &str) -> bool {
-        let actual_hash = Self::hash_file(data);
+        let actual_hash = HashAlgorithm::of(expected_hash).hash(data);
         actual_hash == expected_hash
     }
 }
 
+/// 规范化客户端声明的 MIME 类型
+///
+/// 只保留 `type/subtype` 部分并转为小写，格式无效或为空时返回 None（按未知类型保存）。
+pub fn normalize_content_type(value: &str) -> Option<String> {
+    let essence = value.split(';').next()?.trim().to_ascii_lowercase();
+    let is_token = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    let (kind, subtype) = essence.split_once('/')?;
+    (essence.len() <= 127 && is_token(kind) && is_token(subtype)).then_some(essence)
+}
+
 /// 存储路径
 #[derive(Debug, Clone)]
 pub struct StoragePath {
@@ -168,6 +356,16 @@ impl StoragePath {
         format!("users/{}/files/{}.data", self.user_id, self.file_hash)
     }
 
+    /// 分块路径（按用户隔离，分块哈希去重）
+    pub fn chunk_path(user_id: &Uuid, chunk_hash: &str) -> String {
+        format!("users/{}/chunks/{}.chunk", user_id, chunk_hash)
+    }
+
+    /// 用户数据密钥（已包装）路径
+    pub fn data_key_path(user_id: &Uuid) -> String {
+        format!("users/{}/keys/data.key", user_id)
+    }
+
     /// 版本元数据路径
     pub fn version_metadata_path(&self, version_id: &Uuid) -> String {
         format!("users/{}/versions/{}.meta", self.user_id, version_id)
@@ -182,6 +380,17 @@ impl StoragePath {
     }
 }
 
+/// 分块上传结果
+#[derive(Debug, Clone)]
+pub struct ChunkedUploadResult {
+    /// 文件的有序分块清单
+    pub manifest: Vec<ChunkRef>,
+    /// 实际上传的分块数
+    pub uploaded_chunks: usize,
+    /// 实际上传的字节数
+    pub uploaded_bytes: u64,
+}
+
 /// 分块上传管理器
 pub struct ChunkedUpload {
     storage: StorageService,
@@ -259,6 +468,11 @@ mod tests {
         let hash = StorageService::hash_file(data);
         assert!(StorageService::verify_hash(data, &hash));
         assert!(!StorageService::verify_hash(data, "wrong_hash"));
+
+        let blake3 = HashAlgorithm::Blake3.hash(data);
+        assert!(StorageService::verify_hash(data, &blake3));
+        assert!(!StorageService::verify_hash(b"other data", &blake3));
+        assert!(!StorageService::verify_hash(data, "blake3:wrong_hash"));
     }
 
     #[test]
@@ -270,4 +484,155 @@ mod tests {
         assert!(path.full_path().contains(&user_id.to_string()));
         assert!(path.full_path().contains(file_hash));
     }
+
+    #[test]
+    fn test_normalize_content_type() {
+        assert_eq!(
+            normalize_content_type("text/markdown").as_deref(),
+            Some("text/markdown")
+        );
+        assert_eq!(
+            normalize_content_type(" Application/JSON; charset=utf-8").as_deref(),
+            Some("application/json")
+        );
+        assert_eq!(
+            normalize_content_type("image/vnd.microsoft.icon").as_deref(),
+            Some("image/vnd.microsoft.icon")
+        );
+        for invalid in [
+            "",
+            "text",
+            "text/",
+            "/plain",
+            "text/plain\r\nX-Evil: 1",
+            "a/b/c",
+        ] {
+            assert_eq!(normalize_content_type(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_backend_storage_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::from_env().unwrap();
+        config.minio.backend = "local".to_string();
+        config.minio.local_path = temp_dir.path().to_string_lossy().into_owned();
+        config.minio.encryption_enabled = true;
+        config.minio.encryption_key = "k".repeat(32);
+
+        let storage = StorageService::from_config(&config).await.unwrap();
+        let user_id = Uuid::new_v4();
+        let data = b"{\"theme\": \"dark\"}".to_vec();
+        let hash = StorageService::hash_file(&data);
+
+        assert!(storage
+            .store_content(
+                &user_id,
+                &hash,
+                data.clone(),
+                Some("application/json".to_string())
+            )
+            .await
+            .unwrap());
+        assert!(!storage
+            .store_content(&user_id, &hash, data.clone(), None)
+            .await
+            .unwrap());
+        // MIME 类型随对象保存，重复写入不会覆盖
+        assert_eq!(
+            storage.content_type(&user_id, &hash).await.unwrap(),
+            "application/json"
+        );
+
+        // 磁盘上保存的是密文，下载时透明解密
+        let path = StoragePath::new(&user_id, &hash);
+        let stored = std::fs::read(temp_dir.path().join(path.full_path())).unwrap();
+        assert_ne!(stored, data);
+        assert_eq!(storage.download_file(&user_id, &hash).await.unwrap(), data);
+
+        let large: Vec<u8> = (0..256 * 1024u32).map(|i| (i * 31 % 251) as u8).collect();
+        let result = storage.upload_chunked(&user_id, &large).await.unwrap();
+        assert_eq!(
+            storage
+                .download_chunked(&user_id, &result.manifest)
+                .await
+                .unwrap(),
+            large
+        );
+
+        storage.delete_file(&user_id, &hash).await.unwrap();
+        assert!(!storage.file_exists(&user_id, &hash).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 MinIO 连接
+    async fn test_store_content_writes_once() {
+        let config = Config::from_env().unwrap();
+        let storage = StorageService::from_config(&config).await.unwrap();
+        let user_id = Uuid::new_v4();
+        let data = b"same content".to_vec();
+        let hash = StorageService::hash_file(&data);
+
+        assert!(storage
+            .store_content(&user_id, &hash, data.clone(), None)
+            .await
+            .unwrap());
+        assert!(!storage
+            .store_content(&user_id, &hash, data.clone(), None)
+            .await
+            .unwrap());
+        assert_eq!(storage.download_file(&user_id, &hash).await.unwrap(), data);
+
+        storage.delete_file(&user_id, &hash).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 MinIO 连接
+    async fn test_encrypted_upload_round_trip() {
+        let mut config = Config::from_env().unwrap();
+        config.minio.encryption_enabled = true;
+        config.minio.encryption_key = "k".repeat(32);
+
+        let storage = StorageService::from_config(&config).await.unwrap();
+        let user_id = Uuid::new_v4();
+        let data = b"{\"theme\": \"dark\"}".to_vec();
+        let file_hash = StorageService::hash_file(&data);
+
+        let path = storage
+            .upload_file(&user_id, &file_hash, data.clone(), None)
+            .await
+            .unwrap();
+
+        // 存储桶中保存的是密文
+        let stored = storage.backend.download(&path.full_path()).await.unwrap();
+        assert_ne!(stored, data);
+
+        // 下载时透明解密
+        let downloaded = storage.download_file(&user_id, &file_hash).await.unwrap();
+        assert_eq!(downloaded, data);
+    }
+
+    #[tokio::test]
+    #[ignore] // 需要 MinIO 连接
+    async fn test_chunked_upload_dedup() {
+        let config = Config::from_env().unwrap();
+        let storage = StorageService::from_config(&config).await.unwrap();
+        let user_id = Uuid::new_v4();
+
+        let original: Vec<u8> = (0..256 * 1024u32).map(|i| (i * 31 % 251) as u8).collect();
+        let first = storage.upload_chunked(&user_id, &original).await.unwrap();
+        assert_eq!(first.uploaded_chunks, first.manifest.len());
+
+        // 追加内容后只上传尾部分块
+        let mut appended = original.clone();
+        appended.extend(b"appended line\n".repeat(100));
+        let second = storage.upload_chunked(&user_id, &appended).await.unwrap();
+        assert!(second.uploaded_chunks < second.manifest.len());
+
+        let downloaded = storage
+            .download_chunked(&user_id, &second.manifest)
+            .await
+            .unwrap();
+        assert_eq!(downloaded, appended);
+    }
 }
@@ -0,0 +1,335 @@
+use crate::config::MinioConfig;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use s3::bucket::Bucket;
+use s3::creds::Credentials;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+use uuid::Uuid;
+
+/// 可选的存储后端（STORAGE_BACKEND）
+pub const STORAGE_BACKENDS: [&str; 2] = [S3Backend::NAME, LocalBackend::NAME];
+
+/// 对象存储后端
+///
+/// 只负责按键读写原始字节；加密、内容扫描和分块由 `StorageService` 在其上实现。
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// 写入对象（已存在时覆盖）
+    async fn upload(&self, key: &str, data: &[u8], content_type: &str) -> Result<()>;
+
+    /// 读取对象
+    async fn download(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// 删除对象（不存在时不报错）
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// 对象是否存在
+    async fn exists(&self, key: &str) -> Result<bool>;
+
+    /// 写入对象时记录的 MIME 类型（对象不存在或未记录时返回 None）
+    async fn content_type(&self, key: &str) -> Result<Option<String>>;
+}
+
+/// 按配置创建存储后端
+pub async fn backend_from_config(config: &MinioConfig) -> Result<Arc<dyn StorageBackend>> {
+    match config.backend.as_str() {
+        S3Backend::NAME => Ok(Arc::new(S3Backend::from_config(config)?)),
+        LocalBackend::NAME => Ok(Arc::new(LocalBackend::new(&config.local_path).await?)),
+        other => Err(anyhow::anyhow!("Unknown storage backend: {}", other)),
+    }
+}
+
+/// MinIO/S3 存储后端
+pub struct S3Backend {
+    bucket: Bucket,
+}
+
+impl S3Backend {
+    pub const NAME: &'static str = "s3";
+
+    pub fn from_config(config: &MinioConfig) -> Result<Self> {
+        info!("Connecting to MinIO/S3 storage...");
+
+        // 配置 S3 凭据
+        let credentials = Credentials::new(
+            Some(&config.access_key),
+            Some(&config.secret_key),
+            None,
+            None,
+            None,
+        )?;
+
+        // 创建 Bucket 配置
+        let region = s3::Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.clone(),
+        };
+
+        let bucket = Bucket::new(&config.bucket, region, credentials)?.with_path_style();
+
+        // 验证连接
+        // 注意：rust-s3 没有直接列出 buckets 的方法，我们通过尝试访问来验证
+        info!(
+            "✓ Storage configured successfully: bucket {}",
+            config.bucket
+        );
+
+        Ok(Self { bucket })
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Backend {
+    async fn upload(&self, key: &str, data: &[u8], content_type: &str) -> Result<()> {
+        self.bucket
+            .put_object_with_content_type(key, data, content_type)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to upload object {}: {}", key, e))?;
+        Ok(())
+    }
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>> {
+        let response = self
+            .bucket
+            .get_object(key)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to download object {}: {}", key, e))?;
+        if response.status_code() != 200 {
+            return Err(anyhow::anyhow!(
+                "Failed to download object {}: HTTP {}",
+                key,
+                response.status_code()
+            ));
+        }
+        Ok(response.bytes().to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.bucket
+            .delete_object(key)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to delete object {}: {}", key, e))?;
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.bucket.head_object(key).await {
+            Ok(_result) => Ok(true),
+            Err(e) => {
+                let err_str = e.to_string();
+                if err_str.contains("404") || err_str.contains("Not Found") {
+                    Ok(false)
+                } else {
+                    Err(anyhow::anyhow!("Failed to check file existence: {}", e))
+                }
+            }
+        }
+    }
+
+    async fn content_type(&self, key: &str) -> Result<Option<String>> {
+        match self.bucket.head_object(key).await {
+            Ok((result, _)) => Ok(result.content_type),
+            Err(e) => {
+                let err_str = e.to_string();
+                if err_str.contains("404") || err_str.contains("Not Found") {
+                    Ok(None)
+                } else {
+                    Err(anyhow::anyhow!(
+                        "Failed to read content type of object {}: {}",
+                        key,
+                        e
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// 本地文件系统存储后端（开发和单机部署）
+///
+/// 对象键映射为根目录下的相对路径，写入先落到临时文件再重命名，读取不会看到写了一半的对象。
+/// 对象的 MIME 类型保存在 `.content-types/` 下与对象键同名的文件中。
+pub struct LocalBackend {
+    root: PathBuf,
+}
+
+impl LocalBackend {
+    pub const NAME: &'static str = "local";
+
+    /// 保存 MIME 类型的目录（对象键不能使用此目录）
+    const CONTENT_TYPE_DIR: &'static str = ".content-types";
+
+    /// 创建后端，根目录不存在时自动创建
+    pub async fn new(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        tokio::fs::create_dir_all(&root)
+            .await
+            .with_context(|| format!("Failed to create storage directory {:?}", root))?;
+        info!("✓ Local storage configured successfully: {:?}", root);
+        Ok(Self { root })
+    }
+
+    /// 对象键对应的文件路径（拒绝绝对路径和 `..`，对象不能写到根目录之外）
+    fn object_path(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && !relative.starts_with(Self::CONTENT_TYPE_DIR)
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !valid {
+            return Err(anyhow::anyhow!("Invalid storage key: {}", key));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    /// 对象键对应的 MIME 类型文件路径
+    fn content_type_path(&self, key: &str) -> Result<PathBuf> {
+        self.object_path(key)?;
+        Ok(self.root.join(Self::CONTENT_TYPE_DIR).join(key))
+    }
+}
+
+/// 先写入同目录下的临时文件再重命名
+async fn write_file_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut temp_path = path.to_path_buf().into_os_string();
+    temp_path.push(format!(".{}.tmp", Uuid::new_v4()));
+    let temp_path = PathBuf::from(temp_path);
+    tokio::fs::write(&temp_path, data).await?;
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 删除文件（不存在时不报错）
+async fn remove_file_if_exists(path: &Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalBackend {
+    async fn upload(&self, key: &str, data: &[u8], content_type: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        // 先写 MIME 类型，读到对象时类型一定已经写入
+        write_file_atomic(&self.content_type_path(key)?, content_type.as_bytes())
+            .await
+            .with_context(|| format!("Failed to upload object {}", key))?;
+        write_file_atomic(&path, data)
+            .await
+            .with_context(|| format!("Failed to upload object {}", key))
+    }
+
+    async fn download(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.object_path(key)?;
+        tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("Failed to download object {}", key))
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.object_path(key)?;
+        remove_file_if_exists(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to delete object {}: {}", key, e))?;
+        remove_file_if_exists(&self.content_type_path(key)?)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to delete object {}: {}", key, e))
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool> {
+        let path = self.object_path(key)?;
+        tokio::fs::try_exists(&path)
+            .await
+            .with_context(|| format!("Failed to check file existence: {}", key))
+    }
+
+    async fn content_type(&self, key: &str) -> Result<Option<String>> {
+        if !self.exists(key).await? {
+            return Ok(None);
+        }
+        match tokio::fs::read_to_string(self.content_type_path(key)?).await {
+            Ok(content_type) => Ok(Some(content_type)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to read content type of object {}: {}",
+                key,
+                e
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_backend_round_trip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backend = LocalBackend::new(temp_dir.path().join("storage"))
+            .await
+            .unwrap();
+        let key = "users/u1/files/abc.data";
+
+        assert!(!backend.exists(key).await.unwrap());
+        assert!(backend.download(key).await.is_err());
+
+        backend
+            .upload(key, b"first", "application/octet-stream")
+            .await
+            .unwrap();
+        assert!(backend.exists(key).await.unwrap());
+        assert_eq!(backend.download(key).await.unwrap(), b"first");
+
+        // 覆盖写入，不残留临时文件
+        backend
+            .upload(key, b"second", "application/octet-stream")
+            .await
+            .unwrap();
+        assert_eq!(backend.download(key).await.unwrap(), b"second");
+        let dir = temp_dir.path().join("storage/users/u1/files");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        assert_eq!(
+            backend.content_type(key).await.unwrap().as_deref(),
+            Some("application/octet-stream")
+        );
+
+        backend.delete(key).await.unwrap();
+        assert!(!backend.exists(key).await.unwrap());
+        assert_eq!(backend.content_type(key).await.unwrap(), None);
+        // 删除不存在的对象不报错
+        backend.delete(key).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_local_backend_rejects_keys_outside_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let backend = LocalBackend::new(temp_dir.path().join("storage"))
+            .await
+            .unwrap();
+
+        for key in [
+            "../escape.data",
+            "/etc/passwd",
+            "users/../../escape",
+            "",
+            ".content-types/users/u1/files/abc.data",
+        ] {
+            assert!(backend.upload(key, b"x", "text/plain").await.is_err());
+            assert!(backend.exists(key).await.is_err());
+        }
+        assert!(!temp_dir.path().join("escape.data").exists());
+    }
+}
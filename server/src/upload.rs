@@ -0,0 +1,180 @@
+use crate::content_hash::{ContentHasher, HashAlgorithm};
+use crate::error::ServiceError;
+use crate::proto::claude_sync::FileChunk;
+use std::io::SeekFrom;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// 上传内容的临时文件
+///
+/// 分块到达时立即写入临时文件并按声明哈希的算法累计哈希，不在内存中缓存整个上传流。
+/// 收到的字节数一旦超过声明的 file_size 就拒绝上传，结束时要求收到的字节数与声明一致。
+pub struct UploadSpool {
+    file: File,
+    hasher: ContentHasher,
+    declared_size: u64,
+    received: u64,
+}
+
+impl UploadSpool {
+    /// 为声明大小为 declared_size 的上传创建临时文件（进程退出或丢弃时自动删除）
+    pub fn new(declared_size: u64, algorithm: HashAlgorithm) -> Result<Self, ServiceError> {
+        let file = tempfile::tempfile().map_err(|e| {
+            ServiceError::internal(format!("Failed to create upload spool file: {}", e))
+        })?;
+        Ok(Self {
+            file: File::from_std(file),
+            hasher: algorithm.hasher(),
+            declared_size,
+            received: 0,
+        })
+    }
+
+    /// 写入一个分块，偏移量不连续或超过声明大小时返回错误
+    pub async fn write_chunk(&mut self, chunk: &FileChunk) -> Result<(), ServiceError> {
+        if chunk.offset != self.received as i64 {
+            return Err(ServiceError::invalid_argument(format!(
+                "Chunk {} has offset {}, expected {}",
+                chunk.chunk_number, chunk.offset, self.received
+            )));
+        }
+
+        let received = self.received + chunk.data.len() as u64;
+        if received > self.declared_size {
+            return Err(ServiceError::invalid_argument(format!(
+                "Upload exceeds declared size: received {} of {} bytes",
+                received, self.declared_size
+            )));
+        }
+
+        self.file
+            .write_all(&chunk.data)
+            .await
+            .map_err(|e| ServiceError::internal(format!("Failed to write upload spool: {}", e)))?;
+        self.hasher.update(&chunk.data);
+        self.received = received;
+        Ok(())
+    }
+
+    /// 确认收到了声明的全部字节且内容哈希一致，返回完整内容
+    pub async fn finish(mut self, expected_hash: &str) -> Result<Vec<u8>, ServiceError> {
+        if self.received != self.declared_size {
+            return Err(ServiceError::data_loss(format!(
+                "Upload incomplete: received {} of {} bytes",
+                self.received, self.declared_size
+            )));
+        }
+        if self.hasher.finalize() != expected_hash {
+            return Err(ServiceError::data_loss(format!(
+                "Uploaded content does not match hash {}",
+                expected_hash
+            )));
+        }
+
+        let read_error = |e: std::io::Error| {
+            ServiceError::internal(format!("Failed to read upload spool: {}", e))
+        };
+        let mut content = Vec::with_capacity(self.received as usize);
+        self.file.flush().await.map_err(read_error)?;
+        self.file
+            .seek(SeekFrom::Start(0))
+            .await
+            .map_err(read_error)?;
+        self.file
+            .read_to_end(&mut content)
+            .await
+            .map_err(read_error)?;
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::StorageService;
+    use tonic::Code;
+
+    fn chunks(content: &[u8], chunk_size: usize) -> Vec<FileChunk> {
+        content
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, data)| FileChunk {
+                chunk_number: i as i64,
+                data: data.to_vec(),
+                offset: (i * chunk_size) as i64,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_spooled_upload_round_trip() {
+        let content = b"# CLAUDE.md\n\nAlways run the tests.\n".repeat(10);
+        let mut spool = UploadSpool::new(content.len() as u64, HashAlgorithm::Sha256).unwrap();
+        for chunk in chunks(&content, 64) {
+            spool.write_chunk(&chunk).await.unwrap();
+        }
+        assert_eq!(spool.received, content.len() as u64);
+
+        let hash = StorageService::hash_file(&content);
+        assert_eq!(spool.finish(&hash).await.unwrap(), content);
+    }
+
+    #[tokio::test]
+    async fn test_spooled_upload_with_blake3_hash() {
+        let content = b"# CLAUDE.md\n\nAlways run the tests.\n".repeat(10);
+        let hash = HashAlgorithm::Blake3.hash(&content);
+
+        let mut spool = UploadSpool::new(content.len() as u64, HashAlgorithm::of(&hash)).unwrap();
+        for chunk in chunks(&content, 64) {
+            spool.write_chunk(&chunk).await.unwrap();
+        }
+        assert_eq!(spool.finish(&hash).await.unwrap(), content);
+
+        // 内容与 BLAKE3 哈希不一致时拒绝
+        let mut spool = UploadSpool::new(content.len() as u64, HashAlgorithm::Blake3).unwrap();
+        for chunk in chunks(&content.to_ascii_uppercase(), 64) {
+            spool.write_chunk(&chunk).await.unwrap();
+        }
+        let err = spool.finish(&hash).await.unwrap_err();
+        assert_eq!(err.code(), Code::DataLoss);
+    }
+
+    #[tokio::test]
+    async fn test_oversize_stream_rejected_at_first_excess_chunk() {
+        let content = vec![b'x'; 300];
+        let mut spool = UploadSpool::new(100, HashAlgorithm::Sha256).unwrap();
+        let chunks = chunks(&content, 64);
+
+        spool.write_chunk(&chunks[0]).await.unwrap();
+        let err = spool.write_chunk(&chunks[1]).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+        assert!(err.to_string().contains("received 128 of 100 bytes"));
+        // 超出部分没有写入
+        assert_eq!(spool.received, 64);
+    }
+
+    #[tokio::test]
+    async fn test_short_stream_rejected() {
+        let content = vec![b'x'; 100];
+        let mut spool = UploadSpool::new(200, HashAlgorithm::Sha256).unwrap();
+        for chunk in chunks(&content, 64) {
+            spool.write_chunk(&chunk).await.unwrap();
+        }
+
+        let hash = StorageService::hash_file(&content);
+        let err = spool.finish(&hash).await.unwrap_err();
+        assert_eq!(err.code(), Code::DataLoss);
+        assert!(err.to_string().contains("received 100 of 200 bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_chunk_rejected() {
+        let content = vec![b'x'; 128];
+        let mut spool = UploadSpool::new(128, HashAlgorithm::Sha256).unwrap();
+        let err = spool
+            .write_chunk(&chunks(&content, 64)[1])
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}